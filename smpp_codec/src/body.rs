@@ -0,0 +1,701 @@
+use std::borrow::Cow;
+
+use crate::command::{
+    TAG_MESSAGE_PAYLOAD, TAG_USER_MESSAGE_REFERENCE, TAG_USSD_SERVICE_OP, USSD_NOTIFY, USSD_PSSR_RESPONSE, USSD_TERMINATE_NOTIFY,
+};
+use crate::error::Result;
+use crate::reader::PduReader;
+
+// A TLV following the mandatory fields
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionalParam {
+    pub tag: u16,
+    pub value: Vec<u8>,
+}
+
+impl OptionalParam {
+    pub fn new(tag: u16, value: impl Into<Vec<u8>>) -> Self {
+        OptionalParam { tag, value: value.into() }
+    }
+
+    pub fn u8(tag: u16, value: u8) -> Self {
+        OptionalParam::new(tag, [value])
+    }
+
+    pub fn u16(tag: u16, value: u16) -> Self {
+        OptionalParam::new(tag, value.to_be_bytes())
+    }
+
+    pub fn as_u8(&self) -> Option<u8> {
+        match self.value[..] {
+            [value] => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_u16(&self) -> Option<u16> {
+        match self.value[..] {
+            [high, low] => Some(u16::from_be_bytes([high, low])),
+            _ => None,
+        }
+    }
+}
+
+fn decode_optional_params(reader: &mut PduReader) -> Result<Vec<OptionalParam>> {
+    let mut params = Vec::new();
+    while !reader.is_empty() {
+        let tag = reader.u16()?;
+        let length = reader.u16()?;
+        let value = reader.bytes(length as usize)?.to_vec();
+        params.push(OptionalParam { tag, value });
+    }
+    Ok(params)
+}
+
+fn encode_optional_params(params: &[OptionalParam], buffer: &mut Vec<u8>) {
+    for param in params {
+        buffer.extend_from_slice(&param.tag.to_be_bytes());
+        buffer.extend_from_slice(&(param.value.len() as u16).to_be_bytes());
+        buffer.extend_from_slice(&param.value);
+    }
+}
+
+fn find(params: &[OptionalParam], tag: u16) -> Option<&OptionalParam> {
+    params.iter().find(|param| param.tag == tag)
+}
+
+fn put_c_str(buffer: &mut Vec<u8>, value: &str) {
+    buffer.extend_from_slice(value.as_bytes());
+    buffer.push(0);
+}
+
+// BIND_RECEIVER, BIND_TRANSMITTER and BIND_TRANSCEIVER share one body
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bind<'a> {
+    pub system_id: Cow<'a, str>,
+    pub password: Cow<'a, str>,
+    pub system_type: Cow<'a, str>,
+    pub interface_version: u8,
+    pub addr_ton: u8,
+    pub addr_npi: u8,
+    pub address_range: Cow<'a, str>,
+    pub optional_params: Vec<OptionalParam>,
+}
+
+impl<'a> Bind<'a> {
+    pub fn decode(body: &'a [u8]) -> Result<Self> {
+        let mut reader = PduReader::new(body);
+        Ok(Bind {
+            system_id: reader.c_str(),
+            password: reader.c_str(),
+            system_type: reader.c_str(),
+            interface_version: reader.u8()?,
+            addr_ton: reader.u8()?,
+            addr_npi: reader.u8()?,
+            address_range: reader.c_str(),
+            optional_params: decode_optional_params(&mut reader)?,
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        put_c_str(&mut body, &self.system_id);
+        put_c_str(&mut body, &self.password);
+        put_c_str(&mut body, &self.system_type);
+        body.push(self.interface_version);
+        body.push(self.addr_ton);
+        body.push(self.addr_npi);
+        put_c_str(&mut body, &self.address_range);
+        encode_optional_params(&self.optional_params, &mut body);
+        body
+    }
+
+    pub fn optional_param(&self, tag: u16) -> Option<&OptionalParam> {
+        find(&self.optional_params, tag)
+    }
+}
+
+// Body of the BIND_*_RESP PDUs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BindResp<'a> {
+    pub system_id: Cow<'a, str>,
+    pub optional_params: Vec<OptionalParam>,
+}
+
+impl<'a> BindResp<'a> {
+    pub fn decode(body: &'a [u8]) -> Result<Self> {
+        let mut reader = PduReader::new(body);
+        Ok(BindResp { system_id: reader.c_str(), optional_params: decode_optional_params(&mut reader)? })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        put_c_str(&mut body, &self.system_id);
+        encode_optional_params(&self.optional_params, &mut body);
+        body
+    }
+}
+
+// OUTBIND body: the SMSC's credentials for an ESME that only accepts inbound connections
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Outbind<'a> {
+    pub system_id: Cow<'a, str>,
+    pub password: Cow<'a, str>,
+}
+
+impl<'a> Outbind<'a> {
+    pub fn decode(body: &'a [u8]) -> Result<Self> {
+        let mut reader = PduReader::new(body);
+        Ok(Outbind { system_id: reader.c_str(), password: reader.c_str() })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        put_c_str(&mut body, &self.system_id);
+        put_c_str(&mut body, &self.password);
+        body
+    }
+}
+
+// QUERY_SM body: the message_id a SUBMIT_SM_RESP returned and the address it was submitted from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QuerySm<'a> {
+    pub message_id: Cow<'a, str>,
+    pub source_addr_ton: u8,
+    pub source_addr_npi: u8,
+    pub source_addr: Cow<'a, str>,
+}
+
+impl<'a> QuerySm<'a> {
+    pub fn decode(body: &'a [u8]) -> Result<Self> {
+        let mut reader = PduReader::new(body);
+        Ok(QuerySm {
+            message_id: reader.c_str(),
+            source_addr_ton: reader.u8()?,
+            source_addr_npi: reader.u8()?,
+            source_addr: reader.c_str(),
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        put_c_str(&mut body, &self.message_id);
+        body.push(self.source_addr_ton);
+        body.push(self.source_addr_npi);
+        put_c_str(&mut body, &self.source_addr);
+        body
+    }
+}
+
+// QUERY_SM_RESP body. final_date is empty until the message reaches a final state.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QuerySmResp<'a> {
+    pub message_id: Cow<'a, str>,
+    pub final_date: Cow<'a, str>,
+    pub message_state: u8,
+    pub error_code: u8,
+}
+
+impl<'a> QuerySmResp<'a> {
+    pub fn decode(body: &'a [u8]) -> Result<Self> {
+        let mut reader = PduReader::new(body);
+        Ok(QuerySmResp {
+            message_id: reader.c_str(),
+            final_date: reader.c_str(),
+            message_state: reader.u8()?,
+            error_code: reader.u8()?,
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        put_c_str(&mut body, &self.message_id);
+        put_c_str(&mut body, &self.final_date);
+        body.push(self.message_state);
+        body.push(self.error_code);
+        body
+    }
+}
+
+// CANCEL_SM body. This simulator cancels by message_id only; the addresses narrow the match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CancelSm<'a> {
+    pub service_type: Cow<'a, str>,
+    pub message_id: Cow<'a, str>,
+    pub source_addr_ton: u8,
+    pub source_addr_npi: u8,
+    pub source_addr: Cow<'a, str>,
+    pub dest_addr_ton: u8,
+    pub dest_addr_npi: u8,
+    pub destination_addr: Cow<'a, str>,
+}
+
+impl<'a> CancelSm<'a> {
+    pub fn decode(body: &'a [u8]) -> Result<Self> {
+        let mut reader = PduReader::new(body);
+        Ok(CancelSm {
+            service_type: reader.c_str(),
+            message_id: reader.c_str(),
+            source_addr_ton: reader.u8()?,
+            source_addr_npi: reader.u8()?,
+            source_addr: reader.c_str(),
+            dest_addr_ton: reader.u8()?,
+            dest_addr_npi: reader.u8()?,
+            destination_addr: reader.c_str(),
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        put_c_str(&mut body, &self.service_type);
+        put_c_str(&mut body, &self.message_id);
+        body.push(self.source_addr_ton);
+        body.push(self.source_addr_npi);
+        put_c_str(&mut body, &self.source_addr);
+        body.push(self.dest_addr_ton);
+        body.push(self.dest_addr_npi);
+        put_c_str(&mut body, &self.destination_addr);
+        body
+    }
+}
+
+// REPLACE_SM body: new text, in the replaced message's data_coding, for a message not yet delivered
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplaceSm<'a> {
+    pub message_id: Cow<'a, str>,
+    pub source_addr_ton: u8,
+    pub source_addr_npi: u8,
+    pub source_addr: Cow<'a, str>,
+    pub schedule_delivery_time: Cow<'a, str>,
+    pub validity_period: Cow<'a, str>,
+    pub registered_delivery: u8,
+    pub sm_default_msg_id: u8,
+    pub short_message: Cow<'a, [u8]>,
+}
+
+impl<'a> ReplaceSm<'a> {
+    pub fn decode(body: &'a [u8]) -> Result<Self> {
+        let mut reader = PduReader::new(body);
+        let message_id = reader.c_str();
+        let source_addr_ton = reader.u8()?;
+        let source_addr_npi = reader.u8()?;
+        let source_addr = reader.c_str();
+        let schedule_delivery_time = reader.c_str();
+        let validity_period = reader.c_str();
+        let registered_delivery = reader.u8()?;
+        let sm_default_msg_id = reader.u8()?;
+        let sm_length = reader.u8()?;
+        let short_message = Cow::Borrowed(reader.bytes(sm_length as usize)?);
+
+        Ok(ReplaceSm {
+            message_id,
+            source_addr_ton,
+            source_addr_npi,
+            source_addr,
+            schedule_delivery_time,
+            validity_period,
+            registered_delivery,
+            sm_default_msg_id,
+            short_message,
+        })
+    }
+
+    // A short_message over 255 octets is cut to fit sm_length
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        put_c_str(&mut body, &self.message_id);
+        body.push(self.source_addr_ton);
+        body.push(self.source_addr_npi);
+        put_c_str(&mut body, &self.source_addr);
+        put_c_str(&mut body, &self.schedule_delivery_time);
+        put_c_str(&mut body, &self.validity_period);
+        body.push(self.registered_delivery);
+        body.push(self.sm_default_msg_id);
+        let short_message = &self.short_message[..self.short_message.len().min(255)];
+        body.push(short_message.len() as u8);
+        body.extend_from_slice(short_message);
+        body
+    }
+}
+
+// SUBMIT_SM body. A short_message over 255 octets is encoded as the message_payload TLV with an
+// empty short_message.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubmitSm<'a> {
+    pub service_type: Cow<'a, str>,
+    pub source_addr_ton: u8,
+    pub source_addr_npi: u8,
+    pub source_addr: Cow<'a, str>,
+    pub dest_addr_ton: u8,
+    pub dest_addr_npi: u8,
+    pub destination_addr: Cow<'a, str>,
+    pub esm_class: u8,
+    pub protocol_id: u8,
+    pub priority_flag: u8,
+    pub schedule_delivery_time: Cow<'a, str>,
+    pub validity_period: Cow<'a, str>,
+    pub registered_delivery: u8,
+    pub replace_if_present_flag: u8,
+    pub data_coding: u8,
+    pub sm_default_msg_id: u8,
+    pub short_message: Cow<'a, [u8]>,
+    pub optional_params: Vec<OptionalParam>,
+}
+
+// DELIVER_SM has the same mandatory fields as SUBMIT_SM
+pub type DeliverSm<'a> = SubmitSm<'a>;
+
+impl<'a> SubmitSm<'a> {
+    pub fn decode(body: &'a [u8]) -> Result<Self> {
+        let mut reader = PduReader::new(body);
+        let service_type = reader.c_str();
+        let source_addr_ton = reader.u8()?;
+        let source_addr_npi = reader.u8()?;
+        let source_addr = reader.c_str();
+        let dest_addr_ton = reader.u8()?;
+        let dest_addr_npi = reader.u8()?;
+        let destination_addr = reader.c_str();
+        let esm_class = reader.u8()?;
+        let protocol_id = reader.u8()?;
+        let priority_flag = reader.u8()?;
+        let schedule_delivery_time = reader.c_str();
+        let validity_period = reader.c_str();
+        let registered_delivery = reader.u8()?;
+        let replace_if_present_flag = reader.u8()?;
+        let data_coding = reader.u8()?;
+        let sm_default_msg_id = reader.u8()?;
+        let sm_length = reader.u8()?;
+        let short_message = Cow::Borrowed(reader.bytes(sm_length as usize)?);
+        let optional_params = decode_optional_params(&mut reader)?;
+
+        Ok(SubmitSm {
+            service_type,
+            source_addr_ton,
+            source_addr_npi,
+            source_addr,
+            dest_addr_ton,
+            dest_addr_npi,
+            destination_addr,
+            esm_class,
+            protocol_id,
+            priority_flag,
+            schedule_delivery_time,
+            validity_period,
+            registered_delivery,
+            replace_if_present_flag,
+            data_coding,
+            sm_default_msg_id,
+            short_message,
+            optional_params,
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        put_c_str(&mut body, &self.service_type);
+        body.push(self.source_addr_ton);
+        body.push(self.source_addr_npi);
+        put_c_str(&mut body, &self.source_addr);
+        body.push(self.dest_addr_ton);
+        body.push(self.dest_addr_npi);
+        put_c_str(&mut body, &self.destination_addr);
+        body.push(self.esm_class);
+        body.push(self.protocol_id);
+        body.push(self.priority_flag);
+        put_c_str(&mut body, &self.schedule_delivery_time);
+        put_c_str(&mut body, &self.validity_period);
+        body.push(self.registered_delivery);
+        body.push(self.replace_if_present_flag);
+        body.push(self.data_coding);
+        body.push(self.sm_default_msg_id);
+        if self.short_message.len() <= 255 {
+            body.push(self.short_message.len() as u8);
+            body.extend_from_slice(&self.short_message);
+        } else {
+            body.push(0);
+            encode_optional_params(&[OptionalParam::new(TAG_MESSAGE_PAYLOAD, self.short_message.as_ref())], &mut body);
+        }
+        encode_optional_params(&self.optional_params, &mut body);
+        body
+    }
+
+    pub fn optional_param(&self, tag: u16) -> Option<&OptionalParam> {
+        find(&self.optional_params, tag)
+    }
+
+    // The message octets; sm_length 0 means they are in the message_payload TLV
+    pub fn message(&self) -> &[u8] {
+        if !self.short_message.is_empty() {
+            return &self.short_message;
+        }
+        self.optional_param(TAG_MESSAGE_PAYLOAD).map_or(&self.short_message, |param| param.value.as_slice())
+    }
+
+    pub fn user_message_reference(&self) -> Option<u16> {
+        self.optional_param(TAG_USER_MESSAGE_REFERENCE).and_then(OptionalParam::as_u16)
+    }
+
+    pub fn ussd_service_op(&self) -> Option<u8> {
+        self.optional_param(TAG_USSD_SERVICE_OP).and_then(OptionalParam::as_u8)
+    }
+
+    // Whether the screen closes the subscriber's USSD session, or None without a ussd_service_op
+    // to tell by. A USSR request awaits a reply; notifications and a PSSR response do not.
+    pub fn ends_ussd_session(&self) -> Option<bool> {
+        self.ussd_service_op().map(|op| matches!(op, USSD_NOTIFY | USSD_TERMINATE_NOTIFY | USSD_PSSR_RESPONSE))
+    }
+}
+
+// Body of SUBMIT_SM_RESP and DELIVER_SM_RESP
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubmitSmResp<'a> {
+    pub message_id: Cow<'a, str>,
+}
+
+impl<'a> SubmitSmResp<'a> {
+    pub fn decode(body: &'a [u8]) -> Result<Self> {
+        Ok(SubmitSmResp { message_id: PduReader::new(body).c_str() })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        put_c_str(&mut body, &self.message_id);
+        body
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{ESM_CLASS_USSD, INTERFACE_VERSION_34, TAG_SCREEN_CHARS, USSD_USSR_REQUEST};
+    use crate::error::Error;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_long_message_goes_in_message_payload() {
+        let text = vec![b'x'; 300];
+        let submit_sm = SubmitSm { esm_class: ESM_CLASS_USSD, short_message: Cow::Borrowed(&text), ..Default::default() };
+        let body = submit_sm.encode();
+        let decoded = SubmitSm::decode(&body).unwrap();
+        assert!(decoded.short_message.is_empty());
+        assert_eq!(decoded.message(), &text[..]);
+    }
+
+    #[test]
+    fn test_ussd_optional_params() {
+        let deliver_sm = DeliverSm {
+            short_message: Cow::Borrowed(b"Goodbye"),
+            optional_params: vec![
+                OptionalParam::u16(TAG_USER_MESSAGE_REFERENCE, 7),
+                OptionalParam::u8(TAG_USSD_SERVICE_OP, USSD_NOTIFY),
+            ],
+            ..Default::default()
+        };
+        let body = deliver_sm.encode();
+        let decoded = DeliverSm::decode(&body).unwrap();
+        assert_eq!(decoded.user_message_reference(), Some(7));
+        assert_eq!(decoded.ussd_service_op(), Some(USSD_NOTIFY));
+        assert_eq!(decoded.ends_ussd_session(), Some(true));
+        let menu = DeliverSm { optional_params: vec![OptionalParam::u8(TAG_USSD_SERVICE_OP, USSD_USSR_REQUEST)], ..Default::default() };
+        assert_eq!(menu.ends_ussd_session(), Some(false));
+        assert_eq!(DeliverSm::default().ends_ussd_session(), None);
+
+        // A TLV cut short by the end of the body
+        assert_eq!(DeliverSm::decode(&body[..body.len() - 1]), Err(Error::Truncated { offset: body.len() - 1 }));
+    }
+
+    fn c_str() -> impl Strategy<Value = Cow<'static, str>> {
+        "[^\\x00]{0,16}".prop_map(Cow::Owned)
+    }
+
+    fn optional_params() -> impl Strategy<Value = Vec<OptionalParam>> {
+        // message_payload is left out: encode decides between it and short_message
+        let tag = any::<u16>().prop_filter("message_payload", |tag| *tag != TAG_MESSAGE_PAYLOAD);
+        proptest::collection::vec(
+            (tag, proptest::collection::vec(any::<u8>(), 0..32)).prop_map(|(tag, value)| OptionalParam { tag, value }),
+            0..4,
+        )
+    }
+
+    prop_compose! {
+        fn bind()(
+            (system_id, password, system_type, address_range) in (c_str(), c_str(), c_str(), c_str()),
+            (interface_version, addr_ton, addr_npi) in any::<(u8, u8, u8)>(),
+            optional_params in optional_params(),
+        ) -> Bind<'static> {
+            Bind { system_id, password, system_type, interface_version, addr_ton, addr_npi, address_range, optional_params }
+        }
+    }
+
+    prop_compose! {
+        fn submit_sm()(
+            (service_type, source_addr, destination_addr) in (c_str(), c_str(), c_str()),
+            (schedule_delivery_time, validity_period) in (c_str(), c_str()),
+            (source_addr_ton, source_addr_npi, dest_addr_ton, dest_addr_npi) in any::<(u8, u8, u8, u8)>(),
+            (esm_class, protocol_id, priority_flag, registered_delivery) in any::<(u8, u8, u8, u8)>(),
+            (replace_if_present_flag, data_coding, sm_default_msg_id) in any::<(u8, u8, u8)>(),
+            short_message in proptest::collection::vec(any::<u8>(), 0..=255),
+            optional_params in optional_params(),
+        ) -> SubmitSm<'static> {
+            SubmitSm {
+                service_type,
+                source_addr_ton,
+                source_addr_npi,
+                source_addr,
+                dest_addr_ton,
+                dest_addr_npi,
+                destination_addr,
+                esm_class,
+                protocol_id,
+                priority_flag,
+                schedule_delivery_time,
+                validity_period,
+                registered_delivery,
+                replace_if_present_flag,
+                data_coding,
+                sm_default_msg_id,
+                short_message: Cow::Owned(short_message),
+                optional_params,
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_bind_round_trips(bind in bind()) {
+            let body = bind.encode();
+            prop_assert_eq!(Bind::decode(&body).unwrap(), bind);
+        }
+
+        #[test]
+        fn prop_bind_resp_round_trips(system_id in c_str(), optional_params in optional_params()) {
+            let bind_resp = BindResp { system_id, optional_params };
+            let body = bind_resp.encode();
+            prop_assert_eq!(BindResp::decode(&body).unwrap(), bind_resp);
+        }
+
+        #[test]
+        fn prop_outbind_round_trips(system_id in c_str(), password in c_str()) {
+            let outbind = Outbind { system_id, password };
+            let body = outbind.encode();
+            prop_assert_eq!(Outbind::decode(&body).unwrap(), outbind);
+        }
+
+        #[test]
+        fn prop_query_sm_round_trips(message_id in c_str(), (source_addr_ton, source_addr_npi) in any::<(u8, u8)>(), source_addr in c_str()) {
+            let query_sm = QuerySm { message_id, source_addr_ton, source_addr_npi, source_addr };
+            let body = query_sm.encode();
+            prop_assert_eq!(QuerySm::decode(&body).unwrap(), query_sm);
+        }
+
+        #[test]
+        fn prop_query_sm_resp_round_trips(message_id in c_str(), final_date in c_str(), (message_state, error_code) in any::<(u8, u8)>()) {
+            let resp = QuerySmResp { message_id, final_date, message_state, error_code };
+            let body = resp.encode();
+            prop_assert_eq!(QuerySmResp::decode(&body).unwrap(), resp);
+        }
+
+        #[test]
+        fn prop_cancel_sm_round_trips(
+            (service_type, message_id, source_addr, destination_addr) in (c_str(), c_str(), c_str(), c_str()),
+            (source_addr_ton, source_addr_npi, dest_addr_ton, dest_addr_npi) in any::<(u8, u8, u8, u8)>(),
+        ) {
+            let cancel_sm = CancelSm {
+                service_type,
+                message_id,
+                source_addr_ton,
+                source_addr_npi,
+                source_addr,
+                dest_addr_ton,
+                dest_addr_npi,
+                destination_addr,
+            };
+            let body = cancel_sm.encode();
+            prop_assert_eq!(CancelSm::decode(&body).unwrap(), cancel_sm);
+        }
+
+        #[test]
+        fn prop_replace_sm_round_trips(
+            (message_id, source_addr, schedule_delivery_time, validity_period) in (c_str(), c_str(), c_str(), c_str()),
+            (source_addr_ton, source_addr_npi, registered_delivery, sm_default_msg_id) in any::<(u8, u8, u8, u8)>(),
+            short_message in proptest::collection::vec(any::<u8>(), 0..=255),
+        ) {
+            let replace_sm = ReplaceSm {
+                message_id,
+                source_addr_ton,
+                source_addr_npi,
+                source_addr,
+                schedule_delivery_time,
+                validity_period,
+                registered_delivery,
+                sm_default_msg_id,
+                short_message: Cow::Owned(short_message),
+            };
+            let body = replace_sm.encode();
+            prop_assert_eq!(ReplaceSm::decode(&body).unwrap(), replace_sm);
+        }
+
+        #[test]
+        fn prop_submit_sm_round_trips(submit_sm in submit_sm()) {
+            let body = submit_sm.encode();
+            prop_assert_eq!(SubmitSm::decode(&body).unwrap(), submit_sm);
+        }
+
+        #[test]
+        fn prop_submit_sm_resp_round_trips(message_id in c_str()) {
+            let resp = SubmitSmResp { message_id };
+            let body = resp.encode();
+            prop_assert_eq!(SubmitSmResp::decode(&body).unwrap(), resp);
+        }
+
+        #[test]
+        fn prop_long_messages_round_trip_through_message_payload(
+            message in proptest::collection::vec(any::<u8>(), 256..1024),
+        ) {
+            let submit_sm = SubmitSm { short_message: Cow::Borrowed(&message), ..Default::default() };
+            let body = submit_sm.encode();
+            let decoded = SubmitSm::decode(&body).unwrap();
+            prop_assert_eq!(decoded.message(), &message[..]);
+        }
+
+        #[test]
+        fn prop_decode_never_panics(body in proptest::collection::vec(any::<u8>(), 0..128)) {
+            let _ = Bind::decode(&body);
+            let _ = BindResp::decode(&body);
+            let _ = Outbind::decode(&body);
+            let _ = QuerySm::decode(&body);
+            let _ = QuerySmResp::decode(&body);
+            let _ = CancelSm::decode(&body);
+            let _ = ReplaceSm::decode(&body);
+            let _ = SubmitSm::decode(&body);
+            let _ = SubmitSmResp::decode(&body);
+        }
+
+        // Every cut through the mandatory fields is an error, never a panic or a partial message
+        #[test]
+        fn prop_truncated_bodies_are_refused(submit_sm in submit_sm(), cut in any::<prop::sample::Index>()) {
+            let body = submit_sm.encode();
+            let mandatory = body.len() - submit_sm.optional_params.iter().map(|param| 4 + param.value.len()).sum::<usize>();
+            let cut = cut.index(mandatory);
+            prop_assert!(SubmitSm::decode(&body[..cut]).is_err());
+
+            let replace_sm = ReplaceSm { short_message: submit_sm.short_message.clone(), ..Default::default() };
+            let body = replace_sm.encode();
+            prop_assert!(ReplaceSm::decode(&body[..cut.min(body.len() - 1)]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_bind_screen_chars() {
+        let bind = Bind {
+            system_id: Cow::Borrowed("USSDMobileUser"),
+            interface_version: INTERFACE_VERSION_34,
+            optional_params: vec![OptionalParam::u16(TAG_SCREEN_CHARS, 40)],
+            ..Default::default()
+        };
+        let body = bind.encode();
+        let decoded = Bind::decode(&body).unwrap();
+        assert_eq!(decoded.system_id, "USSDMobileUser");
+        assert_eq!(decoded.optional_param(TAG_SCREEN_CHARS).and_then(OptionalParam::as_u16), Some(40));
+    }
+}
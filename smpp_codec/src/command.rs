@@ -0,0 +1,86 @@
+// Command IDs
+pub const BIND_RECEIVER: u32 = 0x00000001;
+pub const BIND_RECEIVER_RESP: u32 = 0x80000001;
+pub const BIND_TRANSMITTER: u32 = 0x00000002;
+pub const BIND_TRANSMITTER_RESP: u32 = 0x80000002;
+pub const BIND_TRANSCEIVER: u32 = 0x00000009;
+pub const BIND_TRANSCEIVER_RESP: u32 = 0x80000009;
+pub const OUTBIND: u32 = 0x0000000B; // SMSC to ESME, asking it to bind as a receiver; has no response
+pub const QUERY_SM: u32 = 0x00000003;
+pub const QUERY_SM_RESP: u32 = 0x80000003;
+pub const SUBMIT_SM: u32 = 0x00000004;
+pub const SUBMIT_SM_RESP: u32 = 0x80000004;
+pub const DELIVER_SM: u32 = 0x00000005;
+pub const DELIVER_SM_RESP: u32 = 0x80000005;
+pub const REPLACE_SM: u32 = 0x00000007;
+pub const REPLACE_SM_RESP: u32 = 0x80000007;
+pub const CANCEL_SM: u32 = 0x00000008;
+pub const CANCEL_SM_RESP: u32 = 0x80000008;
+pub const UNBIND: u32 = 0x00000006;
+pub const UNBIND_RESP: u32 = 0x80000006;
+pub const ENQUIRE_LINK: u32 = 0x00000015;
+pub const ENQUIRE_LINK_RESP: u32 = 0x80000015;
+pub const GENERIC_NACK: u32 = 0x80000000;
+
+// A response's command_id is its request's with the top bit set
+pub const RESPONSE_BIT: u32 = 0x80000000;
+
+// Command status codes
+pub const ESME_ROK: u32 = 0x00000000;
+pub const ESME_RINVMSGLEN: u32 = 0x00000001;
+pub const ESME_RINVCMDLEN: u32 = 0x00000002;
+pub const ESME_RINVCMDID: u32 = 0x00000003;
+pub const ESME_RINVBNDSTS: u32 = 0x00000004;
+pub const ESME_RALYBND: u32 = 0x00000005;
+pub const ESME_RINVPRTFLG: u32 = 0x00000006;
+pub const ESME_RINVREGDLVFLG: u32 = 0x00000007;
+pub const ESME_RSYSERR: u32 = 0x00000008;
+pub const ESME_RINVSRCADR: u32 = 0x0000000A;
+pub const ESME_RINVDSTADR: u32 = 0x0000000B;
+pub const ESME_RINVMSGID: u32 = 0x0000000C;
+pub const ESME_RBINDFAIL: u32 = 0x0000000D;
+pub const ESME_RINVPASWD: u32 = 0x0000000E;
+pub const ESME_RINVSYSID: u32 = 0x0000000F;
+pub const ESME_RCANCELFAIL: u32 = 0x00000011;
+pub const ESME_RREPLACEFAIL: u32 = 0x00000013;
+pub const ESME_RMSGQFUL: u32 = 0x00000014;
+pub const ESME_RINVSERTYP: u32 = 0x00000015;
+pub const ESME_RSUBMITFAIL: u32 = 0x00000045;
+pub const ESME_RINVSYSTYP: u32 = 0x00000053;
+pub const ESME_RINVREPFLAG: u32 = 0x00000054;
+pub const ESME_RTHROTTLED: u32 = 0x00000058;
+pub const ESME_RINVSCHED: u32 = 0x00000061;
+pub const ESME_RINVEXPIRY: u32 = 0x00000062;
+pub const ESME_RINVDFTMSGID: u32 = 0x00000063;
+pub const ESME_RX_T_APPN: u32 = 0x00000064;
+pub const ESME_RX_R_APPN: u32 = 0x00000065;
+pub const ESME_RQUERYFAIL: u32 = 0x00000067;
+pub const ESME_RINVOPTPARSTREAM: u32 = 0x000000C0;
+
+// ussd_service_op values (SMPP 3.4 section 5.3.2.44)
+pub const USSD_PSSD_INDICATION: u8 = 0; // Subscriber-initiated PSSD operation
+pub const USSD_PSSR_INDICATION: u8 = 1; // Subscriber-initiated request
+pub const USSD_USSR_REQUEST: u8 = 2; // Network-initiated request awaiting the subscriber's reply
+pub const USSD_NOTIFY: u8 = 3; // USSN request: shown to the subscriber, no reply expected
+// Not a value the spec defines (4-15 are reserved): the simulators use it for a screen that tells
+// the subscriber the network released their session
+pub const USSD_TERMINATE_NOTIFY: u8 = 4;
+pub const USSD_PSSR_RESPONSE: u8 = 17; // The network's final screen for a subscriber-initiated request
+
+// message_state values in QUERY_SM_RESP
+pub const MESSAGE_STATE_ENROUTE: u8 = 1;
+pub const MESSAGE_STATE_DELIVERED: u8 = 2;
+pub const MESSAGE_STATE_EXPIRED: u8 = 3;
+pub const MESSAGE_STATE_DELETED: u8 = 4;
+pub const MESSAGE_STATE_UNDELIVERABLE: u8 = 5;
+
+// Optional parameter tags
+pub const TAG_USER_MESSAGE_REFERENCE: u16 = 0x0204;
+pub const TAG_MESSAGE_PAYLOAD: u16 = 0x0424;
+pub const TAG_USSD_SERVICE_OP: u16 = 0x0501;
+pub const TAG_SCREEN_CHARS: u16 = 0x1401; // Simulator vendor TLV: a bind's screen size in characters
+
+// esm_class marking a USSD message
+pub const ESM_CLASS_USSD: u8 = 0x40;
+
+pub const INTERFACE_VERSION_34: u8 = 0x34;
@@ -0,0 +1,141 @@
+use std::fmt;
+use std::io;
+
+use crate::command::{ESME_RINVCMDLEN, ESME_RSUBMITFAIL, ESME_RSYSERR, ESME_RX_T_APPN};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    // A field ran past the end of the PDU
+    Truncated { offset: usize },
+    // command_length shorter than the header or longer than MAX_PDU_LEN
+    InvalidCommandLength(u32),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Truncated { offset } => write!(f, "PDU truncated at offset {}", offset),
+            Error::InvalidCommandLength(length) => write!(f, "Invalid command_length {}", length),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for io::Error {
+    fn from(error: Error) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
+// Why an SMPP request failed, carrying the command_status the peer is answered with
+#[derive(Debug)]
+pub enum SmppError {
+    // A request the bind state or the command does not allow, or one the peer refused
+    Protocol { status: u32, message: String },
+    // Credentials or bind type rejected
+    Auth { status: u32, system_id: String },
+    // No bound connection to hand the message to
+    Routing(String),
+    // The peer did not answer in time
+    Timeout(String),
+    // A body that could not be decoded
+    Encoding(Error),
+    // The connection itself failed
+    Io(io::Error),
+}
+
+impl SmppError {
+    pub fn protocol(status: u32, message: impl Into<String>) -> Self {
+        SmppError::Protocol { status, message: message.into() }
+    }
+
+    pub fn command_status(&self) -> u32 {
+        match self {
+            SmppError::Protocol { status, .. } | SmppError::Auth { status, .. } => *status,
+            SmppError::Routing(_) => ESME_RSUBMITFAIL,
+            SmppError::Timeout(_) => ESME_RX_T_APPN,
+            SmppError::Encoding(_) => ESME_RINVCMDLEN,
+            SmppError::Io(_) => ESME_RSYSERR,
+        }
+    }
+}
+
+impl fmt::Display for SmppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SmppError::Protocol { status, message } => write!(f, "{} (status 0x{:08X})", message, status),
+            SmppError::Auth { status, system_id } => write!(f, "Bind for {} refused (status 0x{:08X})", system_id, status),
+            SmppError::Routing(message) => write!(f, "No route: {}", message),
+            SmppError::Timeout(message) => write!(f, "Timed out: {}", message),
+            SmppError::Encoding(error) => write!(f, "Undecodable PDU: {}", error),
+            SmppError::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for SmppError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SmppError::Encoding(error) => Some(error),
+            SmppError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<Error> for SmppError {
+    fn from(error: Error) -> Self {
+        SmppError::Encoding(error)
+    }
+}
+
+// Read timeouts surface as Timeout so callers can tell a silent peer from a dead one
+impl From<io::Error> for SmppError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => SmppError::Timeout(error.to_string()),
+            _ => SmppError::Io(error),
+        }
+    }
+}
+
+impl From<SmppError> for io::Error {
+    fn from(error: SmppError) -> Self {
+        match error {
+            SmppError::Io(error) => error,
+            SmppError::Timeout(_) => io::Error::new(io::ErrorKind::TimedOut, error),
+            SmppError::Encoding(_) => io::Error::new(io::ErrorKind::InvalidData, error),
+            SmppError::Auth { .. } => io::Error::new(io::ErrorKind::PermissionDenied, error),
+            _ => io::Error::other(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{ESME_RINVBNDSTS, ESME_RINVPASWD};
+
+    #[test]
+    fn test_command_status_per_kind() {
+        assert_eq!(SmppError::protocol(ESME_RINVBNDSTS, "not bound").command_status(), ESME_RINVBNDSTS);
+        let auth = SmppError::Auth { status: ESME_RINVPASWD, system_id: "ussd_client".to_string() };
+        assert_eq!(auth.command_status(), ESME_RINVPASWD);
+        assert_eq!(SmppError::Routing("*555#".to_string()).command_status(), ESME_RSUBMITFAIL);
+        assert_eq!(SmppError::from(Error::Truncated { offset: 3 }).command_status(), ESME_RINVCMDLEN);
+        assert_eq!(SmppError::from(io::Error::from(io::ErrorKind::BrokenPipe)).command_status(), ESME_RSYSERR);
+    }
+
+    #[test]
+    fn test_io_round_trip_keeps_the_kind() {
+        let timeout = SmppError::from(io::Error::from(io::ErrorKind::WouldBlock));
+        assert!(matches!(timeout, SmppError::Timeout(_)));
+        assert_eq!(io::Error::from(timeout).kind(), io::ErrorKind::TimedOut);
+
+        let reset = io::Error::from(SmppError::from(io::Error::from(io::ErrorKind::ConnectionReset)));
+        assert_eq!(reset.kind(), io::ErrorKind::ConnectionReset);
+    }
+}
@@ -0,0 +1,15 @@
+// SMPP 3.4 PDU encoding and decoding shared by the simulator binaries
+pub mod body;
+pub mod command;
+pub mod error;
+pub mod pdu;
+pub mod reader;
+pub mod strict;
+
+pub use body::{
+    Bind, BindResp, CancelSm, DeliverSm, OptionalParam, Outbind, QuerySm, QuerySmResp, ReplaceSm, SubmitSm, SubmitSmResp,
+};
+pub use command::*;
+pub use error::{Error, Result, SmppError};
+pub use pdu::{message_payload_room, SmppHeader, SmppPdu, HEADER_LEN, MAX_PDU_LEN};
+pub use reader::PduReader;
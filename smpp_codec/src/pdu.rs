@@ -0,0 +1,200 @@
+use std::io::{self, Read};
+
+use bytes::Bytes;
+
+use crate::command::{ESME_ROK, RESPONSE_BIT};
+use crate::error::{Error, Result};
+use crate::reader::PduReader;
+
+pub const HEADER_LEN: usize = 16;
+
+// Anything longer is treated as a framing error rather than allocated
+pub const MAX_PDU_LEN: usize = 64 * 1024;
+
+// Octets a message_payload TLV can carry in a PDU whose body, without that TLV, is `body_len`
+// long: the TLV's length field and MAX_PDU_LEN both bound it
+pub fn message_payload_room(body_len: usize) -> usize {
+    MAX_PDU_LEN.saturating_sub(HEADER_LEN + body_len + 4).min(u16::MAX as usize)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmppHeader {
+    pub command_length: u32,
+    pub command_id: u32,
+    pub command_status: u32,
+    pub sequence_number: u32,
+}
+
+impl SmppHeader {
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = PduReader::new(bytes);
+        Ok(SmppHeader {
+            command_length: reader.u32()?,
+            command_id: reader.u32()?,
+            command_status: reader.u32()?,
+            sequence_number: reader.u32()?,
+        })
+    }
+
+    pub fn encode_into(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.command_length.to_be_bytes());
+        buffer.extend_from_slice(&self.command_id.to_be_bytes());
+        buffer.extend_from_slice(&self.command_status.to_be_bytes());
+        buffer.extend_from_slice(&self.sequence_number.to_be_bytes());
+    }
+
+    // Octets following the header, once command_length is checked against HEADER_LEN..=MAX_PDU_LEN
+    pub fn body_len(&self) -> Result<usize> {
+        self.body_len_up_to(MAX_PDU_LEN)
+    }
+
+    // As `body_len`, with the caller's limit in place of MAX_PDU_LEN
+    pub fn body_len_up_to(&self, max_pdu_len: usize) -> Result<usize> {
+        let command_length = self.command_length as usize;
+        if !(HEADER_LEN..=max_pdu_len).contains(&command_length) {
+            return Err(Error::InvalidCommandLength(self.command_length));
+        }
+        Ok(command_length - HEADER_LEN)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmppPdu {
+    pub header: SmppHeader,
+    pub body: Bytes, // Slice of the connection's read buffer for inbound PDUs
+}
+
+impl SmppPdu {
+    // command_length is worked out from the body
+    pub fn new(command_id: u32, command_status: u32, sequence_number: u32, body: impl Into<Bytes>) -> Self {
+        let body = body.into();
+        SmppPdu {
+            header: SmppHeader {
+                command_length: (HEADER_LEN + body.len()) as u32,
+                command_id,
+                command_status,
+                sequence_number,
+            },
+            body,
+        }
+    }
+
+    // The bodiless response to this PDU: DELIVER_SM_RESP for a DELIVER_SM, UNBIND_RESP for an
+    // UNBIND and so on
+    pub fn response(&self, command_status: u32) -> Self {
+        SmppPdu::new(self.header.command_id | RESPONSE_BIT, command_status, self.header.sequence_number, Bytes::new())
+    }
+
+    pub fn ok_response(&self) -> Self {
+        self.response(ESME_ROK)
+    }
+
+    // One whole frame, header included
+    pub fn decode(frame: &[u8]) -> Result<Self> {
+        let header = SmppHeader::decode(frame)?;
+        let body_len = header.body_len()?;
+        let body = PduReader::new(&frame[HEADER_LEN..]).bytes(body_len).map_err(|_| Error::Truncated { offset: frame.len() })?;
+        Ok(SmppPdu { header, body: Bytes::copy_from_slice(body) })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(HEADER_LEN + self.body.len());
+        self.encode_into(&mut buffer);
+        buffer
+    }
+
+    // Appends the wire form to `buffer`, letting writers reuse one allocation
+    pub fn encode_into(&self, buffer: &mut Vec<u8>) {
+        self.header.encode_into(buffer);
+        buffer.extend_from_slice(&self.body);
+    }
+
+    // Blocking read of one PDU, for clients that read a PDU at a time
+    pub fn read_from(stream: &mut impl Read) -> io::Result<Self> {
+        let mut header = [0u8; HEADER_LEN];
+        stream.read_exact(&mut header)?;
+        let header = SmppHeader::decode(&header)?;
+        let mut body = vec![0u8; header.body_len()?];
+        stream.read_exact(&mut body)?;
+        Ok(SmppPdu { header, body: body.into() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::SubmitSm;
+    use crate::command::{DELIVER_SM, DELIVER_SM_RESP, ESME_RTHROTTLED, SUBMIT_SM};
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_response_echoes_the_sequence_number() {
+        let deliver_sm = SmppPdu::new(DELIVER_SM, ESME_ROK, 42, b"body".to_vec());
+        assert_eq!(deliver_sm.header.command_length, 20);
+
+        let response = deliver_sm.response(ESME_RTHROTTLED);
+        assert_eq!(response.header, SmppHeader {
+            command_length: 16,
+            command_id: DELIVER_SM_RESP,
+            command_status: ESME_RTHROTTLED,
+            sequence_number: 42,
+        });
+        assert!(response.body.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_command_length_is_rejected() {
+        let mut frame = SmppPdu::new(SUBMIT_SM, ESME_ROK, 1, Bytes::new()).to_bytes();
+        frame[..4].copy_from_slice(&8u32.to_be_bytes());
+        assert_eq!(SmppPdu::decode(&frame), Err(Error::InvalidCommandLength(8)));
+        let err = SmppPdu::read_from(&mut frame.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        frame[..4].copy_from_slice(&(MAX_PDU_LEN as u32 + 1).to_be_bytes());
+        assert!(SmppPdu::decode(&frame).is_err());
+
+        let header = SmppHeader::decode(&frame).unwrap();
+        assert_eq!(header.body_len_up_to(MAX_PDU_LEN + 1), Ok(MAX_PDU_LEN + 1 - HEADER_LEN));
+        frame[..4].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(SmppHeader::decode(&frame).unwrap().body_len(), Err(Error::InvalidCommandLength(u32::MAX)));
+
+        // command_length promising more than the frame holds
+        frame[..4].copy_from_slice(&20u32.to_be_bytes());
+        assert_eq!(SmppPdu::decode(&frame), Err(Error::Truncated { offset: 16 }));
+    }
+
+    #[test]
+    fn test_a_full_message_payload_fills_the_pdu_exactly() {
+        let empty = SubmitSm { destination_addr: "94771234567".into(), ..SubmitSm::default() };
+        let room = message_payload_room(empty.encode().len());
+        let full = SubmitSm { short_message: vec![b'x'; room].into(), ..empty };
+        let frame = SmppPdu::new(SUBMIT_SM, ESME_ROK, 1, full.encode()).to_bytes();
+        assert_eq!(frame.len(), MAX_PDU_LEN);
+        assert_eq!(SubmitSm::decode(&SmppPdu::decode(&frame).unwrap().body).unwrap().message().len(), room);
+
+        // A body too large for any payload leaves no room rather than underflowing
+        assert_eq!(message_payload_room(MAX_PDU_LEN), 0);
+        assert_eq!(message_payload_room(0), MAX_PDU_LEN - HEADER_LEN - 4);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_frames_round_trip(
+            command_id in any::<u32>(),
+            command_status in any::<u32>(),
+            sequence_number in any::<u32>(),
+            body in proptest::collection::vec(any::<u8>(), 0..512),
+        ) {
+            let pdu = SmppPdu::new(command_id, command_status, sequence_number, body);
+            let bytes = pdu.to_bytes();
+            prop_assert_eq!(bytes.len(), pdu.header.command_length as usize);
+            prop_assert_eq!(&SmppPdu::decode(&bytes).unwrap(), &pdu);
+            prop_assert_eq!(SmppPdu::read_from(&mut bytes.as_slice()).unwrap(), pdu);
+        }
+
+        #[test]
+        fn prop_decode_never_panics(frame in proptest::collection::vec(any::<u8>(), 0..64)) {
+            let _ = SmppPdu::decode(&frame);
+        }
+    }
+}
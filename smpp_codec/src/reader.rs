@@ -0,0 +1,86 @@
+use std::borrow::Cow;
+
+use crate::error::{Error, Result};
+
+// Bounds-checked cursor over a PDU body that hands out borrowed field slices
+pub struct PduReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PduReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        PduReader { data, pos: 0 }
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn u32(&mut self) -> Result<u32> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len).filter(|end| *end <= self.data.len());
+        let Some(end) = end else {
+            return Err(Error::Truncated { offset: self.pos });
+        };
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    // What the cursor has not read yet
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.data[self.pos.min(self.data.len())..]
+    }
+
+    // NUL-terminated string; a missing terminator takes the rest of the body
+    pub fn c_str(&mut self) -> Cow<'a, str> {
+        let rest = &self.data[self.pos.min(self.data.len())..];
+        let len = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+        let value = &rest[..len];
+        self.pos += (len + 1).min(rest.len());
+        String::from_utf8_lossy(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reader_fields() {
+        let data = [0x01, 0x02, 0x03, 0x00, 0x00, 0x00, 0x04, b'h', b'i', 0, b'n', b'o'];
+        let mut reader = PduReader::new(&data);
+        assert_eq!(reader.u8().unwrap(), 0x01);
+        assert_eq!(reader.u16().unwrap(), 0x0203);
+        assert_eq!(reader.u32().unwrap(), 4);
+        assert_eq!(reader.c_str(), "hi");
+        // No terminator: the rest of the body
+        assert_eq!(reader.c_str(), "no");
+        assert!(reader.is_empty());
+        assert_eq!(reader.c_str(), "");
+    }
+
+    #[test]
+    fn test_reader_truncated() {
+        let mut reader = PduReader::new(&[0x00, 0x01, 0x02]);
+        assert_eq!(reader.u32(), Err(Error::Truncated { offset: 0 }));
+        // A failed read leaves the cursor where it was
+        assert_eq!(reader.u16().unwrap(), 0x0001);
+        assert!(reader.bytes(2).is_err());
+        assert_eq!(reader.bytes(1).unwrap(), &[0x02]);
+    }
+}
@@ -0,0 +1,225 @@
+use crate::command::*;
+use crate::error::SmppError;
+use crate::reader::PduReader;
+
+// SMPP 3.4 field rules for a request body, for servers that refuse malformed PDUs instead of
+// reading what they can. Each failure carries the command_status the spec gives it.
+pub fn validate(command_id: u32, body: &[u8]) -> Result<(), SmppError> {
+    let mut body = StrictReader { reader: PduReader::new(body) };
+    match command_id {
+        BIND_RECEIVER | BIND_TRANSMITTER | BIND_TRANSCEIVER => {
+            body.c_str("system_id", 16, ESME_RINVSYSID)?;
+            body.c_str("password", 9, ESME_RINVPASWD)?;
+            body.c_str("system_type", 13, ESME_RINVSYSTYP)?;
+            body.u8("interface_version")?;
+            body.u8("addr_ton")?;
+            body.u8("addr_npi")?;
+            body.c_str("address_range", 41, ESME_RBINDFAIL)?;
+            body.end()
+        }
+        SUBMIT_SM | DELIVER_SM => {
+            let deliver = command_id == DELIVER_SM;
+            body.c_str("service_type", 6, ESME_RINVSERTYP)?;
+            body.address("source_addr", ESME_RINVSRCADR)?;
+            body.address("destination_addr", ESME_RINVDSTADR)?;
+            body.u8("esm_class")?;
+            body.u8("protocol_id")?;
+            body.at_most("priority_flag", 3, ESME_RINVPRTFLG)?;
+            // DELIVER_SM leaves schedule_delivery_time, replace_if_present_flag and sm_default_msg_id unset
+            let schedule_delivery_time = body.time("schedule_delivery_time", ESME_RINVSCHED)?;
+            if deliver && !schedule_delivery_time.is_empty() {
+                return Err(SmppError::protocol(ESME_RINVSCHED, "schedule_delivery_time must be empty in DELIVER_SM"));
+            }
+            body.time("validity_period", ESME_RINVEXPIRY)?;
+            body.registered_delivery()?;
+            body.at_most("replace_if_present_flag", if deliver { 0 } else { 1 }, ESME_RINVREPFLAG)?;
+            body.u8("data_coding")?;
+            if deliver {
+                body.at_most("sm_default_msg_id", 0, ESME_RINVDFTMSGID)?;
+            } else {
+                body.u8("sm_default_msg_id")?;
+            }
+            body.short_message()?;
+            body.optional_params()
+        }
+        QUERY_SM => {
+            body.c_str("message_id", 65, ESME_RINVMSGID)?;
+            body.address("source_addr", ESME_RINVSRCADR)?;
+            body.end()
+        }
+        CANCEL_SM => {
+            body.c_str("service_type", 6, ESME_RINVSERTYP)?;
+            body.c_str("message_id", 65, ESME_RINVMSGID)?;
+            body.address("source_addr", ESME_RINVSRCADR)?;
+            body.address("destination_addr", ESME_RINVDSTADR)?;
+            body.end()
+        }
+        REPLACE_SM => {
+            body.c_str("message_id", 65, ESME_RINVMSGID)?;
+            body.address("source_addr", ESME_RINVSRCADR)?;
+            body.time("schedule_delivery_time", ESME_RINVSCHED)?;
+            body.time("validity_period", ESME_RINVEXPIRY)?;
+            body.registered_delivery()?;
+            body.u8("sm_default_msg_id")?;
+            body.short_message()?;
+            body.end()
+        }
+        ENQUIRE_LINK | UNBIND => body.end(),
+        _ => Ok(()),
+    }
+}
+
+struct StrictReader<'a> {
+    reader: PduReader<'a>,
+}
+
+impl<'a> StrictReader<'a> {
+    fn u8(&mut self, field: &str) -> Result<u8, SmppError> {
+        self.reader.u8().map_err(|_| SmppError::protocol(ESME_RINVCMDLEN, format!("body ends before {}", field)))
+    }
+
+    fn at_most(&mut self, field: &str, max: u8, status: u32) -> Result<u8, SmppError> {
+        let value = self.u8(field)?;
+        if value > max {
+            return Err(SmppError::protocol(status, format!("{} {} is above {}", field, value, max)));
+        }
+        Ok(value)
+    }
+
+    // `max` counts the terminating NUL, as the spec's field sizes do
+    fn c_str(&mut self, field: &str, max: usize, status: u32) -> Result<&'a [u8], SmppError> {
+        let rest = self.reader.remaining();
+        let Some(len) = rest.iter().position(|&b| b == 0) else {
+            return Err(SmppError::protocol(status, format!("{} is not NUL-terminated", field)));
+        };
+        if len + 1 > max {
+            return Err(SmppError::protocol(status, format!("{} is longer than {} octets", field, max - 1)));
+        }
+        self.reader.bytes(len + 1)?;
+        Ok(&rest[..len])
+    }
+
+    // TON, NPI and a 21-octet address
+    fn address(&mut self, field: &str, status: u32) -> Result<(), SmppError> {
+        self.u8(field)?;
+        self.u8(field)?;
+        self.c_str(field, 21, status)?;
+        Ok(())
+    }
+
+    // Empty or a 16-character absolute or relative time
+    fn time(&mut self, field: &str, status: u32) -> Result<&'a [u8], SmppError> {
+        let value = self.c_str(field, 17, status)?;
+        if !value.is_empty() && value.len() != 16 {
+            return Err(SmppError::protocol(status, format!("{} must be empty or 16 characters", field)));
+        }
+        Ok(value)
+    }
+
+    // Bits 5 to 7 are reserved
+    fn registered_delivery(&mut self) -> Result<(), SmppError> {
+        let value = self.u8("registered_delivery")?;
+        if value & 0xE0 != 0 {
+            return Err(SmppError::protocol(ESME_RINVREGDLVFLG, format!("registered_delivery 0x{:02X} sets reserved bits", value)));
+        }
+        Ok(())
+    }
+
+    fn short_message(&mut self) -> Result<(), SmppError> {
+        let sm_length = self.u8("sm_length")? as usize;
+        if sm_length > 254 {
+            return Err(SmppError::protocol(ESME_RINVMSGLEN, format!("sm_length {} is above 254", sm_length)));
+        }
+        if self.reader.bytes(sm_length).is_err() {
+            return Err(SmppError::protocol(ESME_RINVMSGLEN, "body ends inside short_message"));
+        }
+        Ok(())
+    }
+
+    fn optional_params(&mut self) -> Result<(), SmppError> {
+        while !self.reader.is_empty() {
+            let tag = self.reader.u16();
+            let length = self.reader.u16();
+            let (Ok(_), Ok(length)) = (tag, length) else {
+                return Err(SmppError::protocol(ESME_RINVOPTPARSTREAM, "optional parameter header is cut short"));
+            };
+            if self.reader.bytes(length as usize).is_err() {
+                return Err(SmppError::protocol(ESME_RINVOPTPARSTREAM, "optional parameter value runs past the body"));
+            }
+        }
+        Ok(())
+    }
+
+    fn end(&self) -> Result<(), SmppError> {
+        match self.reader.remaining().len() {
+            0 => Ok(()),
+            extra => Err(SmppError::protocol(ESME_RINVCMDLEN, format!("{} bytes after the last field", extra))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::{Bind, SubmitSm};
+    use proptest::prelude::*;
+
+    fn status(command_id: u32, body: &[u8]) -> u32 {
+        validate(command_id, body).map_or_else(|e| e.command_status(), |()| ESME_ROK)
+    }
+
+    #[test]
+    fn test_bind_fields() {
+        let bind = Bind { system_id: "USSDMobileUser".into(), password: "mobile12".into(), interface_version: 0x34, ..Default::default() };
+        assert_eq!(status(BIND_TRANSCEIVER, &bind.encode()), ESME_ROK);
+        let long_id = Bind { system_id: "ASystemIdOver15Chars".into(), ..bind.clone() };
+        assert_eq!(status(BIND_TRANSCEIVER, &long_id.encode()), ESME_RINVSYSID);
+        let long_password = Bind { password: "password10".into(), ..bind.clone() };
+        assert_eq!(status(BIND_TRANSCEIVER, &long_password.encode()), ESME_RINVPASWD);
+        // The lenient reader would take the rest of the body as system_id
+        assert_eq!(status(BIND_TRANSCEIVER, b"USSD"), ESME_RINVSYSID);
+        let mut trailing = bind.encode();
+        trailing.push(0);
+        assert_eq!(status(BIND_TRANSCEIVER, &trailing), ESME_RINVCMDLEN);
+        assert_eq!(status(ENQUIRE_LINK, b""), ESME_ROK);
+        assert_eq!(status(UNBIND, b"\0"), ESME_RINVCMDLEN);
+    }
+
+    #[test]
+    fn test_submit_sm_fields() {
+        let submit_sm = SubmitSm { source_addr: "1234567890".into(), destination_addr: "*123#".into(), short_message: b"*123#".as_slice().into(), ..Default::default() };
+        assert_eq!(status(SUBMIT_SM, &submit_sm.encode()), ESME_ROK);
+        let cases = [
+            (SubmitSm { priority_flag: 4, ..submit_sm.clone() }, ESME_RINVPRTFLG),
+            (SubmitSm { registered_delivery: 0x21, ..submit_sm.clone() }, ESME_RINVREGDLVFLG),
+            (SubmitSm { replace_if_present_flag: 2, ..submit_sm.clone() }, ESME_RINVREPFLAG),
+            (SubmitSm { validity_period: "000001000000R".into(), ..submit_sm.clone() }, ESME_RINVEXPIRY),
+            (SubmitSm { service_type: "USSDXX".into(), ..submit_sm.clone() }, ESME_RINVSERTYP),
+            (SubmitSm { destination_addr: "123456789012345678901".into(), ..submit_sm.clone() }, ESME_RINVDSTADR),
+        ];
+        for (case, expected) in cases {
+            assert_eq!(status(SUBMIT_SM, &case.encode()), expected, "{:?}", case);
+        }
+        let scheduled = SubmitSm { schedule_delivery_time: "000001000000000R".into(), ..submit_sm.clone() };
+        assert_eq!(status(SUBMIT_SM, &scheduled.encode()), ESME_ROK);
+        assert_eq!(status(DELIVER_SM, &scheduled.encode()), ESME_RINVSCHED);
+
+        let long = SubmitSm { short_message: [b'x'; 255].as_slice().into(), ..submit_sm.clone() };
+        assert_eq!(status(SUBMIT_SM, &long.encode()), ESME_RINVMSGLEN);
+        let body = submit_sm.encode();
+        assert_eq!(status(SUBMIT_SM, &body[..body.len() - 2]), ESME_RINVMSGLEN);
+        let mut tlv = body.clone();
+        tlv.extend_from_slice(&[0x05, 0x01, 0x00, 0x02, 0x00]);
+        assert_eq!(status(SUBMIT_SM, &tlv), ESME_RINVOPTPARSTREAM);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_validate_never_panics(
+            command_id in prop::sample::select(vec![BIND_TRANSCEIVER, SUBMIT_SM, DELIVER_SM, QUERY_SM, CANCEL_SM, REPLACE_SM, UNBIND]),
+            body in proptest::collection::vec(any::<u8>(), 0..128),
+        ) {
+            let _ = validate(command_id, &body);
+        }
+    }
+}
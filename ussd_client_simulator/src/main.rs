@@ -1,80 +1,100 @@
-use std::io::{Read, Write};
+use std::io::{ErrorKind, Read, Write};
 use std::net::{TcpStream, TcpListener};
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::env;
 use std::fs;
 use std::path::Path;
+use log::{error, info, LevelFilter};
 use serde::{Deserialize, Serialize};
 
-// SMPP Command IDs
-const BIND_TRANSCEIVER: u32 = 0x00000009;
-const BIND_TRANSCEIVER_RESP: u32 = 0x80000009;
-const SUBMIT_SM: u32 = 0x00000004;
-const SUBMIT_SM_RESP: u32 = 0x80000004;
-const DELIVER_SM: u32 = 0x00000005;
-const DELIVER_SM_RESP: u32 = 0x80000005;
-const ENQUIRE_LINK: u32 = 0x00000015;
-const ENQUIRE_LINK_RESP: u32 = 0x80000015;
-const UNBIND: u32 = 0x00000006;
-const UNBIND_RESP: u32 = 0x80000006;
+use smpp_codec::{
+    Bind, DeliverSm, SmppError, SmppPdu, SubmitSm, SubmitSmResp, BIND_TRANSCEIVER, BIND_TRANSCEIVER_RESP, DELIVER_SM,
+    ENQUIRE_LINK, ESME_RINVBNDSTS, ESME_RINVCMDID, ESME_ROK, ESM_CLASS_USSD, INTERFACE_VERSION_34, SUBMIT_SM, SUBMIT_SM_RESP,
+    UNBIND, UNBIND_RESP,
+};
+use ussd_common::framing::{write_frame, FrameReader};
+use ussd_common::tls::TlsClientConfig;
+use ussd_common::msisdn::MsisdnGenerator;
+use ussd_common::{encoding, gsm7, logger, run_id};
 
-// SMPP Status Codes
-const ESME_ROK: u32 = 0x00000000;
+// A plain or TLS connection to the server
+trait SmppStream: Read + Write + Send {}
 
-#[derive(Debug, Clone)]
-pub struct SmppHeader {
-    pub command_length: u32,
-    pub command_id: u32,
-    pub command_status: u32,
-    pub sequence_number: u32,
-}
-
-#[derive(Debug, Clone)]
-pub struct SmppPdu {
-    pub header: SmppHeader,
-    pub body: Vec<u8>,
-}
+impl<T: Read + Write + Send> SmppStream for T {}
 
 pub struct UssdSmppClient {
-    stream: TcpStream,
+    stream: Box<dyn SmppStream>,
+    socket: TcpStream, // Under stream, kept for its read timeout
     sequence_counter: u32,
     bound: bool,
+    session_id: Option<String>,
+    session_counter: u32,
+    session_ended: Option<bool>, // From the last screen's ussd_service_op, when it had one
 }
 
+// Screens that look final, for a gateway that sends no ussd_service_op to say so
+const SESSION_END_PHRASES: [&str; 3] = ["Thank you", "Goodbye", "session has ended"];
+
 impl UssdSmppClient {
-    pub fn new(server_addr: &str) -> std::io::Result<Self> {
-        let stream = TcpStream::connect(server_addr)?;
-        println!("Connected to USSD SMPP server at {}", server_addr);
+    pub fn new(server_addr: &str, tls: &TlsClientConfig) -> std::io::Result<Self> {
+        let socket = TcpStream::connect(server_addr)?;
+        let handle = socket.try_clone()?;
+        let stream: Box<dyn SmppStream> = if tls.enabled {
+            let host = server_addr.rsplit_once(':').map_or(server_addr, |(host, _)| host);
+            let stream = tls.connect(socket, host)?;
+            info!("Connected to USSD SMPP server at {} over TLS", server_addr);
+            Box::new(stream)
+        } else {
+            info!("Connected to USSD SMPP server at {}", server_addr);
+            Box::new(socket)
+        };
         
         Ok(UssdSmppClient {
             stream,
+            socket: handle,
             sequence_counter: 1,
             bound: false,
+            session_id: None,
+            session_counter: 0,
+            session_ended: None,
         })
     }
 
-    pub fn bind(&mut self, system_id: &str, password: &str) -> std::io::Result<bool> {
-        let mut body = Vec::new();
-        body.extend_from_slice(system_id.as_bytes());
-        body.push(0); // null terminator
-        body.extend_from_slice(password.as_bytes());
-        body.push(0); // null terminator
-        body.extend_from_slice(b"USSD\0"); // system_type
-        body.push(0x34); // interface_version (3.4)
-        body.push(1); // addr_ton
-        body.push(1); // addr_npi
-        body.extend_from_slice(b"\0"); // address_range
-
-        let bind_pdu = SmppPdu {
-            header: SmppHeader {
-                command_length: 16 + body.len() as u32,
-                command_id: BIND_TRANSCEIVER,
-                command_status: ESME_ROK,
-                sequence_number: self.get_next_sequence(),
-            },
-            body,
+    // How long each read waits for the server; None waits forever
+    pub fn set_response_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.socket.set_read_timeout(timeout)
+    }
+
+    pub fn session_id(&self) -> Option<&str> {
+        self.session_id.as_deref()
+    }
+
+    // Whether `screen`, the last response, closed the session: as its ussd_service_op said, or
+    // guessed from the text when it had none
+    pub fn session_ended(&self, screen: &str) -> bool {
+        self.session_ended.unwrap_or_else(|| SESSION_END_PHRASES.iter().any(|phrase| screen.contains(phrase)))
+    }
+
+    // Names the dialogue that the next requests belong to, stamped with the run id
+    pub fn start_session(&mut self) -> &str {
+        self.session_counter += 1;
+        let started = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        self.session_id.insert(run_id::stamp(format!("CSIM{}{:04}", started, self.session_counter)))
+    }
+
+    pub fn bind(&mut self, system_id: &str, password: &str) -> Result<(), SmppError> {
+        let bind = Bind {
+            system_id: system_id.into(),
+            password: password.into(),
+            system_type: "USSD".into(),
+            interface_version: INTERFACE_VERSION_34,
+            addr_ton: 1,
+            addr_npi: 1,
+            ..Default::default()
         };
+        let bind_pdu = SmppPdu::new(BIND_TRANSCEIVER, ESME_ROK, self.get_next_sequence(), bind.encode());
 
         self.send_pdu(bind_pdu)?;
         
@@ -82,133 +102,92 @@ impl UssdSmppClient {
         let response = self.read_pdu()?;
         if response.header.command_id == BIND_TRANSCEIVER_RESP && response.header.command_status == ESME_ROK {
             self.bound = true;
-            println!("Bind successful for system_id: {}", system_id);
-            Ok(true)
+            info!("Bind successful for system_id: {}", system_id);
+            Ok(())
         } else {
-            println!("Bind failed. Status: 0x{:08x}", response.header.command_status);
-            Ok(false)
+            error!("Bind failed. Status: 0x{:08x}", response.header.command_status);
+            Err(SmppError::Auth { status: response.header.command_status, system_id: system_id.to_string() })
         }
     }
 
-    pub fn send_ussd_request(&mut self, from_msisdn: &str, ussd_code: &str) -> std::io::Result<String> {
+    pub fn send_ussd_request(&mut self, from_msisdn: &str, ussd_code: &str) -> Result<String, SmppError> {
         if !self.bound {
-            return Err(std::io::Error::new(std::io::ErrorKind::NotConnected, "Not bound to server"));
+            return Err(SmppError::protocol(ESME_RINVBNDSTS, "Not bound to server"));
         }
 
-        let mut body = Vec::new();
-        body.extend_from_slice(b"USSD\0"); // service_type
-        body.push(1); // source_addr_ton (International)
-        body.push(1); // source_addr_npi (ISDN)
-        body.extend_from_slice(from_msisdn.as_bytes()); // source_addr
-        body.push(0); // null terminator
-        body.push(0); // dest_addr_ton
-        body.push(0); // dest_addr_npi
-        body.extend_from_slice(b"123\0"); // destination_addr (USSD gateway)
-        body.push(0x40); // esm_class (USSD indication)
-        body.push(0); // protocol_id
-        body.push(0); // priority_flag
-        body.extend_from_slice(b"\0"); // schedule_delivery_time
-        body.extend_from_slice(b"\0"); // validity_period
-        body.push(0); // registered_delivery
-        body.push(0); // replace_if_present_flag
-        body.push(0); // data_coding (GSM 7-bit)
-        body.push(0); // sm_default_msg_id
-        body.push(ussd_code.len() as u8); // sm_length
-        body.extend_from_slice(ussd_code.as_bytes()); // short_message
-
-        let submit_pdu = SmppPdu {
-            header: SmppHeader {
-                command_length: 16 + body.len() as u32,
-                command_id: SUBMIT_SM,
-                command_status: ESME_ROK,
-                sequence_number: self.get_next_sequence(),
-            },
-            body,
+        // Unpacked only: point this simulator at a server with smpp.gsm7_packing off
+        let submit_sm = SubmitSm {
+            service_type: "USSD".into(),
+            source_addr_ton: 1, // International
+            source_addr_npi: 1, // ISDN
+            source_addr: from_msisdn.into(),
+            destination_addr: "123".into(), // USSD gateway
+            esm_class: ESM_CLASS_USSD,
+            data_coding: 0, // GSM 7-bit
+            short_message: gsm7::encode(ussd_code, false).into(),
+            ..Default::default()
         };
+        let submit_pdu = SmppPdu::new(SUBMIT_SM, ESME_ROK, self.get_next_sequence(), submit_sm.encode());
 
         self.send_pdu(submit_pdu)?;
-        println!("Sent USSD request from {}: {}", from_msisdn, ussd_code);
+        info!("Sent USSD request from {}: {} (session {})", from_msisdn, ussd_code, self.session_id().unwrap_or("-"));
 
         // Wait for submit response
         let submit_resp = self.read_pdu()?;
         if submit_resp.header.command_id == SUBMIT_SM_RESP && submit_resp.header.command_status == ESME_ROK {
-            let message_id = String::from_utf8_lossy(&submit_resp.body).trim_end_matches('\0').to_string();
-            println!("SUBMIT_SM_RESP received, message_id: {}", message_id);
+            let message_id = SubmitSmResp::decode(&submit_resp.body).map(|resp| resp.message_id.into_owned()).unwrap_or_default();
+            info!("SUBMIT_SM_RESP received, message_id: {} (session {})", message_id, self.session_id().unwrap_or("-"));
             
             // Wait for DELIVER_SM with USSD response
             let deliver_sm = self.read_pdu()?;
             if deliver_sm.header.command_id == DELIVER_SM {
-                let response_text = self.parse_deliver_sm(&deliver_sm.body);
+                let response_text = self.parse_deliver_sm(&deliver_sm.body)?;
                 
                 // Send DELIVER_SM_RESP
-                let deliver_resp = SmppPdu {
-                    header: SmppHeader {
-                        command_length: 16,
-                        command_id: DELIVER_SM_RESP,
-                        command_status: ESME_ROK,
-                        sequence_number: deliver_sm.header.sequence_number,
-                    },
-                    body: Vec::new(),
-                };
-                self.send_pdu(deliver_resp)?;
+                self.send_pdu(deliver_sm.ok_response())?;
                 
                 Ok(response_text)
             } else {
-                Err(std::io::Error::new(std::io::ErrorKind::Other, "Expected DELIVER_SM"))
+                Err(SmppError::protocol(
+                    ESME_RINVCMDID,
+                    format!("Expected DELIVER_SM, got 0x{:08x}", deliver_sm.header.command_id),
+                ))
             }
         } else {
-            Err(std::io::Error::new(std::io::ErrorKind::Other, "SUBMIT_SM failed"))
+            Err(SmppError::protocol(submit_resp.header.command_status, "SUBMIT_SM failed"))
         }
     }
 
-    pub fn start_message_listener(&mut self) -> std::io::Result<()> {
+    pub fn start_message_listener(&mut self) -> Result<(), SmppError> {
         if !self.bound {
-            return Err(std::io::Error::new(std::io::ErrorKind::NotConnected, "Not bound to server"));
+            return Err(SmppError::protocol(ESME_RINVBNDSTS, "Not bound to server"));
         }
 
-        println!("Starting message listener...");
+        info!("Starting message listener...");
         
         loop {
             match self.read_pdu() {
                 Ok(pdu) => {
                     match pdu.header.command_id {
                         DELIVER_SM => {
-                            let response_text = self.parse_deliver_sm(&pdu.body);
-                            println!("Received USSD response: {}", response_text);
-                            
-                            // Send DELIVER_SM_RESP
-                            let deliver_resp = SmppPdu {
-                                header: SmppHeader {
-                                    command_length: 16,
-                                    command_id: DELIVER_SM_RESP,
-                                    command_status: ESME_ROK,
-                                    sequence_number: pdu.header.sequence_number,
-                                },
-                                body: Vec::new(),
-                            };
-                            self.send_pdu(deliver_resp)?;
-                        }
-                        ENQUIRE_LINK => {
-                            // Respond to enquire_link
-                            let enquire_resp = SmppPdu {
-                                header: SmppHeader {
-                                    command_length: 16,
-                                    command_id: ENQUIRE_LINK_RESP,
-                                    command_status: ESME_ROK,
-                                    sequence_number: pdu.header.sequence_number,
-                                },
-                                body: Vec::new(),
-                            };
-                            self.send_pdu(enquire_resp)?;
-                            println!("Responded to ENQUIRE_LINK");
+                            match self.parse_deliver_sm(&pdu.body) {
+                                Ok(response_text) => {
+                                    info!("Received USSD response: {}", response_text);
+                                    self.send_pdu(pdu.ok_response())?;
+                                }
+                                Err(e) => {
+                                    error!("Undecodable DELIVER_SM: {}", e);
+                                    self.send_pdu(pdu.response(e.command_status()))?;
+                                }
+                            }
                         }
                         _ => {
-                            println!("Received unhandled PDU: 0x{:08x}", pdu.header.command_id);
+                            info!("Received unhandled PDU: 0x{:08x}", pdu.header.command_id);
                         }
                     }
                 }
                 Err(e) => {
-                    println!("Error reading PDU: {}", e);
+                    error!("Error reading PDU: {}", e);
                     break;
                 }
             }
@@ -222,15 +201,7 @@ impl UssdSmppClient {
             return Ok(());
         }
 
-        let unbind_pdu = SmppPdu {
-            header: SmppHeader {
-                command_length: 16,
-                command_id: UNBIND,
-                command_status: ESME_ROK,
-                sequence_number: self.get_next_sequence(),
-            },
-            body: Vec::new(),
-        };
+        let unbind_pdu = SmppPdu::new(UNBIND, ESME_ROK, self.get_next_sequence(), Vec::new());
 
         self.send_pdu(unbind_pdu)?;
         
@@ -238,99 +209,40 @@ impl UssdSmppClient {
         let response = self.read_pdu()?;
         if response.header.command_id == UNBIND_RESP {
             self.bound = false;
-            println!("Unbind successful");
+            info!("Unbind successful");
         }
         
         Ok(())
     }
 
-    fn parse_deliver_sm(&self, body: &[u8]) -> String {
-        let mut pos = 0;
-        
-        // Skip service_type
-        while pos < body.len() && body[pos] != 0 { pos += 1; }
-        pos += 1;
-        
-        // Skip source_addr_ton, source_addr_npi
-        pos += 2;
-        
-        // Skip source_addr
-        while pos < body.len() && body[pos] != 0 { pos += 1; }
-        pos += 1;
-        
-        // Skip dest_addr_ton, dest_addr_npi
-        pos += 2;
-        
-        // Skip destination_addr
-        while pos < body.len() && body[pos] != 0 { pos += 1; }
-        pos += 1;
-        
-        // Skip esm_class, protocol_id, priority_flag
-        pos += 3;
-        
-        // Skip schedule_delivery_time
-        while pos < body.len() && body[pos] != 0 { pos += 1; }
-        pos += 1;
-        
-        // Skip validity_period
-        while pos < body.len() && body[pos] != 0 { pos += 1; }
-        pos += 1;
-        
-        // Skip registered_delivery, replace_if_present_flag, data_coding, sm_default_msg_id
-        pos += 4;
-        
-        // Get sm_length and short_message
-        if pos < body.len() {
-            let sm_length = body[pos] as usize;
-            pos += 1;
-            
-            if pos + sm_length <= body.len() {
-                return String::from_utf8_lossy(&body[pos..pos + sm_length]).to_string();
-            }
-        }
-        
-        String::new()
+    // data_coding 0 is GSM 7-bit and 8 is UCS-2; anything else is shown as UTF-8
+    fn parse_deliver_sm(&mut self, body: &[u8]) -> Result<String, SmppError> {
+        let deliver_sm = DeliverSm::decode(body)?;
+        self.session_ended = deliver_sm.ends_ussd_session();
+        Ok(encoding::decode(deliver_sm.message(), deliver_sm.data_coding, false))
     }
 
     fn send_pdu(&mut self, pdu: SmppPdu) -> std::io::Result<()> {
-        let mut buffer = Vec::new();
-        
-        buffer.extend_from_slice(&pdu.header.command_length.to_be_bytes());
-        buffer.extend_from_slice(&pdu.header.command_id.to_be_bytes());
-        buffer.extend_from_slice(&pdu.header.command_status.to_be_bytes());
-        buffer.extend_from_slice(&pdu.header.sequence_number.to_be_bytes());
-        
-        buffer.extend_from_slice(&pdu.body);
-        
-        self.stream.write_all(&buffer)?;
+        self.stream.write_all(&pdu.to_bytes())?;
         self.stream.flush()?;
         
         Ok(())
     }
 
+    // ENQUIRE_LINKs are answered here, so a keepalive can arrive while any response is awaited
     fn read_pdu(&mut self) -> std::io::Result<SmppPdu> {
-        let mut header_buf = [0u8; 16];
-        self.stream.read_exact(&mut header_buf)?;
-
-        let command_length = u32::from_be_bytes([header_buf[0], header_buf[1], header_buf[2], header_buf[3]]);
-        let command_id = u32::from_be_bytes([header_buf[4], header_buf[5], header_buf[6], header_buf[7]]);
-        let command_status = u32::from_be_bytes([header_buf[8], header_buf[9], header_buf[10], header_buf[11]]);
-        let sequence_number = u32::from_be_bytes([header_buf[12], header_buf[13], header_buf[14], header_buf[15]]);
-
-        let header = SmppHeader {
-            command_length,
-            command_id,
-            command_status,
-            sequence_number,
-        };
-
-        let body_length = command_length.saturating_sub(16) as usize;
-        let mut body = vec![0u8; body_length];
-        if body_length > 0 {
-            self.stream.read_exact(&mut body)?;
+        loop {
+            let pdu = self.read_raw_pdu()?;
+            if pdu.header.command_id != ENQUIRE_LINK {
+                return Ok(pdu);
+            }
+            self.send_pdu(pdu.ok_response())?;
+            info!("Responded to ENQUIRE_LINK");
         }
+    }
 
-        Ok(SmppPdu { header, body })
+    fn read_raw_pdu(&mut self) -> std::io::Result<SmppPdu> {
+        SmppPdu::read_from(&mut self.stream)
     }
 
     fn get_next_sequence(&mut self) -> u32 {
@@ -354,6 +266,8 @@ pub struct ClientConfig {
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+    #[serde(default)]
+    pub tls: TlsClientConfig, // For servers that only accept smpps:// connections
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -369,6 +283,10 @@ pub struct DefaultsConfig {
     pub default_msisdn: String,
     pub initial_ussd_code: String,
     pub request_delay_ms: u64,
+    #[serde(default = "default_response_timeout_ms")]
+    pub response_timeout_ms: u64, // A test step fails when its response takes longer (0 = wait forever)
+    #[serde(default)]
+    pub msisdn_generator: MsisdnGenerator, // Numbers for test cases that give no msisdn
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -376,11 +294,41 @@ pub struct TestCasesConfig {
     pub test_cases: Vec<TestCase>,
 }
 
+// A request, or with `steps` a whole dialogue in one session from the same MSISDN, e.g.
+//   [[test_cases.test_cases]]
+//   msisdn = "1234567890"
+//   ussd_code = "*123#"
+//   description = "Buy a data bundle"
+//   expect = ["Data"]
+//   steps = [
+//       { send = "2", expect = ["Package"] },
+//       { send = "1", expect = ["confirm"] },
+//   ]
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct TestCase {
-    pub msisdn: String,
+    #[serde(default)]
+    pub msisdn: String, // Empty: from defaults.msisdn_generator, or else defaults.default_msisdn
     pub ussd_code: String,
     pub description: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expect: Vec<String>, // Text the response to ussd_code must contain, matching case
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<TestStep>, // Replies sent in order after ussd_code, in the same session
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TestStep {
+    pub send: String,
+    #[serde(default)]
+    pub expect: Vec<String>,
+}
+
+impl TestCase {
+    // The dialled code, then each reply, with what its response must contain
+    fn exchanges(&self) -> impl Iterator<Item = (&str, &[String])> {
+        std::iter::once((self.ussd_code.as_str(), self.expect.as_slice()))
+            .chain(self.steps.iter().map(|step| (step.send.as_str(), step.expect.as_slice())))
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -393,9 +341,19 @@ pub struct LoggingConfig {
 pub struct ForwardingConfig {
     pub listen_port: u16,
     pub enabled: bool,
+    #[serde(default = "default_keepalive_interval")]
+    pub keepalive_interval: u64, // Seconds of silence before a ping; twice that closes the connection (0 = never)
     pub responses: ForwardingResponses,
 }
 
+fn default_keepalive_interval() -> u64 {
+    30
+}
+
+fn default_response_timeout_ms() -> u64 {
+    10000
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ForwardingResponses {
     pub custom_services: Vec<CustomService>,
@@ -425,6 +383,7 @@ impl Default for ClientConfig {
             server: ServerConfig {
                 host: "127.0.0.1".to_string(),
                 port: 9090,
+                tls: TlsClientConfig::default(),
             },
             authentication: AuthConfig {
                 system_id: "USSDClient".to_string(),
@@ -436,6 +395,8 @@ impl Default for ClientConfig {
                 default_msisdn: "1234567890".to_string(),
                 initial_ussd_code: "*123#".to_string(),
                 request_delay_ms: 500,
+                response_timeout_ms: default_response_timeout_ms(),
+                msisdn_generator: MsisdnGenerator::default(),
             },
             test_cases: TestCasesConfig {
                 test_cases: vec![
@@ -443,11 +404,15 @@ impl Default for ClientConfig {
                         msisdn: "1234567890".to_string(),
                         ussd_code: "*123#".to_string(),
                         description: "Test main menu access".to_string(),
+                        expect: vec!["Welcome".to_string()],
+                        steps: Vec::new(),
                     },
                     TestCase {
                         msisdn: "1234567890".to_string(),
-                        ussd_code: "1".to_string(),
+                        ussd_code: "*123#".to_string(),
                         description: "Test balance inquiry".to_string(),
+                        expect: Vec::new(),
+                        steps: vec![TestStep { send: "1".to_string(), expect: vec!["balance".to_string()] }],
                     },
                 ],
             },
@@ -458,6 +423,7 @@ impl Default for ClientConfig {
             forwarding: Some(ForwardingConfig {
                 enabled: true,
                 listen_port: 9091,
+                keepalive_interval: default_keepalive_interval(),
                 responses: ForwardingResponses {
                     custom_services: vec![
                         CustomService {
@@ -523,6 +489,7 @@ impl Default for ForwardingConfig {
         ForwardingConfig {
             enabled: true,
             listen_port: 9091,
+            keepalive_interval: default_keepalive_interval(),
             responses: ForwardingResponses {
                 custom_services: vec![
                     CustomService {
@@ -560,18 +527,36 @@ impl Default for ForwardingConfig {
     }
 }
 
-// Simple protocol for forwarding USSD requests
+// Messages on a forwarding service connection, each a length-prefixed JSON frame (see
+// ussd_common::framing). A connection carries any number of requests; each response echoes the
+// request's id. Either side may ping, and the other answers with a pong of the same id.
 #[derive(Debug, Deserialize, Serialize)]
-pub struct ForwardingRequest {
-    pub msisdn: String,
-    pub ussd_code: String,
-    pub session_id: Option<String>,
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ForwardingMessage {
+    Request {
+        id: u64,
+        msisdn: String,
+        ussd_code: String,
+        #[serde(default)]
+        session_id: Option<String>,
+    },
+    Response {
+        id: u64,
+        session_id: String,
+        response_text: String,
+        continue_session: bool,
+    },
+    Ping { id: u64 },
+    Pong { id: u64 },
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct ForwardingResponse {
-    pub response_text: String,
-    pub continue_session: bool,
+// Session ids for forwarded requests that arrive without one
+static FORWARDING_SESSIONS: AtomicU32 = AtomicU32::new(0);
+
+fn next_forwarding_session_id() -> String {
+    let started = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let counter = FORWARDING_SESSIONS.fetch_add(1, Ordering::Relaxed) + 1;
+    run_id::stamp(format!("FWD{}{:04}", started, counter))
 }
 
 // USSD Forwarding Service
@@ -596,84 +581,95 @@ impl UssdForwardingService {
     pub fn start(&self) -> std::io::Result<()> {
         for stream in self.listener.incoming() {
             match stream {
-                Ok(mut stream) => {
+                Ok(stream) => {
                     let config = self.config.clone();
                     thread::spawn(move || {
-                        if let Err(e) = Self::handle_client(&mut stream, &config) {
-                            eprintln!("Error handling client: {}", e);
+                        if let Err(e) = Self::handle_client(stream, &config) {
+                            error!("Error handling client: {}", e);
                         }
                     });
                 }
                 Err(e) => {
-                    eprintln!("Error accepting connection: {}", e);
+                    error!("Error accepting connection: {}", e);
                 }
             }
         }
         Ok(())
     }
 
-    fn handle_client(stream: &mut TcpStream, config: &ClientConfig) -> std::io::Result<()> {
-        let mut buffer = [0; 1024];
-        let bytes_read = stream.read(&mut buffer)?;
-        
-        if bytes_read == 0 {
-            return Ok(());
+    // Serves frames until the peer closes the connection or stays silent through a ping
+    fn handle_client(stream: TcpStream, config: &ClientConfig) -> std::io::Result<()> {
+        let peer = stream.peer_addr()?;
+        let keepalive = config.forwarding.as_ref().map_or(0, |forwarding| forwarding.keepalive_interval);
+        if keepalive > 0 {
+            stream.set_read_timeout(Some(Duration::from_secs(keepalive)))?;
         }
+        let mut writer = stream.try_clone()?;
+        let mut reader = FrameReader::new(stream);
+        let mut next_ping = 0;
+        let mut awaiting_pong = false;
+        info!("Forwarding service connection from {}", peer);
 
-        let request_data = &buffer[..bytes_read];
-        let request: ForwardingRequest = serde_json::from_slice(request_data)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-
-        println!("Forwarding service received request: {:?}", request);
-
-        // Process the USSD request
-        let response = Self::process_ussd_request(&request, config);
-        
-        // Send response back
-        let response_json = serde_json::to_string(&response)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-        
-        stream.write_all(response_json.as_bytes())?;
-        stream.flush()?;
-
-        println!("Forwarding service sent response: {:?}", response);
+        loop {
+            let message = match reader.read::<ForwardingMessage>() {
+                Ok(Some(message)) => message,
+                Ok(None) => break,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    if awaiting_pong {
+                        info!("Forwarding service peer {} missed a keepalive, closing", peer);
+                        break;
+                    }
+                    next_ping += 1;
+                    awaiting_pong = true;
+                    write_frame(&mut writer, &ForwardingMessage::Ping { id: next_ping })?;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            // Any frame shows the peer is alive
+            awaiting_pong = false;
+            match message {
+                ForwardingMessage::Request { id, msisdn, ussd_code, session_id } => {
+                    let session_id = session_id.unwrap_or_else(next_forwarding_session_id);
+                    info!("Forwarding service received request {} from {} in {}: {}", id, msisdn, session_id, ussd_code);
+                    let (response_text, continue_session) = Self::process_ussd_request(&ussd_code, config);
+                    write_frame(&mut writer, &ForwardingMessage::Response { id, session_id, response_text, continue_session })?;
+                }
+                ForwardingMessage::Ping { id } => write_frame(&mut writer, &ForwardingMessage::Pong { id })?,
+                ForwardingMessage::Pong { .. } => {}
+                ForwardingMessage::Response { id, .. } => info!("Forwarding service ignored unexpected response {} from {}", id, peer),
+            }
+        }
+        info!("Forwarding service connection from {} closed", peer);
         Ok(())
     }
 
-    fn process_ussd_request(request: &ForwardingRequest, config: &ClientConfig) -> ForwardingResponse {
+    // The reply text and whether the session continues
+    fn process_ussd_request(ussd_code: &str, config: &ClientConfig) -> (String, bool) {
         let forwarding_config = config.forwarding.as_ref().unwrap();
         
         // Check if it's a custom service USSD code
         for service in &forwarding_config.responses.custom_services {
-            if service.ussd_code == request.ussd_code {
+            if service.ussd_code == ussd_code {
                 let mut response_text = service.welcome_message.clone();
                 if !service.menu_items.is_empty() {
                     response_text.push('\n');
                     response_text.push_str(&service.menu_items.join("\n"));
                 }
                 
-                return ForwardingResponse {
-                    response_text,
-                    continue_session: service.continue_session,
-                };
+                return (response_text, service.continue_session);
             }
         }
         
         // Check if it's a menu option
         for option in &forwarding_config.responses.menu_options {
-            if option.option == request.ussd_code {
-                return ForwardingResponse {
-                    response_text: option.response_text.clone(),
-                    continue_session: option.continue_session,
-                };
+            if option.option == ussd_code {
+                return (option.response_text.clone(), option.continue_session);
             }
         }
         
         // Default response for unknown commands
-        ForwardingResponse {
-            response_text: forwarding_config.responses.default_response.replace("{}", &request.ussd_code),
-            continue_session: true,
-        }
+        (forwarding_config.responses.default_response.replace("{}", ussd_code), true)
     }
 }
 
@@ -686,7 +682,7 @@ pub struct UssdUserSimulator {
 
 impl UssdUserSimulator {
     pub fn new(server_addr: &str, msisdn: &str, config: ClientConfig) -> std::io::Result<Self> {
-        let client = UssdSmppClient::new(server_addr)?;
+        let client = UssdSmppClient::new(server_addr, &config.server.tls)?;
         Ok(UssdUserSimulator {
             client,
             msisdn: msisdn.to_string(),
@@ -696,13 +692,11 @@ impl UssdUserSimulator {
 
     pub fn start_session(&mut self) -> std::io::Result<()> {
         // Bind to server
-        if !self.client.bind(&self.config.authentication.system_id, &self.config.authentication.password)? {
-            return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, "Failed to bind"));
-        }
+        self.client.bind(&self.config.authentication.system_id, &self.config.authentication.password)?;
 
         println!("=== USSD User Simulator ===");
         println!("MSISDN: {}", self.msisdn);
-        println!("Starting USSD session...");
+        println!("Starting USSD session {}...", self.client.start_session());
 
         // Start with initial USSD code
         let mut current_input = self.config.defaults.initial_ussd_code.clone();
@@ -716,7 +710,7 @@ impl UssdUserSimulator {
                     println!("\n--- USSD Response ---");
                     println!("{}", response);
                     
-                    if response.contains("Thank you") || response.contains("Goodbye") || response.contains("session has ended") {
+                    if self.client.session_ended(&response) {
                         println!("\nUSSD session terminated.");
                         break;
                     }
@@ -760,7 +754,9 @@ pub struct UssdTestSuite {
 
 impl UssdTestSuite {
     pub fn new(server_addr: &str, config: ClientConfig) -> std::io::Result<Self> {
-        let client = UssdSmppClient::new(server_addr)?;
+        let client = UssdSmppClient::new(server_addr, &config.server.tls)?;
+        let timeout = config.defaults.response_timeout_ms;
+        client.set_response_timeout((timeout > 0).then(|| Duration::from_millis(timeout)))?;
         Ok(UssdTestSuite { client, config })
     }
 
@@ -768,17 +764,26 @@ impl UssdTestSuite {
         println!("=== USSD Test Suite ===");
         
         // Bind to server
-        if !self.client.bind(&self.config.authentication.test_system_id, &self.config.authentication.test_password)? {
-            return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, "Failed to bind"));
-        }
+        self.client.bind(&self.config.authentication.test_system_id, &self.config.authentication.test_password)?;
 
-        for test_case in &self.config.test_cases.test_cases {
+        let mut test_cases = self.config.test_cases.test_cases.clone();
+        let defaults = &self.config.defaults;
+        for (index, test_case) in test_cases.iter_mut().filter(|test_case| test_case.msisdn.is_empty()).enumerate() {
+            test_case.msisdn = defaults.msisdn_generator.nth(index as u64).unwrap_or_else(|| defaults.default_msisdn.clone());
+        }
+        let mut passed = 0;
+        for test_case in &test_cases {
             println!("\n--- Test Case: {} ---", test_case.description);
             println!("MSISDN: {}, USSD Code: {}", test_case.msisdn, test_case.ussd_code);
+            if test_case.ussd_code.starts_with('*') && test_case.ussd_code.ends_with('#') {
+                println!("Session: {}", self.client.start_session());
+            }
             
-            match self.client.send_ussd_request(&test_case.msisdn, &test_case.ussd_code) {
-                Ok(response) => {
-                    println!("Response: {}", response);
+            let delay = Duration::from_millis(self.config.defaults.request_delay_ms);
+            let client = &mut self.client;
+            match run_dialogue(test_case, delay, |send| client.send_ussd_request(&test_case.msisdn, send)) {
+                Ok(()) => {
+                    passed += 1;
                     println!("✓ Test passed");
                 }
                 Err(e) => {
@@ -791,16 +796,50 @@ impl UssdTestSuite {
 
         // Unbind from server
         self.client.unbind()?;
-        println!("\n=== All tests completed ===");
+        println!("\n=== All tests completed: {} of {} passed ===", passed, test_cases.len());
         
         Ok(())
     }
 }
 
+// Sends the code and every step in order through `request`, `delay` apart, stopping at the first
+// response that misses an expectation, since later steps would then answer the wrong menu
+fn run_dialogue(
+    test_case: &TestCase,
+    delay: Duration,
+    mut request: impl FnMut(&str) -> Result<String, SmppError>,
+) -> Result<(), String> {
+    let total = test_case.steps.len() + 1;
+    for (index, (send, expect)) in test_case.exchanges().enumerate() {
+        if index > 0 {
+            thread::sleep(delay);
+        }
+        let step = format!("step {}/{} ({:?})", index + 1, total, send);
+        let response = request(send).map_err(|e| match e {
+            SmppError::Timeout(_) => format!("{}: no response in time", step),
+            e => format!("{}: {}", step, e),
+        })?;
+        if total > 1 {
+            println!("Step {}/{} sent {:?}", index + 1, total, send);
+        }
+        println!("Response: {}", response);
+        if let Some(missing) = missing_expectation(&response, expect) {
+            return Err(format!("{}: response does not contain {:?}", step, missing));
+        }
+    }
+    Ok(())
+}
+
+// The first text in `expect` the response lacks, matching case
+fn missing_expectation<'a>(response: &str, expect: &'a [String]) -> Option<&'a str> {
+    expect.iter().map(String::as_str).find(|text| !response.contains(text))
+}
+
 fn load_config(config_path: &str) -> Result<ClientConfig, Box<dyn std::error::Error>> {
     if Path::new(config_path).exists() {
         let config_content = fs::read_to_string(config_path)?;
         let config: ClientConfig = toml::from_str(&config_content)?;
+        config.defaults.msisdn_generator.validate().map_err(|e| format!("defaults.msisdn_generator: {}", e))?;
         Ok(config)
     } else {
         println!("Config file not found at '{}', creating default config...", config_path);
@@ -814,7 +853,10 @@ fn load_config(config_path: &str) -> Result<ClientConfig, Box<dyn std::error::Er
 
 // Function removed - usage is now printed inline
 
-fn parse_args() -> Result<(ClientConfig, Option<String>, Option<u16>, Vec<String>), Box<dyn std::error::Error>> {
+// Loaded config plus the --host and --port overrides and the mode with its arguments
+type CliArgs = (ClientConfig, Option<String>, Option<u16>, Vec<String>);
+
+fn parse_args() -> Result<CliArgs, Box<dyn std::error::Error>> {
     let args: Vec<String> = env::args().collect();
     let mut config_path = "client_config.toml".to_string();
     let mut host_override: Option<String> = None;
@@ -848,6 +890,14 @@ fn parse_args() -> Result<(ClientConfig, Option<String>, Option<u16>, Vec<String
                     return Err("--port requires a value".into());
                 }
             }
+            "--run-id" => {
+                if i + 1 < args.len() {
+                    run_id::init(Some(args[i + 1].clone()))?;
+                    i += 2;
+                } else {
+                    return Err("--run-id requires a value".into());
+                }
+            }
             "--create-config" => {
                 let default_config = ClientConfig::default();
                 let config_content = toml::to_string_pretty(&default_config)?;
@@ -875,6 +925,8 @@ fn parse_args() -> Result<(ClientConfig, Option<String>, Option<u16>, Vec<String
 }
 
 fn main() -> std::io::Result<()> {
+    logger::init(LevelFilter::Info);
+
     let (mut config, host_override, port_override, remaining_args) = match parse_args() {
         Ok((config, host, port, args)) => (config, host, port, args),
         Err(e) => {
@@ -907,6 +959,14 @@ fn main() -> std::io::Result<()> {
 
     let mode = &remaining_args[0];
     let server_addr = format!("{}:{}", config.server.host, config.server.port);
+    let run_id = match run_id::init(None) {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("Error: {} (from ${})", e, run_id::RUN_ID_ENV);
+            std::process::exit(1);
+        }
+    };
+    println!("Run ID: {}", run_id);
 
     if config.logging.debug {
         println!("Debug mode enabled");
@@ -930,19 +990,18 @@ fn main() -> std::io::Result<()> {
             let msisdn = remaining_args.get(1)
                 .cloned()
                 .unwrap_or_else(|| config.defaults.default_msisdn.clone());
-            let mut client = UssdSmppClient::new(&server_addr)?;
+            let mut client = UssdSmppClient::new(&server_addr, &config.server.tls)?;
             
-            if client.bind(&config.authentication.system_id, &config.authentication.password)? {
-                println!("Testing basic USSD flow...");
-                
-                let response = client.send_ussd_request(&msisdn, &config.defaults.initial_ussd_code)?;
-                println!("Response: {}", response);
-                
-                let response = client.send_ussd_request(&msisdn, "1")?;
-                println!("Response: {}", response);
-                
-                client.unbind()?;
-            }
+            client.bind(&config.authentication.system_id, &config.authentication.password)?;
+            println!("Testing basic USSD flow...");
+            
+            let response = client.send_ussd_request(&msisdn, &config.defaults.initial_ussd_code)?;
+            println!("Response: {}", response);
+            
+            let response = client.send_ussd_request(&msisdn, "1")?;
+            println!("Response: {}", response);
+            
+            client.unbind()?;
         }
         "forwarding" => {
             if let Some(forwarding_config) = &config.forwarding {
@@ -968,4 +1027,78 @@ fn main() -> std::io::Result<()> {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dialogue() -> TestCase {
+        TestCase {
+            msisdn: "1234567890".to_string(),
+            ussd_code: "*123#".to_string(),
+            description: "Buy a data bundle".to_string(),
+            expect: vec!["Welcome".to_string()],
+            steps: vec![
+                TestStep { send: "2".to_string(), expect: vec!["Package".to_string()] },
+                TestStep { send: "1".to_string(), expect: vec!["confirm".to_string()] },
+            ],
+        }
+    }
+
+    // Answers each request with the next of `screens`, noting what was sent
+    fn scripted<'a>(screens: &'a [&str], sent: &'a mut Vec<String>) -> impl FnMut(&str) -> Result<String, SmppError> + 'a {
+        move |send| {
+            sent.push(send.to_string());
+            Ok(screens[sent.len() - 1].to_string())
+        }
+    }
+
+    #[test]
+    fn test_dialogue_passes_when_every_step_matches() {
+        let screens = ["Welcome\n1. Balance\n2. Data", "Package A\nPackage B", "Please confirm"];
+        let mut sent = Vec::new();
+        assert_eq!(run_dialogue(&dialogue(), Duration::ZERO, scripted(&screens, &mut sent)), Ok(()));
+        assert_eq!(sent, ["*123#", "2", "1"]);
+    }
+
+    #[test]
+    fn test_dialogue_stops_at_the_first_failing_step() {
+        let screens = ["Welcome\n1. Balance\n2. Data", "Your balance is 10.00", "Please confirm"];
+        let mut sent = Vec::new();
+        let error = run_dialogue(&dialogue(), Duration::ZERO, scripted(&screens, &mut sent)).unwrap_err();
+        assert_eq!(error, "step 2/3 (\"2\"): response does not contain \"Package\"");
+        assert_eq!(sent, ["*123#", "2"]);
+    }
+
+    #[test]
+    fn test_expectations_match_case() {
+        let expect = vec!["Welcome".to_string(), "Data".to_string()];
+        assert_eq!(missing_expectation("Welcome\n2. Data", &expect), None);
+        assert_eq!(missing_expectation("welcome\n2. Data", &expect), Some("Welcome"));
+        assert_eq!(missing_expectation("anything", &[]), None);
+    }
+
+    #[test]
+    fn test_step_without_a_response_times_out() {
+        // A server that accepts the bind and then never answers
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let bind = SmppPdu::read_from(&mut socket).unwrap();
+            socket.write_all(&SmppPdu::new(BIND_TRANSCEIVER_RESP, ESME_ROK, bind.header.sequence_number, Vec::new()).to_bytes()).unwrap();
+            let submit_sm = SmppPdu::read_from(&mut socket).unwrap();
+            // Held open until the client gives up and closes its end
+            let _ = SmppPdu::read_from(&mut socket);
+            submit_sm.header.command_id
+        });
+
+        let mut client = UssdSmppClient::new(&addr, &TlsClientConfig::default()).unwrap();
+        client.set_response_timeout(Some(Duration::from_millis(200))).unwrap();
+        client.bind("USSDTestClient", "testpass123").unwrap();
+        let error = run_dialogue(&dialogue(), Duration::ZERO, |send| client.send_ussd_request("1234567890", send)).unwrap_err();
+        assert_eq!(error, "step 1/3 (\"*123#\"): no response in time");
+        drop(client);
+        assert_eq!(server.join().unwrap(), SUBMIT_SM);
+    }
+}
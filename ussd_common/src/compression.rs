@@ -0,0 +1,197 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+// Post-processing applied to every outbound USSD screen, so one menu config can serve both
+// smartphone-friendly and strict legacy handsets
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CompressionConfig {
+    pub enabled: bool,
+    pub strip_emoji: bool,
+    pub collapse_whitespace: bool, // Single spaces, trimmed lines, no blank lines
+    pub abbreviations: BTreeMap<String, String>, // Whole-word, case-sensitive replacements
+    pub max_line_chars: usize, // Longer lines are wrapped at word boundaries (0 = unlimited)
+    pub max_chars: usize, // Whole screen is cut to this many characters (0 = unlimited)
+}
+
+impl CompressionConfig {
+    pub fn apply(&self, text: &str) -> String {
+        if !self.enabled {
+            return text.to_string();
+        }
+
+        let mut text = text.to_string();
+        if self.strip_emoji {
+            text = strip_emoji(&text);
+        }
+        if !self.abbreviations.is_empty() {
+            text = abbreviate(&text, &self.abbreviations);
+        }
+        if self.collapse_whitespace {
+            text = collapse_whitespace(&text);
+        }
+        if self.max_line_chars > 0 {
+            text = wrap_lines(&text, self.max_line_chars);
+        }
+        if self.max_chars > 0 && text.chars().count() > self.max_chars {
+            text = text.chars().take(self.max_chars).collect::<String>().trim_end().to_string();
+        }
+        text
+    }
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32,
+        0x1F000..=0x1FAFF   // Pictographs, emoticons, transport, flags
+        | 0x2300..=0x23FF   // Misc technical (⌛, ⏰)
+        | 0x2600..=0x27BF   // Misc symbols and dingbats
+        | 0x2B00..=0x2BFF   // Arrows and stars
+        | 0xFE0F            // Emoji presentation selector
+        | 0x200D            // Zero width joiner
+        | 0x20E3)           // Combining keycap
+}
+
+// Drops emoji along with the space that separated them from the text
+fn strip_emoji(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if !is_emoji(c) {
+            result.push(c);
+            continue;
+        }
+        let at_word_start = result.is_empty() || result.ends_with(char::is_whitespace);
+        while chars.peek().is_some_and(|next| is_emoji(*next)) {
+            chars.next();
+        }
+        if at_word_start && chars.peek() == Some(&' ') {
+            chars.next();
+        }
+    }
+    result
+}
+
+fn abbreviate(text: &str, abbreviations: &BTreeMap<String, String>) -> String {
+    // Longest words first so "Data Packages" wins over "Data"
+    let mut rules: Vec<(&String, &String)> = abbreviations.iter().filter(|(word, _)| !word.is_empty()).collect();
+    rules.sort_by_key(|(word, _)| std::cmp::Reverse(word.len()));
+
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    'scan: while let Some(c) = rest.chars().next() {
+        let at_boundary = !result.chars().next_back().is_some_and(char::is_alphanumeric);
+        if at_boundary {
+            for (word, replacement) in &rules {
+                if let Some(after) = rest.strip_prefix(word.as_str()) {
+                    if !after.chars().next().is_some_and(char::is_alphanumeric) {
+                        result.push_str(replacement);
+                        rest = after;
+                        continue 'scan;
+                    }
+                }
+            }
+        }
+        result.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    result
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn wrap_lines(text: &str, max_line_chars: usize) -> String {
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let mut current = String::new();
+        for word in line.split(' ') {
+            let mut word = word.to_string();
+            // Words longer than a line are hard-broken
+            while word.chars().count() > max_line_chars {
+                if !current.is_empty() {
+                    lines.push(std::mem::take(&mut current));
+                }
+                let head: String = word.chars().take(max_line_chars).collect();
+                word = word.chars().skip(max_line_chars).collect();
+                lines.push(head);
+            }
+            let needed = current.chars().count() + usize::from(!current.is_empty()) + word.chars().count();
+            if needed > max_line_chars && !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(&word);
+        }
+        lines.push(current);
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> CompressionConfig {
+        CompressionConfig {
+            enabled: true,
+            ..CompressionConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_disabled_leaves_text_alone() {
+        let config = CompressionConfig {
+            strip_emoji: true,
+            ..CompressionConfig::default()
+        };
+        assert_eq!(config.apply("🏠 Main Menu"), "🏠 Main Menu");
+    }
+
+    #[test]
+    fn test_strip_emoji_and_collapse_whitespace() {
+        let config = CompressionConfig {
+            strip_emoji: true,
+            collapse_whitespace: true,
+            ..rules()
+        };
+        assert_eq!(config.apply("🏠 Main Menu\n\n1. 💰  Balance ✅\n"), "Main Menu\n1. Balance");
+    }
+
+    #[test]
+    fn test_abbreviations_match_whole_words() {
+        let mut config = rules();
+        config.abbreviations.insert("Balance".to_string(), "Bal".to_string());
+        config.abbreviations.insert("Data Packages".to_string(), "Data".to_string());
+        assert_eq!(
+            config.apply("1. Balance\n2. Data Packages\nBalances"),
+            "1. Bal\n2. Data\nBalances"
+        );
+    }
+
+    #[test]
+    fn test_line_wrap_and_max_chars() {
+        let config = CompressionConfig {
+            max_line_chars: 10,
+            max_chars: 18,
+            ..rules()
+        };
+        assert_eq!(config.apply("Check your balance now"), "Check your\nbalance");
+    }
+
+    #[test]
+    fn test_words_longer_than_a_line_are_hard_broken() {
+        let config = CompressionConfig {
+            max_line_chars: 4,
+            ..rules()
+        };
+        assert_eq!(config.apply("Go *123456#"), "Go\n*123\n456#");
+    }
+}
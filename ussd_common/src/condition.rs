@@ -0,0 +1,169 @@
+// Menu option conditions such as `data.pin_ok == 'true' && balance >= 10`.
+//
+// An operand is a quoted string, a number or a name looked up by the caller (`data.<name>` for
+// session data, anything else as a placeholder would be). `&&` binds tighter than `||`. A bare
+// operand holds when it is set and not "", "0" or "false"; `!` negates one.
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Literal(String),
+    Name(String),
+    Op(&'static str),
+}
+
+const OPERATORS: [&str; 9] = ["&&", "||", "==", "!=", "<=", ">=", "<", ">", "!"];
+
+fn tokenize(condition: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = condition.trim_start();
+    while !rest.is_empty() {
+        if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else if let Some(quote) = rest.chars().next().filter(|c| *c == '\'' || *c == '"') {
+            let end = rest[1..].find(quote).ok_or_else(|| format!("unclosed quote in {:?}", condition))?;
+            tokens.push(Token::Literal(rest[1..end + 1].to_string()));
+            rest = &rest[end + 2..];
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '.' | '-' | '[' | ']')))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                return Err(format!("unexpected {:?} in {:?}", &rest[..1], condition));
+            }
+            let word = &rest[..end];
+            tokens.push(if word.parse::<f64>().is_ok() { Token::Literal(word.to_string()) } else { Token::Name(word.to_string()) });
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a, F: Fn(&str) -> Option<String>> {
+    tokens: &'a [Token],
+    position: usize,
+    lookup: &'a F,
+}
+
+impl<F: Fn(&str) -> Option<String>> Parser<'_, F> {
+    fn next_is(&mut self, op: &str) -> bool {
+        let found = matches!(self.tokens.get(self.position), Some(Token::Op(next)) if *next == op);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<bool, String> {
+        let mut value = self.and()?;
+        while self.next_is("||") {
+            // Both sides are parsed, so a syntax error on the right is not hidden
+            let right = self.and()?;
+            value = value || right;
+        }
+        Ok(value)
+    }
+
+    fn and(&mut self) -> Result<bool, String> {
+        let mut value = self.comparison()?;
+        while self.next_is("&&") {
+            let right = self.comparison()?;
+            value = value && right;
+        }
+        Ok(value)
+    }
+
+    fn comparison(&mut self) -> Result<bool, String> {
+        let negated = self.next_is("!");
+        let left = self.operand()?;
+        let op = ["==", "!=", "<=", ">=", "<", ">"].into_iter().find(|op| self.next_is(op));
+        let value = match op {
+            None => !matches!(left.as_deref(), None | Some("" | "0" | "false")),
+            Some(op) => compare(&left.unwrap_or_default(), op, &self.operand()?.unwrap_or_default()),
+        };
+        Ok(value != negated)
+    }
+
+    fn operand(&mut self) -> Result<Option<String>, String> {
+        let token = self.tokens.get(self.position).ok_or("condition ends early")?;
+        self.position += 1;
+        match token {
+            Token::Literal(value) => Ok(Some(value.clone())),
+            Token::Name(name) => Ok((self.lookup)(name)),
+            Token::Op(op) => Err(format!("expected a value, found {:?}", op)),
+        }
+    }
+}
+
+// Numbers compare as numbers, anything else as text
+fn compare(left: &str, op: &str, right: &str) -> bool {
+    let ordering = match (left.trim().parse::<f64>(), right.trim().parse::<f64>()) {
+        (Ok(left), Ok(right)) => left.partial_cmp(&right),
+        _ => Some(left.cmp(right)),
+    };
+    let Some(ordering) = ordering else {
+        return false;
+    };
+    match op {
+        "==" => ordering.is_eq(),
+        "!=" => ordering.is_ne(),
+        "<" => ordering.is_lt(),
+        "<=" => ordering.is_le(),
+        ">" => ordering.is_gt(),
+        _ => ordering.is_ge(),
+    }
+}
+
+fn evaluate(condition: &str, lookup: &impl Fn(&str) -> Option<String>) -> Result<bool, String> {
+    let tokens = tokenize(condition)?;
+    let mut parser = Parser { tokens: &tokens, position: 0, lookup };
+    let value = parser.or()?;
+    if parser.position < tokens.len() {
+        return Err(format!("unexpected {:?} in {:?}", tokens[parser.position], condition));
+    }
+    Ok(value)
+}
+
+// Checks the syntax, so a broken condition fails when the config is loaded
+pub fn check(condition: &str) -> Result<(), String> {
+    evaluate(condition, &|_| None).map(|_| ())
+}
+
+// Whether `condition` holds; one that does not parse never does
+pub fn holds(condition: &str, lookup: impl Fn(&str) -> Option<String>) -> bool {
+    evaluate(condition, &lookup).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn lookup(name: &str) -> Option<String> {
+        let values = HashMap::from([("data.pin_ok", "true"), ("data.amount", "250"), ("balance", "100"), ("data.skip", "false")]);
+        values.get(name).map(|value| value.to_string())
+    }
+
+    #[test]
+    fn test_comparisons_and_truthiness() {
+        assert!(holds("data.pin_ok == 'true'", lookup));
+        assert!(holds("data.pin_ok != \"false\"", lookup));
+        assert!(holds("data.amount > balance", lookup)); // 250 > 100 as numbers, not as text
+        assert!(holds("data.amount <= 250 && data.pin_ok", lookup));
+        assert!(!holds("data.skip", lookup));
+        assert!(holds("!data.skip", lookup));
+        assert!(!holds("data.missing", lookup));
+        assert!(holds("data.missing == ''", lookup));
+        assert!(holds("data.skip || data.amount >= 250 && balance == 100", lookup));
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        check("data.a == 'x' || !data.b").unwrap();
+        for broken in ["data.a ==", "data.a == 'x", "&& data.a", "data.a data.b", "data.a = 1"] {
+            assert!(check(broken).is_err(), "{:?}", broken);
+            assert!(!holds(broken, lookup));
+        }
+    }
+}
@@ -0,0 +1,31 @@
+// Dialling strings that carry menu keys after the service code, such as *123*2*1#: open *123#,
+// pick 2, then pick 1
+
+// The keys `dialled` carries after `code`: ["2", "1"] for *123*2*1# on *123#, none for *123#
+// itself. None when `dialled` is neither `code` nor an extension of it, or has an empty key.
+pub fn steps<'a>(code: &str, dialled: &'a str) -> Option<Vec<&'a str>> {
+    let base = code.trim_end_matches('#');
+    let rest = dialled.strip_prefix(base)?;
+    if rest == "#" || rest.is_empty() {
+        return Some(Vec::new());
+    }
+    let keys = rest.strip_prefix('*')?.strip_suffix('#')?;
+    let keys: Vec<&str> = keys.split('*').collect();
+    keys.iter().all(|key| !key.is_empty()).then_some(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps() {
+        assert_eq!(steps("*123#", "*123#"), Some(vec![]));
+        assert_eq!(steps("*123#", "*123*2*1#"), Some(vec!["2", "1"]));
+        assert_eq!(steps("*123#", "*123*0771234567#"), Some(vec!["0771234567"]));
+        assert_eq!(steps("*123#", "*1234#"), None);
+        assert_eq!(steps("*123#", "*124*1#"), None);
+        assert_eq!(steps("*123#", "*123**1#"), None);
+        assert_eq!(steps("*123#", "*123*1"), None);
+    }
+}
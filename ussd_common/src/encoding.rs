@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{gsm7, ucs2};
+
+// data_coding values written by the simulators
+pub const DATA_CODING_GSM7: u8 = 0x00;
+pub const DATA_CODING_UCS2: u8 = 0x08;
+
+// Configured encoding for outgoing text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextEncoding {
+    #[default]
+    Gsm7, // data_coding 0; characters outside the alphabet become '?'
+    Ucs2, // data_coding 8, for Sinhala, Tamil, Arabic and other non-Latin scripts
+    Auto, // GSM 7-bit when every character fits, UCS-2 otherwise
+}
+
+impl TextEncoding {
+    // The encoding a received message used, so a reply can be relayed the same way
+    pub fn from_data_coding(data_coding: u8) -> Self {
+        match data_coding {
+            DATA_CODING_UCS2 => TextEncoding::Ucs2,
+            _ => TextEncoding::Gsm7,
+        }
+    }
+
+    pub fn data_coding(self, text: &str) -> u8 {
+        match self {
+            TextEncoding::Gsm7 => DATA_CODING_GSM7,
+            TextEncoding::Ucs2 => DATA_CODING_UCS2,
+            TextEncoding::Auto if gsm7::is_representable(text) => DATA_CODING_GSM7,
+            TextEncoding::Auto => DATA_CODING_UCS2,
+        }
+    }
+}
+
+// Encodes the longest prefix of `text` that fits in `max_len` octets. Only UCS-2 gets its own
+// encoding; every other data_coding is written as GSM 7-bit.
+pub fn encode_within(text: &str, data_coding: u8, gsm7_packed: bool, max_len: usize) -> Vec<u8> {
+    match data_coding {
+        DATA_CODING_UCS2 => ucs2::encode_within(text, max_len),
+        _ => gsm7::encode_within(text, gsm7_packed, max_len),
+    }
+}
+
+pub fn encode(text: &str, data_coding: u8, gsm7_packed: bool) -> Vec<u8> {
+    encode_within(text, data_coding, gsm7_packed, usize::MAX)
+}
+
+// data_coding 0 is GSM 7-bit and 8 is UCS-2; anything else is taken as UTF-8
+pub fn decode(bytes: &[u8], data_coding: u8, gsm7_packed: bool) -> String {
+    match data_coding {
+        DATA_CODING_GSM7 => gsm7::decode(bytes, gsm7_packed),
+        DATA_CODING_UCS2 => ucs2::decode(bytes),
+        _ => String::from_utf8_lossy(bytes).to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auto_picks_ucs2_only_when_needed() {
+        assert_eq!(TextEncoding::Auto.data_coding("1. Balance"), DATA_CODING_GSM7);
+        assert_eq!(TextEncoding::Auto.data_coding("1. ශේෂය"), DATA_CODING_UCS2);
+        assert_eq!(TextEncoding::Gsm7.data_coding("1. ශේෂය"), DATA_CODING_GSM7);
+        assert_eq!(TextEncoding::Ucs2.data_coding("1. Balance"), DATA_CODING_UCS2);
+    }
+
+    #[test]
+    fn test_round_trip_by_data_coding() {
+        let text = "1. இருப்பு\n2. الرصيد";
+        assert_eq!(decode(&encode(text, DATA_CODING_UCS2, false), DATA_CODING_UCS2, false), text);
+        assert_eq!(decode(&encode("*123#", DATA_CODING_GSM7, true), DATA_CODING_GSM7, true), "*123#");
+        assert_eq!(decode("héllo".as_bytes(), 0x03, false), "héllo");
+    }
+}
@@ -0,0 +1,130 @@
+use std::io::{self, ErrorKind, Read, Write};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+// Length-prefixed JSON messages on a stream: a 4-byte big-endian length, then that many bytes of
+// JSON. Any number of messages may share a connection, and a message may arrive in any number of
+// reads.
+
+pub const MAX_FRAME_LEN: usize = 1024 * 1024; // Larger lengths are taken as a broken stream
+
+pub fn write_frame<T: Serialize>(out: &mut impl Write, message: &T) -> io::Result<()> {
+    let body = serde_json::to_vec(message).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+    if body.len() > MAX_FRAME_LEN {
+        return Err(io::Error::new(ErrorKind::InvalidInput, format!("frame of {} bytes is over {}", body.len(), MAX_FRAME_LEN)));
+    }
+    let mut frame = Vec::with_capacity(4 + body.len());
+    frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&body);
+    out.write_all(&frame)?;
+    out.flush()
+}
+
+// Reads whole frames. The bytes of a frame that has not fully arrived are kept, so a read timeout
+// (WouldBlock or TimedOut) can be retried without losing its place in the stream.
+pub struct FrameReader<R> {
+    inner: R,
+    buffer: Vec<u8>,
+}
+
+impl<R: Read> FrameReader<R> {
+    pub fn new(inner: R) -> Self {
+        FrameReader { inner, buffer: Vec::new() }
+    }
+
+    // The next message, or None when the peer closed the stream between frames
+    pub fn read<T: DeserializeOwned>(&mut self) -> io::Result<Option<T>> {
+        loop {
+            if let Some(body) = self.take_frame()? {
+                return serde_json::from_slice(&body).map(Some).map_err(|e| io::Error::new(ErrorKind::InvalidData, e));
+            }
+            let mut chunk = [0; 4096];
+            let read = self.inner.read(&mut chunk)?;
+            if read == 0 {
+                if self.buffer.is_empty() {
+                    return Ok(None);
+                }
+                return Err(io::Error::new(ErrorKind::UnexpectedEof, "stream closed inside a frame"));
+            }
+            self.buffer.extend_from_slice(&chunk[..read]);
+        }
+    }
+
+    fn take_frame(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let Some(prefix) = self.buffer.first_chunk::<4>() else {
+            return Ok(None);
+        };
+        let len = u32::from_be_bytes(*prefix) as usize;
+        if len > MAX_FRAME_LEN {
+            return Err(io::Error::new(ErrorKind::InvalidData, format!("frame length {} is over {}", len, MAX_FRAME_LEN)));
+        }
+        if self.buffer.len() < 4 + len {
+            return Ok(None);
+        }
+        let body = self.buffer[4..4 + len].to_vec();
+        self.buffer.drain(..4 + len);
+        Ok(Some(body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    // Hands out the bytes a few at a time, with a timeout between every read
+    struct Trickle {
+        data: VecDeque<u8>,
+        timed_out: bool,
+    }
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.timed_out = !self.timed_out;
+            if self.timed_out {
+                return Err(io::Error::new(ErrorKind::WouldBlock, "timeout"));
+            }
+            let count = buf.len().min(3).min(self.data.len());
+            for slot in buf.iter_mut().take(count) {
+                *slot = self.data.pop_front().unwrap();
+            }
+            Ok(count)
+        }
+    }
+
+    #[test]
+    fn test_frames_survive_partial_reads_and_timeouts() {
+        let long = "x".repeat(5000); // Far over a single 1024-byte read
+        let mut data = Vec::new();
+        write_frame(&mut data, &serde_json::json!({"id": 1, "text": long})).unwrap();
+        write_frame(&mut data, &serde_json::json!({"id": 2})).unwrap();
+        let mut reader = FrameReader::new(Trickle { data: data.into(), timed_out: false });
+
+        let mut messages = Vec::new();
+        loop {
+            match reader.read::<serde_json::Value>() {
+                Ok(Some(message)) => messages.push(message),
+                Ok(None) => break,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => continue,
+                Err(e) => panic!("{}", e),
+            }
+        }
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0]["text"].as_str().unwrap().len(), 5000);
+        assert_eq!(messages[1]["id"], 2);
+    }
+
+    #[test]
+    fn test_truncated_and_oversized_frames() {
+        let mut data = Vec::new();
+        write_frame(&mut data, &"hello").unwrap();
+        data.pop();
+        let error = FrameReader::new(&data[..]).read::<String>().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+
+        let oversized = ((MAX_FRAME_LEN + 1) as u32).to_be_bytes();
+        assert_eq!(FrameReader::new(&oversized[..]).read::<String>().unwrap_err().kind(), ErrorKind::InvalidData);
+        assert!(FrameReader::new(&[][..]).read::<String>().unwrap().is_none());
+    }
+}
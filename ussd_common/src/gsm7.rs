@@ -0,0 +1,207 @@
+// GSM 03.38 default alphabet, the character set behind data_coding 0
+
+const ESCAPE: u8 = 0x1B;
+const CR: u8 = 0x0D;
+const UNKNOWN: u8 = 0x3F; // '?'
+
+// Index = septet value; the escape slot holds a placeholder that never matches
+const BASIC: [char; 128] = [
+    '@', '£', '$', '¥', 'è', 'é', 'ù', 'ì', 'ò', 'Ç', '\n', 'Ø', 'ø', '\r', 'Å', 'å',
+    'Δ', '_', 'Φ', 'Γ', 'Λ', 'Ω', 'Π', 'Ψ', 'Σ', 'Θ', 'Ξ', '\u{FFFF}', 'Æ', 'æ', 'ß', 'É',
+    ' ', '!', '"', '#', '¤', '%', '&', '\'', '(', ')', '*', '+', ',', '-', '.', '/',
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', ':', ';', '<', '=', '>', '?',
+    '¡', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O',
+    'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', 'Ä', 'Ö', 'Ñ', 'Ü', '§',
+    '¿', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o',
+    'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', 'ä', 'ö', 'ñ', 'ü', 'à',
+];
+
+// Characters reached through the escape septet
+const EXTENSION: [(u8, char); 10] = [
+    (0x0A, '\u{0C}'), (0x14, '^'), (0x28, '{'), (0x29, '}'), (0x2F, '\\'),
+    (0x3C, '['), (0x3D, '~'), (0x3E, ']'), (0x40, '|'), (0x65, '€'),
+];
+
+// Text to wire bytes; characters outside the alphabet become '?'
+pub fn encode(text: &str, packed: bool) -> Vec<u8> {
+    encode_within(text, packed, usize::MAX)
+}
+
+// Encodes the longest prefix of `text` that fits in `max_len` octets without splitting an
+// escape sequence
+pub fn encode_within(text: &str, packed: bool, max_len: usize) -> Vec<u8> {
+    let mut septets = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        let end = septets.len();
+        push_septets(&mut septets, c);
+        let len = if packed { (septets.len() * 7).div_ceil(8) } else { septets.len() };
+        if len > max_len {
+            septets.truncate(end);
+            break;
+        }
+    }
+    if packed { pack(&septets) } else { septets }
+}
+
+pub fn decode(bytes: &[u8], packed: bool) -> String {
+    if packed { from_septets(&unpack(bytes)) } else { from_septets(bytes) }
+}
+
+// Whether every character of `text` survives encoding
+pub fn is_representable(text: &str) -> bool {
+    text.chars().all(|c| basic_code(c).is_some() || extension_code(c).is_some())
+}
+
+fn basic_code(c: char) -> Option<u8> {
+    let code = BASIC.iter().position(|&basic| basic == c)? as u8;
+    (code != ESCAPE).then_some(code)
+}
+
+fn extension_code(c: char) -> Option<u8> {
+    EXTENSION.iter().find(|(_, ext)| *ext == c).map(|(code, _)| *code)
+}
+
+fn push_septets(septets: &mut Vec<u8>, c: char) {
+    if let Some(code) = basic_code(c) {
+        septets.push(code);
+    } else if let Some(code) = extension_code(c) {
+        septets.extend_from_slice(&[ESCAPE, code]);
+    } else {
+        septets.push(UNKNOWN);
+    }
+}
+
+fn from_septets(septets: &[u8]) -> String {
+    let mut text = String::with_capacity(septets.len());
+    let mut septets = septets.iter().map(|septet| septet & 0x7F);
+    while let Some(septet) = septets.next() {
+        if septet != ESCAPE {
+            text.push(BASIC[septet as usize]);
+            continue;
+        }
+        // An unknown extension shows the basic character instead, as 03.38 asks
+        if let Some(code) = septets.next() {
+            let ext = EXTENSION.iter().find(|(ext, _)| *ext == code).map(|(_, c)| *c);
+            text.push(ext.unwrap_or(BASIC[code as usize]));
+        }
+    }
+    text
+}
+
+// Eight septets per seven octets, least significant bits first. When the last octet would
+// carry seven spare bits they are filled with CR, as for USSD, so they do not read as '@'.
+fn pack(septets: &[u8]) -> Vec<u8> {
+    let mut packed = Vec::with_capacity((septets.len() * 7).div_ceil(8));
+    let mut bits: u32 = 0;
+    let mut count = 0;
+    for septet in septets {
+        bits |= u32::from(septet & 0x7F) << count;
+        count += 7;
+        while count >= 8 {
+            packed.push(bits as u8);
+            bits >>= 8;
+            count -= 8;
+        }
+    }
+    if count == 1 {
+        bits |= u32::from(CR) << count;
+    }
+    if count > 0 {
+        packed.push(bits as u8);
+    }
+    packed
+}
+
+fn unpack(octets: &[u8]) -> Vec<u8> {
+    let mut septets = Vec::with_capacity(octets.len() * 8 / 7);
+    let mut bits: u32 = 0;
+    let mut count = 0;
+    for octet in octets {
+        bits |= u32::from(*octet) << count;
+        count += 8;
+        while count >= 7 {
+            septets.push((bits & 0x7F) as u8);
+            bits >>= 7;
+            count -= 7;
+        }
+    }
+    if octets.len().is_multiple_of(7) && septets.last() == Some(&CR) {
+        septets.pop();
+    }
+    septets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_basic_and_extension() {
+        let text = "Balance: $10 @ 5% {VAT} ~ €2 [ok] £ è\n|^\\";
+        let septets = encode(text, false);
+        assert_eq!(&septets[..3], &[0x42, 0x61, 0x6C]);
+        assert_eq!(septets.iter().filter(|&&s| s == ESCAPE).count(), 9);
+        assert_eq!(decode(&septets, false), text);
+        assert_eq!(decode(&encode(text, true), true), text);
+    }
+
+    #[test]
+    fn test_every_extension_character() {
+        for (code, c) in EXTENSION {
+            assert_eq!(encode(&c.to_string(), false), vec![ESCAPE, code]);
+            assert_eq!(decode(&[ESCAPE, code], false), c.to_string());
+        }
+        // An escape before a code with no extension falls back to the basic character
+        assert_eq!(decode(&[ESCAPE, 0x41], false), "A");
+        // A trailing escape is dropped
+        assert_eq!(decode(&[0x41, ESCAPE], false), "A");
+    }
+
+    #[test]
+    fn test_unrepresentable_becomes_question_mark() {
+        assert!(is_representable("Dial *123# for £5 or €6"));
+        assert!(!is_representable("🏠 Home"));
+        assert_eq!(decode(&encode("🏠 Home", false), false), "? Home");
+    }
+
+    #[test]
+    fn test_packing_seven_and_eight_septets() {
+        // Seven septets fill 49 bits: the spare 7 bits of the last octet get CR
+        let seven = encode("ABCDEFG", true);
+        assert_eq!(seven.len(), 7);
+        assert_eq!(seven[6] >> 1, CR);
+        assert_eq!(decode(&seven, true), "ABCDEFG");
+
+        // Eight septets fill exactly seven octets, nothing to pad
+        let eight = encode("ABCDEFGH", true);
+        assert_eq!(eight.len(), 7);
+        assert_eq!(decode(&eight, true), "ABCDEFGH");
+
+        // Known vector: "hellohello" from 3GPP TS 23.038 examples
+        assert_eq!(encode("hellohello", true), vec![0xE8, 0x32, 0x9B, 0xFD, 0x46, 0x97, 0xD9, 0xEC, 0x37]);
+    }
+
+    #[test]
+    fn test_pack_unpack_round_trip_lengths() {
+        for len in 0..20 {
+            let septets: Vec<u8> = (0..len).map(|i| (i * 13 % 128) as u8).filter(|&s| s != ESCAPE).collect();
+            let packed = pack(&septets);
+            assert_eq!(packed.len(), (septets.len() * 7).div_ceil(8));
+            assert_eq!(unpack(&packed), septets, "len {}", len);
+        }
+    }
+
+    #[test]
+    fn test_real_cr_at_end_survives_when_not_padding() {
+        assert_eq!(decode(&encode("ABCDEF\r", true), true), "ABCDEF\r");
+    }
+
+    #[test]
+    fn test_encode_within_does_not_split_escape() {
+        assert_eq!(encode_within("ab€", false, 3), vec![0x61, 0x62]);
+        assert_eq!(encode_within("ab€", false, 4), vec![0x61, 0x62, ESCAPE, 0x65]);
+        // Packed limits count octets: eight septets fit in seven
+        assert_eq!(encode_within("ABCDEFGHI", true, 7), encode("ABCDEFGH", true));
+        assert!(encode_within("anything", false, 0).is_empty());
+    }
+}
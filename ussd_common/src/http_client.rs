@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+// Minimal HTTP/1.1 client for the server's webhooks and HTTP backend and the forwarding client's
+// "http" menu action: plain http://, one request per connection, so no pooling or TLS to carry
+// around
+
+// Where requests go, split out of a URL once at startup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpTarget {
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+}
+
+impl HttpTarget {
+    // `setting` names the config field in errors, e.g. "webhooks.url"
+    pub fn parse(url: &str, setting: &str) -> Result<HttpTarget, String> {
+        let rest = url.strip_prefix("http://").ok_or_else(|| format!("{} {:?} must start with http://", setting, url))?;
+        let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| format!("{} {:?} has a bad port", setting, url))?),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("{} {:?} has no host", setting, url));
+        }
+        let path = if path.is_empty() { "/" } else { path };
+        Ok(HttpTarget { host: host.to_string(), port, path: path.to_string() })
+    }
+}
+
+impl std::fmt::Display for HttpTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}{}", self.host, self.port, self.path)
+    }
+}
+
+// POSTs a JSON body and returns the response body. Anything but a 2xx status is an error.
+pub fn post_json(target: &HttpTarget, headers: &BTreeMap<String, String>, body: &str, timeout: Duration) -> io::Result<String> {
+    let addr = (target.host.as_str(), target.port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} did not resolve", target.host)))?;
+    let mut stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    let mut request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        target.path, target.host, target.port, body.len()
+    );
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("\r\n");
+    request.push_str(body);
+    stream.write_all(request.as_bytes())?;
+
+    let mut reader = BufReader::new(stream);
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line)?;
+    let status = status_line.split_whitespace().nth(1).and_then(|code| code.parse::<u16>().ok());
+    if !matches!(status, Some(200..=299)) {
+        return Err(io::Error::other(format!("unexpected response {:?}", status_line.trim_end())));
+    }
+    let mut length = None;
+    let mut chunked = false;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':').unwrap_or((&line, ""));
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => length = value.trim().parse::<usize>().ok(),
+            "transfer-encoding" => chunked = value.trim().eq_ignore_ascii_case("chunked"),
+            _ => {}
+        }
+    }
+    let mut body = Vec::new();
+    if status == Some(204) {
+        // No body, and the server need not close the connection to say so
+    } else if chunked {
+        loop {
+            let mut size = String::new();
+            reader.read_line(&mut size)?;
+            let size = usize::from_str_radix(size.trim().split(';').next().unwrap_or(""), 16)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad chunk size"))?;
+            if size == 0 {
+                break;
+            }
+            let start = body.len();
+            body.resize(start + size, 0);
+            reader.read_exact(&mut body[start..])?;
+            reader.read_line(&mut String::new())?; // The CRLF after the chunk
+        }
+    } else if let Some(length) = length {
+        body.resize(length, 0);
+        reader.read_exact(&mut body)?;
+    } else {
+        reader.read_to_end(&mut body)?;
+    }
+    String::from_utf8(body).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_http;
+    use std::thread;
+
+    // Answers one request with `response` and returns what was received
+    fn serve_once(response: &str) -> (HttpTarget, thread::JoinHandle<String>) {
+        let (addr, handle) = test_http::serve_once(response.to_string());
+        (HttpTarget::parse(&format!("http://{}/hook", addr), "url").unwrap(), handle)
+    }
+
+    #[test]
+    fn test_post_reads_plain_and_chunked_bodies() {
+        let headers = BTreeMap::from([("Authorization".to_string(), "Bearer t".to_string())]);
+        let (target, handle) = serve_once("HTTP/1.1 200 OK\r\nContent-Length: 11\r\n\r\n{\"ok\":true}");
+        assert_eq!(post_json(&target, &headers, "{}", Duration::from_secs(5)).unwrap(), "{\"ok\":true}");
+        let request = handle.join().unwrap();
+        assert!(request.starts_with("POST /hook HTTP/1.1\r\n"));
+        assert!(request.contains("Authorization: Bearer t\r\n"));
+        assert!(request.ends_with("\r\n\r\n{}"));
+
+        let (target, _) = serve_once("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\n{\"a\"\r\n3\r\n:1}\r\n0\r\n\r\n");
+        assert_eq!(post_json(&target, &BTreeMap::new(), "{}", Duration::from_secs(5)).unwrap(), "{\"a\":1}");
+
+        let (target, _) = serve_once("HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\n\r\n");
+        assert!(post_json(&target, &BTreeMap::new(), "{}", Duration::from_secs(5)).is_err());
+    }
+
+    #[test]
+    fn test_urls() {
+        assert_eq!(
+            HttpTarget::parse("http://hooks.local:8080/ussd/events", "url"),
+            Ok(HttpTarget { host: "hooks.local".to_string(), port: 8080, path: "/ussd/events".to_string() })
+        );
+        assert_eq!(
+            HttpTarget::parse("http://hooks.local", "url"),
+            Ok(HttpTarget { host: "hooks.local".to_string(), port: 80, path: "/".to_string() })
+        );
+        assert!(HttpTarget::parse("https://hooks.local/", "url").is_err());
+        assert!(HttpTarget::parse("http://:80/", "url").is_err());
+        assert!(HttpTarget::parse("http://hooks.local:x/", "url").is_err());
+    }
+}
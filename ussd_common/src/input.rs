@@ -0,0 +1,71 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+// Free text a menu "input" option collects, such as an amount, a PIN or a phone number
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct InputRule {
+    pub name: String, // Key the value is stored under, and {{session.data.<name>}}
+    pub prompt: String, // Screen asking for the value
+    pub pattern: Option<String>, // Regex the whole value must match
+    pub min_length: usize,
+    pub max_length: usize, // 0 = unlimited
+    pub error: Option<String>, // Shown above the prompt when the value is refused
+}
+
+impl InputRule {
+    // Checks the rule itself, so a bad pattern fails at startup rather than on a subscriber
+    pub fn check(&self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err("input needs a name".to_string());
+        }
+        if let Some(pattern) = &self.pattern {
+            Regex::new(pattern).map_err(|e| format!("input {} pattern: {}", self.name, e))?;
+        }
+        Ok(())
+    }
+
+    pub fn accepts(&self, value: &str) -> bool {
+        let length = value.chars().count();
+        if length < self.min_length || (self.max_length > 0 && length > self.max_length) {
+            return false;
+        }
+        match &self.pattern {
+            // Anchored so "[0-9]+" means digits only, not "contains a digit"
+            Some(pattern) => Regex::new(&format!("^(?:{})$", pattern)).is_ok_and(|regex| regex.is_match(value)),
+            None => true,
+        }
+    }
+
+    // The prompt again after a refused value
+    pub fn retry_prompt(&self, default_error: &str) -> String {
+        format!("{}\n{}", self.error.as_deref().unwrap_or(default_error), self.prompt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_length_and_pattern() {
+        let pin = InputRule {
+            name: "pin".to_string(),
+            prompt: "Enter PIN".to_string(),
+            pattern: Some("[0-9]+".to_string()),
+            min_length: 4,
+            max_length: 4,
+            error: None,
+        };
+        pin.check().unwrap();
+        assert!(pin.accepts("1234"));
+        assert!(!pin.accepts("12a4"));
+        assert!(!pin.accepts("123"));
+        assert!(!pin.accepts("12345"));
+        assert_eq!(pin.retry_prompt("Invalid input."), "Invalid input.\nEnter PIN");
+
+        let free = InputRule { name: "note".to_string(), ..Default::default() };
+        assert!(free.accepts("anything at all"));
+        assert!(InputRule { pattern: Some("(".to_string()), ..free }.check().is_err());
+    }
+}
@@ -0,0 +1,19 @@
+// Code shared by the simulator binaries
+pub mod compression;
+pub mod condition;
+pub mod dial;
+pub mod encoding;
+pub mod framing;
+pub mod gsm7;
+pub mod http_client;
+pub mod input;
+pub mod logger;
+pub mod msisdn;
+pub mod run_id;
+pub mod templates;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_http;
+pub mod tls;
+pub mod ucs2;
+pub mod utc;
+pub mod variables;
@@ -0,0 +1,53 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::run_id;
+
+// Log lines prefixed with the run id once one is set. Info and below go to stdout, warnings
+// and errors to stderr; an empty message prints a blank line.
+struct RunLogger;
+
+impl Log for RunLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format_line(run_id::get(), &record.args().to_string());
+        if record.level() <= Level::Warn {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn format_line(run_id: &str, message: &str) -> String {
+    if run_id.is_empty() || message.is_empty() {
+        message.to_string()
+    } else {
+        format!("[{}] {}", run_id, message)
+    }
+}
+
+// Installs the logger; later calls only change the level
+pub fn init(level: LevelFilter) {
+    let _ = log::set_logger(&RunLogger);
+    log::set_max_level(level);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_line() {
+        assert_eq!(format_line("run-1", "Bind successful"), "[run-1] Bind successful");
+        assert_eq!(format_line("run-1", ""), "");
+        assert_eq!(format_line("", "Bind successful"), "Bind successful");
+    }
+}
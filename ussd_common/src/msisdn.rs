@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+
+// Subscriber numbers for simulated traffic: country code, network code, then a subscriber number
+// of `subscriber_digits` digits, counting up from `start` or scattered by `seed`, e.g.
+//   mode = "random"
+//   country_code = "94"
+//   network_code = "77"
+//   subscriber_digits = 7
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MsisdnGenerator {
+    pub mode: MsisdnMode,
+    pub country_code: String,
+    pub network_code: String,
+    pub subscriber_digits: u32,
+    pub start: u64, // First subscriber number in sequential mode
+    pub seed: u64,  // The same seed gives the same random numbers, run after run
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MsisdnMode {
+    #[default]
+    Fixed, // No generator: the binary's configured MSISDN
+    Sequential,
+    Random,
+}
+
+impl Default for MsisdnGenerator {
+    fn default() -> Self {
+        MsisdnGenerator {
+            mode: MsisdnMode::Fixed,
+            country_code: String::new(),
+            network_code: String::new(),
+            subscriber_digits: 7,
+            start: 0,
+            seed: 0,
+        }
+    }
+}
+
+// Multipliers for random mode; each is prime and not 2 or 5, so coprime with every power of ten
+const MULTIPLIERS: [u64; 4] = [7_919, 104_729, 1_299_709, 15_485_863];
+
+impl MsisdnGenerator {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=15).contains(&self.subscriber_digits) {
+            return Err(format!("subscriber_digits must be 1 to 15, not {}", self.subscriber_digits));
+        }
+        for (name, code) in [("country_code", &self.country_code), ("network_code", &self.network_code)] {
+            if !code.chars().all(|c| c.is_ascii_digit()) {
+                return Err(format!("{} {:?} must be digits", name, code));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_fixed(&self) -> bool {
+        self.mode == MsisdnMode::Fixed
+    }
+
+    // The `index`th MSISDN, or None in fixed mode. Numbers are distinct until the subscriber
+    // number space runs out, then repeat.
+    pub fn nth(&self, index: u64) -> Option<String> {
+        let space = 10u64.pow(self.subscriber_digits.clamp(1, 15));
+        let subscriber = match self.mode {
+            MsisdnMode::Fixed => return None,
+            MsisdnMode::Sequential => (self.start % space + index % space) % space,
+            // An affine map modulo the space is a permutation of it, so no number comes up twice
+            MsisdnMode::Random => {
+                let multiplier = MULTIPLIERS[(self.seed % MULTIPLIERS.len() as u64) as usize] as u128;
+                let offset = self.seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) as u128;
+                ((multiplier * (index % space) as u128 + offset) % space as u128) as u64
+            }
+        };
+        Some(format!(
+            "{}{}{:0width$}",
+            self.country_code,
+            self.network_code,
+            subscriber,
+            width = self.subscriber_digits as usize
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generator(mode: MsisdnMode) -> MsisdnGenerator {
+        MsisdnGenerator {
+            mode,
+            country_code: "94".to_string(),
+            network_code: "77".to_string(),
+            subscriber_digits: 3,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sequential_and_fixed() {
+        assert_eq!(generator(MsisdnMode::Fixed).nth(0), None);
+        let sequential = MsisdnGenerator { start: 998, ..generator(MsisdnMode::Sequential) };
+        let numbers: Vec<String> = (0..3).filter_map(|index| sequential.nth(index)).collect();
+        assert_eq!(numbers, ["9477998", "9477999", "9477000"]);
+    }
+
+    #[test]
+    fn test_random_is_a_permutation() {
+        let random = MsisdnGenerator { seed: 42, ..generator(MsisdnMode::Random) };
+        let mut numbers: Vec<String> = (0..1000).filter_map(|index| random.nth(index)).collect();
+        assert!(numbers.iter().all(|msisdn| msisdn.len() == 7 && msisdn.starts_with("9477")));
+        assert_eq!(random.nth(5), random.nth(5));
+        assert_ne!(numbers[..3], ["9477000", "9477001", "9477002"]);
+        numbers.sort();
+        numbers.dedup();
+        assert_eq!(numbers.len(), 1000);
+
+        let other_seed = MsisdnGenerator { seed: 7, ..random.clone() };
+        assert_ne!(other_seed.nth(0), random.nth(0));
+        assert!(MsisdnGenerator { subscriber_digits: 0, ..random.clone() }.validate().is_err());
+        assert!(MsisdnGenerator { network_code: "7a".to_string(), ..random }.validate().is_err());
+    }
+}
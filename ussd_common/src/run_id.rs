@@ -0,0 +1,114 @@
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Environment variable shared by every binary of a coordinated run
+pub const RUN_ID_ENV: &str = "USSD_RUN_ID";
+
+// As long as a UUID; stamped message_ids must still fit SMPP's 65-octet C-Octet String
+pub const MAX_RUN_ID_LEN: usize = 36;
+
+static RUN_ID: OnceLock<String> = OnceLock::new();
+
+// Namespace for the IDs and log lines of one test campaign: the supplied value, then
+// USSD_RUN_ID, then a random UUID. Only the first successful call has any effect.
+pub fn init(supplied: Option<String>) -> Result<&'static str, String> {
+    if let Some(run_id) = RUN_ID.get() {
+        return Ok(run_id);
+    }
+    let run_id = match supplied
+        .or_else(|| env::var(RUN_ID_ENV).ok())
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+    {
+        Some(id) => validate(id)?,
+        None => generate(),
+    };
+    Ok(RUN_ID.get_or_init(|| run_id))
+}
+
+// Empty until `init` runs, e.g. in the all-in-one demo
+pub fn get() -> &'static str {
+    RUN_ID.get().map_or("", String::as_str)
+}
+
+// Prefixes an ID with the run id so concurrent campaigns never collide
+pub fn stamp(id: String) -> String {
+    stamp_with(get(), id)
+}
+
+fn stamp_with(run_id: &str, id: String) -> String {
+    match run_id {
+        "" => id,
+        run_id => format!("{}-{}", run_id, id),
+    }
+}
+
+fn validate(id: String) -> Result<String, String> {
+    if id.len() > MAX_RUN_ID_LEN {
+        return Err(format!("Run ID {:?} is longer than {} characters", id, MAX_RUN_ID_LEN));
+    }
+    if !id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')) {
+        return Err(format!("Run ID {:?} may only contain ASCII letters, digits, '-', '_' and '.'", id));
+    }
+    Ok(id)
+}
+
+// Random (version 4) UUID without pulling in a crate for it
+fn generate() -> String {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+    let half = || {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(nanos);
+        hasher.write_u32(std::process::id());
+        hasher.finish() as u128
+    };
+    let bits = (half() << 64) | half();
+    let bits = (bits & !(0xF << 76)) | (0x4 << 76); // version 4
+    let bits = (bits & !(0x3 << 62)) | (0x2 << 62); // RFC 4122 variant
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        bits >> 96,
+        (bits >> 80) & 0xFFFF,
+        (bits >> 64) & 0xFFFF,
+        (bits >> 48) & 0xFFFF,
+        bits & 0xFFFF_FFFF_FFFF
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_run_id_is_uuid_v4() {
+        let id = generate();
+        assert_eq!(id.len(), MAX_RUN_ID_LEN);
+        let parts: Vec<&str> = id.split('-').collect();
+        assert_eq!(parts.iter().map(|part| part.len()).collect::<Vec<_>>(), vec![8, 4, 4, 4, 12]);
+        assert!(parts[2].starts_with('4'));
+        assert!(matches!(parts[3].chars().next(), Some('8' | '9' | 'a' | 'b')));
+        assert_ne!(generate(), id);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(validate("nightly-42.a_b".to_string()).as_deref(), Ok("nightly-42.a_b"));
+        assert!(validate("x".repeat(MAX_RUN_ID_LEN + 1)).is_err());
+        assert!(validate("has space".to_string()).is_err());
+        assert!(validate("nul\0".to_string()).is_err());
+        assert!(validate("café".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_longest_stamped_message_id_fits_smpp() {
+        // The widest IDs issued: server "USSD<epoch><seq>", forwarding client "FCLIENT<epoch><counter>"
+        for id in [format!("USSD{}{}", u32::MAX, u32::MAX), format!("FCLIENT{}{}", u32::MAX, u32::MAX)] {
+            let stamped = stamp_with(&"x".repeat(MAX_RUN_ID_LEN), id);
+            assert!(stamped.len() < 65, "{} is {} octets", stamped, stamped.len());
+        }
+        assert_eq!(stamp_with("", "USSD1".to_string()), "USSD1");
+    }
+}
@@ -0,0 +1,145 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+const INCLUDE_OPEN: &str = "{{>";
+const INCLUDE_CLOSE: &str = "}}";
+
+// Long screens kept as files instead of escaped TOML strings
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TemplatesConfig {
+    pub dir: Option<String>, // Directory of <name>.txt files, relative to the config file
+}
+
+impl TemplatesConfig {
+    // Replaces every {{> name}} in `text` with the named template, which may include others.
+    // `base_dir` is the directory of the config file that set `dir`.
+    pub fn expand(&self, base_dir: &Path, text: &str) -> Result<String, String> {
+        if !text.contains(INCLUDE_OPEN) {
+            return Ok(text.to_string());
+        }
+        let Some(dir) = &self.dir else {
+            return Err(format!("{:?} includes a template but templates.dir is not set", text));
+        };
+        expand_in(&base_dir.join(dir), text, &mut Vec::new())
+    }
+
+    pub fn expand_all<'a>(&self, base_dir: &Path, texts: impl IntoIterator<Item = &'a mut String>) -> Result<(), String> {
+        for text in texts {
+            *text = self.expand(base_dir, text)?;
+        }
+        Ok(())
+    }
+}
+
+fn expand_in(dir: &Path, text: &str, including: &mut Vec<String>) -> Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(INCLUDE_OPEN) {
+        let after_open = &rest[start + INCLUDE_OPEN.len()..];
+        let Some(end) = after_open.find(INCLUDE_CLOSE) else {
+            return Err(format!("Unclosed template include in {:?}", text));
+        };
+        let name = after_open[..end].trim();
+        result.push_str(&rest[..start]);
+        result.push_str(&load(dir, name, including)?);
+        rest = &after_open[end + INCLUDE_CLOSE.len()..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+fn load(dir: &Path, name: &str, including: &mut Vec<String>) -> Result<String, String> {
+    let valid = !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if !valid || name.starts_with('.') {
+        return Err(format!("Invalid template name {:?}", name));
+    }
+    if including.iter().any(|parent| parent == name) {
+        return Err(format!("Template include cycle: {} -> {}", including.join(" -> "), name));
+    }
+
+    let path = dir.join(format!("{}.txt", name));
+    let content = fs::read_to_string(&path).map_err(|e| format!("Could not read template {}: {}", path.display(), e))?;
+    // Editors end files with a newline that is not part of the screen
+    let content = content.strip_suffix('\n').unwrap_or(&content);
+    let content = content.strip_suffix('\r').unwrap_or(content);
+
+    including.push(name.to_string());
+    let expanded = expand_in(dir, content, including);
+    including.pop();
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn template_dir(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ussd_templates_{}_{}", test, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, content) in files {
+            fs::write(dir.join(format!("{}.txt", name)), content).unwrap();
+        }
+        dir
+    }
+
+    fn config(dir: &str) -> TemplatesConfig {
+        TemplatesConfig {
+            dir: Some(dir.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_plain_text_needs_no_directory() {
+        let config = TemplatesConfig::default();
+        assert_eq!(config.expand(Path::new(""), "Welcome\n1. Balance").unwrap(), "Welcome\n1. Balance");
+        assert!(config.expand(Path::new(""), "{{> balance}}").is_err());
+    }
+
+    #[test]
+    fn test_nested_includes() {
+        let dir = template_dir("nested", &[
+            ("balance", "Balance: $10\n{{> footer}}\n"),
+            ("footer", "0. Back\r\n"),
+        ]);
+        assert_eq!(
+            config(&dir.to_string_lossy()).expand(Path::new("/unused"), "💰 {{> balance }}").unwrap(),
+            "💰 Balance: $10\n0. Back"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_dir_is_relative_to_config_file() {
+        let base = template_dir("relative", &[]);
+        fs::create_dir_all(base.join("screens")).unwrap();
+        fs::write(base.join("screens").join("menu.txt"), "1. Balance\n").unwrap();
+
+        let mut texts = vec!["{{> menu}}".to_string(), "plain".to_string()];
+        config("screens").expand_all(&base, texts.iter_mut()).unwrap();
+        assert_eq!(texts, ["1. Balance", "plain"]);
+        assert!(config("screens").expand(Path::new("/nonexistent"), "{{> menu}}").is_err());
+        fs::remove_dir_all(base).unwrap();
+    }
+
+    #[test]
+    fn test_include_cycle_is_rejected() {
+        let dir = template_dir("cycle", &[("a", "{{> b}}"), ("b", "{{> a}}")]);
+        let error = config(&dir.to_string_lossy()).expand(Path::new(""), "{{> a}}").unwrap_err();
+        assert!(error.contains("a -> b -> a"), "{}", error);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_includes_are_rejected() {
+        let dir = template_dir("invalid", &[]);
+        let config = config(&dir.to_string_lossy());
+        for text in ["{{> ../secret}}", "{{> .hidden}}", "{{>}}", "{{> open", "{{> missing}}"] {
+            assert!(config.expand(Path::new(""), text).is_err(), "{}", text);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,32 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::thread;
+
+// One-request HTTP servers on loopback for the tests of code that posts through http_client.
+// Built for this crate's tests and, with the "test-support" feature, for the other crates'.
+
+// A 200 carrying `body` as JSON
+pub fn json_response(body: &str) -> String {
+    format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", body.len(), body)
+}
+
+// Answers one request with `response` and returns the request it got
+pub fn serve_once(response: String) -> (SocketAddr, thread::JoinHandle<String>) {
+    serve_once_after(response, || ())
+}
+
+// As serve_once, but runs `before_answer` between reading the request and answering it, so a
+// test can hold the reply back
+pub fn serve_once_after(response: String, before_answer: impl FnOnce() + Send + 'static) -> (SocketAddr, thread::JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handle = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = vec![0; 4096];
+        let read = stream.read(&mut request).unwrap();
+        before_answer();
+        stream.write_all(response.as_bytes()).unwrap();
+        String::from_utf8_lossy(&request[..read]).to_string()
+    });
+    (addr, handle)
+}
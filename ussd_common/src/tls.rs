@@ -0,0 +1,323 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{ClientConfig, ClientConnection, Connection, RootCertStore, ServerConfig, ServerConnection};
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use serde::{Deserialize, Serialize};
+
+pub use rustls;
+
+// Certificates for a TLS listener, for ESMEs that only connect over smpps://
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TlsServerConfig {
+    pub enabled: bool,
+    pub cert: String,              // PEM certificate chain the server presents
+    pub key: String,               // PEM private key for `cert`
+    pub client_ca: String,         // PEM CA bundle client certificates are checked against
+    pub require_client_cert: bool, // Refuse clients without a certificate signed by `client_ca`
+}
+
+// Connects to the server over TLS instead of plain TCP
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TlsClientConfig {
+    pub enabled: bool,
+    pub ca: String,          // PEM CA bundle the server certificate is checked against
+    pub cert: String,        // PEM client certificate, for servers that require one
+    pub key: String,         // PEM private key for `cert`
+    pub server_name: String, // Name the server certificate must carry (empty = the host connected to)
+}
+
+impl TlsClientConfig {
+    pub fn server_name(&self, host: &str) -> io::Result<ServerName<'static>> {
+        let name = if self.server_name.is_empty() { host } else { &self.server_name };
+        ServerName::try_from(name.to_string()).map_err(|e| invalid(format!("TLS server name {}: {}", name, e)))
+    }
+
+    // Handshakes over an open socket, failing if the server's certificate is not trusted
+    pub fn connect(&self, socket: TcpStream, host: &str) -> io::Result<TlsStream> {
+        TlsStream::connect(socket, client_config(self)?, self.server_name(host)?)
+    }
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn load_certs(path: &str) -> io::Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| invalid(format!("{}: {}", path, e)))?;
+    if certs.is_empty() {
+        return Err(invalid(format!("{}: no certificates found", path)));
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &str) -> io::Result<PrivateKeyDer<'static>> {
+    PrivateKeyDer::from_pem_file(path).map_err(|e| invalid(format!("{}: {}", path, e)))
+}
+
+fn load_roots(path: &str) -> io::Result<Arc<RootCertStore>> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots.add(cert).map_err(|e| invalid(format!("{}: {}", path, e)))?;
+    }
+    Ok(Arc::new(roots))
+}
+
+pub fn server_config(config: &TlsServerConfig) -> io::Result<Arc<ServerConfig>> {
+    if config.cert.is_empty() || config.key.is_empty() {
+        return Err(invalid("TLS needs both cert and key".to_string()));
+    }
+    let builder = ServerConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .map_err(io::Error::other)?;
+    let builder = if config.client_ca.is_empty() {
+        if config.require_client_cert {
+            return Err(invalid("require_client_cert needs a client_ca to check certificates against".to_string()));
+        }
+        builder.with_no_client_auth()
+    } else {
+        let verifier = WebPkiClientVerifier::builder_with_provider(load_roots(&config.client_ca)?, provider());
+        let verifier = if config.require_client_cert { verifier } else { verifier.allow_unauthenticated() };
+        builder.with_client_cert_verifier(verifier.build().map_err(io::Error::other)?)
+    };
+    let server_config = builder
+        .with_single_cert(load_certs(&config.cert)?, load_key(&config.key)?)
+        .map_err(|e| invalid(format!("{}: {}", config.cert, e)))?;
+    Ok(Arc::new(server_config))
+}
+
+pub fn client_config(config: &TlsClientConfig) -> io::Result<Arc<ClientConfig>> {
+    if config.ca.is_empty() {
+        return Err(invalid("TLS needs a ca to check the server certificate against".to_string()));
+    }
+    let builder = ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .map_err(io::Error::other)?
+        .with_root_certificates(load_roots(&config.ca)?);
+    let client_config = match (config.cert.is_empty(), config.key.is_empty()) {
+        (true, true) => builder.with_no_client_auth(),
+        (false, false) => builder
+            .with_client_auth_cert(load_certs(&config.cert)?, load_key(&config.key)?)
+            .map_err(|e| invalid(format!("{}: {}", config.cert, e)))?,
+        _ => return Err(invalid("A TLS client certificate needs both cert and key".to_string())),
+    };
+    Ok(Arc::new(client_config))
+}
+
+// A TLS connection over a blocking socket. Clones share the TLS session, so one thread can read
+// while others write, as with a TcpStream; a reader waiting for data does not hold the session.
+pub struct TlsStream {
+    socket: TcpStream,
+    session: Arc<Mutex<Connection>>,
+}
+
+impl fmt::Debug for TlsStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsStream").field("peer", &self.socket.peer_addr().ok()).finish()
+    }
+}
+
+impl TlsStream {
+    // The handshake runs as part of the first reads, so accepting never blocks the listener
+    pub fn accept(socket: TcpStream, config: Arc<ServerConfig>) -> io::Result<Self> {
+        let session = ServerConnection::new(config).map_err(io::Error::other)?;
+        Ok(TlsStream { socket, session: Arc::new(Mutex::new(session.into())) })
+    }
+
+    // Returns once the handshake has completed
+    pub fn connect(mut socket: TcpStream, config: Arc<ClientConfig>, server_name: ServerName<'static>) -> io::Result<Self> {
+        let mut session: Connection = ClientConnection::new(config, server_name).map_err(io::Error::other)?.into();
+        while session.is_handshaking() {
+            session.complete_io(&mut socket)?;
+        }
+        Ok(TlsStream { socket, session: Arc::new(Mutex::new(session)) })
+    }
+
+    pub fn try_clone(&self) -> io::Result<TlsStream> {
+        Ok(TlsStream { socket: self.socket.try_clone()?, session: Arc::clone(&self.session) })
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.socket.set_read_timeout(timeout)
+    }
+
+    pub fn peer_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.socket.peer_addr()
+    }
+
+    // Sends close_notify before closing the socket for writing
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        if how != Shutdown::Read {
+            let mut session = self.session.lock().unwrap();
+            session.send_close_notify();
+            let _ = send_pending(&mut session, &self.socket);
+        }
+        self.socket.shutdown(how)
+    }
+}
+
+fn send_pending(session: &mut Connection, mut socket: &TcpStream) -> io::Result<()> {
+    while session.wants_write() {
+        session.write_tls(&mut socket)?;
+    }
+    Ok(())
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut incoming = [0u8; 4096];
+        loop {
+            {
+                let mut session = self.session.lock().unwrap();
+                match session.reader().read(buf) {
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                    result => return result,
+                }
+                send_pending(&mut session, &self.socket)?;
+            }
+
+            let n = (&self.socket).read(&mut incoming)?;
+            let mut session = self.session.lock().unwrap();
+            let mut received = &incoming[..n];
+            loop {
+                // An empty read tells the session the peer has closed the socket
+                session.read_tls(&mut received)?;
+                if let Err(e) = session.process_new_packets() {
+                    let _ = send_pending(&mut session, &self.socket);
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, e));
+                }
+                if received.is_empty() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut session = self.session.lock().unwrap();
+        let written = session.writer().write(buf)?;
+        send_pending(&mut session, &self.socket)?;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut session = self.session.lock().unwrap();
+        session.writer().flush()?;
+        send_pending(&mut session, &self.socket)?;
+        (&self.socket).flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::net::TcpListener;
+    use std::thread;
+
+    use rcgen::{BasicConstraints, CertificateParams, CertifiedIssuer, IsCa, KeyPair};
+
+    // A CA with a "localhost" server certificate and a client certificate, written as PEM files
+    fn write_certs(name: &str) -> (TlsServerConfig, TlsClientConfig) {
+        let dir = std::env::temp_dir().join(format!("ussd_tls_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |file: &str| dir.join(file).to_str().unwrap().to_string();
+
+        let mut ca_params = CertificateParams::new(Vec::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = CertifiedIssuer::self_signed(ca_params, KeyPair::generate().unwrap()).unwrap();
+        fs::write(path("ca.pem"), ca.pem()).unwrap();
+        for (leaf, names) in [("server", vec!["localhost".to_string()]), ("client", vec!["esme.test".to_string()])] {
+            let key = KeyPair::generate().unwrap();
+            let cert = CertificateParams::new(names).unwrap().signed_by(&key, &ca).unwrap();
+            fs::write(path(&format!("{}.pem", leaf)), cert.pem()).unwrap();
+            fs::write(path(&format!("{}.key", leaf)), key.serialize_pem()).unwrap();
+        }
+
+        let server = TlsServerConfig {
+            enabled: true,
+            cert: path("server.pem"),
+            key: path("server.key"),
+            client_ca: path("ca.pem"),
+            require_client_cert: true,
+        };
+        let client = TlsClientConfig { enabled: true, ca: path("ca.pem"), cert: path("client.pem"), key: path("client.key"), server_name: String::new() };
+        (server, client)
+    }
+
+    // Accepts one connection and echoes what it reads until the client closes
+    fn echo_server(config: &TlsServerConfig) -> (u16, thread::JoinHandle<io::Result<Vec<u8>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let config = server_config(config).unwrap();
+        let handle = thread::spawn(move || {
+            let (socket, _) = listener.accept()?;
+            let mut reader = TlsStream::accept(socket, config)?;
+            let mut writer = reader.try_clone()?;
+            let mut received = Vec::new();
+            let mut buf = [0u8; 64];
+            loop {
+                let n = reader.read(&mut buf)?;
+                if n == 0 {
+                    return Ok(received);
+                }
+                received.extend_from_slice(&buf[..n]);
+                writer.write_all(&buf[..n])?;
+            }
+        });
+        (port, handle)
+    }
+
+    #[test]
+    fn test_client_certificate_round_trip() {
+        let (server, client) = write_certs("mutual");
+        let (port, handle) = echo_server(&server);
+
+        let socket = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let mut stream = client.connect(socket, "localhost").unwrap();
+        stream.write_all(b"bind").unwrap();
+        let mut echoed = [0u8; 4];
+        stream.read_exact(&mut echoed).unwrap();
+        assert_eq!(&echoed, b"bind");
+        stream.shutdown(Shutdown::Write).unwrap();
+        assert_eq!(handle.join().unwrap().unwrap(), b"bind");
+    }
+
+    #[test]
+    fn test_missing_client_certificate_is_refused() {
+        let (server, client) = write_certs("refused");
+        let (port, handle) = echo_server(&server);
+
+        let anonymous = TlsClientConfig { cert: String::new(), key: String::new(), ..client.clone() };
+        let socket = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        // TLS 1.3 clients finish their side of the handshake before the server checks them
+        if let Ok(mut stream) = anonymous.connect(socket, "localhost") {
+            let _ = stream.write_all(b"bind");
+            assert!(stream.read(&mut [0u8; 4]).is_err());
+        }
+        assert!(handle.join().unwrap().is_err());
+
+        // The certificate names localhost, not the address
+        let socket = TcpStream::connect(("127.0.0.1", echo_server(&server).0)).unwrap();
+        assert!(client.connect(socket, "127.0.0.1").is_err());
+
+        let optional = TlsServerConfig { client_ca: String::new(), ..server };
+        assert!(server_config(&optional).is_err());
+    }
+}
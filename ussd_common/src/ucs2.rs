@@ -0,0 +1,58 @@
+// UCS-2 (data_coding 8): two octets per character, big-endian. Characters outside the Basic
+// Multilingual Plane are sent as UTF-16 surrogate pairs, which handsets treat the same way.
+
+pub fn encode(text: &str) -> Vec<u8> {
+    encode_within(text, usize::MAX)
+}
+
+// Encodes the longest prefix of `text` that fits in `max_len` octets without splitting a
+// surrogate pair
+pub fn encode_within(text: &str, max_len: usize) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len() * 2);
+    let mut units = [0u16; 2];
+    for c in text.chars() {
+        let units = c.encode_utf16(&mut units);
+        if bytes.len() + units.len() * 2 > max_len {
+            break;
+        }
+        for unit in units.iter() {
+            bytes.extend_from_slice(&unit.to_be_bytes());
+        }
+    }
+    bytes
+}
+
+// A trailing odd octet is dropped and unpaired surrogates become U+FFFD
+pub fn decode(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+    String::from_utf16_lossy(&units)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_non_latin_scripts() {
+        for text in ["ශේෂය: රු. 25.50", "இருப்பு: ₹25.50", "الرصيد: 25.50", "Balance $25.50"] {
+            assert_eq!(encode(text).len(), text.chars().count() * 2);
+            assert_eq!(decode(&encode(text)), text);
+        }
+        assert_eq!(encode("A"), vec![0x00, 0x41]);
+        assert_eq!(encode("ශ"), vec![0x0D, 0xC1]);
+    }
+
+    #[test]
+    fn test_encode_within_keeps_whole_characters() {
+        assert_eq!(encode_within("ABC", 5), vec![0x00, 0x41, 0x00, 0x42]);
+        // A surrogate pair is never cut in half
+        assert_eq!(decode(&encode_within("A🏠B", 4)), "A");
+        assert_eq!(decode(&encode_within("A🏠B", 6)), "A🏠");
+    }
+
+    #[test]
+    fn test_decode_tolerates_malformed_input() {
+        assert_eq!(decode(&[0x00, 0x41, 0x00]), "A");
+        assert_eq!(decode(&[0xD8, 0x3D, 0x00, 0x41]), "\u{FFFD}A");
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::utc::utc_parts;
+
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+
+// Per-MSISDN values for {{name}} placeholders in menus and responses
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ProfilesConfig {
+    pub defaults: BTreeMap<String, String>, // For MSISDNs without their own value
+    pub msisdns: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+// What {{...}} placeholders expand to for one request
+#[derive(Debug, Clone)]
+pub struct Variables<'a> {
+    profiles: &'a ProfilesConfig,
+    msisdn: &'a str,
+    inputs: &'a [String], // The dialled code, then each reply in the session
+    values: BTreeMap<String, String>, // Looked up before the profiles, e.g. a subscriber record
+    now: SystemTime,
+}
+
+impl ProfilesConfig {
+    pub fn variables<'a>(&'a self, msisdn: &'a str, inputs: &'a [String]) -> Variables<'a> {
+        Variables { profiles: self, msisdn, inputs, values: BTreeMap::new(), now: SystemTime::now() }
+    }
+}
+
+impl Variables<'_> {
+    pub fn with_values(mut self, values: BTreeMap<String, String>) -> Self {
+        self.values = values;
+        self
+    }
+
+    // Values an "input" option collected, as {{session.data.<name>}} or, as conditions name
+    // them, {{data.<name>}}
+    pub fn with_session_data<'d>(mut self, data: impl IntoIterator<Item = (&'d String, &'d String)>) -> Self {
+        for (name, value) in data {
+            self.values.insert(format!("session.data.{}", name), value.clone());
+            self.values.insert(format!("data.{}", name), value.clone());
+        }
+        self
+    }
+
+    pub fn at(mut self, now: SystemTime) -> Self {
+        self.now = now;
+        self
+    }
+
+    // {{msisdn}}, {{date}}, {{time}} and {{now}} (UTC), {{input}} (the latest input) and
+    // {{session.input[n]}} (0 is the dialled code), then `with_values` and `with_session_data`,
+    // the MSISDN's profile and the profile defaults
+    pub fn get(&self, name: &str) -> Option<String> {
+        match name {
+            "msisdn" => return Some(self.msisdn.to_string()),
+            "input" => return self.inputs.last().cloned(),
+            "now" => {
+                let (year, month, day, hour, minute, second) = utc_parts(self.now);
+                return Some(format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, hour, minute, second));
+            }
+            "date" => {
+                let (year, month, day, ..) = utc_parts(self.now);
+                return Some(format!("{:04}-{:02}-{:02}", year, month, day));
+            }
+            "time" => {
+                let (.., hour, minute, _) = utc_parts(self.now);
+                return Some(format!("{:02}:{:02}", hour, minute));
+            }
+            _ => {}
+        }
+        if let Some(index) = name.strip_prefix("session.input[").and_then(|rest| rest.strip_suffix(']')) {
+            return index.trim().parse::<usize>().ok().and_then(|index| self.inputs.get(index).cloned());
+        }
+        self.values
+            .get(name)
+            .or_else(|| self.profiles.msisdns.get(self.msisdn).and_then(|profile| profile.get(name)))
+            .or_else(|| self.profiles.defaults.get(name))
+            .cloned()
+    }
+
+    // Replaces every known {{name}} in `text`. Unknown names are left as they are, so a typo
+    // shows up on the screen instead of silently vanishing.
+    pub fn apply(&self, text: &str) -> String {
+        if !text.contains(OPEN) {
+            return text.to_string();
+        }
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find(OPEN) {
+            let after_open = &rest[start + OPEN.len()..];
+            let Some(end) = after_open.find(CLOSE) else {
+                break;
+            };
+            result.push_str(&rest[..start]);
+            match self.get(after_open[..end].trim()) {
+                Some(value) => result.push_str(&value),
+                None => result.push_str(&rest[start..start + OPEN.len() + end + CLOSE.len()]),
+            }
+            rest = &after_open[end + CLOSE.len()..];
+        }
+        result.push_str(rest);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn profiles() -> ProfilesConfig {
+        let mut profiles = ProfilesConfig::default();
+        profiles.defaults.insert("balance".to_string(), "$0.00".to_string());
+        profiles.defaults.insert("name".to_string(), "Customer".to_string());
+        profiles.msisdns.insert("111".to_string(), BTreeMap::from([("balance".to_string(), "$12.50".to_string())]));
+        profiles
+    }
+
+    #[test]
+    fn test_builtins_profiles_and_defaults() {
+        let profiles = profiles();
+        let inputs = vec!["*123#".to_string(), "2".to_string()];
+        // 2024-02-29 12:34 UTC
+        let now = UNIX_EPOCH + Duration::from_secs(1_709_210_040);
+        let variables = profiles.variables("111", &inputs).at(now);
+        assert_eq!(
+            variables.apply("{{ name }} {{msisdn}}: {{balance}} on {{date}} {{time}}, chose {{session.input[1]}} in {{session.input[0]}}"),
+            "Customer 111: $12.50 on 2024-02-29 12:34, chose 2 in *123#"
+        );
+        assert_eq!(variables.apply("{{input}} at {{now}}"), "2 at 2024-02-29 12:34:00");
+        assert_eq!(profiles.variables("222", &inputs).apply("{{balance}}"), "$0.00");
+        let values = BTreeMap::from([("balance".to_string(), "$1.00".to_string())]);
+        let data = BTreeMap::from([("amount".to_string(), "20".to_string())]);
+        assert_eq!(
+            profiles.variables("111", &inputs).with_values(values).with_session_data(&data).apply("{{balance}} {{name}} {{session.data.amount}} {{data.amount}}"),
+            "$1.00 Customer 20 20"
+        );
+    }
+
+    #[test]
+    fn test_unknown_and_unclosed_placeholders_are_kept() {
+        let profiles = profiles();
+        let variables = profiles.variables("111", &[]);
+        assert_eq!(variables.apply("{{nope}} {{session.input[3]}} {{input}} {{balance"), "{{nope}} {{session.input[3]}} {{input}} {{balance");
+        assert_eq!(variables.apply("No placeholders"), "No placeholders");
+    }
+}
@@ -0,0 +1,82 @@
+// One binary for the whole toolkit: each subcommand runs the matching simulator with the rest of
+// the command line, so `ussd-sim server -c config.toml` is `ussd_smpp_simulator -c config.toml`
+use std::env;
+use std::process;
+
+mod selftest;
+
+fn print_usage() {
+    println!("USSD Simulator Toolkit");
+    println!("Usage: ussd-sim <COMMAND> [OPTIONS]");
+    println!();
+    println!("Commands:");
+    println!("  server [OPTIONS]         Run the USSD SMPP simulator (ussd_smpp_simulator)");
+    println!("  user [OPTIONS]           Run the interactive phone (ussd_user_simulator)");
+    println!("  forwarder [OPTIONS]      Run the forwarding client and its menus (ussd_smpp_client_simulator)");
+    println!("  loadgen [OPTIONS]        Run the user simulator's load test (ussd_user_simulator --load)");
+    println!("  decode <FILE>            Decode a [capture] file (pcap or binary) and exit");
+    println!("  selftest                 Start the server, a forwarding client and a scripted phone in");
+    println!("                           this process, dial through the forwarding path and report");
+    println!("                           pass/fail");
+    println!("  help                     Show this help message");
+    println!();
+    println!("Run `ussd-sim <COMMAND> --help` for the options of each command.");
+    println!();
+    println!("Examples:");
+    println!("  ussd-sim server --profile happy-path");
+    println!("  ussd-sim forwarder -c client_config.toml");
+    println!("  ussd-sim user --once \"*123#,1,0\"");
+    println!("  ussd-sim loadgen --sessions 50 --tps 200 --duration 120");
+    println!("  ussd-sim decode capture.pcap");
+    println!("  ussd-sim selftest");
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let Some(command) = args.get(1) else {
+        print_usage();
+        process::exit(1);
+    };
+    // Each simulator parses its own options; it sees "ussd-sim <command>" as its program name
+    let program = format!("ussd-sim {}", command);
+    let rest = args[2..].iter().cloned();
+
+    let result = match command.as_str() {
+        "server" => run_server(std::iter::once(program).chain(rest).collect()),
+        "user" => ussd_user_simulator::run(std::iter::once(program).chain(rest).collect()),
+        "loadgen" => ussd_user_simulator::run([program, "--load".to_string()].into_iter().chain(rest).collect()),
+        "forwarder" => run_forwarder(std::iter::once(program).chain(rest).collect()),
+        "decode" => {
+            let [file] = &args[2..] else {
+                eprintln!("Error: decode takes one capture file");
+                print_usage();
+                process::exit(1);
+            };
+            run_server(vec![program, "--dump".to_string(), file.clone()])
+        }
+        "selftest" => selftest::run(),
+        "help" | "--help" | "-h" => {
+            print_usage();
+            Ok(())
+        }
+        _ => {
+            eprintln!("Error: Unknown command '{}'", command);
+            print_usage();
+            process::exit(1);
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run_server(args: Vec<String>) -> std::io::Result<()> {
+    ussd_smpp_simulator::cli::run(args)
+}
+
+// The forwarding client is async; the other simulators run on plain threads
+fn run_forwarder(args: Vec<String>) -> std::io::Result<()> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(ussd_smpp_client_simulator::cli::run(args)).map_err(std::io::Error::other)
+}
@@ -0,0 +1,106 @@
+// `ussd-sim selftest`: the server, a forwarding client and a scripted phone, each built from its
+// own crate, in one process on an ephemeral port. The phone dials a code the server forwards, so
+// every screen makes the full trip phone -> server -> forwarding client -> server -> phone.
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ussd_smpp_client_simulator::{ClientConfig, UssdApp, UssdMenuManager};
+use ussd_smpp_simulator::{Config, UssdSmppServer};
+use ussd_smpp_simulator::admin::SessionControl;
+use ussd_smpp_simulator::selftest::{self, Step, StepResult};
+use ussd_user_simulator::{UserSimulatorConfig, UssdSmppClient};
+
+// Not one of the server's service codes, so it is forwarded
+const SERVICE_CODE: &str = "*555#";
+const BIND_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Fails when the components do not come up or any step does
+pub fn run() -> io::Result<()> {
+    selftest::print_results(&self_test()?)
+}
+
+fn self_test() -> io::Result<Vec<StepResult>> {
+    let embedded = UssdSmppServer::builder()
+        .config(Config::deterministic())
+        .configure(|config| {
+            config.server.host = "127.0.0.1".to_string();
+            config.server.port = 0;
+        })
+        .spawn()?;
+    println!("Self-test server listening on {}", embedded.addr);
+    let server_config = embedded.server.config.get();
+
+    // The forwarding client binds with its default menus and one of the server's forwarding IDs
+    let mut client = ClientConfig::default();
+    client.client.host = embedded.addr.ip().to_string();
+    client.client.port = embedded.addr.port();
+    client.client.auto_reconnect = false;
+    client.client.heartbeat_interval = 0;
+    if let Some(system_id) = server_config.client_simulator.forwarding_clients.first() {
+        client.client.system_id = system_id.clone();
+    }
+    let app = UssdApp::with_config(client.clone()).fallback(UssdMenuManager::new(client.clone()));
+    let runtime = tokio::runtime::Runtime::new()?;
+    let serving = app.clone();
+    let forwarder = runtime.spawn(async move { serving.start().await });
+
+    let controller = embedded.server.controller();
+    let deadline = Instant::now() + BIND_TIMEOUT;
+    while !controller.binds().iter().any(|bind| bind.role == "forwarding") {
+        if forwarder.is_finished() || Instant::now() >= deadline {
+            let error = match runtime.block_on(forwarder) {
+                Ok(Err(e)) => e.to_string(),
+                _ => format!("no bind within {}s", BIND_TIMEOUT.as_secs()),
+            };
+            return Err(io::Error::other(format!("Forwarding client did not bind: {}", error)));
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    println!("Forwarding client bound as {}", client.client.system_id);
+
+    let mut user = UserSimulatorConfig::default();
+    user.server.host = embedded.addr.ip().to_string();
+    user.server.port = embedded.addr.port();
+    if let Some(system_id) = server_config.client_simulator.user_clients.first() {
+        user.authentication.system_id = system_id.clone();
+    }
+    let mut phone = UssdSmppClient::new(user);
+    if !phone.connect()? {
+        return Err(io::Error::other("Phone could not bind"));
+    }
+
+    // Expected screens come from the forwarding client's default menus
+    let main_menu = client.menus.menus.get("main").map(|menu| menu.title.clone()).unwrap_or_default();
+    let services = client.responses.responses.get("services").cloned().unwrap_or_default();
+    let steps = [
+        Step { input: SERVICE_CODE, expect: main_menu.clone() },
+        Step { input: "1", expect: services.lines().next().unwrap_or_default().to_string() },
+        Step { input: SERVICE_CODE, expect: main_menu },
+        Step { input: "0", expect: client.responses.defaults.exit_message.clone() },
+    ];
+
+    let results = selftest::walk("forwarded", &steps, |input| {
+        phone.send_ussd_request(input).map(|response| response.text).map_err(|e| e.to_string())
+    });
+
+    phone.unbind()?;
+    if let Err(e) = runtime.block_on(app.stop()) {
+        println!("⚠️  Forwarding client did not stop cleanly: {}", e);
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_test_passes() {
+        let results = self_test().unwrap();
+        assert_eq!(results.len(), 4);
+        for result in results {
+            assert_eq!(result.outcome, Ok(()), "{}", result.name);
+        }
+    }
+}
@@ -0,0 +1,37 @@
+// A tiny USSD application on the simulator's SMPP plumbing. Start ussd_smpp_simulator with
+// *500# routed to this client (or listed outside its service_codes), then dial *500#.
+//
+//     cargo run --example custom_app -- 127.0.0.1:2775 ForwardingClient forward123
+use anyhow::Result;
+use ussd_smpp_client_simulator::{UssdApp, UssdReply, UssdRequest};
+
+fn greeter(request: &mut UssdRequest) -> UssdReply {
+    if request.new_session {
+        return UssdReply::menu("Welcome to Greeter\n1. Say hello\n2. Count visits\n0. Exit");
+    }
+    match request.input {
+        "1" => UssdReply::end(format!("Hello, {}!", request.msisdn)),
+        "2" => {
+            let visits = request.session.data.entry("visits".to_string()).or_insert_with(|| "0".to_string());
+            *visits = (visits.parse::<u32>().unwrap_or(0) + 1).to_string();
+            UssdReply::menu(format!("You counted {} time(s)\n2. Count again\n0. Exit", visits))
+        }
+        "0" => UssdReply::end("Goodbye!"),
+        _ => UssdReply::menu("Invalid option\n1. Say hello\n2. Count visits\n0. Exit"),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::Builder::from_default_env().filter_level(log::LevelFilter::Info).init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let addr = args.first().map_or("127.0.0.1:2775", String::as_str);
+    let system_id = args.get(1).map_or("ForwardingClient", String::as_str);
+    let password = args.get(2).map_or("forward123", String::as_str);
+
+    UssdApp::new()
+        .handle("*500#", greeter)
+        .handle("*501#", |request: &mut UssdRequest| UssdReply::end(format!("Your number is {}", request.msisdn)))
+        .connect(addr, system_id, password)
+        .await
+}
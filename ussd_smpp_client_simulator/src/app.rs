@@ -0,0 +1,888 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Result, anyhow};
+use log::{info, debug, error, warn};
+use smpp_codec::{
+    message_payload_room, DeliverSm, OptionalParam, SmppError, SubmitSm, SubmitSmResp, DELIVER_SM, DELIVER_SM_RESP, ENQUIRE_LINK, ENQUIRE_LINK_RESP, ESME_ROK,
+    ESM_CLASS_USSD, GENERIC_NACK, SUBMIT_SM, SUBMIT_SM_RESP, TAG_USER_MESSAGE_REFERENCE, TAG_USSD_SERVICE_OP, UNBIND,
+    UNBIND_RESP, USSD_NOTIFY,
+};
+use tokio::sync::{mpsc, Mutex as AsyncMutex, Notify};
+use tokio::task::JoinHandle;
+use ussd_common::encoding::{self, TextEncoding};
+use ussd_common::run_id;
+
+use crate::chaos::{ChaosInjector, SubmitSmRespAction};
+use crate::config::ClientConfig;
+use crate::reconnect::Backoff;
+use crate::session_store::SessionStore;
+use crate::smpp::{self, SmppClient, SmppPdu, SmppReader};
+use crate::ussd::{UssdMenuManager, UssdSession};
+
+// One request from a subscriber, with the session it belongs to
+pub struct UssdRequest<'a> {
+    pub msisdn: &'a str,
+    pub input: &'a str, // The dialled code on the first request of a session, then each reply
+    pub new_session: bool, // False when session.remember_last_menu resumes a session with its own code
+    pub session: &'a mut UssdSession, // `session.data` keeps values between requests
+}
+
+// A handler's answer: a screen awaiting the subscriber's reply, or a closing notification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UssdReply {
+    Menu(String),
+    End(String), // Sent with ussd_service_op USSD_NOTIFY; the session is closed
+}
+
+impl UssdReply {
+    pub fn menu(text: impl Into<String>) -> Self {
+        UssdReply::Menu(text.into())
+    }
+
+    pub fn end(text: impl Into<String>) -> Self {
+        UssdReply::End(text.into())
+    }
+
+    pub fn text(&self) -> &str {
+        match self {
+            UssdReply::Menu(text) | UssdReply::End(text) => text,
+        }
+    }
+
+    fn map_text(self, f: impl FnOnce(&str) -> String) -> Self {
+        match self {
+            UssdReply::Menu(text) => UssdReply::Menu(f(&text)),
+            UssdReply::End(text) => UssdReply::End(f(&text)),
+        }
+    }
+}
+
+// Application logic for one or more USSD codes. Handlers run on a blocking thread, so they may
+// call out to slow backends; the subscriber gets an error screen after 10 seconds.
+pub trait UssdHandler: Send + Sync {
+    fn handle(&self, request: &mut UssdRequest) -> UssdReply;
+}
+
+impl<F> UssdHandler for F
+where
+    F: Fn(&mut UssdRequest) -> UssdReply + Send + Sync,
+{
+    fn handle(&self, request: &mut UssdRequest) -> UssdReply {
+        self(request)
+    }
+}
+
+// A USSD application on an SMPP bind: the server forwards requests for its codes as SUBMIT_SM
+// and each reply goes back as DELIVER_SM. One task reads the bind and hands each SUBMIT_SM to a
+// pool of `client.workers` workers, and another writes everything they send back, so a slow
+// handler holds up neither reading nor the other subscribers.
+//
+//     UssdApp::new()
+//         .handle("*500#", |request: &mut UssdRequest| UssdReply::end(format!("Hello {}", request.msisdn)))
+//         .connect("127.0.0.1:2775", "ForwardingClient", "forward123")
+//         .await?;
+#[derive(Clone)]
+pub struct UssdApp {
+    config: ClientConfig,
+    handlers: Vec<(String, Arc<dyn UssdHandler>)>, // Keyed by the code that opens a session
+    fallback: Option<Arc<dyn UssdHandler>>, // Codes without a handler, and replies outside a session
+    chaos: ChaosInjector,
+    sessions: Arc<Mutex<HashMap<String, UssdSession>>>,
+    sequence_counter: Arc<Mutex<u32>>,
+    running: Arc<Mutex<bool>>,
+    writer: Arc<Mutex<Option<Outbound>>>, // None while not bound
+    stopped: Arc<Notify>,
+    metrics: Arc<Mutex<SessionMetrics>>,
+    store: Arc<SessionStore>,
+}
+
+// Session counts since the app started
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SessionMetrics {
+    pub active: usize,
+    pub expired: u64,  // Swept after session.timeout_seconds without a request
+    pub notified: u64, // Expired sessions whose subscriber was sent the timeout screen
+}
+
+// The queue into the writer task of the current bind, the task itself and the bind's heartbeat
+struct Outbound {
+    queue: mpsc::Sender<SmppPdu>,
+    task: JoinHandle<()>,
+    heartbeat: Option<JoinHandle<()>>, // Holds a queue of its own, so must be stopped
+}
+
+impl Default for UssdApp {
+    fn default() -> Self {
+        UssdApp::new()
+    }
+}
+
+impl UssdApp {
+    // An app with the default client settings; `connect` supplies the server and credentials
+    pub fn new() -> Self {
+        UssdApp::with_config(ClientConfig::default())
+    }
+
+    // Connection, session timeout, chaos and compression settings come from `config`
+    pub fn with_config(config: ClientConfig) -> Self {
+        let chaos = ChaosInjector::new(config.chaos.clone());
+        let store = Arc::new(SessionStore::open(&config.session.store_path));
+
+        UssdApp {
+            config,
+            handlers: Vec::new(),
+            fallback: None,
+            chaos,
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            sequence_counter: Arc::new(Mutex::new(1)),
+            running: Arc::new(Mutex::new(false)),
+            writer: Arc::new(Mutex::new(None)),
+            stopped: Arc::new(Notify::new()),
+            metrics: Arc::new(Mutex::new(SessionMetrics::default())),
+            store,
+        }
+    }
+
+    // Sessions opened by dialling `code` go to `handler`
+    pub fn handle(mut self, code: &str, handler: impl UssdHandler + 'static) -> Self {
+        self.handlers.push((code.to_string(), Arc::new(handler)));
+        self
+    }
+
+    // Handles codes no `handle` call registered; without one they get the system error screen
+    pub fn fallback(mut self, handler: impl UssdHandler + 'static) -> Self {
+        self.fallback = Some(Arc::new(handler));
+        self
+    }
+
+    // Binds to the SMPP server at `addr` ("host:port") and serves requests until unbound
+    pub async fn connect(mut self, addr: &str, system_id: &str, password: &str) -> Result<()> {
+        let (host, port) = addr.rsplit_once(':').ok_or_else(|| anyhow!("Expected host:port, got {}", addr))?;
+        self.config.client.host = host.to_string();
+        self.config.client.port = port.parse().map_err(|_| anyhow!("Invalid port in {}", addr))?;
+        self.config.client.system_id = system_id.to_string();
+        self.config.client.password = password.to_string();
+        self.start().await
+    }
+
+    // Binds with the configured [client] settings and serves requests until unbound or stopped
+    pub async fn start(&self) -> Result<()> {
+        info!("📡 Connecting to server: {}:{}", self.config.client.host, self.config.client.port);
+        info!("🆔 System ID: {}", self.config.client.system_id);
+        for (code, _) in &self.handlers {
+            info!("📋 Handling {}", code);
+        }
+
+        // Set running state
+        *self.running.lock().unwrap() = true;
+        self.restore_sessions();
+
+        // Connect and bind to SMPP server
+        let reader = self.connect_and_bind().await?;
+
+        // Workers take turns at the queue; the reader waits when every one of them is busy
+        let workers = self.config.client.workers.max(1);
+        let (jobs, queue) = mpsc::channel::<SmppPdu>(workers * 4);
+        let queue = Arc::new(AsyncMutex::new(queue));
+        for _ in 0..workers {
+            let (app, queue) = (self.clone(), Arc::clone(&queue));
+            tokio::spawn(async move {
+                loop {
+                    let Some(pdu) = queue.lock().await.recv().await else {
+                        return;
+                    };
+                    if let Err(e) = app.handle_submit_sm(pdu).await {
+                        error!("❌ Error processing PDU: {}", e);
+                    }
+                }
+            });
+        }
+        info!("👷 {} workers handling SUBMIT_SMs", workers);
+        self.spawn_session_cleanup();
+        self.spawn_session_snapshots();
+
+        // Start message processing loop
+        let result = self.start_message_loop(reader, jobs).await;
+        self.save_sessions();
+        result
+    }
+
+    // Binds, starts the writer task and returns the half to read from
+    async fn connect_and_bind(&self) -> Result<SmppReader> {
+        let mut client = SmppClient::new(
+            &self.config.client.host,
+            self.config.client.port,
+            &self.config.client.system_id,
+            &self.config.client.password,
+        )
+        .with_tls(self.config.client.tls.clone());
+
+        client.connect().await?;
+        client.bind().await?;
+        let (reader, mut writer) = client.split()?;
+
+        let (queue, mut outbound) = mpsc::channel::<SmppPdu>(256);
+        let task = tokio::spawn(async move {
+            while let Some(pdu) = outbound.recv().await {
+                if let Err(e) = writer.send_pdu(pdu).await {
+                    error!("❌ Error writing PDU: {}", e);
+                    return;
+                }
+            }
+        });
+        let heartbeat = (self.config.client.heartbeat_interval > 0).then(|| {
+            let app = self.clone();
+            smpp::spawn_heartbeat(
+                queue.clone(),
+                reader.liveness(),
+                Duration::from_secs(self.config.client.heartbeat_interval),
+                Duration::from_secs(self.config.client.heartbeat_timeout.max(1)),
+                move || app.next_sequence(),
+            )
+        });
+        *self.writer.lock().unwrap() = Some(Outbound { queue, task, heartbeat });
+        info!("✅ Successfully connected and bound to SMPP server");
+
+        Ok(reader)
+    }
+
+    async fn start_message_loop(&self, mut reader: SmppReader, jobs: mpsc::Sender<SmppPdu>) -> Result<()> {
+        info!("👂 Starting message processing loop");
+
+        while *self.running.lock().unwrap() {
+            let liveness = reader.liveness();
+            let result = tokio::select! {
+                result = reader.read_pdu() => result,
+                _ = liveness.lost() => Err(anyhow!("Server stopped answering ENQUIRE_LINK")),
+                _ = self.stopped.notified() => break,
+            };
+            match result {
+                Ok(pdu) => {
+                    if let Err(e) = self.process_pdu(pdu, &jobs).await {
+                        error!("❌ Error processing PDU: {}", e);
+                    }
+                }
+                Err(e) => {
+                    error!("❌ Error reading PDU: {}", e);
+                    let heartbeat = self.writer.lock().unwrap().take().and_then(|outbound| outbound.heartbeat);
+                    if let Some(heartbeat) = heartbeat {
+                        heartbeat.abort();
+                    }
+                    if !self.config.client.auto_reconnect {
+                        break;
+                    }
+                    match self.reconnect().await? {
+                        Some(next) => reader = next,
+                        None => break,
+                    }
+                }
+            }
+        }
+
+        info!("🛑 Message processing loop stopped");
+        Ok(())
+    }
+
+    // Sweeps expired sessions every session.cleanup_interval_seconds while the app runs
+    fn spawn_session_cleanup(&self) {
+        let interval = self.config.session.cleanup_interval_seconds;
+        if interval == 0 {
+            return;
+        }
+        let app = self.clone();
+        tokio::spawn(async move {
+            let manager = UssdMenuManager::new(app.config.clone());
+            while *app.running.lock().unwrap() {
+                tokio::time::sleep(Duration::from_secs(interval)).await;
+                app.expire_sessions(&manager).await;
+            }
+        });
+    }
+
+    // Picks up the sessions session.store_path kept from the last run
+    fn restore_sessions(&self) {
+        let Some(path) = self.store.path() else {
+            return;
+        };
+        match self.store.load(self.config.session.timeout_seconds) {
+            Ok(stored) => {
+                info!("💾 Restored {} session(s) from {}", stored.len(), path);
+                self.sessions.lock().unwrap().extend(stored);
+            }
+            Err(e) => warn!("⚠️  Could not restore sessions from {}: {}", path, e),
+        }
+    }
+
+    // Saves the sessions every session.store_interval_ms while the app runs
+    fn spawn_session_snapshots(&self) {
+        let Some(path) = self.store.path() else {
+            return;
+        };
+        info!("💾 Persisting sessions to {}", path);
+        let app = self.clone();
+        let interval = Duration::from_millis(self.config.session.store_interval_ms.max(1));
+        tokio::spawn(async move {
+            while *app.running.lock().unwrap() {
+                tokio::time::sleep(interval).await;
+                app.save_sessions();
+            }
+        });
+    }
+
+    fn save_sessions(&self) {
+        let sessions = self.sessions.lock().unwrap().clone();
+        if let Err(e) = self.store.save(&sessions) {
+            warn!("⚠️  Could not save sessions to {}: {}", self.store.path().unwrap_or_default(), e);
+        }
+    }
+
+    async fn expire_sessions(&self, manager: &UssdMenuManager) {
+        let expired = manager.cleanup_expired_sessions(&mut self.sessions.lock().unwrap());
+        if expired.is_empty() {
+            return;
+        }
+        let mut notified = 0;
+        if self.config.session.notify_on_timeout {
+            let text = self.config.compression.apply(&self.config.responses.defaults.session_timeout);
+            for session in &expired {
+                match self.send_deliver_sm(&session.msisdn, &text, None, Some(USSD_NOTIFY)).await {
+                    Ok(()) => notified += 1,
+                    Err(e) => warn!("⚠️  Timeout screen for {} not sent: {}", session.msisdn, e),
+                }
+            }
+        }
+        let metrics = {
+            let mut metrics = self.metrics.lock().unwrap();
+            metrics.expired += expired.len() as u64;
+            metrics.notified += notified;
+            *metrics
+        };
+        info!("⌛ Expired {} session(s) after {}s, {} in total", expired.len(), self.config.session.timeout_seconds, metrics.expired);
+    }
+
+    pub fn session_metrics(&self) -> SessionMetrics {
+        SessionMetrics { active: self.sessions.lock().unwrap().len(), ..*self.metrics.lock().unwrap() }
+    }
+
+    // Rebinds with backoff until it works, the app is stopped or [client.reconnect] max_attempts
+    // run out, then keeps the sessions recent enough to carry on. None once stopped.
+    async fn reconnect(&self) -> Result<Option<SmppReader>> {
+        let mut backoff = Backoff::new(self.config.client.reconnect.clone());
+        while *self.running.lock().unwrap() {
+            let Some(delay) = backoff.next_delay() else {
+                return Err(anyhow!("Gave up reconnecting after {} attempts", backoff.attempts()));
+            };
+            warn!("🔄 Reconnecting in {}ms (attempt {})", delay.as_millis(), backoff.attempts());
+            tokio::time::sleep(delay).await;
+            match self.connect_and_bind().await {
+                Ok(reader) => {
+                    self.resume_sessions();
+                    return Ok(Some(reader));
+                }
+                Err(e) => error!("❌ Reconnection failed: {}", e),
+            }
+        }
+        Ok(None)
+    }
+
+    // The server routes a subscriber's next reply to whichever forwarding client is bound, so a
+    // session that was mid-menu when the bind dropped carries on where it was
+    fn resume_sessions(&self) {
+        let window = self.config.client.reconnect.session_resume_seconds;
+        let mut sessions = self.sessions.lock().unwrap();
+        let before = sessions.len();
+        if window == 0 {
+            sessions.clear();
+        } else {
+            sessions.retain(|_, session| !session.is_expired(window));
+        }
+        if before > 0 {
+            info!("♻️  Resuming {} session(s), {} expired during the outage", sessions.len(), before - sessions.len());
+        }
+    }
+
+    // SUBMIT_SMs go to the workers; everything else is quick enough to answer here
+    async fn process_pdu(&self, pdu: SmppPdu, jobs: &mpsc::Sender<SmppPdu>) -> Result<()> {
+        debug!("📥 Received PDU: cmd=0x{:08x}, seq={}", pdu.header.command_id, pdu.header.sequence_number);
+
+        match pdu.header.command_id {
+            SUBMIT_SM => {
+                jobs.send(pdu).await.map_err(|_| anyhow!("Workers have stopped"))?;
+            }
+            DELIVER_SM_RESP => {
+                self.handle_deliver_sm_resp(pdu).await?;
+            }
+            ENQUIRE_LINK => {
+                self.handle_enquire_link(pdu).await?;
+            }
+            ENQUIRE_LINK_RESP => {
+                debug!("💓 Received ENQUIRE_LINK_RESP");
+            }
+            UNBIND => {
+                self.handle_unbind(pdu).await?;
+            }
+            UNBIND_RESP => {
+                debug!("📴 Received UNBIND_RESP");
+            }
+            _ => {
+                warn!("🤷 Unhandled command ID: 0x{:08x}", pdu.header.command_id);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn handle_submit_sm(&self, pdu: SmppPdu) -> Result<()> {
+        info!("📨 Received SUBMIT_SM (forwarded USSD request)");
+
+        // Parse the SUBMIT_SM to extract USSD information. One that does not decode is refused
+        // with GENERIC_NACK so the server does not hold its window slot until the timeout.
+        let submit_sm = match SubmitSm::decode(&pdu.body) {
+            Ok(submit_sm) => submit_sm,
+            Err(e) => {
+                let error = SmppError::from(e);
+                warn!("⚠️  Refusing SUBMIT_SM seq={}: {}", pdu.header.sequence_number, error);
+                let nack = SmppPdu::new(GENERIC_NACK, error.command_status(), pdu.header.sequence_number, Vec::new());
+                return self.send_pdu(nack).await;
+            }
+        };
+        // data_coding 0 is the GSM 7-bit default alphabet and 8 is UCS-2; anything else is taken as UTF-8
+        let ussd_code = encoding::decode(submit_sm.message(), submit_sm.data_coding, false);
+        let msisdn = submit_sm.source_addr.to_string();
+
+        info!("🔄 Processing forwarded USSD request: {} from {}", ussd_code, msisdn);
+
+        // Send SUBMIT_SM_RESP first (unless chaos decides otherwise)
+        match self.chaos.submit_sm_resp_action(pdu.header.sequence_number) {
+            SubmitSmRespAction::Send(sequence_number) => {
+                debug!("📤 Sending SUBMIT_SM_RESP...");
+                self.send_submit_sm_resp(sequence_number).await?;
+            }
+            SubmitSmRespAction::Skip => {}
+        }
+
+        // Run the handler off the runtime so a slow one cannot stall the bind, with a timeout
+        debug!("🔄 Processing USSD request...");
+        let app = self.clone();
+        let (request_msisdn, request_code) = (msisdn.clone(), ussd_code.clone());
+        let response = tokio::time::timeout(
+            Duration::from_secs(10), // 10 second timeout
+            tokio::task::spawn_blocking(move || app.process_ussd_request(&request_msisdn, &request_code))
+        ).await;
+
+        let response = match response {
+            Ok(Ok(response)) => response,
+            Ok(Err(e)) => {
+                error!("❌ Error processing USSD request: {}", e);
+                UssdReply::end("🔧 System temporarily unavailable. Please try again later.")
+            }
+            Err(_) => {
+                error!("⏰ USSD processing timed out");
+                UssdReply::end("⏰ Request timed out. Please try again.")
+            }
+        };
+
+        if let Some(delay) = self.chaos.deliver_sm_delay() {
+            tokio::time::sleep(delay).await;
+        }
+
+        let text = self.config.compression.apply(response.text());
+        let service_op = matches!(response, UssdReply::End(_)).then_some(USSD_NOTIFY);
+
+        // Send response back via DELIVER_SM
+        debug!("📤 Sending DELIVER_SM response...");
+        self.send_deliver_sm(&msisdn, &text, submit_sm.user_message_reference(), service_op).await?;
+
+        debug!("✅ SUBMIT_SM handling completed successfully");
+        Ok(())
+    }
+
+    // Routes the request to the handler of the code that opened the subscriber's session. The
+    // session is taken out of the map while its handler runs, so a slow handler holds up only its
+    // own subscriber.
+    pub fn process_ussd_request(&self, msisdn: &str, ussd_code: &str) -> UssdReply {
+        debug!("🔍 Processing USSD request: {} from {}", ussd_code, msisdn);
+        let timeout = self.config.session.timeout_seconds;
+        
+        let (mut session, new_session) = {
+            // Other subscribers' expired sessions are the cleanup task's, which may tell them
+            let mut sessions = self.sessions.lock().unwrap();
+            if sessions.get(msisdn).is_some_and(|session| session.is_expired(timeout)) {
+                sessions.remove(msisdn);
+            }
+            
+            // Dialling a code starts over, unless session.remember_last_menu keeps the session the
+            // subscriber opened with the same code
+            let dialled = ussd_code.starts_with('*') && ussd_code.ends_with('#');
+            let resumed = dialled
+                && self.config.session.remember_last_menu
+                && sessions.get(msisdn).is_some_and(|session| session.service_code == ussd_code);
+            let new_session = dialled && !resumed;
+            if resumed {
+                debug!("↩️  Resuming {}'s session on {}", msisdn, ussd_code);
+            }
+            let session = match sessions.remove(msisdn) {
+                Some(session) if !new_session => session,
+                _ => {
+                    let mut session = UssdSession::new(msisdn.to_string());
+                    if dialled {
+                        session.service_code = ussd_code.to_string();
+                    }
+                    debug!("📝 Creating new session {} for {} on {}", session.session_id, msisdn, ussd_code);
+                    session
+                }
+            };
+            (session, new_session)
+        };
+        session.inputs.push(ussd_code.to_string());
+
+        let handler = self
+            .handlers
+            .iter()
+            .find(|(code, _)| *code == session.service_code)
+            .map(|(_, handler)| handler)
+            .or(self.fallback.as_ref());
+        let reply = match handler {
+            Some(handler) => handler.handle(&mut UssdRequest { msisdn, input: ussd_code, new_session, session: &mut session }),
+            None => {
+                warn!("❌ No handler for {} (session code {:?})", ussd_code, session.service_code);
+                UssdReply::end(self.config.responses.defaults.system_error.clone())
+            }
+        };
+        let reply = reply.map_text(|text| {
+            let values = BTreeMap::from([("session_id".to_string(), session.session_id.clone())]);
+            self.config.profiles.variables(msisdn, &session.inputs).with_values(values).with_session_data(&session.data).apply(text)
+        });
+        debug!("📤 Generated response: {:?}", reply);
+
+        // A session the subscriber opened again while this handler ran (after its reply timed out)
+        // is newer, so it stays
+        if !matches!(reply, UssdReply::End(_)) {
+            session.update_last_activity();
+            self.sessions.lock().unwrap().entry(msisdn.to_string()).or_insert(session);
+        }
+        reply
+    }
+
+    async fn send_submit_sm_resp(&self, sequence_number: u32) -> Result<()> {
+        debug!("🔄 Generating message ID...");
+        let message_id = self.generate_message_id();
+        debug!("✅ Generated message ID: {}", message_id);
+        
+        debug!("🔄 Building SUBMIT_SM_RESP body...");
+        let body = SubmitSmResp { message_id: message_id.as_str().into() }.encode();
+        debug!("✅ Body built, length: {}", body.len());
+
+        debug!("🔄 Creating SUBMIT_SM_RESP PDU...");
+        let response = SmppPdu::new(SUBMIT_SM_RESP, ESME_ROK, sequence_number, body);
+        debug!("✅ PDU created");
+
+        self.send_pdu(response).await?;
+        info!("📤 Sent SUBMIT_SM_RESP with message_id: {}", message_id);
+
+        debug!("✅ SUBMIT_SM_RESP sending completed");
+        Ok(())
+    }
+
+    async fn send_deliver_sm(&self, msisdn: &str, response_text: &str, user_message_reference: Option<u16>, service_op: Option<u8>) -> Result<()> {
+        debug!("🔄 Building DELIVER_SM PDU...");
+        let seq_num = self.next_sequence();
+
+        // Menus GSM 7-bit cannot carry (Sinhala, Tamil, Arabic...) go as UCS-2; the server
+        // relays them to the subscriber with the same data_coding
+        let data_coding = TextEncoding::Auto.data_coding(response_text);
+        if data_coding == encoding::DATA_CODING_UCS2 {
+            debug!("🔤 Sending as UCS-2 (data_coding 0x08)");
+        }
+        
+        // Responses that do not fit short_message go in the message_payload TLV, cut to what one
+        // PDU can carry. The server exchanges one septet per octet with forwarding clients, so
+        // nothing here packs.
+        let mut optional_params = Vec::new();
+        if let Some(reference) = user_message_reference {
+            optional_params.push(OptionalParam::u16(TAG_USER_MESSAGE_REFERENCE, reference));
+        }
+        if let Some(op) = service_op {
+            optional_params.push(OptionalParam::u8(TAG_USSD_SERVICE_OP, op));
+        }
+
+        // Build DELIVER_SM PDU
+        let mut body = DeliverSm {
+            service_type: "USSD".into(),
+            source_addr_ton: 1,
+            source_addr_npi: 1,
+            source_addr: "FORWARD".into(), // forwarding client
+            dest_addr_ton: 1,
+            dest_addr_npi: 1,
+            destination_addr: msisdn.into(),
+            esm_class: ESM_CLASS_USSD,
+            data_coding, // 0 GSM 7-bit, 8 UCS-2
+            optional_params,
+            ..Default::default()
+        };
+        let encoded = encoding::encode(response_text, data_coding, false);
+        let room = message_payload_room(body.encode().len());
+        body.short_message = if encoded.len() > room {
+            let cut = encoding::encode_within(response_text, data_coding, false, room);
+            warn!("⚠️  Response to {} cut from {} to {} octets to fit one PDU", msisdn, encoded.len(), cut.len());
+            cut
+        } else {
+            encoded
+        }
+        .into();
+        let deliver_sm = SmppPdu::new(DELIVER_SM, ESME_ROK, seq_num, body.encode());
+
+        self.send_pdu(deliver_sm).await?;
+        info!("📤 Sent DELIVER_SM response to {}: {}", msisdn, response_text);
+
+        Ok(())
+    }
+
+    // Queues `pdu` for the writer task of the current bind
+    async fn send_pdu(&self, pdu: SmppPdu) -> Result<()> {
+        let queue = self.writer.lock().unwrap().as_ref().map(|writer| writer.queue.clone());
+        let queue = queue.ok_or_else(smpp::not_connected)?;
+        queue.send(pdu).await.map_err(|_| smpp::not_connected())?;
+        Ok(())
+    }
+
+    async fn handle_deliver_sm_resp(&self, _pdu: SmppPdu) -> Result<()> {
+        debug!("📥 Received DELIVER_SM_RESP");
+        Ok(())
+    }
+
+    async fn handle_enquire_link(&self, pdu: SmppPdu) -> Result<()> {
+        debug!("💓 Received ENQUIRE_LINK");
+
+        self.send_pdu(pdu.ok_response()).await
+    }
+
+    async fn handle_unbind(&self, pdu: SmppPdu) -> Result<()> {
+        info!("📴 Received UNBIND request");
+
+        self.send_pdu(pdu.ok_response()).await?;
+
+        *self.running.lock().unwrap() = false;
+        Ok(())
+    }
+
+    fn next_sequence(&self) -> u32 {
+        let mut sequence = self.sequence_counter.lock().unwrap();
+        *sequence += 1;
+        *sequence
+    }
+
+    fn generate_message_id(&self) -> String {
+        debug!("🔄 Getting timestamp...");
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        debug!("✅ Timestamp: {}", timestamp);
+        
+        debug!("🔒 Acquiring sequence counter lock...");
+        let mut counter = self.sequence_counter.lock().unwrap();
+        *counter += 1;
+        let current_counter = *counter;
+        debug!("✅ Counter incremented to: {}", current_counter);
+        
+        let message_id = run_id::stamp(format!("FCLIENT{}{:04}", timestamp, current_counter));
+        debug!("✅ Generated message ID: {}", message_id);
+        message_id
+    }
+
+    pub async fn stop(&self) -> Result<()> {
+        info!("🛑 Stopping USSD app");
+        *self.running.lock().unwrap() = false;
+
+        // The writer sends whatever is queued, then the UNBIND, and ends once its queue is dropped
+        let writer = self.writer.lock().unwrap().take();
+        if let Some(Outbound { queue, task, heartbeat }) = writer {
+            info!("📴 Disconnecting from SMPP server");
+            if let Some(heartbeat) = heartbeat {
+                heartbeat.abort();
+            }
+            let sequence_number = self.next_sequence();
+            if queue.send(SmppPdu::new(UNBIND, ESME_ROK, sequence_number, Vec::new())).await.is_err() {
+                error!("❌ Error sending unbind: connection already closed");
+            }
+            drop(queue);
+            let _ = tokio::time::timeout(Duration::from_secs(2), task).await;
+        }
+        self.stopped.notify_one();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app() -> UssdApp {
+        UssdApp::new()
+            .handle("*500#", |request: &mut UssdRequest| {
+                if request.new_session {
+                    return UssdReply::menu("Your name?");
+                }
+                request.session.data.insert("name".to_string(), request.input.to_string());
+                UssdReply::end(format!("Hello {}", request.input))
+            })
+            .handle("*600#", |request: &mut UssdRequest| {
+                let visits = request.session.data.entry("visits".to_string()).or_default();
+                visits.push('+');
+                UssdReply::menu(format!("Visits: {}", visits.len()))
+            })
+    }
+
+    // Points the app's writer at a queue the test reads, in place of a bind
+    fn capture_replies(app: &UssdApp) -> mpsc::Receiver<SmppPdu> {
+        let (queue, replies) = mpsc::channel(16);
+        *app.writer.lock().unwrap() = Some(Outbound { queue, task: tokio::spawn(async {}), heartbeat: None });
+        replies
+    }
+
+    fn submit_sm(msisdn: &str, text: &str, sequence_number: u32) -> SmppPdu {
+        let body = SubmitSm { source_addr: msisdn.into(), short_message: text.as_bytes().into(), ..Default::default() }.encode();
+        SmppPdu::new(SUBMIT_SM, ESME_ROK, sequence_number, body)
+    }
+
+    // The DELIVER_SM's destination and text
+    fn delivered(pdu: &SmppPdu) -> (String, String) {
+        assert_eq!(pdu.header.command_id, DELIVER_SM);
+        let deliver_sm = DeliverSm::decode(&pdu.body).unwrap();
+        (deliver_sm.destination_addr.to_string(), encoding::decode(deliver_sm.message(), deliver_sm.data_coding, false))
+    }
+
+    // Runs `handler` on a blocking thread until `release` says otherwise
+    fn blocking(handler: impl UssdHandler + 'static) -> (impl UssdHandler, std::sync::mpsc::Sender<()>) {
+        let (release, released) = std::sync::mpsc::channel::<()>();
+        let released = Mutex::new(released);
+        let blocked = move |request: &mut UssdRequest| {
+            let _ = released.lock().unwrap().recv();
+            handler.handle(request)
+        };
+        (blocked, release)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_a_slow_handler_does_not_hold_up_other_subscribers() {
+        let (slow, release) = blocking(|_: &mut UssdRequest| UssdReply::end("Slow"));
+        let app = UssdApp::new().handle("*1#", slow).handle("*2#", |_: &mut UssdRequest| UssdReply::end("Fast"));
+        let mut replies = capture_replies(&app);
+
+        let first = tokio::spawn({
+            let app = app.clone();
+            async move { app.handle_submit_sm(submit_sm("111", "*1#", 1)).await }
+        });
+        // The SUBMIT_SM_RESP goes out before the handler runs
+        assert_eq!(replies.recv().await.unwrap().header.sequence_number, 1);
+
+        tokio::time::timeout(Duration::from_secs(2), app.handle_submit_sm(submit_sm("222", "*2#", 2)))
+            .await
+            .expect("the second subscriber waited for the first one's handler")
+            .unwrap();
+        assert_eq!(replies.recv().await.unwrap().header.sequence_number, 2);
+        assert_eq!(delivered(&replies.recv().await.unwrap()), ("222".to_string(), "Fast".to_string()));
+
+        release.send(()).unwrap();
+        first.await.unwrap().unwrap();
+        assert_eq!(delivered(&replies.recv().await.unwrap()), ("111".to_string(), "Slow".to_string()));
+    }
+
+    #[test]
+    fn test_requests_follow_the_session_code() {
+        let app = app();
+        assert_eq!(app.process_ussd_request("111", "*500#"), UssdReply::menu("Your name?"));
+        assert_eq!(app.process_ussd_request("222", "*600#"), UssdReply::menu("Visits: 1"));
+        assert_eq!(app.process_ussd_request("111", "Ada"), UssdReply::end("Hello Ada"));
+        assert_eq!(app.process_ussd_request("222", "1"), UssdReply::menu("Visits: 2"));
+
+        // An ending reply closes the session; dialling again starts a fresh one
+        assert!(!app.sessions.lock().unwrap().contains_key("111"));
+        assert_eq!(app.process_ussd_request("222", "*600#"), UssdReply::menu("Visits: 1"));
+    }
+
+    #[test]
+    fn test_unhandled_codes_use_the_fallback() {
+        let app = app();
+        let error = ClientConfig::default().responses.defaults.system_error;
+        assert_eq!(app.process_ussd_request("111", "*999#"), UssdReply::End(error));
+
+        let app = app.fallback(|request: &mut UssdRequest| UssdReply::menu(format!("Fallback {}", request.input)));
+        assert_eq!(app.process_ussd_request("111", "*999#"), UssdReply::menu("Fallback *999#"));
+        assert_eq!(app.process_ussd_request("333", "1"), UssdReply::menu("Fallback 1"));
+    }
+
+    #[test]
+    fn test_recent_sessions_survive_a_rebind() {
+        let app = app();
+        app.process_ussd_request("111", "*500#");
+        app.process_ussd_request("222", "*600#");
+        app.sessions.lock().unwrap().get_mut("222").unwrap().last_activity -= Duration::from_secs(120);
+        app.resume_sessions();
+        assert_eq!(app.process_ussd_request("111", "Ada"), UssdReply::end("Hello Ada"));
+        assert!(!app.sessions.lock().unwrap().contains_key("222"));
+
+        let mut config = ClientConfig::default();
+        config.client.reconnect.session_resume_seconds = 0;
+        let app = UssdApp { config, ..app };
+        app.process_ussd_request("111", "*500#");
+        app.resume_sessions();
+        assert!(app.sessions.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_remember_last_menu_resumes_the_same_code() {
+        let mut config = ClientConfig::default();
+        config.menus.menus.insert("banking".to_string(), toml::from_str("title = \"Banking\"\noptions = []").unwrap());
+        config.menus.menus.get_mut("main").unwrap().options[0] = toml::from_str(
+            "key = \"1\"\ntext = \"Banking\"\naction = \"submenu\"\ntarget = \"banking\"",
+        )
+        .unwrap();
+        config.session.remember_last_menu = true;
+        let app = UssdApp::with_config(config.clone()).fallback(UssdMenuManager::new(config));
+
+        app.process_ussd_request("111", "*999#");
+        assert!(app.process_ussd_request("111", "1").text().starts_with("Banking"));
+        assert!(app.process_ussd_request("111", "*999#").text().starts_with("Banking"));
+        // Another code, or an expired session, starts over
+        assert!(app.process_ussd_request("111", "*998#").text().starts_with("🏠 Main Menu"));
+        app.process_ussd_request("111", "1");
+        app.sessions.lock().unwrap().get_mut("111").unwrap().last_activity -= Duration::from_secs(301);
+        assert!(app.process_ussd_request("111", "*998#").text().starts_with("🏠 Main Menu"));
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_counts_expired_sessions() {
+        let app = app();
+        app.process_ussd_request("111", "*500#");
+        app.process_ussd_request("222", "*600#");
+        app.sessions.lock().unwrap().get_mut("222").unwrap().last_activity -= Duration::from_secs(301);
+
+        app.expire_sessions(&UssdMenuManager::new(app.config.clone())).await;
+        assert_eq!(app.session_metrics(), SessionMetrics { active: 1, expired: 1, notified: 0 });
+        assert!(app.sessions.lock().unwrap().contains_key("111"));
+    }
+
+    #[test]
+    fn test_replies_expand_profile_variables() {
+        let mut config = ClientConfig::default();
+        config.profiles.defaults.insert("balance".to_string(), "$0.00".to_string());
+        config.profiles.msisdns.insert("111".to_string(), [("balance".to_string(), "$9.99".to_string())].into());
+        let app = UssdApp::with_config(config)
+            .handle("*700#", |_: &mut UssdRequest| UssdReply::menu("{{msisdn}} has {{balance}} after {{session.input[0]}}"))
+            .handle("*701#", |request: &mut UssdRequest| {
+                request.session.data.insert("last".to_string(), request.input.to_string());
+                UssdReply::menu("{{session_id}} {{input}} {{data.last}}")
+            });
+        assert_eq!(app.process_ussd_request("111", "*700#"), UssdReply::menu("111 has $9.99 after *700#"));
+        assert_eq!(app.process_ussd_request("222", "*700#"), UssdReply::menu("222 has $0.00 after *700#"));
+
+        app.process_ussd_request("333", "*701#");
+        let session_id = app.sessions.lock().unwrap()["333"].session_id.clone();
+        assert_eq!(app.process_ussd_request("333", "4"), UssdReply::menu(format!("{} 4 4", session_id)));
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use ussd_common::http_client::{self, HttpTarget};
+
+use crate::ussd::UssdSession;
+
+// A REST service "http" menu options call, e.g.
+//   [backends.balance]
+//   url = "http://127.0.0.1:8080/balance"
+//   headers = { Authorization = "Bearer test" }
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BackendConfig {
+    pub url: String, // http://host[:port]/path the session context is POSTed to
+    pub headers: BTreeMap<String, String>, // Extra request headers, e.g. Authorization
+    pub timeout_ms: u64, // Connect, write and read timeout for one request
+}
+
+impl Default for BackendConfig {
+    fn default() -> Self {
+        BackendConfig {
+            url: String::new(),
+            headers: BTreeMap::new(),
+            timeout_ms: 5000,
+        }
+    }
+}
+
+// The JSON body POSTed when an "http" option is picked, and written to "exec" commands that ask
+// for JSON
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct BackendRequest {
+    pub msisdn: String,
+    pub session_id: String,
+    pub service_code: String,
+    pub menu: String, // The menu the option was picked from
+    pub option: String, // The option's key
+    pub inputs: Vec<String>, // The dialled code, then each reply
+    pub data: HashMap<String, String>, // session.data
+}
+
+// What the backend answers with
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct BackendReply {
+    pub text: String, // Shown as it is, after the usual {{placeholders}}
+    #[serde(default)]
+    pub data: HashMap<String, String>, // Merged into session.data
+    #[serde(default)]
+    pub end: bool, // Back to the main menu afterwards, like an "exit" option
+}
+
+impl BackendRequest {
+    // The session as it stands when the option keyed `option` is picked
+    pub fn new(session: &UssdSession, option: &str) -> Self {
+        BackendRequest {
+            msisdn: session.msisdn.clone(),
+            session_id: session.session_id.clone(),
+            service_code: session.service_code.clone(),
+            menu: session.current_menu.clone(),
+            option: option.to_string(),
+            inputs: session.inputs.clone(),
+            data: session.data.clone(),
+        }
+    }
+}
+
+impl BackendConfig {
+    // `setting` names the backend in errors, e.g. "backends.balance.url"
+    pub fn validate(&self, setting: &str) -> Result<(), String> {
+        HttpTarget::parse(&self.url, setting).map(|_| ())
+    }
+
+    pub fn call(&self, request: &BackendRequest) -> Result<BackendReply, String> {
+        let target = HttpTarget::parse(&self.url, "url")?;
+        let body = serde_json::to_string(request).map_err(|e| e.to_string())?;
+        let response = http_client::post_json(&target, &self.headers, &body, Duration::from_millis(self.timeout_ms.max(1)))
+            .map_err(|e| format!("POST to {} failed: {}", target, e))?;
+        serde_json::from_str(&response).map_err(|e| format!("{} answered {:?}: {}", target, response, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ClientConfig, MenuOption};
+    use crate::app::UssdApp;
+    use crate::ussd::UssdMenuManager;
+    use std::net::SocketAddr;
+    use std::sync::mpsc;
+    use std::thread;
+    use ussd_common::test_http;
+
+    // A backend that answers one request with `body` and returns the request it got
+    fn serve_once(body: &str) -> (BackendConfig, thread::JoinHandle<String>) {
+        let (addr, handle) = test_http::serve_once(test_http::json_response(body));
+        (backend_at(addr), handle)
+    }
+
+    fn backend_at(addr: SocketAddr) -> BackendConfig {
+        BackendConfig { url: format!("http://{}/balance", addr), ..Default::default() }
+    }
+
+    // A backend that takes the request, then answers only once `release` is sent to. `accepted`
+    // hears when the request has arrived.
+    fn slow_backend() -> (BackendConfig, mpsc::Receiver<()>, mpsc::Sender<()>) {
+        let (accepted_tx, accepted) = mpsc::channel();
+        let (release, released) = mpsc::channel::<()>();
+        let (addr, _) = test_http::serve_once_after(test_http::json_response(r#"{"text":"Balance: $5"}"#), move || {
+            accepted_tx.send(()).unwrap();
+            let _ = released.recv();
+        });
+        (backend_at(addr), accepted, release)
+    }
+
+    fn config_calling(backend: BackendConfig) -> ClientConfig {
+        let mut config = ClientConfig::default();
+        config.menus.menus.get_mut("main").unwrap().options.push(MenuOption {
+            key: "7".to_string(),
+            text: "Balance".to_string(),
+            action: "http".to_string(),
+            target: "balance".to_string(),
+            input: None,
+            condition: None,
+            set: HashMap::new(),
+        });
+        config.backends.insert("balance".to_string(), backend);
+        config
+    }
+
+    fn menus_calling(backend: BackendConfig) -> UssdMenuManager {
+        UssdMenuManager::new(config_calling(backend))
+    }
+
+    #[test]
+    fn test_http_option_posts_the_session() {
+        let (backend, handle) = serve_once(r#"{"text":"Balance: $5","data":{"balance":"5"}}"#);
+        let manager = menus_calling(backend);
+        let mut session = UssdSession::new("1234567890".to_string());
+        session.service_code = "*123#".to_string();
+        session.inputs = vec!["*123#".to_string(), "7".to_string()];
+        session.data.insert("pin".to_string(), "1111".to_string());
+
+        assert_eq!(manager.process_input(&mut session, "7"), "Balance: $5");
+        assert_eq!(session.data["balance"], "5");
+        let sent = handle.join().unwrap();
+        assert!(sent.starts_with("POST /balance HTTP/1.1\r\n"));
+        let body: BackendRequest = serde_json::from_str(&sent[sent.find("\r\n\r\n").unwrap() + 4..]).unwrap();
+        assert_eq!(body.msisdn, "1234567890");
+        assert_eq!(body.service_code, "*123#");
+        assert_eq!((body.menu.as_str(), body.option.as_str()), ("main", "7"));
+        assert_eq!(body.inputs, ["*123#", "7"]);
+        assert_eq!(body.data["pin"], "1111");
+    }
+
+    #[test]
+    fn test_failed_and_closing_replies() {
+        let (backend, _) = serve_once("not json");
+        let manager = menus_calling(backend);
+        let error = ClientConfig::default().responses.defaults.system_error;
+        assert_eq!(manager.process_input(&mut UssdSession::new("1".to_string()), "7"), error);
+
+        let (backend, _) = serve_once(r#"{"text":"Goodbye","end":true}"#);
+        let manager = menus_calling(backend);
+        let mut session = UssdSession::new("1".to_string());
+        session.data.insert("pin".to_string(), "1111".to_string());
+        assert_eq!(manager.process_input(&mut session, "7"), "Goodbye");
+        assert!(session.data.is_empty());
+
+        assert!(BackendConfig::default().validate("backends.balance.url").unwrap_err().contains("backends.balance.url"));
+    }
+
+    #[test]
+    fn test_a_slow_backend_does_not_hold_up_other_subscribers() {
+        let (backend, accepted, release) = slow_backend();
+        let config = config_calling(backend);
+        let app = UssdApp::with_config(config.clone()).fallback(UssdMenuManager::new(config));
+        app.process_ussd_request("111", "*123#");
+        let waiting = thread::spawn({
+            let app = app.clone();
+            move || app.process_ussd_request("111", "7")
+        });
+        accepted.recv_timeout(Duration::from_secs(2)).unwrap();
+
+        let (reply_tx, reply) = mpsc::channel();
+        thread::spawn({
+            let app = app.clone();
+            move || reply_tx.send(app.process_ussd_request("222", "*123#")).unwrap()
+        });
+        let reply = reply.recv_timeout(Duration::from_secs(2)).expect("the second subscriber waited for the backend");
+        assert!(reply.text().starts_with("🏠 Main Menu"));
+
+        release.send(()).unwrap();
+        assert_eq!(waiting.join().unwrap().text(), "Balance: $5");
+    }
+}
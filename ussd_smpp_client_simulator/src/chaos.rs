@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+use log::warn;
+use rand::Rng;
+
+use crate::config::ChaosConfig;
+
+// What to do with the SUBMIT_SM_RESP for a forwarded request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubmitSmRespAction {
+    Send(u32), // Sequence number to answer with
+    Skip,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChaosInjector {
+    config: ChaosConfig,
+}
+
+impl ChaosInjector {
+    pub fn new(config: ChaosConfig) -> Self {
+        if config.enabled {
+            warn!(
+                "🎲 Chaos enabled: delay={}% ({}-{}ms), skip_resp={}%, wrong_seq={}%",
+                config.delay_percentage,
+                config.delay_min_ms,
+                config.delay_max_ms,
+                config.skip_submit_sm_resp_percentage,
+                config.wrong_sequence_percentage
+            );
+        }
+        ChaosInjector { config }
+    }
+
+    pub fn submit_sm_resp_action(&self, sequence_number: u32) -> SubmitSmRespAction {
+        if self.roll(self.config.skip_submit_sm_resp_percentage) {
+            warn!("🎲 Chaos: skipping SUBMIT_SM_RESP for seq={}", sequence_number);
+            return SubmitSmRespAction::Skip;
+        }
+        if self.roll(self.config.wrong_sequence_percentage) {
+            let wrong = sequence_number.wrapping_add(rand::thread_rng().gen_range(1..=1000));
+            warn!("🎲 Chaos: answering seq={} with wrong seq={}", sequence_number, wrong);
+            return SubmitSmRespAction::Send(wrong);
+        }
+        SubmitSmRespAction::Send(sequence_number)
+    }
+
+    pub fn deliver_sm_delay(&self) -> Option<Duration> {
+        if !self.roll(self.config.delay_percentage) {
+            return None;
+        }
+        let min = self.config.delay_min_ms.min(self.config.delay_max_ms);
+        let delay = Duration::from_millis(rand::thread_rng().gen_range(min..=self.config.delay_max_ms.max(min)));
+        warn!("🎲 Chaos: delaying DELIVER_SM by {}ms", delay.as_millis());
+        Some(delay)
+    }
+
+    fn roll(&self, percentage: f64) -> bool {
+        self.config.enabled && percentage > 0.0 && rand::thread_rng().gen_range(0.0..100.0) < percentage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chaos(enabled: bool, percentage: f64) -> ChaosInjector {
+        ChaosInjector::new(ChaosConfig {
+            enabled,
+            delay_percentage: percentage,
+            delay_min_ms: 10,
+            delay_max_ms: 20,
+            skip_submit_sm_resp_percentage: 0.0,
+            wrong_sequence_percentage: percentage,
+        })
+    }
+
+    #[test]
+    fn test_disabled_chaos_never_fires() {
+        let chaos = chaos(false, 100.0);
+        assert_eq!(chaos.submit_sm_resp_action(7), SubmitSmRespAction::Send(7));
+        assert_eq!(chaos.deliver_sm_delay(), None);
+    }
+
+    #[test]
+    fn test_full_chaos_always_fires() {
+        let chaos = chaos(true, 100.0);
+        assert_ne!(chaos.submit_sm_resp_action(7), SubmitSmRespAction::Send(7));
+        let delay = chaos.deliver_sm_delay().unwrap();
+        assert!(delay >= Duration::from_millis(10) && delay <= Duration::from_millis(20));
+    }
+}
@@ -0,0 +1,130 @@
+// The forwarding client's command line, shared by the ussd_smpp_client_simulator binary and
+// `ussd-sim forwarder`
+use std::ffi::OsString;
+use std::io::Write;
+
+use anyhow::{Result, anyhow};
+use clap::{Arg, Command};
+use log::{info, error};
+use tokio::signal::unix::{signal, SignalKind};
+use ussd_common::run_id;
+use crate::{ClientConfig, LiveMenus, UssdApp};
+
+// `args` as the process got them, program name first
+pub async fn run<I, T>(args: I) -> Result<()>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    // Parse command line arguments first
+    let matches = Command::new("USSD SMPP Client Simulator")
+        .version("1.0")
+        .author("Your Name <your.email@example.com>")
+        .about("A configurable USSD SMPP client simulator for handling custom USSD codes")
+        .arg(
+            Arg::new("config")
+                .short('c')
+                .long("config")
+                .value_name("FILE")
+                .help("Configuration file path")
+                .default_value("client_config.toml")
+        )
+        .arg(
+            Arg::new("debug")
+                .short('d')
+                .long("debug")
+                .help("Enable debug logging")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("chaos")
+                .long("chaos")
+                .help("Enable fault injection configured in the [chaos] section")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("run-id")
+                .long("run-id")
+                .value_name("ID")
+                .help("Namespace for IDs and log lines (default: $USSD_RUN_ID or a random UUID)")
+        )
+        .get_matches_from(args);
+
+    let config_path = matches.get_one::<String>("config").unwrap();
+    let debug = matches.get_flag("debug");
+    let chaos = matches.get_flag("chaos");
+    let run_id = run_id::init(matches.get_one::<String>("run-id").cloned()).map_err(|e| anyhow!(e))?;
+
+    // Load configuration
+    let mut config = ClientConfig::load(config_path)?;
+    let menus = LiveMenus::new(config.clone(), config_path);
+    
+    // Override debug setting from command line
+    if debug {
+        config.logging.debug = true;
+    }
+    if chaos {
+        config.chaos.enabled = true;
+    }
+
+    // Initialize logging based on configuration
+    let log_level = if config.logging.debug {
+        "debug"
+    } else {
+        &config.logging.level
+    };
+    
+    env_logger::Builder::from_default_env()
+        .filter_level(match log_level {
+            "trace" => log::LevelFilter::Trace,
+            "debug" => log::LevelFilter::Debug,
+            "info" => log::LevelFilter::Info,
+            "warn" => log::LevelFilter::Warn,
+            "error" => log::LevelFilter::Error,
+            _ => log::LevelFilter::Info,
+        })
+        .format(move |buf, record| {
+            writeln!(buf, "[{} {} {:<5} {}] {}", run_id, buf.timestamp(), record.level(), record.target(), record.args())
+        })
+        .init();
+
+    info!("🚀 Starting USSD SMPP Client Simulator");
+    info!("🏷️  Run ID: {}", run_id);
+    info!("📄 Using config file: {}", config_path);
+    info!("📊 Log level: {}", log_level);
+
+    // The configured menus answer every code; see the library's UssdApp for custom handlers
+    let menu_manager = menus.get();
+    for code in menu_manager.get_supported_ussd_codes() {
+        let description = menu_manager.get_ussd_code_description(&code).unwrap_or_default();
+        info!("📋 Handling {} {}", code, description);
+    }
+    let app = UssdApp::with_config(config).fallback(menus.clone());
+
+    // SIGHUP re-reads the menus without dropping the bind
+    let mut hangups = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            if let Err(e) = menus.reload() {
+                error!("❌ Reload failed, keeping the running menus: {}", e);
+            }
+        }
+    });
+    
+    // Set up signal handling for graceful shutdown
+    let app_clone = app.clone();
+    tokio::spawn(async move {
+        tokio::signal::ctrl_c().await.expect("Failed to listen for ctrl-c");
+        info!("🛑 Received shutdown signal");
+        if let Err(e) = app_clone.stop().await {
+            error!("❌ Error during shutdown: {}", e);
+        }
+    });
+
+    // Start the application
+    app.start().await?;
+    let metrics = app.session_metrics();
+    info!("📊 Sessions: {} active, {} expired, {} sent the timeout screen", metrics.active, metrics.expired, metrics.notified);
+
+    Ok(())
+}
@@ -1,7 +1,16 @@
 use std::collections::HashMap;
 use std::fs;
-use anyhow::Result;
+use std::path::Path;
+use anyhow::{Result, anyhow, bail};
 use serde::{Deserialize, Serialize};
+use crate::backend::BackendConfig;
+use crate::exec::CommandConfig;
+use ussd_common::compression::CompressionConfig;
+use ussd_common::condition;
+use ussd_common::input::InputRule;
+use ussd_common::templates::TemplatesConfig;
+use ussd_common::tls::TlsClientConfig;
+use ussd_common::variables::ProfilesConfig;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ClientConfig {
@@ -11,6 +20,18 @@ pub struct ClientConfig {
     pub menus: MenuConfigs,
     pub responses: ResponseConfigs,
     pub session: SessionConfig,
+    #[serde(default)]
+    pub chaos: ChaosConfig,
+    #[serde(default)]
+    pub compression: CompressionConfig,
+    #[serde(default)]
+    pub templates: TemplatesConfig,
+    #[serde(default)]
+    pub profiles: ProfilesConfig,
+    #[serde(default)]
+    pub backends: HashMap<String, BackendConfig>, // REST services "http" options call, by name
+    #[serde(default)]
+    pub commands: HashMap<String, CommandConfig>, // Local programs "exec" options run, by name
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -21,7 +42,53 @@ pub struct ClientSettings {
     pub password: String,
     pub bind_type: String,
     pub auto_reconnect: bool,
-    pub heartbeat_interval: u64,
+    pub heartbeat_interval: u64, // Seconds between ENQUIRE_LINKs; 0 sends none
+    #[serde(default = "default_heartbeat_timeout")]
+    pub heartbeat_timeout: u64, // Seconds to hear back before the connection is given up on
+    #[serde(default = "default_workers")]
+    pub workers: usize, // SUBMIT_SMs handled at the same time
+    #[serde(default)]
+    pub tls: TlsClientConfig, // For servers that only accept smpps:// connections
+    #[serde(default)]
+    pub reconnect: ReconnectConfig,
+}
+
+fn default_heartbeat_timeout() -> u64 {
+    5
+}
+
+fn default_workers() -> usize {
+    8
+}
+
+// How a lost bind is retried when auto_reconnect is on, e.g.
+//   [client.reconnect]
+//   initial_delay_ms = 500
+//   max_delay_ms = 30000
+//   max_attempts = 10          # 0 keeps trying until stopped
+//   session_resume_seconds = 60
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ReconnectConfig {
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub multiplier: f64,              // Each failed attempt multiplies the delay by this
+    pub jitter: f64,                  // Each delay varies by up to this fraction either way
+    pub max_attempts: u32,
+    pub session_resume_seconds: u64,  // Sessions idle longer than this when the bind is back are dropped; 0 drops them all
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        ReconnectConfig {
+            initial_delay_ms: 500,
+            max_delay_ms: 30000,
+            multiplier: 2.0,
+            jitter: 0.2,
+            max_attempts: 0,
+            session_resume_seconds: 60,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -41,14 +108,67 @@ pub struct UssdCodeConfig {
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct UssdCodeMapping {
-    pub code: String,
+    pub code: String, // Exact, or with {name} segments such as "*123*{amount}#"
     pub menu: String,
     pub description: String,
 }
 
+impl UssdCodeMapping {
+    // The {name} segments of `code` taken from `dialled`, or None when it does not match
+    pub fn parameters(&self, dialled: &str) -> Option<HashMap<String, String>> {
+        code_parameters(&self.code, dialled)
+    }
+}
+
+// Matches a dialled code against `pattern`, in which each {name} stands for one non-empty segment
+// (anything but * and #). The segments are returned by name; an exact code matches with none.
+pub fn code_parameters(pattern: &str, dialled: &str) -> Option<HashMap<String, String>> {
+    let mut parameters = HashMap::new();
+    let mut pattern = pattern;
+    let mut dialled = dialled;
+    while !pattern.is_empty() {
+        match pattern.strip_prefix('{') {
+            Some(rest) => {
+                let (name, after) = rest.split_once('}')?;
+                let end = dialled.find(['*', '#']).unwrap_or(dialled.len());
+                if end == 0 {
+                    return None;
+                }
+                parameters.insert(name.to_string(), dialled[..end].to_string());
+                dialled = &dialled[end..];
+                pattern = after;
+            }
+            None => {
+                let end = pattern.find('{').unwrap_or(pattern.len());
+                dialled = dialled.strip_prefix(&pattern[..end])?;
+                pattern = &pattern[end..];
+            }
+        }
+    }
+    dialled.is_empty().then_some(parameters)
+}
+
+// Whether a code pattern's {name} segments are closed, named and apart
+fn check_code_pattern(pattern: &str) -> Result<()> {
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        let (name, after) = rest[start + 1..].split_once('}').ok_or_else(|| anyhow!("code {:?} has an unclosed {{", pattern))?;
+        if name.is_empty() || name.contains(['{', '*', '#']) {
+            bail!("code {:?} has a bad parameter name {:?}", pattern, name);
+        }
+        if after.starts_with('{') {
+            bail!("code {:?} has two parameters with nothing between them", pattern);
+        }
+        rest = after;
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct MenuConfigs {
     pub default_menu: String,
+    #[serde(default)]
+    pub pagination: PaginationConfig,
     #[serde(flatten)]
     pub menus: HashMap<String, MenuConfig>,
 }
@@ -57,14 +177,47 @@ pub struct MenuConfigs {
 pub struct MenuConfig {
     pub title: String,
     pub options: Vec<MenuOption>,
+    #[serde(default)]
+    pub page_size: Option<usize>, // Overrides menus.pagination.page_size for this menu
+}
+
+// Long menus split into pages of options, e.g.
+//   [menus.pagination]
+//   page_size = 5
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PaginationConfig {
+    pub page_size: usize, // Options per page; 0 shows every option on one screen
+    pub next_key: String,
+    pub next_text: String,
+    pub prev_key: String,
+    pub prev_text: String,
+}
+
+impl Default for PaginationConfig {
+    fn default() -> Self {
+        PaginationConfig {
+            page_size: 0,
+            next_key: "98".to_string(),
+            next_text: "➡️ Next".to_string(),
+            prev_key: "97".to_string(),
+            prev_text: "⬅️ Prev".to_string(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct MenuOption {
     pub key: String,
     pub text: String,
-    pub action: String, // "submenu", "response", "exit"
-    pub target: String,
+    pub action: String, // "submenu", "response", "input", "set", "http", "exec", "exit"
+    pub target: String, // For "input" and "set", the menu or response shown next; for "http" and "exec", the backend or command
+    #[serde(default)]
+    pub input: Option<InputRule>, // What an "input" option asks for
+    #[serde(default)]
+    pub condition: Option<String>, // Shown and selectable only while this holds
+    #[serde(default)]
+    pub set: HashMap<String, String>, // Session data a "set" option writes
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -87,16 +240,117 @@ pub struct SessionConfig {
     pub timeout_seconds: u64,
     pub max_menu_depth: u32,
     pub enable_back_navigation: bool,
-    pub remember_last_menu: bool,
+    pub remember_last_menu: bool, // Dialling the same code again within timeout_seconds resumes the session where it was
+    #[serde(default = "default_cleanup_interval_seconds")]
+    pub cleanup_interval_seconds: u64, // How often expired sessions are swept; 0 leaves them to their subscriber's next request
+    #[serde(default)]
+    pub notify_on_timeout: bool, // Send the subscriber responses.defaults.session_timeout when a sweep ends their session
+    #[serde(default)]
+    pub store_path: String, // JSON file sessions are saved to and restored from on restart; empty keeps them in memory
+    #[serde(default = "default_store_interval_ms")]
+    pub store_interval_ms: u64, // How often changed sessions are saved to store_path
+}
+
+fn default_cleanup_interval_seconds() -> u64 {
+    30
+}
+
+fn default_store_interval_ms() -> u64 {
+    1000
+}
+
+// Fault injection for exercising the server against a misbehaving application server
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ChaosConfig {
+    pub enabled: bool,
+    pub delay_percentage: f64,                // Chance of delaying a DELIVER_SM response
+    pub delay_min_ms: u64,
+    pub delay_max_ms: u64,
+    pub skip_submit_sm_resp_percentage: f64,  // Chance of never answering a SUBMIT_SM
+    pub wrong_sequence_percentage: f64,       // Chance of answering with the wrong sequence number
+}
+
+impl Default for ChaosConfig {
+    fn default() -> Self {
+        ChaosConfig {
+            enabled: false,
+            delay_percentage: 0.0,
+            delay_min_ms: 500,
+            delay_max_ms: 5000,
+            skip_submit_sm_resp_percentage: 0.0,
+            wrong_sequence_percentage: 0.0,
+        }
+    }
 }
 
 impl ClientConfig {
     pub fn load(path: &str) -> Result<Self> {
         let content = fs::read_to_string(path)?;
-        let config: ClientConfig = toml::from_str(&content)?;
+        let mut config: ClientConfig = toml::from_str(&content)?;
+        config.expand_templates(Path::new(path).parent().unwrap_or(Path::new("")))?;
+        config.check_options()?;
         Ok(config)
     }
 
+    // Code patterns, input rules, conditions, backends and commands are checked here, so a bad
+    // pattern, expression or URL fails at startup rather than mid-session
+    fn check_options(&self) -> Result<()> {
+        for code in self.ussd_codes.codes.iter().map(|mapping| &mapping.code).chain(&self.ussd_codes.handle_codes) {
+            check_code_pattern(code).map_err(|e| anyhow!("ussd_codes: {}", e))?;
+        }
+        for (name, backend) in &self.backends {
+            backend.validate(&format!("backends.{}.url", name)).map_err(|e| anyhow!(e))?;
+        }
+        for (name, command) in &self.commands {
+            command.validate(&format!("commands.{}", name)).map_err(|e| anyhow!(e))?;
+        }
+        for (name, menu) in &self.menus.menus {
+            for option in &menu.options {
+                if let Some(expression) = &option.condition {
+                    condition::check(expression).map_err(|e| anyhow!("menus.{} option {} condition: {}", name, option.key, e))?;
+                }
+                match option.action.as_str() {
+                    "input" => {
+                        let rule = option.input.as_ref().ok_or_else(|| anyhow!("menus.{} option {} needs an input rule", name, option.key))?;
+                        rule.check().map_err(|e| anyhow!("menus.{} option {}: {}", name, option.key, e))?;
+                    }
+                    "set" if option.set.is_empty() => bail!("menus.{} option {} has nothing to set", name, option.key),
+                    "http" if !self.backends.contains_key(&option.target) => {
+                        bail!("menus.{} option {} calls unknown backend {:?}", name, option.key, option.target)
+                    }
+                    "exec" if !self.commands.contains_key(&option.target) => {
+                        bail!("menus.{} option {} runs unknown command {:?}", name, option.key, option.target)
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Inlines {{> name}} template includes in menu titles, option texts and responses
+    fn expand_templates(&mut self, base_dir: &Path) -> Result<()> {
+        let templates = &self.templates;
+        let defaults = &mut self.responses.defaults;
+        templates.expand_all(
+            base_dir,
+            [
+                &mut self.ussd_codes.unrecognized_message,
+                &mut defaults.invalid_option,
+                &mut defaults.session_timeout,
+                &mut defaults.system_error,
+                &mut defaults.exit_message,
+            ]
+            .into_iter()
+            .chain(self.responses.responses.values_mut())
+            .chain(self.menus.menus.values_mut().flat_map(|menu| {
+                std::iter::once(&mut menu.title).chain(menu.options.iter_mut().map(|option| &mut option.text))
+            })),
+        )
+        .map_err(|e| anyhow!(e))
+    }
+
     pub fn save(&self, path: &str) -> Result<()> {
         let content = toml::to_string_pretty(self)?;
         fs::write(path, content)?;
@@ -117,14 +371,21 @@ impl Default for ClientConfig {
                     text: "💰 Services".to_string(),
                     action: "response".to_string(),
                     target: "services".to_string(),
+                    input: None,
+                    condition: None,
+                    set: HashMap::new(),
                 },
                 MenuOption {
                     key: "0".to_string(),
                     text: "❌ Exit".to_string(),
                     action: "exit".to_string(),
                     target: "".to_string(),
+                    input: None,
+                    condition: None,
+                    set: HashMap::new(),
                 },
             ],
+            page_size: None,
         });
 
         let mut responses = HashMap::new();
@@ -139,6 +400,10 @@ impl Default for ClientConfig {
                 bind_type: "transceiver".to_string(),
                 auto_reconnect: true,
                 heartbeat_interval: 30,
+                heartbeat_timeout: default_heartbeat_timeout(),
+                workers: default_workers(),
+                tls: TlsClientConfig::default(),
+                reconnect: ReconnectConfig::default(),
             },
             logging: LoggingConfig {
                 level: "info".to_string(),
@@ -153,6 +418,7 @@ impl Default for ClientConfig {
             },
             menus: MenuConfigs {
                 default_menu: "main".to_string(),
+                pagination: PaginationConfig::default(),
                 menus,
             },
             responses: ResponseConfigs {
@@ -169,7 +435,17 @@ impl Default for ClientConfig {
                 max_menu_depth: 10,
                 enable_back_navigation: true,
                 remember_last_menu: false,
+                cleanup_interval_seconds: default_cleanup_interval_seconds(),
+                store_path: String::new(),
+                store_interval_ms: default_store_interval_ms(),
+                notify_on_timeout: false,
             },
+            chaos: ChaosConfig::default(),
+            compression: CompressionConfig::default(),
+            templates: TemplatesConfig::default(),
+            profiles: ProfilesConfig::default(),
+            backends: HashMap::new(),
+            commands: HashMap::new(),
         }
     }
 }
@@ -0,0 +1,168 @@
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::backend::BackendRequest;
+
+// A local program "exec" menu options run, for services prototyped in any language, e.g.
+//   [commands.weather]
+//   program = "python3"
+//   args = ["scripts/weather.py"]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CommandConfig {
+    pub program: String, // Looked up on PATH unless it contains a /
+    pub args: Vec<String>, // Passed before the session arguments
+    pub json: bool, // Write the session to stdin as JSON instead of passing the MSISDN and inputs as arguments
+    pub timeout_ms: u64, // The program is killed if it has not exited by then
+}
+
+impl Default for CommandConfig {
+    fn default() -> Self {
+        CommandConfig {
+            program: String::new(),
+            args: Vec::new(),
+            json: false,
+            timeout_ms: 5000,
+        }
+    }
+}
+
+impl CommandConfig {
+    // `setting` names the command in errors, e.g. "commands.weather"
+    pub fn validate(&self, setting: &str) -> Result<(), String> {
+        if self.program.trim().is_empty() {
+            return Err(format!("{} has no program", setting));
+        }
+        Ok(())
+    }
+
+    // Runs the program and returns its stdout, trimmed. Without `json` it gets the MSISDN, then
+    // the dialled code and each reply, after `args`. Failing to start, a non-zero exit or running
+    // past the timeout is an error.
+    pub fn run(&self, request: &BackendRequest) -> Result<String, String> {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::inherit());
+        if !self.json {
+            command.arg(&request.msisdn).args(&request.inputs);
+        }
+        let mut child = command.spawn().map_err(|e| format!("{} did not start: {}", self.program, e))?;
+
+        let mut stdin = child.stdin.take();
+        if self.json {
+            let body = serde_json::to_string(request).map_err(|e| e.to_string())?;
+            if let Some(stdin) = stdin.as_mut() {
+                // A program that does not read its input closes the pipe, which is fine
+                let _ = stdin.write_all(body.as_bytes());
+            }
+        }
+        drop(stdin);
+
+        let mut stdout = child.stdout.take().expect("stdout is piped");
+        let reader = thread::spawn(move || {
+            let mut output = Vec::new();
+            stdout.read_to_end(&mut output).map(|_| output)
+        });
+
+        let deadline = Instant::now() + Duration::from_millis(self.timeout_ms.max(1));
+        let status = loop {
+            match child.try_wait().map_err(|e| e.to_string())? {
+                Some(status) => break status,
+                None if Instant::now() >= deadline => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(format!("{} ran past {}ms and was killed", self.program, self.timeout_ms));
+                }
+                None => thread::sleep(Duration::from_millis(10)),
+            }
+        };
+        let output = reader.join().map_err(|_| "stdout reader panicked".to_string())?.map_err(|e| e.to_string())?;
+        if !status.success() {
+            return Err(format!("{} exited with {}", self.program, status));
+        }
+        Ok(String::from_utf8_lossy(&output).trim().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::UssdApp;
+    use crate::config::{ClientConfig, MenuOption};
+    use crate::ussd::{UssdMenuManager, UssdSession};
+    use std::collections::HashMap;
+    use std::sync::mpsc;
+
+    fn command(script: &str, json: bool) -> CommandConfig {
+        CommandConfig {
+            program: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            json,
+            timeout_ms: 2000,
+        }
+    }
+
+    fn request() -> BackendRequest {
+        let mut session = UssdSession::new("1234567890".to_string());
+        session.inputs = vec!["*123#".to_string(), "4".to_string()];
+        BackendRequest::new(&session, "4")
+    }
+
+    #[test]
+    fn test_arguments_and_stdin() {
+        // sh -c puts the first argument after the script in $0
+        let reply = command("echo \"Hi $0, you sent $1 then $2\"", false).run(&request()).unwrap();
+        assert_eq!(reply, "Hi 1234567890, you sent *123# then 4");
+
+        let reply = command("cat", true).run(&request()).unwrap();
+        assert_eq!(serde_json::from_str::<BackendRequest>(&reply).unwrap(), request());
+    }
+
+    #[test]
+    fn test_failures() {
+        assert!(command("exit 3", false).run(&request()).unwrap_err().contains("exited"));
+        let mut slow = command("sleep 5", false);
+        slow.timeout_ms = 100;
+        let started = Instant::now();
+        assert!(slow.run(&request()).unwrap_err().contains("killed"));
+        assert!(started.elapsed() < Duration::from_secs(2));
+        let missing = CommandConfig { program: "/nonexistent/ussd-service".to_string(), ..Default::default() };
+        assert!(missing.run(&request()).unwrap_err().contains("did not start"));
+        assert!(CommandConfig::default().validate("commands.weather").unwrap_err().contains("commands.weather"));
+    }
+
+    #[test]
+    fn test_a_slow_command_does_not_hold_up_other_subscribers() {
+        let mut config = ClientConfig::default();
+        config.menus.menus.get_mut("main").unwrap().options.push(MenuOption {
+            key: "7".to_string(),
+            text: "Weather".to_string(),
+            action: "exec".to_string(),
+            target: "weather".to_string(),
+            input: None,
+            condition: None,
+            set: HashMap::new(),
+        });
+        config.commands.insert("weather".to_string(), command("sleep 2; echo Sunny", false));
+        let app = UssdApp::with_config(config.clone()).fallback(UssdMenuManager::new(config));
+        app.process_ussd_request("111", "*123#");
+        let waiting = thread::spawn({
+            let app = app.clone();
+            move || app.process_ussd_request("111", "7")
+        });
+        // Long enough for the command to have started
+        thread::sleep(Duration::from_millis(200));
+
+        let (reply_tx, reply) = mpsc::channel();
+        thread::spawn({
+            let app = app.clone();
+            move || reply_tx.send(app.process_ussd_request("222", "*123#")).unwrap()
+        });
+        let reply = reply.recv_timeout(Duration::from_secs(1)).expect("the second subscriber waited for the command");
+        assert!(reply.text().starts_with("🏠 Main Menu"));
+        assert_eq!(waiting.join().unwrap().text(), "Sunny");
+    }
+}
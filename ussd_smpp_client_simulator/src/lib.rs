@@ -0,0 +1,18 @@
+// SMPP plumbing for USSD applications: bind to a USSD gateway as a forwarding client and answer
+// the requests it forwards. The simulator binary is one such app, driven by its menu config.
+pub mod app;
+pub mod backend;
+pub mod chaos;
+pub mod cli;
+pub mod config;
+pub mod exec;
+pub mod reconnect;
+pub mod reload;
+pub mod session_store;
+pub mod smpp;
+pub mod ussd;
+
+pub use app::{SessionMetrics, UssdApp, UssdHandler, UssdReply, UssdRequest};
+pub use config::ClientConfig;
+pub use reload::LiveMenus;
+pub use ussd::{UssdMenuManager, UssdSession};
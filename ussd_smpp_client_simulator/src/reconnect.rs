@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::config::ReconnectConfig;
+
+// Delays between attempts to rebind: exponential from initial_delay_ms up to max_delay_ms, with
+// jitter so several clients that lost the same server do not all come back at once
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    config: ReconnectConfig,
+    attempts: u32,
+}
+
+impl Backoff {
+    pub fn new(config: ReconnectConfig) -> Self {
+        Backoff { config, attempts: 0 }
+    }
+
+    // The wait before the next attempt, or None once max_attempts have been made
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if self.config.max_attempts > 0 && self.attempts >= self.config.max_attempts {
+            return None;
+        }
+        let delay = self.base_delay(self.attempts);
+        self.attempts += 1;
+        let jitter = self.config.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return Some(delay);
+        }
+        Some(delay.mul_f64(rand::thread_rng().gen_range(1.0 - jitter..=1.0 + jitter)))
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    fn base_delay(&self, attempt: u32) -> Duration {
+        let delay = self.config.initial_delay_ms as f64 * self.config.multiplier.max(1.0).powi(attempt.min(64) as i32);
+        Duration::from_millis(delay.min(self.config.max_delay_ms as f64) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backoff(jitter: f64, max_attempts: u32) -> Backoff {
+        Backoff::new(ReconnectConfig {
+            initial_delay_ms: 100,
+            max_delay_ms: 1000,
+            multiplier: 2.0,
+            jitter,
+            max_attempts,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_delays_double_up_to_the_cap() {
+        let mut backoff = backoff(0.0, 0);
+        let delays: Vec<u128> = (0..6).filter_map(|_| backoff.next_delay()).map(|delay| delay.as_millis()).collect();
+        assert_eq!(delays, [100, 200, 400, 800, 1000, 1000]);
+        assert_eq!(backoff.attempts(), 6);
+    }
+
+    #[test]
+    fn test_jitter_and_max_attempts() {
+        let mut backoff = backoff(0.5, 3);
+        for expected in [100.0, 200.0, 400.0] {
+            let delay = backoff.next_delay().unwrap().as_millis() as f64;
+            assert!(delay >= expected * 0.5 && delay <= expected * 1.5, "{} for {}", delay, expected);
+        }
+        assert_eq!(backoff.next_delay(), None);
+    }
+}
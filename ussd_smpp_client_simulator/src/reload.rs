@@ -0,0 +1,117 @@
+use std::sync::{Arc, RwLock};
+
+use anyhow::{Result, anyhow};
+use log::{info, warn};
+
+use crate::app::{UssdHandler, UssdReply, UssdRequest};
+use crate::config::ClientConfig;
+use crate::ussd::UssdMenuManager;
+
+// The sections a reload applies; everything else keeps the value it had at startup
+const RELOADED: &[&str] = &["menus", "responses", "ussd_codes", "templates", "backends", "commands"];
+
+// The menu manager requests go to, swapped whole when the config file is re-read. Each input is
+// handled by the menus current when it arrived, so the bind stays up and a session picks up the
+// new menus on its next step. Clones share the same menus.
+#[derive(Clone)]
+pub struct LiveMenus {
+    current: Arc<RwLock<Arc<UssdMenuManager>>>,
+    config: Arc<RwLock<ClientConfig>>,
+    path: String,
+}
+
+impl LiveMenus {
+    // `config` as loaded from `path`, before any command-line overrides, so they do not count
+    // as changes on reload
+    pub fn new(config: ClientConfig, path: &str) -> Self {
+        LiveMenus {
+            current: Arc::new(RwLock::new(Arc::new(UssdMenuManager::new(config.clone())))),
+            config: Arc::new(RwLock::new(config)),
+            path: path.to_string(),
+        }
+    }
+
+    pub fn get(&self) -> Arc<UssdMenuManager> {
+        Arc::clone(&self.current.read().unwrap())
+    }
+
+    // Re-reads [menus], [responses], [ussd_codes], [templates], [backends] and [commands] from the file. A file that does
+    // not load leaves the running menus as they were. Returns the other sections that changed,
+    // which only a restart applies.
+    pub fn reload(&self) -> Result<Vec<String>> {
+        let reloaded = ClientConfig::load(&self.path).map_err(|e| anyhow!("{}: {}", self.path, e))?;
+        let mut config = self.config.write().unwrap();
+        let restart_required = changed(&config, &reloaded)?;
+
+        config.menus = reloaded.menus;
+        config.responses = reloaded.responses;
+        config.ussd_codes = reloaded.ussd_codes;
+        config.templates = reloaded.templates;
+        config.backends = reloaded.backends;
+        config.commands = reloaded.commands;
+        *self.current.write().unwrap() = Arc::new(UssdMenuManager::new(config.clone()));
+        info!("🔄 Reloaded menus from {}", self.path);
+        for section in &restart_required {
+            warn!("⚠️  [{}] changed in {}; restart the client to apply it", section, self.path);
+        }
+        Ok(restart_required)
+    }
+}
+
+impl UssdHandler for LiveMenus {
+    fn handle(&self, request: &mut UssdRequest) -> UssdReply {
+        self.get().handle(request)
+    }
+}
+
+fn changed(running: &ClientConfig, reloaded: &ClientConfig) -> Result<Vec<String>> {
+    let running = toml::Value::try_from(running)?;
+    let reloaded = toml::Value::try_from(reloaded)?;
+    let (Some(running), Some(reloaded)) = (running.as_table(), reloaded.as_table()) else {
+        return Ok(Vec::new());
+    };
+    Ok(running
+        .keys()
+        .chain(reloaded.keys())
+        .filter(|section| !RELOADED.contains(&section.as_str()) && running.get(*section) != reloaded.get(*section))
+        .cloned()
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ussd::UssdSession;
+    use std::fs;
+
+    fn main_menu(menus: &LiveMenus) -> String {
+        menus.get().process_input(&mut UssdSession::new("111".to_string()), "*1#")
+    }
+
+    #[test]
+    fn test_reload_swaps_menus_only() {
+        let path = std::env::temp_dir().join(format!("ussd_client_reload_{}.toml", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let mut config = ClientConfig::default();
+        fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+        let menus = LiveMenus::new(ClientConfig::load(&path).unwrap(), &path);
+        let before = menus.get();
+        assert!(main_menu(&menus).starts_with("🏠 Main Menu"));
+
+        config.menus.menus.get_mut("main").unwrap().title = "New Menu".to_string();
+        config.client.port = 9999;
+        fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+        assert_eq!(menus.reload().unwrap(), ["client"]);
+        assert!(main_menu(&menus).starts_with("New Menu"));
+        assert_eq!(menus.config.read().unwrap().client.port, 2775);
+        // Inputs already being handled keep the menus they started with
+        assert!(before.process_input(&mut UssdSession::new("111".to_string()), "*1#").starts_with("🏠 Main Menu"));
+
+        fs::write(&path, "[menus\n").unwrap();
+        assert!(menus.reload().is_err());
+        assert!(main_menu(&menus).starts_with("New Menu"));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::sync::Mutex;
+
+use crate::ussd::UssdSession;
+
+// Where sessions are kept across restarts: nowhere, or a JSON file named by session.store_path.
+// The whole session is saved, so a restarted client carries on with each subscriber's menu,
+// history and session.data.
+#[derive(Debug)]
+pub struct SessionStore {
+    path: Option<String>,
+    written: Mutex<String>, // Last snapshot saved, so an idle client does not rewrite it
+}
+
+impl SessionStore {
+    // An empty path keeps sessions in memory only
+    pub fn open(path: &str) -> Self {
+        SessionStore {
+            path: (!path.is_empty()).then(|| path.to_string()),
+            written: Mutex::new(String::new()),
+        }
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    // Sessions saved by the previous run that have not been idle for longer than
+    // `timeout_seconds`. A missing file is an empty store.
+    pub fn load(&self, timeout_seconds: u64) -> io::Result<HashMap<String, UssdSession>> {
+        let Some(path) = &self.path else {
+            return Ok(HashMap::new());
+        };
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => return Err(e),
+        };
+        let stored: Vec<UssdSession> = serde_json::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        *self.written.lock().unwrap() = content;
+        Ok(stored
+            .into_iter()
+            .filter(|session| !session.is_expired(timeout_seconds))
+            .map(|session| (session.msisdn.clone(), session))
+            .collect())
+    }
+
+    // Replaces the stored sessions with `sessions`. Returns whether anything was written.
+    pub fn save(&self, sessions: &HashMap<String, UssdSession>) -> io::Result<bool> {
+        let Some(path) = &self.path else {
+            return Ok(false);
+        };
+        let mut snapshot: Vec<&UssdSession> = sessions.values().collect();
+        snapshot.sort_by(|a, b| a.msisdn.cmp(&b.msisdn));
+        let content = serde_json::to_string_pretty(&snapshot)?;
+
+        let mut written = self.written.lock().unwrap();
+        if *written == content {
+            return Ok(false);
+        }
+        // Written aside and renamed over the old file, so a crash mid-write loses nothing
+        let partial = format!("{}.tmp", path);
+        fs::write(&partial, &content)?;
+        fs::rename(&partial, path)?;
+        *written = content;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_sessions_round_trip_and_expire() {
+        let path = std::env::temp_dir().join(format!("ussd_client_sessions_{}.json", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let _ = fs::remove_file(&path);
+        let store = SessionStore::open(&path);
+        assert!(store.load(300).unwrap().is_empty());
+
+        let mut recent = UssdSession::new("111".to_string());
+        recent.navigate_to_menu("banking");
+        recent.data.insert("phone".to_string(), "0771234567".to_string());
+        let mut stale = UssdSession::new("222".to_string());
+        stale.last_activity = SystemTime::now() - Duration::from_secs(600);
+        let sessions = HashMap::from([("111".to_string(), recent), ("222".to_string(), stale)]);
+        assert!(store.save(&sessions).unwrap());
+        assert!(!store.save(&sessions).unwrap());
+
+        let loaded = SessionStore::open(&path).load(300).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded["111"].current_menu, "banking");
+        assert_eq!(loaded["111"].menu_history, ["main"]);
+        assert_eq!(loaded["111"].data["phone"], "0771234567");
+        fs::remove_file(&path).unwrap();
+
+        let memory = SessionStore::open("");
+        assert!(!memory.save(&sessions).unwrap());
+        assert!(memory.load(300).unwrap().is_empty());
+    }
+}
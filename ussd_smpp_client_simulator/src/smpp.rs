@@ -1,29 +1,60 @@
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf, ReadHalf, WriteHalf};
 use tokio::net::TcpStream;
-use anyhow::{Result, anyhow};
-use log::{debug, info, error};
-
-// SMPP Command IDs
-const BIND_TRANSCEIVER: u32 = 0x00000009;
-const BIND_TRANSCEIVER_RESP: u32 = 0x80000009;
-const UNBIND: u32 = 0x00000006;
-const UNBIND_RESP: u32 = 0x80000006;
-
-// SMPP Status Codes
-const ESME_ROK: u32 = 0x00000000;
-
-#[derive(Debug, Clone)]
-pub struct SmppHeader {
-    pub command_length: u32,
-    pub command_id: u32,
-    pub command_status: u32,
-    pub sequence_number: u32,
+use tokio::sync::{mpsc, Notify};
+use tokio::task::JoinHandle;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::client::TlsStream;
+use anyhow::Result;
+use log::{debug, info, error, warn};
+use smpp_codec::{
+    Bind, SmppError, BIND_TRANSCEIVER, BIND_TRANSCEIVER_RESP, ENQUIRE_LINK, ESME_ROK, HEADER_LEN, INTERFACE_VERSION_34, UNBIND,
+};
+use ussd_common::tls::{self, TlsClientConfig};
+
+pub use smpp_codec::{SmppHeader, SmppPdu};
+
+// The connection to the server, over TLS when `[client.tls]` is enabled
+#[derive(Debug)]
+enum SmppStream {
+    Tcp(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
 }
 
-#[derive(Debug, Clone)]
-pub struct SmppPdu {
-    pub header: SmppHeader,
-    pub body: Vec<u8>,
+impl AsyncRead for SmppStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            SmppStream::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            SmppStream::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for SmppStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            SmppStream::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            SmppStream::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            SmppStream::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            SmppStream::Tls(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            SmppStream::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            SmppStream::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -32,7 +63,8 @@ pub struct SmppClient {
     port: u16,
     system_id: String,
     password: String,
-    stream: Option<TcpStream>,
+    tls: TlsClientConfig,
+    stream: Option<SmppStream>,
     sequence_counter: u32,
     bound: bool,
 }
@@ -44,51 +76,54 @@ impl SmppClient {
             port,
             system_id: system_id.to_string(),
             password: password.to_string(),
+            tls: TlsClientConfig::default(),
             stream: None,
             sequence_counter: 1,
             bound: false,
         }
     }
 
+    // Connects over TLS, checking the server certificate against `tls.ca`, when `tls.enabled`
+    pub fn with_tls(mut self, tls: TlsClientConfig) -> Self {
+        self.tls = tls;
+        self
+    }
+
     pub async fn connect(&mut self) -> Result<()> {
         info!("🔌 Connecting to SMPP server at {}:{}", self.host, self.port);
         
         let stream = TcpStream::connect(format!("{}:{}", self.host, self.port)).await?;
         
-        self.stream = Some(stream);
-        info!("✅ Connected to SMPP server");
+        if self.tls.enabled {
+            let connector = TlsConnector::from(tls::client_config(&self.tls)?);
+            let stream = connector.connect(self.tls.server_name(&self.host)?, stream).await?;
+            self.stream = Some(SmppStream::Tls(Box::new(stream)));
+            info!("✅ Connected to SMPP server over TLS");
+        } else {
+            self.stream = Some(SmppStream::Tcp(stream));
+            info!("✅ Connected to SMPP server");
+        }
         
         Ok(())
     }
 
     pub async fn bind(&mut self) -> Result<()> {
         if self.stream.is_none() {
-            return Err(anyhow!("Not connected to server"));
+            return Err(not_connected().into());
         }
 
         info!("🔗 Binding to SMPP server as {}", self.system_id);
 
         // Create bind request
-        let mut body = Vec::new();
-        body.extend_from_slice(self.system_id.as_bytes());
-        body.push(0); // null terminator
-        body.extend_from_slice(self.password.as_bytes());
-        body.push(0); // null terminator
-        body.extend_from_slice(b"SMPP\0"); // system_type
-        body.push(0x34); // interface_version
-        body.push(0x00); // addr_ton
-        body.push(0x00); // addr_npi
-        body.extend_from_slice(b"\0"); // address_range
-
-        let bind_pdu = SmppPdu {
-            header: SmppHeader {
-                command_length: 16 + body.len() as u32,
-                command_id: BIND_TRANSCEIVER,
-                command_status: ESME_ROK,
-                sequence_number: self.get_next_sequence(),
-            },
-            body,
+        let bind = Bind {
+            system_id: self.system_id.as_str().into(),
+            password: self.password.as_str().into(),
+            system_type: "SMPP".into(),
+            interface_version: INTERFACE_VERSION_34,
+            ..Default::default()
         };
+        let body = bind.encode();
+        let bind_pdu = SmppPdu::new(BIND_TRANSCEIVER, ESME_ROK, self.get_next_sequence(), body);
 
         // Send bind request
         self.send_pdu(bind_pdu).await?;
@@ -101,83 +136,38 @@ impl SmppClient {
             info!("✅ Successfully bound to SMPP server");
             Ok(())
         } else {
-            Err(anyhow!("Bind failed with status: 0x{:08x}", response.header.command_status))
+            Err(SmppError::Auth { status: response.header.command_status, system_id: self.system_id.clone() }.into())
         }
     }
 
     pub async fn send_pdu(&mut self, pdu: SmppPdu) -> Result<()> {
-        if let Some(stream) = &mut self.stream {
-            let mut buffer = Vec::new();
-            
-            // Write header
-            buffer.extend_from_slice(&pdu.header.command_length.to_be_bytes());
-            buffer.extend_from_slice(&pdu.header.command_id.to_be_bytes());
-            buffer.extend_from_slice(&pdu.header.command_status.to_be_bytes());
-            buffer.extend_from_slice(&pdu.header.sequence_number.to_be_bytes());
-            
-            // Write body
-            buffer.extend_from_slice(&pdu.body);
-            
-            debug!("📤 Sending PDU: cmd=0x{:08x}, seq={}, len={}", 
-                pdu.header.command_id, pdu.header.sequence_number, buffer.len());
-            
-            stream.write_all(&buffer).await?;
-            stream.flush().await?;
-            
-            Ok(())
-        } else {
-            Err(anyhow!("Not connected to server"))
+        match &mut self.stream {
+            Some(stream) => write_pdu(stream, pdu).await,
+            None => Err(not_connected().into()),
         }
     }
 
     pub async fn read_pdu(&mut self) -> Result<SmppPdu> {
-        if let Some(stream) = &mut self.stream {
-            // Read header
-            let mut header_buf = [0u8; 16];
-            stream.read_exact(&mut header_buf).await?;
-
-            let command_length = u32::from_be_bytes([header_buf[0], header_buf[1], header_buf[2], header_buf[3]]);
-            let command_id = u32::from_be_bytes([header_buf[4], header_buf[5], header_buf[6], header_buf[7]]);
-            let command_status = u32::from_be_bytes([header_buf[8], header_buf[9], header_buf[10], header_buf[11]]);
-            let sequence_number = u32::from_be_bytes([header_buf[12], header_buf[13], header_buf[14], header_buf[15]]);
-
-            let header = SmppHeader {
-                command_length,
-                command_id,
-                command_status,
-                sequence_number,
-            };
-
-            // Read body
-            let body_length = command_length.saturating_sub(16) as usize;
-            let mut body = vec![0u8; body_length];
-            if body_length > 0 {
-                stream.read_exact(&mut body).await?;
-            }
-
-            debug!("📥 Received PDU: cmd=0x{:08x}, seq={}, status=0x{:08x}", 
-                command_id, sequence_number, command_status);
-
-            Ok(SmppPdu { header, body })
-        } else {
-            Err(anyhow!("Not connected to server"))
+        match &mut self.stream {
+            Some(stream) => read_pdu(stream).await,
+            None => Err(not_connected().into()),
         }
     }
 
+    // Hands the bound connection over as halves that can be read and written from different
+    // tasks. Unbinding is then up to whoever holds the writer.
+    pub fn split(mut self) -> Result<(SmppReader, SmppWriter)> {
+        let stream = self.stream.take().ok_or_else(not_connected)?;
+        let (reader, writer) = tokio::io::split(stream);
+        Ok((SmppReader { stream: reader, liveness: Arc::new(Liveness::new()) }, SmppWriter { stream: writer }))
+    }
+
     pub async fn disconnect(&mut self) -> Result<()> {
         if self.bound {
             info!("📴 Disconnecting from SMPP server");
             
             // Send unbind request
-            let unbind_pdu = SmppPdu {
-                header: SmppHeader {
-                    command_length: 16,
-                    command_id: UNBIND,
-                    command_status: ESME_ROK,
-                    sequence_number: self.get_next_sequence(),
-                },
-                body: Vec::new(),
-            };
+            let unbind_pdu = SmppPdu::new(UNBIND, ESME_ROK, self.get_next_sequence(), Vec::new());
 
             if let Err(e) = self.send_pdu(unbind_pdu).await {
                 error!("❌ Error sending unbind: {}", e);
@@ -198,8 +188,123 @@ impl SmppClient {
         self.sequence_counter += 1;
         self.sequence_counter
     }
+}
+
+// The receiving half of a split client
+#[derive(Debug)]
+pub struct SmppReader {
+    stream: ReadHalf<SmppStream>,
+    liveness: Arc<Liveness>,
+}
+
+impl SmppReader {
+    pub async fn read_pdu(&mut self) -> Result<SmppPdu> {
+        let pdu = read_pdu(&mut self.stream).await?;
+        self.liveness.heard();
+        Ok(pdu)
+    }
+
+    // What the heartbeat goes by; shared with it
+    pub fn liveness(&self) -> Arc<Liveness> {
+        Arc::clone(&self.liveness)
+    }
+}
+
+// When the server was last heard from, so the heartbeat can tell a live connection from a
+// half-open one
+#[derive(Debug)]
+pub struct Liveness {
+    last_heard: Mutex<Instant>,
+    lost: Notify,
+}
+
+impl Liveness {
+    fn new() -> Self {
+        Liveness { last_heard: Mutex::new(Instant::now()), lost: Notify::new() }
+    }
 
-    pub fn is_bound(&self) -> bool {
-        self.bound
+    fn heard(&self) {
+        *self.last_heard.lock().unwrap() = Instant::now();
     }
+
+    fn heard_since(&self, instant: Instant) -> bool {
+        *self.last_heard.lock().unwrap() >= instant
+    }
+
+    // Resolves once the heartbeat has given up on the connection
+    pub async fn lost(&self) {
+        self.lost.notified().await
+    }
+}
+
+// Queues an ENQUIRE_LINK every `interval` and gives up on the connection when nothing at all
+// comes back within `timeout`. Runs until then, or until the queue closes.
+pub fn spawn_heartbeat(
+    queue: mpsc::Sender<SmppPdu>,
+    liveness: Arc<Liveness>,
+    interval: Duration,
+    timeout: Duration,
+    mut next_sequence: impl FnMut() -> u32 + Send + 'static,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            let sequence_number = next_sequence();
+            debug!("💓 Sending ENQUIRE_LINK seq={}", sequence_number);
+            let sent_at = Instant::now();
+            if queue.send(SmppPdu::new(ENQUIRE_LINK, ESME_ROK, sequence_number, Vec::new())).await.is_err() {
+                return;
+            }
+            tokio::time::sleep(timeout).await;
+            if !liveness.heard_since(sent_at) {
+                warn!("💔 No answer to ENQUIRE_LINK within {}s", timeout.as_secs());
+                liveness.lost.notify_one();
+                return;
+            }
+        }
+    })
+}
+
+// The sending half of a split client
+#[derive(Debug)]
+pub struct SmppWriter {
+    stream: WriteHalf<SmppStream>,
+}
+
+impl SmppWriter {
+    pub async fn send_pdu(&mut self, pdu: SmppPdu) -> Result<()> {
+        write_pdu(&mut self.stream, pdu).await
+    }
+}
+
+async fn write_pdu(stream: &mut (impl AsyncWrite + Unpin), pdu: SmppPdu) -> Result<()> {
+    let buffer = pdu.to_bytes();
+
+    debug!("📤 Sending PDU: cmd=0x{:08x}, seq={}, len={}",
+        pdu.header.command_id, pdu.header.sequence_number, buffer.len());
+
+    stream.write_all(&buffer).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+async fn read_pdu(stream: &mut (impl AsyncRead + Unpin)) -> Result<SmppPdu> {
+    // Read header
+    let mut header = [0u8; HEADER_LEN];
+    stream.read_exact(&mut header).await?;
+    let header = SmppHeader::decode(&header)?;
+
+    // Read body
+    let mut body = vec![0u8; header.body_len()?];
+    stream.read_exact(&mut body).await?;
+
+    debug!("📥 Received PDU: cmd=0x{:08x}, seq={}, status=0x{:08x}",
+        header.command_id, header.sequence_number, header.command_status);
+
+    Ok(SmppPdu { header, body: body.into() })
+}
+
+// The error for any use of a client that has no stream, or whose stream was taken away
+pub fn not_connected() -> SmppError {
+    SmppError::Io(io::Error::new(io::ErrorKind::NotConnected, "Not connected to server"))
 }
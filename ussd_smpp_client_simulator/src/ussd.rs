@@ -1,17 +1,29 @@
 use std::collections::HashMap;
 use std::time::{SystemTime, UNIX_EPOCH};
 use log::{debug, warn};
-use crate::config::{ClientConfig, MenuOption};
+use serde::{Deserialize, Serialize};
+use crate::app::{UssdHandler, UssdReply, UssdRequest};
+use crate::backend::BackendRequest;
+use crate::config::{code_parameters, ClientConfig, MenuConfig, MenuOption};
+use ussd_common::{condition, dial};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct UssdSession {
     pub msisdn: String,
     pub session_id: String,
+    pub service_code: String, // Code dialled to open the session; empty until one is
     pub current_menu: String,
     pub menu_history: Vec<String>,
     pub last_activity: SystemTime,
     pub menu_depth: u32,
-    pub data: HashMap<String, String>, // For storing user inputs
+    #[serde(default)]
+    pub page: usize, // Page of current_menu showing, from 0, when the menu is paginated
+    #[serde(default)]
+    pub data: HashMap<String, String>, // Values "input" options collected and "set" options wrote
+    #[serde(default)]
+    pub inputs: Vec<String>, // The dialled code, then each reply, for {{session.input[n]}}
+    #[serde(default)]
+    pub awaiting_input: Option<String>, // Key of the "input" option whose prompt is showing
 }
 
 impl UssdSession {
@@ -19,11 +31,15 @@ impl UssdSession {
         UssdSession {
             msisdn,
             session_id: generate_session_id(),
+            service_code: String::new(),
             current_menu: "main".to_string(),
             menu_history: Vec::new(),
             last_activity: SystemTime::now(),
             menu_depth: 0,
+            page: 0,
             data: HashMap::new(),
+            inputs: Vec::new(),
+            awaiting_input: None,
         }
     }
 
@@ -40,6 +56,7 @@ impl UssdSession {
     }
 
     pub fn navigate_to_menu(&mut self, menu_name: &str) {
+        self.page = 0;
         if menu_name != self.current_menu {
             self.menu_history.push(self.current_menu.clone());
             self.current_menu = menu_name.to_string();
@@ -50,6 +67,7 @@ impl UssdSession {
     pub fn go_back(&mut self) -> bool {
         if let Some(previous_menu) = self.menu_history.pop() {
             self.current_menu = previous_menu;
+            self.page = 0;
             self.menu_depth = self.menu_depth.saturating_sub(1);
             true
         } else {
@@ -61,7 +79,9 @@ impl UssdSession {
         self.current_menu = default_menu.to_string();
         self.menu_history.clear();
         self.menu_depth = 0;
+        self.page = 0;
         self.data.clear();
+        self.awaiting_input = None;
     }
 }
 
@@ -78,7 +98,7 @@ impl UssdMenuManager {
     pub fn process_input(&self, session: &mut UssdSession, input: &str) -> String {
         let input = input.trim();
         
-        debug!("🔍 UssdMenuManager::process_input called with input: '{}'", input);
+        debug!("🔍 UssdMenuManager::process_input called by {} with input: '{}'", session.msisdn, input);
         
         // Check for session timeout
         if session.is_expired(self.config.session.timeout_seconds) {
@@ -99,6 +119,10 @@ impl UssdMenuManager {
         // Handle back navigation
         if input == "00" && self.config.session.enable_back_navigation {
             debug!("🔍 Back navigation requested");
+            // Backing out of a prompt returns to the menu that showed it
+            if session.awaiting_input.take().is_some() {
+                return self.show_menu(session, &session.current_menu.clone());
+            }
             if session.go_back() {
                 return self.show_menu(session, &session.current_menu.clone());
             } else {
@@ -106,14 +130,24 @@ impl UssdMenuManager {
             }
         }
 
+        if let Some(key) = session.awaiting_input.clone() {
+            return self.handle_input_value(session, &key, input);
+        }
+
+        if let Some(page) = self.turn_page(session, input) {
+            debug!("📄 Page {} of menu {}", page + 1, session.current_menu);
+            session.page = page;
+            return self.show_menu(session, &session.current_menu.clone());
+        }
+
         debug!("🔍 Looking for menu option in current menu: {}", session.current_menu);
 
         // Get current menu
         let current_menu_name = session.current_menu.clone();
         if let Some(menu) = self.config.menus.menus.get(&current_menu_name) {
             debug!("✅ Found menu: {}", current_menu_name);
-            // Find matching option
-            if let Some(option) = menu.options.iter().find(|opt| opt.key == input) {
+            // Find matching option; one whose condition fails is not there
+            if let Some(option) = menu.options.iter().find(|opt| opt.key == input && self.is_visible(session, opt)) {
                 debug!("✅ Found matching option: {} -> {}", option.key, option.action);
                 return self.handle_menu_option(session, option);
             } else {
@@ -160,25 +194,177 @@ impl UssdMenuManager {
                     self.config.responses.defaults.system_error.clone()
                 }
             }
+            "input" => {
+                // Prompt for a value; the next reply is checked against the rule
+                let Some(rule) = &option.input else {
+                    warn!("❌ Input option {} has no input rule", option.key);
+                    return self.config.responses.defaults.system_error.clone();
+                };
+                session.awaiting_input = Some(option.key.clone());
+                rule.prompt.clone()
+            }
+            "set" => {
+                // Write the session data, then carry on to the target
+                debug!("✅ Set {:?}", option.set);
+                session.data.extend(option.set.clone());
+                self.go_to_target(session, &option.target)
+            }
+            "http" => self.call_backend(session, option),
+            "exec" => self.run_command(session, option),
             "exit" => {
                 // Exit session
                 session.reset_to_main(&self.config.menus.default_menu);
                 self.config.responses.defaults.exit_message.clone()
             }
-            _ => {
+            _ => { // "forward" and anything unrecognised
                 warn!("❌ Unknown action: {}", option.action);
                 self.config.responses.defaults.system_error.clone()
             }
         }
     }
 
+    // Sends the session to the option's backend and shows what it answers. The subscriber stays on
+    // the menu the option is in unless the reply ends the session.
+    fn call_backend(&self, session: &mut UssdSession, option: &MenuOption) -> String {
+        let Some(backend) = self.config.backends.get(&option.target) else {
+            warn!("❌ Backend '{}' not found", option.target);
+            return self.config.responses.defaults.system_error.clone();
+        };
+        match backend.call(&BackendRequest::new(session, &option.key)) {
+            Ok(reply) => {
+                debug!("✅ Backend {} answered {:?}", option.target, reply);
+                if reply.end {
+                    session.reset_to_main(&self.config.menus.default_menu);
+                } else {
+                    session.data.extend(reply.data);
+                }
+                reply.text
+            }
+            Err(e) => {
+                warn!("❌ Backend {}: {}", option.target, e);
+                self.config.responses.defaults.system_error.clone()
+            }
+        }
+    }
+
+    // Runs the option's command and shows what it prints. The subscriber stays on the menu the
+    // option is in.
+    fn run_command(&self, session: &mut UssdSession, option: &MenuOption) -> String {
+        let Some(command) = self.config.commands.get(&option.target) else {
+            warn!("❌ Command '{}' not found", option.target);
+            return self.config.responses.defaults.system_error.clone();
+        };
+        match command.run(&BackendRequest::new(session, &option.key)) {
+            Ok(text) => {
+                debug!("✅ Command {} printed {:?}", option.target, text);
+                text
+            }
+            Err(e) => {
+                warn!("❌ Command {}: {}", option.target, e);
+                self.config.responses.defaults.system_error.clone()
+            }
+        }
+    }
+
+    // The reply to an "input" prompt: stored in session.data when the rule accepts it, then the
+    // option's target menu or response
+    fn handle_input_value(&self, session: &mut UssdSession, key: &str, value: &str) -> String {
+        let rule = self.config.menus.menus.get(&session.current_menu)
+            .and_then(|menu| menu.options.iter().find(|option| option.key == key && option.input.is_some() && self.is_visible(session, option)))
+            .and_then(|option| Some((option.target.clone(), option.input.as_ref()?)));
+        let Some((target, rule)) = rule else {
+            session.awaiting_input = None;
+            return self.config.responses.defaults.system_error.clone();
+        };
+        if !rule.accepts(value) {
+            debug!("❌ Input {:?} refused for {}", value, rule.name);
+            return rule.retry_prompt(&self.config.responses.defaults.invalid_option);
+        }
+
+        debug!("✅ Input {} = {:?}", rule.name, value);
+        session.awaiting_input = None;
+        session.data.insert(rule.name.clone(), value.to_string());
+        self.go_to_target(session, &target)
+    }
+
+    // Where "input" and "set" options lead: into the target menu, or to the target response
+    fn go_to_target(&self, session: &mut UssdSession, target: &str) -> String {
+        if self.config.menus.menus.contains_key(target) {
+            session.navigate_to_menu(target);
+            return self.show_menu(session, target);
+        }
+        match self.config.responses.responses.get(target) {
+            Some(response) => response.clone(),
+            None => {
+                warn!("❌ Option target '{}' not found", target);
+                self.config.responses.defaults.system_error.clone()
+            }
+        }
+    }
+
+    // Whether an option's condition holds: `data.<name>` reads session.data, other names the
+    // same values as {{name}} placeholders
+    fn is_visible(&self, session: &UssdSession, option: &MenuOption) -> bool {
+        let Some(expression) = &option.condition else {
+            return true;
+        };
+        let variables = self.config.profiles.variables(&session.msisdn, &session.inputs);
+        condition::holds(expression, |name| match name.strip_prefix("data.") {
+            Some(key) => session.data.get(key).cloned(),
+            None => variables.get(name),
+        })
+    }
+
+    // Options per page of the menu, or None when it fits on one screen
+    fn page_size(&self, menu: &MenuConfig) -> Option<usize> {
+        Some(menu.page_size.unwrap_or(self.config.menus.pagination.page_size)).filter(|size| *size > 0)
+    }
+
+    // The page the next or previous key leads to from the current one, if the screen offers it.
+    // Option keys that collide with them win.
+    fn turn_page(&self, session: &UssdSession, input: &str) -> Option<usize> {
+        let pagination = &self.config.menus.pagination;
+        let menu = self.config.menus.menus.get(&session.current_menu)?;
+        let size = self.page_size(menu)?;
+        if menu.options.iter().any(|option| option.key == input && self.is_visible(session, option)) {
+            return None;
+        }
+        let visible = menu.options.iter().filter(|option| self.is_visible(session, option)).count();
+        let last = visible.saturating_sub(1) / size;
+        let page = session.page.min(last);
+        if input == pagination.next_key && page < last {
+            Some(page + 1)
+        } else if input == pagination.prev_key && page > 0 {
+            Some(page - 1)
+        } else {
+            None
+        }
+    }
+
     fn show_menu(&self, session: &UssdSession, menu_name: &str) -> String {
         if let Some(menu) = self.config.menus.menus.get(menu_name) {
             let mut response = format!("{}\n\n", menu.title);
-            
-            for option in &menu.options {
+
+            let visible: Vec<&MenuOption> = menu.options.iter().filter(|option| self.is_visible(session, option)).collect();
+            let (shown, has_prev, has_next) = match self.page_size(menu) {
+                Some(size) => {
+                    // Conditions can hide options after the subscriber paged on, so stay in range
+                    let page = session.page.min(visible.len().saturating_sub(1) / size);
+                    let start = page * size;
+                    (&visible[start..visible.len().min(start + size)], page > 0, start + size < visible.len())
+                }
+                None => (&visible[..], false, false),
+            };
+            for option in shown {
                 response.push_str(&format!("{}. {}\n", option.key, option.text));
             }
+            let pagination = &self.config.menus.pagination;
+            if has_prev {
+                response.push_str(&format!("{}. {}\n", pagination.prev_key, pagination.prev_text));
+            }
+            if has_next {
+                response.push_str(&format!("{}. {}\n", pagination.next_key, pagination.next_text));
+            }
 
             // Add navigation help
             if self.config.session.enable_back_navigation && session.menu_depth > 0 {
@@ -192,11 +378,26 @@ impl UssdMenuManager {
         }
     }
 
-    pub fn get_welcome_message(&self) -> String {
-        self.show_menu(&UssdSession::new("temp".to_string()), &self.config.menus.default_menu)
+    // The screen a returning subscriber left off at: the prompt they had not answered, or the
+    // page of the menu they were on
+    pub fn resume(&self, session: &mut UssdSession) -> String {
+        debug!("↩️  Resuming session {} at menu {}", session.session_id, session.current_menu);
+        let prompt = session.awaiting_input.as_ref().and_then(|key| {
+            let menu = self.config.menus.menus.get(&session.current_menu)?;
+            let option = menu.options.iter().find(|option| &option.key == key && self.is_visible(session, option))?;
+            Some(option.input.as_ref()?.prompt.clone())
+        });
+        match prompt {
+            Some(prompt) => prompt,
+            None => {
+                session.awaiting_input = None;
+                self.show_menu(session, &session.current_menu.clone())
+            }
+        }
     }
 
-    pub fn cleanup_expired_sessions(&self, sessions: &mut HashMap<String, UssdSession>) {
+    // Removes the sessions idle for longer than session.timeout_seconds and returns them
+    pub fn cleanup_expired_sessions(&self, sessions: &mut HashMap<String, UssdSession>) -> Vec<UssdSession> {
         let timeout = self.config.session.timeout_seconds;
         let expired_keys: Vec<String> = sessions
             .iter()
@@ -204,28 +405,45 @@ impl UssdMenuManager {
             .map(|(key, _)| key.clone())
             .collect();
 
+        let mut expired = Vec::new();
         for key in expired_keys {
-            sessions.remove(&key);
-            debug!("🗑️ Removed expired session: {}", key);
+            if let Some(session) = sessions.remove(&key) {
+                debug!("🗑️ Removed expired session: {}", key);
+                expired.push(session);
+            }
         }
+        expired
     }
 
     fn handle_ussd_code(&self, session: &mut UssdSession, ussd_code: &str) -> String {
         debug!("🔍 Handling USSD code: {}", ussd_code);
+
+        // A mapped code with menu keys dialled after it, such as *123*2*1#, opens the menu and
+        // picks each key in turn; only the last screen is shown
+        if let Some((code, keys)) = self.dialled_keys(ussd_code) {
+            debug!("🔗 {} opens {} with keys {:?}", ussd_code, code, keys);
+            let mut screen = self.handle_ussd_code(session, code);
+            for key in keys {
+                screen = self.process_input(session, key);
+            }
+            return screen;
+        }
         
         // Check if this client should handle this USSD code
-        if !self.config.ussd_codes.handle_codes.is_empty() {
-            if !self.config.ussd_codes.handle_codes.contains(&ussd_code.to_string()) {
-                debug!("🚫 USSD code {} not in handle_codes list", ussd_code);
-                return self.handle_unrecognized_code(ussd_code);
-            }
+        if !self.config.ussd_codes.handle_codes.is_empty()
+            && !self.config.ussd_codes.handle_codes.iter().any(|code| code_parameters(code, ussd_code).is_some())
+        {
+            debug!("🚫 USSD code {} not in handle_codes list", ussd_code);
+            return self.handle_unrecognized_code(ussd_code);
         }
 
-        // Look for specific mapping for this USSD code
+        // Look for specific mapping for this USSD code; the first that matches wins, and its
+        // {name} segments go into session.data
         for mapping in &self.config.ussd_codes.codes {
-            if mapping.code == ussd_code {
-                debug!("✅ Found mapping for USSD code {} -> menu {}", ussd_code, mapping.menu);
+            if let Some(parameters) = mapping.parameters(ussd_code) {
+                debug!("✅ Found mapping for USSD code {} -> menu {} with {:?}", ussd_code, mapping.menu, parameters);
                 session.reset_to_main(&mapping.menu);
+                session.data.extend(parameters);
                 return self.show_menu(session, &mapping.menu);
             }
         }
@@ -237,6 +455,20 @@ impl UssdMenuManager {
         self.show_menu(session, default_menu)
     }
 
+    // The mapped code `dialled` extends and the keys after it, unless a mapping matches the whole
+    // of `dialled`
+    fn dialled_keys<'a>(&'a self, dialled: &'a str) -> Option<(&'a str, Vec<&'a str>)> {
+        let codes = &self.config.ussd_codes.codes;
+        if codes.iter().any(|mapping| mapping.parameters(dialled).is_some()) {
+            return None;
+        }
+        codes
+            .iter()
+            .filter(|mapping| !mapping.code.contains('{'))
+            .find_map(|mapping| Some((mapping.code.as_str(), dial::steps(&mapping.code, dialled)?)))
+            .filter(|(_, keys)| !keys.is_empty())
+    }
+
     fn handle_unrecognized_code(&self, ussd_code: &str) -> String {
         debug!("🚫 Handling unrecognized USSD code: {}", ussd_code);
         
@@ -249,7 +481,7 @@ impl UssdMenuManager {
                 format!("⚠️ USSD code {} redirected to main menu.\n\n{}", 
                     ussd_code, self.config.ussd_codes.unrecognized_message)
             }
-            "forward" | _ => {
+            _ => { // "forward" and anything unrecognised
                 // In a real implementation, this would forward to the actual USSD gateway
                 // For now, we'll show a message
                 format!("🔄 USSD code {} forwarded to network.\n\n{}", 
@@ -273,19 +505,29 @@ impl UssdMenuManager {
     }
 }
 
+// The configured menus as an app handler, for every code the [ussd_codes] section maps
+impl UssdHandler for UssdMenuManager {
+    fn handle(&self, request: &mut UssdRequest) -> UssdReply {
+        // The same code dialled again into a session that session.remember_last_menu kept
+        if !request.new_session && request.input == request.session.service_code {
+            return UssdReply::menu(self.resume(request.session));
+        }
+        UssdReply::menu(self.process_input(request.session, request.input))
+    }
+}
+
 fn generate_session_id() -> String {
     let timestamp = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
         .as_secs();
     
-    format!("USSD{}", timestamp)
+    ussd_common::run_id::stamp(format!("USSD{}", timestamp))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::ClientConfig;
 
     #[test]
     fn test_session_creation() {
@@ -310,6 +552,140 @@ mod tests {
         assert_eq!(session.menu_depth, 0);
     }
 
+    #[test]
+    fn test_input_option_collects_a_value() {
+        let mut config = ClientConfig::default();
+        config.menus.menus.get_mut("main").unwrap().options.push(MenuOption {
+            key: "2".to_string(),
+            text: "Send money".to_string(),
+            action: "input".to_string(),
+            target: "sent".to_string(),
+            input: Some(toml::from_str("name = \"phone\"\nprompt = \"Number?\"\npattern = \"[0-9]{10}\"").unwrap()),
+            condition: None,
+            set: HashMap::new(),
+        });
+        config.responses.responses.insert("sent".to_string(), "Sent to {{session.data.phone}}".to_string());
+        let invalid = config.responses.defaults.invalid_option.clone();
+        let manager = UssdMenuManager::new(config);
+        let mut session = UssdSession::new("1234567890".to_string());
+
+        assert_eq!(manager.process_input(&mut session, "2"), "Number?");
+        assert_eq!(manager.process_input(&mut session, "12345"), format!("{}\nNumber?", invalid));
+        assert_eq!(manager.process_input(&mut session, "0771234567"), "Sent to {{session.data.phone}}");
+        assert_eq!(session.data["phone"], "0771234567");
+        assert_eq!(session.awaiting_input, None);
+    }
+
+    #[test]
+    fn test_conditions_and_set_options_branch_on_session_data() {
+        let mut config = ClientConfig::default();
+        let main = toml::from_str::<crate::config::MenuConfig>(
+            r#"
+            title = "Account"
+            options = [
+                { key = "1", text = "Confirm", action = "set", target = "confirmed", set = { confirmed = "true" }, condition = "data.confirmed != 'true'" },
+                { key = "1", text = "Transfer", action = "response", target = "services", condition = "data.confirmed == 'true'" },
+            ]
+            "#,
+        )
+        .unwrap();
+        config.menus.menus.insert("main".to_string(), main);
+        config.responses.responses.insert("confirmed".to_string(), "Confirmed".to_string());
+        let services = config.responses.responses["services"].clone();
+        let manager = UssdMenuManager::new(config);
+        let mut session = UssdSession::new("1234567890".to_string());
+
+        assert_eq!(manager.show_menu(&session, "main"), "Account\n\n1. Confirm\n");
+        assert_eq!(manager.process_input(&mut session, "1"), "Confirmed");
+        assert_eq!(session.data["confirmed"], "true");
+        // The same key now picks the other branch
+        assert_eq!(manager.show_menu(&session, "main"), "Account\n\n1. Transfer\n");
+        assert_eq!(manager.process_input(&mut session, "1"), services);
+    }
+
+    #[test]
+    fn test_long_menus_are_paged() {
+        let mut config = ClientConfig::default();
+        config.menus.pagination.page_size = 2;
+        let main = toml::from_str::<crate::config::MenuConfig>(
+            r#"
+            title = "Bundles"
+            options = [
+                { key = "1", text = "Daily", action = "response", target = "services" },
+                { key = "2", text = "Weekly", action = "response", target = "services" },
+                { key = "3", text = "Monthly", action = "response", target = "services" },
+                { key = "97", text = "Help", action = "response", target = "services", condition = "data.help == 'on'" },
+            ]
+            "#,
+        )
+        .unwrap();
+        config.menus.menus.insert("main".to_string(), main);
+        let services = config.responses.responses["services"].clone();
+        let manager = UssdMenuManager::new(config);
+        let mut session = UssdSession::new("1234567890".to_string());
+
+        assert_eq!(manager.show_menu(&session, "main"), "Bundles\n\n1. Daily\n2. Weekly\n98. ➡️ Next\n");
+        assert_eq!(manager.process_input(&mut session, "97"), format!("{}\n\n{}", "❌ Invalid option. Please try again.", manager.show_menu(&session, "main")));
+        assert_eq!(manager.process_input(&mut session, "98"), "Bundles\n\n3. Monthly\n97. ⬅️ Prev\n");
+        assert_eq!(session.page, 1);
+        assert_eq!(manager.process_input(&mut session, "98"), format!("{}\n\n{}", "❌ Invalid option. Please try again.", manager.show_menu(&session, "main")));
+        // Options on other pages can still be picked by key
+        assert_eq!(manager.process_input(&mut session, "1"), services);
+        assert_eq!(manager.process_input(&mut session, "97"), "Bundles\n\n1. Daily\n2. Weekly\n98. ➡️ Next\n");
+
+        // An option with the same key as a paging key is picked instead
+        session.data.insert("help".to_string(), "on".to_string());
+        session.page = 1;
+        assert_eq!(manager.process_input(&mut session, "97"), services);
+        session.navigate_to_menu("main");
+        assert_eq!(session.page, 0);
+    }
+
+    #[test]
+    fn test_code_patterns_capture_parameters() {
+        assert_eq!(code_parameters("*123#", "*123#"), Some(HashMap::new()));
+        assert_eq!(code_parameters("*123#", "*1234#"), None);
+        let parameters = code_parameters("*123*{amount}*{to}#", "*123*50*0771234567#").unwrap();
+        assert_eq!((parameters["amount"].as_str(), parameters["to"].as_str()), ("50", "0771234567"));
+        assert_eq!(code_parameters("*123*{amount}#", "*123**#"), None);
+        assert_eq!(code_parameters("*123*{amount}#", "*123*50*1#"), None);
+
+        let mut config = ClientConfig::default();
+        config.ussd_codes.codes = vec![toml::from_str("code = \"*123*{amount}#\"\nmenu = \"main\"\ndescription = \"Top up\"").unwrap()];
+        config.ussd_codes.handle_codes = vec!["*123*{amount}#".to_string()];
+        let manager = UssdMenuManager::new(config);
+        let mut session = UssdSession::new("1234567890".to_string());
+        assert!(manager.process_input(&mut session, "*123*75#").starts_with("🏠 Main Menu"));
+        assert_eq!(session.data["amount"], "75");
+        assert!(manager.process_input(&mut session, "*124*75#").contains("Unrecognized USSD code"));
+    }
+
+    #[test]
+    fn test_dialled_keys_walk_into_the_menu() {
+        let mut config = ClientConfig::default();
+        config.ussd_codes.codes = vec![toml::from_str("code = \"*123#\"\nmenu = \"main\"\ndescription = \"Main\"").unwrap()];
+        config.menus.menus.insert("banking".to_string(), toml::from_str(
+            r#"
+            title = "Banking"
+            options = [{ key = "1", text = "Balance", action = "response", target = "services" }]
+            "#,
+        ).unwrap());
+        config.menus.menus.get_mut("main").unwrap().options[0] = toml::from_str(
+            "key = \"1\"\ntext = \"Banking\"\naction = \"submenu\"\ntarget = \"banking\"",
+        )
+        .unwrap();
+        let services = config.responses.responses["services"].clone();
+        let manager = UssdMenuManager::new(config);
+
+        let mut session = UssdSession::new("1234567890".to_string());
+        assert!(manager.process_input(&mut session, "*123*1#").starts_with("Banking"));
+        assert_eq!(session.current_menu, "banking");
+        let mut session = UssdSession::new("1234567890".to_string());
+        assert_eq!(manager.process_input(&mut session, "*123*1*1#"), services);
+        // Back navigation works from where the keys led
+        assert!(manager.process_input(&mut session, "00").starts_with("🏠 Main Menu"));
+    }
+
     #[test]
     fn test_session_timeout() {
         let mut session = UssdSession::new("1234567890".to_string());
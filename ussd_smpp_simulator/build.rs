@@ -0,0 +1,14 @@
+// Generates the gRPC control service from proto/control.proto when the grpc feature is on
+fn main() {
+    println!("cargo:rerun-if-changed=proto/control.proto");
+    #[cfg(feature = "grpc")]
+    {
+        // The bundled protoc, so the build needs nothing installed unless PROTOC points elsewhere
+        if std::env::var_os("PROTOC").is_none() {
+            let protoc = protoc_bin_vendored::protoc_bin_path().expect("no bundled protoc for this platform");
+            // SAFETY: the build script is single-threaded
+            unsafe { std::env::set_var("PROTOC", protoc) };
+        }
+        tonic_build::compile_protos("proto/control.proto").expect("could not compile proto/control.proto");
+    }
+}
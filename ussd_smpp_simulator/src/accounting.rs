@@ -0,0 +1,367 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::time::{Duration, UNIX_EPOCH};
+
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+
+use crate::persistence::{MessageIdRecord, StateStore};
+use crate::smpp_time::utc_parts;
+
+// Layout of exported accounting records: one line per message_id in the persisted registry
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AccountingConfig {
+    pub format: ExportFormat,
+    pub delimiter: String, // Between CSV fields; ignored for fixed_width
+    pub header: bool,      // Write the field names as the first line
+    pub fields: Vec<FieldMapping>,
+}
+
+impl Default for AccountingConfig {
+    fn default() -> Self {
+        AccountingConfig {
+            format: ExportFormat::Csv,
+            delimiter: ",".to_string(),
+            header: true,
+            fields: vec![
+                FieldMapping::new("MESSAGE_ID", FieldSource::MessageId),
+                FieldMapping::new("MSISDN", FieldSource::Msisdn),
+                FieldMapping::new("SYSTEM_ID", FieldSource::SystemId),
+                FieldMapping { time_format: TimeFormat::Compact, ..FieldMapping::new("EVENT_TIME", FieldSource::IssuedAt) },
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    FixedWidth, // Every field padded or cut to its width, nothing between them
+}
+
+// One output column and the rules that turn a record into its value
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FieldMapping {
+    pub name: String,
+    pub source: FieldSource,
+    #[serde(default)]
+    pub value: String, // Text for source = "constant"
+    #[serde(default)]
+    pub time_format: TimeFormat, // For source = "issued_at" or "final_at"
+    #[serde(default)]
+    pub strip_prefix: String, // Removed from the start of the value when present, e.g. "+"
+    #[serde(default)]
+    pub prefix: String, // Added after strip_prefix, e.g. a country code
+    #[serde(default)]
+    pub width: usize, // Pad or cut to this many characters (0 = as is; required for fixed_width)
+    #[serde(default)]
+    pub align: Align,
+    #[serde(default = "default_pad")]
+    pub pad: char,
+}
+
+fn default_pad() -> char {
+    ' '
+}
+
+impl FieldMapping {
+    fn new(name: &str, source: FieldSource) -> Self {
+        FieldMapping {
+            name: name.to_string(),
+            source,
+            value: String::new(),
+            time_format: TimeFormat::default(),
+            strip_prefix: String::new(),
+            prefix: String::new(),
+            width: 0,
+            align: Align::default(),
+            pad: default_pad(),
+        }
+    }
+
+    fn render(&self, record: &MessageIdRecord) -> String {
+        let value = match self.source {
+            FieldSource::MessageId => record.message_id.clone(),
+            FieldSource::SystemId => record.system_id.clone(),
+            FieldSource::Msisdn => record.msisdn.clone(),
+            FieldSource::SessionId => record.session_id.clone(),
+            FieldSource::ServiceCode => record.service_code.clone(),
+            FieldSource::IssuedAt => self.time_format.format(record.issued_at),
+            FieldSource::State => record.state.name().to_string(),
+            // Empty while the message is still ENROUTE
+            FieldSource::FinalAt => record.final_at.map(|secs| self.time_format.format(secs)).unwrap_or_default(),
+            FieldSource::Constant => self.value.clone(),
+        };
+        let value = value.strip_prefix(self.strip_prefix.as_str()).unwrap_or(&value);
+        fit(&format!("{}{}", self.prefix, value), self.width, self.align, self.pad)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldSource {
+    MessageId,
+    SystemId,
+    Msisdn,
+    SessionId,
+    ServiceCode,
+    IssuedAt,
+    State,
+    FinalAt,
+    Constant,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeFormat {
+    #[default]
+    Epoch,   // Seconds since 1970-01-01
+    Compact, // YYYYMMDDhhmmss, UTC
+    Iso8601, // YYYY-MM-DDThh:mm:ssZ
+}
+
+impl TimeFormat {
+    fn format(self, secs: u64) -> String {
+        let (year, month, day, hour, minute, second) = utc_parts(UNIX_EPOCH + Duration::from_secs(secs));
+        match self {
+            TimeFormat::Epoch => secs.to_string(),
+            TimeFormat::Compact => {
+                format!("{:04}{:02}{:02}{:02}{:02}{:02}", year, month, day, hour, minute, second)
+            }
+            TimeFormat::Iso8601 => {
+                format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, hour, minute, second)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Align {
+    #[default]
+    Left,
+    Right,
+}
+
+// Pads to `width` characters, or keeps the first `width` when the value is longer
+fn fit(value: &str, width: usize, align: Align, pad: char) -> String {
+    let len = value.chars().count();
+    if width == 0 || len == width {
+        return value.to_string();
+    }
+    if len > width {
+        return value.chars().take(width).collect();
+    }
+    let padding: String = std::iter::repeat_n(pad, width - len).collect();
+    match align {
+        Align::Left => format!("{}{}", value, padding),
+        Align::Right => format!("{}{}", padding, value),
+    }
+}
+
+impl AccountingConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.fields.is_empty() {
+            return Err("accounting.fields is empty".to_string());
+        }
+        if self.format == ExportFormat::FixedWidth
+            && let Some(field) = self.fields.iter().find(|field| field.width == 0)
+        {
+            return Err(format!("accounting field {} needs a width for the fixed_width format", field.name));
+        }
+        Ok(())
+    }
+
+    pub fn export(&self, records: &[MessageIdRecord], out: &mut impl Write) -> io::Result<()> {
+        if self.header {
+            let names: Vec<String> = self.fields.iter().map(|field| fit(&field.name, field.width, field.align, ' ')).collect();
+            self.write_line(&names, out)?;
+        }
+        for record in records {
+            let values: Vec<String> = self.fields.iter().map(|field| field.render(record)).collect();
+            self.write_line(&values, out)?;
+        }
+        Ok(())
+    }
+
+    fn write_line(&self, values: &[String], out: &mut impl Write) -> io::Result<()> {
+        let line = match self.format {
+            ExportFormat::FixedWidth => values.concat(),
+            ExportFormat::Csv => values.iter().map(|value| self.quote(value)).collect::<Vec<_>>().join(&self.delimiter),
+        };
+        writeln!(out, "{}", line)
+    }
+
+    // RFC 4180 quoting, only where a value would otherwise break the line apart
+    fn quote(&self, value: &str) -> String {
+        let needs_quotes = value.contains('"')
+            || value.contains('\n')
+            || value.contains('\r')
+            || (!self.delimiter.is_empty() && value.contains(self.delimiter.as_str()));
+        if needs_quotes {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    }
+}
+
+pub struct ExportOptions {
+    pub config_path: String,
+    pub output: Option<String>, // Standard output when not set
+}
+
+impl ExportOptions {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = ExportOptions { config_path: "config.toml".to_string(), output: None };
+        let mut i = 0;
+        while i < args.len() {
+            let value = args.get(i + 1).ok_or_else(|| format!("{} requires a value", args[i]))?;
+            match args[i].as_str() {
+                "-c" | "--config" => options.config_path = value.clone(),
+                "-o" | "--output" => options.output = Some(value.clone()),
+                other => return Err(format!("Unknown export-cdrs option: {}", other)),
+            }
+            i += 2;
+        }
+        Ok(options)
+    }
+}
+
+// Writes the persisted message_id registry in the configured accounting layout
+pub fn run(options: ExportOptions) -> io::Result<()> {
+    let config = crate::config::load_config(&options.config_path).map_err(|e| io::Error::other(e.to_string()))?;
+    config.accounting.validate().map_err(io::Error::other)?;
+    if !config.persistence.enabled {
+        return Err(io::Error::other(
+            "persistence is disabled, so no records were kept; set [persistence] enabled = true and rerun the traffic",
+        ));
+    }
+
+    // Info lines go to standard output, where they would end up inside the export
+    log::set_max_level(LevelFilter::Warn);
+    let records = StateStore::load(&config.persistence).message_ids();
+    match &options.output {
+        Some(path) => {
+            let mut out = BufWriter::new(File::create(path)?);
+            config.accounting.export(&records, &mut out)?;
+            out.flush()?;
+            println!("Exported {} record(s) from {} to {}", records.len(), config.persistence.path, path);
+        }
+        None => config.accounting.export(&records, &mut io::stdout().lock())?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::MessageState;
+
+    // 2024-02-29 12:00:00 UTC
+    const LEAP_DAY_NOON: u64 = 1_709_208_000;
+
+    fn record(message_id: &str, msisdn: &str) -> MessageIdRecord {
+        MessageIdRecord {
+            message_id: message_id.to_string(),
+            system_id: "USSDMobileUser".to_string(),
+            msisdn: msisdn.to_string(),
+            issued_at: LEAP_DAY_NOON,
+            session_id: String::new(),
+            service_code: String::new(),
+            state: MessageState::Delivered,
+            final_at: Some(LEAP_DAY_NOON),
+        }
+    }
+
+    fn export(config: &AccountingConfig, records: &[MessageIdRecord]) -> String {
+        let mut out = Vec::new();
+        config.export(records, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_default_csv_layout() {
+        let csv = export(&AccountingConfig::default(), &[record("USSD17092080000001", "94771234567")]);
+        assert_eq!(csv, "MESSAGE_ID,MSISDN,SYSTEM_ID,EVENT_TIME\nUSSD17092080000001,94771234567,USSDMobileUser,20240229120000\n");
+    }
+
+    #[test]
+    fn test_mapping_rules() {
+        let config = AccountingConfig {
+            delimiter: ";".to_string(),
+            header: false,
+            fields: vec![
+                FieldMapping { strip_prefix: "+".to_string(), prefix: "00".to_string(), ..FieldMapping::new("A", FieldSource::Msisdn) },
+                FieldMapping { value: "USSD;MO".to_string(), ..FieldMapping::new("TYPE", FieldSource::Constant) },
+                FieldMapping { time_format: TimeFormat::Iso8601, ..FieldMapping::new("T", FieldSource::IssuedAt) },
+                FieldMapping::new("EPOCH", FieldSource::IssuedAt),
+            ],
+            ..AccountingConfig::default()
+        };
+        let csv = export(&config, &[record("M1", "+94771234567"), record("M2", "94771234567")]);
+        assert_eq!(csv, concat!(
+            "0094771234567;\"USSD;MO\";2024-02-29T12:00:00Z;1709208000\n",
+            "0094771234567;\"USSD;MO\";2024-02-29T12:00:00Z;1709208000\n",
+        ));
+    }
+
+    #[test]
+    fn test_fixed_width_pads_and_cuts() {
+        let config = AccountingConfig {
+            format: ExportFormat::FixedWidth,
+            fields: vec![
+                FieldMapping { width: 6, ..FieldMapping::new("MSISDN", FieldSource::Msisdn) },
+                FieldMapping { width: 4, align: Align::Right, pad: '0', ..FieldMapping::new("ID", FieldSource::MessageId) },
+                FieldMapping { width: 3, value: "ABCDEF".to_string(), ..FieldMapping::new("TYPE", FieldSource::Constant) },
+            ],
+            ..AccountingConfig::default()
+        };
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(export(&config, &[record("42", "777")]), "MSISDN  IDTYP\n777   0042ABC\n");
+    }
+
+    // Session columns for a session that got its screen and for one whose reply never went out
+    #[test]
+    fn test_session_cdrs_carry_the_final_state() {
+        let config = AccountingConfig {
+            fields: vec![
+                FieldMapping::new("SESSION_ID", FieldSource::SessionId),
+                FieldMapping::new("SERVICE_CODE", FieldSource::ServiceCode),
+                FieldMapping::new("STATE", FieldSource::State),
+                FieldMapping { time_format: TimeFormat::Iso8601, ..FieldMapping::new("FINAL_AT", FieldSource::FinalAt) },
+            ],
+            ..AccountingConfig::default()
+        };
+        let completed = MessageIdRecord {
+            session_id: "SESS17092080000001".to_string(),
+            service_code: "*123#".to_string(),
+            ..record("M1", "94771234567")
+        };
+        let failed = MessageIdRecord {
+            session_id: "SESS17092080000002".to_string(),
+            service_code: "*456#".to_string(),
+            state: MessageState::Undeliverable,
+            final_at: Some(LEAP_DAY_NOON + 30),
+            ..record("M2", "94771234567")
+        };
+        let pending = MessageIdRecord { state: MessageState::Enroute, final_at: None, ..record("M3", "94771234567") };
+        assert_eq!(export(&config, &[completed, failed, pending]), concat!(
+            "SESSION_ID,SERVICE_CODE,STATE,FINAL_AT\n",
+            "SESS17092080000001,*123#,DELIVERED,2024-02-29T12:00:00Z\n",
+            "SESS17092080000002,*456#,UNDELIVERABLE,2024-02-29T12:00:30Z\n",
+            ",,ENROUTE,\n",
+        ));
+    }
+
+    #[test]
+    fn test_fixed_width_requires_widths() {
+        let config = AccountingConfig { format: ExportFormat::FixedWidth, ..AccountingConfig::default() };
+        assert_eq!(config.validate(), Err("accounting field MESSAGE_ID needs a width for the fixed_width format".to_string()));
+        let config = AccountingConfig { fields: Vec::new(), ..AccountingConfig::default() };
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,109 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+// TON/NPI combinations and address formats accepted in SUBMIT_SM and forwarded DELIVER_SM, e.g.
+//   [addressing]
+//   enabled = true
+//   [[addressing.rules]]
+//   ton = 5                      # Alphanumeric
+//   npi = [0]
+//   pattern = "[A-Za-z0-9 ]{1,11}"
+// A TON without a rule is refused. Rules given in the file replace the built-in ones.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AddressingConfig {
+    pub enabled: bool,
+    pub rules: Vec<AddressRule>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AddressRule {
+    pub ton: u8,
+    #[serde(default)]
+    pub npi: Vec<u8>, // Empty allows any NPI
+    pub pattern: String, // Regex the whole address must match
+}
+
+impl AddressRule {
+    fn new(ton: u8, npi: &[u8], pattern: &str) -> Self {
+        AddressRule { ton, npi: npi.to_vec(), pattern: pattern.to_string() }
+    }
+}
+
+impl Default for AddressingConfig {
+    fn default() -> Self {
+        AddressingConfig {
+            enabled: false,
+            rules: vec![
+                AddressRule::new(0, &[0, 1], r"[0-9*#+]{1,20}"), // Unknown: digits or a USSD string
+                AddressRule::new(1, &[1], r"\+?[1-9][0-9]{6,14}"), // International, E.164
+                AddressRule::new(2, &[1], r"[0-9]{4,15}"), // National
+                AddressRule::new(3, &[0, 1, 9], r"[0-9*#]{1,20}"), // Network specific, such as service codes
+                AddressRule::new(4, &[1], r"[0-9]{1,15}"), // Subscriber number
+                AddressRule::new(5, &[0], r"[ -~]{1,11}"), // Alphanumeric sender id
+                AddressRule::new(6, &[0, 1], r"[0-9*#]{1,8}"), // Abbreviated short code
+            ],
+        }
+    }
+}
+
+impl AddressingConfig {
+    // Checks the patterns, so a bad one fails at startup rather than on the first SUBMIT_SM
+    pub fn validate(&self) -> Result<(), String> {
+        for rule in &self.rules {
+            Regex::new(&rule.pattern).map_err(|e| format!("addressing rule for TON {} pattern: {}", rule.ton, e))?;
+        }
+        Ok(())
+    }
+
+    // Why `address` is refused with this TON and NPI, if it is
+    pub fn check(&self, ton: u8, npi: u8, address: &str) -> Result<(), String> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let Some(rule) = self.rules.iter().find(|rule| rule.ton == ton) else {
+            return Err(format!("TON {} is not accepted", ton));
+        };
+        if !rule.npi.is_empty() && !rule.npi.contains(&npi) {
+            return Err(format!("NPI {} is not accepted with TON {}", npi, ton));
+        }
+        // Anchored so "[0-9]+" means digits only, not "contains a digit"
+        let matches = Regex::new(&format!("^(?:{})$", rule.pattern)).is_ok_and(|regex| regex.is_match(address));
+        if !matches {
+            return Err(format!("{:?} does not match the format for TON {}", address, ton));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_rules() {
+        let config = AddressingConfig { enabled: true, ..Default::default() };
+        config.validate().unwrap();
+        assert_eq!(config.check(1, 1, "94771234567"), Ok(()));
+        assert_eq!(config.check(0, 0, "*123#"), Ok(()));
+        assert_eq!(config.check(5, 0, "MyBank"), Ok(()));
+        assert_eq!(config.check(1, 1, "0771234567"), Err("\"0771234567\" does not match the format for TON 1".to_string()));
+        assert_eq!(config.check(5, 1, "MyBank"), Err("NPI 1 is not accepted with TON 5".to_string()));
+        assert_eq!(config.check(5, 0, "ALongSenderName"), Err("\"ALongSenderName\" does not match the format for TON 5".to_string()));
+        assert_eq!(config.check(7, 0, "123"), Err("TON 7 is not accepted".to_string()));
+
+        assert_eq!(AddressingConfig::default().check(7, 0, ""), Ok(()));
+    }
+
+    #[test]
+    fn test_rules_from_config_replace_the_defaults() {
+        let config: AddressingConfig = toml::from_str("enabled = true\n[[rules]]\nton = 1\npattern = \"94[0-9]{9}\"\n").unwrap();
+        assert_eq!(config.rules.len(), 1);
+        assert_eq!(config.check(1, 9, "94771234567"), Ok(()));
+        assert!(config.check(1, 1, "44771234567").is_err());
+        assert!(config.check(0, 0, "*123#").is_err());
+
+        let bad = AddressingConfig { rules: vec![AddressRule::new(1, &[], "[0-9")], ..Default::default() };
+        assert!(bad.validate().unwrap_err().starts_with("addressing rule for TON 1 pattern:"));
+    }
+}
@@ -0,0 +1,481 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::control::{BindInfo, RateUpdate, ResponsePercentages, ScenarioState, UssdSessionInfo};
+use crate::live::{self, LiveSubscription};
+use crate::logging::{LogLevel, LogLevels, Subsystem};
+use crate::outbound::PriorityMetrics;
+use crate::persistence::StateStore;
+use crate::push::{PushReceipt, PushRequest};
+use crate::reload::ReloadReport;
+use crate::throttle::Throttler;
+use crate::window::SubmitWindows;
+
+// The web dashboard served at `GET /`; it reads the JSON routes below and the /events stream
+const DASHBOARD: &str = include_str!("dashboard.html");
+
+// Sends a network-initiated push on behalf of `POST /push`
+pub type PushHandler = Arc<dyn Fn(&PushRequest) -> Result<PushReceipt, String> + Send + Sync>;
+
+// Re-reads the config file on behalf of `POST /config/reload`
+pub type ReloadHandler = Arc<dyn Fn() -> Result<ReloadReport, String> + Send + Sync>;
+
+// Live sessions behind /binds, /ussd_sessions and /response_percentage
+pub trait SessionControl: Send + Sync {
+    fn binds(&self) -> Vec<BindInfo>;
+    fn unbind(&self, connection_id: &str) -> bool; // False when no bound connection has that id
+    fn ussd_sessions(&self) -> Vec<UssdSessionInfo>;
+    fn terminate(&self, msisdn: &str, text: Option<&str>) -> bool; // False when the MSISDN has no session
+    fn response_percentage(&self) -> ResponsePercentages;
+    fn set_response_percentage(&self, update: &RateUpdate) -> Result<ResponsePercentages, String>;
+    fn reset_response_percentage(&self) -> ResponsePercentages;
+    fn scenario(&self) -> ScenarioState;
+    fn set_scenario(&self, name: &str) -> Result<ScenarioState, String>; // Re-reads the config file
+    fn watch(&self) -> LiveSubscription; // Events for a GET /events WebSocket
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TerminateRequest {
+    text: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AdminConfig {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        AdminConfig {
+            enabled: false,
+            host: "127.0.0.1".to_string(),
+            port: 8775,
+        }
+    }
+}
+
+pub struct AdminRequest {
+    pub method: String,
+    pub path: String,
+    pub body: String,
+    pub websocket_key: Option<String>, // Sec-WebSocket-Key, when the client asks to upgrade
+}
+
+pub struct AdminResponse {
+    pub status: u16,
+    pub body: serde_json::Value,
+    pub page: Option<&'static str>, // HTML sent instead of the JSON body
+}
+
+impl AdminResponse {
+    pub fn ok(body: serde_json::Value) -> Self {
+        AdminResponse { status: 200, body, page: None }
+    }
+
+    pub fn error(status: u16, message: &str) -> Self {
+        AdminResponse {
+            status,
+            body: json!({ "error": message }),
+            page: None,
+        }
+    }
+
+    pub fn page(html: &'static str) -> Self {
+        AdminResponse { status: 200, body: serde_json::Value::Null, page: Some(html) }
+    }
+}
+
+// Counters reported under /metrics
+pub struct AdminMetrics {
+    pub priority: Arc<PriorityMetrics>,
+    pub throttle: Arc<Throttler>,
+    pub windows: Arc<SubmitWindows>,
+}
+
+// Small HTTP/1.1 admin interface for adjusting the simulator at runtime
+pub struct AdminServer {
+    config: AdminConfig,
+    log_levels: Arc<LogLevels>,
+    metrics: AdminMetrics,
+    state_store: Arc<StateStore>,
+    push: PushHandler,
+    reload: ReloadHandler,
+    control: Arc<dyn SessionControl>,
+}
+
+impl AdminServer {
+    pub fn new(
+        config: AdminConfig,
+        log_levels: Arc<LogLevels>,
+        metrics: AdminMetrics,
+        state_store: Arc<StateStore>,
+        push: PushHandler,
+        reload: ReloadHandler,
+        control: Arc<dyn SessionControl>,
+    ) -> Self {
+        AdminServer {
+            config,
+            log_levels,
+            metrics,
+            state_store,
+            push,
+            reload,
+            control,
+        }
+    }
+
+    pub fn spawn(self) -> std::io::Result<()> {
+        let addr = format!("{}:{}", self.config.host, self.config.port);
+        let listener = TcpListener::bind(&addr)?;
+        info!("🛠️  Admin interface listening on http://{}", addr);
+
+        let server = Arc::new(self);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let server = Arc::clone(&server);
+                        thread::spawn(move || {
+                            if let Err(e) = server.handle_connection(stream) {
+                                info!("Admin connection error: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) => info!("Admin connection failed: {}", e),
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn handle_connection(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        let request = match read_request(&mut stream)? {
+            Some(request) => request,
+            None => return Ok(()),
+        };
+        if request.method == "GET" && request.path.trim_matches('/') == "events"
+            && let Some(key) = &request.websocket_key
+        {
+            info!("🛠️  Dashboard connected to /events");
+            return live::serve_websocket(stream, key, self.control.watch());
+        }
+
+        let response = self.route(&request);
+        write_response(&mut stream, &response)
+    }
+
+    fn route(&self, request: &AdminRequest) -> AdminResponse {
+        let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+
+        match (request.method.as_str(), segments.as_slice()) {
+            ("GET", [""]) | ("GET", ["dashboard"]) => AdminResponse::page(DASHBOARD),
+            ("GET", ["logging"]) => AdminResponse::ok(json!(self.log_levels.snapshot())),
+            ("GET", ["logging", subsystem]) => match subsystem.parse::<Subsystem>() {
+                Ok(sub) => AdminResponse::ok(json!({ sub.name(): self.log_levels.level(sub).to_string() })),
+                Err(e) => AdminResponse::error(404, &e),
+            },
+            ("PUT", ["logging", subsystem]) | ("POST", ["logging", subsystem]) => {
+                self.set_log_level(subsystem, request.body.trim())
+            }
+            ("GET", ["metrics", "priority"]) => AdminResponse::ok(json!(self.metrics.priority.snapshot())),
+            ("GET", ["metrics", "throttle"]) => AdminResponse::ok(json!(self.metrics.throttle.snapshot())),
+            ("GET", ["metrics", "window"]) => AdminResponse::ok(json!(self.metrics.windows.snapshot())),
+            ("GET", ["message_ids", message_id]) => match self.state_store.lookup_message_id(message_id) {
+                Some(record) => AdminResponse::ok(json!(record)),
+                None => AdminResponse::error(404, "Unknown message_id"),
+            },
+            ("POST", ["push"]) => match serde_json::from_str::<PushRequest>(&request.body) {
+                Ok(push) => match (self.push)(&push) {
+                    Ok(receipt) => AdminResponse::ok(json!(receipt)),
+                    Err(e) => AdminResponse::error(409, &e),
+                },
+                Err(e) => AdminResponse::error(400, &e.to_string()),
+            },
+            ("POST", ["config", "reload"]) => match (self.reload)() {
+                Ok(report) => AdminResponse::ok(json!(report)),
+                Err(e) => AdminResponse::error(409, &e),
+            },
+            ("GET", ["binds"]) => AdminResponse::ok(json!(self.control.binds())),
+            ("DELETE", ["binds", connection_id]) => match self.control.unbind(connection_id) {
+                true => AdminResponse::ok(json!({ "closed": connection_id })),
+                false => AdminResponse::error(404, "No bound connection with that id"),
+            },
+            ("GET", ["ussd_sessions"]) => AdminResponse::ok(json!(self.control.ussd_sessions())),
+            ("DELETE", ["ussd_sessions", msisdn]) => {
+                // The body is optional; without one the subscriber is sent the goodbye message
+                let terminate = match request.body.trim() {
+                    "" => Ok(TerminateRequest::default()),
+                    body => serde_json::from_str::<TerminateRequest>(body),
+                };
+                match terminate {
+                    Ok(terminate) => match self.control.terminate(msisdn, terminate.text.as_deref()) {
+                        true => AdminResponse::ok(json!({ "terminated": msisdn })),
+                        false => AdminResponse::error(404, "No USSD session for that MSISDN"),
+                    },
+                    Err(e) => AdminResponse::error(400, &e.to_string()),
+                }
+            }
+            ("GET", ["response_percentage"]) => AdminResponse::ok(json!(self.control.response_percentage())),
+            ("PUT", ["response_percentage"]) => match serde_json::from_str::<RateUpdate>(&request.body) {
+                Ok(update) => match self.control.set_response_percentage(&update) {
+                    Ok(rates) => AdminResponse::ok(json!(rates)),
+                    Err(e) => AdminResponse::error(400, &e),
+                },
+                Err(e) => AdminResponse::error(400, &e.to_string()),
+            },
+            ("DELETE", ["response_percentage"]) => AdminResponse::ok(json!(self.control.reset_response_percentage())),
+            ("GET", ["scenario"]) => AdminResponse::ok(json!(self.control.scenario())),
+            ("PUT", ["scenario"]) | ("POST", ["scenario"]) => {
+                // Accept either a bare name ("flaky-network") or a JSON body ({"name": "flaky-network"})
+                let body = request.body.trim();
+                let name = serde_json::from_str::<HashMap<String, String>>(body)
+                    .ok()
+                    .and_then(|body| body.get("name").cloned())
+                    .unwrap_or_else(|| body.trim_matches('"').to_string());
+                let state = self.control.scenario();
+                if !state.profiles.iter().any(|profile| profile.name == name) {
+                    let names: Vec<&str> = state.profiles.iter().map(|profile| profile.name.as_str()).collect();
+                    return AdminResponse::error(400, &format!("unknown scenario profile {:?} (available: {})", name, names.join(", ")));
+                }
+                match self.control.set_scenario(&name) {
+                    Ok(state) => AdminResponse::ok(json!(state)),
+                    Err(e) => AdminResponse::error(409, &e),
+                }
+            }
+            ("GET", ["events"]) => AdminResponse::error(400, "GET /events is a WebSocket; send Upgrade: websocket"),
+            _ => AdminResponse::error(404, "Not found"),
+        }
+    }
+
+    fn set_log_level(&self, subsystem: &str, level: &str) -> AdminResponse {
+        let levels: Vec<Subsystem> = if subsystem == "all" {
+            Subsystem::ALL.to_vec()
+        } else {
+            match subsystem.parse::<Subsystem>() {
+                Ok(sub) => vec![sub],
+                Err(e) => return AdminResponse::error(404, &e),
+            }
+        };
+
+        // Accept either a bare level ("debug") or a JSON body ({"level": "debug"})
+        let level = serde_json::from_str::<HashMap<String, String>>(level)
+            .ok()
+            .and_then(|body| body.get("level").cloned())
+            .unwrap_or_else(|| level.trim_matches('"').to_string());
+
+        match level.parse::<LogLevel>() {
+            Ok(level) => {
+                for sub in levels {
+                    self.log_levels.set(sub, level);
+                    info!("🛠️  Log level for '{}' set to {}", sub.name(), level);
+                }
+                AdminResponse::ok(json!(self.log_levels.snapshot()))
+            }
+            Err(e) => AdminResponse::error(400, &e),
+        }
+    }
+}
+
+pub fn read_request(stream: &mut TcpStream) -> std::io::Result<Option<AdminRequest>> {
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line)? == 0 {
+        return Ok(None);
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_uppercase();
+    let path = parts.next().unwrap_or("/").to_string();
+
+    let mut content_length = 0usize;
+    let mut websocket_key = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        if name.trim().eq_ignore_ascii_case("content-length") {
+            content_length = value.trim().parse().unwrap_or(0);
+        } else if name.trim().eq_ignore_ascii_case("sec-websocket-key") {
+            websocket_key = Some(value.trim().to_string());
+        }
+    }
+
+    let mut body = vec![0u8; content_length];
+    if content_length > 0 {
+        reader.read_exact(&mut body)?;
+    }
+
+    Ok(Some(AdminRequest {
+        method,
+        path,
+        body: String::from_utf8_lossy(&body).to_string(),
+        websocket_key,
+    }))
+}
+
+pub fn write_response(stream: &mut TcpStream, response: &AdminResponse) -> std::io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        409 => "Conflict",
+        _ => "Error",
+    };
+    let (content_type, body) = match response.page {
+        Some(html) => ("text/html; charset=utf-8", html.to_string()),
+        None => ("application/json", serde_json::to_string_pretty(&response.body).unwrap_or_default()),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logging::SubsystemLevelsConfig;
+    use crate::persistence::PersistenceConfig;
+    use crate::throttle::ThrottleConfig;
+    use crate::config::Config;
+    use crate::server::UssdSmppServer;
+
+    fn server() -> AdminServer {
+        AdminServer::new(
+            AdminConfig::default(),
+            Arc::new(LogLevels::new(&SubsystemLevelsConfig::default(), false)),
+            AdminMetrics {
+                priority: Arc::new(PriorityMetrics::default()),
+                throttle: Arc::new(Throttler::new(ThrottleConfig::default())),
+                windows: Arc::new(SubmitWindows::default()),
+            },
+            Arc::new(StateStore::load(&PersistenceConfig::default())),
+            Arc::new(|push: &PushRequest| match push.msisdn.as_str() {
+                "busy" => Err("busy is already in a USSD session".to_string()),
+                _ => Ok(PushReceipt { message_id: "USSD1".to_string(), session_id: None }),
+            }),
+            Arc::new(|| Ok(ReloadReport { restart_required: vec!["server"] })),
+            Arc::new(UssdSmppServer::new(Config::default()).controller()),
+        )
+    }
+
+    fn request(method: &str, path: &str, body: &str) -> AdminRequest {
+        AdminRequest {
+            method: method.to_string(),
+            path: path.to_string(),
+            body: body.to_string(),
+            websocket_key: None,
+        }
+    }
+
+    #[test]
+    fn test_set_log_level_bare_and_json() {
+        let server = server();
+        let response = server.route(&request("PUT", "/logging/codec", "debug"));
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body["codec"], "debug");
+
+        let response = server.route(&request("POST", "/logging/all", r#"{"level": "warn"}"#));
+        assert_eq!(response.status, 200);
+        assert!(Subsystem::ALL.iter().all(|sub| server.log_levels.level(*sub) == LogLevel::Warn));
+
+        let response = server.route(&request("GET", "/logging/routing/", ""));
+        assert_eq!(response.body["routing"], "warn");
+    }
+
+    #[test]
+    fn test_bad_requests() {
+        let server = server();
+        assert_eq!(server.route(&request("PUT", "/logging/codec", "loud")).status, 400);
+        assert_eq!(server.route(&request("PUT", "/logging/network", "debug")).status, 404);
+        assert_eq!(server.route(&request("GET", "/message_ids/USSD0", "")).status, 404);
+        assert_eq!(server.route(&request("DELETE", "/logging", "")).status, 404);
+        assert_eq!(server.route(&request("GET", "/events", "")).status, 400);
+    }
+
+    #[test]
+    fn test_push() {
+        let server = server();
+        let response = server.route(&request("POST", "/push", r#"{"msisdn": "111", "text": "Rate us 1-5"}"#));
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body["message_id"], "USSD1");
+        assert_eq!(server.route(&request("POST", "/push", r#"{"msisdn": "busy", "text": "Hi"}"#)).status, 409);
+        assert_eq!(server.route(&request("POST", "/push", r#"{"msisdn": "111", "mode": "page"}"#)).status, 400);
+    }
+
+    #[test]
+    fn test_config_reload() {
+        let server = server();
+        let response = server.route(&request("POST", "/config/reload", ""));
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body["restart_required"], json!(["server"]));
+        assert_eq!(server.route(&request("GET", "/config/reload", "")).status, 404);
+    }
+
+    #[test]
+    fn test_session_control() {
+        let server = server();
+        assert_eq!(server.route(&request("GET", "/binds", "")).body, json!([]));
+        assert_eq!(server.route(&request("DELETE", "/binds/conn_1", "")).status, 404);
+        assert_eq!(server.route(&request("GET", "/ussd_sessions", "")).body, json!([]));
+        assert_eq!(server.route(&request("DELETE", "/ussd_sessions/111", "")).status, 404);
+        assert_eq!(server.route(&request("DELETE", "/ussd_sessions/111", "bye")).status, 400);
+    }
+
+    #[test]
+    fn test_response_percentage() {
+        let server = server();
+        let response = server.route(&request("PUT", "/response_percentage", r#"{"failure_percentage": 25, "codes": ["*123#"]}"#));
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body["codes"]["*123#"]["failure"], 25.0);
+        assert_eq!(server.route(&request("PUT", "/response_percentage", r#"{"failure_percentage": 101}"#)).status, 400);
+        assert_eq!(server.route(&request("PUT", "/response_percentage", "{}")).status, 400);
+
+        let configured = server.route(&request("DELETE", "/response_percentage", ""));
+        assert_eq!(configured.body, server.route(&request("GET", "/response_percentage", "")).body);
+        assert_ne!(configured.body["codes"]["*123#"]["failure"], 25.0);
+    }
+
+    #[test]
+    fn test_scenario() {
+        let server = server();
+        let response = server.route(&request("GET", "/scenario", ""));
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body["active"], json!(null));
+        assert_eq!(response.body["profiles"][1]["name"], "flaky-network");
+        assert_eq!(server.route(&request("PUT", "/scenario", r#"{"name": "flaky_network"}"#)).status, 400);
+        // Switching re-reads the config file, which this server was not started from
+        assert_eq!(server.route(&request("PUT", "/scenario", "flaky-network")).status, 409);
+    }
+
+    #[test]
+    fn test_dashboard() {
+        let server = server();
+        let response = server.route(&request("GET", "/", ""));
+        assert_eq!(response.status, 200);
+        assert!(response.page.is_some_and(|html| html.contains("/events") && html.contains("/ussd_sessions")));
+        assert!(server.route(&request("GET", "/dashboard", "")).page.is_some());
+        assert!(server.route(&request("GET", "/binds", "")).page.is_none());
+    }
+}
@@ -0,0 +1,120 @@
+use std::io;
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::LevelFilter;
+
+use crate::demo::DemoClient;
+use crate::config::Config;
+use crate::server::UssdSmppServer;
+
+const BENCH_USER_CLIENT: &str = "USSDMobileUser";
+const BENCH_MSISDN_BASE: u64 = 2_000_000_000;
+// The server's main menu: every request opens (or resets) a session and gets a screen back
+const BENCH_SERVICE_CODE: &str = "*123#";
+
+pub struct BenchOptions {
+    pub phones: usize,
+    pub requests: usize,
+    pub shards: Option<usize>, // Overrides smpp.session_shards; 1 gives the old single-lock maps
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        BenchOptions { phones: 8, requests: 2000, shards: None }
+    }
+}
+
+impl BenchOptions {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = BenchOptions::default();
+        let mut i = 0;
+        while i < args.len() {
+            let value = args.get(i + 1).ok_or_else(|| format!("{} requires a value", args[i]))?;
+            let value: usize = value.parse().map_err(|_| format!("Invalid value for {}: {}", args[i], value))?;
+            match args[i].as_str() {
+                "--phones" => options.phones = value.max(1),
+                "--requests" => options.requests = value.max(1),
+                "--shards" => options.shards = Some(value.max(1)),
+                other => return Err(format!("Unknown bench option: {}", other)),
+            }
+            i += 2;
+        }
+        Ok(options)
+    }
+}
+
+// Drives the server in-process with `phones` concurrent binds, each dialling `requests` times,
+// and reports throughput and round-trip latency. Per-PDU logging is muted while it runs, so the
+// figures reflect the codec and session handling rather than the terminal.
+pub fn run(options: BenchOptions) -> io::Result<()> {
+    let mut config = Config::deterministic();
+    config.client_simulator.user_clients = vec![BENCH_USER_CLIENT.to_string()];
+    if let Some(shards) = options.shards {
+        config.smpp.session_shards = shards;
+    }
+
+    let server = UssdSmppServer::new(config);
+    let config = server.config.get();
+    let clients = (0..options.phones)
+        .map(|_| DemoClient::bind(server.connect(), &config, BENCH_USER_CLIENT, "mobile123"))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    println!("Benchmarking {} phones x {} requests ({}, {} session shards)",
+        options.phones, options.requests, BENCH_SERVICE_CODE, config.smpp.session_shards);
+    let level = log::max_level();
+    log::set_max_level(LevelFilter::Warn);
+
+    let start = Arc::new(Barrier::new(options.phones + 1));
+    let workers: Vec<_> = clients
+        .into_iter()
+        .enumerate()
+        .map(|(index, mut client)| {
+            let start = Arc::clone(&start);
+            let requests = options.requests;
+            thread::spawn(move || -> io::Result<Vec<Duration>> {
+                let msisdn = (BENCH_MSISDN_BASE + index as u64).to_string();
+                let mut latencies = Vec::with_capacity(requests);
+                start.wait();
+                for _ in 0..requests {
+                    let sent = Instant::now();
+                    client.ussd_request(&msisdn, BENCH_SERVICE_CODE)?;
+                    latencies.push(sent.elapsed());
+                }
+                client.unbind()?;
+                Ok(latencies)
+            })
+        })
+        .collect();
+
+    start.wait();
+    let began = Instant::now();
+    let mut latencies = Vec::new();
+    let mut failure = None;
+    for worker in workers {
+        match worker.join().expect("bench worker panicked") {
+            Ok(worker_latencies) => latencies.extend(worker_latencies),
+            Err(e) => failure = Some(e),
+        }
+    }
+    let elapsed = began.elapsed();
+    log::set_max_level(level);
+    if let Some(e) = failure {
+        return Err(e);
+    }
+
+    latencies.sort();
+    let total = latencies.len();
+    let mean = latencies.iter().sum::<Duration>() / total as u32;
+    println!("Requests:   {}", total);
+    println!("Elapsed:    {:.2?}", elapsed);
+    println!("Throughput: {:.0} requests/s", total as f64 / elapsed.as_secs_f64());
+    println!("Latency:    mean {:.2?}, p50 {:.2?}, p99 {:.2?}, max {:.2?}",
+        mean, percentile(&latencies, 50), percentile(&latencies, 99), latencies[total - 1]);
+    Ok(())
+}
+
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    sorted[(sorted.len() * percent / 100).min(sorted.len() - 1)]
+}
@@ -0,0 +1,426 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::net::Ipv4Addr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::codec::{PduReader, HEADER_LEN};
+use crate::live::LiveFeed;
+use crate::smpp_time::utc_parts;
+use crate::pdu::{SmppHeader, SmppPdu};
+
+const BINARY_MAGIC: &[u8; 8] = b"USSDCAP1";
+const PCAP_MAGIC: u32 = 0xa1b2_c3d4;
+const LINKTYPE_RAW: u32 = 101; // Packets start at the IPv4 header
+const PCAP_SNAPLEN: u32 = 65_535;
+
+// The server's side of every capture; each connection is given a client address of its own
+const SERVER_ADDR: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+const CLIENT_NETWORK: u32 = 0x0a01_0000; // 10.1.0.0 plus the connection number
+const CLIENT_PORT: u16 = 40_000;
+
+// Record of every PDU sent and received, for settling protocol disputes from the simulator's side
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CaptureConfig {
+    pub file: String, // Empty disables capture; an existing file is overwritten
+    pub format: CaptureFormat,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureFormat {
+    #[default]
+    Pcap,   // Synthetic IPv4/TCP packets, opens in Wireshark and tcpdump
+    Binary, // Length-prefixed records that keep the connection_id
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Inbound,  // From the ESME to the simulator
+    Outbound, // From the simulator to the ESME
+}
+
+impl Direction {
+    fn arrow(self) -> &'static str {
+        match self {
+            Direction::Inbound => "->",
+            Direction::Outbound => "<-",
+        }
+    }
+}
+
+// One captured PDU, as written and as read back by --dump
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureRecord {
+    pub timestamp: SystemTime,
+    pub direction: Direction,
+    pub connection_id: String,
+    pub pdu: Vec<u8>,
+}
+
+struct CaptureWriter {
+    out: BufWriter<File>,
+    format: CaptureFormat,
+    server_port: u16,
+    tcp_sequence: HashMap<(String, Direction), u32>, // Next TCP sequence number per stream direction
+}
+
+// Shared by every connection; does nothing until started with a capture file
+#[derive(Default)]
+pub struct PduCapture {
+    writer: Mutex<Option<CaptureWriter>>,
+}
+
+impl std::fmt::Debug for PduCapture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PduCapture").field("enabled", &self.enabled()).finish()
+    }
+}
+
+impl PduCapture {
+    // Opens the capture file; `server_port` is the port PDUs are shown on in pcap captures
+    pub fn start(&self, config: &CaptureConfig, server_port: u16) -> io::Result<()> {
+        if config.file.is_empty() {
+            return Ok(());
+        }
+        let mut out = BufWriter::new(File::create(&config.file)?);
+        match config.format {
+            CaptureFormat::Pcap => {
+                // Version 2.4 (two u16s, major first), UTC timestamps, no accuracy claim
+                for field in [PCAP_MAGIC, 0x0004_0002, 0, 0, PCAP_SNAPLEN, LINKTYPE_RAW] {
+                    out.write_all(&field.to_le_bytes())?;
+                }
+            }
+            CaptureFormat::Binary => out.write_all(BINARY_MAGIC)?,
+        }
+        out.flush()?;
+        info!("📼 Capturing PDUs to {} ({:?})", config.file, config.format);
+        *self.writer.lock().unwrap() = Some(CaptureWriter {
+            out,
+            format: config.format,
+            server_port,
+            tcp_sequence: HashMap::new(),
+        });
+        Ok(())
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.writer.lock().unwrap().is_some()
+    }
+
+    pub fn record_pdu(&self, direction: Direction, connection_id: &str, pdu: &SmppPdu) {
+        if self.enabled() {
+            self.record(direction, connection_id, &pdu.to_bytes());
+        }
+    }
+
+    // Appends one PDU as it went over the wire. A write failure stops the capture rather than
+    // the connection.
+    pub fn record(&self, direction: Direction, connection_id: &str, pdu: &[u8]) {
+        let mut writer = self.writer.lock().unwrap();
+        let Some(capture) = writer.as_mut() else {
+            return;
+        };
+        let record = CaptureRecord {
+            timestamp: SystemTime::now(),
+            direction,
+            connection_id: connection_id.to_string(),
+            pdu: pdu.to_vec(),
+        };
+        // Flushed per PDU so a capture survives the process being killed
+        if let Err(e) = capture.write(&record).and_then(|_| capture.out.flush()) {
+            info!("⚠️  PDU capture stopped: {}", e);
+            *writer = None;
+        }
+    }
+}
+
+// What a connection's outbound writer records its PDUs through, for the capture file and the
+// live event feed
+#[derive(Clone)]
+pub struct CaptureTap {
+    capture: Arc<PduCapture>,
+    live: Arc<LiveFeed>,
+    connection_id: String,
+}
+
+impl CaptureTap {
+    pub fn new(capture: Arc<PduCapture>, live: Arc<LiveFeed>, connection_id: &str) -> Self {
+        CaptureTap { capture, live, connection_id: connection_id.to_string() }
+    }
+
+    pub fn outbound(&self, pdu: &[u8]) {
+        self.capture.record(Direction::Outbound, &self.connection_id, pdu);
+        if let Ok(header) = SmppHeader::decode(pdu) {
+            self.live.pdu(Direction::Outbound, &self.connection_id, &header);
+        }
+    }
+}
+
+impl CaptureWriter {
+    fn write(&mut self, record: &CaptureRecord) -> io::Result<()> {
+        let since_epoch = record.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+        match self.format {
+            CaptureFormat::Binary => {
+                let id = record.connection_id.as_bytes();
+                self.out.write_all(&(record.pdu.len() as u32).to_be_bytes())?;
+                self.out.write_all(&(since_epoch.as_micros() as u64).to_be_bytes())?;
+                self.out.write_all(&[record.direction as u8])?;
+                self.out.write_all(&(id.len() as u16).to_be_bytes())?;
+                self.out.write_all(id)?;
+                self.out.write_all(&record.pdu)
+            }
+            CaptureFormat::Pcap => {
+                let packet = self.packet(record);
+                for field in [since_epoch.as_secs() as u32, since_epoch.subsec_micros(), packet.len() as u32, packet.len() as u32] {
+                    self.out.write_all(&field.to_le_bytes())?;
+                }
+                self.out.write_all(&packet)
+            }
+        }
+    }
+
+    // IPv4 + TCP segment carrying the PDU, numbered so each connection reassembles as one stream
+    fn packet(&mut self, record: &CaptureRecord) -> Vec<u8> {
+        let client = client_addr(&record.connection_id);
+        let (src, dst, src_port, dst_port) = match record.direction {
+            Direction::Inbound => (client, SERVER_ADDR, CLIENT_PORT, self.server_port),
+            Direction::Outbound => (SERVER_ADDR, client, self.server_port, CLIENT_PORT),
+        };
+        let reverse = match record.direction {
+            Direction::Inbound => Direction::Outbound,
+            Direction::Outbound => Direction::Inbound,
+        };
+        let ack = *self.tcp_sequence.get(&(record.connection_id.clone(), reverse)).unwrap_or(&1);
+        let sequence = self.tcp_sequence.entry((record.connection_id.clone(), record.direction)).or_insert(1);
+        let seq = *sequence;
+        *sequence = sequence.wrapping_add(record.pdu.len() as u32);
+
+        let total_len = 40 + record.pdu.len();
+        let mut packet = Vec::with_capacity(total_len);
+        packet.extend_from_slice(&[0x45, 0]);
+        packet.extend_from_slice(&(total_len as u16).to_be_bytes());
+        packet.extend_from_slice(&[0, 0, 0x40, 0, 64, 6, 0, 0]); // Don't fragment, TTL 64, TCP
+        packet.extend_from_slice(&src.octets());
+        packet.extend_from_slice(&dst.octets());
+        let checksum = internet_checksum(&packet, 0);
+        packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+        packet.extend_from_slice(&src_port.to_be_bytes());
+        packet.extend_from_slice(&dst_port.to_be_bytes());
+        packet.extend_from_slice(&seq.to_be_bytes());
+        packet.extend_from_slice(&ack.to_be_bytes());
+        packet.extend_from_slice(&[0x50, 0x18, 0xff, 0xff, 0, 0, 0, 0]); // PSH+ACK, window 65535
+        packet.extend_from_slice(&record.pdu);
+        let pseudo_header = [&src.octets()[..], &dst.octets()[..], &[0, 6], &((total_len - 20) as u16).to_be_bytes()].concat();
+        let checksum = internet_checksum(&packet[20..], internet_sum(&pseudo_header));
+        packet[36..38].copy_from_slice(&checksum.to_be_bytes());
+        packet
+    }
+}
+
+// conn_N is shown as 10.1.0.0 + N
+fn client_addr(connection_id: &str) -> Ipv4Addr {
+    let number = connection_id.strip_prefix("conn_").and_then(|n| n.parse::<u32>().ok()).unwrap_or(0);
+    Ipv4Addr::from(CLIENT_NETWORK.wrapping_add(number & 0xffff))
+}
+
+fn internet_sum(data: &[u8]) -> u32 {
+    data.chunks(2).map(|pair| u32::from(u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]))).sum()
+}
+
+fn internet_checksum(data: &[u8], initial: u32) -> u16 {
+    let mut sum = initial + internet_sum(data);
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+// Reads back a capture in either format
+pub fn read(path: &str) -> io::Result<Vec<CaptureRecord>> {
+    let data = fs::read(path)?;
+    if data.starts_with(BINARY_MAGIC) {
+        read_binary(&data[BINARY_MAGIC.len()..])
+    } else if data.len() >= 24 && u32::from_le_bytes([data[0], data[1], data[2], data[3]]) == PCAP_MAGIC {
+        read_pcap(&data)
+    } else {
+        Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} is not a PDU capture", path)))
+    }
+}
+
+fn read_binary(data: &[u8]) -> io::Result<Vec<CaptureRecord>> {
+    let mut reader = PduReader::new(data);
+    let mut records = Vec::new();
+    while !reader.is_empty() {
+        let len = reader.u32()? as usize;
+        let micros = (u64::from(reader.u32()?) << 32) | u64::from(reader.u32()?);
+        let direction = if reader.u8()? == 0 { Direction::Inbound } else { Direction::Outbound };
+        let id_len = reader.u16()? as usize;
+        let connection_id = String::from_utf8_lossy(reader.bytes(id_len)?).into_owned();
+        records.push(CaptureRecord {
+            timestamp: UNIX_EPOCH + Duration::from_micros(micros),
+            direction,
+            connection_id,
+            pdu: reader.bytes(len)?.to_vec(),
+        });
+    }
+    Ok(records)
+}
+
+fn read_pcap(data: &[u8]) -> io::Result<Vec<CaptureRecord>> {
+    let le = |bytes: &[u8]| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    let mut records = Vec::new();
+    let mut pos = 24;
+    while pos + 16 <= data.len() {
+        let (secs, micros, len) = (le(&data[pos..]), le(&data[pos + 4..]), le(&data[pos + 8..]) as usize);
+        pos += 16;
+        let Some(packet) = data.get(pos..pos + len) else {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Capture truncated mid-packet"));
+        };
+        pos += len;
+        if packet.len() < 40 {
+            continue;
+        }
+        let src = Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]);
+        let (direction, client) = if src == SERVER_ADDR {
+            (Direction::Outbound, Ipv4Addr::new(packet[16], packet[17], packet[18], packet[19]))
+        } else {
+            (Direction::Inbound, src)
+        };
+        records.push(CaptureRecord {
+            timestamp: UNIX_EPOCH + Duration::from_secs(secs.into()) + Duration::from_micros(micros.into()),
+            direction,
+            connection_id: format!("conn_{}", u32::from(client).wrapping_sub(CLIENT_NETWORK)),
+            pdu: packet[40..].to_vec(),
+        });
+    }
+    Ok(records)
+}
+
+pub(crate) fn command_name(command_id: u32) -> String {
+    let name = match command_id & !0x8000_0000 {
+        0x0000_0000 => "generic_nack",
+        0x0000_0001 => "bind_receiver",
+        0x0000_0002 => "bind_transmitter",
+        0x0000_0003 => "query_sm",
+        0x0000_0004 => "submit_sm",
+        0x0000_0005 => "deliver_sm",
+        0x0000_0006 => "unbind",
+        0x0000_0007 => "replace_sm",
+        0x0000_0008 => "cancel_sm",
+        0x0000_0009 => "bind_transceiver",
+        0x0000_000b => "outbind",
+        0x0000_0015 => "enquire_link",
+        0x0000_0021 => "submit_multi",
+        0x0000_0102 => "alert_notification",
+        0x0000_0103 => "data_sm",
+        _ => return format!("0x{:08x}", command_id),
+    };
+    if command_id & 0x8000_0000 != 0 && command_id != 0x8000_0000 {
+        format!("{}_resp", name)
+    } else {
+        name.to_string()
+    }
+}
+
+// One header line per PDU followed by a hex dump of its bytes
+pub fn format_record(record: &CaptureRecord) -> String {
+    let since_epoch = record.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let (year, month, day, hour, minute, second) = utc_parts(record.timestamp);
+    let mut text = format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:06} {} {} ",
+        year, month, day, hour, minute, second, since_epoch.subsec_micros(), record.connection_id, record.direction.arrow(),
+    );
+    match SmppHeader::decode(&record.pdu) {
+        Ok(header) => text.push_str(&format!(
+            "{} seq={} status=0x{:08x} len={}",
+            command_name(header.command_id), header.sequence_number, header.command_status, header.command_length,
+        )),
+        Err(_) => text.push_str(&format!("truncated PDU ({} bytes, header is {})", record.pdu.len(), HEADER_LEN)),
+    }
+    for (line, chunk) in record.pdu.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let ascii: String = chunk.iter().map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' }).collect();
+        text.push_str(&format!("\n  {:04x}  {:<47}  {}", line * 16, hex.join(" "), ascii));
+    }
+    text
+}
+
+// --dump: prints every PDU in a capture file
+pub fn dump(path: &str) -> io::Result<()> {
+    let records = read(path)?;
+    let mut out = io::stdout().lock();
+    for record in &records {
+        writeln!(out, "{}", format_record(record))?;
+    }
+    writeln!(out, "{} PDUs", records.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capture(format: CaptureFormat) -> Vec<CaptureRecord> {
+        let file = std::env::temp_dir().join(format!("ussd_capture_{}_{:?}", std::process::id(), format));
+        let config = CaptureConfig { file: file.to_str().unwrap().to_string(), format };
+        let capture = PduCapture::default();
+        capture.record(Direction::Inbound, "conn_1", b"ignored before start");
+        capture.start(&config, 2775).unwrap();
+
+        let enquire_link = [0, 0, 0, 16, 0, 0, 0, 0x15, 0, 0, 0, 0, 0, 0, 0, 7];
+        let enquire_link_resp = [0, 0, 0, 16, 0x80, 0, 0, 0x15, 0, 0, 0, 0, 0, 0, 0, 7];
+        capture.record(Direction::Inbound, "conn_3", &enquire_link);
+        capture.record(Direction::Outbound, "conn_3", &enquire_link_resp);
+        let records = read(&config.file).unwrap();
+        fs::remove_file(&config.file).unwrap();
+        records
+    }
+
+    #[test]
+    fn test_captures_read_back_in_both_formats() {
+        for format in [CaptureFormat::Pcap, CaptureFormat::Binary] {
+            let records = capture(format);
+            assert_eq!(records.len(), 2, "{:?}", format);
+            assert_eq!(records[0].direction, Direction::Inbound);
+            assert_eq!(records[1].direction, Direction::Outbound);
+            assert!(records.iter().all(|record| record.connection_id == "conn_3"));
+            assert_eq!(records[1].pdu[4], 0x80);
+            assert!(format_record(&records[0]).contains("conn_3 -> enquire_link seq=7 status=0x00000000 len=16"));
+            assert!(format_record(&records[1]).contains("enquire_link_resp"));
+        }
+    }
+
+    #[test]
+    fn test_pcap_packets_carry_valid_checksums() {
+        let mut writer = CaptureWriter {
+            out: BufWriter::new(File::create(std::env::temp_dir().join("ussd_capture_checksum")).unwrap()),
+            format: CaptureFormat::Pcap,
+            server_port: 2775,
+            tcp_sequence: HashMap::new(),
+        };
+        let record = |pdu: &[u8], direction| CaptureRecord {
+            timestamp: SystemTime::now(),
+            direction,
+            connection_id: "conn_9".to_string(),
+            pdu: pdu.to_vec(),
+        };
+        let first = writer.packet(&record(&[1; 21], Direction::Inbound));
+        assert_eq!(internet_checksum(&first[..20], 0), 0);
+        let pseudo_header = [&first[12..20], &[0, 6], &41u16.to_be_bytes()].concat();
+        assert_eq!(internet_checksum(&first[20..], internet_sum(&pseudo_header)), 0);
+        assert_eq!(&first[12..16], &[10, 1, 0, 9]);
+
+        // The next inbound segment continues the stream; the reply acknowledges it
+        let second = writer.packet(&record(&[2; 16], Direction::Inbound));
+        assert_eq!(u32::from_be_bytes(second[24..28].try_into().unwrap()), 1 + 21);
+        let reply = writer.packet(&record(&[3; 16], Direction::Outbound));
+        assert_eq!(u32::from_be_bytes(reply[28..32].try_into().unwrap()), 1 + 21 + 16);
+        let _ = fs::remove_file(std::env::temp_dir().join("ussd_capture_checksum"));
+    }
+}
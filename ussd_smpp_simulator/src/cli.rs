@@ -0,0 +1,351 @@
+// The simulator's command line, shared by the ussd_smpp_simulator binary and `ussd-sim server`
+use std::fs;
+
+use log::{info, LevelFilter};
+use ussd_common::{logger, run_id};
+
+use crate::{
+    accounting, bench, capture, demo, load_config, migrate, probe, replay, scenario_runner, selftest, Config,
+    UssdSmppServer,
+};
+
+fn print_usage() {
+    println!("USSD SMPP Simulator");
+    println!("Usage: ussd_smpp_simulator [OPTIONS]");
+    println!();
+    println!("Options:");
+    println!("  -c, --config <CONFIG>    Path to configuration file (default: config.toml)");
+    println!("  -h, --host <HOST>        Override host from config");
+    println!("  -p, --port <PORT>        Override port from config");
+    println!("  --run-id <ID>            Namespace for IDs and log lines (default: $USSD_RUN_ID or a UUID)");
+    println!("  --timeline <FILE>        Run the fault timeline in FILE (overrides timeline.path)");
+    println!("  --profile <NAME>         Apply a scenario profile, such as flaky-network (overrides scenarios.active)");
+    println!("  --create-config          Create a default config file and exit");
+    println!("  --migrate-config         Rewrite the config file in the current format (original kept");
+    println!("                           as <CONFIG>.bak) and exit");
+    println!("  --dump <FILE>            Decode a [capture] file (pcap or binary) and exit");
+    println!("  --help                   Show this help message");
+    println!();
+    println!("Commands:");
+    println!("  all-in-one               Run server, sample forwarding client and an interactive");
+    println!("                           phone in one process (no config files needed)");
+    println!("  bench [--phones N] [--requests M] [--shards S]");
+    println!("                           Measure in-process throughput with N concurrent phones");
+    println!("                           sending M requests each (default: 8 x 2000)");
+    println!("  export-cdrs [-c CONFIG] [-o FILE]");
+    println!("                           Write the persisted message_id records in the");
+    println!("                           [accounting] layout (default: to standard output)");
+    println!("  probe <HOST:PORT> [--system-id ID] [--password PW] [--source ADDR]");
+    println!("        [--destination ADDR] [--timeout SECS]");
+    println!("                           Bind to a remote SMSC/ESME, send benign test PDUs and");
+    println!("                           report which the peer accepts or rejects");
+    println!("  replay <TRANSCRIPT> [-c CONFIG] [--dialog N] [--fast] [--wait SECS]");
+    println!("                           Start the server, wait for a forwarding client to bind and");
+    println!("                           re-drive the recorded [transcript] dialogues, reporting");
+    println!("                           every screen that differs");
+    println!("  scenario <FILE> [-c CONFIG] [--server HOST:PORT] [--junit FILE] [--wait SECS]");
+    println!("                           Dial the dialogues in a scenario file, check each screen");
+    println!("                           and write the results as JUnit XML");
+    println!("  selftest                 Start the server on an ephemeral port, run a built-in and");
+    println!("                           a forwarded USSD flow over TCP and report pass/fail");
+    println!();
+    println!("Examples:");
+    println!("  ussd_smpp_simulator");
+    println!("  ussd_smpp_simulator -c /path/to/config.toml");
+    println!("  ussd_smpp_simulator --config myconfig.toml --host 0.0.0.0");
+    println!("  ussd_smpp_simulator --timeline incident.toml");
+    println!("  ussd_smpp_simulator --profile slow-gateway");
+    println!("  ussd_smpp_simulator --create-config");
+    println!("  ussd_smpp_simulator --dump capture.pcap");
+    println!("  ussd_smpp_simulator all-in-one");
+    println!("  ussd_smpp_simulator bench --phones 16 --requests 5000");
+    println!("  ussd_smpp_simulator export-cdrs -c prod.toml -o cdrs.csv");
+    println!("  ussd_smpp_simulator probe smsc.example.net:2775 --system-id esme --password secret");
+    println!("  ussd_smpp_simulator replay dialogues.jsonl -c dev.toml --dialog 3");
+    println!("  ussd_smpp_simulator scenario balance.toml --server 127.0.0.1:2775 --junit results.xml");
+    println!("  ussd_smpp_simulator selftest");
+}
+
+// Loaded config plus the --host, --port, --timeline and --profile overrides
+type CliArgs = (Config, String, ConfigOverrides);
+
+// Command-line settings that win over the config file, applied again on every reload
+#[derive(Debug, Clone, Default)]
+struct ConfigOverrides {
+    host: Option<String>,
+    port: Option<u16>,
+    timeline: Option<String>,
+    profile: Option<String>,
+}
+
+impl ConfigOverrides {
+    fn apply(&self, config: &mut Config) {
+        if let Some(host) = &self.host {
+            config.server.host = host.clone();
+        }
+        if let Some(port) = self.port {
+            config.server.port = port;
+        }
+        if self.timeline.is_some() {
+            config.timeline.path = self.timeline.clone();
+        }
+        if self.profile.is_some() {
+            config.scenarios.active = self.profile.clone();
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<CliArgs, Box<dyn std::error::Error>> {
+    let mut config_path = "config.toml".to_string();
+    let mut host_override: Option<String> = None;
+    let mut port_override: Option<u16> = None;
+    let mut timeline_override: Option<String> = None;
+    let mut profile_override: Option<String> = None;
+    let mut migrate_config = false;
+    
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "-c" | "--config" => {
+                if i + 1 < args.len() {
+                    config_path = args[i + 1].clone();
+                    i += 2;
+                } else {
+                    eprintln!("Error: Config argument requires a value");
+                    print_usage();
+                    std::process::exit(1);
+                }
+            }
+            "-h" | "--host" => {
+                if i + 1 < args.len() {
+                    host_override = Some(args[i + 1].clone());
+                    i += 2;
+                } else {
+                    eprintln!("Error: Host argument requires a value");
+                    print_usage();
+                    std::process::exit(1);
+                }
+            }
+            "-p" | "--port" => {
+                if i + 1 < args.len() {
+                    match args[i + 1].parse::<u16>() {
+                        Ok(p) => {
+                            port_override = Some(p);
+                            i += 2;
+                        }
+                        Err(_) => {
+                            eprintln!("Error: Invalid port number '{}'", args[i + 1]);
+                            print_usage();
+                            std::process::exit(1);
+                        }
+                    }
+                } else {
+                    eprintln!("Error: Port argument requires a value");
+                    print_usage();
+                    std::process::exit(1);
+                }
+            }
+            "--run-id" => {
+                if i + 1 < args.len() {
+                    if let Err(e) = run_id::init(Some(args[i + 1].clone())) {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                    i += 2;
+                } else {
+                    eprintln!("Error: Run ID argument requires a value");
+                    print_usage();
+                    std::process::exit(1);
+                }
+            }
+            "--timeline" => {
+                if i + 1 < args.len() {
+                    timeline_override = Some(args[i + 1].clone());
+                    i += 2;
+                } else {
+                    eprintln!("Error: Timeline argument requires a value");
+                    print_usage();
+                    std::process::exit(1);
+                }
+            }
+            "--profile" => {
+                if i + 1 < args.len() {
+                    profile_override = Some(args[i + 1].clone());
+                    i += 2;
+                } else {
+                    eprintln!("Error: Profile argument requires a value");
+                    print_usage();
+                    std::process::exit(1);
+                }
+            }
+            "--create-config" => {
+                let default_config = Config::default();
+                let config_content = toml::to_string_pretty(&default_config)?;
+                fs::write("config.toml", config_content)?;
+                println!("Default configuration file created: config.toml");
+                println!("Edit this file to customize your USSD SMPP simulator settings.");
+                std::process::exit(0);
+            }
+            "--migrate-config" => {
+                migrate_config = true;
+                i += 1;
+            }
+            "--dump" => {
+                let Some(path) = args.get(i + 1) else {
+                    eprintln!("Error: Dump argument requires a capture file");
+                    print_usage();
+                    std::process::exit(1);
+                };
+                capture::dump(path)?;
+                std::process::exit(0);
+            }
+            "--help" => {
+                print_usage();
+                std::process::exit(0);
+            }
+            _ => {
+                eprintln!("Error: Unknown argument '{}'", args[i]);
+                print_usage();
+                std::process::exit(1);
+            }
+        }
+    }
+    
+    if migrate_config {
+        let deprecations = migrate::migrate_file(&config_path)?;
+        if deprecations.is_empty() {
+            println!("{} is already in the current format", config_path);
+        } else {
+            for deprecation in &deprecations {
+                println!("  {}: {}", deprecation.key, deprecation.guidance);
+            }
+            println!("Migrated {} ({} changes); the original is in {}.bak", config_path, deprecations.len(), config_path);
+        }
+        std::process::exit(0);
+    }
+    
+    let config = load_config(&config_path)?;
+    if let Some(profile) = &profile_override
+        && config.scenarios.profile(profile).is_none()
+    {
+        return Err(config.scenarios.unknown(profile).into());
+    }
+    let overrides = ConfigOverrides { host: host_override, port: port_override, timeline: timeline_override, profile: profile_override };
+    Ok((config, config_path, overrides))
+}
+
+// `args` as the process got them, program name first
+pub fn run(args: Vec<String>) -> std::io::Result<()> {
+    logger::init(LevelFilter::Info);
+    let command = args.get(1).map(String::as_str);
+    let rest = args.get(2..).unwrap_or_default();
+    if command == Some("all-in-one") {
+        return demo::run_all_in_one();
+    }
+    if command == Some("export-cdrs") {
+        let options = match accounting::ExportOptions::parse(rest) {
+            Ok(options) => options,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                print_usage();
+                std::process::exit(1);
+            }
+        };
+        if let Err(e) = accounting::run(options) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if command == Some("probe") {
+        let options = match probe::ProbeOptions::parse(rest) {
+            Ok(options) => options,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                print_usage();
+                std::process::exit(1);
+            }
+        };
+        return probe::run(options);
+    }
+    if command == Some("selftest") {
+        if let Err(e) = selftest::run() {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if command == Some("replay") {
+        let options = match replay::ReplayOptions::parse(rest) {
+            Ok(options) => options,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                print_usage();
+                std::process::exit(1);
+            }
+        };
+        if let Err(e) = replay::run(options) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if command == Some("scenario") {
+        let options = match scenario_runner::ScenarioOptions::parse(rest) {
+            Ok(options) => options,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                print_usage();
+                std::process::exit(1);
+            }
+        };
+        if let Err(e) = scenario_runner::run(options) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if command == Some("bench") {
+        let options = match bench::BenchOptions::parse(rest) {
+            Ok(options) => options,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                print_usage();
+                std::process::exit(1);
+            }
+        };
+        return bench::run(options);
+    }
+    
+    let (mut config, config_path, overrides) = match parse_args(&args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("Error loading configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    
+    // Apply command-line overrides
+    overrides.apply(&mut config);
+    
+    let addr = format!("{}:{}", config.server.host, config.server.port);
+    
+    let run_id = match run_id::init(None) {
+        Ok(run_id) => run_id,
+        Err(e) => {
+            eprintln!("Error: {} (from ${})", e, run_id::RUN_ID_ENV);
+            std::process::exit(1);
+        }
+    };
+    info!("Starting USSD SMPP Simulator");
+    info!("Run ID: {}", run_id);
+    info!("Service Codes: {:?}", config.ussd.service_codes);
+    info!("System ID: {}", config.smpp.system_id);
+    
+    let server = UssdSmppServer::builder()
+        .config(config)
+        .reload_from(config_path, move |config| overrides.apply(config))
+        .build();
+    server.start(&addr)?;
+    Ok(())
+}
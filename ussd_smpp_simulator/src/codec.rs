@@ -0,0 +1,167 @@
+use std::io::{self, Read};
+
+use bytes::{BufMut, BytesMut};
+
+use crate::pdu::{SmppHeader, SmppPdu};
+
+pub use smpp_codec::{PduReader, HEADER_LEN, MAX_PDU_LEN};
+
+// Smallest read attempted; the buffer grows past this when peers send bursts
+const MIN_READ: usize = 4096;
+
+// Per-connection read buffer. Each read takes whatever the socket has, so a burst of PDUs
+// costs one syscall, and bodies are split off as `Bytes` handles into the same allocation,
+// which is reclaimed once the handler drops them.
+pub struct PduReadBuffer {
+    buf: BytesMut,
+    max_pdu_len: usize, // Longer command_lengths fail before anything is allocated for the body
+}
+
+impl Default for PduReadBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PduReadBuffer {
+    pub fn new() -> Self {
+        PduReadBuffer {
+            buf: BytesMut::with_capacity(MIN_READ),
+            max_pdu_len: MAX_PDU_LEN,
+        }
+    }
+
+    pub fn set_max_pdu_len(&mut self, max_pdu_len: usize) {
+        self.max_pdu_len = max_pdu_len;
+    }
+
+    // Header of the frame a failed `read_pdu` stopped at, such as one with a bad command_length
+    pub fn buffered_header(&self) -> Option<SmppHeader> {
+        self.buf.get(..HEADER_LEN).and_then(|bytes| SmppHeader::decode(bytes).ok())
+    }
+
+    pub fn read_pdu(&mut self, stream: &mut impl Read) -> io::Result<SmppPdu> {
+        self.fill(stream, HEADER_LEN)?;
+        let header = SmppHeader::decode(&self.buf[..HEADER_LEN])?;
+        let command_length = HEADER_LEN + header.body_len_up_to(self.max_pdu_len)?;
+        self.fill(stream, command_length)?;
+
+        let mut frame = self.buf.split_to(command_length);
+        let body = frame.split_off(HEADER_LEN).freeze();
+        Ok(SmppPdu { header, body })
+    }
+
+    fn fill(&mut self, stream: &mut impl Read, needed: usize) -> io::Result<()> {
+        while self.buf.len() < needed {
+            self.buf.reserve((needed - self.buf.len()).max(MIN_READ));
+
+            let spare = self.buf.chunk_mut();
+            // SAFETY: the slice covers only spare capacity of `buf`, and `Read` implementations
+            // (TcpStream, the in-process channel, byte slices) write into it without reading it.
+            // Only the `read` bytes reported as written are committed by `advance_mut`.
+            let spare = unsafe { std::slice::from_raw_parts_mut(spare.as_mut_ptr(), spare.len()) };
+            let read = stream.read(spare)?;
+            if read == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed by peer"));
+            }
+            unsafe { self.buf.advance_mut(read) };
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(command_id: u32, sequence_number: u32, body: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(HEADER_LEN as u32 + body.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&command_id.to_be_bytes());
+        bytes.extend_from_slice(&0u32.to_be_bytes());
+        bytes.extend_from_slice(&sequence_number.to_be_bytes());
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    // Hands out at most `chunk` bytes per read, like a socket delivering a PDU in pieces
+    struct Trickle<'a> {
+        data: &'a [u8],
+        chunk: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = self.chunk.min(buf.len()).min(self.data.len());
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn test_read_pdus_from_one_burst() {
+        let mut bytes = frame(0x15, 1, b"");
+        bytes.extend(frame(0x04, 2, b"body"));
+        let mut stream = bytes.as_slice();
+        let mut reader = PduReadBuffer::new();
+
+        let first = reader.read_pdu(&mut stream).unwrap();
+        assert_eq!((first.header.command_id, first.header.sequence_number), (0x15, 1));
+        assert!(first.body.is_empty());
+
+        let second = reader.read_pdu(&mut stream).unwrap();
+        assert_eq!((second.header.command_id, second.header.sequence_number), (0x04, 2));
+        assert_eq!(&second.body[..], b"body");
+
+        let err = reader.read_pdu(&mut stream).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_read_pdu_split_across_reads() {
+        let body = vec![b'x'; MIN_READ * 2];
+        let bytes = frame(0x05, 7, &body);
+        let mut stream = Trickle { data: &bytes, chunk: 5 };
+
+        let pdu = PduReadBuffer::new().read_pdu(&mut stream).unwrap();
+        assert_eq!(pdu.header.command_length as usize, HEADER_LEN + body.len());
+        assert_eq!(&pdu.body[..], &body[..]);
+    }
+
+    #[test]
+    fn test_reject_invalid_command_length() {
+        let mut short = frame(0x04, 1, b"");
+        short[..4].copy_from_slice(&8u32.to_be_bytes());
+        let err = PduReadBuffer::new().read_pdu(&mut short.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let mut huge = frame(0x04, 1, b"");
+        huge[..4].copy_from_slice(&(MAX_PDU_LEN as u32 + 1).to_be_bytes());
+        let err = PduReadBuffer::new().read_pdu(&mut huge.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_max_pdu_len_is_checked_before_reading_the_body() {
+        let bytes = frame(0x04, 9, &[b'x'; 100]);
+        let mut reader = PduReadBuffer::new();
+        reader.set_max_pdu_len(HEADER_LEN + 99);
+        // Only the header is there, so a limit applied after reading the body would hit EOF instead
+        let err = reader.read_pdu(&mut &bytes[..HEADER_LEN]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(reader.buffered_header().unwrap().sequence_number, 9);
+
+        for length in [0, HEADER_LEN as u32 - 1, u32::MAX] {
+            let mut pathological = frame(0x04, 1, b"");
+            pathological[..4].copy_from_slice(&length.to_be_bytes());
+            let err = PduReadBuffer::new().read_pdu(&mut pathological.as_slice()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "command_length {}", length);
+        }
+
+        let mut reader = PduReadBuffer::new();
+        reader.set_max_pdu_len(HEADER_LEN + 100);
+        assert_eq!(reader.read_pdu(&mut bytes.as_slice()).unwrap().body.len(), 100);
+        assert!(reader.buffered_header().is_none());
+    }
+}
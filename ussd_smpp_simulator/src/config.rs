@@ -0,0 +1,659 @@
+use std::fs;
+use std::path::Path;
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use ussd_common::compression::CompressionConfig;
+use ussd_common::encoding::TextEncoding;
+use ussd_common::templates::TemplatesConfig;
+use ussd_common::variables::{ProfilesConfig, Variables};
+
+use crate::accounting::AccountingConfig;
+use crate::addressing::AddressingConfig;
+use crate::admin::AdminConfig;
+use crate::capture::CaptureConfig;
+use crate::codec::{HEADER_LEN, MAX_PDU_LEN};
+use crate::flood::FloodProtectionConfig;
+use crate::grpc::GrpcConfig;
+use crate::http_backend::HttpBackendConfig;
+use crate::journal::DeliveryRetryConfig;
+use crate::latency::LatencyConfig;
+use crate::listeners::{ListenerConfig, TlsListenerConfig};
+use crate::logging::SubsystemLevelsConfig;
+use crate::menu_tree::MenuTreeConfig;
+use crate::migrate;
+use crate::outbind::OutbindConfig;
+use crate::outbound::OverflowPolicy;
+use crate::persistence::PersistenceConfig;
+use crate::push::PushConfig;
+use crate::receipts::DeliveryReceiptsConfig;
+use crate::routing::RoutingConfig;
+use crate::scenarios::ScenariosConfig;
+use crate::screens::ScreensConfig;
+use crate::scripting::ScriptingConfig;
+use crate::subscribers::Subscriber;
+use crate::throttle::ThrottleConfig;
+use crate::timeline::{ResponseRates, TimelineConfig};
+use crate::transcript::TranscriptConfig;
+use crate::webhooks::WebhooksConfig;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryPolicy {
+    #[default]
+    RoundRobin,
+    LeastRecentlyUsed,
+}
+
+// Where a DELIVER_SM goes when the bind that originated its MSISDN is no longer usable
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteFallback {
+    #[default]
+    SameSystemId,
+    AnyUserClient,
+    Drop,
+}
+
+// Configuration structures
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub smpp: SmppConfig,
+    pub ussd: UssdConfig,
+    pub client_simulator: ClientSimulatorConfig,
+    pub logging: LoggingConfig,
+    pub response_percentage: ResponsePercentageConfig,
+    #[serde(default)]
+    pub admin: AdminConfig,
+    #[serde(default)]
+    pub grpc: GrpcConfig,
+    #[serde(default)]
+    pub routing: RoutingConfig,
+    #[serde(default)]
+    pub persistence: PersistenceConfig,
+    #[serde(default)]
+    pub compression: CompressionConfig,
+    #[serde(default)]
+    pub timeline: TimelineConfig,
+    #[serde(default)]
+    pub templates: TemplatesConfig,
+    #[serde(default)]
+    pub accounting: AccountingConfig,
+    #[serde(default)]
+    pub push: PushConfig,
+    #[serde(default)]
+    pub screens: ScreensConfig,
+    #[serde(default)]
+    pub latency: LatencyConfig,
+    #[serde(default)]
+    pub scenarios: ScenariosConfig, // Named bundles of rates, latency, window and strictness
+    #[serde(default)]
+    pub throttle: ThrottleConfig,
+    #[serde(default)]
+    pub capture: CaptureConfig,
+    #[serde(default)]
+    pub transcript: TranscriptConfig,
+    #[serde(default)]
+    pub webhooks: WebhooksConfig,
+    #[serde(default)]
+    pub http_backend: HttpBackendConfig,
+    #[serde(default)]
+    pub delivery_retry: DeliveryRetryConfig,
+    #[serde(default)]
+    pub delivery_receipts: DeliveryReceiptsConfig,
+    #[serde(default)]
+    pub addressing: AddressingConfig,
+    #[serde(default)]
+    pub scripting: ScriptingConfig,
+    #[serde(default)]
+    pub menu_tree: MenuTreeConfig,
+    #[serde(default)]
+    pub profiles: ProfilesConfig,
+    #[serde(default)]
+    pub subscribers: Vec<Subscriber>,
+    #[serde(default)]
+    pub outbind: Vec<OutbindConfig>, // ESMEs the simulator dials out to
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    #[serde(default = "default_shutdown_timeout")]
+    pub shutdown_timeout: u64, // Seconds to wait for UNBIND_RESPs when shutting down
+    #[serde(default)]
+    pub tls: TlsListenerConfig, // smpps:// listener on its own port, next to the plain one
+    #[serde(default)]
+    pub listeners: Vec<ListenerConfig>, // Further endpoints serving the same sessions
+    #[serde(default)]
+    pub flood_protection: FloodProtectionConfig, // Per-IP connection limits, allow/deny lists and bans
+}
+
+fn default_shutdown_timeout() -> u64 {
+    5
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SmppConfig {
+    pub system_id: String,
+    pub max_connections: u32,
+    pub connection_timeout: u64,
+    #[serde(default)]
+    pub accounts: Vec<SmppAccount>, // Empty list accepts any non-empty system_id/password
+    #[serde(default)]
+    pub delivery_policy: DeliveryPolicy, // How to pick between several binds of one system_id
+    #[serde(default)]
+    pub route_fallback: RouteFallback, // Used when an MSISDN's originating bind has gone away
+    #[serde(default = "default_enquire_link_interval")]
+    pub enquire_link_interval: u64, // Seconds between server ENQUIRE_LINKs (0 = disabled)
+    #[serde(default = "default_enquire_link_timeout")]
+    pub enquire_link_timeout: u64, // Seconds to wait for each ENQUIRE_LINK_RESP
+    #[serde(default = "default_enquire_link_max_missed")]
+    pub enquire_link_max_missed: u32, // Consecutive misses before the bind is dropped
+    #[serde(default = "default_outbound_queue_capacity")]
+    pub outbound_queue_capacity: usize, // PDUs waiting per connection (0 = unbounded)
+    #[serde(default)]
+    pub outbound_overflow: OverflowPolicy, // What a full queue does with another PDU
+    #[serde(default = "default_outbound_block_timeout_ms")]
+    pub outbound_block_timeout_ms: u64, // How long `block` waits for room
+    #[serde(default)]
+    pub gsm7_packing: bool, // Pack subscriber-facing data_coding 0 text 8 septets per 7 octets
+    #[serde(default = "default_session_shards")]
+    pub session_shards: usize, // Independently locked shards of the session maps
+    #[serde(default)]
+    pub window_size: usize, // Un-responded SUBMIT_SMs per connection before ESME_RMSGQFUL (0 = unlimited)
+    #[serde(default = "default_window_timeout")]
+    pub window_timeout: u64, // Seconds a dropped SUBMIT_SM holds its slot (0 = frees it at once)
+    #[serde(default)]
+    pub strict: bool, // Refuse requests whose fields break SMPP 3.4 rules instead of reading what parses
+    #[serde(default = "default_max_pdu_len")]
+    pub max_pdu_len: usize, // Largest command_length accepted; a longer one gets GENERIC_NACK and a close
+}
+
+impl SmppConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_pdu_len < HEADER_LEN {
+            return Err(format!("smpp.max_pdu_len {} is shorter than the {}-octet header", self.max_pdu_len, HEADER_LEN));
+        }
+        Ok(())
+    }
+}
+
+fn default_outbound_queue_capacity() -> usize {
+    1000
+}
+
+fn default_outbound_block_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_response_delay_ms() -> u64 {
+    50
+}
+
+fn default_max_pdu_len() -> usize {
+    MAX_PDU_LEN
+}
+
+fn default_session_shards() -> usize {
+    16
+}
+
+fn default_window_timeout() -> u64 {
+    30
+}
+
+fn default_enquire_link_interval() -> u64 {
+    30
+}
+
+fn default_enquire_link_timeout() -> u64 {
+    10
+}
+
+fn default_enquire_link_max_missed() -> u32 {
+    3
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SmppAccount {
+    pub system_id: String,
+    pub password: String,
+    #[serde(default = "default_allowed_bind_types")]
+    pub allowed_bind_types: Vec<String>, // "transmitter", "receiver", "transceiver"
+}
+
+fn default_allowed_bind_types() -> Vec<String> {
+    vec!["transmitter".to_string(), "receiver".to_string(), "transceiver".to_string()]
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UssdConfig {
+    pub service_codes: Vec<String>,
+    pub session_timeout: u64, // Seconds of inactivity before a USSD session is dropped (0 = never)
+    #[serde(default)]
+    pub notify_on_timeout: bool, // Send a terminate notification to the subscriber on timeout
+    #[serde(default = "default_forward_timeout")]
+    pub forward_timeout: u64, // Seconds to wait for a forwarding client's reply (0 = forever)
+    #[serde(default)]
+    pub long_responses: LongResponseMode, // How responses over 255 bytes are sent
+    #[serde(default = "default_session_store")]
+    pub session_store: String, // "memory", or "sqlite:<path>" to keep open sessions across restarts
+    pub menu: MenuConfig,
+    pub responses: ResponsesConfig,
+    pub data_packages: DataPackagesConfig,
+}
+
+fn default_forward_timeout() -> u64 {
+    30
+}
+
+fn default_session_store() -> String {
+    "memory".to_string()
+}
+
+// short_message holds at most 255 bytes
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LongResponseMode {
+    #[default]
+    MessagePayload, // Whole text in the message_payload TLV, sm_length 0
+    Truncate, // Cut to 255 bytes on a character boundary
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MenuConfig {
+    pub welcome_message: String,
+    pub main_menu: Vec<String>,
+    #[serde(default)]
+    pub encoding: TextEncoding, // Main and data package menus: "gsm7", "ucs2" or "auto"
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ResponsesConfig {
+    pub balance_message: String,
+    pub invalid_code: String,
+    pub invalid_option: String,
+    pub goodbye_message: String,
+    #[serde(default = "default_session_timeout_message")]
+    pub session_timeout_message: String,
+    #[serde(default = "default_forward_error_message")]
+    pub forward_error_message: String, // Sent when a forwarded request times out or is rejected
+    #[serde(default = "default_notify_screens")]
+    pub notify_screens: Vec<NotifyScreen>, // Built-in screens sent as USSD_NOTIFY, ending the session
+    #[serde(default)]
+    pub encoding: TextEncoding, // Every other screen: "gsm7", "ucs2" or "auto"
+}
+
+// Built-in screens that can be sent as a notification instead of a menu awaiting a reply
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyScreen {
+    Goodbye,  // goodbye_message on exit
+    Balance,  // balance_message, without the "Press 0" prompt
+    Purchase, // Data package purchase confirmation
+}
+
+fn default_session_timeout_message() -> String {
+    "Your session has ended due to inactivity. Thank you!".to_string()
+}
+
+fn default_forward_error_message() -> String {
+    "Service temporarily unavailable. Please try again later.".to_string()
+}
+
+fn default_notify_screens() -> Vec<NotifyScreen> {
+    vec![NotifyScreen::Goodbye]
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DataPackagesConfig {
+    pub packages: Vec<DataPackage>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DataPackage {
+    pub name: String,
+    pub price: f64,
+    pub data: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LoggingConfig {
+    pub debug: bool,
+    pub log_file: String,
+    #[serde(default)]
+    pub subsystems: SubsystemLevelsConfig,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ClientSimulatorConfig {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    pub system_id: String,
+    pub password: String,
+    pub forwarding_clients: Vec<String>, // List of system IDs that handle custom USSD codes
+    pub user_clients: Vec<String>, // List of system IDs that are user simulators
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ResponsePercentageConfig {
+    pub success_percentage: f64,
+    pub failure_percentage: f64,
+    pub no_response_percentage: f64,
+    pub failure_error_code: u32,
+    pub no_response_delay_ms: u64,
+    #[serde(default = "default_response_delay_ms")]
+    pub response_delay_ms: u64, // Pause before each server-generated screen
+    #[serde(default)]
+    pub seed: Option<u64>, // Fixed seed for the response roll so runs can be replayed
+    #[serde(default)]
+    pub overrides: Vec<ResponseOverride>,
+}
+
+// Response profile for one USSD code and/or bound system_id. Unset keys match anything, unset
+// rates keep the global value and success defaults to the rest; the first matching entry wins.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ResponseOverride {
+    pub code: Option<String>,
+    pub system_id: Option<String>,
+    pub success_percentage: Option<f64>,
+    pub failure_percentage: Option<f64>,
+    pub no_response_percentage: Option<f64>,
+    pub failure_error_code: Option<u32>,
+}
+
+impl ResponseOverride {
+    fn matches(&self, code: &str, system_id: Option<&str>) -> bool {
+        self.code.as_deref().is_none_or(|expected| expected == code)
+            && self.system_id.as_ref().is_none_or(|expected| system_id == Some(expected.as_str()))
+    }
+}
+
+impl ResponsePercentageConfig {
+    // Answers every request, for runs whose outcome must not depend on the roll; overrides go too
+    pub fn always_succeed(&mut self) {
+        self.success_percentage = 100.0;
+        self.failure_percentage = 0.0;
+        self.no_response_percentage = 0.0;
+        self.overrides.clear();
+    }
+
+    // Rates and failure status for a SUBMIT_SM on `code` from a bind as `system_id`
+    pub fn profile_for(&self, code: &str, system_id: Option<&str>) -> (ResponseRates, u32) {
+        let mut rates = ResponseRates {
+            success: self.success_percentage,
+            failure: self.failure_percentage,
+            no_response: self.no_response_percentage,
+        };
+        let mut error_code = self.failure_error_code;
+        if let Some(rule) = self.overrides.iter().find(|rule| rule.matches(code, system_id)) {
+            rates.failure = rule.failure_percentage.unwrap_or(rates.failure);
+            rates.no_response = rule.no_response_percentage.unwrap_or(rates.no_response);
+            rates.success = rule.success_percentage.unwrap_or((100.0 - rates.failure - rates.no_response).max(0.0));
+            error_code = rule.failure_error_code.unwrap_or(error_code);
+        }
+        (rates, error_code)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            server: ServerConfig {
+                host: "127.0.0.1".to_string(),
+                port: 2775,
+                shutdown_timeout: default_shutdown_timeout(),
+                tls: TlsListenerConfig::default(),
+                listeners: Vec::new(),
+                flood_protection: FloodProtectionConfig::default(),
+            },
+            smpp: SmppConfig {
+                system_id: "USSDGateway".to_string(),
+                max_connections: 100,
+                connection_timeout: 300,
+                accounts: Vec::new(),
+                delivery_policy: DeliveryPolicy::RoundRobin,
+                route_fallback: RouteFallback::SameSystemId,
+                enquire_link_interval: default_enquire_link_interval(),
+                enquire_link_timeout: default_enquire_link_timeout(),
+                enquire_link_max_missed: default_enquire_link_max_missed(),
+                outbound_queue_capacity: default_outbound_queue_capacity(),
+                outbound_overflow: OverflowPolicy::Block,
+                outbound_block_timeout_ms: default_outbound_block_timeout_ms(),
+                gsm7_packing: false,
+                session_shards: default_session_shards(),
+                window_size: 0,
+                window_timeout: default_window_timeout(),
+                strict: false,
+                max_pdu_len: default_max_pdu_len(),
+            },
+            ussd: UssdConfig {
+                service_codes: vec!["*123#".to_string()],
+                session_timeout: 180,
+                notify_on_timeout: false,
+                forward_timeout: default_forward_timeout(),
+                long_responses: LongResponseMode::MessagePayload,
+                session_store: default_session_store(),
+                menu: MenuConfig {
+                    welcome_message: "Welcome to MyTelecom USSD Service".to_string(),
+                    main_menu: vec![
+                        "1. Balance Inquiry".to_string(),
+                        "2. Data Packages".to_string(),
+                        "3. Customer Service".to_string(),
+                        "0. Exit".to_string(),
+                    ],
+                    encoding: TextEncoding::default(),
+                },
+                responses: ResponsesConfig {
+                    balance_message: "Your current balance is {{balance}}\nYour data balance is {{data_balance}}".to_string(),
+                    invalid_code: "Invalid USSD code. Please try again.".to_string(),
+                    invalid_option: "Invalid option. Please try again.".to_string(),
+                    session_timeout_message: default_session_timeout_message(),
+                    forward_error_message: default_forward_error_message(),
+                    goodbye_message: "Thank you for using MyTelecom USSD Service. Goodbye!".to_string(),
+                    notify_screens: default_notify_screens(),
+                    encoding: TextEncoding::default(),
+                },
+                data_packages: DataPackagesConfig {
+                    packages: vec![
+                        DataPackage {
+                            name: "1GB Package".to_string(),
+                            price: 10.0,
+                            data: "1GB".to_string(),
+                        },
+                        DataPackage {
+                            name: "5GB Package".to_string(),
+                            price: 40.0,
+                            data: "5GB".to_string(),
+                        },
+                        DataPackage {
+                            name: "10GB Package".to_string(),
+                            price: 70.0,
+                            data: "10GB".to_string(),
+                        },
+                    ],
+                },
+            },
+            client_simulator: ClientSimulatorConfig {
+                enabled: false,
+                host: "127.0.0.1".to_string(),
+                port: 9090,
+                system_id: "USSDClient".to_string(),
+                password: "password123".to_string(),
+                forwarding_clients: vec!["ForwardingClient".to_string(), "JavaClient".to_string()],
+                user_clients: vec!["USSDMobileUser".to_string()],
+            },
+            logging: LoggingConfig {
+                debug: false,
+                log_file: "".to_string(),
+                subsystems: SubsystemLevelsConfig::default(),
+            },
+            response_percentage: ResponsePercentageConfig {
+                success_percentage: 95.0,
+                failure_percentage: 4.0,
+                no_response_percentage: 1.0,
+                failure_error_code: 0x00000008, // ESME_RSYSERR
+                no_response_delay_ms: 5000,
+                response_delay_ms: default_response_delay_ms(),
+                seed: None,
+                overrides: Vec::new(),
+            },
+            admin: AdminConfig::default(),
+            grpc: GrpcConfig::default(),
+            routing: RoutingConfig::default(),
+            persistence: PersistenceConfig::default(),
+            compression: CompressionConfig::default(),
+            timeline: TimelineConfig::default(),
+            templates: TemplatesConfig::default(),
+            accounting: AccountingConfig::default(),
+            push: PushConfig::default(),
+            screens: ScreensConfig::default(),
+            latency: LatencyConfig::default(),
+            scenarios: ScenariosConfig::default(),
+            throttle: ThrottleConfig::default(),
+            capture: CaptureConfig::default(),
+            transcript: TranscriptConfig::default(),
+            webhooks: WebhooksConfig::default(),
+            http_backend: HttpBackendConfig::default(),
+            delivery_retry: DeliveryRetryConfig::default(),
+            delivery_receipts: DeliveryReceiptsConfig::default(),
+            addressing: AddressingConfig::default(),
+            scripting: ScriptingConfig::default(),
+            menu_tree: MenuTreeConfig::default(),
+            profiles: ProfilesConfig {
+                defaults: [("balance", "$25.50"), ("data_balance", "2.5GB")]
+                    .into_iter()
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect(),
+                msisdns: Default::default(),
+            },
+            subscribers: Vec::new(),
+            outbind: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    // The defaults with every request answered at once and no ENQUIRE_LINKs, for self-tests,
+    // benchmarks and the tests that drive an embedded server
+    pub fn deterministic() -> Config {
+        let mut config = Config::default();
+        config.response_percentage.always_succeed();
+        config.response_percentage.response_delay_ms = 0;
+        config.smpp.enquire_link_interval = 0;
+        config
+    }
+
+    pub fn subscriber(&self, msisdn: &str) -> Option<&Subscriber> {
+        self.subscribers.iter().find(|subscriber| subscriber.msisdn == msisdn)
+    }
+
+    // Placeholder values for a request from `msisdn`: its `[[subscribers]]` entry, then `[profiles]`
+    pub fn variables<'a>(&'a self, msisdn: &'a str, inputs: &'a [String]) -> Variables<'a> {
+        let values = self.subscriber(msisdn).map(Subscriber::fields).unwrap_or_default();
+        self.profiles.variables(msisdn, inputs).with_values(values)
+    }
+
+    // Inlines {{> name}} template includes in the menu and response texts
+    fn expand_templates(&mut self, base_dir: &Path) -> Result<(), String> {
+        let ussd = &mut self.ussd;
+        let responses = &mut ussd.responses;
+        let tree = &mut self.menu_tree;
+        self.templates.expand_all(
+            base_dir,
+            [
+                &mut ussd.menu.welcome_message,
+                &mut responses.balance_message,
+                &mut responses.invalid_code,
+                &mut responses.invalid_option,
+                &mut responses.goodbye_message,
+                &mut responses.session_timeout_message,
+                &mut responses.forward_error_message,
+                &mut self.push.reply_message,
+            ]
+            .into_iter()
+            .chain(ussd.menu.main_menu.iter_mut())
+            .chain(tree.responses.values_mut())
+            .chain(tree.menus.values_mut().flat_map(|menu| {
+                std::iter::once(&mut menu.title).chain(menu.options.iter_mut().map(|option| &mut option.text))
+            })),
+        )
+    }
+}
+
+pub fn load_config(config_path: &str) -> Result<Config, Box<dyn std::error::Error>> {
+    if Path::new(config_path).exists() {
+        let config_content = fs::read_to_string(config_path)?;
+        let (config_content, deprecations) = migrate::migrate(&config_content)?;
+        for deprecation in &deprecations {
+            warn!("⚠️  {}: `{}` {}", config_path, deprecation.key, deprecation.guidance);
+        }
+        if !deprecations.is_empty() {
+            warn!("⚠️  Run with `-c {} --migrate-config` to rewrite the file in the current format", config_path);
+        }
+        let mut config: Config = toml::from_str(&config_content)?;
+        let base_dir = Path::new(config_path).parent().unwrap_or(Path::new(""));
+        config.expand_templates(base_dir)?;
+        config.server.flood_protection.validate()?;
+        config.smpp.validate()?;
+        config.menu_tree.validate()?;
+        config.http_backend.validate()?;
+        config.addressing.validate()?;
+        config.scenarios.validate()?;
+        config.scripting.compile(base_dir)?;
+        Ok(config)
+    } else {
+        info!("Config file not found at '{}', creating default config...", config_path);
+        let default_config = Config::default();
+        let config_content = toml::to_string_pretty(&default_config)?;
+        fs::write(config_path, config_content)?;
+        info!("Default config created at '{}'", config_path);
+        Ok(default_config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timeline::ResponseRates;
+
+    #[test]
+    fn test_response_overrides_match_code_and_system_id() {
+        let config: ResponsePercentageConfig = toml::from_str(
+            "success_percentage = 95.0\nfailure_percentage = 4.0\nno_response_percentage = 1.0\n\
+             failure_error_code = 8\nno_response_delay_ms = 0\n\n\
+             [[overrides]]\ncode = \"*123#\"\nsuccess_percentage = 100.0\nfailure_percentage = 0.0\nno_response_percentage = 0.0\n\n\
+             [[overrides]]\ncode = \"*999#\"\nsystem_id = \"LoadTester\"\nsuccess_percentage = 50.0\nfailure_percentage = 50.0\n\
+             no_response_percentage = 0.0\nfailure_error_code = 0x58\n\n\
+             [[overrides]]\nsystem_id = \"LoadTester\"\nfailure_percentage = 10.0\n",
+        )
+        .unwrap();
+
+        assert_eq!(config.profile_for("*123#", Some("LoadTester")), (ResponseRates { success: 100.0, failure: 0.0, no_response: 0.0 }, 8));
+        assert_eq!(config.profile_for("*999#", Some("LoadTester")), (ResponseRates { success: 50.0, failure: 50.0, no_response: 0.0 }, 0x58));
+        // Unset fields keep the global values and success takes up the rest
+        assert_eq!(config.profile_for("*100#", Some("LoadTester")), (ResponseRates { success: 89.0, failure: 10.0, no_response: 1.0 }, 8));
+        assert_eq!(config.profile_for("*999#", Some("USSDMobileUser")), (ResponseRates { success: 95.0, failure: 4.0, no_response: 1.0 }, 8));
+        assert_eq!(config.profile_for("*999#", None).1, 8);
+
+        // Overrides would bring the failures back
+        let mut config = config;
+        config.always_succeed();
+        let always = ResponseRates { success: 100.0, failure: 0.0, no_response: 0.0 };
+        assert_eq!(config.profile_for("*999#", Some("LoadTester")).0, always);
+        assert_eq!(config.profile_for("*100#", None).0, always);
+    }
+
+    #[test]
+    fn test_max_pdu_len_must_hold_a_header() {
+        let mut config = Config::default();
+        assert_eq!(config.smpp.max_pdu_len, MAX_PDU_LEN);
+        config.smpp.validate().unwrap();
+        config.smpp.max_pdu_len = HEADER_LEN - 1;
+        assert_eq!(config.smpp.validate().unwrap_err(), "smpp.max_pdu_len 15 is shorter than the 16-octet header");
+    }
+}
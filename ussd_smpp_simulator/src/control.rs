@@ -0,0 +1,296 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::admin::SessionControl;
+use crate::live::LiveSubscription;
+use crate::logging::LogLevels;
+use crate::persistence::StateStore;
+use crate::reload::{ConfigReloader, LiveConfig};
+use crate::shard::ShardedMap;
+use crate::timeline::{FaultAction, ResponseRates};
+use crate::pdu::bind_type_name;
+use crate::router::ConnectionManager;
+use crate::scenarios::ScenarioInfo;
+use crate::server::send_terminate_notification;
+use crate::session::{Session, UssdSession};
+
+// A bound SMPP session as listed by `GET /binds`
+#[derive(Debug, Clone, Serialize)]
+pub struct BindInfo {
+    pub connection_id: String,
+    pub system_id: String,
+    pub bind_type: &'static str,
+    pub role: &'static str, // "user", "forwarding" or "esme"
+    pub screen_chars: Option<usize>,
+}
+
+// An open USSD dialogue as listed by `GET /ussd_sessions`
+#[derive(Debug, Clone, Serialize)]
+pub struct UssdSessionInfo {
+    pub msisdn: String,
+    pub session_id: String,
+    pub state: String,
+    pub service_code: String,
+    pub menu_level: u8,
+    pub idle_secs: u64,
+    pub forward_route: Option<String>,
+}
+
+// Body of `PUT /response_percentage`; the rates stay in effect until reset or the fault
+// timeline's next recover event
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RateUpdate {
+    pub failure_percentage: Option<f64>,
+    pub no_response_percentage: Option<f64>,
+    pub codes: Vec<String>, // Empty means every code
+}
+
+// Response rates in effect, for codes without a rule of their own and per configured code
+#[derive(Debug, Clone, Serialize)]
+pub struct ResponsePercentages {
+    pub default: ResponseRates,
+    pub codes: BTreeMap<String, ResponseRates>,
+}
+
+// The scenario profile in effect and those that can be switched to, for `GET /scenario`
+#[derive(Debug, Clone, Serialize)]
+pub struct ScenarioState {
+    pub active: Option<String>,
+    pub profiles: Vec<ScenarioInfo>,
+}
+
+// Inspects and steers live sessions on behalf of the admin interface
+#[derive(Clone)]
+pub struct Controller {
+    pub sessions: Arc<ShardedMap<Session>>,
+    pub ussd_sessions: Arc<ShardedMap<UssdSession>>,
+    pub state_store: Arc<StateStore>,
+    pub config: Arc<LiveConfig>,
+    pub connection_manager: ConnectionManager,
+    pub log_levels: Arc<LogLevels>,
+    pub reloader: Option<Arc<ConfigReloader>>, // Scenario switches re-read the config file
+}
+
+impl RateUpdate {
+    fn actions(&self) -> Result<Vec<FaultAction>, String> {
+        let valid = |percentage: f64| (0.0..=100.0).contains(&percentage);
+        if self.failure_percentage.is_none() && self.no_response_percentage.is_none() {
+            return Err("Set failure_percentage, no_response_percentage or both".to_string());
+        }
+        if !self.failure_percentage.into_iter().chain(self.no_response_percentage).all(valid) {
+            return Err("Percentages must be between 0 and 100".to_string());
+        }
+        if self.failure_percentage.unwrap_or(0.0) + self.no_response_percentage.unwrap_or(0.0) > 100.0 {
+            return Err("failure_percentage and no_response_percentage add up to more than 100".to_string());
+        }
+
+        let mut actions = Vec::new();
+        if let Some(percentage) = self.failure_percentage {
+            actions.push(FaultAction::SetFailureRate { percentage, codes: self.codes.clone() });
+        }
+        if let Some(percentage) = self.no_response_percentage {
+            actions.push(FaultAction::SetNoResponseRate { percentage, codes: self.codes.clone() });
+        }
+        Ok(actions)
+    }
+}
+
+impl SessionControl for Controller {
+    fn binds(&self) -> Vec<BindInfo> {
+        let mut binds = self.sessions.filter_map(|session| {
+            let connection_id = session.connection_id.clone().filter(|_| session.bound)?;
+            Some(BindInfo {
+                connection_id,
+                system_id: session.system_id.clone(),
+                bind_type: bind_type_name(session.bind_type),
+                role: session.role(),
+                screen_chars: session.screen_chars,
+            })
+        });
+        binds.sort_by(|a, b| a.connection_id.cmp(&b.connection_id));
+        binds
+    }
+
+    fn unbind(&self, connection_id: &str) -> bool {
+        let dropped = self.connection_manager.drop_connections(&self.sessions, |session| {
+            session.connection_id.as_deref() == Some(connection_id)
+        });
+        if dropped > 0 {
+            info!("🔌 Closed {} from the admin interface", connection_id);
+        }
+        dropped > 0
+    }
+
+    fn ussd_sessions(&self) -> Vec<UssdSessionInfo> {
+        let mut ussd_sessions = self.ussd_sessions.filter_map(|session| {
+            Some(UssdSessionInfo {
+                msisdn: session.msisdn.clone(),
+                session_id: session.session_id.clone(),
+                state: format!("{:?}", session.state),
+                service_code: session.service_code.clone(),
+                menu_level: session.menu_level,
+                idle_secs: session.last_activity.elapsed().as_secs(),
+                forward_route: session.forward_route.clone(),
+            })
+        });
+        ussd_sessions.sort_by(|a, b| a.msisdn.cmp(&b.msisdn));
+        ussd_sessions
+    }
+
+    fn terminate(&self, msisdn: &str, text: Option<&str>) -> bool {
+        let Some(session) = self.ussd_sessions.remove(msisdn) else {
+            return false;
+        };
+        let config = self.config.get();
+        info!("🛑 USSD session {} for {} terminated from the admin interface", session.session_id, msisdn);
+        let text = text.unwrap_or(&config.ussd.responses.goodbye_message);
+        send_terminate_notification(
+            &config,
+            &self.sessions,
+            &self.state_store,
+            &self.connection_manager,
+            &self.log_levels,
+            &session,
+            text,
+        );
+        self.connection_manager.forget_origin(msisdn);
+        true
+    }
+
+    fn response_percentage(&self) -> ResponsePercentages {
+        let config = self.config.get();
+        let faults = &self.connection_manager.faults;
+        let rates_for = |code: &str| faults.rates_for(code, config.response_percentage.profile_for(code, None).0);
+        ResponsePercentages {
+            default: rates_for(""),
+            codes: config.ussd.service_codes.iter().map(|code| (code.clone(), rates_for(code))).collect(),
+        }
+    }
+
+    fn set_response_percentage(&self, update: &RateUpdate) -> Result<ResponsePercentages, String> {
+        for action in update.actions()? {
+            info!("🌪️  Admin interface: {}", action);
+            self.connection_manager.faults.apply(&action);
+        }
+        Ok(self.response_percentage())
+    }
+
+    fn reset_response_percentage(&self) -> ResponsePercentages {
+        info!("🌪️  Admin interface: configured response percentages restored");
+        self.connection_manager.faults.clear_rates();
+        self.response_percentage()
+    }
+
+    fn scenario(&self) -> ScenarioState {
+        let config = self.config.get();
+        ScenarioState { active: config.scenarios.active.clone(), profiles: config.scenarios.list() }
+    }
+
+    fn set_scenario(&self, name: &str) -> Result<ScenarioState, String> {
+        let Some(reloader) = &self.reloader else {
+            return Err("the server was not started from a config file".to_string());
+        };
+        info!("🎬 Admin interface: switching to scenario profile '{}'", name);
+        reloader.switch_scenario(name)?;
+        Ok(self.scenario())
+    }
+
+    fn watch(&self) -> LiveSubscription {
+        LiveSubscription::new(&self.connection_manager.live, &self.connection_manager.webhooks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    use crate::codec::PduReadBuffer;
+    use crate::config::Config;
+    use crate::pdu::{SmppPdu, BIND_TRANSCEIVER, DELIVER_SM, ESME_ROK, USSD_TERMINATE_NOTIFY};
+    use crate::server::UssdSmppServer;
+
+    fn bind(server: &UssdSmppServer, system_id: &str) -> (crate::transport::SmppStream, PduReadBuffer) {
+        let mut client = server.connect();
+        let body = format!("{}\0secret\0USSD\0\x34\x01\x01\0", system_id).into_bytes();
+        let pdu = SmppPdu::new(BIND_TRANSCEIVER, ESME_ROK, 1, body);
+        client.write_all(&pdu.to_bytes()).unwrap();
+        let mut buffer = PduReadBuffer::new();
+        assert_eq!(buffer.read_pdu(&mut client).unwrap().header.command_status, ESME_ROK);
+        (client, buffer)
+    }
+
+    #[test]
+    fn test_binds_and_forced_unbind() {
+        let mut config = Config::default();
+        config.smpp.enquire_link_interval = 0;
+        let server = UssdSmppServer::new(config);
+        let controller = server.controller();
+        let (mut user, mut user_buffer) = bind(&server, "USSDMobileUser");
+        let (_forwarder, _) = bind(&server, "ForwardingClient");
+
+        let binds = controller.binds();
+        let roles: Vec<(&str, &str)> = binds.iter().map(|bind| (bind.system_id.as_str(), bind.role)).collect();
+        assert_eq!(roles.len(), 2);
+        assert!(roles.contains(&("USSDMobileUser", "user")));
+        assert!(roles.contains(&("ForwardingClient", "forwarding")));
+
+        let user_bind = binds.iter().find(|bind| bind.role == "user").unwrap();
+        assert!(controller.unbind(&user_bind.connection_id));
+        assert!(user_buffer.read_pdu(&mut user).is_err());
+        assert!(!controller.unbind("conn_missing"));
+    }
+
+    #[test]
+    fn test_terminate_notifies_subscriber() {
+        let mut config = Config::default();
+        config.smpp.enquire_link_interval = 0;
+        let server = UssdSmppServer::new(config);
+        let controller = server.controller();
+        let (mut user, mut user_buffer) = bind(&server, "USSDMobileUser");
+
+        let push = crate::push::PushRequest {
+            msisdn: "1234567890".to_string(),
+            text: "Rate us 1-5".to_string(),
+            mode: crate::push::PushMode::Request,
+            priority_flag: 0,
+        };
+        server.pusher().push(&push).unwrap();
+        assert_eq!(user_buffer.read_pdu(&mut user).unwrap().header.command_id, DELIVER_SM);
+        let listed = controller.ussd_sessions();
+        assert_eq!(listed.len(), 1);
+        assert_eq!((listed[0].msisdn.as_str(), listed[0].state.as_str()), ("1234567890", "Pushed"));
+
+        assert!(controller.terminate("1234567890", Some("Closed by operator")));
+        let notify = user_buffer.read_pdu(&mut user).unwrap();
+        assert_eq!(notify.header.command_id, DELIVER_SM);
+        let body = String::from_utf8_lossy(&notify.body);
+        assert!(body.contains("Closed by operator"));
+        assert!(notify.body.windows(5).any(|tlv| tlv == [0x05, 0x01, 0x00, 0x01, USSD_TERMINATE_NOTIFY]));
+        assert!(controller.ussd_sessions().is_empty());
+        assert!(!controller.terminate("1234567890", None));
+    }
+
+    #[test]
+    fn test_response_percentage_overrides_and_reset() {
+        let server = UssdSmppServer::new(Config::default());
+        let controller = server.controller();
+        let configured = controller.response_percentage();
+
+        let update = RateUpdate { failure_percentage: Some(30.0), codes: vec!["*123#".to_string()], ..Default::default() };
+        let rates = controller.set_response_percentage(&update).unwrap();
+        assert_eq!(rates.codes["*123#"].failure, 30.0);
+        assert_eq!(rates.codes["*123#"].success, 100.0 - 30.0 - configured.codes["*123#"].no_response);
+        assert_eq!(rates.default, configured.default);
+
+        let too_much = RateUpdate { failure_percentage: Some(60.0), no_response_percentage: Some(50.0), codes: Vec::new() };
+        assert!(controller.set_response_percentage(&too_much).is_err());
+        assert!(controller.set_response_percentage(&RateUpdate::default()).is_err());
+
+        assert_eq!(controller.reset_response_percentage().codes["*123#"], configured.codes["*123#"]);
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Forwarded SUBMIT_SM still waiting for the forwarding client's DELIVER_SM
+#[derive(Debug, Clone)]
+pub struct PendingRequest {
+    pub sequence_number: u32,
+    pub msisdn: String,
+    pub session_id: String,
+    pub priority_flag: u8,
+    pub forwarded_at: Instant,
+    pub rejected_with: Option<u32>, // SUBMIT_SM_RESP error status from the client
+    pub reference: u16, // user_message_reference, assigned on insert
+    expires_at: Option<Instant>,
+}
+
+impl PendingRequest {
+    pub fn new(sequence_number: u32, msisdn: &str, session_id: &str, priority_flag: u8, timeout: Duration) -> Self {
+        let forwarded_at = Instant::now();
+        PendingRequest {
+            sequence_number,
+            msisdn: msisdn.to_string(),
+            session_id: session_id.to_string(),
+            priority_flag,
+            forwarded_at,
+            rejected_with: None,
+            reference: 0,
+            expires_at: (!timeout.is_zero()).then(|| forwarded_at + timeout),
+        }
+    }
+}
+
+// Forwarded requests keyed by the sequence number of the SUBMIT_SM sent to the client. The
+// user_message_reference TLV only holds 16 bits, so references come from their own counter and
+// skip any still in use instead of truncating the 32-bit sequence number.
+#[derive(Debug, Default)]
+pub struct PendingRequests {
+    requests: Mutex<HashMap<u32, PendingRequest>>,
+    last_reference: Mutex<u16>,
+}
+
+impl PendingRequests {
+    // Returns the reference the forwarded SUBMIT_SM must carry
+    pub fn insert(&self, mut request: PendingRequest) -> u16 {
+        let mut requests = self.requests.lock().unwrap();
+        let mut last_reference = self.last_reference.lock().unwrap();
+        let mut reference = *last_reference;
+        for _ in 0..u16::MAX {
+            reference = reference.checked_add(1).unwrap_or(1);
+            if !requests.values().any(|pending| pending.reference == reference) {
+                break;
+            }
+        }
+        *last_reference = reference;
+        request.reference = reference;
+        requests.insert(request.sequence_number, request);
+        reference
+    }
+
+    // The forward never reached the client's queue, so nothing will answer it
+    pub fn remove(&self, sequence_number: u32) -> Option<PendingRequest> {
+        self.requests.lock().unwrap().remove(&sequence_number)
+    }
+
+    // A response carrying user_message_reference belongs to exactly that request; one without it
+    // is matched to the oldest outstanding request for its MSISDN
+    pub fn resolve(&self, reference: Option<u16>, msisdn: &str) -> Option<PendingRequest> {
+        let mut requests = self.requests.lock().unwrap();
+        let sequence_number = match reference {
+            Some(reference) => requests
+                .values()
+                .filter(|request| request.reference == reference)
+                .min_by_key(|request| request.forwarded_at)?
+                .sequence_number,
+            None => requests
+                .values()
+                .filter(|request| request.msisdn == msisdn)
+                .min_by_key(|request| request.forwarded_at)?
+                .sequence_number,
+        };
+        requests.remove(&sequence_number)
+    }
+
+    // The client refused the forward, so the request fails on the next expiry sweep
+    pub fn reject(&self, sequence_number: u32, status: u32) -> bool {
+        let mut requests = self.requests.lock().unwrap();
+        let Some(request) = requests.get_mut(&sequence_number) else {
+            return false;
+        };
+        request.rejected_with = Some(status);
+        request.expires_at = Some(Instant::now());
+        true
+    }
+
+    pub fn take_expired(&self) -> Vec<PendingRequest> {
+        let now = Instant::now();
+        let mut requests = self.requests.lock().unwrap();
+        let expired: Vec<u32> = requests
+            .values()
+            .filter(|request| request.expires_at.is_some_and(|at| at <= now))
+            .map(|request| request.sequence_number)
+            .collect();
+        let mut expired: Vec<PendingRequest> = expired.iter().filter_map(|seq| requests.remove(seq)).collect();
+        expired.sort_by_key(|request| request.forwarded_at);
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(sequence_number: u32, msisdn: &str) -> PendingRequest {
+        PendingRequest::new(sequence_number, msisdn, "session", 0, Duration::from_secs(30))
+    }
+
+    #[test]
+    fn test_references_do_not_collide_when_sequences_share_low_bits() {
+        let pending = PendingRequests::default();
+        let first = pending.insert(request(1, "111"));
+        let second = pending.insert(request(0x10001, "222"));
+        assert_ne!(first, second);
+
+        assert_eq!(pending.resolve(Some(second), "").unwrap().msisdn, "222");
+        assert_eq!(pending.resolve(Some(first), "").unwrap().msisdn, "111");
+    }
+
+    #[test]
+    fn test_references_skip_zero_and_those_in_use() {
+        let pending = PendingRequests::default();
+        *pending.last_reference.lock().unwrap() = u16::MAX - 1;
+        assert_eq!(pending.insert(request(1, "111")), u16::MAX);
+        // Wraps past 0
+        assert_eq!(pending.insert(request(2, "111")), 1);
+
+        *pending.last_reference.lock().unwrap() = u16::MAX - 1;
+        assert_eq!(pending.insert(request(3, "111")), 2);
+    }
+
+    #[test]
+    fn test_resolve_without_reference_takes_oldest_for_msisdn() {
+        let pending = PendingRequests::default();
+        pending.insert(request(1, "111"));
+        pending.insert(request(2, "222"));
+        pending.insert(request(3, "111"));
+
+        assert_eq!(pending.resolve(None, "111").unwrap().sequence_number, 1);
+        assert_eq!(pending.resolve(None, "111").unwrap().sequence_number, 3);
+        assert!(pending.resolve(None, "111").is_none());
+    }
+
+    #[test]
+    fn test_removed_request_is_not_expired_later() {
+        let pending = PendingRequests::default();
+        pending.insert(PendingRequest::new(1, "111", "session", 0, Duration::from_millis(1)));
+        assert!(pending.remove(1).is_some());
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(pending.take_expired().is_empty());
+    }
+
+    #[test]
+    fn test_rejected_request_expires_immediately() {
+        let pending = PendingRequests::default();
+        pending.insert(request(1, "111"));
+        assert!(pending.reject(1, 0x45));
+        assert!(!pending.reject(2, 0x45));
+
+        let expired = pending.take_expired();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].rejected_with, Some(0x45));
+    }
+}
@@ -0,0 +1,272 @@
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use ussd_common::encoding::TextEncoding;
+
+use crate::codec::PduReadBuffer;
+use crate::transport::SmppStream;
+use crate::config::Config;
+use crate::pdu::{
+    build_ussd_deliver_sm, build_ussd_submit_sm, message_text, DeliverSm, SmppError, SmppPdu, SubmitSm,
+    BIND_TRANSCEIVER, BIND_TRANSCEIVER_RESP, DELIVER_SM, DELIVER_SM_RESP, ENQUIRE_LINK, ENQUIRE_LINK_RESP, ESME_ROK,
+    SUBMIT_SM, SUBMIT_SM_RESP, UNBIND, USSD_NOTIFY, USSD_PSSR_RESPONSE, USSD_TERMINATE_NOTIFY,
+};
+use crate::server::UssdSmppServer;
+
+// Identities used by the in-process demo components and the self-test
+pub(crate) const DEMO_FORWARDING_CLIENT: &str = "ForwardingClient";
+pub(crate) const DEMO_USER_CLIENT: &str = "USSDMobileUser";
+pub(crate) const DEMO_MSISDN: &str = "1234567890";
+pub(crate) const DEMO_SERVICE_CODE: &str = "*555#";
+
+// Runs the server, a sample forwarding client and an interactive phone in one process.
+// The clients talk SMPP to the server over in-process channels, so no port is opened.
+pub fn run_all_in_one() -> io::Result<()> {
+    let mut config = Config::default();
+    config.client_simulator.forwarding_clients = vec![DEMO_FORWARDING_CLIENT.to_string()];
+    config.client_simulator.user_clients = vec![DEMO_USER_CLIENT.to_string()];
+    // Keep the demo deterministic: no simulated failures or dropped responses
+    config.response_percentage.always_succeed();
+
+    let service_codes = config.ussd.service_codes.clone();
+    let server = UssdSmppServer::new(config);
+    server.start_services()?;
+    let config = &server.config.get();
+
+    let forwarder = DemoClient::bind(server.connect(), config, DEMO_FORWARDING_CLIENT, "forward123")?;
+    thread::spawn(move || {
+        if let Err(e) = run_forwarding_client(forwarder) {
+            println!("Demo forwarding client stopped: {}", e);
+        }
+    });
+
+    let mut phone = DemoClient::bind(server.connect(), config, DEMO_USER_CLIENT, "mobile123")?;
+
+    println!();
+    println!("╔════════════════════════════════════════╗");
+    println!("║          USSD ALL-IN-ONE DEMO          ║");
+    println!("╚════════════════════════════════════════╝");
+    println!("📱 Phone MSISDN: {}", DEMO_MSISDN);
+    println!("🌐 Built-in server codes: {}", service_codes.join(", "));
+    println!("🏢 Forwarded demo code: {}", DEMO_SERVICE_CODE);
+    println!();
+
+    run_phone(&mut phone)
+}
+
+fn run_phone(phone: &mut DemoClient) -> io::Result<()> {
+    loop {
+        let code = prompt(&format!("Dial a USSD code (e.g. *123# or {}), or 'q' to quit: ", DEMO_SERVICE_CODE))?;
+        if code.is_empty() {
+            continue;
+        }
+        if code.eq_ignore_ascii_case("q") {
+            break;
+        }
+
+        let mut input = code;
+        loop {
+            let (response, service_op) = match phone.ussd_exchange(DEMO_MSISDN, &input) {
+                Ok(exchange) => exchange,
+                Err(e) => {
+                    println!("❌ USSD request failed: {}", e);
+                    break;
+                }
+            };
+
+            println!();
+            println!("┌────────────────────────────────────────┐");
+            println!("│              USSD RESPONSE             │");
+            println!("└────────────────────────────────────────┘");
+            println!("{}", response);
+            println!();
+
+            if is_session_end(&response, service_op) {
+                println!("📱 USSD session ended.");
+                println!();
+                break;
+            }
+
+            input = prompt("Your reply (empty to cancel): ")?;
+            if input.is_empty() {
+                println!("📱 USSD session cancelled.");
+                println!();
+                break;
+            }
+        }
+    }
+
+    phone.unbind()?;
+    println!("📱 Goodbye!");
+    Ok(())
+}
+
+// The screen's ussd_service_op says whether the session is over; its text is only a guess, for
+// a gateway that sends none
+fn is_session_end(response: &str, service_op: Option<u8>) -> bool {
+    match service_op {
+        Some(op) => matches!(op, USSD_NOTIFY | USSD_TERMINATE_NOTIFY | USSD_PSSR_RESPONSE),
+        None => response.contains("Goodbye") || response.contains("Thank you") || response.contains("session has ended"),
+    }
+}
+
+fn prompt(text: &str) -> io::Result<String> {
+    print!("{}", text);
+    io::stdout().flush()?;
+    let mut line = String::new();
+    if io::stdin().read_line(&mut line)? == 0 {
+        // EOF on stdin behaves like quitting
+        return Ok("q".to_string());
+    }
+    Ok(line.trim().to_string())
+}
+
+pub(crate) fn run_forwarding_client(mut client: DemoClient) -> io::Result<()> {
+    loop {
+        let pdu = client.read_pdu()?;
+        match pdu.header.command_id {
+            SUBMIT_SM => {
+                let submit_sm = SubmitSm::decode(&pdu.body)?;
+                let message_id = format!("DEMO{:08}", pdu.header.sequence_number);
+                client.send_pdu(SUBMIT_SM_RESP, pdu.header.sequence_number, format!("{}\0", message_id).into_bytes())?;
+
+                // Forwarding clients always send one septet per octet
+                let reply = sample_menu(&message_text(submit_sm.data_coding, submit_sm.message(), false));
+                let sequence = client.next_sequence();
+                client.send(build_ussd_deliver_sm(&submit_sm.source_addr, &reply, 0, sequence, None, TextEncoding::Auto, &client.config))?;
+            }
+            ENQUIRE_LINK => {
+                client.send_pdu(ENQUIRE_LINK_RESP, pdu.header.sequence_number, Vec::new())?;
+            }
+            DELIVER_SM_RESP => {}
+            _ => {}
+        }
+    }
+}
+
+// A tiny stateless service so the forwarding path can be tried without any config. Screens stay
+// inside the GSM 7-bit alphabet that data_coding 0 carries; the terms screen is longer than
+// short_message allows, so it travels in message_payload.
+fn sample_menu(input: &str) -> String {
+    let main_menu = "Demo Bank\n1. Check Balance\n2. Mini Statement\n3. Terms\n4. Exit";
+    match input.trim() {
+        DEMO_SERVICE_CODE | "0" => main_menu.to_string(),
+        "1" => "Balance: $1,250.00\n0. Main menu".to_string(),
+        "2" => "Last transactions:\n- Coffee $3.50\n- Salary +$2,000.00\n0. Main menu".to_string(),
+        "3" => format!("{}\n0. Main menu", DEMO_TERMS),
+        "4" => "Thank you for trying the demo. Goodbye!".to_string(),
+        _ => format!("Invalid option.\n{}", main_menu),
+    }
+}
+
+const DEMO_TERMS: &str = "Terms: Demo Bank is a simulation. Balances and statements shown here are \
+sample data and no money moves. Screens longer than 255 octets, like this one, are sent in the \
+message_payload TLV instead of short_message, so handsets and gateways that support it see the \
+whole text without truncation.";
+
+// The first configured user client and its password, for in-process subscribers that bind to a
+// server started from a config file. Without [[smpp.accounts]] any password is accepted.
+pub(crate) fn user_client_credentials(config: &Config) -> Option<(String, String)> {
+    let system_id = config.client_simulator.user_clients.first()?;
+    let password = config
+        .smpp
+        .accounts
+        .iter()
+        .find(|account| &account.system_id == system_id)
+        .map_or("mobile123".to_string(), |account| account.password.clone());
+    Some((system_id.clone(), password))
+}
+
+// Minimal blocking ESME used by the demo phone and forwarding client, and by the bench
+pub(crate) struct DemoClient {
+    stream: SmppStream,
+    reader: PduReadBuffer,
+    sequence: u32,
+    config: Arc<Config>,
+}
+
+impl DemoClient {
+    pub(crate) fn bind(stream: SmppStream, config: &Arc<Config>, system_id: &str, password: &str) -> Result<Self, SmppError> {
+        let mut client = DemoClient { stream, reader: PduReadBuffer::new(), sequence: 0, config: Arc::clone(config) };
+
+        let mut body = Vec::new();
+        body.extend_from_slice(system_id.as_bytes());
+        body.push(0);
+        body.extend_from_slice(password.as_bytes());
+        body.push(0);
+        body.extend_from_slice(b"DEMO\0"); // system_type
+        body.push(0x34); // interface_version
+        body.push(1); // addr_ton
+        body.push(1); // addr_npi
+        body.push(0); // address_range
+
+        let sequence = client.next_sequence();
+        client.send_pdu(BIND_TRANSCEIVER, sequence, body)?;
+        let response = client.read_pdu()?;
+        if response.header.command_id != BIND_TRANSCEIVER_RESP || response.header.command_status != ESME_ROK {
+            return Err(SmppError::Auth { status: response.header.command_status, system_id: system_id.to_string() });
+        }
+        Ok(client)
+    }
+
+    pub(crate) fn ussd_request(&mut self, msisdn: &str, input: &str) -> Result<String, SmppError> {
+        self.ussd_exchange(msisdn, input).map(|(text, _)| text)
+    }
+
+    // The screen sent back and its ussd_service_op, which is set when the screen closes the session
+    pub(crate) fn ussd_exchange(&mut self, msisdn: &str, input: &str) -> Result<(String, Option<u8>), SmppError> {
+        let sequence = self.next_sequence();
+        self.send(build_ussd_submit_sm(msisdn, "123", input, 0, sequence, None))?;
+
+        self.stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+        let result = loop {
+            let pdu = match self.read_pdu() {
+                Ok(pdu) => pdu,
+                Err(e) => break Err(e.into()),
+            };
+            match pdu.header.command_id {
+                SUBMIT_SM_RESP if pdu.header.command_status != ESME_ROK => {
+                    break Err(SmppError::protocol(pdu.header.command_status, "SUBMIT_SM rejected"));
+                }
+                DELIVER_SM => {
+                    self.send_pdu(DELIVER_SM_RESP, pdu.header.sequence_number, Vec::new())?;
+                    let deliver_sm = DeliverSm::decode(&pdu.body)?;
+                    // The server packs septets towards subscribers when `smpp.gsm7_packing` is on
+                    let text = message_text(deliver_sm.data_coding, deliver_sm.message(), self.config.smpp.gsm7_packing);
+                    break Ok((text, deliver_sm.ussd_service_op()));
+                }
+                ENQUIRE_LINK => {
+                    self.send_pdu(ENQUIRE_LINK_RESP, pdu.header.sequence_number, Vec::new())?;
+                }
+                _ => {}
+            }
+        };
+        self.stream.set_read_timeout(None)?;
+        result
+    }
+
+    pub(crate) fn unbind(&mut self) -> io::Result<()> {
+        let sequence = self.next_sequence();
+        self.send_pdu(UNBIND, sequence, Vec::new())
+    }
+
+    fn next_sequence(&mut self) -> u32 {
+        self.sequence += 1;
+        self.sequence
+    }
+
+    fn send_pdu(&mut self, command_id: u32, sequence_number: u32, body: Vec<u8>) -> io::Result<()> {
+        self.send(SmppPdu::new(command_id, ESME_ROK, sequence_number, body))
+    }
+
+    fn send(&mut self, pdu: SmppPdu) -> io::Result<()> {
+        self.stream.write_all(&pdu.to_bytes())?;
+        self.stream.flush()
+    }
+
+    fn read_pdu(&mut self) -> io::Result<SmppPdu> {
+        self.reader.read_pdu(&mut self.stream)
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+// Peers tracked before idle ones are forgotten
+const MAX_TRACKED_PEERS: usize = 4096;
+
+// `[server.flood_protection]`: per-IP checks made as each connection is accepted, before any TLS
+// handshake or bind, e.g.
+//   connections_per_second = 2.0
+//   deny = ["10.0.5.0/24"]
+//   ban_after_malformed = 5
+// Lists take single addresses or CIDR ranges. With `allow` set only those peers may connect, and
+// `deny` wins over `allow`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FloodProtectionConfig {
+    pub connections_per_second: f64, // New connections per IP (0 = unlimited)
+    pub burst: u32, // Connections allowed back to back (0 = one second's worth)
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+    pub ban_after_malformed: u32, // Malformed PDUs within ban_window_secs that ban the IP (0 = never)
+    pub ban_window_secs: u64,
+    pub ban_secs: u64, // How long a ban lasts
+}
+
+impl Default for FloodProtectionConfig {
+    fn default() -> Self {
+        FloodProtectionConfig {
+            connections_per_second: 0.0,
+            burst: 0,
+            allow: Vec::new(),
+            deny: Vec::new(),
+            ban_after_malformed: 0,
+            ban_window_secs: 60,
+            ban_secs: 300,
+        }
+    }
+}
+
+impl FloodProtectionConfig {
+    // Checks the lists, so a typo fails at startup rather than letting everyone in
+    pub fn validate(&self) -> Result<(), String> {
+        for entry in self.allow.iter().chain(&self.deny) {
+            IpRange::parse(entry)?;
+        }
+        Ok(())
+    }
+
+    fn burst(&self) -> f64 {
+        if self.burst > 0 { self.burst as f64 } else { self.connections_per_second.ceil() }
+    }
+
+    fn listed(list: &[String], ip: IpAddr) -> bool {
+        list.iter().filter_map(|entry| IpRange::parse(entry).ok()).any(|range| range.contains(ip))
+    }
+}
+
+// An address, or a network in CIDR notation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct IpRange {
+    network: IpAddr,
+    prefix: u32,
+}
+
+impl IpRange {
+    fn parse(entry: &str) -> Result<Self, String> {
+        let (address, prefix) = match entry.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (entry, None),
+        };
+        let network: IpAddr = address.trim().parse().map_err(|_| format!("flood_protection: {:?} is not an IP address or range", entry))?;
+        let bits = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.trim().parse().ok().filter(|prefix| *prefix <= bits),
+            None => Some(bits),
+        };
+        let Some(prefix) = prefix else {
+            return Err(format!("flood_protection: {:?} has a prefix length above {}", entry, bits));
+        };
+        Ok(IpRange { network: network.to_canonical(), prefix })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+// Why a connection was turned away
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refusal {
+    Denied,
+    NotAllowed,
+    Banned,
+    RateLimited,
+}
+
+impl Refusal {
+    pub fn reason(self) -> &'static str {
+        match self {
+            Refusal::Denied => "on the deny list",
+            Refusal::NotAllowed => "not on the allow list",
+            Refusal::Banned => "banned for malformed PDUs",
+            Refusal::RateLimited => "connecting too fast",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Peer {
+    tokens: f64,
+    refilled_at: Instant,
+    malformed: VecDeque<Instant>, // Within the ban window
+    banned_until: Option<Instant>,
+}
+
+// Connection buckets, malformed-PDU counts and bans per peer address. The config is passed in on
+// each call so a reload applies to the next connection.
+#[derive(Debug, Default)]
+pub struct FloodGuard {
+    peers: Mutex<HashMap<IpAddr, Peer>>,
+}
+
+impl FloodGuard {
+    // Whether a new connection from `ip` may be served
+    pub fn admit(&self, config: &FloodProtectionConfig, ip: IpAddr) -> Result<(), Refusal> {
+        self.admit_at(config, ip, Instant::now())
+    }
+
+    fn admit_at(&self, config: &FloodProtectionConfig, ip: IpAddr, now: Instant) -> Result<(), Refusal> {
+        let ip = ip.to_canonical();
+        if FloodProtectionConfig::listed(&config.deny, ip) {
+            return Err(Refusal::Denied);
+        }
+        if !config.allow.is_empty() && !FloodProtectionConfig::listed(&config.allow, ip) {
+            return Err(Refusal::NotAllowed);
+        }
+
+        let mut peers = self.peers.lock().unwrap();
+        if peers.len() >= MAX_TRACKED_PEERS {
+            let window = Duration::from_secs(config.ban_window_secs);
+            let recent = |at: Instant| now.saturating_duration_since(at) < window;
+            peers.retain(|_, peer| {
+                peer.banned_until.is_some_and(|until| until > now) || recent(peer.refilled_at) || peer.malformed.back().is_some_and(|at| recent(*at))
+            });
+        }
+        let peer = Self::peer(&mut peers, ip, config.burst(), now);
+        if peer.banned_until.is_some_and(|until| until > now) {
+            return Err(Refusal::Banned);
+        }
+        if config.connections_per_second <= 0.0 {
+            return Ok(());
+        }
+        let elapsed = now.saturating_duration_since(peer.refilled_at).as_secs_f64();
+        peer.tokens = (peer.tokens + elapsed * config.connections_per_second).min(config.burst());
+        peer.refilled_at = now;
+        if peer.tokens < 1.0 {
+            return Err(Refusal::RateLimited);
+        }
+        peer.tokens -= 1.0;
+        Ok(())
+    }
+
+    // Counts a malformed PDU from `ip`; true when it earns the address a ban
+    pub fn malformed(&self, config: &FloodProtectionConfig, ip: IpAddr) -> bool {
+        self.malformed_at(config, ip, Instant::now())
+    }
+
+    fn malformed_at(&self, config: &FloodProtectionConfig, ip: IpAddr, now: Instant) -> bool {
+        if config.ban_after_malformed == 0 {
+            return false;
+        }
+        let mut peers = self.peers.lock().unwrap();
+        let peer = Self::peer(&mut peers, ip.to_canonical(), config.burst(), now);
+        let window = Duration::from_secs(config.ban_window_secs);
+        while peer.malformed.front().is_some_and(|at| now.saturating_duration_since(*at) >= window) {
+            peer.malformed.pop_front();
+        }
+        peer.malformed.push_back(now);
+        if peer.malformed.len() < config.ban_after_malformed as usize {
+            return false;
+        }
+        peer.malformed.clear();
+        peer.banned_until = Some(now + Duration::from_secs(config.ban_secs));
+        true
+    }
+
+    fn peer(peers: &mut HashMap<IpAddr, Peer>, ip: IpAddr, burst: f64, now: Instant) -> &mut Peer {
+        peers.entry(ip).or_insert_with(|| Peer { tokens: burst, refilled_at: now, malformed: VecDeque::new(), banned_until: None })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn test_lists_and_connection_rate() {
+        let config: FloodProtectionConfig = toml::from_str(
+            "connections_per_second = 2.0\nburst = 3\nallow = [\"10.0.0.0/8\", \"::1\"]\ndeny = [\"10.0.5.0/24\"]\n",
+        )
+        .unwrap();
+        config.validate().unwrap();
+        let guard = FloodGuard::default();
+        let start = Instant::now();
+        assert_eq!(guard.admit_at(&config, ip("10.0.5.9"), start), Err(Refusal::Denied));
+        assert_eq!(guard.admit_at(&config, ip("192.168.1.4"), start), Err(Refusal::NotAllowed));
+        // IPv4-mapped IPv6 peers match IPv4 ranges
+        assert_eq!(guard.admit_at(&config, ip("::ffff:10.0.5.9"), start), Err(Refusal::Denied));
+        assert_eq!(guard.admit_at(&config, ip("::1"), start), Ok(()));
+
+        let admitted = (0..5).filter(|_| guard.admit_at(&config, ip("10.1.2.3"), start).is_ok()).count();
+        assert_eq!(admitted, 3);
+        assert_eq!(guard.admit_at(&config, ip("10.1.2.3"), start), Err(Refusal::RateLimited));
+        // Each address has its own bucket, refilled at the configured rate
+        assert_eq!(guard.admit_at(&config, ip("10.1.2.4"), start), Ok(()));
+        assert_eq!(guard.admit_at(&config, ip("10.1.2.3"), start + Duration::from_millis(500)), Ok(()));
+
+        let bad: FloodProtectionConfig = toml::from_str("deny = [\"10.0.0.0/33\"]\n").unwrap();
+        assert_eq!(bad.validate().unwrap_err(), "flood_protection: \"10.0.0.0/33\" has a prefix length above 32");
+        let bad: FloodProtectionConfig = toml::from_str("allow = [\"lab-host\"]\n").unwrap();
+        assert_eq!(bad.validate().unwrap_err(), "flood_protection: \"lab-host\" is not an IP address or range");
+    }
+
+    #[test]
+    fn test_malformed_pdus_ban_for_a_while() {
+        let config = FloodProtectionConfig { ban_after_malformed: 3, ban_window_secs: 10, ban_secs: 60, ..Default::default() };
+        let guard = FloodGuard::default();
+        let peer = ip("192.0.2.7");
+        let start = Instant::now();
+        assert!(!guard.malformed_at(&config, peer, start));
+        assert!(!guard.malformed_at(&config, peer, start + Duration::from_secs(1)));
+        // The first one has left the window
+        assert!(!guard.malformed_at(&config, peer, start + Duration::from_secs(10)));
+        assert_eq!(guard.admit_at(&config, peer, start + Duration::from_secs(10)), Ok(()));
+        assert!(guard.malformed_at(&config, peer, start + Duration::from_millis(10_500)));
+
+        assert_eq!(guard.admit_at(&config, peer, start + Duration::from_secs(70)), Err(Refusal::Banned));
+        assert_eq!(guard.admit_at(&config, ip("192.0.2.8"), start + Duration::from_secs(70)), Ok(()));
+        assert_eq!(guard.admit_at(&config, peer, start + Duration::from_secs(71)), Ok(()));
+
+        let never = FloodProtectionConfig::default();
+        assert!((0..100).all(|_| !guard.malformed_at(&never, peer, start)));
+    }
+}
@@ -0,0 +1,307 @@
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::control::Controller;
+use crate::transport::SmppStream;
+
+// Opens an in-process connection to the server, for requests injected through the API
+pub type Connector = Arc<dyn Fn() -> SmppStream + Send + Sync>;
+
+// gRPC control API (proto/control.proto) for test orchestrators; needs `--features grpc`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GrpcConfig {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+}
+
+impl Default for GrpcConfig {
+    fn default() -> Self {
+        GrpcConfig {
+            enabled: false,
+            host: "127.0.0.1".to_string(),
+            port: 50051,
+        }
+    }
+}
+
+// Binds [grpc] host/port (port 0 picks a free one) and serves the API on its own runtime
+#[cfg(feature = "grpc")]
+pub fn spawn(config: &GrpcConfig, controller: Controller, connect: Connector) -> io::Result<SocketAddr> {
+    service::spawn(config, controller, connect)
+}
+
+#[cfg(not(feature = "grpc"))]
+pub fn spawn(_config: &GrpcConfig, _controller: Controller, _connect: Connector) -> io::Result<SocketAddr> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "[grpc] is enabled but the simulator was built without the grpc feature (cargo build --features grpc)",
+    ))
+}
+
+#[cfg(feature = "grpc")]
+pub mod service {
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+    use std::sync::mpsc::RecvTimeoutError;
+    use std::thread;
+    use std::time::Duration;
+
+    use log::info;
+    use tokio::sync::mpsc;
+    use tokio_stream::wrappers::ReceiverStream;
+    use tonic::transport::Server;
+    use tonic::transport::server::TcpIncoming;
+    use tonic::{Code, Request, Response, Status};
+
+    use super::*;
+    use crate::admin::SessionControl;
+    use crate::control::{RateUpdate, ResponsePercentages};
+    use crate::demo::{self, DemoClient};
+    use crate::pdu::{SmppError, USSD_NOTIFY, USSD_PSSR_RESPONSE, USSD_TERMINATE_NOTIFY};
+    use crate::timeline::ResponseRates;
+    use crate::webhooks::{EndReason, EventKind, WebhookEvent};
+
+    pub mod proto {
+        tonic::include_proto!("ussd.control.v1");
+    }
+
+    use proto::simulator_control_server::{SimulatorControl, SimulatorControlServer};
+
+    // Events a watcher may fall behind by before newer ones are dropped for it
+    const EVENT_BUFFER: usize = 1000;
+
+    #[derive(Clone)]
+    struct ControlService {
+        controller: Controller,
+        connect: Connector,
+        phones: Arc<Mutex<Vec<DemoClient>>>, // Idle in-process user clients for injected requests
+    }
+
+    pub(super) fn spawn(config: &GrpcConfig, controller: Controller, connect: Connector) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind((config.host.as_str(), config.port))?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().thread_name("grpc").build()?;
+        let service = ControlService { controller, connect, phones: Arc::default() };
+        info!("🛰️  gRPC control API listening on {}", addr);
+
+        thread::spawn(move || {
+            let served = runtime.block_on(async move {
+                let incoming = TcpIncoming::from_listener(tokio::net::TcpListener::from_std(listener)?, true, None)?;
+                Server::builder()
+                    .add_service(SimulatorControlServer::new(service))
+                    .serve_with_incoming(incoming)
+                    .await?;
+                Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+            });
+            if let Err(e) = served {
+                info!("⚠️  gRPC control API stopped: {}", e);
+            }
+        });
+        Ok(addr)
+    }
+
+    // Why an injected request failed; turned into a Status once off the blocking thread
+    type Failure = (Code, String);
+
+    impl ControlService {
+        // An idle subscriber connection, or a new one bound as the first configured user client
+        fn phone(&self) -> Result<DemoClient, Failure> {
+            if let Some(phone) = self.phones.lock().unwrap().pop() {
+                return Ok(phone);
+            }
+            let config = self.controller.config.get();
+            let (system_id, password) = demo::user_client_credentials(&config).ok_or_else(|| {
+                (Code::FailedPrecondition, "injected requests bind as a user client; client_simulator.user_clients is empty".to_string())
+            })?;
+            DemoClient::bind((self.connect)(), &config, &system_id, &password).map_err(|e| (Code::Unavailable, e.to_string()))
+        }
+
+        fn inject(&self, request: &proto::InjectRequestMessage) -> Result<proto::InjectReply, Failure> {
+            if request.msisdn.is_empty() || request.input.is_empty() {
+                return Err((Code::InvalidArgument, "msisdn and input are required".to_string()));
+            }
+            let mut phone = self.phone()?;
+            let (text, service_op) = phone.ussd_exchange(&request.msisdn, &request.input).map_err(|e| match e {
+                SmppError::Io(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                    (Code::DeadlineExceeded, "the network did not answer".to_string())
+                }
+                e => (Code::Unavailable, e.to_string()),
+            })?;
+            // A connection that failed is dropped, so a late answer cannot be taken for the next one's
+            self.phones.lock().unwrap().push(phone);
+
+            let session_ended = matches!(service_op, Some(USSD_NOTIFY | USSD_TERMINATE_NOTIFY | USSD_PSSR_RESPONSE));
+            let session_id = match session_ended {
+                true => String::new(),
+                false => self.controller.ussd_sessions.read(&request.msisdn, |session| session.session_id.clone()).unwrap_or_default(),
+            };
+            Ok(proto::InjectReply { text, session_ended, session_id })
+        }
+    }
+
+    #[tonic::async_trait]
+    impl SimulatorControl for ControlService {
+        async fn inject_request(&self, request: Request<proto::InjectRequestMessage>) -> Result<Response<proto::InjectReply>, Status> {
+            let service = self.clone();
+            let request = request.into_inner();
+            // The exchange blocks on the in-process SMPP connection
+            tokio::task::spawn_blocking(move || service.inject(&request))
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?
+                .map(Response::new)
+                .map_err(|(code, message)| Status::new(code, message))
+        }
+
+        type WatchSessionsStream = ReceiverStream<Result<proto::SessionEvent, Status>>;
+
+        async fn watch_sessions(&self, request: Request<proto::WatchSessionsRequest>) -> Result<Response<Self::WatchSessionsStream>, Status> {
+            let msisdn = request.into_inner().msisdn;
+            let events = self.controller.connection_manager.webhooks.subscribe(EVENT_BUFFER);
+            let (sender, receiver) = mpsc::channel(EVENT_BUFFER);
+            // Carries events from the emitter to the stream until the watcher hangs up
+            thread::spawn(move || loop {
+                match events.recv_timeout(Duration::from_secs(1)) {
+                    Ok(event) if msisdn.is_empty() || event.msisdn == msisdn => {
+                        if sender.blocking_send(Ok(session_event(event))).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(RecvTimeoutError::Timeout) if !sender.is_closed() => {}
+                    Err(_) => break,
+                }
+            });
+            Ok(Response::new(ReceiverStream::new(receiver)))
+        }
+
+        async fn list_sessions(&self, _request: Request<proto::ListSessionsRequest>) -> Result<Response<proto::ListSessionsReply>, Status> {
+            let sessions = self
+                .controller
+                .ussd_sessions()
+                .into_iter()
+                .map(|session| proto::UssdSession {
+                    msisdn: session.msisdn,
+                    session_id: session.session_id,
+                    state: session.state,
+                    service_code: session.service_code,
+                    menu_level: session.menu_level.into(),
+                    idle_secs: session.idle_secs,
+                    forward_route: session.forward_route,
+                })
+                .collect();
+            Ok(Response::new(proto::ListSessionsReply { sessions }))
+        }
+
+        async fn get_fault_profile(&self, _request: Request<proto::GetFaultProfileRequest>) -> Result<Response<proto::FaultRates>, Status> {
+            Ok(Response::new(fault_rates(self.controller.response_percentage())))
+        }
+
+        async fn set_fault_profile(&self, request: Request<proto::FaultProfile>) -> Result<Response<proto::FaultRates>, Status> {
+            let profile = request.into_inner();
+            let update = RateUpdate {
+                failure_percentage: profile.failure_percentage,
+                no_response_percentage: profile.no_response_percentage,
+                codes: profile.codes,
+            };
+            match self.controller.set_response_percentage(&update) {
+                Ok(percentages) => Ok(Response::new(fault_rates(percentages))),
+                Err(e) => Err(Status::invalid_argument(e)),
+            }
+        }
+
+        async fn reset_fault_profile(&self, _request: Request<proto::ResetFaultProfileRequest>) -> Result<Response<proto::FaultRates>, Status> {
+            Ok(Response::new(fault_rates(self.controller.reset_response_percentage())))
+        }
+    }
+
+    fn session_event(event: WebhookEvent) -> proto::SessionEvent {
+        use proto::session_event::{EndReason as Reason, Kind};
+        let kind = match event.event {
+            EventKind::SessionStart => Kind::SessionStart,
+            EventKind::Request => Kind::Request,
+            EventKind::Response => Kind::Response,
+            EventKind::SessionEnd => Kind::SessionEnd,
+        };
+        let reason = match event.reason {
+            Some(EndReason::Released) => Reason::Released,
+            Some(EndReason::Timeout) => Reason::Timeout,
+            Some(EndReason::Replaced) => Reason::Replaced,
+            Some(EndReason::Shutdown) => Reason::Shutdown,
+            None => Reason::Unspecified,
+        };
+        proto::SessionEvent {
+            kind: kind.into(),
+            session_id: event.session_id,
+            msisdn: event.msisdn,
+            service_code: event.service_code,
+            timestamp_ms: event.timestamp,
+            text: event.text,
+            service_op: event.service_op.map(u32::from),
+            reason: reason.into(),
+            duration_ms: event.duration_ms,
+        }
+    }
+
+    fn fault_rates(percentages: ResponsePercentages) -> proto::FaultRates {
+        let rates = |rates: ResponseRates| proto::ResponseRates { success: rates.success, failure: rates.failure, no_response: rates.no_response };
+        proto::FaultRates {
+            default: Some(rates(percentages.default)),
+            codes: percentages.codes.into_iter().map(|(code, code_rates)| (code, rates(code_rates))).collect(),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::config::Config;
+        use crate::server::UssdSmppServer;
+        use proto::simulator_control_client::SimulatorControlClient;
+        use proto::session_event::Kind;
+
+        #[test]
+        fn test_inject_watch_and_fault_profile() {
+            let server = UssdSmppServer::new(Config::deterministic());
+            let connecting = server.clone();
+            let grpc = GrpcConfig { enabled: true, port: 0, ..Default::default() };
+            let addr = spawn(&grpc, server.controller(), Arc::new(move || connecting.connect())).unwrap();
+
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            runtime.block_on(async {
+                let mut client = SimulatorControlClient::connect(format!("http://{}", addr)).await.unwrap();
+                let watch = proto::WatchSessionsRequest { msisdn: "1234567890".to_string() };
+                let mut events = client.watch_sessions(watch).await.unwrap().into_inner();
+
+                let dial = proto::InjectRequestMessage { msisdn: "1234567890".to_string(), input: "*123#".to_string() };
+                let reply = client.inject_request(dial).await.unwrap().into_inner();
+                assert!(!reply.session_ended);
+                let listed = client.list_sessions(proto::ListSessionsRequest {}).await.unwrap().into_inner().sessions;
+                assert_eq!(listed.len(), 1);
+                assert_eq!((listed[0].msisdn.as_str(), listed[0].session_id.as_str()), ("1234567890", reply.session_id.as_str()));
+
+                let mut kinds = Vec::new();
+                while kinds.len() < 3 {
+                    kinds.push(events.message().await.unwrap().unwrap().kind());
+                }
+                assert_eq!(kinds, vec![Kind::SessionStart, Kind::Request, Kind::Response]);
+
+                let profile = proto::FaultProfile { failure_percentage: Some(30.0), codes: vec!["*123#".to_string()], ..Default::default() };
+                let rates = client.set_fault_profile(profile).await.unwrap().into_inner();
+                assert_eq!(rates.codes["*123#"].failure, 30.0);
+                let too_much = proto::FaultProfile { failure_percentage: Some(101.0), ..Default::default() };
+                assert_eq!(client.set_fault_profile(too_much).await.unwrap_err().code(), tonic::Code::InvalidArgument);
+                let reset = client.reset_fault_profile(proto::ResetFaultProfileRequest {}).await.unwrap().into_inner();
+                assert_eq!(reset, client.get_fault_profile(proto::GetFaultProfileRequest {}).await.unwrap().into_inner());
+                assert_ne!(reset.codes["*123#"].failure, 30.0);
+
+                let empty = proto::InjectRequestMessage { msisdn: "1234567890".to_string(), input: String::new() };
+                assert_eq!(client.inject_request(empty).await.unwrap_err().code(), tonic::Code::InvalidArgument);
+            });
+        }
+    }
+}
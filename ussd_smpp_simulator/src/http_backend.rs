@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use ussd_common::http_client::{self, HttpTarget};
+
+// Unknown USSD codes answered by an HTTP service instead of an SMPP-bound forwarding client
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HttpBackendConfig {
+    pub enabled: bool,
+    pub url: String, // http://host[:port]/path the requests are POSTed to
+    pub codes: Vec<String>, // Codes sent to the backend, matched like ussd.service_codes (empty = every unknown code)
+    pub headers: BTreeMap<String, String>, // Extra request headers, e.g. Authorization
+    pub timeout_ms: u64, // Connect, write and read timeout for one request
+    pub error_message: String, // Sent, closing the session, when the backend fails or answers badly
+}
+
+impl Default for HttpBackendConfig {
+    fn default() -> Self {
+        HttpBackendConfig {
+            enabled: false,
+            url: String::new(),
+            codes: Vec::new(),
+            headers: BTreeMap::new(),
+            timeout_ms: 5000,
+            error_message: "Service temporarily unavailable.".to_string(),
+        }
+    }
+}
+
+// The JSON body POSTed for each request in a backend session
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct BackendRequest {
+    pub msisdn: String,
+    pub input: String, // The dialled code, then each reply
+    pub session_id: String,
+    pub service_code: String,
+}
+
+// What the backend answers with
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct BackendReply {
+    pub text: String,
+    #[serde(default, rename = "continue")]
+    pub continues: bool, // False, or missing, closes the session with USSD_NOTIFY
+}
+
+impl HttpBackendConfig {
+    // Checks the URL, so a typo fails at startup rather than on the first subscriber
+    pub fn validate(&self) -> Result<(), String> {
+        if self.enabled {
+            HttpTarget::parse(&self.url, "http_backend.url")?;
+        }
+        Ok(())
+    }
+
+    // Whether a dialled code that nothing else serves goes to the backend
+    pub fn serves(&self, code: &str) -> bool {
+        self.enabled && (self.codes.is_empty() || self.codes.iter().any(|mapped| code.starts_with(mapped.trim_end_matches('#'))))
+    }
+
+    pub fn call(&self, request: &BackendRequest) -> Result<BackendReply, String> {
+        let target = HttpTarget::parse(&self.url, "http_backend.url")?;
+        let body = serde_json::to_string(request).map_err(|e| e.to_string())?;
+        let response = http_client::post_json(&target, &self.headers, &body, Duration::from_millis(self.timeout_ms.max(1)))
+            .map_err(|e| format!("POST to {} failed: {}", target, e))?;
+        serde_json::from_str(&response).map_err(|e| format!("{} answered {:?}: {}", target, response, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use ussd_common::test_http;
+
+    // A backend that answers one request with `body` and returns the request it got
+    fn backend(body: &'static str) -> (HttpBackendConfig, thread::JoinHandle<String>) {
+        let (addr, handle) = test_http::serve_once(test_http::json_response(body));
+        let config = HttpBackendConfig {
+            enabled: true,
+            url: format!("http://{}/ussd", addr),
+            codes: vec!["*500#".to_string()],
+            ..Default::default()
+        };
+        (config, handle)
+    }
+
+    fn request(input: &str) -> BackendRequest {
+        BackendRequest {
+            msisdn: "1234567890".to_string(),
+            input: input.to_string(),
+            session_id: "S1".to_string(),
+            service_code: "*500#".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_requests_and_replies() {
+        let (config, handle) = backend(r#"{"text":"Pick one\n1. Yes","continue":true}"#);
+        config.validate().unwrap();
+        assert!(config.serves("*500*1#"));
+        assert!(!config.serves("*123#"));
+        let reply = config.call(&request("*500#")).unwrap();
+        assert_eq!(reply, BackendReply { text: "Pick one\n1. Yes".to_string(), continues: true });
+        let sent = handle.join().unwrap();
+        let body = &sent[sent.find("\r\n\r\n").unwrap() + 4..];
+        assert_eq!(serde_json::from_str::<BackendRequest>(body).unwrap(), request("*500#"));
+
+        // A reply without "continue" ends the session
+        let (config, _) = backend(r#"{"text":"Bye"}"#);
+        assert!(!config.call(&request("1")).unwrap().continues);
+        let (config, _) = backend("not json");
+        assert!(config.call(&request("1")).unwrap_err().contains("not json"));
+    }
+
+    #[test]
+    fn test_disabled_and_bad_urls() {
+        let mut config = HttpBackendConfig::default();
+        assert!(!config.serves("*500#"));
+        config.validate().unwrap();
+        config.enabled = true;
+        assert!(config.serves("*500#"));
+        config.url = "https://backend.local/".to_string();
+        assert!(config.validate().unwrap_err().contains("http_backend.url"));
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::outbound::OutboundQueue;
+use crate::pdu::SmppPdu;
+
+// DELIVER_SMs whose subscriber had no bound user client, held and retried instead of dropped
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DeliveryRetryConfig {
+    pub enabled: bool,
+    pub attempts: u32, // Retry passes a held DELIVER_SM survives without a route before it is dropped
+    pub interval_ms: u64, // Time between retry passes
+    pub max_per_msisdn: usize, // Oldest held DELIVER_SMs are dropped beyond this
+    pub spool: String, // JSON file keeping held DELIVER_SMs across restarts (empty = memory only)
+}
+
+impl Default for DeliveryRetryConfig {
+    fn default() -> Self {
+        DeliveryRetryConfig {
+            enabled: false,
+            attempts: 10,
+            interval_ms: 2000,
+            max_per_msisdn: 16,
+            spool: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct HeldDelivery {
+    priority_flag: u8,
+    attempts: u32,
+    frame: Vec<u8>, // The whole DELIVER_SM as first built, sequence number included
+}
+
+// Per-MSISDN queues of DELIVER_SMs waiting for the subscriber's client to bind again
+#[derive(Debug, Default)]
+pub struct DeliveryJournal {
+    config: Mutex<DeliveryRetryConfig>,
+    held: Mutex<BTreeMap<String, VecDeque<HeldDelivery>>>,
+}
+
+impl DeliveryJournal {
+    // Applies `[delivery_retry]` and reloads anything spooled by the previous run
+    pub fn start(&self, config: &DeliveryRetryConfig) -> io::Result<()> {
+        *self.config.lock().unwrap() = config.clone();
+        if !config.enabled || config.spool.is_empty() || !Path::new(&config.spool).exists() {
+            return Ok(());
+        }
+        let content = fs::read_to_string(&config.spool)?;
+        let held: BTreeMap<String, VecDeque<HeldDelivery>> =
+            serde_json::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", config.spool, e)))?;
+        let count: usize = held.values().map(VecDeque::len).sum();
+        if count > 0 {
+            info!("📥 Restored {} held DELIVER_SM(s) for {} MSISDN(s) from {}", count, held.len(), config.spool);
+        }
+        *self.held.lock().unwrap() = held;
+        Ok(())
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.lock().unwrap().enabled
+    }
+
+    pub fn interval_ms(&self) -> u64 {
+        self.config.lock().unwrap().interval_ms
+    }
+
+    // Keeps `pdu` for a later retry. False when retries are off, so the caller drops it as before.
+    pub fn hold(&self, msisdn: &str, priority_flag: u8, pdu: &SmppPdu) -> bool {
+        let config = self.config.lock().unwrap().clone();
+        if !config.enabled {
+            return false;
+        }
+        let mut held = self.held.lock().unwrap();
+        let queue = held.entry(msisdn.to_string()).or_default();
+        queue.push_back(HeldDelivery { priority_flag, attempts: 0, frame: pdu.to_bytes() });
+        while queue.len() > config.max_per_msisdn.max(1) {
+            queue.pop_front();
+            info!("🗑️  Held DELIVER_SMs for {} over max_per_msisdn, dropping the oldest", msisdn);
+        }
+        info!("📥 Holding DELIVER_SM for {} until its client binds ({} held)", msisdn, queue.len());
+        self.spool(&config, &held);
+        true
+    }
+
+    pub fn held_count(&self, msisdn: &str) -> usize {
+        self.held.lock().unwrap().get(msisdn).map_or(0, VecDeque::len)
+    }
+
+    // Hands each MSISDN's held DELIVER_SMs, oldest first, to the queue `route` finds for it.
+    // A retry pass (`count_attempt`) uses up one attempt of every MSISDN still without a route.
+    // Returns how many were released.
+    pub fn release(&self, route: impl Fn(&str) -> Option<Arc<OutboundQueue>>, count_attempt: bool) -> usize {
+        let config = self.config.lock().unwrap().clone();
+        let mut held = self.held.lock().unwrap();
+        if held.is_empty() {
+            return 0;
+        }
+        let mut released = 0;
+        let mut changed = false;
+        held.retain(|msisdn, queue| {
+            if let Some(target) = route(msisdn) {
+                let mut sent = 0;
+                while let Some(delivery) = queue.pop_front() {
+                    changed = true;
+                    let pdu = match SmppPdu::decode(&delivery.frame) {
+                        Ok(pdu) => pdu,
+                        Err(e) => {
+                            info!("⚠️  Dropping unreadable held DELIVER_SM for {}: {}", msisdn, e);
+                            continue;
+                        }
+                    };
+                    if let Err(e) = target.push(delivery.priority_flag, pdu) {
+                        // The bind went away again; keep the rest for the next pass
+                        info!("⚠️  Held DELIVER_SM for {} not queued: {}", msisdn, e);
+                        queue.push_front(delivery);
+                        break;
+                    }
+                    sent += 1;
+                }
+                if sent > 0 {
+                    info!("📤 Released {} held DELIVER_SM(s) to {}", sent, msisdn);
+                }
+                released += sent;
+            } else if count_attempt {
+                changed = true;
+                queue.retain_mut(|delivery| {
+                    delivery.attempts += 1;
+                    delivery.attempts <= config.attempts
+                });
+                if queue.is_empty() {
+                    info!("🗑️  No client bound for {} after {} retries, dropping its held DELIVER_SMs", msisdn, config.attempts);
+                }
+            }
+            !queue.is_empty()
+        });
+        if changed {
+            self.spool(&config, &held);
+        }
+        released
+    }
+
+    // Write then rename so a crash mid-write never leaves a truncated spool
+    fn spool(&self, config: &DeliveryRetryConfig, held: &BTreeMap<String, VecDeque<HeldDelivery>>) {
+        if config.spool.is_empty() {
+            return;
+        }
+        let temp_path = format!("{}.tmp", config.spool);
+        let written = serde_json::to_string(held)
+            .map_err(io::Error::other)
+            .and_then(|content| fs::write(&temp_path, content))
+            .and_then(|_| fs::rename(&temp_path, &config.spool));
+        if let Err(e) = written {
+            info!("⚠️  Could not spool held DELIVER_SMs to {}: {}", config.spool, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::codec::PduReadBuffer;
+    use crate::outbound::{OverflowPolicy, PriorityMetrics, QueueLimits};
+    use crate::pdu::{DELIVER_SM, ESME_ROK};
+    use crate::transport;
+
+    fn config(spool: &str) -> DeliveryRetryConfig {
+        DeliveryRetryConfig { enabled: true, attempts: 2, interval_ms: 10, max_per_msisdn: 2, spool: spool.to_string() }
+    }
+
+    fn deliver_sm(sequence_number: u32) -> SmppPdu {
+        SmppPdu::new(DELIVER_SM, ESME_ROK, sequence_number, b"body".to_vec())
+    }
+
+    #[test]
+    fn test_held_deliveries_go_out_in_order_once_routed() {
+        let journal = DeliveryJournal::default();
+        assert!(!journal.hold("111", 0, &deliver_sm(1)));
+        journal.start(&config("")).unwrap();
+        for sequence_number in 1..=3 {
+            assert!(journal.hold("111", 0, &deliver_sm(sequence_number)));
+        }
+        assert_eq!(journal.held_count("111"), 2);
+
+        let (server, mut phone) = transport::channel_pair();
+        let limits = QueueLimits { capacity: 0, overflow: OverflowPolicy::default(), block_timeout: Duration::from_millis(10) };
+        let queue = OutboundQueue::spawn(Arc::new(Mutex::new(server)), limits, Arc::new(PriorityMetrics::default()), None);
+        assert_eq!(journal.release(|_| Some(Arc::clone(&queue)), true), 2);
+        assert_eq!(journal.held_count("111"), 0);
+
+        let mut buffer = PduReadBuffer::new();
+        let sequences: Vec<u32> = (0..2).map(|_| buffer.read_pdu(&mut phone).unwrap().header.sequence_number).collect();
+        assert_eq!(sequences, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_unrouted_deliveries_are_dropped_after_their_attempts() {
+        let journal = DeliveryJournal::default();
+        journal.start(&config("")).unwrap();
+        journal.hold("111", 0, &deliver_sm(1));
+        // A bind arriving is not a retry pass and costs no attempt
+        journal.release(|_| None, false);
+        journal.release(|_| None, true);
+        journal.release(|_| None, true);
+        assert_eq!(journal.held_count("111"), 1);
+        journal.release(|_| None, true);
+        assert_eq!(journal.held_count("111"), 0);
+    }
+
+    #[test]
+    fn test_spool_survives_a_restart() {
+        let path = std::env::temp_dir().join(format!("ussd_journal_{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let config = config(&path.to_string_lossy());
+
+        let journal = DeliveryJournal::default();
+        journal.start(&config).unwrap();
+        journal.hold("111", 2, &deliver_sm(7));
+        drop(journal);
+
+        let restored = DeliveryJournal::default();
+        restored.start(&config).unwrap();
+        assert_eq!(restored.held_count("111"), 1);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,175 @@
+use std::net::Shutdown;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use log::info;
+
+use crate::outbound::{OutboundQueue, PRIORITY_LEVELS};
+use crate::persistence::StateStore;
+use crate::transport::SmppStream;
+use crate::pdu::{SmppPdu, ENQUIRE_LINK, ESME_ROK, UNBIND};
+
+// Granularity for noticing responses and shutdown without busy waiting
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy)]
+pub struct KeepaliveSettings {
+    pub interval: Duration,
+    pub timeout: Duration,
+    pub max_missed: u32,
+}
+
+// Server-initiated ENQUIRE_LINK for one bound connection
+#[derive(Debug)]
+pub struct Keepalive {
+    pending: Mutex<Option<u32>>, // Sequence number of the unanswered ENQUIRE_LINK
+    stopped: AtomicBool,
+}
+
+impl Keepalive {
+    pub fn spawn(
+        settings: KeepaliveSettings,
+        label: String,
+        queue: Arc<OutboundQueue>,
+        stream: SmppStream,
+        state_store: Arc<StateStore>,
+    ) -> Arc<Self> {
+        let keepalive = Arc::new(Keepalive {
+            pending: Mutex::new(None),
+            stopped: AtomicBool::new(false),
+        });
+
+        let worker = Arc::clone(&keepalive);
+        thread::spawn(move || worker.run(settings, &label, &queue, &stream, &state_store));
+        keepalive
+    }
+
+    pub fn acknowledge(&self, sequence_number: u32) {
+        let mut pending = self.pending.lock().unwrap();
+        if *pending == Some(sequence_number) {
+            *pending = None;
+        }
+    }
+
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    fn run(&self, settings: KeepaliveSettings, label: &str, queue: &OutboundQueue, stream: &SmppStream, state_store: &StateStore) {
+        let mut missed = 0;
+
+        while self.sleep(settings.interval) {
+            let sequence_number = state_store.next_sequence();
+            *self.pending.lock().unwrap() = Some(sequence_number);
+            // Keepalives jump ahead of queued traffic so a busy link is not mistaken for a dead one
+            if queue.push(PRIORITY_LEVELS as u8 - 1, header_only(ENQUIRE_LINK, sequence_number)).is_err() {
+                return;
+            }
+
+            let deadline = Instant::now() + settings.timeout;
+            while self.pending.lock().unwrap().is_some() && Instant::now() < deadline {
+                if !self.sleep(POLL_INTERVAL) {
+                    return;
+                }
+            }
+
+            if self.pending.lock().unwrap().take().is_none() {
+                missed = 0;
+                continue;
+            }
+            missed += 1;
+            info!("⚠️  {} missed ENQUIRE_LINK_RESP seq={} ({}/{})", label, sequence_number, missed, settings.max_missed);
+
+            if missed >= settings.max_missed {
+                info!("🔌 Dropping {} after {} unanswered ENQUIRE_LINKs", label, missed);
+                let _ = queue.push(PRIORITY_LEVELS as u8 - 1, header_only(UNBIND, state_store.next_sequence()));
+                thread::sleep(POLL_INTERVAL);
+                // Ends the handler's read loop, which removes the session and connection
+                let _ = stream.shutdown(Shutdown::Both);
+                return;
+            }
+        }
+    }
+
+    // Sleeps for `duration`, returning false as soon as the keepalive is stopped
+    fn sleep(&self, duration: Duration) -> bool {
+        let until = Instant::now() + duration;
+        while Instant::now() < until {
+            if self.stopped.load(Ordering::Relaxed) {
+                return false;
+            }
+            thread::sleep(POLL_INTERVAL.min(until.saturating_duration_since(Instant::now())));
+        }
+        !self.stopped.load(Ordering::Relaxed)
+    }
+}
+
+pub(crate) fn header_only(command_id: u32, sequence_number: u32) -> SmppPdu {
+    SmppPdu::new(command_id, ESME_ROK, sequence_number, Bytes::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    use crate::outbound::{OverflowPolicy, PriorityMetrics, QueueLimits};
+    use crate::persistence::PersistenceConfig;
+    use crate::transport;
+
+    // Keepalive on one end of a channel pair; the other end plays the ESME
+    fn start(max_missed: u32) -> (Arc<Keepalive>, SmppStream) {
+        let (server, peer) = transport::channel_pair();
+        let limits = QueueLimits { capacity: 0, overflow: OverflowPolicy::default(), block_timeout: Duration::from_millis(10) };
+        let queue = OutboundQueue::spawn(Arc::new(Mutex::new(server.try_clone().unwrap())), limits, Arc::new(PriorityMetrics::default()), None);
+        let settings = KeepaliveSettings {
+            interval: Duration::from_millis(20),
+            timeout: Duration::from_millis(150),
+            max_missed,
+        };
+        let state_store = Arc::new(StateStore::load(&PersistenceConfig::default()));
+        (Keepalive::spawn(settings, "test".to_string(), queue, server, state_store), peer)
+    }
+
+    // (command_id, sequence_number) of the next PDU, or None at end of stream
+    fn next_pdu(peer: &mut SmppStream) -> Option<(u32, u32)> {
+        let mut header = [0u8; 16];
+        peer.read_exact(&mut header).ok()?;
+        let word = |at: usize| u32::from_be_bytes(header[at..at + 4].try_into().unwrap());
+        Some((word(4), word(12)))
+    }
+
+    #[test]
+    fn test_unbinds_and_closes_after_max_missed() {
+        let (_keepalive, mut peer) = start(2);
+        assert_eq!(next_pdu(&mut peer).map(|pdu| pdu.0), Some(ENQUIRE_LINK));
+        assert_eq!(next_pdu(&mut peer).map(|pdu| pdu.0), Some(ENQUIRE_LINK));
+        assert_eq!(next_pdu(&mut peer).map(|pdu| pdu.0), Some(UNBIND));
+        assert_eq!(next_pdu(&mut peer), None);
+    }
+
+    #[test]
+    fn test_an_answer_resets_the_missed_count() {
+        let (keepalive, mut peer) = start(2);
+        // Missing every other ENQUIRE_LINK never reaches two in a row
+        for round in 0..4 {
+            let (command_id, sequence_number) = next_pdu(&mut peer).unwrap();
+            assert_eq!(command_id, ENQUIRE_LINK, "round {}", round);
+            if round % 2 == 1 {
+                keepalive.acknowledge(sequence_number);
+            }
+        }
+        keepalive.stop();
+    }
+
+    #[test]
+    fn test_stale_acknowledgement_is_ignored() {
+        let (keepalive, mut peer) = start(1);
+        let (_, sequence_number) = next_pdu(&mut peer).unwrap();
+        keepalive.acknowledge(sequence_number.wrapping_sub(1));
+        assert_eq!(next_pdu(&mut peer).map(|pdu| pdu.0), Some(UNBIND));
+    }
+}
@@ -0,0 +1,123 @@
+use std::time::Duration;
+
+use rand::Rng;
+use rand_distr::{Distribution, Exp, Normal};
+use serde::{Deserialize, Serialize};
+
+use crate::routing::glob_match;
+
+// Simulated gateway latency per route, e.g.
+//   [[latency.routes]]
+//   pattern = "*999%"
+//   submit_sm_resp = { distribution = "uniform", min_ms = 200, max_ms = 800 }
+//   deliver_sm = { distribution = "normal", mean_ms = 1500.0, std_dev_ms = 300.0 }
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LatencyConfig {
+    pub routes: Vec<LatencyRoute>, // First matching route wins
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LatencyRoute {
+    #[serde(default)]
+    pub code_prefix: Option<String>, // Same matching as routing rules; neither field matches every code
+    #[serde(default)]
+    pub pattern: Option<String>,
+    #[serde(default)]
+    pub submit_sm_resp: Option<Delay>,
+    #[serde(default)]
+    pub deliver_sm: Option<Delay>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LatencyStage {
+    SubmitSmResp,
+    DeliverSm,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "distribution", rename_all = "snake_case")]
+pub enum Delay {
+    Fixed { ms: u64 },
+    Uniform { min_ms: u64, max_ms: u64 },
+    Normal { mean_ms: f64, std_dev_ms: f64 }, // Negative draws count as no delay
+    Exponential { mean_ms: f64 },
+}
+
+impl LatencyRoute {
+    fn matches(&self, code: &str) -> bool {
+        self.code_prefix.as_ref().is_none_or(|prefix| code.starts_with(prefix.as_str()))
+            && self.pattern.as_ref().is_none_or(|pattern| glob_match(pattern.as_bytes(), code.as_bytes()))
+    }
+}
+
+impl LatencyConfig {
+    pub fn delay_for(&self, code: &str, stage: LatencyStage) -> Option<&Delay> {
+        let route = self.routes.iter().find(|route| route.matches(code))?;
+        match stage {
+            LatencyStage::SubmitSmResp => route.submit_sm_resp.as_ref(),
+            LatencyStage::DeliverSm => route.deliver_sm.as_ref(),
+        }
+    }
+}
+
+impl Delay {
+    pub fn sample(&self, rng: &mut impl Rng) -> Duration {
+        let ms = match *self {
+            Delay::Fixed { ms } => ms as f64,
+            Delay::Uniform { min_ms, max_ms } => rng.gen_range(min_ms.min(max_ms)..=max_ms.max(min_ms)) as f64,
+            Delay::Normal { mean_ms, std_dev_ms } => match Normal::new(mean_ms, std_dev_ms) {
+                Ok(normal) => normal.sample(rng),
+                Err(_) => mean_ms,
+            },
+            Delay::Exponential { mean_ms } if mean_ms > 0.0 => match Exp::new(1.0 / mean_ms) {
+                Ok(exp) => exp.sample(rng),
+                Err(_) => mean_ms,
+            },
+            Delay::Exponential { .. } => 0.0,
+        };
+        Duration::from_secs_f64(ms.max(0.0) / 1000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_routes_parse_and_first_match_wins() {
+        let config: LatencyConfig = toml::from_str(
+            "[[routes]]\npattern = \"*999%\"\nsubmit_sm_resp = { distribution = \"fixed\", ms = 250 }\n\n\
+             [[routes]]\ndeliver_sm = { distribution = \"exponential\", mean_ms = 100.0 }\n",
+        )
+        .unwrap();
+        assert!(matches!(config.delay_for("*999*1#", LatencyStage::SubmitSmResp), Some(Delay::Fixed { ms: 250 })));
+        // The first match has no DELIVER_SM delay, and later routes are not consulted
+        assert!(config.delay_for("*999#", LatencyStage::DeliverSm).is_none());
+        assert!(matches!(config.delay_for("*123#", LatencyStage::DeliverSm), Some(Delay::Exponential { .. })));
+        assert!(config.delay_for("*123#", LatencyStage::SubmitSmResp).is_none());
+        assert!(toml::from_str::<LatencyConfig>("[[routes]]\ndeliver_sm = { distribution = \"pareto\" }\n").is_err());
+    }
+
+    #[test]
+    fn test_samples_stay_within_their_distribution() {
+        let mut rng = StdRng::seed_from_u64(7);
+        assert_eq!(Delay::Fixed { ms: 40 }.sample(&mut rng), Duration::from_millis(40));
+
+        let uniform = Delay::Uniform { min_ms: 300, max_ms: 100 };
+        for _ in 0..100 {
+            let ms = uniform.sample(&mut rng).as_millis();
+            assert!((100..=300).contains(&ms));
+        }
+
+        let normal = Delay::Normal { mean_ms: 10.0, std_dev_ms: 50.0 };
+        assert!((0..1000).all(|_| normal.sample(&mut rng) >= Duration::ZERO));
+
+        let exponential = Delay::Exponential { mean_ms: 200.0 };
+        let mean = (0..5000).map(|_| exponential.sample(&mut rng).as_secs_f64() * 1000.0).sum::<f64>() / 5000.0;
+        assert!((180.0..220.0).contains(&mean), "mean {}", mean);
+        assert_eq!(Delay::Exponential { mean_ms: 0.0 }.sample(&mut rng), Duration::ZERO);
+    }
+}
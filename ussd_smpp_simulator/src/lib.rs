@@ -0,0 +1,56 @@
+// The simulator as a library, so test harnesses can run it in-process; cli.rs is the command line
+pub mod accounting;
+pub mod addressing;
+pub mod admin;
+pub mod bench;
+pub mod capture;
+pub mod cli;
+pub mod codec;
+pub mod config;
+pub mod control;
+pub mod correlation;
+pub mod demo;
+pub mod flood;
+pub mod grpc;
+pub mod http_backend;
+pub mod journal;
+pub mod keepalive;
+pub mod latency;
+pub mod listeners;
+pub mod live;
+pub mod logging;
+pub mod menu;
+pub mod menu_tree;
+pub mod migrate;
+pub mod outbind;
+pub mod outbound;
+pub mod pdu;
+pub mod persistence;
+pub mod probe;
+pub mod push;
+pub mod receipts;
+pub mod reload;
+pub mod replay;
+pub mod router;
+pub mod routing;
+pub mod scenario_runner;
+pub mod scenarios;
+pub mod screens;
+pub mod scripting;
+pub mod selftest;
+pub mod server;
+pub mod session;
+pub mod session_store;
+pub mod shard;
+pub mod shutdown;
+pub mod smpp_time;
+pub mod subscribers;
+pub mod throttle;
+pub mod timeline;
+pub mod transcript;
+pub mod transport;
+pub mod webhooks;
+pub mod window;
+
+pub use config::{load_config, Config};
+pub use server::{EmbeddedServer, ServerBuilder, UssdSmppServer};
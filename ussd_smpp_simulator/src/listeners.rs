@@ -0,0 +1,248 @@
+use std::io;
+use std::net::{Shutdown, TcpListener};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use ussd_common::tls::{self, rustls, TlsServerConfig, TlsStream};
+
+use crate::transport::SmppStream;
+use crate::server::UssdSmppServer;
+
+// `[server.tls]`: an smpps:// listener on the [server] host, next to the plain port
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TlsListenerConfig {
+    pub port: u16,
+    #[serde(flatten)]
+    pub settings: TlsServerConfig,
+}
+
+impl Default for TlsListenerConfig {
+    fn default() -> Self {
+        TlsListenerConfig { port: 3550, settings: TlsServerConfig::default() }
+    }
+}
+
+// `[[server.listeners]]`: a further endpoint, e.g. an external interface with TLS and a lower
+// connection cap. Every endpoint feeds the same sessions.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ListenerConfig {
+    pub host: String,
+    pub port: u16,
+    #[serde(default)]
+    pub max_connections: u32, // Connections open at once through this endpoint (0 = unlimited)
+    #[serde(default)]
+    pub tls: TlsServerConfig,
+}
+
+// A bound endpoint, ready to serve
+pub struct Listener {
+    socket: TcpListener,
+    addr: String,
+    tls: Option<Arc<rustls::ServerConfig>>,
+    max_connections: u32,
+    open: Arc<AtomicU32>,
+}
+
+// Held by a connection's thread; gives its place under the listener's cap back when dropped
+pub struct ConnectionSlot(Arc<AtomicU32>);
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Listener {
+    pub fn bind(config: &ListenerConfig) -> io::Result<Self> {
+        let tls = if config.tls.enabled { Some(tls::server_config(&config.tls)?) } else { None };
+        let socket = TcpListener::bind(format!("{}:{}", config.host, config.port))?;
+        Ok(Listener {
+            addr: socket.local_addr()?.to_string(),
+            socket,
+            tls,
+            max_connections: config.max_connections,
+            open: Arc::new(AtomicU32::new(0)),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    fn describe(&self, config: &ListenerConfig) -> String {
+        let mut details = Vec::new();
+        if config.tls.enabled {
+            details.push(match (config.tls.client_ca.is_empty(), config.tls.require_client_cert) {
+                (true, _) => "TLS".to_string(),
+                (false, false) => "TLS, client certificates optional".to_string(),
+                (false, true) => "TLS, client certificates required".to_string(),
+            });
+        }
+        if config.max_connections > 0 {
+            details.push(format!("up to {} connections", config.max_connections));
+        }
+        if details.is_empty() {
+            self.addr.clone()
+        } else {
+            format!("{} ({})", self.addr, details.join(", "))
+        }
+    }
+
+    // None when the endpoint already has max_connections open
+    fn slot(&self) -> Option<ConnectionSlot> {
+        let open = self.open.fetch_add(1, Ordering::SeqCst) + 1;
+        let slot = ConnectionSlot(Arc::clone(&self.open));
+        (self.max_connections == 0 || open <= self.max_connections).then_some(slot)
+    }
+}
+
+// The endpoints besides [server] host/port: `[server.tls]` and each `[[server.listeners]]`
+pub fn configured(server: &crate::config::ServerConfig) -> Vec<ListenerConfig> {
+    let mut listeners = Vec::new();
+    if server.tls.settings.enabled {
+        listeners.push(ListenerConfig {
+            host: server.host.clone(),
+            port: server.tls.port,
+            max_connections: 0,
+            tls: server.tls.settings.clone(),
+        });
+    }
+    listeners.extend(server.listeners.iter().cloned());
+    listeners
+}
+
+// Binds every endpoint up front, so a bad address or certificate stops the server from starting
+pub fn bind_all(server: &crate::config::ServerConfig) -> io::Result<Vec<Listener>> {
+    configured(server)
+        .iter()
+        .map(|config| {
+            let listener = Listener::bind(config)
+                .map_err(|e| io::Error::new(e.kind(), format!("listener {}:{}: {}", config.host, config.port, e)))?;
+            info!("🔌 USSD SMPP Server also listening on {}", listener.describe(config));
+            Ok(listener)
+        })
+        .collect()
+}
+
+impl UssdSmppServer {
+    // Accepts connections on one endpoint; TLS handshakes complete on each connection's own thread
+    pub fn serve_listener(&self, listener: Listener) {
+        for stream in listener.socket.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    info!("Connection failed on {}: {}", listener.addr, e);
+                    continue;
+                }
+            };
+            if self.refuse_peer(&stream) {
+                continue;
+            }
+            let Some(slot) = listener.slot() else {
+                info!("🚫 {} already has {} connections open; refusing another", listener.addr, listener.max_connections);
+                let _ = stream.shutdown(Shutdown::Both);
+                continue;
+            };
+            let stream = match &listener.tls {
+                Some(config) => match TlsStream::accept(stream, Arc::clone(config)) {
+                    Ok(stream) => SmppStream::Tls(stream),
+                    Err(e) => {
+                        info!("TLS connection failed on {}: {}", listener.addr, e);
+                        continue;
+                    }
+                },
+                None => SmppStream::Tcp(stream),
+            };
+            self.accept(stream, Some(slot));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpStream;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use crate::config::Config;
+
+    #[test]
+    fn test_listener_refuses_connections_over_its_cap() {
+        let server = Arc::new(UssdSmppServer::new(Config::default()));
+        let config = ListenerConfig { host: "127.0.0.1".to_string(), port: 0, max_connections: 1, tls: TlsServerConfig::default() };
+        let listener = Listener::bind(&config).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let open = Arc::clone(&listener.open);
+        thread::spawn(move || server.serve_listener(listener));
+
+        let first = TcpStream::connect(addr).unwrap();
+        let mut second = TcpStream::connect(addr).unwrap();
+        second.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        assert_eq!(second.read(&mut [0u8; 16]).unwrap(), 0);
+
+        // Closing the first connection gives its place back
+        drop(first);
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while open.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(open.load(Ordering::SeqCst), 0);
+        let third = TcpStream::connect(addr).unwrap();
+        while open.load(Ordering::SeqCst) == 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(open.load(Ordering::SeqCst), 1);
+        drop(third);
+    }
+
+    #[test]
+    fn test_configured_endpoints_from_toml() {
+        let mut config: Config = toml::from_str(&toml::to_string(&Config::default()).unwrap()).unwrap();
+        config.server = toml::from_str(
+            r#"
+            host = "10.0.0.5"
+            port = 2775
+
+            [tls]
+            enabled = true
+            port = 3551
+            cert = "server.pem"
+            key = "server.key"
+
+            [[listeners]]
+            host = "0.0.0.0"
+            port = 2776
+            max_connections = 20
+
+            [[listeners]]
+            host = "0.0.0.0"
+            port = 3550
+            [listeners.tls]
+            enabled = true
+            cert = "external.pem"
+            key = "external.key"
+            "#,
+        )
+        .unwrap();
+
+        let endpoints: Vec<(String, u16, u32, bool)> = configured(&config.server)
+            .into_iter()
+            .map(|listener| (listener.host, listener.port, listener.max_connections, listener.tls.enabled))
+            .collect();
+        assert_eq!(endpoints, vec![
+            ("10.0.0.5".to_string(), 3551, 0, true),
+            ("0.0.0.0".to_string(), 2776, 20, false),
+            ("0.0.0.0".to_string(), 3550, 0, true),
+        ]);
+
+        // Survives the round trip the reload check makes
+        let reparsed: Config = toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+        assert_eq!(reparsed.server.tls.settings.cert, "server.pem");
+        assert_eq!(reparsed.server.listeners[1].tls.cert, "external.pem");
+    }
+}
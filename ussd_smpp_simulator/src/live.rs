@@ -0,0 +1,235 @@
+use std::io::{self, Write};
+use std::net::TcpStream;
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+use crate::capture::{command_name, Direction};
+use crate::pdu::{bind_type_name, SmppHeader};
+use crate::session::Session;
+use crate::webhooks::{WebhookEmitter, WebhookEvent};
+
+// Events a dashboard may fall behind by before newer ones are dropped for it
+const EVENT_BUFFER: usize = 1000;
+
+// One message on the admin interface's /events WebSocket
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveEvent {
+    Bind {
+        timestamp: u64, // Milliseconds since the Unix epoch
+        connection_id: String,
+        system_id: String,
+        bind_type: &'static str,
+        role: &'static str,
+    },
+    Unbind {
+        timestamp: u64,
+        connection_id: String,
+        system_id: String,
+    },
+    Pdu {
+        timestamp: u64,
+        connection_id: String,
+        direction: &'static str, // "in" from the ESME, "out" to it
+        command: String,
+        sequence: u32,
+        status: u32,
+        length: u32,
+    },
+    Session(WebhookEvent), // The events [webhooks] posts, whatever webhooks.events selects
+}
+
+// Bind and PDU events for dashboards watching the simulator. Nothing is built while no one watches.
+#[derive(Debug, Default)]
+pub struct LiveFeed {
+    subscribers: Mutex<Vec<SyncSender<LiveEvent>>>,
+}
+
+impl LiveFeed {
+    pub fn subscribe(&self, capacity: usize) -> Receiver<LiveEvent> {
+        let (sender, receiver) = mpsc::sync_channel(capacity.max(1));
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    pub fn watching(&self) -> bool {
+        !self.subscribers.lock().unwrap().is_empty()
+    }
+
+    pub fn bind(&self, session: &Session) {
+        if self.watching() {
+            self.publish(LiveEvent::Bind {
+                timestamp: now(),
+                connection_id: session.connection_id.clone().unwrap_or_default(),
+                system_id: session.system_id.clone(),
+                bind_type: bind_type_name(session.bind_type),
+                role: session.role(),
+            });
+        }
+    }
+
+    pub fn unbind(&self, connection_id: &str, system_id: &str) {
+        if self.watching() {
+            self.publish(LiveEvent::Unbind { timestamp: now(), connection_id: connection_id.to_string(), system_id: system_id.to_string() });
+        }
+    }
+
+    pub fn pdu(&self, direction: Direction, connection_id: &str, header: &SmppHeader) {
+        if !self.watching() {
+            return;
+        }
+        self.publish(LiveEvent::Pdu {
+            timestamp: now(),
+            connection_id: connection_id.to_string(),
+            direction: match direction {
+                Direction::Inbound => "in",
+                Direction::Outbound => "out",
+            },
+            command: command_name(header.command_id),
+            sequence: header.sequence_number,
+            status: header.command_status,
+            length: header.command_length,
+        });
+    }
+
+    // Never blocks the caller; a subscriber that is full misses the event
+    fn publish(&self, event: LiveEvent) {
+        self.subscribers.lock().unwrap().retain(|subscriber| !matches!(subscriber.try_send(event.clone()), Err(TrySendError::Disconnected(_))));
+    }
+}
+
+// Connection and session events for one dashboard, from the moment it subscribed
+pub struct LiveSubscription {
+    connections: Receiver<LiveEvent>,
+    sessions: Receiver<WebhookEvent>,
+}
+
+impl LiveSubscription {
+    pub fn new(feed: &LiveFeed, webhooks: &WebhookEmitter) -> Self {
+        LiveSubscription { connections: feed.subscribe(EVENT_BUFFER), sessions: webhooks.subscribe(EVENT_BUFFER) }
+    }
+
+    // Everything that arrived since the last call
+    pub fn drain(&self) -> Vec<LiveEvent> {
+        let mut events: Vec<LiveEvent> = self.connections.try_iter().collect();
+        events.extend(self.sessions.try_iter().map(LiveEvent::Session));
+        events
+    }
+}
+
+// Completes the WebSocket handshake for `key` (the request's Sec-WebSocket-Key) and sends every
+// event as a JSON text message until the dashboard closes the connection
+pub fn serve_websocket(mut stream: TcpStream, key: &str, subscription: LiveSubscription) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        derive_accept_key(key.as_bytes())
+    )?;
+    // Reads time out so events go out between the dashboard's frames
+    stream.set_read_timeout(Some(Duration::from_millis(200)))?;
+    let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+    loop {
+        for event in subscription.drain() {
+            let text = serde_json::to_string(&event).map_err(io::Error::other)?;
+            socket.send(Message::Text(text)).map_err(io::Error::other)?;
+        }
+        // Reading answers pings and close frames; their replies go out on the flush
+        if closed(socket.read().map(|_| ()))? || closed(socket.flush())? {
+            return Ok(());
+        }
+    }
+}
+
+// True once the dashboard has closed the connection; a read timing out is not an error
+fn closed(result: tungstenite::Result<()>) -> io::Result<bool> {
+    match result {
+        Ok(()) => Ok(false),
+        Err(tungstenite::Error::Io(e)) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => Ok(false),
+        Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => Ok(true),
+        Err(e) => Err(io::Error::other(e)),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::thread;
+
+    use crate::admin::read_request;
+    use crate::pdu::{SmppPdu, BIND_TRANSCEIVER, ESME_ROK, SUBMIT_SM_RESP};
+
+    #[test]
+    fn test_feed_is_quiet_until_watched() {
+        let feed = LiveFeed::default();
+        let webhooks = WebhookEmitter::default();
+        feed.unbind("conn_1", "nobody"); // Not watched, so not sent anywhere
+        let subscription = LiveSubscription::new(&feed, &webhooks);
+        assert!(feed.watching());
+
+        let session = Session {
+            system_id: "USSDMobileUser".to_string(),
+            password: String::new(),
+            bound: true,
+            bind_type: BIND_TRANSCEIVER,
+            can_receive_forwards: false,
+            is_user_client: true,
+            connection_id: Some("conn_1".to_string()),
+            screen_chars: None,
+        };
+        feed.bind(&session);
+        feed.pdu(Direction::Outbound, "conn_1", &SmppPdu::new(SUBMIT_SM_RESP, ESME_ROK, 7, b"M1\0".to_vec()).header);
+        webhooks.request("111", "S1", "*123#", "*123#");
+
+        let events: Vec<serde_json::Value> = subscription.drain().iter().map(|event| serde_json::to_value(event).unwrap()).collect();
+        assert_eq!(events.len(), 4);
+        assert_eq!((events[0]["type"].as_str(), events[0]["role"].as_str()), (Some("bind"), Some("user")));
+        assert_eq!((events[1]["command"].as_str(), events[1]["length"].as_u64()), (Some("submit_sm_resp"), Some(19)));
+        assert_eq!((events[1]["direction"].as_str(), events[1]["sequence"].as_u64()), (Some("out"), Some(7)));
+        assert_eq!((events[2]["type"].as_str(), events[2]["event"].as_str()), (Some("session"), Some("session_start")));
+        assert_eq!(events[3]["text"], "*123#");
+
+        drop(subscription);
+        feed.unbind("conn_1", "USSDMobileUser");
+        assert!(!feed.watching());
+    }
+
+    #[test]
+    fn test_websocket_streams_events_until_closed() {
+        let feed = Arc::new(LiveFeed::default());
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let serving = Arc::clone(&feed);
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let request = read_request(&mut stream).unwrap().unwrap();
+            serve_websocket(stream, &request.websocket_key.unwrap(), LiveSubscription::new(&serving, &WebhookEmitter::default()))
+        });
+
+        let (mut socket, _) = tungstenite::client(format!("ws://{}/events", addr), TcpStream::connect(addr).unwrap()).unwrap();
+        while !feed.watching() {
+            thread::sleep(Duration::from_millis(10));
+        }
+        feed.unbind("conn_1", "ForwardingClient");
+        let Message::Text(text) = socket.read().unwrap() else {
+            panic!("expected a text message");
+        };
+        let event: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!((event["type"].as_str(), event["system_id"].as_str()), (Some("unbind"), Some("ForwardingClient")));
+
+        socket.close(None).unwrap();
+        while !matches!(socket.read(), Err(tungstenite::Error::ConnectionClosed)) {}
+        server.join().unwrap().unwrap();
+    }
+}
@@ -0,0 +1,335 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use log::{info, Level};
+use serde::{Deserialize, Serialize};
+
+// Subsystems that can have their verbosity tuned independently
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subsystem {
+    Codec,
+    Routing,
+    Sessions,
+    Forwarding,
+    Chaos,
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 5] = [
+        Subsystem::Codec,
+        Subsystem::Routing,
+        Subsystem::Sessions,
+        Subsystem::Forwarding,
+        Subsystem::Chaos,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Subsystem::Codec => "codec",
+            Subsystem::Routing => "routing",
+            Subsystem::Sessions => "sessions",
+            Subsystem::Forwarding => "forwarding",
+            Subsystem::Chaos => "chaos",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+impl FromStr for Subsystem {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Subsystem::ALL
+            .iter()
+            .find(|sub| sub.name() == s.trim().to_lowercase())
+            .copied()
+            .ok_or_else(|| format!("Unknown logging subsystem '{}'", s))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Off = 0,
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl LogLevel {
+    // What a line let through at this level is printed at. The process logger runs at info, so
+    // debug and trace lines go out at info; the subsystem's own level is their filter.
+    pub fn record_level(self) -> Level {
+        match self {
+            LogLevel::Error => Level::Error,
+            LogLevel::Warn => Level::Warn,
+            _ => Level::Info,
+        }
+    }
+
+    fn from_u8(value: u8) -> LogLevel {
+        match value {
+            0 => LogLevel::Off,
+            1 => LogLevel::Error,
+            2 => LogLevel::Warn,
+            3 => LogLevel::Info,
+            4 => LogLevel::Debug,
+            _ => LogLevel::Trace,
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LogLevel::Off => "off",
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "off" => Ok(LogLevel::Off),
+            "error" => Ok(LogLevel::Error),
+            "warn" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            "trace" => Ok(LogLevel::Trace),
+            other => Err(format!("Unknown log level '{}'", other)),
+        }
+    }
+}
+
+// Logs a line of `subsystem` at `level` when the subsystem is set to that level or above, e.g.
+// `subsystem_log!(self.log_levels, Subsystem::Codec, LogLevel::Trace, "📤 {:02x?}", buffer)`
+macro_rules! subsystem_log {
+    ($levels:expr, $subsystem:expr, $level:expr, $($arg:tt)+) => {
+        if $levels.enabled($subsystem, $level) {
+            log::log!($level.record_level(), $($arg)+);
+        }
+    };
+}
+pub(crate) use subsystem_log;
+
+// Per-subsystem levels as they appear in the [logging.subsystems] table
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SubsystemLevelsConfig {
+    pub codec: String,
+    pub routing: String,
+    pub sessions: String,
+    pub forwarding: String,
+    pub chaos: String,
+}
+
+impl Default for SubsystemLevelsConfig {
+    fn default() -> Self {
+        SubsystemLevelsConfig {
+            codec: "info".to_string(),
+            routing: "info".to_string(),
+            sessions: "info".to_string(),
+            forwarding: "info".to_string(),
+            chaos: "info".to_string(),
+        }
+    }
+}
+
+impl SubsystemLevelsConfig {
+    fn get(&self, subsystem: Subsystem) -> &str {
+        match subsystem {
+            Subsystem::Codec => &self.codec,
+            Subsystem::Routing => &self.routing,
+            Subsystem::Sessions => &self.sessions,
+            Subsystem::Forwarding => &self.forwarding,
+            Subsystem::Chaos => &self.chaos,
+        }
+    }
+}
+
+// Runtime log levels shared by all connection handlers and the admin interface
+#[derive(Debug)]
+pub struct LogLevels {
+    levels: [AtomicU8; 5],
+}
+
+impl LogLevels {
+    pub fn new(config: &SubsystemLevelsConfig, debug: bool) -> Self {
+        let levels = LogLevels {
+            levels: Default::default(),
+        };
+
+        for subsystem in Subsystem::ALL {
+            let level = match config.get(subsystem).parse::<LogLevel>() {
+                Ok(level) => level,
+                Err(e) => {
+                    info!("⚠️  {} for subsystem '{}', using info", e, subsystem.name());
+                    LogLevel::Info
+                }
+            };
+            // The legacy `logging.debug` switch raises every subsystem to at least debug
+            let level = if debug { level.max(LogLevel::Debug) } else { level };
+            levels.set(subsystem, level);
+        }
+
+        levels
+    }
+
+    pub fn level(&self, subsystem: Subsystem) -> LogLevel {
+        LogLevel::from_u8(self.levels[subsystem.index()].load(Ordering::Relaxed))
+    }
+
+    pub fn set(&self, subsystem: Subsystem, level: LogLevel) {
+        self.levels[subsystem.index()].store(level as u8, Ordering::Relaxed);
+    }
+
+    pub fn enabled(&self, subsystem: Subsystem, level: LogLevel) -> bool {
+        level != LogLevel::Off && level <= self.level(subsystem)
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, String> {
+        Subsystem::ALL
+            .iter()
+            .map(|sub| (sub.name().to_string(), self.level(*sub).to_string()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use log::{LevelFilter, Log, Metadata, Record};
+
+    use super::*;
+
+    // Keeps what each thread logs to itself, so tests running alongside do not mix their lines in
+    struct Capture;
+
+    thread_local! {
+        static CAPTURED: RefCell<Vec<(Level, String)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    impl Log for Capture {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            CAPTURED.with(|lines| lines.borrow_mut().push((record.level(), record.args().to_string())));
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn capture(log: impl FnOnce()) -> Vec<(Level, String)> {
+        static CAPTURE: Capture = Capture;
+        let _ = log::set_logger(&CAPTURE);
+        log::set_max_level(LevelFilter::Trace);
+        CAPTURED.with(|lines| lines.borrow_mut().clear());
+        log();
+        CAPTURED.with(|lines| lines.take())
+    }
+
+    #[test]
+    fn test_off_silences_a_subsystem_at_every_level() {
+        let config = SubsystemLevelsConfig {
+            routing: "off".to_string(),
+            codec: "trace".to_string(),
+            ..Default::default()
+        };
+        let levels = LogLevels::new(&config, false);
+        let lines = capture(|| {
+            for level in [LogLevel::Error, LogLevel::Warn, LogLevel::Info, LogLevel::Debug, LogLevel::Trace] {
+                for subsystem in [Subsystem::Routing, Subsystem::Codec, Subsystem::Sessions] {
+                    subsystem_log!(levels, subsystem, level, "{} {}", subsystem.name(), level);
+                }
+            }
+        });
+
+        assert!(lines.iter().all(|(_, line)| !line.starts_with("routing")), "{:?}", lines);
+        let codec: Vec<(Level, &str)> =
+            lines.iter().filter(|(_, line)| line.starts_with("codec")).map(|(level, line)| (*level, line.as_str())).collect();
+        assert_eq!(
+            codec,
+            [
+                (Level::Error, "codec error"),
+                (Level::Warn, "codec warn"),
+                (Level::Info, "codec info"),
+                (Level::Info, "codec debug"),
+                (Level::Info, "codec trace"),
+            ]
+        );
+        // Info, the default, stops at info
+        let sessions: Vec<&str> = lines.iter().filter(|(_, line)| line.starts_with("sessions")).map(|(_, line)| line.as_str()).collect();
+        assert_eq!(sessions, ["sessions error", "sessions warn", "sessions info"]);
+
+        // Turned off at runtime, from the admin interface
+        levels.set(Subsystem::Codec, LogLevel::Off);
+        assert!(capture(|| subsystem_log!(levels, Subsystem::Codec, LogLevel::Error, "codec error")).is_empty());
+    }
+
+    #[test]
+    fn test_level_names_round_trip() {
+        for level in [LogLevel::Off, LogLevel::Error, LogLevel::Warn, LogLevel::Info, LogLevel::Debug, LogLevel::Trace] {
+            assert_eq!(level.to_string().parse::<LogLevel>(), Ok(level));
+        }
+        assert_eq!(" DEBUG ".parse::<LogLevel>(), Ok(LogLevel::Debug));
+        assert!("verbose".parse::<LogLevel>().is_err());
+        assert_eq!("Routing".parse::<Subsystem>(), Ok(Subsystem::Routing));
+        assert!("network".parse::<Subsystem>().is_err());
+    }
+
+    #[test]
+    fn test_config_levels_and_invalid_fallback() {
+        let config = SubsystemLevelsConfig {
+            codec: "trace".to_string(),
+            routing: "off".to_string(),
+            chaos: "loud".to_string(),
+            ..Default::default()
+        };
+        let levels = LogLevels::new(&config, false);
+        assert_eq!(levels.level(Subsystem::Codec), LogLevel::Trace);
+        assert_eq!(levels.level(Subsystem::Routing), LogLevel::Off);
+        assert_eq!(levels.level(Subsystem::Chaos), LogLevel::Info);
+        assert!(levels.enabled(Subsystem::Codec, LogLevel::Debug));
+        assert!(!levels.enabled(Subsystem::Sessions, LogLevel::Debug));
+        assert!(!levels.enabled(Subsystem::Routing, LogLevel::Error));
+        assert!(!levels.enabled(Subsystem::Codec, LogLevel::Off));
+    }
+
+    #[test]
+    fn test_debug_switch_raises_but_never_lowers() {
+        let config = SubsystemLevelsConfig {
+            codec: "trace".to_string(),
+            ..Default::default()
+        };
+        let levels = LogLevels::new(&config, true);
+        assert_eq!(levels.level(Subsystem::Codec), LogLevel::Trace);
+        assert_eq!(levels.level(Subsystem::Sessions), LogLevel::Debug);
+    }
+
+    #[test]
+    fn test_runtime_change_shows_in_snapshot() {
+        let levels = LogLevels::new(&SubsystemLevelsConfig::default(), false);
+        levels.set(Subsystem::Forwarding, LogLevel::Warn);
+        let snapshot = levels.snapshot();
+        assert_eq!(snapshot.len(), Subsystem::ALL.len());
+        assert_eq!(snapshot["forwarding"], "warn");
+        assert_eq!(snapshot["codec"], "info");
+    }
+}
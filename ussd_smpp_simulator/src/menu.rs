@@ -0,0 +1,222 @@
+use log::info;
+use ussd_common::dial;
+
+use crate::config::NotifyScreen;
+use crate::logging::{subsystem_log, LogLevel, Subsystem};
+use crate::menu_tree::{TreePosition, TreeStep};
+use crate::pdu::USSD_NOTIFY;
+use crate::server::UssdConnectionHandler;
+use crate::session::{UssdScreen, UssdSession, UssdState};
+
+impl UssdConnectionHandler {
+    // The screen for a request. A screen configured in `ussd.responses.notify_screens` is sent
+    // as USSD_NOTIFY and also ends the session. A code dialled with menu keys after it, such as
+    // *123*2*1#, opens the menu and picks each key in turn; only the last screen is sent.
+    pub(crate) fn generate_ussd_response(&self, session: &mut UssdSession, request: &str) -> UssdScreen {
+        let opening = matches!(session.state, UssdState::Initial);
+        let mut screen = self.next_screen(session, request);
+        if opening && matches!(session.state, UssdState::MainMenu | UssdState::Tree(_)) {
+            for key in self.dialled_keys(request) {
+                if matches!(session.state, UssdState::Terminated) {
+                    break;
+                }
+                screen = self.next_screen(session, key);
+            }
+        }
+        screen
+    }
+
+    // The keys dialled after the code of the menu that `request` opened
+    fn dialled_keys<'a>(&self, request: &'a str) -> Vec<&'a str> {
+        self.config
+            .menu_tree
+            .codes
+            .iter()
+            .map(|mapping| mapping.code.as_str())
+            .chain(self.config.ussd.service_codes.iter().map(String::as_str))
+            .find_map(|code| dial::steps(code, request))
+            .unwrap_or_default()
+    }
+
+    fn next_screen(&self, session: &mut UssdSession, request: &str) -> UssdScreen {
+        match &session.state {
+            UssdState::Initial => {
+                if self.config.scripting.service_for(request).is_some() {
+                    session.state = UssdState::Scripted("{}".to_string());
+                    self.scripted_screen(session, request)
+                } else if let Some(menu) = self.config.menu_tree.menu_for(request) {
+                    let position = TreePosition::new(menu);
+                    let variables = self.config.variables(&session.msisdn, &session.inputs);
+                    let text = self.config.menu_tree.render(&position, &|name| variables.get(name));
+                    session.state = UssdState::Tree(position);
+                    session.menu_level = 1;
+                    self.menu_screen(text)
+                } else if self.config.ussd.service_codes.iter().any(|code| request.starts_with(code.trim_end_matches('#'))) {
+                    session.state = UssdState::MainMenu;
+                    session.menu_level = 1;
+                    self.menu_screen(format!("{}\n{}", 
+                        self.config.ussd.menu.welcome_message,
+                        self.config.ussd.menu.main_menu.join("\n")))
+                } else if self.config.http_backend.serves(request) {
+                    // The caller asks the backend once the session lock is released
+                    session.state = UssdState::HttpBackend;
+                    self.response_screen(String::new(), None)
+                } else {
+                    // Try to forward to the bound client the routing table picks for this code
+                    let route = self.config.routing.route(request).map(str::to_string);
+                    subsystem_log!(self.log_levels, Subsystem::Routing, LogLevel::Debug,
+                        "🧭 Route for {}: {}", request, route.as_deref().unwrap_or("any forwarding client"));
+                    // The caller forwards once the session lock is released
+                    session.state = UssdState::Forwarded;
+                    session.forward_route = route;
+                    self.response_screen(String::new(), None)
+                }
+            }
+            UssdState::MainMenu => {
+                match request {
+                    "1" if self.notifies(NotifyScreen::Balance) => {
+                        session.state = UssdState::Terminated;
+                        self.response_screen(self.config.ussd.responses.balance_message.clone(), Some(USSD_NOTIFY))
+                    }
+                    "1" => {
+                        session.state = UssdState::BalanceInquiry;
+                        self.response_screen(format!("{}\nPress 0 to return to main menu", self.config.ussd.responses.balance_message), None)
+                    }
+                    "2" => {
+                        session.state = UssdState::DataPackages;
+                        let mut menu = "Available Data Packages:\n".to_string();
+                        for (i, package) in self.config.ussd.data_packages.packages.iter().enumerate() {
+                            menu.push_str(&format!("{}. {} - ${:.2}\n", i + 1, package.data, package.price));
+                        }
+                        menu.push_str("0. Back to main menu");
+                        self.menu_screen(menu)
+                    }
+                    "3" => {
+                        session.state = UssdState::CustomerService;
+                        self.response_screen("Customer Service:\nCall 123 for support\nEmail: support@mytelecom.com\nPress 0 to return to main menu".to_string(), None)
+                    }
+                    "0" => {
+                        session.state = UssdState::Terminated;
+                        self.response_screen(self.config.ussd.responses.goodbye_message.clone(), self.notify_op(NotifyScreen::Goodbye))
+                    }
+                    _ => {
+                        self.menu_screen(format!("{}\n{}", 
+                            self.config.ussd.responses.invalid_option,
+                            self.config.ussd.menu.main_menu.join("\n")))
+                    }
+                }
+            }
+            UssdState::BalanceInquiry | UssdState::DataPackages | UssdState::CustomerService => {
+                if request == "0" {
+                    session.state = UssdState::MainMenu;
+                    session.menu_level = 1;
+                    self.menu_screen(format!("{}\n{}", 
+                        self.config.ussd.menu.welcome_message,
+                        self.config.ussd.menu.main_menu.join("\n")))
+                } else if request == "00" {
+                    session.state = UssdState::Terminated;
+                    self.response_screen(self.config.ussd.responses.goodbye_message.clone(), self.notify_op(NotifyScreen::Goodbye))
+                } else {
+                    match &session.state {
+                        UssdState::DataPackages => {
+                            if let Ok(choice) = request.parse::<usize>() {
+                                if choice > 0 && choice <= self.config.ussd.data_packages.packages.len() {
+                                    let package = &self.config.ussd.data_packages.packages[choice - 1];
+                                    self.response_screen(format!("{} selected. Reply with 'YES' to confirm purchase for ${:.2}", 
+                                        package.name, package.price), None)
+                                } else {
+                                    self.response_screen("Invalid option. Please select a valid package number, or 0 to go back".to_string(), None)
+                                }
+                            } else if request.to_uppercase() == "YES" && self.notifies(NotifyScreen::Purchase) {
+                                session.state = UssdState::Terminated;
+                                self.response_screen("Package purchased successfully! You will receive a confirmation SMS shortly.".to_string(), Some(USSD_NOTIFY))
+                            } else if request.to_uppercase() == "YES" {
+                                session.state = UssdState::MainMenu;
+                                self.response_screen("Package purchased successfully! You will receive a confirmation SMS shortly.\nPress 0 to return to main menu".to_string(), None)
+                            } else {
+                                self.response_screen("Invalid option. Please select a valid package number, or 0 to go back".to_string(), None)
+                            }
+                        }
+                        _ => self.response_screen("Press 0 to return to main menu or 00 to exit".to_string(), None),
+                    }
+                }
+            }
+            UssdState::Pushed => {
+                // The reply to a network-initiated request is acknowledged and the dialogue closed
+                info!("📲 Reply to network-initiated request from {}: {}", session.msisdn, request);
+                session.state = UssdState::Terminated;
+                self.response_screen(self.config.push.reply_message.clone(), Some(USSD_NOTIFY))
+            }
+            UssdState::Scripted(_) => self.scripted_screen(session, request),
+            UssdState::Tree(position) => {
+                let mut position = position.clone();
+                let variables = self.config.variables(&session.msisdn, &session.inputs);
+                let invalid_option = &self.config.ussd.responses.invalid_option;
+                match self.config.menu_tree.select(&mut position, request, invalid_option, &|name| variables.get(name)) {
+                    TreeStep::Screen(text) => {
+                        session.menu_level = position.path.len() as u8;
+                        session.state = UssdState::Tree(position);
+                        self.menu_screen(text)
+                    }
+                    TreeStep::Exit => {
+                        session.state = UssdState::Terminated;
+                        self.response_screen(self.config.ussd.responses.goodbye_message.clone(), self.notify_op(NotifyScreen::Goodbye))
+                    }
+                }
+            }
+            UssdState::Forwarded | UssdState::HttpBackend => {
+                // Follow-ups go to the client or backend that owns this session, again outside the lock
+                self.response_screen(String::new(), None)
+            }
+            UssdState::Terminated => {
+                let code_list = self.config.ussd.service_codes.join(", ");
+                self.response_screen(format!("USSD session has ended. Please dial one of [{}] to start a new session.", code_list), None)
+            }
+        }
+    }
+
+    // The next screen of the `[scripting]` service that opened the session. A script that ends
+    // the dialogue, or fails, closes it with USSD_NOTIFY.
+    fn scripted_screen(&self, session: &mut UssdSession, request: &str) -> UssdScreen {
+        let UssdState::Scripted(script_state) = &session.state else {
+            return self.response_screen(String::new(), None);
+        };
+        let scripting = &self.config.scripting;
+        let reply = match scripting.service_for(&session.service_code) {
+            Some(service) => scripting.run(service, &session.msisdn, script_state, request),
+            None => Err(format!("{} is no longer scripted", session.service_code)),
+        };
+        match reply {
+            Ok(reply) if reply.end => {
+                session.state = UssdState::Terminated;
+                self.response_screen(reply.text, Some(USSD_NOTIFY))
+            }
+            Ok(reply) => {
+                session.state = UssdState::Scripted(reply.state);
+                self.response_screen(reply.text, None)
+            }
+            Err(e) => {
+                info!("📜 Script for {} failed for {}: {}", session.service_code, session.msisdn, e);
+                session.state = UssdState::Terminated;
+                self.response_screen(scripting.error_message.clone(), Some(USSD_NOTIFY))
+            }
+        }
+    }
+
+    // Menus are encoded as `ussd.menu.encoding` asks, every other screen as `ussd.responses.encoding`
+    fn menu_screen(&self, text: String) -> UssdScreen {
+        UssdScreen { text, service_op: None, encoding: self.config.ussd.menu.encoding }
+    }
+
+    pub(crate) fn response_screen(&self, text: String, service_op: Option<u8>) -> UssdScreen {
+        UssdScreen { text, service_op, encoding: self.config.ussd.responses.encoding }
+    }
+
+    fn notifies(&self, screen: NotifyScreen) -> bool {
+        self.config.ussd.responses.notify_screens.contains(&screen)
+    }
+
+    fn notify_op(&self, screen: NotifyScreen) -> Option<u8> {
+        self.notifies(screen).then_some(USSD_NOTIFY)
+    }
+}
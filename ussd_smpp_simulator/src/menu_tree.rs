@@ -0,0 +1,355 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use ussd_common::condition;
+use ussd_common::input::InputRule;
+
+// Menus described in config instead of code, the same model as the forwarding client's
+// [menus]: each code opens a named menu whose options lead to submenus, responses, free-text
+// prompts, session data updates or the exit
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MenuTreeConfig {
+    pub codes: Vec<TreeCode>,
+    pub menus: BTreeMap<String, TreeMenu>,
+    pub responses: BTreeMap<String, String>,
+    pub enable_back_navigation: bool, // "00" returns to the previous menu, or exits from the first
+    pub back_text: String, // Last line of every menu below the first
+    pub max_menu_depth: usize,
+}
+
+impl Default for MenuTreeConfig {
+    fn default() -> Self {
+        MenuTreeConfig {
+            codes: Vec::new(),
+            menus: BTreeMap::new(),
+            responses: BTreeMap::new(),
+            enable_back_navigation: true,
+            back_text: "00. Back".to_string(),
+            max_menu_depth: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TreeCode {
+    pub code: String, // e.g. "*200#"; matched like ussd.service_codes
+    pub menu: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TreeMenu {
+    pub title: String,
+    pub options: Vec<TreeOption>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TreeOption {
+    pub key: String,
+    pub text: String,
+    pub action: TreeAction,
+    #[serde(default)]
+    pub target: String, // Menu for "submenu", response for "response", either for "input" and "set"
+    #[serde(default)]
+    pub input: Option<InputRule>, // What an "input" option asks for
+    #[serde(default)]
+    pub condition: Option<String>, // Shown and selectable only while this holds
+    #[serde(default)]
+    pub set: BTreeMap<String, String>, // Session data a "set" option writes
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TreeAction {
+    Submenu,
+    Response, // Shows the response; the subscriber stays in the same menu
+    Input, // Prompts for a value, then goes on to the target menu or response
+    Set, // Writes `set` into the session data, then goes on to the target menu or response
+    Exit,
+}
+
+// Where a subscriber is in a tree, kept in the session between requests
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TreePosition {
+    pub path: Vec<String>, // The menus visited since the code was dialled
+    #[serde(default)]
+    pub data: BTreeMap<String, String>, // Values collected by "input" options and written by "set" ones
+    #[serde(default)]
+    pub awaiting: Option<String>, // Key of the "input" option whose prompt is showing
+}
+
+impl TreePosition {
+    pub fn new(menu: &str) -> Self {
+        TreePosition { path: vec![menu.to_string()], ..Default::default() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeStep {
+    Screen(String),
+    Exit,
+}
+
+// Names in option conditions other than `data.<name>`, e.g. the request's placeholders
+pub type Lookup<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+fn visible(option: &TreeOption, data: &BTreeMap<String, String>, lookup: Lookup) -> bool {
+    option.condition.as_deref().is_none_or(|expression| {
+        condition::holds(expression, |name| match name.strip_prefix("data.") {
+            Some(key) => data.get(key).cloned(),
+            None => lookup(name),
+        })
+    })
+}
+
+impl MenuTreeConfig {
+    // The first menu of a dialled code, if a tree serves it
+    pub fn menu_for(&self, code: &str) -> Option<&str> {
+        self.codes
+            .iter()
+            .find(|mapping| code.starts_with(mapping.code.trim_end_matches('#')))
+            .map(|mapping| mapping.menu.as_str())
+    }
+
+    // Every code must open a menu and every option must lead somewhere
+    pub fn validate(&self) -> Result<(), String> {
+        for mapping in &self.codes {
+            if !self.menus.contains_key(&mapping.menu) {
+                return Err(format!("menu_tree code {} opens unknown menu {:?}", mapping.code, mapping.menu));
+            }
+        }
+        for (name, menu) in &self.menus {
+            for option in &menu.options {
+                if let Some(expression) = &option.condition {
+                    condition::check(expression).map_err(|e| format!("menu_tree.menus.{} option {} condition: {}", name, option.key, e))?;
+                }
+                let known = match option.action {
+                    TreeAction::Submenu => self.menus.contains_key(&option.target),
+                    TreeAction::Response => self.responses.contains_key(&option.target),
+                    TreeAction::Input => {
+                        let Some(rule) = &option.input else {
+                            return Err(format!("menu_tree.menus.{} option {} needs an input rule", name, option.key));
+                        };
+                        rule.check().map_err(|e| format!("menu_tree.menus.{} option {}: {}", name, option.key, e))?;
+                        self.menus.contains_key(&option.target) || self.responses.contains_key(&option.target)
+                    }
+                    TreeAction::Set => {
+                        if option.set.is_empty() {
+                            return Err(format!("menu_tree.menus.{} option {} has nothing to set", name, option.key));
+                        }
+                        self.menus.contains_key(&option.target) || self.responses.contains_key(&option.target)
+                    }
+                    TreeAction::Exit => true,
+                };
+                if !known {
+                    return Err(format!("menu_tree.menus.{} option {} targets unknown {:?}", name, option.key, option.target));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The menu at the end of the position's path, without the options whose condition fails
+    pub fn render(&self, position: &TreePosition, lookup: Lookup) -> String {
+        let Some(menu) = position.path.last().and_then(|name| self.menus.get(name)) else {
+            return String::new();
+        };
+        let mut lines = vec![menu.title.clone()];
+        lines.extend(
+            menu.options
+                .iter()
+                .filter(|option| visible(option, &position.data, lookup))
+                .map(|option| format!("{}. {}", option.key, option.text)),
+        );
+        if self.enable_back_navigation && position.path.len() > 1 {
+            lines.push(self.back_text.clone());
+        }
+        lines.join("\n")
+    }
+
+    // Applies the subscriber's input to the menu at the end of the position's path. Options
+    // whose condition fails cannot be picked, so two options may share a key as branches.
+    pub fn select(&self, position: &mut TreePosition, input: &str, invalid_option: &str, lookup: Lookup) -> TreeStep {
+        if input == "00" && self.enable_back_navigation {
+            // Backing out of a prompt returns to the menu that showed it
+            if position.awaiting.take().is_some() {
+                return TreeStep::Screen(self.render(position, lookup));
+            }
+            if position.path.len() <= 1 {
+                return TreeStep::Exit;
+            }
+            position.path.pop();
+            return TreeStep::Screen(self.render(position, lookup));
+        }
+        let menu = position.path.last().and_then(|name| self.menus.get(name));
+        if let Some(key) = position.awaiting.clone() {
+            let option = menu.and_then(|menu| {
+                menu.options.iter().find(|option| option.key == key && option.input.is_some() && visible(option, &position.data, lookup))
+            });
+            let Some((option, rule)) = option.and_then(|option| Some((option, option.input.as_ref()?))) else {
+                position.awaiting = None;
+                return TreeStep::Screen(self.render(position, lookup));
+            };
+            if !rule.accepts(input) {
+                return TreeStep::Screen(rule.retry_prompt(invalid_option));
+            }
+            position.awaiting = None;
+            position.data.insert(rule.name.clone(), input.to_string());
+            return self.go_to(position, &option.target, lookup);
+        }
+
+        let option = menu.and_then(|menu| menu.options.iter().find(|option| option.key == input && visible(option, &position.data, lookup)));
+        match option {
+            Some(option) if option.action == TreeAction::Submenu && position.path.len() <= self.max_menu_depth => {
+                position.path.push(option.target.clone());
+                TreeStep::Screen(self.render(position, lookup))
+            }
+            Some(option) if option.action == TreeAction::Response => {
+                TreeStep::Screen(self.responses.get(&option.target).cloned().unwrap_or_default())
+            }
+            Some(TreeOption { action: TreeAction::Input, input: Some(rule), key, .. }) => {
+                position.awaiting = Some(key.clone());
+                TreeStep::Screen(rule.prompt.clone())
+            }
+            Some(option) if option.action == TreeAction::Set => {
+                position.data.extend(option.set.clone());
+                self.go_to(position, &option.target, lookup)
+            }
+            Some(option) if option.action == TreeAction::Exit => TreeStep::Exit,
+            _ => TreeStep::Screen(format!("{}\n{}", invalid_option, self.render(position, lookup))),
+        }
+    }
+
+    // Where "input" and "set" options lead: into the target menu, or to the target response
+    fn go_to(&self, position: &mut TreePosition, target: &str, lookup: Lookup) -> TreeStep {
+        if self.menus.contains_key(target) {
+            position.path.push(target.to_string());
+            return TreeStep::Screen(self.render(position, lookup));
+        }
+        TreeStep::Screen(self.responses.get(target).cloned().unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> MenuTreeConfig {
+        toml::from_str(
+            r#"
+            codes = [{ code = "*200#", menu = "main" }]
+            max_menu_depth = 1
+
+            [menus.main]
+            title = "Bank"
+            options = [
+                { key = "1", text = "Balance", action = "response", target = "balance" },
+                { key = "2", text = "Loans", action = "submenu", target = "loans" },
+                { key = "0", text = "Exit", action = "exit" },
+            ]
+
+            [menus.loans]
+            title = "Loans"
+            options = [{ key = "1", text = "Again", action = "submenu", target = "loans" }]
+
+            [responses]
+            balance = "You have $5"
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn none(_: &str) -> Option<String> {
+        None
+    }
+
+    #[test]
+    fn test_navigation_through_the_tree() {
+        let tree = tree();
+        tree.validate().unwrap();
+        assert_eq!(tree.menu_for("*200*1#"), Some("main"));
+        assert_eq!(tree.menu_for("*123#"), None);
+
+        let mut position = TreePosition::new("main");
+        assert_eq!(tree.render(&position, &none), "Bank\n1. Balance\n2. Loans\n0. Exit");
+        assert_eq!(tree.select(&mut position, "1", "Invalid", &none), TreeStep::Screen("You have $5".to_string()));
+        assert_eq!(tree.select(&mut position, "2", "Invalid", &none), TreeStep::Screen("Loans\n1. Again\n00. Back".to_string()));
+        // max_menu_depth stops the loans menu from nesting in itself
+        assert_eq!(tree.select(&mut position, "1", "Invalid", &none), TreeStep::Screen("Invalid\nLoans\n1. Again\n00. Back".to_string()));
+        assert_eq!(tree.select(&mut position, "00", "Invalid", &none), TreeStep::Screen("Bank\n1. Balance\n2. Loans\n0. Exit".to_string()));
+        assert_eq!(tree.select(&mut position, "00", "Invalid", &none), TreeStep::Exit);
+        assert_eq!(tree.select(&mut position, "0", "Invalid", &none), TreeStep::Exit);
+    }
+
+    #[test]
+    fn test_input_options_collect_and_validate() {
+        let mut tree = tree();
+        tree.menus.get_mut("main").unwrap().options.push(toml::from_str(
+            r#"
+            key = "3"
+            text = "Top up"
+            action = "input"
+            target = "topped_up"
+            input = { name = "amount", prompt = "Amount?", pattern = "[0-9]+", max_length = 3, error = "Digits only" }
+            "#,
+        ).unwrap());
+        tree.responses.insert("topped_up".to_string(), "Topped up {{session.data.amount}}".to_string());
+        tree.validate().unwrap();
+
+        let mut position = TreePosition::new("main");
+        assert_eq!(tree.select(&mut position, "3", "Invalid", &none), TreeStep::Screen("Amount?".to_string()));
+        assert_eq!(tree.select(&mut position, "12x", "Invalid", &none), TreeStep::Screen("Digits only\nAmount?".to_string()));
+        assert_eq!(tree.select(&mut position, "1000", "Invalid", &none), TreeStep::Screen("Digits only\nAmount?".to_string()));
+        assert_eq!(tree.select(&mut position, "50", "Invalid", &none), TreeStep::Screen("Topped up {{session.data.amount}}".to_string()));
+        assert_eq!(position.data["amount"], "50");
+        assert_eq!(position.awaiting, None);
+
+        // "00" at a prompt goes back to the menu that showed it
+        tree.select(&mut position, "3", "Invalid", &none);
+        assert_eq!(tree.select(&mut position, "00", "Invalid", &none), TreeStep::Screen(tree.render(&position, &none)));
+        assert_eq!(position.awaiting, None);
+    }
+
+    #[test]
+    fn test_conditions_and_set_options_branch_on_session_data() {
+        let mut tree = tree();
+        let main = tree.menus.get_mut("main").unwrap();
+        main.options = toml::from_str::<TreeMenu>(
+            r#"
+            title = "Bank"
+            options = [
+                { key = "1", text = "Register", action = "set", target = "registered", set = { registered = "yes" }, condition = "!data.registered" },
+                { key = "1", text = "Transfer", action = "response", target = "balance", condition = "data.registered == 'yes'" },
+                { key = "2", text = "Gold", action = "exit", condition = "tier == 'gold'" },
+            ]
+            "#,
+        )
+        .unwrap()
+        .options;
+        tree.responses.insert("registered".to_string(), "Registered".to_string());
+        tree.validate().unwrap();
+
+        let gold = |name: &str| (name == "tier").then(|| "gold".to_string());
+        let mut position = TreePosition::new("main");
+        assert_eq!(tree.render(&position, &none), "Bank\n1. Register");
+        assert_eq!(tree.render(&position, &gold), "Bank\n1. Register\n2. Gold");
+        assert_eq!(tree.select(&mut position, "2", "Invalid", &none), TreeStep::Screen("Invalid\nBank\n1. Register".to_string()));
+        assert_eq!(tree.select(&mut position, "1", "Invalid", &none), TreeStep::Screen("Registered".to_string()));
+        assert_eq!(position.data["registered"], "yes");
+        // The same key now picks the other branch
+        assert_eq!(tree.render(&position, &none), "Bank\n1. Transfer");
+        assert_eq!(tree.select(&mut position, "1", "Invalid", &none), TreeStep::Screen("You have $5".to_string()));
+
+        tree.menus.get_mut("main").unwrap().options[2].condition = Some("tier ==".to_string());
+        assert!(tree.validate().unwrap_err().contains("condition"));
+    }
+
+    #[test]
+    fn test_dangling_targets_fail_validation() {
+        let mut tree = tree();
+        tree.responses.clear();
+        assert!(tree.validate().unwrap_err().contains("balance"));
+        tree.codes[0].menu = "missing".to_string();
+        assert!(tree.validate().unwrap_err().contains("missing"));
+    }
+}
@@ -0,0 +1,221 @@
+use std::error::Error;
+use std::fs;
+
+use serde::Serialize;
+use toml_edit::{Array, DocumentMut, Item, Key, Table, Value};
+
+use crate::config::Config;
+
+// A legacy key the loader mapped onto the current schema, and what to write instead
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deprecation {
+    pub key: &'static str,
+    pub guidance: String,
+}
+
+// Rewrites configs written for older releases so they load unchanged. Comments and layout are
+// kept, so the result can also be written back with --migrate-config.
+pub fn migrate(content: &str) -> Result<(String, Vec<Deprecation>), Box<dyn Error>> {
+    let mut doc: DocumentMut = content.parse()?;
+    let mut deprecations = Vec::new();
+    service_code(&mut doc, &mut deprecations);
+    client_simulator(&mut doc, &mut deprecations)?;
+    response_percentage(&mut doc, &mut deprecations)?;
+    Ok((doc.to_string(), deprecations))
+}
+
+// Rewrites `config_path` in the current schema, keeping the original next to it as .bak
+pub fn migrate_file(config_path: &str) -> Result<Vec<Deprecation>, Box<dyn Error>> {
+    let content = fs::read_to_string(config_path)?;
+    let (migrated, deprecations) = migrate(&content)?;
+    // Refuse to write a file the server would still reject
+    toml::from_str::<Config>(&migrated)?;
+    if !deprecations.is_empty() {
+        fs::write(format!("{}.bak", config_path), &content)?;
+        fs::write(config_path, migrated)?;
+    }
+    Ok(deprecations)
+}
+
+// Single `ussd.service_code` string, from before several codes could be served
+fn service_code(doc: &mut DocumentMut, deprecations: &mut Vec<Deprecation>) {
+    let Some(ussd) = doc.get_mut("ussd").and_then(Item::as_table_mut) else {
+        return;
+    };
+    let Some(item) = ussd.get("service_code") else {
+        return;
+    };
+    let legacy: Vec<String> = match item {
+        Item::Value(Value::String(code)) => vec![code.value().clone()],
+        Item::Value(Value::Array(codes)) => codes.iter().filter_map(|code| code.as_str()).map(str::to_string).collect(),
+        _ => Vec::new(),
+    };
+    let mut codes: Vec<String> = ussd
+        .get("service_codes")
+        .and_then(Item::as_array)
+        .map(|codes| codes.iter().filter_map(|code| code.as_str()).map(str::to_string).collect())
+        .unwrap_or_default();
+    for code in legacy {
+        if !codes.contains(&code) {
+            codes.push(code);
+        }
+    }
+
+    // Re-insert every key in order so `service_codes` takes the old key's place and comment
+    let keys: Vec<String> = ussd.iter().map(|(key, _)| key.to_string()).collect();
+    let entries: Vec<(Key, Item)> = keys.iter().filter_map(|name| ussd.remove_entry(name)).collect();
+    for (key, item) in entries {
+        match key.get() {
+            "service_code" => {
+                let mut renamed = Key::new("service_codes");
+                *renamed.leaf_decor_mut() = key.leaf_decor().clone();
+                ussd.insert_formatted(&renamed, toml_edit::value(codes.iter().collect::<Array>()));
+            }
+            "service_codes" => {}
+            _ => {
+                ussd.insert_formatted(&key, item);
+            }
+        }
+    }
+    deprecations.push(Deprecation {
+        key: "ussd.service_code",
+        guidance: format!("renamed to a list; write `service_codes = {:?}`", codes),
+    });
+}
+
+// `[client_simulator]` arrived with forwarding clients, `user_clients` with user simulators
+fn client_simulator(doc: &mut DocumentMut, deprecations: &mut Vec<Deprecation>) -> Result<(), Box<dyn Error>> {
+    let defaults = Config::default().client_simulator;
+    match doc.get_mut("client_simulator").and_then(Item::as_table_mut) {
+        None => {
+            doc.insert("client_simulator", Item::Table(section(&defaults)?));
+            deprecations.push(Deprecation {
+                key: "client_simulator",
+                guidance: format!(
+                    "section is now required; add [client_simulator] (defaults used: forwarding_clients = {:?}, user_clients = {:?})",
+                    defaults.forwarding_clients, defaults.user_clients,
+                ),
+            });
+        }
+        Some(table) if !table.contains_key("user_clients") => {
+            table.insert("user_clients", toml_edit::value(defaults.user_clients.iter().collect::<Array>()));
+            deprecations.push(Deprecation {
+                key: "client_simulator.user_clients",
+                guidance: format!(
+                    "now required; list the system_ids that bind as subscribers (default used: {:?})",
+                    defaults.user_clients,
+                ),
+            });
+        }
+        Some(_) => {}
+    }
+    Ok(())
+}
+
+// Before `[response_percentage]` every SUBMIT_SM succeeded, so that is what a missing section keeps
+fn response_percentage(doc: &mut DocumentMut, deprecations: &mut Vec<Deprecation>) -> Result<(), Box<dyn Error>> {
+    if doc.contains_key("response_percentage") {
+        return Ok(());
+    }
+    let mut defaults = Config::default().response_percentage;
+    defaults.success_percentage = 100.0;
+    defaults.failure_percentage = 0.0;
+    defaults.no_response_percentage = 0.0;
+    doc.insert("response_percentage", Item::Table(section(&defaults)?));
+    deprecations.push(Deprecation {
+        key: "response_percentage",
+        guidance: "section is now required; add [response_percentage] (used: success_percentage = 100.0, \
+                   failure_percentage = 0.0, no_response_percentage = 0.0)"
+            .to_string(),
+    });
+    Ok(())
+}
+
+fn section(value: &impl Serialize) -> Result<Table, Box<dyn Error>> {
+    let doc: DocumentMut = toml::to_string(value)?.parse()?;
+    let mut table = doc.as_table().clone();
+    table.set_implicit(false);
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(content: &str) -> (Config, Vec<&'static str>) {
+        let (migrated, deprecations) = migrate(content).unwrap();
+        let config = toml::from_str(&migrated).unwrap();
+        (config, deprecations.iter().map(|deprecation| deprecation.key).collect())
+    }
+
+    #[test]
+    fn test_current_configs_need_no_migration() {
+        for content in [include_str!("../config.toml"), include_str!("../dev.toml"), include_str!("../prod.toml")] {
+            let (migrated, deprecations) = migrate(content).unwrap();
+            assert!(deprecations.is_empty(), "{:?}", deprecations);
+            assert_eq!(migrated, content);
+        }
+    }
+
+    // Layout of configs written before multiple service codes, forwarding clients and chaos rates
+    const LEGACY: &str = r#"
+[server]
+host = "0.0.0.0"
+port = 2775
+
+[smpp]
+system_id = "USSD_PROD_GW"
+max_connections = 500
+connection_timeout = 600
+
+[ussd]
+service_code = "*100#"
+session_timeout = 300
+
+[ussd.menu]
+welcome_message = "Welcome"
+main_menu = ["1. Check Balance", "0. Exit"]
+
+[ussd.responses]
+balance_message = "Your balance: $15.75"
+invalid_code = "Invalid service code."
+invalid_option = "Invalid selection."
+goodbye_message = "Goodbye"
+
+[ussd.data_packages]
+packages = []
+
+[logging]
+debug = false
+log_file = ""
+"#;
+
+    #[test]
+    fn test_legacy_configs_load_with_deprecations() {
+        let (config, keys) = load(LEGACY);
+        assert_eq!(keys, vec!["ussd.service_code", "client_simulator", "response_percentage"]);
+        assert_eq!(config.ussd.service_codes, vec!["*100#"]);
+        assert!(!config.client_simulator.enabled);
+        assert_eq!(config.response_percentage.success_percentage, 100.0);
+        assert_eq!(config.smpp.system_id, "USSD_PROD_GW");
+
+        let with_forwarding = format!(
+            "{}\n[client_simulator]\nenabled = true\nhost = \"127.0.0.1\"\nport = 9091\nsystem_id = \"ForwardingClient\"\n\
+             password = \"forward123\"\nforwarding_clients = [\"ForwardingClient\"]\n",
+            LEGACY,
+        );
+        let (config, keys) = load(&with_forwarding);
+        assert_eq!(keys, vec!["ussd.service_code", "client_simulator.user_clients", "response_percentage"]);
+        assert_eq!(config.client_simulator.user_clients, vec!["USSDMobileUser"]);
+        assert_eq!(config.client_simulator.system_id, "ForwardingClient");
+    }
+
+    #[test]
+    fn test_service_code_merges_and_keeps_comments() {
+        let content = "[ussd]\n# Production service code\nservice_code = \"*100#\"\nservice_codes = [\"*123#\", \"*100#\"]\n";
+        let (migrated, deprecations) = migrate(content).unwrap();
+        assert!(migrated.contains("# Production service code\nservice_codes = [\"*123#\", \"*100#\"]\n"));
+        assert!(!migrated.contains("service_code ="));
+        assert!(deprecations[0].guidance.contains("service_codes = [\"*123#\", \"*100#\"]"));
+    }
+}
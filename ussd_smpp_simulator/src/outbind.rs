@@ -0,0 +1,70 @@
+use std::io;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::pdu::{Outbind, SmppPdu, ESME_ROK, OUTBIND};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// `[[outbind]]`: an ESME that only accepts inbound connections. The simulator dials it, sends
+// OUTBIND and serves the BIND_RECEIVER that comes back like a bind on a listener.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OutbindConfig {
+    pub host: String,
+    pub port: u16,
+    #[serde(default)]
+    pub system_id: String, // Sent in the OUTBIND; empty sends smpp.system_id
+    #[serde(default)]
+    pub password: String,
+    #[serde(default = "default_reconnect_interval")]
+    pub reconnect_interval: u64, // Seconds before dialling again after a failed attempt or a disconnect
+}
+
+fn default_reconnect_interval() -> u64 {
+    10
+}
+
+impl OutbindConfig {
+    pub fn addr(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+
+    pub fn dial(&self) -> io::Result<TcpStream> {
+        let mut last_error = io::Error::new(io::ErrorKind::NotFound, format!("{} did not resolve", self.addr()));
+        for addr in (self.host.as_str(), self.port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    // `smpp_system_id` stands in when the target names no system_id of its own
+    pub fn pdu(&self, smpp_system_id: &str, sequence_number: u32) -> SmppPdu {
+        let system_id = if self.system_id.is_empty() { smpp_system_id } else { &self.system_id };
+        let outbind = Outbind { system_id: system_id.into(), password: self.password.as_str().into() };
+        SmppPdu::new(OUTBIND, ESME_ROK, sequence_number, outbind.encode())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outbind_pdu_falls_back_to_smpp_system_id() {
+        let mut target: OutbindConfig = toml::from_str("host = \"10.0.0.5\"\nport = 2775\npassword = \"secret\"").unwrap();
+        assert_eq!(target.reconnect_interval, 10);
+
+        let pdu = target.pdu("USSDGateway", 7);
+        assert_eq!((pdu.header.command_id, pdu.header.sequence_number), (OUTBIND, 7));
+        let outbind = Outbind::decode(&pdu.body).unwrap();
+        assert_eq!((outbind.system_id.as_ref(), outbind.password.as_ref()), ("USSDGateway", "secret"));
+
+        target.system_id = "SMSC01".to_string();
+        assert_eq!(Outbind::decode(&target.pdu("USSDGateway", 8).body).unwrap().system_id, "SMSC01");
+    }
+}
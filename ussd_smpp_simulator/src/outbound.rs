@@ -0,0 +1,338 @@
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::pdu::SmppPdu;
+use crate::capture::CaptureTap;
+use crate::transport::SmppStream;
+
+// SMPP 3.4 priority_flag values: 0 (lowest) to 3 (highest)
+pub const PRIORITY_LEVELS: usize = 4;
+
+// How often an idle writer wakes up to expire queued PDUs
+const EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_millis(500);
+
+// What a full outbound queue does with one more PDU
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    #[default]
+    Block, // Wait for the writer to make room, then drop the new PDU on timeout
+    DropNewest,
+    DropLowest, // Evict the newest PDU of the lowest queued priority
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct QueueLimits {
+    pub capacity: usize, // 0 = unbounded
+    pub overflow: OverflowPolicy,
+    pub block_timeout: Duration,
+}
+
+pub fn clamp_priority(priority_flag: u8) -> u8 {
+    priority_flag.min(PRIORITY_LEVELS as u8 - 1)
+}
+
+// Counters shared by every outbound queue, indexed by priority_flag
+#[derive(Debug, Default)]
+pub struct PriorityMetrics {
+    enqueued: [AtomicU64; PRIORITY_LEVELS],
+    sent: [AtomicU64; PRIORITY_LEVELS],
+    jumped: [AtomicU64; PRIORITY_LEVELS],
+    wait_micros: [AtomicU64; PRIORITY_LEVELS],
+    expired: [AtomicU64; PRIORITY_LEVELS],
+    dropped: [AtomicU64; PRIORITY_LEVELS],
+}
+
+impl PriorityMetrics {
+    pub fn snapshot(&self) -> BTreeMap<String, serde_json::Value> {
+        (0..PRIORITY_LEVELS)
+            .map(|level| {
+                let sent = self.sent[level].load(Ordering::Relaxed);
+                let wait_micros = self.wait_micros[level].load(Ordering::Relaxed);
+                let avg_wait_ms = if sent > 0 { wait_micros as f64 / sent as f64 / 1000.0 } else { 0.0 };
+                (
+                    format!("priority_{}", level),
+                    serde_json::json!({
+                        "enqueued": self.enqueued[level].load(Ordering::Relaxed),
+                        "sent": sent,
+                        "jumped_queue": self.jumped[level].load(Ordering::Relaxed),
+                        "avg_wait_ms": avg_wait_ms,
+                        "expired": self.expired[level].load(Ordering::Relaxed),
+                        "dropped": self.dropped[level].load(Ordering::Relaxed),
+                    }),
+                )
+            })
+            .collect()
+    }
+}
+
+// validity_period of a queued PDU, plus the EXPIRED receipt to emit if one was requested
+pub struct Expiry {
+    pub expires_at: SystemTime,
+    pub receipt: Option<(SmppPdu, Arc<OutboundQueue>)>,
+}
+
+struct QueuedPdu {
+    priority: u8,
+    order: u64,
+    queued_at: Instant,
+    pdu: SmppPdu,
+    expiry: Option<Expiry>,
+    message_id: Option<String>, // The message a DELIVER_SM carries, for CANCEL_SM and REPLACE_SM
+}
+
+impl QueuedPdu {
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expiry.as_ref().is_some_and(|expiry| expiry.expires_at <= now)
+    }
+}
+
+// Highest priority first, FIFO within the same priority
+impl Ord for QueuedPdu {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.order.cmp(&self.order))
+    }
+}
+
+impl PartialOrd for QueuedPdu {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for QueuedPdu {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for QueuedPdu {}
+
+#[derive(Default)]
+struct QueueState {
+    pending: BinaryHeap<QueuedPdu>,
+    next_order: u64,
+    closed: bool,
+}
+
+// Per-connection outbound queue drained by a dedicated writer thread, which is the only
+// place that writes to the connection's socket
+pub struct OutboundQueue {
+    state: Mutex<QueueState>,
+    ready: Condvar,
+    space: Condvar,
+    limits: QueueLimits,
+    metrics: Arc<PriorityMetrics>,
+}
+
+impl std::fmt::Debug for OutboundQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pending = self.state.lock().map(|state| state.pending.len()).unwrap_or_default();
+        f.debug_struct("OutboundQueue").field("pending", &pending).finish()
+    }
+}
+
+impl OutboundQueue {
+    pub fn spawn(
+        stream: Arc<Mutex<SmppStream>>,
+        limits: QueueLimits,
+        metrics: Arc<PriorityMetrics>,
+        capture: Option<CaptureTap>,
+    ) -> Arc<Self> {
+        let queue = Arc::new(OutboundQueue {
+            state: Mutex::new(QueueState::default()),
+            ready: Condvar::new(),
+            space: Condvar::new(),
+            limits,
+            metrics,
+        });
+
+        let writer = Arc::clone(&queue);
+        thread::spawn(move || writer.drain(stream, capture));
+        queue
+    }
+
+    pub fn push(&self, priority_flag: u8, pdu: SmppPdu) -> Result<(), String> {
+        self.push_expiring(priority_flag, pdu, None, None)
+    }
+
+    pub fn push_expiring(&self, priority_flag: u8, pdu: SmppPdu, expiry: Option<Expiry>, message_id: Option<&str>) -> Result<(), String> {
+        let priority = clamp_priority(priority_flag);
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return Err("Connection closed".to_string());
+        }
+        
+        if self.limits.capacity > 0 && state.pending.len() >= self.limits.capacity {
+            if let OverflowPolicy::Block = self.limits.overflow {
+                let deadline = Instant::now() + self.limits.block_timeout;
+                while state.pending.len() >= self.limits.capacity && !state.closed {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        break;
+                    }
+                    state = self.space.wait_timeout(state, remaining).unwrap().0;
+                }
+                if state.closed {
+                    return Err("Connection closed".to_string());
+                }
+            }
+            
+            if state.pending.len() >= self.limits.capacity {
+                let lowest = state.pending.iter().min().map(|queued| queued.priority);
+                match (self.limits.overflow, lowest) {
+                    (OverflowPolicy::DropLowest, Some(lowest)) if lowest < priority => {
+                        let mut pending = std::mem::take(&mut state.pending).into_vec();
+                        let evict = (0..pending.len()).min_by(|a, b| pending[*a].cmp(&pending[*b])).unwrap();
+                        let evicted = pending.swap_remove(evict);
+                        state.pending = pending.into();
+                        self.drop_pdu(evicted.priority, &evicted.pdu);
+                    }
+                    _ => {
+                        self.drop_pdu(priority, &pdu);
+                        return Err("Outbound queue full".to_string());
+                    }
+                }
+            }
+        }
+
+        // Count PDUs that will overtake lower-priority traffic already waiting
+        if state.pending.iter().any(|queued| queued.priority < priority) {
+            self.metrics.jumped[priority as usize].fetch_add(1, Ordering::Relaxed);
+        }
+        self.metrics.enqueued[priority as usize].fetch_add(1, Ordering::Relaxed);
+
+        let order = state.next_order;
+        state.next_order += 1;
+        state.pending.push(QueuedPdu {
+            priority,
+            order,
+            queued_at: Instant::now(),
+            pdu,
+            expiry,
+            message_id: message_id.map(str::to_string),
+        });
+        self.ready.notify_one();
+        Ok(())
+    }
+
+    // Takes the PDU queued for `message_id` out; false when none is waiting, e.g. it has been written
+    pub fn cancel(&self, message_id: &str) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let before = state.pending.len();
+        state.pending.retain(|queued| queued.message_id.as_deref() != Some(message_id));
+        let cancelled = state.pending.len() < before;
+        if cancelled {
+            self.space.notify_all();
+        }
+        cancelled
+    }
+
+    // Swaps the PDU queued for `message_id` for what `edit` makes of it, keeping its place in the
+    // queue; false when none is waiting or `edit` declines
+    pub fn replace(&self, message_id: &str, edit: impl FnOnce(&SmppPdu) -> Option<SmppPdu>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let mut pending = std::mem::take(&mut state.pending).into_vec();
+        let replacement = pending
+            .iter_mut()
+            .find(|queued| queued.message_id.as_deref() == Some(message_id))
+            .and_then(|queued| edit(&queued.pdu).map(|pdu| queued.pdu = pdu));
+        state.pending = pending.into();
+        replacement.is_some()
+    }
+
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.ready.notify_all();
+        self.space.notify_all();
+    }
+    
+    fn drop_pdu(&self, priority: u8, pdu: &SmppPdu) {
+        self.metrics.dropped[priority as usize].fetch_add(1, Ordering::Relaxed);
+        info!(
+            "⚠️  Outbound queue full, dropped PDU cmd=0x{:08x} seq={}",
+            pdu.header.command_id, pdu.header.sequence_number
+        );
+    }
+
+    fn drain(&self, stream: Arc<Mutex<SmppStream>>, capture: Option<CaptureTap>) {
+        let mut encoded = Vec::with_capacity(256); // Reused for every PDU on this connection
+        loop {
+            let (queued, expired, closed) = {
+                let mut state = self.state.lock().unwrap();
+                let mut expired = Self::take_expired(&mut state);
+                while state.pending.is_empty() && !state.closed && expired.is_empty() {
+                    state = self.ready.wait_timeout(state, EXPIRY_SWEEP_INTERVAL).unwrap().0;
+                    expired = Self::take_expired(&mut state);
+                }
+                (state.pending.pop(), expired, state.closed)
+            };
+
+            for queued in expired {
+                self.expire(queued);
+            }
+            let Some(queued) = queued else {
+                if closed {
+                    return;
+                }
+                continue;
+            };
+
+            self.space.notify_one();
+            
+            let level = queued.priority as usize;
+            let waited = queued.queued_at.elapsed().as_micros() as u64;
+            self.metrics.wait_micros[level].fetch_add(waited, Ordering::Relaxed);
+
+            encoded.clear();
+            queued.pdu.encode_into(&mut encoded);
+            let mut stream = stream.lock().unwrap();
+            if let Err(e) = stream.write_all(&encoded).and_then(|_| stream.flush()) {
+                info!("⚠️  Outbound write failed: {}", e);
+                drop(stream);
+                self.close();
+                self.state.lock().unwrap().pending.clear();
+                return;
+            }
+            drop(stream);
+            if let Some(capture) = &capture {
+                capture.outbound(&encoded);
+            }
+            self.metrics.sent[level].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn take_expired(state: &mut QueueState) -> Vec<QueuedPdu> {
+        let now = SystemTime::now();
+        if !state.pending.iter().any(|queued| queued.is_expired(now)) {
+            return Vec::new();
+        }
+        let (expired, pending): (Vec<_>, Vec<_>) = state.pending.drain().partition(|queued| queued.is_expired(now));
+        state.pending = pending.into();
+        expired
+    }
+
+    fn expire(&self, queued: QueuedPdu) {
+        self.metrics.expired[queued.priority as usize].fetch_add(1, Ordering::Relaxed);
+        info!(
+            "⌛ Validity period expired for queued PDU cmd=0x{:08x} seq={}",
+            queued.pdu.header.command_id, queued.pdu.header.sequence_number
+        );
+
+        if let Some((receipt, target)) = queued.expiry.and_then(|expiry| expiry.receipt)
+            && let Err(e) = target.push(queued.priority, receipt)
+        {
+            info!("⚠️  Could not deliver EXPIRED receipt: {}", e);
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use log::info;
+use ussd_common::encoding::{self, TextEncoding};
+
+use crate::config::{Config, LongResponseMode};
+
+pub use smpp_codec::command::*;
+pub use smpp_codec::strict;
+pub use smpp_codec::{
+    message_payload_room, Bind, CancelSm, DeliverSm, OptionalParam, Outbind, QuerySm, QuerySmResp, ReplaceSm, SmppError, SmppHeader, SmppPdu, SubmitSm,
+    SubmitSmResp,
+};
+
+// DELIVER_SM carrying USSD text from the gateway to a subscriber, GSM 7-bit or UCS-2 encoded as
+// `encoding` picks. Text over 255 octets goes in the message_payload TLV unless
+// `ussd.long_responses` asks for truncation; text too long for one PDU is cut to fit.
+pub fn build_ussd_deliver_sm(
+    msisdn: &str,
+    text: &str,
+    priority_flag: u8,
+    sequence_number: u32,
+    service_op: Option<u8>,
+    encoding: TextEncoding,
+    config: &Config,
+) -> SmppPdu {
+    let packed = config.smpp.gsm7_packing;
+    let data_coding = encoding.data_coding(text);
+    let encoded = encoding::encode(text, data_coding, packed);
+    let mut deliver_sm = DeliverSm {
+        service_type: "USSD".into(),
+        source_addr_ton: 1, // International
+        source_addr_npi: 1, // ISDN
+        source_addr: "123".into(), // USSD gateway
+        dest_addr_ton: 1,
+        dest_addr_npi: 1,
+        destination_addr: msisdn.into(),
+        esm_class: ESM_CLASS_USSD,
+        priority_flag,
+        data_coding, // 0 GSM 7-bit, 8 UCS-2
+        optional_params: service_op.map(|op| OptionalParam::u8(TAG_USSD_SERVICE_OP, op)).into_iter().collect(),
+        ..Default::default()
+    };
+    let short_message = match config.ussd.long_responses {
+        _ if encoded.len() <= 255 => encoded,
+        LongResponseMode::MessagePayload => {
+            let payload = encoding::encode_within(text, data_coding, packed, message_payload_room(deliver_sm.encode().len()));
+            if payload.len() < encoded.len() {
+                info!("⚠️  Response to {} cut from {} to {} octets to fit one PDU", msisdn, encoded.len(), payload.len());
+            }
+            deliver_sm.optional_params.insert(0, OptionalParam::new(TAG_MESSAGE_PAYLOAD, payload));
+            Vec::new()
+        }
+        LongResponseMode::Truncate => encoding::encode_within(text, data_coding, packed, 255),
+    };
+    deliver_sm.short_message = short_message.into();
+    SmppPdu::new(DELIVER_SM, ESME_ROK, sequence_number, deliver_sm.encode())
+}
+
+// SUBMIT_SM carrying USSD text, GSM 7-bit encoded one septet per octet, or UCS-2 when the
+// subscriber typed characters outside that alphabet. Text over 255 octets goes in the
+// message_payload TLV, cut to what one PDU can carry.
+pub fn build_ussd_submit_sm(
+    source_addr: &str,
+    destination_addr: &str,
+    text: &str,
+    priority_flag: u8,
+    sequence_number: u32,
+    user_message_reference: Option<u16>,
+) -> SmppPdu {
+    let data_coding = TextEncoding::Auto.data_coding(text);
+    let mut submit_sm = SubmitSm {
+        service_type: "USSD".into(),
+        source_addr_ton: 1,
+        source_addr_npi: 1,
+        source_addr: source_addr.into(),
+        destination_addr: destination_addr.into(),
+        esm_class: ESM_CLASS_USSD,
+        priority_flag,
+        data_coding, // 0 GSM 7-bit, 8 UCS-2
+        optional_params: user_message_reference
+            .map(|reference| OptionalParam::u16(TAG_USER_MESSAGE_REFERENCE, reference))
+            .into_iter()
+            .collect(),
+        ..Default::default()
+    };
+    let encoded = encoding::encode(text, data_coding, false);
+    let room = message_payload_room(submit_sm.encode().len());
+    submit_sm.short_message = if encoded.len() > room {
+        let cut = encoding::encode_within(text, data_coding, false, room);
+        info!("⚠️  Request from {} cut from {} to {} octets to fit one PDU", source_addr, encoded.len(), cut.len());
+        cut
+    } else {
+        encoded
+    }
+    .into();
+    SmppPdu::new(SUBMIT_SM, ESME_ROK, sequence_number, submit_sm.encode())
+}
+
+pub fn bind_type_name(command_id: u32) -> &'static str {
+    match command_id {
+        BIND_RECEIVER => "receiver",
+        BIND_TRANSMITTER => "transmitter",
+        _ => "transceiver",
+    }
+}
+
+// data_coding 0 is the GSM 7-bit default alphabet and 8 is UCS-2; anything else is taken as UTF-8
+pub fn message_text(data_coding: u8, message: &[u8], gsm7_packing: bool) -> String {
+    encoding::decode(message, data_coding, gsm7_packing)
+}
+
+// Text of a DELIVER_SM from a forwarding client, which always sends one septet per octet
+pub fn deliver_sm_text(deliver_sm: &DeliverSm) -> String {
+    message_text(deliver_sm.data_coding, deliver_sm.message(), false)
+}
+
+// The bind's TAG_SCREEN_CHARS, when it declares a non-zero size
+pub fn declared_screen_chars(bind: &Bind) -> Option<usize> {
+    bind.optional_param(TAG_SCREEN_CHARS)
+        .and_then(OptionalParam::as_u16)
+        .map(|chars| chars as usize)
+        .filter(|&chars| chars > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, LongResponseMode};
+
+    #[test]
+    fn test_notify_service_op_round_trips() {
+        let config = Config::default();
+        let notify = build_ussd_deliver_sm("111", "Goodbye!", 0, 1, Some(USSD_NOTIFY), TextEncoding::Gsm7, &config);
+        let menu = build_ussd_deliver_sm("111", "1. Balance", 0, 2, None, TextEncoding::Gsm7, &config);
+
+        let notify = DeliverSm::decode(&notify.body).unwrap();
+        assert_eq!(notify.ussd_service_op(), Some(USSD_NOTIFY));
+        assert_eq!(deliver_sm_text(&notify), "Goodbye!");
+        assert_eq!(DeliverSm::decode(&menu.body).unwrap().ussd_service_op(), None);
+    }
+
+    #[test]
+    fn test_ucs2_menu_round_trips() {
+        let config = Config::default();
+        let text = "1. ශේෂය\n2. இருப்பு\n3. الرصيد";
+        let pdu = build_ussd_deliver_sm("111", text, 0, 1, None, TextEncoding::Ucs2, &config);
+        let deliver_sm = DeliverSm::decode(&pdu.body).unwrap();
+        assert_eq!(deliver_sm.data_coding, encoding::DATA_CODING_UCS2);
+        assert_eq!(deliver_sm.short_message.len(), text.chars().count() * 2);
+        assert_eq!(deliver_sm_text(&deliver_sm), text);
+
+        // Auto only switches to UCS-2 for text GSM 7-bit cannot carry
+        let pdu = build_ussd_deliver_sm("111", "1. Balance", 0, 2, None, TextEncoding::Auto, &config);
+        assert_eq!(DeliverSm::decode(&pdu.body).unwrap().data_coding, encoding::DATA_CODING_GSM7);
+    }
+
+    // message_payload is bounded by MAX_PDU_LEN, not just the TLV's u16 length field
+    #[test]
+    fn test_oversized_text_is_cut_to_one_pdu() {
+        let config = Config::default();
+        let text = "x".repeat(u16::MAX as usize);
+        let pdu = build_ussd_deliver_sm("111", &text, 0, 1, Some(USSD_NOTIFY), TextEncoding::Gsm7, &config);
+        assert_eq!(pdu.to_bytes().len(), smpp_codec::MAX_PDU_LEN);
+        let deliver_sm = DeliverSm::decode(&pdu.body).unwrap();
+        assert_eq!(deliver_sm.ussd_service_op(), Some(USSD_NOTIFY));
+        assert!(deliver_sm.message().len() < text.len());
+        assert!(SmppPdu::decode(&pdu.to_bytes()).is_ok());
+
+        let pdu = build_ussd_submit_sm("111", "*123#", &"ශ".repeat(u16::MAX as usize / 2), 0, 1, Some(7));
+        assert!(pdu.to_bytes().len() <= smpp_codec::MAX_PDU_LEN);
+        let submit_sm = SubmitSm::decode(&pdu.body).unwrap();
+        assert!(submit_sm.message().len() < u16::MAX as usize - 1);
+        assert_eq!(submit_sm.message().len() % 2, 0); // Whole UCS-2 characters
+        assert_eq!(submit_sm.user_message_reference(), Some(7));
+
+        // Text that fits is carried whole
+        let pdu = build_ussd_deliver_sm("111", &"x".repeat(1000), 0, 1, None, TextEncoding::Gsm7, &config);
+        assert_eq!(DeliverSm::decode(&pdu.body).unwrap().message().len(), 1000);
+    }
+
+    #[test]
+    fn test_long_ucs2_response_truncates_on_character_boundary() {
+        let mut config = Config::default();
+        config.ussd.long_responses = LongResponseMode::Truncate;
+        let pdu = build_ussd_deliver_sm("111", &"ශ".repeat(200), 0, 1, None, TextEncoding::Ucs2, &config);
+        let deliver_sm = DeliverSm::decode(&pdu.body).unwrap();
+        assert_eq!(deliver_sm.short_message.len(), 254);
+        assert_eq!(deliver_sm_text(&deliver_sm), "ශ".repeat(127));
+    }
+}
@@ -0,0 +1,424 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use ussd_common::run_id;
+
+use crate::pdu::{
+    MESSAGE_STATE_DELETED, MESSAGE_STATE_DELIVERED, MESSAGE_STATE_ENROUTE, MESSAGE_STATE_EXPIRED, MESSAGE_STATE_UNDELIVERABLE,
+};
+
+// SMPP sequence numbers run from 0x00000001 to 0x7FFFFFFF
+const MAX_SEQUENCE: u32 = 0x7FFF_FFFF;
+
+// Sequence numbers are reserved on disk this many at a time, so a crash between flushes
+// never hands out a number twice: a restart resumes past the whole reserved block
+const SEQUENCE_BLOCK: u32 = 1000;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PersistenceConfig {
+    pub enabled: bool,
+    pub path: String,
+    pub flush_interval_ms: u64,
+    pub max_message_ids: usize, // Oldest entries are dropped from the registry beyond this
+}
+
+impl Default for PersistenceConfig {
+    fn default() -> Self {
+        PersistenceConfig {
+            enabled: false,
+            path: "simulator_state.json".to_string(),
+            flush_interval_ms: 1000,
+            max_message_ids: 10000,
+        }
+    }
+}
+
+// Where an issued message stands, as QUERY_SM reports it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MessageState {
+    #[default]
+    Enroute,
+    Delivered,
+    Expired, // Reported EXPIRED by a delivery receipt
+    Deleted, // Cancelled by CANCEL_SM before it left the queue
+    Undeliverable,
+}
+
+impl MessageState {
+    // message_state in QUERY_SM_RESP
+    pub fn code(self) -> u8 {
+        match self {
+            MessageState::Enroute => MESSAGE_STATE_ENROUTE,
+            MessageState::Delivered => MESSAGE_STATE_DELIVERED,
+            MessageState::Expired => MESSAGE_STATE_EXPIRED,
+            MessageState::Deleted => MESSAGE_STATE_DELETED,
+            MessageState::Undeliverable => MESSAGE_STATE_UNDELIVERABLE,
+        }
+    }
+
+    // The SMPP message_state name, spelt as the saved registry spells it
+    pub fn name(self) -> &'static str {
+        match self {
+            MessageState::Enroute => "ENROUTE",
+            MessageState::Delivered => "DELIVERED",
+            MessageState::Expired => "EXPIRED",
+            MessageState::Deleted => "DELETED",
+            MessageState::Undeliverable => "UNDELIVERABLE",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MessageIdRecord {
+    pub message_id: String,
+    pub system_id: String,
+    pub msisdn: String,
+    pub issued_at: u64,
+    #[serde(default)]
+    pub session_id: String, // Empty until a USSD session takes the message, and for pushes
+    #[serde(default)]
+    pub service_code: String, // The code that opened that session
+    #[serde(default)]
+    pub state: MessageState,
+    #[serde(default)]
+    pub final_at: Option<u64>, // Seconds since the epoch when the state stopped being ENROUTE
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+struct PersistedState {
+    sequence: u32,
+    reserved_through: u32, // High-water mark: no sequence beyond this has been issued
+    #[serde(skip)]
+    reserved_left: u32, // Sequences left in the current block before another must be saved
+    // Last request sequence per "system_id@connection_id". Connection ids start over with the
+    // process, so a saved entry would belong to whichever connection reuses the id: never saved.
+    #[serde(skip)]
+    inbound_sequences: BTreeMap<String, u32>,
+    message_ids: VecDeque<MessageIdRecord>,
+}
+
+impl Default for PersistedState {
+    fn default() -> Self {
+        PersistedState {
+            sequence: 1,
+            reserved_through: 0,
+            reserved_left: 0,
+            inbound_sequences: BTreeMap::new(),
+            message_ids: VecDeque::new(),
+        }
+    }
+}
+
+// Sequence numbers and issued message_ids, optionally saved so a restart continues where it left off
+#[derive(Debug)]
+pub struct StateStore {
+    config: PersistenceConfig,
+    state: Mutex<PersistedState>,
+    dirty: AtomicBool,
+}
+
+impl StateStore {
+    pub fn load(config: &PersistenceConfig) -> Self {
+        let state = if config.enabled && Path::new(&config.path).exists() {
+            match fs::read_to_string(&config.path).map_err(|e| e.to_string()).and_then(|content| {
+                serde_json::from_str::<PersistedState>(&content).map_err(|e| e.to_string())
+            }) {
+                Ok(mut state) => {
+                    // Anything up to the high-water mark may have been issued before a crash.
+                    // Files written before the mark existed skip a whole block instead.
+                    state.sequence = if state.reserved_through != 0 {
+                        state.reserved_through
+                    } else {
+                        advance_sequence(state.sequence, SEQUENCE_BLOCK)
+                    };
+                    info!(
+                        "💾 Restored state from {}: sequence={}, {} message_id(s)",
+                        config.path,
+                        state.sequence,
+                        state.message_ids.len()
+                    );
+                    state
+                }
+                Err(e) => {
+                    info!("⚠️  Could not read state file {}: {}, starting fresh", config.path, e);
+                    PersistedState::default()
+                }
+            }
+        } else {
+            PersistedState::default()
+        };
+
+        StateStore {
+            config: config.clone(),
+            state: Mutex::new(state),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    pub fn next_sequence(&self) -> u32 {
+        let mut state = self.state.lock().unwrap();
+        if self.config.enabled && state.reserved_left == 0 {
+            state.reserved_through = advance_sequence(state.sequence, SEQUENCE_BLOCK);
+            state.reserved_left = SEQUENCE_BLOCK;
+            // Saved before any number in the block goes out; a failure only weakens crash safety
+            if let Err(e) = self.write_state(&state) {
+                info!("⚠️  Could not save state to {}: {}", self.config.path, e);
+            }
+        }
+        state.sequence = advance_sequence(state.sequence, 1);
+        state.reserved_left = state.reserved_left.saturating_sub(1);
+        self.dirty.store(true, Ordering::Relaxed);
+        state.sequence
+    }
+
+    pub fn issue_message_id(&self, system_id: &str, msisdn: &str) -> String {
+        let issued_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let message_id = run_id::stamp(format!("USSD{}{:04}", issued_at, self.next_sequence()));
+
+        // Kept in memory either way so QUERY_SM can answer; only saved when persistence is on
+        let mut state = self.state.lock().unwrap();
+        state.message_ids.push_back(MessageIdRecord {
+            message_id: message_id.clone(),
+            system_id: system_id.to_string(),
+            msisdn: msisdn.to_string(),
+            issued_at,
+            session_id: String::new(),
+            service_code: String::new(),
+            state: MessageState::Enroute,
+            final_at: None,
+        });
+        while state.message_ids.len() > self.config.max_message_ids {
+            state.message_ids.pop_front();
+        }
+        message_id
+    }
+
+    // Moves a registered message on from ENROUTE; false when the registry no longer holds it
+    pub fn set_message_state(&self, message_id: &str, message_state: MessageState) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let Some(record) = state.message_ids.iter_mut().rev().find(|record| record.message_id == message_id) else {
+            return false;
+        };
+        record.state = message_state;
+        record.final_at = (message_state != MessageState::Enroute)
+            .then(|| SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs());
+        self.dirty.store(true, Ordering::Relaxed);
+        true
+    }
+
+    // Ties a registered message to the USSD session that handled it, for the CDR export
+    pub fn set_message_session(&self, message_id: &str, session_id: &str, service_code: &str) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let Some(record) = state.message_ids.iter_mut().rev().find(|record| record.message_id == message_id) else {
+            return false;
+        };
+        record.session_id = session_id.to_string();
+        record.service_code = service_code.to_string();
+        self.dirty.store(true, Ordering::Relaxed);
+        true
+    }
+
+    pub fn lookup_message_id(&self, message_id: &str) -> Option<MessageIdRecord> {
+        let state = self.state.lock().unwrap();
+        state.message_ids.iter().rev().find(|record| record.message_id == message_id).cloned()
+    }
+
+    // Every registered message_id, oldest first
+    pub fn message_ids(&self) -> Vec<MessageIdRecord> {
+        self.state.lock().unwrap().message_ids.iter().cloned().collect()
+    }
+
+    // Records an inbound request sequence number and reports how it relates to the last one
+    pub fn record_inbound_sequence(&self, binding: &str, sequence: u32) -> InboundSequence {
+        if !self.config.enabled {
+            return InboundSequence::Advanced;
+        }
+        let mut state = self.state.lock().unwrap();
+        match state.inbound_sequences.insert(binding.to_string(), sequence) {
+            Some(previous) if previous == sequence => InboundSequence::Repeated,
+            // A jump back of more than half the range is the counter wrapping, not a rewind
+            Some(previous) if sequence < previous && previous - sequence < MAX_SEQUENCE / 2 => {
+                InboundSequence::Rewound(previous)
+            }
+            _ => InboundSequence::Advanced,
+        }
+    }
+
+    // Each bind numbers its requests afresh, so a closed connection's entry is dropped
+    pub fn forget_inbound_sequence(&self, binding: &str) {
+        self.state.lock().unwrap().inbound_sequences.remove(binding);
+    }
+
+    pub fn spawn_flusher(self: &Arc<Self>) {
+        if !self.config.enabled {
+            return;
+        }
+        info!("💾 Persisting sequence and message_id state to {}", self.config.path);
+
+        let store = Arc::clone(self);
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_millis(store.config.flush_interval_ms));
+            if store.dirty.swap(false, Ordering::Relaxed)
+                && let Err(e) = store.flush()
+            {
+                info!("⚠️  Could not save state to {}: {}", store.config.path, e);
+            }
+        });
+    }
+
+    pub fn flush(&self) -> std::io::Result<()> {
+        let state = self.state.lock().unwrap();
+        self.write_state(&state)
+    }
+
+    // Final save on a graceful exit; does nothing when persistence is off
+    pub fn shutdown(&self) {
+        if !self.config.enabled {
+            return;
+        }
+        match self.flush() {
+            Ok(()) => info!("💾 Saved state to {}", self.config.path),
+            Err(e) => info!("⚠️  Could not save state to {}: {}", self.config.path, e),
+        }
+    }
+
+    fn write_state(&self, state: &PersistedState) -> std::io::Result<()> {
+        let content = serde_json::to_string_pretty(state)?;
+        // Write then rename so a crash mid-write never leaves a truncated file
+        let temp_path = format!("{}.tmp", self.config.path);
+        fs::write(&temp_path, content)?;
+        fs::rename(&temp_path, &self.config.path)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InboundSequence {
+    Advanced,
+    Repeated,      // Same number as the last request: a retransmission
+    Rewound(u32),  // Lower than the last request (carried here) without wrapping
+}
+
+fn advance_sequence(sequence: u32, by: u32) -> u32 {
+    ((sequence.max(1) as u64 - 1 + by as u64) % MAX_SEQUENCE as u64) as u32 + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(name: &str) -> PersistenceConfig {
+        let path = std::env::temp_dir().join(format!("ussd_state_{}_{}.json", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        PersistenceConfig {
+            enabled: true,
+            path: path.to_string_lossy().into_owned(),
+            flush_interval_ms: 1000,
+            max_message_ids: 2,
+        }
+    }
+
+    #[test]
+    fn test_restart_after_crash_never_reissues_a_sequence() {
+        let config = config("crash");
+        let store = StateStore::load(&config);
+        let issued: Vec<u32> = (0..5).map(|_| store.next_sequence()).collect();
+        assert_eq!(issued, vec![2, 3, 4, 5, 6]);
+        // No flush: the process dies with only the block reservation on disk
+        drop(store);
+
+        let restored = StateStore::load(&config);
+        assert_eq!(restored.next_sequence(), 2 + SEQUENCE_BLOCK);
+        fs::remove_file(&config.path).unwrap();
+    }
+
+    #[test]
+    fn test_flush_and_restore_message_ids() {
+        let config = config("flush");
+        let store = StateStore::load(&config);
+        let ids: Vec<String> = ["111", "222", "333"]
+            .iter()
+            .map(|msisdn| store.issue_message_id("USSDMobileUser", msisdn))
+            .collect();
+        store.flush().unwrap();
+
+        let restored = StateStore::load(&config);
+        // The registry keeps only the newest max_message_ids entries
+        assert!(restored.lookup_message_id(&ids[0]).is_none());
+        let record = restored.lookup_message_id(&ids[2]).unwrap();
+        assert_eq!((record.system_id.as_str(), record.msisdn.as_str()), ("USSDMobileUser", "333"));
+        fs::remove_file(&config.path).unwrap();
+    }
+
+    #[test]
+    fn test_message_state_tracking() {
+        let store = StateStore::load(&PersistenceConfig::default());
+        let message_id = store.issue_message_id("USSDMobileUser", "111");
+        let record = store.lookup_message_id(&message_id).unwrap();
+        assert_eq!((record.state, record.final_at), (MessageState::Enroute, None));
+
+        assert!(store.set_message_state(&message_id, MessageState::Undeliverable));
+        let record = store.lookup_message_id(&message_id).unwrap();
+        assert_eq!(record.state.code(), MESSAGE_STATE_UNDELIVERABLE);
+        assert!(record.final_at.is_some_and(|final_at| final_at >= record.issued_at));
+        assert!(!store.set_message_state("USSD0", MessageState::Delivered));
+    }
+
+    #[test]
+    fn test_unreadable_file_starts_fresh() {
+        let config = config("corrupt");
+        fs::write(&config.path, "{not json").unwrap();
+        let store = StateStore::load(&config);
+        assert_eq!(store.next_sequence(), 2);
+        fs::remove_file(&config.path).unwrap();
+    }
+
+    #[test]
+    fn test_inbound_sequence_tracking() {
+        let config = config("inbound");
+        let store = StateStore::load(&config);
+        assert_eq!(store.record_inbound_sequence("ESME@conn_1", 7), InboundSequence::Advanced);
+        assert_eq!(store.record_inbound_sequence("ESME@conn_1", 7), InboundSequence::Repeated);
+        assert_eq!(store.record_inbound_sequence("ESME@conn_1", 5), InboundSequence::Rewound(7));
+        assert_eq!(store.record_inbound_sequence("ESME@conn_1", MAX_SEQUENCE), InboundSequence::Advanced);
+        assert_eq!(store.record_inbound_sequence("ESME@conn_1", 1), InboundSequence::Advanced);
+        // Another bind of the same system_id numbers independently
+        assert_eq!(store.record_inbound_sequence("ESME@conn_2", 1), InboundSequence::Advanced);
+
+        store.forget_inbound_sequence("ESME@conn_1");
+        assert_eq!(store.record_inbound_sequence("ESME@conn_1", 1), InboundSequence::Advanced);
+        let _ = fs::remove_file(&config.path);
+    }
+
+    #[test]
+    fn test_inbound_sequences_are_not_restored() {
+        let config = config("inbound_restart");
+        let store = StateStore::load(&config);
+        assert_eq!(store.record_inbound_sequence("ESME@conn_1", 7), InboundSequence::Advanced);
+        store.flush().unwrap();
+
+        // The next process's conn_1 is another connection, so its 7 is not a repeat
+        let restored = StateStore::load(&config);
+        assert_eq!(restored.record_inbound_sequence("ESME@conn_1", 7), InboundSequence::Advanced);
+        fs::remove_file(&config.path).unwrap();
+    }
+
+    #[test]
+    fn test_sequence_wraps_within_smpp_range() {
+        assert_eq!(advance_sequence(MAX_SEQUENCE, 1), 1);
+        assert_eq!(advance_sequence(MAX_SEQUENCE - 1, 3), 2);
+        assert_eq!(advance_sequence(0, 1), 2);
+    }
+}
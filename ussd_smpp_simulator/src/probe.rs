@@ -0,0 +1,374 @@
+use std::io::{self, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+use crate::codec::{PduReadBuffer, PduReader};
+use crate::transport::SmppStream;
+use crate::pdu::{
+    build_ussd_submit_sm, SmppPdu, BIND_TRANSCEIVER, DELIVER_SM, DELIVER_SM_RESP, ENQUIRE_LINK,
+    ENQUIRE_LINK_RESP, ESME_ROK, GENERIC_NACK, SUBMIT_SM, TAG_MESSAGE_PAYLOAD, TAG_USSD_SERVICE_OP, UNBIND,
+};
+
+const DATA_SM: u32 = 0x00000103;
+// Never assigned by SMPP 3.4 or 5.0; a compliant peer answers it with GENERIC_NACK
+const UNASSIGNED_COMMAND: u32 = 0x000000FF;
+// Start of the vendor-specific TLV range, which peers should skip when they do not know it
+const TAG_VENDOR_SPECIFIC: u16 = 0x1400;
+const TAG_SC_INTERFACE_VERSION: u16 = 0x0210;
+
+pub struct ProbeOptions {
+    pub addr: String,
+    pub system_id: String,
+    pub password: String,
+    pub source_addr: String,
+    pub destination_addr: String,
+    pub timeout: Duration, // Per check
+}
+
+impl ProbeOptions {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut addr = None;
+        let mut options = ProbeOptions {
+            addr: String::new(),
+            system_id: "probe".to_string(),
+            password: "probe".to_string(),
+            source_addr: "0".to_string(),
+            destination_addr: "0".to_string(),
+            timeout: Duration::from_secs(5),
+        };
+        let mut i = 0;
+        while i < args.len() {
+            if !args[i].starts_with('-') {
+                if addr.replace(args[i].clone()).is_some() {
+                    return Err(format!("Unexpected argument: {}", args[i]));
+                }
+                i += 1;
+                continue;
+            }
+            let value = args.get(i + 1).ok_or_else(|| format!("{} requires a value", args[i]))?.clone();
+            match args[i].as_str() {
+                "--system-id" => options.system_id = value,
+                "--password" => options.password = value,
+                "--source" => options.source_addr = value,
+                "--destination" => options.destination_addr = value,
+                "--timeout" => {
+                    let seconds: u64 = value.parse().map_err(|_| format!("Invalid value for --timeout: {}", value))?;
+                    options.timeout = Duration::from_secs(seconds.max(1));
+                }
+                other => return Err(format!("Unknown probe option: {}", other)),
+            }
+            i += 2;
+        }
+        options.addr = addr.ok_or("probe requires <host:port>")?;
+        Ok(options)
+    }
+}
+
+// How the peer answered one check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Accepted,
+    Rejected(u32),    // The expected response with a non-zero command_status
+    GenericNack(u32), // GENERIC_NACK with its command_status
+    Unexpected(u32),  // Some other command_id answered the sequence number
+    NoResponse,
+    Disconnected,
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Accepted => write!(f, "supported"),
+            Outcome::Rejected(status) => write!(f, "rejected 0x{:08X}", status),
+            Outcome::GenericNack(status) => write!(f, "generic_nack 0x{:08X}", status),
+            Outcome::Unexpected(command_id) => write!(f, "unexpected reply 0x{:08x}", command_id),
+            Outcome::NoResponse => write!(f, "no response"),
+            Outcome::Disconnected => write!(f, "connection closed"),
+        }
+    }
+}
+
+pub struct CheckResult {
+    pub name: &'static str,
+    pub outcome: Outcome,
+    pub elapsed: Duration,
+}
+
+// Blocking ESME that sends one PDU at a time and waits for the reply to its sequence number
+pub struct Prober {
+    stream: SmppStream,
+    reader: PduReadBuffer,
+    sequence: u32,
+    timeout: Duration,
+}
+
+impl Prober {
+    pub fn new(stream: SmppStream, timeout: Duration) -> Self {
+        Prober { stream, reader: PduReadBuffer::new(), sequence: 0, timeout }
+    }
+
+    // Binds, runs every check and unbinds. A failed bind ends the battery, as nothing else is
+    // answered before it.
+    pub fn run(&mut self, options: &ProbeOptions) -> Vec<CheckResult> {
+        let mut results = Vec::new();
+        let (bind, peer) = self.bind(&options.system_id, &options.password);
+        let bound = bind.outcome == Outcome::Accepted;
+        results.push(bind);
+        if let Some(version) = peer.interface_version {
+            println!("Peer system_id {:?}, sc_interface_version 0x{:02x}", peer.system_id, version);
+        } else if bound {
+            println!("Peer system_id {:?}, no sc_interface_version (SMPP 3.3 or unspecified)", peer.system_id);
+        }
+        if !bound {
+            return results;
+        }
+
+        let (source, destination) = (options.source_addr.as_str(), options.destination_addr.as_str());
+        results.push(self.check("enquire_link", ENQUIRE_LINK, Vec::new()));
+        results.push(self.check("submit_sm (minimal)", SUBMIT_SM, minimal_submit_body(source, destination)));
+
+        let mut payload = minimal_submit_body(source, destination);
+        push_tlv(&mut payload, TAG_MESSAGE_PAYLOAD, b"probe");
+        results.push(self.check("submit_sm + message_payload", SUBMIT_SM, payload));
+
+        let mut ussd = minimal_submit_body(source, destination);
+        push_tlv(&mut ussd, TAG_USSD_SERVICE_OP, &[1]);
+        results.push(self.check("submit_sm + ussd_service_op", SUBMIT_SM, ussd));
+
+        let sequence = self.next_sequence();
+        let ussd_submit = build_ussd_submit_sm(source, destination, "*100#", 0, sequence, None);
+        results.push(self.check_pdu("submit_sm (USSD service_type)", ussd_submit));
+
+        let mut vendor = minimal_submit_body(source, destination);
+        push_tlv(&mut vendor, TAG_VENDOR_SPECIFIC, &[0]);
+        results.push(self.check("submit_sm + unknown TLV 0x1400", SUBMIT_SM, vendor));
+
+        results.push(self.check("data_sm", DATA_SM, minimal_data_sm_body(source, destination)));
+        results.push(self.check("unassigned command 0xff", UNASSIGNED_COMMAND, Vec::new()));
+        results.push(self.check("unbind", UNBIND, Vec::new()));
+        results
+    }
+
+    fn bind(&mut self, system_id: &str, password: &str) -> (CheckResult, PeerInfo) {
+        let mut body = Vec::new();
+        body.extend_from_slice(system_id.as_bytes());
+        body.push(0);
+        body.extend_from_slice(password.as_bytes());
+        body.push(0);
+        body.extend_from_slice(b"PROBE\0"); // system_type
+        body.push(0x34); // interface_version
+        body.push(0); // addr_ton
+        body.push(0); // addr_npi
+        body.push(0); // address_range
+
+        let sequence = self.next_sequence();
+        let started = Instant::now();
+        let (outcome, reply) = self.exchange(request(BIND_TRANSCEIVER, sequence, body));
+        let peer = reply.map(|pdu| PeerInfo::parse(&pdu.body)).unwrap_or_default();
+        (CheckResult { name: "bind_transceiver", outcome, elapsed: started.elapsed() }, peer)
+    }
+
+    fn check(&mut self, name: &'static str, command_id: u32, body: Vec<u8>) -> CheckResult {
+        let sequence = self.next_sequence();
+        self.check_pdu(name, request(command_id, sequence, body))
+    }
+
+    fn check_pdu(&mut self, name: &'static str, pdu: SmppPdu) -> CheckResult {
+        let started = Instant::now();
+        let (outcome, _) = self.exchange(pdu);
+        CheckResult { name, outcome, elapsed: started.elapsed() }
+    }
+
+    // Sends `pdu` and classifies the reply to its sequence number. Requests the peer sends in the
+    // meantime are acknowledged so it does not give up on the bind.
+    fn exchange(&mut self, pdu: SmppPdu) -> (Outcome, Option<SmppPdu>) {
+        let sequence = pdu.header.sequence_number;
+        let expected = pdu.header.command_id | 0x80000000;
+        if self.send(&pdu).is_err() {
+            return (Outcome::Disconnected, None);
+        }
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() || self.stream.set_read_timeout(Some(remaining)).is_err() {
+                return (Outcome::NoResponse, None);
+            }
+            let reply = match self.reader.read_pdu(&mut self.stream) {
+                Ok(reply) => reply,
+                Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                    return (Outcome::NoResponse, None);
+                }
+                Err(_) => return (Outcome::Disconnected, None),
+            };
+            match reply.header.command_id {
+                DELIVER_SM | ENQUIRE_LINK => {
+                    let ack = if reply.header.command_id == DELIVER_SM { DELIVER_SM_RESP } else { ENQUIRE_LINK_RESP };
+                    let _ = self.send(&request(ack, reply.header.sequence_number, Vec::new()));
+                }
+                _ if reply.header.sequence_number != sequence => {}
+                command_id => {
+                    let status = reply.header.command_status;
+                    let outcome = match command_id {
+                        GENERIC_NACK => Outcome::GenericNack(status),
+                        _ if command_id != expected => Outcome::Unexpected(command_id),
+                        _ if status == ESME_ROK => Outcome::Accepted,
+                        _ => Outcome::Rejected(status),
+                    };
+                    return (outcome, Some(reply));
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, pdu: &SmppPdu) -> io::Result<()> {
+        self.stream.write_all(&pdu.to_bytes())?;
+        self.stream.flush()
+    }
+
+    fn next_sequence(&mut self) -> u32 {
+        self.sequence += 1;
+        self.sequence
+    }
+}
+
+#[derive(Debug, Default)]
+struct PeerInfo {
+    system_id: String,
+    interface_version: Option<u8>,
+}
+
+impl PeerInfo {
+    fn parse(body: &[u8]) -> Self {
+        let mut reader = PduReader::new(body);
+        let system_id = reader.c_str().into_owned();
+        let mut interface_version = None;
+        while let (Ok(tag), Ok(length)) = (reader.u16(), reader.u16()) {
+            let Ok(value) = reader.bytes(length as usize) else { break };
+            if tag == TAG_SC_INTERFACE_VERSION && length == 1 {
+                interface_version = Some(value[0]);
+            }
+        }
+        PeerInfo { system_id, interface_version }
+    }
+}
+
+fn request(command_id: u32, sequence_number: u32, body: Vec<u8>) -> SmppPdu {
+    SmppPdu::new(command_id, ESME_ROK, sequence_number, body)
+}
+
+// Mandatory SUBMIT_SM fields only, with an empty short_message and no delivery receipt
+fn minimal_submit_body(source_addr: &str, destination_addr: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.push(0); // service_type
+    push_addresses(&mut body, source_addr, destination_addr);
+    body.push(0); // esm_class
+    body.push(0); // protocol_id
+    body.push(0); // priority_flag
+    body.push(0); // schedule_delivery_time
+    body.push(0); // validity_period
+    body.push(0); // registered_delivery
+    body.push(0); // replace_if_present_flag
+    body.push(0); // data_coding
+    body.push(0); // sm_default_msg_id
+    body.push(0); // sm_length
+    body
+}
+
+fn minimal_data_sm_body(source_addr: &str, destination_addr: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.push(0); // service_type
+    push_addresses(&mut body, source_addr, destination_addr);
+    body.push(0); // esm_class
+    body.push(0); // registered_delivery
+    body.push(0); // data_coding
+    body
+}
+
+fn push_addresses(body: &mut Vec<u8>, source_addr: &str, destination_addr: &str) {
+    body.push(1); // source_addr_ton
+    body.push(1); // source_addr_npi
+    body.extend_from_slice(source_addr.as_bytes());
+    body.push(0);
+    body.push(1); // dest_addr_ton
+    body.push(1); // dest_addr_npi
+    body.extend_from_slice(destination_addr.as_bytes());
+    body.push(0);
+}
+
+fn push_tlv(body: &mut Vec<u8>, tag: u16, value: &[u8]) {
+    body.extend_from_slice(&tag.to_be_bytes());
+    body.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    body.extend_from_slice(value);
+}
+
+// Probes the peer at `options.addr` and prints what it supports and rejects
+pub fn run(options: ProbeOptions) -> io::Result<()> {
+    println!("Probing {} as {}", options.addr, options.system_id);
+    let stream = TcpStream::connect(&options.addr)?;
+    let mut prober = Prober::new(SmppStream::Tcp(stream), options.timeout);
+    let results = prober.run(&options);
+
+    println!();
+    println!("{:<32} {:<28} {:>8}", "Check", "Result", "Time");
+    for result in &results {
+        println!("{:<32} {:<28} {:>6}ms", result.name, result.outcome.to_string(), result.elapsed.as_millis());
+    }
+    let supported = results.iter().filter(|result| result.outcome == Outcome::Accepted).count();
+    println!();
+    println!("{} of {} checks accepted", supported, results.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::pdu::ESME_RINVCMDID;
+    use crate::server::UssdSmppServer;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_options() {
+        let options = ProbeOptions::parse(&args(&["10.0.0.1:2775", "--system-id", "esme", "--timeout", "2"])).unwrap();
+        assert_eq!(options.addr, "10.0.0.1:2775");
+        assert_eq!(options.system_id, "esme");
+        assert_eq!(options.timeout, Duration::from_secs(2));
+        assert!(ProbeOptions::parse(&args(&["--system-id", "esme"])).is_err());
+        assert!(ProbeOptions::parse(&args(&["a:1", "b:2"])).is_err());
+        assert!(ProbeOptions::parse(&args(&["a:1", "--tls"])).is_err());
+    }
+
+    #[test]
+    fn test_probe_against_simulator() {
+        let server = UssdSmppServer::new(Config::deterministic());
+
+        let options = ProbeOptions::parse(&args(&[
+            "in-process", "--system-id", "USSDMobileUser", "--password", "mobile123", "--timeout", "2",
+        ]))
+        .unwrap();
+        let results = Prober::new(server.connect(), options.timeout).run(&options);
+        let outcome = |name: &str| results.iter().find(|result| result.name == name).map(|result| result.outcome.clone());
+
+        assert_eq!(outcome("bind_transceiver"), Some(Outcome::Accepted));
+        assert_eq!(outcome("enquire_link"), Some(Outcome::Accepted));
+        assert_eq!(outcome("submit_sm + unknown TLV 0x1400"), Some(Outcome::Accepted));
+        assert_eq!(outcome("data_sm"), Some(Outcome::GenericNack(ESME_RINVCMDID)));
+        assert_eq!(outcome("unassigned command 0xff"), Some(Outcome::GenericNack(ESME_RINVCMDID)));
+        assert_eq!(outcome("unbind"), Some(Outcome::Accepted));
+    }
+
+    #[test]
+    fn test_failed_bind_ends_the_battery() {
+        let mut config = Config::default();
+        config.smpp.enquire_link_interval = 0;
+        let server = UssdSmppServer::new(config);
+        let options = ProbeOptions::parse(&args(&["in-process", "--password", ""])).unwrap();
+        let results = Prober::new(server.connect(), options.timeout).run(&options);
+        assert_eq!(results.len(), 1);
+        assert!(matches!(results[0].outcome, Outcome::Rejected(_)));
+    }
+}
@@ -0,0 +1,208 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use ussd_common::run_id;
+
+use crate::logging::{subsystem_log, LogLevel, LogLevels, Subsystem};
+use crate::persistence::StateStore;
+use crate::reload::LiveConfig;
+use crate::shard::ShardedMap;
+use crate::pdu::{build_ussd_deliver_sm, USSD_NOTIFY, USSD_USSR_REQUEST};
+use crate::router::ConnectionManager;
+use crate::session::{MessageContext, Session, UssdScreen, UssdSession, UssdState};
+
+// Network-initiated USSD: pushes sent on demand through the admin interface or on a schedule
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PushConfig {
+    pub reply_message: String, // Answer to a reply to a `request` push, sent as USSD_NOTIFY
+    pub schedule: Vec<ScheduledPush>,
+}
+
+impl Default for PushConfig {
+    fn default() -> Self {
+        PushConfig {
+            reply_message: "Thank you for your response.".to_string(),
+            schedule: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ScheduledPush {
+    pub at: u64, // Seconds after server start
+    #[serde(flatten)]
+    pub push: PushRequest,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PushRequest {
+    pub msisdn: String,
+    pub text: String,
+    #[serde(default)]
+    pub mode: PushMode,
+    #[serde(default)]
+    pub priority_flag: u8,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PushMode {
+    #[default]
+    Request, // USSR request: opens a session and waits for the subscriber's reply
+    Notify,  // USSN request: shown to the subscriber, no session
+}
+
+// What a push was sent as, for the admin response and the log
+#[derive(Debug, Clone, Serialize)]
+pub struct PushReceipt {
+    pub message_id: String,
+    pub session_id: Option<String>,
+}
+
+// Originates DELIVER_SMs toward bound user clients without a SUBMIT_SM to answer
+#[derive(Clone)]
+pub struct Pusher {
+    pub sessions: Arc<ShardedMap<Session>>,
+    pub ussd_sessions: Arc<ShardedMap<UssdSession>>,
+    pub state_store: Arc<StateStore>,
+    pub config: Arc<LiveConfig>,
+    pub connection_manager: ConnectionManager,
+    pub log_levels: Arc<LogLevels>,
+}
+
+impl Pusher {
+    pub fn push(&self, request: &PushRequest) -> Result<PushReceipt, String> {
+        let msisdn = request.msisdn.as_str();
+        if msisdn.is_empty() || request.text.is_empty() {
+            return Err("A push needs an msisdn and a text".to_string());
+        }
+
+        let config = self.config.get();
+        let message_id = self.state_store.issue_message_id(&config.smpp.system_id, msisdn);
+        let service_op = match request.mode {
+            PushMode::Request => USSD_USSR_REQUEST,
+            PushMode::Notify => USSD_NOTIFY,
+        };
+
+        // A USSR request opens a session that the subscriber's reply continues. The network
+        // allows one dialogue per subscriber, so an open session refuses the push.
+        // A request is paginated for the subscriber's client like any menu; a notification can
+        // only be cut to fit
+        let text = config.compression.apply(&request.text);
+        let screen_chars = self.connection_manager.screen_chars(&self.sessions, msisdn, &config.screens);
+        let encoding = config.ussd.responses.encoding;
+        let mut pages = match request.mode {
+            PushMode::Request => config.screens.pages(&text, screen_chars),
+            PushMode::Notify => vec![config.screens.truncate(&text, screen_chars)],
+        };
+        let text = pages.remove(0);
+
+        let session_id = match request.mode {
+            PushMode::Request => {
+                let mut ussd_sessions = self.ussd_sessions.shard(msisdn);
+                if ussd_sessions.get(msisdn).is_some_and(|session| !matches!(session.state, UssdState::Terminated)) {
+                    return Err(format!("{} is already in a USSD session", msisdn));
+                }
+                let session_id = run_id::stamp(format!("PUSH{}", SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()));
+                let message = MessageContext::new(message_id.clone(), &config.smpp.system_id, msisdn, request.priority_flag, 0, "");
+                ussd_sessions.insert(msisdn.to_string(), UssdSession {
+                    msisdn: msisdn.to_string(),
+                    session_id: session_id.clone(),
+                    state: UssdState::Pushed,
+                    menu_level: 0,
+                    last_request: String::new(),
+                    inputs: Vec::new(),
+                    service_code: String::new(),
+                    last_message: message,
+                    forward_route: None,
+                    pages: pages
+                        .into_iter()
+                        .map(|text| UssdScreen { text, service_op: None, encoding })
+                        .collect(),
+                    last_activity: Instant::now(),
+                });
+                Some(session_id)
+            }
+            PushMode::Notify => None,
+        };
+
+        let deliver_sm = build_ussd_deliver_sm(
+            msisdn,
+            &text,
+            request.priority_flag,
+            self.state_store.next_sequence(),
+            Some(service_op),
+            encoding,
+            &config,
+        );
+        let queue = self
+            .connection_manager
+            .get_msisdn_connection(&self.sessions, msisdn, &config.client_simulator.user_clients);
+        let sent = match queue {
+            Some(queue) => queue.push(request.priority_flag, deliver_sm),
+            // Held pushes go out when the subscriber's client binds again
+            None if self.connection_manager.journal.hold(msisdn, request.priority_flag, &deliver_sm) => Ok(()),
+            None => Err("No user connection available".to_string()),
+        };
+        if let Err(e) = sent {
+            if let Some(session_id) = &session_id {
+                self.ussd_sessions.remove_where(|session| &session.session_id == session_id);
+            }
+            return Err(e);
+        }
+
+        info!("📲 Network-initiated {:?} push {} sent to {}: {}", request.mode, message_id, msisdn, text);
+        if let Some(session_id) = &session_id {
+            subsystem_log!(self.log_levels, Subsystem::Sessions, LogLevel::Debug,
+                "🗂️  Session {} for {} opened by push, awaiting reply", session_id, msisdn);
+        }
+        Ok(PushReceipt { message_id, session_id })
+    }
+
+    // Sends the `[[push.schedule]]` entries at their times, in time order
+    pub fn spawn_schedule(&self) {
+        let mut schedule = self.config.get().push.schedule.clone();
+        if schedule.is_empty() {
+            return;
+        }
+        schedule.sort_by_key(|entry| entry.at);
+        info!("📲 Push schedule loaded with {} push(es)", schedule.len());
+
+        let pusher = self.clone();
+        let started = Instant::now();
+        thread::spawn(move || {
+            for entry in schedule {
+                let due = started + Duration::from_secs(entry.at);
+                thread::sleep(due.saturating_duration_since(Instant::now()));
+
+                if let Err(e) = pusher.push(&entry.push) {
+                    info!("⚠️  Scheduled push to {} at T+{}s failed: {}", entry.push.msisdn, entry.at, e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedule_parses_with_defaults() {
+        let config: PushConfig = toml::from_str(
+            "[[schedule]]\nat = 10\nmsisdn = \"1234567890\"\ntext = \"Rate us 1-5\"\n\n\
+             [[schedule]]\nat = 20\nmsisdn = \"1234567890\"\ntext = \"Bye\"\nmode = \"notify\"\npriority_flag = 2\n",
+        )
+        .unwrap();
+        assert_eq!(config.reply_message, PushConfig::default().reply_message);
+        assert_eq!(config.schedule[0].push.mode, PushMode::Request);
+        assert_eq!(config.schedule[0].push.priority_flag, 0);
+        assert_eq!(config.schedule[1].push.mode, PushMode::Notify);
+        assert_eq!(config.schedule[1].at, 20);
+        assert!(toml::from_str::<PushConfig>("[[schedule]]\nat = 1\nmsisdn = \"1\"\ntext = \"x\"\nmode = \"page\"\n").is_err());
+    }
+}
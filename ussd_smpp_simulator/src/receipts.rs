@@ -0,0 +1,223 @@
+use std::collections::VecDeque;
+use std::sync::{Condvar, Mutex};
+use std::time::{Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::pdu::{DeliverSm, SmppPdu, DELIVER_SM, ESME_ROK};
+use crate::persistence::MessageState;
+use crate::session::MessageContext;
+use crate::smpp_time::receipt_date;
+
+// Delivery receipts for SUBMIT_SMs with registered_delivery set. `stat` is drawn from the
+// percentages, which are relative to each other; a request that could not be handled always
+// reports UNDELIV.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DeliveryReceiptsConfig {
+    pub enabled: bool,
+    pub delay_ms: u64, // Time between the SUBMIT_SM_RESP and its receipt
+    pub delivered_percentage: f64,
+    pub undeliverable_percentage: f64,
+    pub expired_percentage: f64,
+}
+
+impl Default for DeliveryReceiptsConfig {
+    fn default() -> Self {
+        DeliveryReceiptsConfig {
+            enabled: true,
+            delay_ms: 1000,
+            delivered_percentage: 100.0,
+            undeliverable_percentage: 0.0,
+            expired_percentage: 0.0,
+        }
+    }
+}
+
+impl DeliveryReceiptsConfig {
+    // `roll` is uniform in [0, 100)
+    pub fn pick(&self, roll: f64) -> ReceiptStat {
+        let weights = [
+            (ReceiptStat::Delivered, self.delivered_percentage.max(0.0)),
+            (ReceiptStat::Undeliverable, self.undeliverable_percentage.max(0.0)),
+            (ReceiptStat::Expired, self.expired_percentage.max(0.0)),
+        ];
+        let total: f64 = weights.iter().map(|(_, weight)| weight).sum();
+        if total <= 0.0 {
+            return ReceiptStat::Delivered;
+        }
+        let mut threshold = 0.0;
+        for (stat, weight) in weights {
+            threshold += weight * 100.0 / total;
+            if roll < threshold {
+                return stat;
+            }
+        }
+        ReceiptStat::Delivered
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReceiptStat {
+    Delivered,
+    Undeliverable,
+    Expired,
+}
+
+impl ReceiptStat {
+    pub fn label(self) -> &'static str {
+        match self {
+            ReceiptStat::Delivered => "DELIVRD",
+            ReceiptStat::Undeliverable => "UNDELIV",
+            ReceiptStat::Expired => "EXPIRED",
+        }
+    }
+
+    pub fn message_state(self) -> MessageState {
+        match self {
+            ReceiptStat::Delivered => MessageState::Delivered,
+            ReceiptStat::Undeliverable => MessageState::Undeliverable,
+            ReceiptStat::Expired => MessageState::Expired,
+        }
+    }
+
+    // registered_delivery 1 asks for every outcome, 2 for failures only
+    pub fn requested_by(self, registered_delivery: u8) -> bool {
+        match registered_delivery & 0x03 {
+            0 => false,
+            2 => self != ReceiptStat::Delivered,
+            _ => true,
+        }
+    }
+}
+
+// DELIVER_SM receipt for `message` back to its originator, in the usual
+// "id:... sub:001 dlvrd:... submit date:... done date:... stat:... err:000 text:..." format
+pub fn build_receipt(message: &MessageContext, stat: ReceiptStat, done_at: SystemTime, text: &str, sequence_number: u32) -> SmppPdu {
+    let receipt_text = format!(
+        "id:{} sub:001 dlvrd:{} submit date:{} done date:{} stat:{} err:000 text:{}",
+        message.message_id,
+        if stat == ReceiptStat::Delivered { "001" } else { "000" },
+        receipt_date(message.submitted_at),
+        receipt_date(done_at),
+        stat.label(),
+        text.chars().take(20).collect::<String>()
+    );
+    // IA5 keeps the id byte-for-byte; in GSM 7-bit a run id's '_' would become 0x11
+    let receipt_text: Vec<u8> = receipt_text.chars().take(255).map(|c| if c.is_ascii() { c as u8 } else { b'?' }).collect();
+
+    let deliver_sm = DeliverSm {
+        source_addr_ton: 1,
+        source_addr_npi: 1,
+        source_addr: message.recipient.as_str().into(),
+        dest_addr_ton: 1,
+        dest_addr_npi: 1,
+        destination_addr: message.originator.as_str().into(),
+        esm_class: 0x04, // SMSC delivery receipt
+        data_coding: 1, // IA5/ASCII
+        short_message: receipt_text.into(),
+        ..Default::default()
+    };
+    SmppPdu::new(DELIVER_SM, ESME_ROK, sequence_number, deliver_sm.encode())
+}
+
+// A receipt waiting for its done date
+#[derive(Debug, Clone)]
+pub struct ScheduledReceipt {
+    pub due: Instant,
+    pub message_id: String,
+    pub system_id: String, // Any receiving bind of the submitting system_id takes it
+    pub priority_flag: u8,
+    pub stat: ReceiptStat,
+    pub pdu: SmppPdu,
+}
+
+// Receipts in due order, handed to one sender thread as they fall due
+#[derive(Debug, Default)]
+pub struct ReceiptScheduler {
+    pending: Mutex<VecDeque<ScheduledReceipt>>,
+    changed: Condvar,
+}
+
+impl ReceiptScheduler {
+    pub fn schedule(&self, receipt: ScheduledReceipt) {
+        let mut pending = self.pending.lock().unwrap();
+        let position = pending.partition_point(|queued| queued.due <= receipt.due);
+        pending.insert(position, receipt);
+        self.changed.notify_all();
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Blocks until the earliest receipt is due
+    pub fn next_due(&self) -> ScheduledReceipt {
+        let mut pending = self.pending.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            match pending.front() {
+                Some(receipt) if receipt.due <= now => return pending.pop_front().unwrap(),
+                Some(receipt) => {
+                    let wait = receipt.due - now;
+                    pending = self.changed.wait_timeout(pending, wait).unwrap().0;
+                }
+                None => pending = self.changed.wait(pending).unwrap(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::pdu::deliver_sm_text;
+
+    #[test]
+    fn test_pick_follows_relative_percentages() {
+        let config = DeliveryReceiptsConfig { delivered_percentage: 2.0, undeliverable_percentage: 1.0, expired_percentage: 1.0, ..Default::default() };
+        assert_eq!(config.pick(0.0), ReceiptStat::Delivered);
+        assert_eq!(config.pick(49.9), ReceiptStat::Delivered);
+        assert_eq!(config.pick(50.0), ReceiptStat::Undeliverable);
+        assert_eq!(config.pick(99.9), ReceiptStat::Expired);
+        let none = DeliveryReceiptsConfig { delivered_percentage: 0.0, ..Default::default() };
+        assert_eq!(none.pick(10.0), ReceiptStat::Delivered);
+
+        assert!(!ReceiptStat::Delivered.requested_by(0));
+        assert!(ReceiptStat::Delivered.requested_by(1));
+        assert!(!ReceiptStat::Delivered.requested_by(2));
+        assert!(ReceiptStat::Expired.requested_by(2));
+    }
+
+    #[test]
+    fn test_receipts_come_out_in_due_order() {
+        let message = MessageContext::new("USSD17".to_string(), "1234567890", "*123#", 0, 1, "");
+        let pdu = build_receipt(&message, ReceiptStat::Delivered, SystemTime::now(), "*123#", 5);
+        let text = deliver_sm_text(&DeliverSm::decode(&pdu.body).unwrap());
+        assert!(text.starts_with("id:USSD17 sub:001 dlvrd:001 submit date:"), "{}", text);
+        assert!(text.ends_with(" stat:DELIVRD err:000 text:*123#"), "{}", text);
+
+        let scheduler = ReceiptScheduler::default();
+        let now = Instant::now();
+        for (offset, message_id) in [(40, "late"), (0, "first"), (20, "second")] {
+            scheduler.schedule(ScheduledReceipt {
+                due: now + Duration::from_millis(offset),
+                message_id: message_id.to_string(),
+                system_id: "ESME".to_string(),
+                priority_flag: 0,
+                stat: ReceiptStat::Delivered,
+                pdu: pdu.clone(),
+            });
+        }
+        let order: Vec<String> = (0..3).map(|_| scheduler.next_due().message_id).collect();
+        assert_eq!(order, ["first", "second", "late"]);
+        assert!(now.elapsed() >= Duration::from_millis(40));
+        assert!(scheduler.is_empty());
+    }
+}
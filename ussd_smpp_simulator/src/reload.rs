@@ -0,0 +1,172 @@
+use std::sync::{Arc, Mutex, RwLock};
+
+use log::info;
+use serde::Serialize;
+
+use crate::config::Config;
+
+// Settings read once when the server starts; a reload that changes them only takes effect after
+// a restart
+const RESTART_ONLY: &[&str] = &[
+    "server.host",
+    "server.port",
+    "server.shutdown_timeout",
+    "server.tls",
+    "server.listeners",
+    "admin",
+    "persistence",
+    "timeline",
+    "throttle",
+    "logging",
+    "capture",
+    "transcript",
+    "push.schedule",
+    "response_percentage.seed",
+    "ussd.session_timeout",
+    "smpp.session_shards",
+    "smpp.delivery_policy",
+    "smpp.route_fallback",
+    "smpp.outbound_queue_capacity",
+    "smpp.outbound_overflow",
+    "smpp.outbound_block_timeout_ms",
+    "outbind",
+];
+
+// The Config connections read, swapped whole on reload. Each PDU is handled against the Config
+// current when it arrived, so binds stay up and a dialogue picks up new menus on its next step.
+#[derive(Debug)]
+pub struct LiveConfig {
+    current: RwLock<Arc<Config>>,
+}
+
+impl LiveConfig {
+    pub fn new(config: Config) -> Self {
+        LiveConfig { current: RwLock::new(Arc::new(config)) }
+    }
+
+    pub fn get(&self) -> Arc<Config> {
+        Arc::clone(&self.current.read().unwrap())
+    }
+
+    fn replace(&self, config: Config) {
+        *self.current.write().unwrap() = Arc::new(config);
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReloadReport {
+    pub restart_required: Vec<&'static str>, // Changed settings still running with their old value
+}
+
+// Re-reads the config file the server was started with, applying the same command-line overrides
+pub struct ConfigReloader {
+    live: Arc<LiveConfig>,
+    path: String,
+    overrides: Box<dyn Fn(&mut Config) + Send + Sync>,
+    scenario: Mutex<Option<String>>, // Chosen on the admin interface; wins over the file and --profile
+}
+
+impl ConfigReloader {
+    pub fn new(live: Arc<LiveConfig>, path: String, overrides: impl Fn(&mut Config) + Send + Sync + 'static) -> Self {
+        ConfigReloader { live, path, overrides: Box::new(overrides), scenario: Mutex::new(None) }
+    }
+
+    pub fn reload(&self) -> Result<ReloadReport, String> {
+        let mut config = crate::config::load_config(&self.path).map_err(|e| format!("{}: {}", self.path, e))?;
+        (self.overrides)(&mut config);
+        if let Some(name) = self.scenario.lock().unwrap().clone() {
+            config.scenarios.active = Some(name);
+        }
+        crate::scenarios::apply_active(&mut config);
+
+        let restart_required = changed(&self.live.get(), &config)?;
+        self.live.replace(config);
+        info!("🔄 Reloaded {}", self.path);
+        for key in &restart_required {
+            info!("⚠️  `{}` changed in {}; restart the server to apply it", key, self.path);
+        }
+        Ok(ReloadReport { restart_required })
+    }
+
+    // Re-reads the file with the scenario profile `name` applied over it; it stays applied on
+    // later reloads
+    pub fn switch_scenario(&self, name: &str) -> Result<ReloadReport, String> {
+        let running = self.live.get();
+        if running.scenarios.profile(name).is_none() {
+            return Err(running.scenarios.unknown(name));
+        }
+        let previous = self.scenario.lock().unwrap().replace(name.to_string());
+        self.reload().inspect_err(|_| *self.scenario.lock().unwrap() = previous)
+    }
+}
+
+fn changed(running: &Config, reloaded: &Config) -> Result<Vec<&'static str>, String> {
+    let running = toml::Value::try_from(running).map_err(|e| e.to_string())?;
+    let reloaded = toml::Value::try_from(reloaded).map_err(|e| e.to_string())?;
+    let lookup = |value: &toml::Value, key: &str| {
+        key.split('.').try_fold(value.clone(), |value, part| value.get(part).cloned())
+    };
+    Ok(RESTART_ONLY.iter().copied().filter(|key| lookup(&running, key) != lookup(&reloaded, key)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_reload_swaps_config_and_reports_restart_only_changes() {
+        let path = std::env::temp_dir().join(format!("ussd_reload_{}.toml", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let mut config = Config::default();
+        config.ussd.menu.welcome_message = "Before".to_string();
+        fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+
+        let mut running = crate::config::load_config(&path).unwrap();
+        running.server.port = 9999;
+        let live = Arc::new(LiveConfig::new(running));
+        // The command-line override is reapplied, so it does not count as a change
+        let reloader = ConfigReloader::new(Arc::clone(&live), path.clone(), |config| config.server.port = 9999);
+        let before = live.get();
+
+        config.ussd.menu.welcome_message = "After".to_string();
+        config.response_percentage.failure_percentage = 50.0;
+        config.smpp.session_shards = 4;
+        fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+        let report = reloader.reload().unwrap();
+        assert_eq!(report.restart_required, vec!["smpp.session_shards"]);
+        assert_eq!(live.get().ussd.menu.welcome_message, "After");
+        assert_eq!(live.get().response_percentage.failure_percentage, 50.0);
+        assert_eq!(live.get().server.port, 9999);
+        // Snapshots taken before the reload are unchanged
+        assert_eq!(before.ussd.menu.welcome_message, "Before");
+
+        fs::write(&path, "[server\n").unwrap();
+        assert!(reloader.reload().is_err());
+        assert_eq!(live.get().ussd.menu.welcome_message, "After");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_switch_scenario_survives_reloads() {
+        let path = std::env::temp_dir().join(format!("ussd_scenario_{}.toml", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let mut config = Config::default();
+        config.scenarios.active = Some("happy-path".to_string());
+        fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+        let live = Arc::new(LiveConfig::new(crate::config::load_config(&path).unwrap()));
+        let reloader = ConfigReloader::new(Arc::clone(&live), path.clone(), |_| {});
+
+        let error = reloader.switch_scenario("strict_smsc").unwrap_err();
+        assert!(error.starts_with("unknown scenario profile \"strict_smsc\""), "{}", error);
+        reloader.switch_scenario("strict-smsc").unwrap();
+        assert!(live.get().smpp.strict);
+        assert_eq!(live.get().scenarios.active.as_deref(), Some("strict-smsc"));
+
+        // The file still names happy-path, but the switch holds until the next one
+        reloader.reload().unwrap();
+        assert!(live.get().smpp.strict);
+        assert_eq!(live.get().smpp.window_size, 10);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,227 @@
+use std::io;
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::LevelFilter;
+
+use crate::demo::{self, DemoClient};
+use crate::pdu::SmppError;
+use crate::selftest::StepResult;
+use crate::transcript::{self, Dialog, Step};
+use crate::config::Config;
+use crate::server::UssdSmppServer;
+
+pub struct ReplayOptions {
+    pub transcript: String,
+    pub config_path: String,
+    pub dialog: Option<usize>, // 1-based line in the transcript; every dialogue when unset
+    pub fast: bool,            // Send inputs back to back instead of at their recorded times
+    pub wait: Duration,        // For a forwarding client to bind
+}
+
+impl ReplayOptions {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut transcript = None;
+        let mut options = ReplayOptions {
+            transcript: String::new(),
+            config_path: "config.toml".to_string(),
+            dialog: None,
+            fast: false,
+            wait: Duration::from_secs(60),
+        };
+        let mut i = 0;
+        while i < args.len() {
+            if args[i] == "--fast" {
+                options.fast = true;
+                i += 1;
+                continue;
+            }
+            if !args[i].starts_with('-') {
+                if transcript.replace(args[i].clone()).is_some() {
+                    return Err(format!("Unexpected argument: {}", args[i]));
+                }
+                i += 1;
+                continue;
+            }
+            let value = args.get(i + 1).ok_or_else(|| format!("{} requires a value", args[i]))?.clone();
+            match args[i].as_str() {
+                "-c" | "--config" => options.config_path = value,
+                "--dialog" => {
+                    let dialog: usize = value.parse().map_err(|_| format!("Invalid value for --dialog: {}", value))?;
+                    options.dialog = Some(dialog.max(1));
+                }
+                "--wait" => {
+                    let seconds: u64 = value.parse().map_err(|_| format!("Invalid value for --wait: {}", value))?;
+                    options.wait = Duration::from_secs(seconds);
+                }
+                other => return Err(format!("Unknown replay option: {}", other)),
+            }
+            i += 2;
+        }
+        options.transcript = transcript.ok_or("replay needs a transcript file")?;
+        Ok(options)
+    }
+}
+
+// Starts the server from the config, waits for a forwarding client to bind and re-drives each
+// recorded dialogue through an in-process subscriber, comparing every screen with the recording
+pub fn run(options: ReplayOptions) -> io::Result<()> {
+    let mut dialogs = transcript::read(&options.transcript)?;
+    if let Some(number) = options.dialog {
+        if number > dialogs.len() {
+            return Err(io::Error::other(format!("{} has {} dialogues", options.transcript, dialogs.len())));
+        }
+        dialogs = vec![dialogs.swap_remove(number - 1)];
+    }
+
+    let mut config = crate::config::load_config(&options.config_path).map_err(|e| io::Error::other(e.to_string()))?;
+    // Simulated failures would make the replay differ from run to run, and the replay is not
+    // itself recorded
+    config.response_percentage.always_succeed();
+    config.transcript.file.clear();
+    let (system_id, password) = subscriber_credentials(&config)?;
+
+    let addr = format!("{}:{}", config.server.host, config.server.port);
+    let listener = TcpListener::bind(&addr)?;
+    let server = Arc::new(UssdSmppServer::new(config));
+    let serving = Arc::clone(&server);
+    thread::spawn(move || {
+        if let Err(e) = serving.serve(listener) {
+            println!("Replay server stopped: {}", e);
+        }
+    });
+
+    println!("Replay server listening on {}; waiting up to {:?} for a forwarding client to bind", addr, options.wait);
+    let deadline = Instant::now() + options.wait;
+    while !forwarding_client_bound(&server) {
+        if Instant::now() >= deadline {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "No forwarding client bound"));
+        }
+        thread::sleep(Duration::from_millis(200));
+    }
+
+    // The server's own log would bury the comparison
+    let level = log::max_level();
+    log::set_max_level(LevelFilter::Warn);
+    let config = server.config.get();
+    let mut phone = DemoClient::bind(server.connect(), &config, &system_id, &password)?;
+    let mut failed = 0;
+    let mut total = 0;
+    for (index, dialog) in dialogs.iter().enumerate() {
+        println!();
+        println!("Dialogue {} ({} dialled {})", options.dialog.unwrap_or(index + 1), dialog.msisdn, dialog.service_code);
+        for result in replay_dialog(&mut phone, dialog, options.fast) {
+            match &result.outcome {
+                Ok(()) => println!("✅ MATCH  {:<36} {:>6}ms", result.name, result.elapsed.as_millis()),
+                Err(e) => println!("❌ DIFF   {:<36} {}", result.name, e),
+            }
+            failed += usize::from(result.outcome.is_err());
+            total += 1;
+        }
+    }
+    phone.unbind()?;
+    log::set_max_level(level);
+
+    println!();
+    if failed > 0 {
+        return Err(io::Error::other(format!("{} of {} replayed steps differ from the transcript", failed, total)));
+    }
+    println!("Replay matched the transcript: {} steps", total);
+    Ok(())
+}
+
+// The first configured user client, with its account password when binds are authenticated
+fn subscriber_credentials(config: &Config) -> io::Result<(String, String)> {
+    demo::user_client_credentials(config)
+        .ok_or_else(|| io::Error::other("replay binds as a user client; client_simulator.user_clients is empty"))
+}
+
+pub(crate) fn forwarding_client_bound(server: &UssdSmppServer) -> bool {
+    !server.sessions.filter_map(|session| (session.bound && session.can_receive_forwards && !session.is_user_client).then_some(())).is_empty()
+}
+
+// Sends each input at its recorded offset (or at once when `fast`) and checks the screen that
+// comes back against the response recorded after it. Screens the network sent unprompted, such
+// as timeout notifications, cannot be re-driven and are left out.
+pub(crate) fn replay_dialog(phone: &mut DemoClient, dialog: &Dialog, fast: bool) -> Vec<StepResult> {
+    let started = Instant::now();
+    let mut results = Vec::new();
+    let mut steps = dialog.steps.iter().peekable();
+    while let Some(step) = steps.next() {
+        let Step::Input { at_ms, input } = step else {
+            continue;
+        };
+        let expected = match steps.peek() {
+            Some(Step::Response { response, .. }) => Some(response.as_str()),
+            _ => None,
+        };
+        if !fast && let Some(wait) = Duration::from_millis(*at_ms).checked_sub(started.elapsed()) {
+            thread::sleep(wait);
+        }
+
+        let sent = Instant::now();
+        let outcome = match (phone.ussd_request(&dialog.msisdn, input), expected) {
+            (Ok(screen), Some(expected)) if screen == expected => Ok(()),
+            (Ok(screen), Some(expected)) => Err(format!("expected {:?}, got {:?}", expected, screen)),
+            (Ok(screen), None) => Err(format!("expected no screen, got {:?}", screen)),
+            (Err(SmppError::Timeout(_)), None) => Ok(()),
+            (Err(e), _) => Err(e.to_string()),
+        };
+        let failed = outcome.is_err();
+        results.push(StepResult { name: format!("{} {}", dialog.msisdn, input), outcome, elapsed: sent.elapsed() });
+        // Later inputs of a diverged dialogue would only repeat the difference
+        if failed {
+            break;
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::demo::{self, DEMO_FORWARDING_CLIENT, DEMO_MSISDN, DEMO_SERVICE_CODE, DEMO_USER_CLIENT};
+    use crate::transcript::TranscriptConfig;
+
+    #[test]
+    fn test_recorded_forwarded_dialog_replays() {
+        let path = std::env::temp_dir().join(format!("ussd_replay_{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let _ = std::fs::remove_file(&path);
+
+        let mut config = Config::deterministic();
+        config.client_simulator.forwarding_clients = vec![DEMO_FORWARDING_CLIENT.to_string()];
+        config.client_simulator.user_clients = vec![DEMO_USER_CLIENT.to_string()];
+        let server = UssdSmppServer::new(config);
+        let recorder = &server.connection_manager.transcripts;
+        recorder.start(&TranscriptConfig { file: path.clone() }).unwrap();
+        let config = server.config.get();
+        let forwarder = DemoClient::bind(server.connect(), &config, DEMO_FORWARDING_CLIENT, "forward123").unwrap();
+        thread::spawn(move || demo::run_forwarding_client(forwarder));
+        assert!(forwarding_client_bound(&server));
+
+        let mut phone = DemoClient::bind(server.connect(), &config, DEMO_USER_CLIENT, "mobile123").unwrap();
+        for input in [DEMO_SERVICE_CODE, "1", "0"] {
+            phone.ussd_request(DEMO_MSISDN, input).unwrap();
+        }
+        recorder.finish_all();
+        let mut dialogs = transcript::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(dialogs.len(), 1);
+        assert_eq!(dialogs[0].steps.len(), 6);
+
+        let results = replay_dialog(&mut phone, &dialogs[0], true);
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|result| result.outcome.is_ok()), "{:?}", results.iter().map(|r| &r.outcome).collect::<Vec<_>>());
+
+        // A screen that no longer matches stops the dialogue at that step
+        if let Step::Response { response, .. } = &mut dialogs[0].steps[3] {
+            *response = "Balance: $0.00".to_string();
+        }
+        let results = replay_dialog(&mut phone, &dialogs[0], true);
+        assert_eq!(results.len(), 2);
+        assert!(results[1].outcome.as_ref().unwrap_err().contains("Balance: $0.00"));
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+use std::net::Shutdown;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use log::info;
+
+use crate::capture::{CaptureTap, PduCapture};
+use crate::config::{DeliveryPolicy, RouteFallback};
+use crate::correlation::PendingRequests;
+use crate::flood::FloodGuard;
+use crate::journal::DeliveryJournal;
+use crate::live::LiveFeed;
+use crate::outbound::{OutboundQueue, PriorityMetrics, QueueLimits};
+use crate::pdu::SmppPdu;
+use crate::receipts::ReceiptScheduler;
+use crate::screens::ScreensConfig;
+use crate::session::Session;
+use crate::shard::ShardedMap;
+use crate::shutdown::ShutdownState;
+use crate::throttle::{ThrottleConfig, Throttler};
+use crate::timeline::FaultState;
+use crate::transcript::TranscriptRecorder;
+use crate::transport::SmppStream;
+use crate::webhooks::WebhookEmitter;
+use crate::window::SubmitWindows;
+
+// Connection tracking for forwarding
+#[derive(Debug, Clone)]
+pub struct ConnectionManager {
+    pub connections: Arc<Mutex<HashMap<String, Arc<OutboundQueue>>>>,
+    pub priority_metrics: Arc<PriorityMetrics>,
+    pub faults: Arc<FaultState>, // Driven by the fault timeline
+    pub pending: Arc<PendingRequests>, // Forwarded requests awaiting the client's DELIVER_SM
+    pub throttle: Arc<Throttler>, // SUBMIT_SM rate limits per system_id
+    pub windows: Arc<SubmitWindows>, // Un-responded SUBMIT_SMs per connection
+    pub shutdown: Arc<ShutdownState>,
+    pub capture: Arc<PduCapture>, // Every PDU in and out, when [capture] names a file
+    pub transcripts: Arc<TranscriptRecorder>, // Whole USSD dialogues, when [transcript] names a file
+    pub webhooks: Arc<WebhookEmitter>, // Session events POSTed to [webhooks] url
+    pub live: Arc<LiveFeed>, // Binds and PDUs for the admin interface's /events WebSocket
+    pub journal: Arc<DeliveryJournal>, // DELIVER_SMs held for subscribers with no bound user client
+    pub receipts: Arc<ReceiptScheduler>, // Delivery receipts waiting for their done date
+    pub flood: Arc<FloodGuard>, // Per-IP connection buckets and bans
+    streams: Arc<Mutex<HashMap<String, SmppStream>>>, // Socket handles for forced disconnects
+    delivery_policy: DeliveryPolicy,
+    route_fallback: RouteFallback,
+    queue_limits: QueueLimits,
+    round_robin: Arc<Mutex<HashMap<String, usize>>>, // Next bind index per system_id
+    last_used: Arc<Mutex<HashMap<String, Instant>>>, // Last delivery time per connection_id
+    msisdn_routes: Arc<Mutex<HashMap<String, MsisdnRoute>>>, // Originating bind per MSISDN
+}
+
+#[derive(Debug, Clone)]
+struct MsisdnRoute {
+    connection_id: String,
+    system_id: String,
+}
+
+impl ConnectionManager {
+    pub(crate) fn new(
+        delivery_policy: DeliveryPolicy,
+        route_fallback: RouteFallback,
+        queue_limits: QueueLimits,
+        response_seed: Option<u64>,
+        throttle: ThrottleConfig,
+    ) -> Self {
+        ConnectionManager {
+            connections: Arc::new(Mutex::new(HashMap::new())),
+            priority_metrics: Arc::new(PriorityMetrics::default()),
+            faults: Arc::new(FaultState::seeded(response_seed)),
+            pending: Arc::new(PendingRequests::default()),
+            throttle: Arc::new(Throttler::new(throttle)),
+            windows: Arc::new(SubmitWindows::default()),
+            shutdown: Arc::new(ShutdownState::default()),
+            capture: Arc::new(PduCapture::default()),
+            transcripts: Arc::new(TranscriptRecorder::default()),
+            webhooks: Arc::new(WebhookEmitter::default()),
+            live: Arc::new(LiveFeed::default()),
+            journal: Arc::new(DeliveryJournal::default()),
+            receipts: Arc::new(ReceiptScheduler::default()),
+            flood: Arc::new(FloodGuard::default()),
+            streams: Arc::new(Mutex::new(HashMap::new())),
+            delivery_policy,
+            route_fallback,
+            queue_limits,
+            round_robin: Arc::new(Mutex::new(HashMap::new())),
+            last_used: Arc::new(Mutex::new(HashMap::new())),
+            msisdn_routes: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+    
+    pub(crate) fn record_origin(&self, msisdn: &str, connection_id: &str, system_id: &str) {
+        let mut routes = self.msisdn_routes.lock().unwrap();
+        routes.insert(msisdn.to_string(), MsisdnRoute {
+            connection_id: connection_id.to_string(),
+            system_id: system_id.to_string(),
+        });
+    }
+    
+    // Once the MSISDN's USSD session is over nothing more is delivered along its route
+    pub(crate) fn forget_origin(&self, msisdn: &str) {
+        self.msisdn_routes.lock().unwrap().remove(msisdn);
+    }
+    
+    // Screen size of the bind the MSISDN talks through; a bind that has gone keeps the size
+    // configured for its system_id
+    pub(crate) fn screen_chars(&self, sessions: &ShardedMap<Session>, msisdn: &str, screens: &ScreensConfig) -> Option<usize> {
+        let route = self.msisdn_routes.lock().unwrap().get(msisdn).cloned()?;
+        sessions
+            .read(&route.connection_id, |session| session.screen_chars)
+            .unwrap_or_else(|| screens.configured_chars(&route.system_id))
+    }
+    
+    // Connection that originated the MSISDN's last SUBMIT_SM, or a fallback when it is gone
+    pub(crate) fn get_msisdn_connection(&self, sessions: &ShardedMap<Session>, msisdn: &str, user_clients: &[String]) -> Option<Arc<OutboundQueue>> {
+        let Some(route) = self.msisdn_routes.lock().unwrap().get(msisdn).cloned() else {
+            return self.get_user_connection(sessions, user_clients);
+        };
+        
+        let origin_usable = sessions
+            .read(&route.connection_id, |session| session.bound && session.can_receive())
+            .unwrap_or(false);
+        if origin_usable && let Some(queue) = self.connections.lock().unwrap().get(&route.connection_id).cloned() {
+            self.last_used.lock().unwrap().insert(route.connection_id, Instant::now());
+            return Some(queue);
+        }
+        
+        match self.route_fallback {
+            RouteFallback::SameSystemId => self
+                .select_connection(sessions, std::slice::from_ref(&route.system_id), |session| session.system_id == route.system_id)
+                .or_else(|| self.get_user_connection(sessions, user_clients)),
+            RouteFallback::AnyUserClient => self.get_user_connection(sessions, user_clients),
+            RouteFallback::Drop => {
+                info!("🗑️  Originating bind for {} is gone, dropping DELIVER_SM", msisdn);
+                None
+            }
+        }
+    }
+    
+    pub(crate) fn add_connection(&self, connection_id: String, stream: Arc<Mutex<SmppStream>>) {
+        if let Ok(handle) = stream.lock().unwrap().try_clone() {
+            self.streams.lock().unwrap().insert(connection_id.clone(), handle);
+        }
+        let capture = CaptureTap::new(Arc::clone(&self.capture), Arc::clone(&self.live), &connection_id);
+        let queue = OutboundQueue::spawn(stream, self.queue_limits, Arc::clone(&self.priority_metrics), Some(capture));
+        let mut connections = self.connections.lock().unwrap();
+        connections.insert(connection_id, queue);
+    }
+    
+    pub(crate) fn get_connection(&self, connection_id: &str) -> Option<Arc<OutboundQueue>> {
+        self.connections.lock().unwrap().get(connection_id).cloned()
+    }
+    
+    // Withdraws the PDU queued for `message_id` from whichever connection holds it
+    pub(crate) fn cancel_queued(&self, message_id: &str) -> bool {
+        let queues: Vec<Arc<OutboundQueue>> = self.connections.lock().unwrap().values().cloned().collect();
+        queues.iter().any(|queue| queue.cancel(message_id))
+    }
+    
+    pub(crate) fn replace_queued(&self, message_id: &str, edit: impl Fn(&SmppPdu) -> Option<SmppPdu>) -> bool {
+        let queues: Vec<Arc<OutboundQueue>> = self.connections.lock().unwrap().values().cloned().collect();
+        queues.iter().any(|queue| queue.replace(message_id, &edit))
+    }
+    
+    pub(crate) fn remove_connection(&self, connection_id: &str) {
+        let mut connections = self.connections.lock().unwrap();
+        if let Some(queue) = connections.remove(connection_id) {
+            queue.close();
+        }
+        self.last_used.lock().unwrap().remove(connection_id);
+        self.streams.lock().unwrap().remove(connection_id);
+        // Same-system_id fallback still needs the route's system_id; the session sweeper
+        // forgets those routes when their sessions expire
+        if !matches!(self.route_fallback, RouteFallback::SameSystemId) {
+            self.msisdn_routes.lock().unwrap().retain(|_, route| route.connection_id != connection_id);
+        }
+    }
+    
+    // Shuts down the sockets of matching binds; their handlers then clean up as on any disconnect
+    pub(crate) fn drop_connections(&self, sessions: &ShardedMap<Session>, eligible: impl Fn(&Session) -> bool) -> usize {
+        let connection_ids = sessions.filter_map(|session| {
+            if session.bound && eligible(session) { session.connection_id.clone() } else { None }
+        });
+        let streams = self.streams.lock().unwrap();
+        connection_ids
+            .iter()
+            .filter_map(|id| streams.get(id))
+            .filter(|stream| stream.shutdown(Shutdown::Both).is_ok())
+            .count()
+    }
+    
+    pub(crate) fn get_forwarding_connection(&self, sessions: &ShardedMap<Session>, preferred: &[String]) -> Option<Arc<OutboundQueue>> {
+        // Sessions that can receive forwards (custom USSD handlers)
+        self.select_connection(sessions, preferred, |session| session.can_receive_forwards && !session.is_user_client)
+    }
+    
+    pub(crate) fn get_routed_connection(&self, sessions: &ShardedMap<Session>, system_id: &str) -> Option<Arc<OutboundQueue>> {
+        let preferred = [system_id.to_string()];
+        self.select_connection(sessions, &preferred, |session| session.system_id == system_id && !session.is_user_client)
+    }
+    
+    // Any receiving bind of `system_id`, user client or not
+    pub(crate) fn get_receipt_connection(&self, sessions: &ShardedMap<Session>, system_id: &str) -> Option<Arc<OutboundQueue>> {
+        let preferred = [system_id.to_string()];
+        self.select_connection(sessions, &preferred, |session| session.system_id == system_id)
+    }
+    
+    fn get_user_connection(&self, sessions: &ShardedMap<Session>, preferred: &[String]) -> Option<Arc<OutboundQueue>> {
+        self.select_connection(sessions, preferred, |session| session.is_user_client)
+    }
+    
+    // Picks the first system_id from `preferred` that has a usable bind, then chooses
+    // between that system_id's binds according to the delivery policy
+    fn select_connection(
+        &self,
+        sessions: &ShardedMap<Session>,
+        preferred: &[String],
+        eligible: impl Fn(&Session) -> bool,
+    ) -> Option<Arc<OutboundQueue>> {
+        let connections = self.connections.lock().unwrap();
+        
+        let mut candidates: Vec<Session> = sessions.filter_map(|session| {
+            let usable = session.bound
+                && session.can_receive()
+                && eligible(session)
+                && session.connection_id.as_ref().is_some_and(|id| connections.contains_key(id));
+            usable.then(|| session.clone())
+        });
+        candidates.sort_by(|a, b| a.connection_id.cmp(&b.connection_id));
+        
+        let system_id = preferred
+            .iter()
+            .find(|id| candidates.iter().any(|session| &session.system_id == *id))
+            .or_else(|| candidates.first().map(|session| &session.system_id))?;
+        let binds: Vec<&Session> = candidates.iter().filter(|session| &session.system_id == system_id).collect();
+        
+        let chosen = match self.delivery_policy {
+            DeliveryPolicy::RoundRobin => {
+                let mut round_robin = self.round_robin.lock().unwrap();
+                let next = round_robin.entry(system_id.clone()).or_insert(0);
+                let chosen = binds[*next % binds.len()];
+                *next = next.wrapping_add(1);
+                chosen
+            }
+            DeliveryPolicy::LeastRecentlyUsed => {
+                let last_used = self.last_used.lock().unwrap();
+                // Binds that never received anything go first
+                *binds
+                    .iter()
+                    .min_by_key(|session| session.connection_id.as_ref().and_then(|id| last_used.get(id)).copied())
+                    .unwrap()
+            }
+        };
+        
+        let connection_id = chosen.connection_id.clone()?;
+        self.last_used.lock().unwrap().insert(connection_id.clone(), Instant::now());
+        connections.get(&connection_id).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::config::{DeliveryPolicy, RouteFallback};
+    use crate::outbound::{OverflowPolicy, QueueLimits};
+    use crate::throttle::ThrottleConfig;
+    use crate::transport;
+
+    fn manager(route_fallback: RouteFallback) -> ConnectionManager {
+        ConnectionManager::new(DeliveryPolicy::default(), route_fallback, QueueLimits {
+            capacity: 0,
+            overflow: OverflowPolicy::default(),
+            block_timeout: Duration::from_millis(10),
+        }, None, ThrottleConfig::default())
+    }
+
+    fn connect(manager: &ConnectionManager, connection_id: &str) {
+        let (stream, _peer) = transport::channel_pair();
+        manager.add_connection(connection_id.to_string(), Arc::new(Mutex::new(stream)));
+    }
+
+    #[test]
+    fn test_disconnect_prunes_msisdn_routes() {
+        let manager = manager(RouteFallback::AnyUserClient);
+        connect(&manager, "conn_1");
+        connect(&manager, "conn_2");
+        manager.record_origin("111", "conn_1", "USSDMobileUser");
+        manager.record_origin("222", "conn_2", "USSDMobileUser");
+
+        manager.remove_connection("conn_1");
+        let routes = manager.msisdn_routes.lock().unwrap();
+        assert!(!routes.contains_key("111"));
+        assert!(routes.contains_key("222"));
+    }
+
+    #[test]
+    fn test_same_system_id_fallback_keeps_routes_until_session_ends() {
+        let manager = manager(RouteFallback::SameSystemId);
+        connect(&manager, "conn_1");
+        manager.record_origin("111", "conn_1", "USSDMobileUser");
+
+        manager.remove_connection("conn_1");
+        assert!(manager.msisdn_routes.lock().unwrap().contains_key("111"));
+        manager.forget_origin("111");
+        assert!(manager.msisdn_routes.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+
+// Maps non-builtin USSD codes to the forwarding client (system_id) that should handle them
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RoutingConfig {
+    pub rules: Vec<RoutingRule>,
+    pub default_system_id: Option<String>, // Used when no rule matches
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RoutingRule {
+    #[serde(default)]
+    pub code_prefix: Option<String>, // e.g. "*1*" matches "*1*2#"
+    #[serde(default)]
+    pub pattern: Option<String>, // '?' matches one character, '%' any run ('*' and '#' are literal)
+    pub system_id: String,
+}
+
+impl RoutingRule {
+    pub fn matches(&self, code: &str) -> bool {
+        let prefix_ok = self.code_prefix.as_ref().is_none_or(|prefix| code.starts_with(prefix.as_str()));
+        let pattern_ok = self.pattern.as_ref().is_none_or(|pattern| glob_match(pattern.as_bytes(), code.as_bytes()));
+        // A rule with neither field would match everything; use default_system_id for that
+        (self.code_prefix.is_some() || self.pattern.is_some()) && prefix_ok && pattern_ok
+    }
+}
+
+impl RoutingConfig {
+    // First matching rule wins; None means "any forwarding client"
+    pub fn route(&self, code: &str) -> Option<&str> {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(code))
+            .map(|rule| rule.system_id.as_str())
+            .or(self.default_system_id.as_deref())
+    }
+}
+
+// Two-pointer wildcard match: on a mismatch, return to the last '%' and let it swallow one more
+// character. Only the last '%' is ever retried, so the cost is at most pattern x text steps.
+pub fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut last_star: Option<(usize, usize)> = None; // Positions just after the last '%'
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'%') => {
+                p += 1;
+                last_star = Some((p, t));
+            }
+            Some(&c) if c == b'?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match last_star {
+                Some((star_p, star_t)) => {
+                    p = star_p;
+                    t = star_t + 1;
+                    last_star = Some((star_p, t));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'%')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(code_prefix: Option<&str>, pattern: Option<&str>, system_id: &str) -> RoutingRule {
+        RoutingRule {
+            code_prefix: code_prefix.map(str::to_string),
+            pattern: pattern.map(str::to_string),
+            system_id: system_id.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"*1*%#", b"*1*2*3#"));
+        assert!(glob_match(b"*1?#", b"*15#"));
+        assert!(!glob_match(b"*1?#", b"*155#"));
+        assert!(glob_match(b"%", b""));
+        assert!(glob_match(b"%%#", b"*100#"));
+        assert!(!glob_match(b"*100#", b"*100"));
+        assert!(!glob_match(b"", b"*"));
+        // '*' and '#' are literal, so only '%' spans characters
+        assert!(!glob_match(b"*#", b"*123#"));
+    }
+
+    #[test]
+    fn test_glob_match_backtracking_stays_fast() {
+        let text = "a".repeat(64) + "b";
+        let pattern = "%a".repeat(16) + "c";
+        assert!(!glob_match(pattern.as_bytes(), text.as_bytes()));
+        assert!(glob_match(("%a".repeat(16) + "%b").as_bytes(), text.as_bytes()));
+    }
+
+    #[test]
+    fn test_prefix_and_pattern_must_both_match() {
+        let both = rule(Some("*1"), Some("%#"), "Bank");
+        assert!(both.matches("*123#"));
+        assert!(!both.matches("*123"));
+        assert!(!both.matches("*223#"));
+        assert!(rule(Some("*1"), None, "Bank").matches("*1"));
+        assert!(rule(None, Some("*2??#"), "Data").matches("*200#"));
+        // A rule with neither field would swallow every code
+        assert!(!rule(None, None, "Any").matches("*123#"));
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins_then_default() {
+        let config = RoutingConfig {
+            rules: vec![
+                rule(None, Some("*1*%#"), "Specific"),
+                rule(Some("*1"), None, "Broad"),
+                rule(Some("*1*2"), None, "Unreachable"),
+            ],
+            default_system_id: Some("Fallback".to_string()),
+        };
+        assert_eq!(config.route("*1*2#"), Some("Specific"));
+        assert_eq!(config.route("*123#"), Some("Broad"));
+        assert_eq!(config.route("*555#"), Some("Fallback"));
+
+        let no_default = RoutingConfig { default_system_id: None, ..config };
+        assert_eq!(no_default.route("*555#"), None);
+    }
+}
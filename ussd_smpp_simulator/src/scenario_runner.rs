@@ -0,0 +1,312 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::LevelFilter;
+use serde::Deserialize;
+
+use crate::demo::{self, DemoClient, DEMO_MSISDN};
+use crate::pdu::{USSD_NOTIFY, USSD_PSSR_RESPONSE, USSD_TERMINATE_NOTIFY};
+use crate::replay;
+use crate::selftest::StepResult;
+use crate::transport::SmppStream;
+use crate::server::UssdSmppServer;
+
+pub struct ScenarioOptions {
+    pub file: String,
+    pub config_path: String,
+    pub server: Option<String>, // HOST:PORT of a running server; the config's server is started when unset
+    pub junit: Option<String>,  // Where to write the JUnit XML report
+    pub wait: Duration,         // For a forwarding client to bind to the started server
+}
+
+impl ScenarioOptions {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut file = None;
+        let mut options = ScenarioOptions {
+            file: String::new(),
+            config_path: "config.toml".to_string(),
+            server: None,
+            junit: None,
+            wait: Duration::ZERO,
+        };
+        let mut i = 0;
+        while i < args.len() {
+            if !args[i].starts_with('-') {
+                if file.replace(args[i].clone()).is_some() {
+                    return Err(format!("Unexpected argument: {}", args[i]));
+                }
+                i += 1;
+                continue;
+            }
+            let value = args.get(i + 1).ok_or_else(|| format!("{} requires a value", args[i]))?.clone();
+            match args[i].as_str() {
+                "-c" | "--config" => options.config_path = value,
+                "--server" => options.server = Some(value),
+                "--junit" => options.junit = Some(value),
+                "--wait" => {
+                    let seconds: u64 = value.parse().map_err(|_| format!("Invalid value for --wait: {}", value))?;
+                    options.wait = Duration::from_secs(seconds);
+                }
+                other => return Err(format!("Unknown scenario option: {}", other)),
+            }
+            i += 2;
+        }
+        options.file = file.ok_or("scenario needs a scenario file")?;
+        Ok(options)
+    }
+}
+
+// A scenario file: dialogues to dial and what each screen must show, e.g.
+//   msisdn = "1234567890"
+//   [[cases]]
+//   name = "Balance"
+//   steps = [
+//     { send = "*123#", expect = "Balance" },
+//     { send = "1", expect = "$25.50" },
+//     { send = "00", end = true },
+//   ]
+#[derive(Debug, Deserialize)]
+pub struct Scenario {
+    pub name: Option<String>, // The JUnit test suite; the file name when unset
+    #[serde(default = "default_msisdn")]
+    pub msisdn: String,
+    pub cases: Vec<Case>,
+}
+
+fn default_msisdn() -> String {
+    DEMO_MSISDN.to_string()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Case {
+    pub name: String,
+    pub msisdn: Option<String>, // Overrides the file's
+    pub steps: Vec<Expectation>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Expectation {
+    pub send: String,
+    pub expect: Option<String>, // Text the screen must contain
+    pub end: Option<bool>,      // Whether the screen must close the session, or keep it open
+}
+
+pub struct CaseResult {
+    pub name: String,
+    pub steps: Vec<StepResult>,
+}
+
+impl CaseResult {
+    fn failure(&self) -> Option<(usize, &StepResult, &str)> {
+        self.steps.iter().enumerate().find_map(|(index, step)| step.outcome.as_ref().err().map(|e| (index + 1, step, e.as_str())))
+    }
+}
+
+pub fn load(path: &str) -> io::Result<Scenario> {
+    let text = fs::read_to_string(path)?;
+    let scenario: Scenario = toml::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e)))?;
+    if let Some(case) = scenario.cases.iter().find(|case| case.steps.is_empty()) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{}: case {:?} has no steps", path, case.name)));
+    }
+    Ok(scenario)
+}
+
+// Drives each case of the scenario through a subscriber bound as the first
+// `client_simulator.user_clients` entry, against a running server or one started from the config,
+// and writes the results as JUnit XML for CI
+pub fn run(options: ScenarioOptions) -> io::Result<()> {
+    let scenario = load(&options.file)?;
+    let suite = scenario.name.clone().unwrap_or_else(|| {
+        Path::new(&options.file).file_stem().map_or(options.file.clone(), |stem| stem.to_string_lossy().into_owned())
+    });
+    let mut config = crate::config::load_config(&options.config_path).map_err(|e| io::Error::other(e.to_string()))?;
+    let (system_id, password) = demo::user_client_credentials(&config)
+        .ok_or_else(|| io::Error::other("scenario binds as a user client; client_simulator.user_clients is empty"))?;
+
+    let (stream, config) = match &options.server {
+        Some(addr) => {
+            println!("Running {} against {}", options.file, addr);
+            (SmppStream::Tcp(TcpStream::connect(addr)?), Arc::new(config))
+        }
+        None => {
+            // Simulated failures would make the outcome differ from run to run
+            config.response_percentage.always_succeed();
+            let addr = format!("{}:{}", config.server.host, config.server.port);
+            let listener = TcpListener::bind(&addr)?;
+            let server = Arc::new(UssdSmppServer::new(config));
+            let serving = Arc::clone(&server);
+            thread::spawn(move || {
+                if let Err(e) = serving.serve(listener) {
+                    println!("Scenario server stopped: {}", e);
+                }
+            });
+            println!("Scenario server listening on {}", addr);
+            let deadline = Instant::now() + options.wait;
+            while Instant::now() < deadline && !replay::forwarding_client_bound(&server) {
+                thread::sleep(Duration::from_millis(200));
+            }
+            (server.connect(), server.config.get())
+        }
+    };
+
+    // The server's own log would bury the results
+    let level = log::max_level();
+    log::set_max_level(LevelFilter::Warn);
+    let mut phone = DemoClient::bind(stream, &config, &system_id, &password)?;
+    let results = run_cases(&mut phone, &scenario);
+    phone.unbind()?;
+    log::set_max_level(level);
+
+    println!();
+    for result in &results {
+        let elapsed: Duration = result.steps.iter().map(|step| step.elapsed).sum();
+        match result.failure() {
+            None => println!("✅ PASS  {:<36} {:>6}ms", result.name, elapsed.as_millis()),
+            Some((number, step, e)) => println!("❌ FAIL  {:<36} step {} ({}): {}", result.name, number, step.name, e),
+        }
+    }
+    if let Some(path) = &options.junit {
+        fs::write(path, junit(&suite, &results))?;
+        println!("JUnit report written to {}", path);
+    }
+    let failed = results.iter().filter(|result| result.failure().is_some()).count();
+    println!();
+    if failed > 0 {
+        return Err(io::Error::other(format!("{} of {} scenario cases failed", failed, results.len())));
+    }
+    println!("Scenario passed: {} cases", results.len());
+    Ok(())
+}
+
+pub(crate) fn run_cases(phone: &mut DemoClient, scenario: &Scenario) -> Vec<CaseResult> {
+    let mut results = Vec::new();
+    for case in &scenario.cases {
+        let msisdn = case.msisdn.as_deref().unwrap_or(&scenario.msisdn);
+        let mut steps = Vec::new();
+        for step in &case.steps {
+            let started = Instant::now();
+            let outcome = phone.ussd_exchange(msisdn, &step.send).map_err(|e| e.to_string()).and_then(|(screen, service_op)| {
+                let ended = matches!(service_op, Some(USSD_NOTIFY | USSD_TERMINATE_NOTIFY | USSD_PSSR_RESPONSE));
+                match (&step.expect, step.end) {
+                    (Some(expect), _) if !screen.contains(expect.as_str()) => Err(format!("expected {:?}, got {:?}", expect, screen)),
+                    (_, Some(true)) if !ended => Err(format!("expected the session to end, got {:?}", screen)),
+                    (_, Some(false)) if ended => Err(format!("expected the session to stay open, got {:?}", screen)),
+                    _ => Ok(()),
+                }
+            });
+            let failed = outcome.is_err();
+            steps.push(StepResult { name: step.send.clone(), outcome, elapsed: started.elapsed() });
+            // Later steps of a broken dialogue would only repeat the failure
+            if failed {
+                break;
+            }
+        }
+        results.push(CaseResult { name: case.name.clone(), steps });
+    }
+    results
+}
+
+// One <testsuite> with a <testcase> per case; a failed case carries the step that broke it
+pub fn junit(suite: &str, results: &[CaseResult]) -> String {
+    let seconds = |elapsed: Duration| format!("{:.3}", elapsed.as_secs_f64());
+    let case_time = |result: &CaseResult| result.steps.iter().map(|step| step.elapsed).sum::<Duration>();
+    let failures = results.iter().filter(|result| result.failure().is_some()).count();
+    let total: Duration = results.iter().map(case_time).sum();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" time=\"{}\">",
+        escape(suite),
+        results.len(),
+        failures,
+        seconds(total)
+    );
+    for result in results {
+        let _ = write!(xml, "  <testcase classname=\"{}\" name=\"{}\" time=\"{}\"", escape(suite), escape(&result.name), seconds(case_time(result)));
+        match result.failure() {
+            None => xml.push_str("/>\n"),
+            Some((number, step, e)) => {
+                let message = format!("step {} ({}): {}", number, step.name, e);
+                let _ = writeln!(xml, ">\n    <failure message=\"{}\">{}</failure>\n  </testcase>", escape(&message), escape(&message));
+            }
+        }
+    }
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\n' => escaped.push_str("&#10;"),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::demo::DEMO_USER_CLIENT;
+
+    #[test]
+    fn test_cases_run_and_report_as_junit() {
+        let scenario: Scenario = toml::from_str(
+            r#"
+            [[cases]]
+            name = "Balance"
+            steps = [
+              { send = "*123#", expect = "Welcome", end = false },
+              { send = "1", expect = "$25.50" },
+              { send = "00", expect = "Goodbye", end = true },
+            ]
+
+            [[cases]]
+            name = "Wrong <balance>"
+            msisdn = "1234567891"
+            steps = [
+              { send = "*123#" },
+              { send = "1", expect = "$99.00" },
+              { send = "00" },
+            ]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(scenario.msisdn, DEMO_MSISDN);
+
+        let mut config = Config::deterministic();
+        config.client_simulator.user_clients = vec![DEMO_USER_CLIENT.to_string()];
+        let server = UssdSmppServer::new(config);
+        let mut phone = DemoClient::bind(server.connect(), &server.config.get(), DEMO_USER_CLIENT, "mobile123").unwrap();
+        let results = run_cases(&mut phone, &scenario);
+
+        assert_eq!(results.len(), 2);
+        assert!(results[0].failure().is_none(), "{:?}", results[0].failure().map(|(_, _, e)| e.to_string()));
+        // The failing step ends the case
+        assert_eq!(results[1].steps.len(), 2);
+        let (number, _, e) = results[1].failure().unwrap();
+        assert_eq!(number, 2);
+        assert!(e.starts_with("expected \"$99.00\""), "{}", e);
+
+        let xml = junit("balance checks", &results);
+        assert!(xml.contains("<testsuite name=\"balance checks\" tests=\"2\" failures=\"1\" errors=\"0\""), "{}", xml);
+        assert!(xml.contains("<testcase classname=\"balance checks\" name=\"Balance\" time=\""), "{}", xml);
+        assert!(xml.contains("name=\"Wrong &lt;balance&gt;\""), "{}", xml);
+        assert!(xml.contains("<failure message=\"step 2 (1): expected &quot;$99.00&quot;, got "), "{}", xml);
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::BTreeMap;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::latency::{Delay, LatencyConfig, LatencyRoute};
+
+// Named bundles of the settings testers change together, applied over the rest of the file, e.g.
+//   [scenarios]
+//   active = "flaky-network"     # Or `--profile NAME`, or PUT /scenario on the admin interface
+//   [scenarios.profiles.busy-hour]
+//   failure_percentage = 20.0
+//   window_size = 5
+// The built-in profiles are happy-path, flaky-network, slow-gateway and strict-smsc; one in the
+// file with the same name replaces it.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ScenariosConfig {
+    pub active: Option<String>,
+    pub profiles: BTreeMap<String, ScenarioProfile>,
+}
+
+// Settings left unset keep the value from the rest of the config
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ScenarioProfile {
+    pub description: String,
+    pub success_percentage: Option<f64>,
+    pub failure_percentage: Option<f64>,
+    pub no_response_percentage: Option<f64>,
+    pub failure_error_code: Option<u32>,
+    pub no_response_delay_ms: Option<u64>,
+    pub response_delay_ms: Option<u64>,
+    pub latency: Option<LatencyConfig>, // Replaces [latency] whole
+    pub window_size: Option<usize>,
+    pub window_timeout: Option<u64>,
+    pub strict: Option<bool>,
+}
+
+pub const BUILTIN: &[&str] = &["happy-path", "flaky-network", "slow-gateway", "strict-smsc"];
+
+// Each built-in sets every field, so switching between them leaves nothing of the last one behind
+fn builtin(name: &str) -> Option<ScenarioProfile> {
+    let delays = |submit_sm_resp: Delay, deliver_sm: Delay| LatencyConfig {
+        routes: vec![LatencyRoute { code_prefix: None, pattern: None, submit_sm_resp: Some(submit_sm_resp), deliver_sm: Some(deliver_sm) }],
+    };
+    let profile = |description: &str, rates: (f64, f64, f64), latency: LatencyConfig, window_size: usize, strict: bool| ScenarioProfile {
+        description: description.to_string(),
+        success_percentage: Some(rates.0),
+        failure_percentage: Some(rates.1),
+        no_response_percentage: Some(rates.2),
+        failure_error_code: Some(0x08), // ESME_RSYSERR
+        no_response_delay_ms: Some(5000),
+        response_delay_ms: Some(50),
+        latency: Some(latency),
+        window_size: Some(window_size),
+        window_timeout: Some(30),
+        strict: Some(strict),
+    };
+    Some(match name {
+        "happy-path" => profile("Every request succeeds at once", (100.0, 0.0, 0.0), LatencyConfig::default(), 0, false),
+        "flaky-network" => profile(
+            "One request in five fails or goes unanswered, with jittery latency",
+            (80.0, 10.0, 10.0),
+            delays(Delay::Normal { mean_ms: 300.0, std_dev_ms: 150.0 }, Delay::Normal { mean_ms: 800.0, std_dev_ms: 400.0 }),
+            0,
+            false,
+        ),
+        "slow-gateway" => profile(
+            "Responses take seconds and at most 10 SUBMIT_SMs are in flight",
+            (100.0, 0.0, 0.0),
+            delays(Delay::Normal { mean_ms: 2000.0, std_dev_ms: 500.0 }, Delay::Exponential { mean_ms: 4000.0 }),
+            10,
+            false,
+        ),
+        "strict-smsc" => profile(
+            "SMPP 3.4 field rules enforced and a window of 10",
+            (100.0, 0.0, 0.0),
+            LatencyConfig::default(),
+            10,
+            true,
+        ),
+        _ => return None,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScenarioInfo {
+    pub name: String,
+    pub description: String,
+}
+
+impl ScenariosConfig {
+    pub fn profile(&self, name: &str) -> Option<ScenarioProfile> {
+        self.profiles.get(name).cloned().or_else(|| builtin(name))
+    }
+
+    // Built-ins first, then the file's own in name order
+    pub fn list(&self) -> Vec<ScenarioInfo> {
+        let names = BUILTIN.iter().map(|name| name.to_string()).chain(self.profiles.keys().filter(|name| !BUILTIN.contains(&name.as_str())).cloned());
+        names
+            .filter_map(|name| self.profile(&name).map(|profile| ScenarioInfo { name, description: profile.description }))
+            .collect()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match &self.active {
+            Some(name) if self.profile(name).is_none() => Err(self.unknown(name)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn unknown(&self, name: &str) -> String {
+        let names: Vec<String> = self.list().into_iter().map(|info| info.name).collect();
+        format!("unknown scenario profile {:?} (available: {})", name, names.join(", "))
+    }
+}
+
+impl ScenarioProfile {
+    pub fn apply(&self, config: &mut Config) {
+        let rates = &mut config.response_percentage;
+        let overrides = [
+            (&mut rates.success_percentage, self.success_percentage),
+            (&mut rates.failure_percentage, self.failure_percentage),
+            (&mut rates.no_response_percentage, self.no_response_percentage),
+        ];
+        for (setting, value) in overrides {
+            if let Some(value) = value {
+                *setting = value;
+            }
+        }
+        if let Some(code) = self.failure_error_code {
+            rates.failure_error_code = code;
+        }
+        if let Some(delay) = self.no_response_delay_ms {
+            rates.no_response_delay_ms = delay;
+        }
+        if let Some(delay) = self.response_delay_ms {
+            rates.response_delay_ms = delay;
+        }
+        if let Some(latency) = &self.latency {
+            config.latency = latency.clone();
+        }
+        if let Some(window_size) = self.window_size {
+            config.smpp.window_size = window_size;
+        }
+        if let Some(window_timeout) = self.window_timeout {
+            config.smpp.window_timeout = window_timeout;
+        }
+        if let Some(strict) = self.strict {
+            config.smpp.strict = strict;
+        }
+    }
+}
+
+// Applies `scenarios.active` over the rest of `config`; called once per loaded config, after
+// command-line overrides
+pub fn apply_active(config: &mut Config) {
+    let Some(name) = config.scenarios.active.clone() else {
+        return;
+    };
+    match config.scenarios.profile(&name) {
+        Some(profile) => {
+            profile.apply(config);
+            info!("🎬 Scenario profile '{}' applied", name);
+        }
+        None => info!("⚠️  {}; running without one", config.scenarios.unknown(&name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_profiles_set_every_field() {
+        let mut config = Config::default();
+        config.scenarios.active = Some("strict-smsc".to_string());
+        apply_active(&mut config);
+        assert!(config.smpp.strict);
+        assert_eq!(config.smpp.window_size, 10);
+        assert_eq!(config.response_percentage.success_percentage, 100.0);
+
+        config.scenarios.active = Some("flaky-network".to_string());
+        apply_active(&mut config);
+        assert!(!config.smpp.strict);
+        assert_eq!(config.smpp.window_size, 0);
+        assert_eq!(config.response_percentage.no_response_percentage, 10.0);
+        assert!(config.latency.delay_for("*123#", crate::latency::LatencyStage::DeliverSm).is_some());
+
+        config.scenarios.active = Some("happy-path".to_string());
+        apply_active(&mut config);
+        assert!(config.latency.routes.is_empty());
+        assert_eq!(config.response_percentage.failure_percentage, 0.0);
+    }
+
+    #[test]
+    fn test_profiles_from_config() {
+        let scenarios: ScenariosConfig = toml::from_str(
+            "active = \"busy-hour\"\n\n\
+             [profiles.busy-hour]\ndescription = \"Evening peak\"\nfailure_percentage = 20.0\nwindow_size = 5\n\n\
+             [profiles.happy-path]\ndescription = \"Ours\"\nstrict = true\n",
+        )
+        .unwrap();
+        scenarios.validate().unwrap();
+        let names: Vec<String> = scenarios.list().into_iter().map(|info| info.name).collect();
+        assert_eq!(names, ["happy-path", "flaky-network", "slow-gateway", "strict-smsc", "busy-hour"]);
+        assert_eq!(scenarios.list()[0].description, "Ours");
+
+        // Only the settings the profile names change
+        let mut config = Config { scenarios, ..Config::default() };
+        config.response_percentage.success_percentage = 70.0;
+        apply_active(&mut config);
+        assert_eq!(config.response_percentage.failure_percentage, 20.0);
+        assert_eq!(config.response_percentage.success_percentage, 70.0);
+        assert_eq!(config.smpp.window_size, 5);
+
+        let typo = ScenariosConfig { active: Some("happy_path".to_string()), ..Default::default() };
+        assert!(typo.validate().unwrap_err().starts_with("unknown scenario profile \"happy_path\" (available: happy-path, "));
+    }
+}
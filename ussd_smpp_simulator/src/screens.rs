@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+
+// Screen sizes of the user clients, so one run can mix constrained handsets and modern clients.
+// A bind's size comes from TAG_SCREEN_CHARS, else from `clients` by system_id; without either
+// screens are sent whole.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ScreensConfig {
+    pub overflow: ScreenOverflow,
+    pub more_option: String, // Reply that asks for the next page
+    pub more_label: String,
+    pub clients: Vec<ClientScreen>,
+}
+
+impl Default for ScreensConfig {
+    fn default() -> Self {
+        ScreensConfig {
+            overflow: ScreenOverflow::default(),
+            more_option: "99".to_string(),
+            more_label: "More".to_string(),
+            clients: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ClientScreen {
+    pub system_id: String,
+    pub max_chars: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScreenOverflow {
+    #[default]
+    Paginate, // Pages end in the more option; the next page is sent when the subscriber picks it
+    Truncate, // Cut to the screen size
+}
+
+impl ScreensConfig {
+    pub fn configured_chars(&self, system_id: &str) -> Option<usize> {
+        self.clients
+            .iter()
+            .find(|client| client.system_id == system_id)
+            .map(|client| client.max_chars)
+            .filter(|&max_chars| max_chars > 0)
+    }
+
+    // `text` as screens of at most `max_chars` characters: one truncated screen, or pages when
+    // `overflow` paginates
+    pub fn pages(&self, text: &str, max_chars: Option<usize>) -> Vec<String> {
+        let Some(max_chars) = max_chars.filter(|&max_chars| text.chars().count() > max_chars) else {
+            return vec![text.to_string()];
+        };
+        let footer = format!("{}. {}", self.more_option, self.more_label);
+        let room = max_chars.saturating_sub(footer.chars().count() + 1);
+        if self.overflow == ScreenOverflow::Truncate || room == 0 {
+            return vec![truncate(text, max_chars)];
+        }
+
+        let mut pages = Vec::new();
+        let mut rest = text.trim();
+        while rest.chars().count() > max_chars {
+            let cut = page_break(rest, room);
+            pages.push(format!("{}\n{}", rest[..cut].trim_end(), footer));
+            rest = rest[cut..].trim_start();
+        }
+        pages.push(rest.to_string());
+        pages
+    }
+
+    // For screens the subscriber cannot page through, such as notifications
+    pub fn truncate(&self, text: &str, max_chars: Option<usize>) -> String {
+        match max_chars {
+            Some(max_chars) if text.chars().count() > max_chars => truncate(text, max_chars),
+            _ => text.to_string(),
+        }
+    }
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    text.chars().take(max_chars).collect::<String>().trim_end().to_string()
+}
+
+// Byte offset to end a page of at most `room` characters at: the last line break, else the last
+// space, else mid-word
+fn page_break(text: &str, room: usize) -> usize {
+    let limit = text.char_indices().nth(room).map_or(text.len(), |(offset, _)| offset);
+    let (head, tail) = text.split_at(limit);
+    if tail.starts_with('\n') {
+        return limit;
+    }
+    if let Some(offset) = head.rfind('\n').filter(|&offset| offset > 0) {
+        return offset;
+    }
+    if tail.starts_with(' ') {
+        return limit;
+    }
+    head.rfind(' ').filter(|&offset| offset > 0).unwrap_or(limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MENU: &str = "Welcome to MyTelecom\n1. Balance\n2. Data Packages\n3. Customer Service\n0. Exit";
+
+    #[test]
+    fn test_pages_break_at_lines_and_end_in_more_option() {
+        let screens = ScreensConfig::default();
+        let pages = screens.pages(MENU, Some(40));
+        assert_eq!(pages, vec![
+            "Welcome to MyTelecom\n1. Balance\n99. More",
+            "2. Data Packages\n99. More",
+            "3. Customer Service\n0. Exit",
+        ]);
+        assert!(pages.iter().all(|page| page.chars().count() <= 40));
+
+        assert_eq!(screens.pages(MENU, None), vec![MENU]);
+        assert_eq!(screens.pages(MENU, Some(200)), vec![MENU]);
+    }
+
+    #[test]
+    fn test_long_lines_break_at_spaces_and_truncate_mode_cuts() {
+        let screens = ScreensConfig::default();
+        let pages = screens.pages("Your balance is 12.50 and your data bundle expires tomorrow", Some(30));
+        assert_eq!(pages[0], "Your balance is 12.50\n99. More");
+        assert_eq!(pages[pages.len() - 1], "expires tomorrow");
+
+        let screens = ScreensConfig { overflow: ScreenOverflow::Truncate, ..ScreensConfig::default() };
+        assert_eq!(screens.pages(MENU, Some(25)), vec!["Welcome to MyTelecom\n1. B"]);
+        assert_eq!(screens.truncate(MENU, Some(20)), "Welcome to MyTelecom");
+    }
+
+    #[test]
+    fn test_configured_chars_by_system_id() {
+        let screens: ScreensConfig = toml::from_str(
+            "[[clients]]\nsystem_id = \"FeaturePhone\"\nmax_chars = 64\n\n[[clients]]\nsystem_id = \"Unlimited\"\nmax_chars = 0\n",
+        )
+        .unwrap();
+        assert_eq!(screens.configured_chars("FeaturePhone"), Some(64));
+        assert_eq!(screens.configured_chars("Unlimited"), None);
+        assert_eq!(screens.configured_chars("USSDMobileUser"), None);
+        assert_eq!(screens.more_option, "99");
+    }
+}
@@ -0,0 +1,185 @@
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use log::info;
+use rhai::{AST, Dynamic, Engine, Map, Scope};
+use serde::{Deserialize, Serialize};
+
+// USSD services written as Rhai scripts instead of the built-in menu or a forwarding client
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ScriptingConfig {
+    pub max_operations: u64, // Rhai operations one call may run before it is stopped (0 = unlimited)
+    pub error_message: String, // Closing screen when a script fails
+    pub services: Vec<ScriptedService>,
+    #[serde(skip)]
+    compiled: OnceLock<Result<ScriptRuntime, String>>,
+}
+
+impl Default for ScriptingConfig {
+    fn default() -> Self {
+        ScriptingConfig {
+            max_operations: 100_000,
+            error_message: "Service temporarily unavailable.".to_string(),
+            services: Vec::new(),
+            compiled: OnceLock::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ScriptedService {
+    pub code: String, // e.g. "*777#"; matched like ussd.service_codes
+    #[serde(default)]
+    pub script: Option<String>, // .rhai file, relative to the config file
+    #[serde(default)]
+    pub source: Option<String>, // Inline script instead of a file
+}
+
+#[derive(Debug)]
+struct ScriptRuntime {
+    engine: Engine,
+    services: Vec<(String, AST)>,
+}
+
+// What a script's `handle` returned
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptReply {
+    pub text: String,
+    pub end: bool,
+    pub state: String, // JSON object handed back to the next call
+}
+
+impl ScriptingConfig {
+    pub fn new(services: Vec<ScriptedService>) -> Self {
+        ScriptingConfig { services, ..Default::default() }
+    }
+
+    // Reads and compiles every service script. `base_dir` is the directory of the config file.
+    pub fn compile(&self, base_dir: &Path) -> Result<(), String> {
+        self.compiled.get_or_init(|| self.build(base_dir)).as_ref().map(|_| ()).map_err(String::clone)
+    }
+
+    fn build(&self, base_dir: &Path) -> Result<ScriptRuntime, String> {
+        let mut engine = Engine::new();
+        if self.max_operations > 0 {
+            engine.set_max_operations(self.max_operations);
+        }
+        let mut services = Vec::new();
+        for service in &self.services {
+            let (name, source) = match (&service.script, &service.source) {
+                (Some(script), None) => {
+                    let path = base_dir.join(script);
+                    let source = fs::read_to_string(&path).map_err(|e| format!("Could not read script {}: {}", path.display(), e))?;
+                    (path.display().to_string(), source)
+                }
+                (None, Some(source)) => (service.code.clone(), source.clone()),
+                _ => return Err(format!("Scripted service {} needs exactly one of script or source", service.code)),
+            };
+            let ast = engine.compile(&source).map_err(|e| format!("Script {}: {}", name, e))?;
+            if !ast.iter_functions().any(|function| function.name == "handle" && function.params.len() == 3) {
+                return Err(format!("Script {} does not define fn handle(msisdn, state, input)", name));
+            }
+            services.push((service.code.clone(), ast));
+        }
+        Ok(ScriptRuntime { engine, services })
+    }
+
+    // The scripted service for a dialled code or the code that opened the session
+    pub fn service_for(&self, code: &str) -> Option<&str> {
+        self.services
+            .iter()
+            .find(|service| code.starts_with(service.code.trim_end_matches('#')))
+            .map(|service| service.code.as_str())
+    }
+
+    // Calls `handle(msisdn, state, input)` in the script of `service`. `state` is the JSON object
+    // the previous call returned, "{}" on the first one.
+    pub fn run(&self, service: &str, msisdn: &str, state: &str, input: &str) -> Result<ScriptReply, String> {
+        // Configs built in code rather than loaded from a file resolve scripts from the working directory
+        let runtime = self.compiled.get_or_init(|| self.build(Path::new(""))).as_ref().map_err(String::clone)?;
+        let Some((_, ast)) = runtime.services.iter().find(|(code, _)| code == service) else {
+            return Err(format!("no script for {}", service));
+        };
+        let state = runtime.engine.parse_json(state, true).map_err(|e| e.to_string())?;
+        let result: Dynamic = runtime
+            .engine
+            .call_fn(&mut Scope::new(), ast, "handle", (msisdn.to_string(), state.clone(), input.to_string()))
+            .map_err(|e| e.to_string())?;
+
+        // A plain string is a screen that awaits a reply and keeps the state
+        if result.is_string() {
+            return Ok(ScriptReply { text: result.into_string()?, end: false, state: rhai::format_map_as_json(&state) });
+        }
+        let Some(mut reply) = result.try_cast::<Map>() else {
+            return Err("handle must return a string or #{ text, end, state }".to_string());
+        };
+        let text = reply.remove("text").map(|text| text.to_string()).unwrap_or_default();
+        let end = reply.remove("end").map(|end| end.as_bool()).transpose().map_err(|_| "end must be a bool")?.unwrap_or(false);
+        let state = match reply.remove("state") {
+            Some(state) => state.try_cast::<Map>().ok_or("state must be an object map")?,
+            None => state,
+        };
+        Ok(ScriptReply { text, end, state: rhai::format_map_as_json(&state) })
+    }
+
+    pub fn log_services(&self) {
+        for service in &self.services {
+            info!("📜 {} is served by {}", service.code, service.script.as_deref().unwrap_or("an inline script"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUIZ: &str = r#"
+        fn handle(msisdn, state, input) {
+            if !("score" in state) {
+                return #{ text: "Capital of France?\n1. Paris\n2. Lyon", state: #{ score: 0 } };
+            }
+            if input == "1" {
+                state.score += 1;
+            }
+            #{ text: `Score for ${msisdn}: ${state.score}`, end: true, state: state }
+        }
+    "#;
+
+    fn service(code: &str, source: &str) -> ScriptedService {
+        ScriptedService { code: code.to_string(), script: None, source: Some(source.to_string()) }
+    }
+
+    #[test]
+    fn test_script_state_carries_between_calls() {
+        let scripting = ScriptingConfig::new(vec![service("*777#", QUIZ)]);
+        scripting.compile(Path::new("")).unwrap();
+        assert_eq!(scripting.service_for("*777*1#"), Some("*777#"));
+        assert_eq!(scripting.service_for("*123#"), None);
+
+        let first = scripting.run("*777#", "111", "{}", "*777#").unwrap();
+        assert!(first.text.starts_with("Capital of France?") && !first.end);
+        let last = scripting.run("*777#", "111", &first.state, "1").unwrap();
+        assert_eq!(last, ScriptReply { text: "Score for 111: 1".to_string(), end: true, state: r#"{"score":1}"#.to_string() });
+    }
+
+    #[test]
+    fn test_plain_string_replies_example_script_and_errors() {
+        let echo = ScriptingConfig::new(vec![service("*1#", r#"fn handle(msisdn, state, input) { `You sent ${input}` }"#)]);
+        let reply = echo.run("*1#", "111", r#"{"seen":true}"#, "42").unwrap();
+        assert_eq!((reply.text.as_str(), reply.end, reply.state.as_str()), ("You sent 42", false, r#"{"seen":true}"#));
+
+        let mut looping = ScriptingConfig::new(vec![service("*2#", "fn handle(msisdn, state, input) { loop {} }")]);
+        looping.max_operations = 1000;
+        assert!(looping.run("*2#", "111", "{}", "*2#").is_err());
+
+        let quiz = ScriptedService { code: "*777#".to_string(), script: Some("scripts/airtime_quiz.rhai".to_string()), source: None };
+        let example = ScriptingConfig::new(vec![quiz]);
+        example.compile(Path::new(env!("CARGO_MANIFEST_DIR"))).unwrap();
+        assert!(example.run("*777#", "111", "{}", "*777#").unwrap().text.starts_with("Capital of Kenya?"));
+
+        let missing = ScriptingConfig::new(vec![service("*3#", "fn other() { 1 }")]);
+        assert!(missing.compile(Path::new("")).unwrap_err().contains("fn handle"));
+    }
+}
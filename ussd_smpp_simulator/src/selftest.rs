@@ -0,0 +1,127 @@
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::LevelFilter;
+
+use crate::demo::{self, DemoClient, DEMO_FORWARDING_CLIENT, DEMO_MSISDN, DEMO_SERVICE_CODE, DEMO_USER_CLIENT};
+use crate::transport::SmppStream;
+use crate::config::Config;
+use crate::server::UssdSmppServer;
+
+// One dialogue step: what is dialled or replied, and text the screen must contain
+pub struct Step {
+    pub input: &'static str,
+    pub expect: String,
+}
+
+pub struct StepResult {
+    pub name: String,
+    pub outcome: Result<(), String>,
+    pub elapsed: Duration,
+}
+
+// Starts the server on an ephemeral port, binds a user client and the demo forwarding client
+// over TCP and walks a built-in menu flow and a forwarded flow. Fails when any step does.
+pub fn run() -> io::Result<()> {
+    let level = log::max_level();
+    log::set_max_level(LevelFilter::Warn);
+    let results = self_test();
+    log::set_max_level(level);
+    print_results(&results?)
+}
+
+// One PASS or FAIL line per step, then a summary. Fails when any step did.
+pub fn print_results(results: &[StepResult]) -> io::Result<()> {
+    println!();
+    for result in results {
+        match &result.outcome {
+            Ok(()) => println!("✅ PASS  {:<36} {:>6}ms", result.name, result.elapsed.as_millis()),
+            Err(e) => println!("❌ FAIL  {:<36} {}", result.name, e),
+        }
+    }
+    let failed = results.iter().filter(|result| result.outcome.is_err()).count();
+    println!();
+    if failed > 0 {
+        return Err(io::Error::other(format!("{} of {} self-test steps failed", failed, results.len())));
+    }
+    println!("Self-test passed: {} steps", results.len());
+    Ok(())
+}
+
+fn self_test() -> io::Result<Vec<StepResult>> {
+    let mut config = Config::deterministic();
+    config.client_simulator.forwarding_clients = vec![DEMO_FORWARDING_CLIENT.to_string()];
+    config.client_simulator.user_clients = vec![DEMO_USER_CLIENT.to_string()];
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    println!("Self-test server listening on {}", addr);
+    let server = UssdSmppServer::new(config);
+    let config = server.config.get();
+    thread::spawn(move || {
+        if let Err(e) = server.serve(listener) {
+            println!("Self-test server stopped: {}", e);
+        }
+    });
+
+    let connect = || TcpStream::connect(addr).map(SmppStream::Tcp);
+    let forwarder = DemoClient::bind(connect()?, &config, DEMO_FORWARDING_CLIENT, "forward123")?;
+    thread::spawn(move || demo::run_forwarding_client(forwarder));
+    let mut phone = DemoClient::bind(connect()?, &config, DEMO_USER_CLIENT, "mobile123")?;
+
+    let responses = &config.ussd.responses;
+    let balance_inputs = ["*123#".to_string(), "1".to_string()];
+    let builtin = [
+        Step { input: "*123#", expect: config.ussd.menu.welcome_message.clone() },
+        Step { input: "1", expect: config.variables(DEMO_MSISDN, &balance_inputs).apply(&responses.balance_message) },
+        Step { input: "00", expect: responses.goodbye_message.clone() },
+    ];
+    let forwarded = [
+        Step { input: DEMO_SERVICE_CODE, expect: "Demo Bank".to_string() },
+        Step { input: "1", expect: "Balance: $1,250.00".to_string() },
+        Step { input: "4", expect: "Goodbye!".to_string() },
+    ];
+
+    let mut results = Vec::new();
+    for (flow, steps) in [("built-in", &builtin), ("forwarded", &forwarded)] {
+        results.extend(walk(flow, steps, |input| phone.ussd_request(DEMO_MSISDN, input).map_err(|e| e.to_string())));
+    }
+    phone.unbind()?;
+    Ok(results)
+}
+
+// Sends each step's input with `send`, which returns the screen it got, and checks the screen.
+// Stops at the first failure, as later steps of a broken dialogue would only repeat it.
+pub fn walk(flow: &str, steps: &[Step], mut send: impl FnMut(&str) -> Result<String, String>) -> Vec<StepResult> {
+    let mut results = Vec::new();
+    for step in steps {
+        let started = Instant::now();
+        let outcome = match send(step.input) {
+            Ok(screen) if screen.contains(&step.expect) => Ok(()),
+            Ok(screen) => Err(format!("expected {:?}, got {:?}", step.expect, screen)),
+            Err(e) => Err(e),
+        };
+        let failed = outcome.is_err();
+        results.push(StepResult { name: format!("{} {}", flow, step.input), outcome, elapsed: started.elapsed() });
+        if failed {
+            break;
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_test_passes() {
+        let results = self_test().unwrap();
+        assert_eq!(results.len(), 6);
+        for result in results {
+            assert_eq!(result.outcome, Ok(()), "{}", result.name);
+        }
+    }
+}
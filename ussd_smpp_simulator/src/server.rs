@@ -0,0 +1,2470 @@
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use log::info;
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+use ussd_common::{gsm7, run_id};
+use ussd_common::encoding::{self, TextEncoding};
+
+use crate::{grpc, listeners, shutdown, transport};
+use crate::admin::{AdminMetrics, AdminServer};
+use crate::capture::Direction;
+use crate::codec::{PduReadBuffer, PduReader};
+use crate::config::Config;
+use crate::control::Controller;
+use crate::correlation::PendingRequest;
+use crate::http_backend::BackendRequest;
+use crate::keepalive::{Keepalive, KeepaliveSettings};
+use crate::latency::LatencyStage;
+use crate::listeners::ConnectionSlot;
+use crate::logging::{subsystem_log, LogLevel, LogLevels, Subsystem};
+use crate::outbind::OutbindConfig;
+use crate::outbound::{Expiry, OutboundQueue, QueueLimits, PRIORITY_LEVELS};
+use crate::pdu::{
+    bind_type_name, build_ussd_deliver_sm, build_ussd_submit_sm, declared_screen_chars, deliver_sm_text, message_text,
+    strict, Bind, CancelSm, DeliverSm, QuerySm, QuerySmResp, ReplaceSm, SmppError, SmppHeader, SmppPdu, SubmitSm,
+    BIND_RECEIVER, BIND_TRANSCEIVER, BIND_TRANSMITTER, CANCEL_SM, DELIVER_SM, DELIVER_SM_RESP, ENQUIRE_LINK,
+    ENQUIRE_LINK_RESP, ESME_RALYBND, ESME_RBINDFAIL, ESME_RCANCELFAIL, ESME_RINVBNDSTS, ESME_RINVCMDID, ESME_RINVCMDLEN,
+    ESME_RINVDSTADR, ESME_RINVPASWD, ESME_RINVSRCADR, ESME_RINVSYSID, ESME_RMSGQFUL, ESME_ROK, ESME_RQUERYFAIL,
+    ESME_RREPLACEFAIL, ESME_RSUBMITFAIL, ESME_RTHROTTLED, ESME_RX_R_APPN, GENERIC_NACK, QUERY_SM, QUERY_SM_RESP,
+    REPLACE_SM, RESPONSE_BIT, SUBMIT_SM, SUBMIT_SM_RESP, TAG_MESSAGE_PAYLOAD, UNBIND, UNBIND_RESP, USSD_NOTIFY,
+    USSD_PSSR_RESPONSE, USSD_TERMINATE_NOTIFY, USSD_USSR_REQUEST,
+};
+use crate::persistence::{InboundSequence, MessageIdRecord, MessageState, StateStore};
+use crate::push::Pusher;
+use crate::receipts::{build_receipt, ReceiptStat, ScheduledReceipt};
+use crate::reload::{ConfigReloader, LiveConfig};
+use crate::router::ConnectionManager;
+use crate::session::{MessageContext, Session, UssdScreen, UssdSession, UssdState};
+use crate::session_store::SessionStore;
+use crate::shard::ShardedMap;
+use crate::smpp_time::absolute_time;
+use crate::timeline::FaultTimeline;
+use crate::transport::SmppStream;
+use crate::webhooks::EndReason;
+
+// A clone is another handle to the same server; every field is shared
+#[derive(Clone)]
+pub struct UssdSmppServer {
+    pub sessions: Arc<ShardedMap<Session>>, // Keyed by connection_id
+    pub ussd_sessions: Arc<ShardedMap<UssdSession>>, // Keyed by MSISDN
+    pub state_store: Arc<StateStore>,
+    pub session_store: Arc<SessionStore>,
+    pub config: Arc<LiveConfig>,
+    pub connection_manager: ConnectionManager,
+    pub log_levels: Arc<LogLevels>,
+    reloader: Option<Arc<ConfigReloader>>, // Set when the config came from a file
+}
+
+// Builds a server for embedding in another program or test harness, e.g.
+// `UssdSmppServer::builder().configure(|c| c.server.port = 0).spawn()`
+#[derive(Default)]
+pub struct ServerBuilder {
+    config: Config,
+    reload: Option<(String, ReloadOverrides)>,
+}
+
+type ReloadOverrides = Box<dyn Fn(&mut Config) + Send + Sync>;
+
+impl ServerBuilder {
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    // Adjusts the config set so far (Config::default() unless `config` was called)
+    pub fn configure(mut self, adjust: impl FnOnce(&mut Config)) -> Self {
+        adjust(&mut self.config);
+        self
+    }
+
+    // See UssdSmppServer::with_reload
+    pub fn reload_from(mut self, config_path: impl Into<String>, overrides: impl Fn(&mut Config) + Send + Sync + 'static) -> Self {
+        self.reload = Some((config_path.into(), Box::new(overrides)));
+        self
+    }
+
+    pub fn build(self) -> UssdSmppServer {
+        let server = UssdSmppServer::new(self.config);
+        match self.reload {
+            Some((config_path, overrides)) => server.with_reload(config_path, overrides),
+            None => server,
+        }
+    }
+
+    // Binds [server] host/port (port 0 picks a free one) and serves it on a background thread
+    pub fn spawn(self) -> std::io::Result<EmbeddedServer> {
+        let server = Arc::new(self.build());
+        let config = server.config.get();
+        let listener = TcpListener::bind((config.server.host.as_str(), config.server.port))?;
+        let addr = listener.local_addr()?;
+        server.start_services()?;
+        let serving = Arc::clone(&server);
+        thread::spawn(move || serving.accept_incoming(listener));
+        Ok(EmbeddedServer { server, addr })
+    }
+}
+
+// A server running inside the current process. Clients connect to `addr` over TCP, or
+// in-process through `server.connect()`.
+pub struct EmbeddedServer {
+    pub server: Arc<UssdSmppServer>,
+    pub addr: SocketAddr,
+}
+
+impl UssdSmppServer {
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder::default()
+    }
+
+    pub fn new(mut config: Config) -> Self {
+        crate::scenarios::apply_active(&mut config);
+        let log_levels = Arc::new(LogLevels::new(&config.logging.subsystems, config.logging.debug));
+        let connection_manager = ConnectionManager::new(config.smpp.delivery_policy, config.smpp.route_fallback, QueueLimits {
+            capacity: config.smpp.outbound_queue_capacity,
+            overflow: config.smpp.outbound_overflow,
+            block_timeout: Duration::from_millis(config.smpp.outbound_block_timeout_ms),
+        }, config.response_percentage.seed, config.throttle.clone());
+
+        let ussd_sessions = ShardedMap::with_shards(config.smpp.session_shards);
+        let session_store = SessionStore::open(&config.ussd.session_store).unwrap_or_else(|e| {
+            info!("⚠️  Could not open session store: {}, keeping sessions in memory", e);
+            SessionStore::memory()
+        });
+        match session_store.load() {
+            Ok(restored) if !restored.is_empty() => {
+                info!("💾 Restored {} USSD session(s) from {}", restored.len(), config.ussd.session_store);
+                for session in restored {
+                    ussd_sessions.insert(session.msisdn.clone(), session);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => info!("⚠️  Could not read sessions from {}: {}, starting fresh", config.ussd.session_store, e),
+        }
+
+        UssdSmppServer {
+            sessions: Arc::new(ShardedMap::with_shards(config.smpp.session_shards)),
+            ussd_sessions: Arc::new(ussd_sessions),
+            state_store: Arc::new(StateStore::load(&config.persistence)),
+            session_store: Arc::new(session_store),
+            config: Arc::new(LiveConfig::new(config)),
+            connection_manager,
+            log_levels,
+            reloader: None,
+        }
+    }
+
+    // Lets SIGHUP and the admin interface re-read `config_path` while the server runs
+    pub fn with_reload(mut self, config_path: String, overrides: impl Fn(&mut Config) + Send + Sync + 'static) -> Self {
+        self.reloader = Some(Arc::new(ConfigReloader::new(Arc::clone(&self.config), config_path, overrides)));
+        self
+    }
+
+    pub fn start(&self, addr: &str) -> std::io::Result<()> {
+        let listener = TcpListener::bind(addr)?;
+        info!("USSD SMPP Server listening on {}", addr);
+        let listeners = listeners::bind_all(&self.config.get().server)?;
+        self.spawn_signal_handler()?;
+        self.spawn_reload_handler()?;
+        thread::scope(|scope| {
+            for extra in listeners {
+                scope.spawn(move || self.serve_listener(extra));
+            }
+            self.serve(listener)
+        })
+    }
+
+    // SIGHUP re-reads the config file
+    fn spawn_reload_handler(&self) -> std::io::Result<()> {
+        let Some(reloader) = self.reloader.clone() else {
+            return Ok(());
+        };
+        let mut signals = Signals::new([SIGHUP])?;
+        thread::spawn(move || {
+            for _ in signals.forever() {
+                if let Err(e) = reloader.reload() {
+                    info!("⚠️  Config reload failed, keeping the running config: {}", e);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    // Ctrl+C and SIGTERM unbind every session and save persisted state before the process
+    // exits; a second signal exits at once
+    fn spawn_signal_handler(&self) -> std::io::Result<()> {
+        let mut signals = Signals::new([SIGINT, SIGTERM])?;
+        let sessions = Arc::clone(&self.sessions);
+        let connection_manager = self.connection_manager.clone();
+        let state_store = Arc::clone(&self.state_store);
+        let ussd_sessions = Arc::clone(&self.ussd_sessions);
+        let session_store = Arc::clone(&self.session_store);
+        let timeout = Duration::from_secs(self.config.get().server.shutdown_timeout);
+        thread::spawn(move || {
+            let mut signals = signals.forever();
+            if let Some(signal) = signals.next() {
+                info!("Received signal {}, shutting down", signal);
+                thread::spawn(move || {
+                    let summary = shutdown::unbind_all(&sessions, &connection_manager, &state_store, timeout);
+                    info!("{} of {} sessions acknowledged UNBIND", summary.acknowledged, summary.sent);
+                    info!("📊 Deliveries by priority: {}", serde_json::json!(connection_manager.priority_metrics.snapshot()));
+                    connection_manager.transcripts.finish_all();
+                    connection_manager.webhooks.finish_all(timeout);
+                    state_store.shutdown();
+                    session_store.shutdown(&ussd_sessions);
+                    std::process::exit(0);
+                });
+            }
+            if let Some(signal) = signals.next() {
+                info!("Received signal {} again, exiting without waiting", signal);
+                std::process::exit(1);
+            }
+        });
+        Ok(())
+    }
+
+    pub fn serve(&self, listener: TcpListener) -> std::io::Result<()> {
+        self.start_services()?;
+        self.accept_incoming(listener);
+        Ok(())
+    }
+
+    fn accept_incoming(&self, listener: TcpListener) {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) if self.refuse_peer(&stream) => {}
+                Ok(stream) => self.accept(SmppStream::Tcp(stream), None),
+                Err(e) => info!("Connection failed: {}", e),
+            }
+        }
+    }
+
+    // In-process connection for embedding the server; the returned end is the ESME's socket
+    pub fn connect(&self) -> SmppStream {
+        let (client, server) = transport::channel_pair();
+        self.accept(server, None);
+        client
+    }
+
+    // Background threads and the admin interface; call once before accepting connections
+    pub fn start_services(&self) -> std::io::Result<()> {
+        let config = self.config.get();
+        if config.logging.debug {
+            info!("Debug logging enabled");
+            info!("Configuration: {:#?}", config);
+        }
+        info!("Log levels: {:?}", self.log_levels.snapshot());
+        self.state_store.spawn_flusher();
+        self.session_store.spawn_snapshots(Arc::clone(&self.ussd_sessions), Duration::from_millis(config.persistence.flush_interval_ms));
+        self.connection_manager.capture.start(&config.capture, config.server.port)?;
+        self.connection_manager.transcripts.start(&config.transcript)?;
+        self.connection_manager.webhooks.start(&config.webhooks)?;
+        self.connection_manager.journal.start(&config.delivery_retry)?;
+        config.scripting.log_services();
+        self.spawn_session_sweeper();
+        self.spawn_delivery_retry();
+        self.spawn_delivery_receipts();
+        self.spawn_forward_expiry();
+        self.spawn_fault_timeline()?;
+        for target in &config.outbind {
+            self.spawn_outbind(target.clone());
+        }
+        
+        let pusher = self.pusher();
+        pusher.spawn_schedule();
+
+        if config.admin.enabled {
+            let reloader = self.reloader.clone();
+            AdminServer::new(
+                config.admin.clone(),
+                Arc::clone(&self.log_levels),
+                AdminMetrics {
+                    priority: Arc::clone(&self.connection_manager.priority_metrics),
+                    throttle: Arc::clone(&self.connection_manager.throttle),
+                    windows: Arc::clone(&self.connection_manager.windows),
+                },
+                Arc::clone(&self.state_store),
+                Arc::new(move |request| pusher.push(request)),
+                Arc::new(move || match &reloader {
+                    Some(reloader) => reloader.reload(),
+                    None => Err("the server was not started from a config file".to_string()),
+                }),
+                Arc::new(self.controller()),
+            ).spawn()?;
+        }
+        if config.grpc.enabled {
+            let server = self.clone();
+            grpc::spawn(&config.grpc, self.controller(), Arc::new(move || server.connect()))?;
+        }
+        Ok(())
+    }
+
+    // Sends network-initiated USSD to bound user clients
+    pub fn pusher(&self) -> Pusher {
+        Pusher {
+            sessions: Arc::clone(&self.sessions),
+            ussd_sessions: Arc::clone(&self.ussd_sessions),
+            state_store: Arc::clone(&self.state_store),
+            config: Arc::clone(&self.config),
+            connection_manager: self.connection_manager.clone(),
+            log_levels: Arc::clone(&self.log_levels),
+        }
+    }
+
+    // Lists and steers live sessions for the admin interface
+    pub fn controller(&self) -> Controller {
+        Controller {
+            sessions: Arc::clone(&self.sessions),
+            ussd_sessions: Arc::clone(&self.ussd_sessions),
+            state_store: Arc::clone(&self.state_store),
+            config: Arc::clone(&self.config),
+            connection_manager: self.connection_manager.clone(),
+            log_levels: Arc::clone(&self.log_levels),
+            reloader: self.reloader.clone(),
+        }
+    }
+
+    // Closes a new socket whose peer [server.flood_protection] turns away
+    pub(crate) fn refuse_peer(&self, stream: &TcpStream) -> bool {
+        let Ok(peer) = stream.peer_addr() else {
+            return false;
+        };
+        let config = self.config.get();
+        let Err(refusal) = self.connection_manager.flood.admit(&config.server.flood_protection, peer.ip()) else {
+            return false;
+        };
+        info!("🚫 Refusing connection from {}: {}", peer, refusal.reason());
+        let _ = stream.shutdown(Shutdown::Both);
+        true
+    }
+
+    // `slot` counts the connection against its listener's max_connections until it closes
+    pub(crate) fn accept(&self, stream: SmppStream, slot: Option<ConnectionSlot>) {
+        if self.connection_manager.shutdown.draining() {
+            let _ = stream.shutdown(Shutdown::Both);
+            return;
+        }
+        let server = self.clone();
+        thread::spawn(move || {
+            let _slot = slot;
+            server.serve_connection(stream, None);
+        });
+    }
+
+    // Runs one connection on the calling thread until it closes. On a connection the server
+    // dialled itself, `outbind` goes out first to ask the ESME to bind.
+    fn serve_connection(&self, stream: SmppStream, outbind: Option<SmppPdu>) {
+        let mut handler = UssdConnectionHandler::new(
+            stream,
+            Arc::clone(&self.sessions),
+            Arc::clone(&self.ussd_sessions),
+            Arc::clone(&self.state_store),
+            Arc::clone(&self.config),
+            self.connection_manager.clone(),
+            Arc::clone(&self.log_levels),
+        );
+        if let Err(e) = handler.handle(outbind) {
+            info!("Connection error: {}", e);
+        }
+    }
+
+    // Keeps a connection open to an ESME that only accepts inbound ones, dialling again after
+    // each failure or disconnect until the server shuts down
+    fn spawn_outbind(&self, target: OutbindConfig) {
+        let server = self.clone();
+        thread::spawn(move || {
+            let interval = Duration::from_secs(target.reconnect_interval.max(1));
+            while !server.connection_manager.shutdown.draining() {
+                match target.dial() {
+                    Ok(stream) => {
+                        info!("📞 Outbind: connected to {}, waiting for its BIND_RECEIVER", target.addr());
+                        let config = server.config.get();
+                        let outbind = target.pdu(&config.smpp.system_id, server.state_store.next_sequence());
+                        server.serve_connection(SmppStream::Tcp(stream), Some(outbind));
+                        info!("📞 Outbind: connection to {} closed", target.addr());
+                    }
+                    Err(e) => info!("📞 Outbind: could not reach {}: {}", target.addr(), e),
+                }
+                thread::sleep(interval);
+            }
+        });
+    }
+}
+
+impl UssdSmppServer {
+    fn spawn_fault_timeline(&self) -> std::io::Result<()> {
+        let config = self.config.get();
+        let Some(path) = &config.timeline.path else {
+            return Ok(());
+        };
+        let timeline = FaultTimeline::load(path)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        
+        let sessions = Arc::clone(&self.sessions);
+        let connection_manager = self.connection_manager.clone();
+        timeline.spawn(Arc::clone(&self.connection_manager.faults), move || {
+            connection_manager.drop_connections(&sessions, |session| session.can_receive_forwards && !session.is_user_client)
+        });
+        Ok(())
+    }
+    
+    // Retries held DELIVER_SMs every delivery_retry.interval_ms until a user client takes them
+    fn spawn_delivery_retry(&self) {
+        let journal = Arc::clone(&self.connection_manager.journal);
+        if !journal.enabled() {
+            return;
+        }
+        info!("📥 Holding undeliverable DELIVER_SMs, retrying every {}ms", journal.interval_ms());
+
+        let sessions = Arc::clone(&self.sessions);
+        let config = Arc::clone(&self.config);
+        let connection_manager = self.connection_manager.clone();
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_millis(journal.interval_ms()));
+            let user_clients = config.get().client_simulator.user_clients.clone();
+            journal.release(|msisdn| connection_manager.get_msisdn_connection(&sessions, msisdn, &user_clients), true);
+        });
+    }
+
+    // Sends each scheduled delivery receipt when it falls due, to a receiving bind of the
+    // system_id that submitted the message, and moves the message to the state it reports
+    fn spawn_delivery_receipts(&self) {
+        let receipts = Arc::clone(&self.connection_manager.receipts);
+        let sessions = Arc::clone(&self.sessions);
+        let state_store = Arc::clone(&self.state_store);
+        let connection_manager = self.connection_manager.clone();
+        thread::spawn(move || loop {
+            let receipt = receipts.next_due();
+            // A message cancelled with CANCEL_SM was never delivered to report on
+            let record = state_store.lookup_message_id(&receipt.message_id);
+            if record.is_some_and(|record| record.state == MessageState::Deleted) {
+                continue;
+            }
+            state_store.set_message_state(&receipt.message_id, receipt.stat.message_state());
+            
+            let Some(queue) = connection_manager.get_receipt_connection(&sessions, &receipt.system_id) else {
+                info!("⚠️  No receiving bind of {} for the {} receipt of {}", receipt.system_id, receipt.stat.label(), receipt.message_id);
+                continue;
+            };
+            match queue.push(receipt.priority_flag, receipt.pdu) {
+                Ok(()) => info!("🧾 {} receipt sent for {}", receipt.stat.label(), receipt.message_id),
+                Err(e) => info!("⚠️  Could not deliver the {} receipt of {}: {}", receipt.stat.label(), receipt.message_id, e),
+            }
+        });
+    }
+
+    // Drops USSD sessions idle for longer than ussd.session_timeout
+    fn spawn_session_sweeper(&self) {
+        let timeout = Duration::from_secs(self.config.get().ussd.session_timeout);
+        if timeout.is_zero() {
+            return;
+        }
+        
+        let ussd_sessions = Arc::clone(&self.ussd_sessions);
+        let sessions = Arc::clone(&self.sessions);
+        let state_store = Arc::clone(&self.state_store);
+        let config = Arc::clone(&self.config);
+        let connection_manager = self.connection_manager.clone();
+        let log_levels = Arc::clone(&self.log_levels);
+        let sweep_interval = timeout.min(Duration::from_secs(5));
+        
+        thread::spawn(move || loop {
+            thread::sleep(sweep_interval);
+            
+            let expired = ussd_sessions.remove_where(|session| session.last_activity.elapsed() >= timeout);
+            
+            let config = config.get();
+            for session in expired {
+                info!("⌛ USSD session {} for {} timed out after {}s",
+                    session.session_id, session.msisdn, timeout.as_secs());
+                // Ended first, so the timeout screen below does not report it as released
+                connection_manager.webhooks.finish(&session.msisdn, EndReason::Timeout);
+                if config.ussd.notify_on_timeout {
+                    let text = &config.ussd.responses.session_timeout_message;
+                    send_terminate_notification(&config, &sessions, &state_store, &connection_manager, &log_levels, &session, text);
+                }
+                connection_manager.transcripts.finish(&session.msisdn);
+                connection_manager.forget_origin(&session.msisdn);
+            }
+        });
+    }
+}
+
+// USSD_TERMINATE_NOTIFY telling the subscriber the network closed their session
+pub fn send_terminate_notification(
+    config: &Config,
+    sessions: &ShardedMap<Session>,
+    state_store: &StateStore,
+    connection_manager: &ConnectionManager,
+    log_levels: &LogLevels,
+    session: &UssdSession,
+    text: &str,
+) {
+    let text = config.compression.apply(text);
+    let screen_chars = connection_manager.screen_chars(sessions, &session.msisdn, &config.screens);
+    let text = config.screens.truncate(&text, screen_chars);
+    let notify = build_ussd_deliver_sm(
+        &session.msisdn,
+        &text,
+        session.last_message.priority_flag,
+        state_store.next_sequence(),
+        Some(USSD_TERMINATE_NOTIFY),
+        config.ussd.responses.encoding,
+        config,
+    );
+    match connection_manager.get_msisdn_connection(sessions, &session.msisdn, &config.client_simulator.user_clients) {
+        Some(queue) => {
+            if let Err(e) = queue.push(session.last_message.priority_flag, notify) {
+                info!("⚠️  Could not send termination notice to {}: {}", session.msisdn, e);
+            } else {
+                connection_manager.transcripts.response(&session.msisdn, &text, Some(USSD_TERMINATE_NOTIFY));
+                connection_manager.webhooks.response(&session.msisdn, &text, Some(USSD_TERMINATE_NOTIFY));
+                subsystem_log!(log_levels, Subsystem::Sessions, LogLevel::Debug,
+                    "🗂️  Termination notice queued for {}", session.msisdn);
+            }
+        }
+        None => {
+            if !connection_manager.journal.hold(&session.msisdn, session.last_message.priority_flag, &notify) {
+                subsystem_log!(log_levels, Subsystem::Sessions, LogLevel::Warn,
+                    "⚠️  No user connection for termination notice to {}", session.msisdn);
+            }
+        }
+    }
+}
+
+impl UssdSmppServer {
+    // Fails forwarded requests the client never answered or refused, so the subscriber is not
+    // left waiting on a screen that will not come
+    fn spawn_forward_expiry(&self) {
+        let sessions = Arc::clone(&self.sessions);
+        let ussd_sessions = Arc::clone(&self.ussd_sessions);
+        let state_store = Arc::clone(&self.state_store);
+        let config = Arc::clone(&self.config);
+        let connection_manager = self.connection_manager.clone();
+        
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_millis(500));
+            
+            let config = config.get();
+            for request in connection_manager.pending.take_expired() {
+                match request.rejected_with {
+                    Some(status) => info!("❌ Forwarded request seq={} for {} rejected with 0x{:08x}",
+                        request.sequence_number, request.msisdn, status),
+                    None => info!("⌛ Forwarded request seq={} for {} got no reply within {}s",
+                        request.sequence_number, request.msisdn, config.ussd.forward_timeout),
+                }
+                
+                // The subscriber's session ends with the error screen
+                ussd_sessions.update(&request.msisdn, |session| {
+                    if session.session_id == request.session_id {
+                        session.state = UssdState::Terminated;
+                    }
+                });
+                
+                let text = config.compression.apply(&config.ussd.responses.forward_error_message);
+                let screen_chars = connection_manager.screen_chars(&sessions, &request.msisdn, &config.screens);
+                let text = config.screens.truncate(&text, screen_chars);
+                let error = build_ussd_deliver_sm(
+                    &request.msisdn,
+                    &text,
+                    request.priority_flag,
+                    state_store.next_sequence(),
+                    Some(USSD_TERMINATE_NOTIFY),
+                    config.ussd.responses.encoding,
+                    &config,
+                );
+                match connection_manager.get_msisdn_connection(&sessions, &request.msisdn, &config.client_simulator.user_clients) {
+                    Some(queue) => {
+                        if let Err(e) = queue.push(request.priority_flag, error) {
+                            info!("⚠️  Could not send forward error to {}: {}", request.msisdn, e);
+                        } else {
+                            connection_manager.transcripts.response(&request.msisdn, &text, Some(USSD_TERMINATE_NOTIFY));
+                            connection_manager.webhooks.response(&request.msisdn, &text, Some(USSD_TERMINATE_NOTIFY));
+                        }
+                    }
+                    None => {
+                        if !connection_manager.journal.hold(&request.msisdn, request.priority_flag, &error) {
+                            info!("⚠️  No user connection for forward error to {}", request.msisdn);
+                        }
+                    }
+                }
+            }
+        });
+    }
+}
+
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(1);
+static NEXT_SESSION: AtomicU64 = AtomicU64::new(1);
+
+pub(crate) struct UssdConnectionHandler {
+    stream: SmppStream,
+    sessions: Arc<ShardedMap<Session>>,
+    ussd_sessions: Arc<ShardedMap<UssdSession>>,
+    state_store: Arc<StateStore>,
+    current_session: Option<String>,
+    live_config: Arc<LiveConfig>,
+    pub(crate) config: Arc<Config>, // live_config as of the PDU being handled
+    connection_id: String,
+    connection_manager: ConnectionManager,
+    pub(crate) log_levels: Arc<LogLevels>,
+    keepalive: Option<Arc<Keepalive>>,
+    read_buffer: PduReadBuffer,
+    unbound: bool, // Answered the server's UNBIND, so the connection is finished
+    peer_ip: Option<IpAddr>, // Malformed PDUs count towards a ban of this address
+}
+
+impl UssdConnectionHandler {
+    fn new(
+        stream: SmppStream,
+        sessions: Arc<ShardedMap<Session>>,
+        ussd_sessions: Arc<ShardedMap<UssdSession>>,
+        state_store: Arc<StateStore>,
+        live_config: Arc<LiveConfig>,
+        connection_manager: ConnectionManager,
+        log_levels: Arc<LogLevels>,
+    ) -> Self {
+        // Unique for the life of the process, however close together connections arrive
+        let connection_id = format!("conn_{}", NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed));
+        let peer_ip = stream.peer_addr().map(|peer| peer.ip());
+        
+        UssdConnectionHandler {
+            stream,
+            sessions,
+            ussd_sessions,
+            state_store,
+            current_session: None,
+            config: live_config.get(),
+            live_config,
+            connection_id,
+            connection_manager,
+            log_levels,
+            keepalive: None,
+            read_buffer: PduReadBuffer::new(),
+            unbound: false,
+            peer_ip,
+        }
+    }
+
+    fn handle(&mut self, outbind: Option<SmppPdu>) -> std::io::Result<()> {
+        info!("New USSD connection established");
+        
+        // Add connection to manager
+        self.connection_manager.add_connection(self.connection_id.clone(), Arc::new(Mutex::new(self.stream.try_clone()?)));
+        if let Some(outbind) = outbind {
+            // The ESME answers with a bind, served below like one on an accepted connection
+            self.send_pdu(outbind)?;
+        }
+        
+        while !self.unbound {
+            match self.read_pdu() {
+                Ok(pdu) => {
+                    if let Err(e) = self.process_pdu(pdu) {
+                        info!("Error processing PDU: {}", e);
+                        break;
+                    }
+                }
+                Err(e) => {
+                    info!("Error reading PDU: {}", e);
+                    if e.kind() == std::io::ErrorKind::InvalidData {
+                        self.nack_bad_frame();
+                    }
+                    break;
+                }
+            }
+        }
+        
+        if let Some(keepalive) = &self.keepalive {
+            keepalive.stop();
+        }
+        if self.unbound {
+            // The side that sent the UNBIND closes the connection
+            let _ = self.stream.shutdown(Shutdown::Both);
+        }
+        
+        if let Some(connection_id) = &self.current_session
+            && let Some(session) = self.sessions.remove(connection_id)
+        {
+            info!("Session {} ({}) disconnected", session.system_id, connection_id);
+            self.connection_manager.live.unbind(connection_id, &session.system_id);
+            self.state_store.forget_inbound_sequence(&format!("{}@{}", session.system_id, connection_id));
+        }
+        
+        // Remove connection from manager
+        self.connection_manager.remove_connection(&self.connection_id);
+        self.connection_manager.windows.remove(&self.connection_id);
+        
+        Ok(())
+    }
+
+    fn read_pdu(&mut self) -> std::io::Result<SmppPdu> {
+        self.read_buffer.set_max_pdu_len(self.config.smpp.max_pdu_len);
+        let pdu = self.read_buffer.read_pdu(&mut self.stream)?;
+        self.connection_manager.capture.record_pdu(Direction::Inbound, &self.connection_id, &pdu);
+        self.connection_manager.live.pdu(Direction::Inbound, &self.connection_id, &pdu.header);
+        Ok(pdu)
+    }
+
+    // A request that fails is answered with the error's status and the bind kept; only a failed
+    // connection is returned
+    fn process_pdu(&mut self, pdu: SmppPdu) -> Result<(), SmppError> {
+        let header = pdu.header;
+        match self.dispatch_pdu(pdu) {
+            Err(e @ (SmppError::Io(_) | SmppError::Timeout(_))) => Err(e),
+            Err(e) => Ok(self.reject(header, &e)?),
+            Ok(()) => Ok(()),
+        }
+    }
+
+    fn dispatch_pdu(&mut self, pdu: SmppPdu) -> Result<(), SmppError> {
+        // A reload applies from the next PDU on
+        self.config = self.live_config.get();
+        
+        // Checked before the sequence is recorded, so a corrected retransmission is not a repeat
+        if self.config.smpp.strict
+            && pdu.header.command_id & RESPONSE_BIT == 0
+            && let Err(e) = strict::validate(pdu.header.command_id, &pdu.body)
+        {
+            info!("🧐 Strict mode refused 0x{:08x}: {}", pdu.header.command_id, e);
+            return Err(e);
+        }
+        
+        // Responses echo our own sequence numbers, so only requests are tracked
+        if pdu.header.command_id & 0x80000000 == 0
+            && let Some(system_id) = self.bound_system_id()
+        {
+            let binding = format!("{}@{}", system_id, self.connection_id);
+            match self.state_store.record_inbound_sequence(&binding, pdu.header.sequence_number) {
+                InboundSequence::Advanced => {}
+                InboundSequence::Repeated if matches!(pdu.header.command_id, SUBMIT_SM | DELIVER_SM) => {
+                    // A retransmission must not move the USSD dialogue on a second time
+                    info!("🔢 Rejecting repeated sequence {} from {} (0x{:08x})",
+                        pdu.header.sequence_number, system_id, pdu.header.command_id);
+                    let status = if pdu.header.command_id == SUBMIT_SM { ESME_RSUBMITFAIL } else { ESME_RX_R_APPN };
+                    return Err(SmppError::protocol(status, "repeated sequence number"));
+                }
+                InboundSequence::Repeated => {}
+                InboundSequence::Rewound(previous) => {
+                    subsystem_log!(self.log_levels, Subsystem::Sessions, LogLevel::Debug,
+                        "🔢 Sequence from {} went backwards: {} after {}",
+                        system_id, pdu.header.sequence_number, previous);
+                }
+            }
+        }
+        
+        match pdu.header.command_id {
+            BIND_RECEIVER | BIND_TRANSMITTER | BIND_TRANSCEIVER => {
+                self.handle_bind(pdu)?;
+            }
+            SUBMIT_SM => {
+                self.handle_ussd_submit_sm(pdu)?;
+            }
+            SUBMIT_SM_RESP => {
+                self.handle_submit_sm_resp(pdu)?;
+            }
+            QUERY_SM => {
+                self.handle_query_sm(pdu)?;
+            }
+            CANCEL_SM => {
+                self.handle_cancel_sm(pdu)?;
+            }
+            REPLACE_SM => {
+                self.handle_replace_sm(pdu)?;
+            }
+            DELIVER_SM => {
+                self.handle_deliver_sm(pdu)?;
+            }
+            DELIVER_SM_RESP => {
+                self.handle_deliver_sm_resp(pdu)?;
+            }
+            ENQUIRE_LINK => {
+                self.handle_enquire_link(pdu)?;
+            }
+            ENQUIRE_LINK_RESP => {
+                if let Some(keepalive) = &self.keepalive {
+                    keepalive.acknowledge(pdu.header.sequence_number);
+                }
+            }
+            UNBIND => {
+                self.handle_unbind(pdu)?;
+            }
+            UNBIND_RESP => {
+                if self.connection_manager.shutdown.acknowledge(&self.connection_id, pdu.header.sequence_number) {
+                    info!("Connection {} acknowledged shutdown UNBIND", self.connection_id);
+                    self.unbound = true;
+                }
+            }
+            command_id if command_id & 0x80000000 == 0 => {
+                return Err(SmppError::protocol(ESME_RINVCMDID, "unknown command"));
+            }
+            _ => {
+                info!("Unhandled command ID: 0x{:08x}", pdu.header.command_id);
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_bind(&mut self, pdu: SmppPdu) -> Result<(), SmppError> {
+        let (system_id, password, declared_chars) = self.parse_bind_request(&pdu.body);
+        
+        info!("Bind request from system_id: {}", system_id);
+        
+        // A connection carries exactly one bind; extra binds go on new connections
+        let result = if self.current_session.is_some() {
+            Err(SmppError::protocol(ESME_RALYBND, "connection already bound"))
+        } else if self.connection_manager.faults.forwarding_down()
+            && self.config.client_simulator.forwarding_clients.contains(&system_id)
+            && !self.config.client_simulator.user_clients.contains(&system_id)
+        {
+            // Fault timeline outage: forwarding clients stay away until recovery
+            Err(SmppError::Auth { status: ESME_RBINDFAIL, system_id: system_id.clone() })
+        } else {
+            self.authenticate(&system_id, &password, pdu.header.command_id)
+        };
+        if result.is_ok() {
+            // Check if this system_id can receive forwarded requests
+            let can_receive_forwards = self.config.client_simulator.forwarding_clients
+                .contains(&system_id);
+            
+            // Check if this is a user client
+            let is_user_client = self.config.client_simulator.user_clients
+                .contains(&system_id);
+            
+            let session = Session {
+                system_id: system_id.clone(),
+                password: password.clone(),
+                bound: true,
+                bind_type: pdu.header.command_id,
+                can_receive_forwards,
+                is_user_client,
+                connection_id: Some(self.connection_id.clone()),
+                screen_chars: declared_chars.or_else(|| self.config.screens.configured_chars(&system_id)),
+            };
+            
+            // Sessions are keyed by connection so one system_id can hold several binds
+            self.connection_manager.live.bind(&session);
+            self.sessions.insert(self.connection_id.clone(), session);
+            self.current_session = Some(self.connection_id.clone());
+            
+            self.start_keepalive(&system_id)?;
+            
+            if is_user_client {
+                info!("Bind successful for system_id: {} (user client)", system_id);
+            } else if can_receive_forwards {
+                info!("Bind successful for system_id: {} (forwarding client)", system_id);
+            } else {
+                info!("Bind successful for system_id: {} (regular client)", system_id);
+            }
+        }
+
+        // A refused bind is answered here rather than by the connection loop, as the response
+        // still names this SMSC
+        let status = match result {
+            Ok(()) => ESME_ROK,
+            Err(e) => {
+                info!("Bind failed for system_id: {}: {}", system_id, e);
+                e.command_status()
+            }
+        };
+        let resp_command_id = pdu.header.command_id | 0x80000000;
+        let response = self.create_bind_response(resp_command_id, status, pdu.header.sequence_number);
+        self.send_pdu(response)?;
+
+        // DELIVER_SMs held while no user client was bound follow the BIND response
+        if status == ESME_ROK && self.config.client_simulator.user_clients.contains(&system_id) {
+            let user_clients = &self.config.client_simulator.user_clients;
+            self.connection_manager.journal.release(
+                |msisdn| self.connection_manager.get_msisdn_connection(&self.sessions, msisdn, user_clients),
+                false,
+            );
+        }
+        
+        Ok(())
+    }
+
+    fn start_keepalive(&mut self, system_id: &str) -> std::io::Result<()> {
+        let smpp = &self.config.smpp;
+        if smpp.enquire_link_interval == 0 {
+            return Ok(());
+        }
+        let Some(queue) = self.connection_manager.get_connection(&self.connection_id) else {
+            return Ok(());
+        };
+        
+        let settings = KeepaliveSettings {
+            interval: Duration::from_secs(smpp.enquire_link_interval),
+            timeout: Duration::from_secs(smpp.enquire_link_timeout),
+            max_missed: smpp.enquire_link_max_missed.max(1),
+        };
+        let label = format!("{} ({})", system_id, self.connection_id);
+        self.keepalive = Some(Keepalive::spawn(settings, label, queue, self.stream.try_clone()?, Arc::clone(&self.state_store)));
+        Ok(())
+    }
+
+    fn bound_system_id(&self) -> Option<String> {
+        let connection_id = self.current_session.as_ref()?;
+        self.sessions.read(connection_id, |session| session.system_id.clone())
+    }
+
+    fn bound_session_allows(&self, permitted: fn(&Session) -> bool) -> bool {
+        let Some(connection_id) = &self.current_session else {
+            return false;
+        };
+        self.sessions.read(connection_id, |session| session.bound && permitted(session)).unwrap_or(false)
+    }
+
+    fn authenticate(&self, system_id: &str, password: &str, bind_command: u32) -> Result<(), SmppError> {
+        let accounts = &self.config.smpp.accounts;
+        let refuse = |status| Err(SmppError::Auth { status, system_id: system_id.to_string() });
+        
+        // Without a credential store any non-empty pair is accepted
+        if accounts.is_empty() {
+            return if system_id.is_empty() {
+                refuse(ESME_RINVSYSID)
+            } else if password.is_empty() {
+                refuse(ESME_RINVPASWD)
+            } else {
+                Ok(())
+            };
+        }
+        
+        let Some(account) = accounts.iter().find(|account| account.system_id == system_id) else {
+            return refuse(ESME_RINVSYSID);
+        };
+        
+        if account.password != password {
+            return refuse(ESME_RINVPASWD);
+        }
+        
+        let bind_type = bind_type_name(bind_command);
+        if !account.allowed_bind_types.iter().any(|allowed| allowed.eq_ignore_ascii_case(bind_type)) {
+            info!("Bind type {} not allowed for system_id: {}", bind_type, system_id);
+            return refuse(ESME_RBINDFAIL);
+        }
+        
+        Ok(())
+    }
+
+    fn handle_ussd_submit_sm(&mut self, pdu: SmppPdu) -> Result<(), SmppError> {
+        info!("Received USSD SUBMIT_SM");
+        
+        if !self.bound_session_allows(Session::can_transmit) {
+            return Err(SmppError::protocol(ESME_RINVBNDSTS, "connection is not bound as transmitter or transceiver"));
+        }
+        
+        // The window goes first so a SUBMIT_SM it refuses does not spend a throttle token
+        let system_id = self.bound_system_id().unwrap_or_default();
+        let windows = &self.connection_manager.windows;
+        if !windows.admit(&self.connection_id, &system_id, pdu.header.sequence_number, self.config.smpp.window_size) {
+            info!("🪟 Rejecting SUBMIT_SM from {}: {} SUBMIT_SMs still unanswered", system_id, self.config.smpp.window_size);
+            return Err(SmppError::protocol(ESME_RMSGQFUL, "window full"));
+        }
+        
+        if let Some(system_id) = self.bound_system_id()
+            && !self.connection_manager.throttle.admit(&system_id)
+        {
+            info!("🚦 Throttling SUBMIT_SM from {}: over its messages_per_second", system_id);
+            return Err(SmppError::protocol(ESME_RTHROTTLED, "over messages_per_second"));
+        }
+        
+        let submit_sm = SubmitSm::decode(&pdu.body)?;
+        self.check_addressing(&submit_sm)?;
+        
+        // Remember which bind this MSISDN is talking through so responses find their way back
+        if let Some(system_id) = self.bound_system_id() {
+            self.connection_manager.record_origin(&submit_sm.source_addr, &self.connection_id, &system_id);
+        }
+        
+        // Determine response type based on configured percentages
+        let text = message_text(submit_sm.data_coding, submit_sm.message(), self.config.smpp.gsm7_packing);
+        let service_code = self.service_code_for(&submit_sm.source_addr, &text);
+        let response_type = self.determine_response_type(&service_code);
+        
+        if !matches!(response_type, ResponseType::NoResponse) {
+            self.inject_latency(&service_code, LatencyStage::SubmitSmResp);
+        }
+        
+        match response_type {
+            ResponseType::Success => {
+                // Normal processing - send success response
+                let message_id = self.generate_message_id(&submit_sm.source_addr);
+                let body = format!("{}\0", message_id).into_bytes();
+                let response = SmppPdu::new(SUBMIT_SM_RESP, ESME_ROK, pdu.header.sequence_number, body);
+                
+                self.send_pdu(response)?;
+                info!("SUBMIT_SM_RESP sent with message_id: {}", message_id);
+                
+                // Process USSD request and send response. The SUBMIT_SM is answered already, so
+                // only a failed connection is passed up.
+                let outcome = self.process_ussd_request(&submit_sm, &message_id);
+                let state = match &outcome {
+                    Ok(state) => *state,
+                    Err(e) => {
+                        info!("⚠️  No screen for {}: {}", submit_sm.source_addr, e);
+                        MessageState::Undeliverable
+                    }
+                };
+                self.state_store.set_message_state(&message_id, state);
+                self.schedule_delivery_receipt(&submit_sm, &message_id, state);
+                if let Err(e @ SmppError::Io(_)) = outcome {
+                    return Err(e);
+                }
+            }
+            ResponseType::Failure(error_code) => {
+                // Send failure response
+                info!("Simulating failure response for SUBMIT_SM");
+                self.send_submit_sm_resp_error(pdu.header.sequence_number, error_code)?;
+            }
+            ResponseType::NoResponse => {
+                // No response - just log and delay
+                info!("Simulating no response for SUBMIT_SM");
+                let timeout = Duration::from_secs(self.config.smpp.window_timeout);
+                self.connection_manager.windows.unanswered(&self.connection_id, pdu.header.sequence_number, timeout);
+                thread::sleep(Duration::from_millis(self.config.response_percentage.no_response_delay_ms));
+                // Don't send any response
+            }
+        }
+        
+        Ok(())
+    }
+
+    // Refuses TON/NPI and address formats [addressing] does not accept. An empty source_addr asks
+    // for the SMSC's default address, so only the destination must be given.
+    fn check_addressing(&self, message: &SubmitSm) -> Result<(), SmppError> {
+        let addressing = &self.config.addressing;
+        if !message.source_addr.is_empty()
+            && let Err(reason) = addressing.check(message.source_addr_ton, message.source_addr_npi, &message.source_addr)
+        {
+            info!("📵 Refusing source_addr from {}: {}", self.bound_system_id().unwrap_or_default(), reason);
+            return Err(SmppError::protocol(ESME_RINVSRCADR, "invalid source address"));
+        }
+        if let Err(reason) = addressing.check(message.dest_addr_ton, message.dest_addr_npi, &message.destination_addr) {
+            info!("📵 Refusing destination_addr from {}: {}", self.bound_system_id().unwrap_or_default(), reason);
+            return Err(SmppError::protocol(ESME_RINVDSTADR, "invalid destination address"));
+        }
+        Ok(())
+    }
+
+    // The state reached is DELIVERED once a screen went back or the request reached its
+    // forwarding client, and UNDELIVERABLE when forwarding failed
+    fn process_ussd_request(&mut self, submit_sm: &SubmitSm, message_id: &str) -> Result<MessageState, SmppError> {
+        let msisdn = submit_sm.source_addr.to_string();
+        let ussd_code = message_text(submit_sm.data_coding, submit_sm.message(), self.config.smpp.gsm7_packing);
+        let message = MessageContext::new(
+            message_id.to_string(),
+            &msisdn,
+            &submit_sm.destination_addr,
+            submit_sm.priority_flag,
+            submit_sm.registered_delivery,
+            &submit_sm.validity_period,
+        );
+        
+        info!("Processing USSD request from {}: {}", msisdn, ussd_code);
+        self.connection_manager.transcripts.input(&msisdn, &ussd_code);
+        
+        if let Some(page) = self.take_next_page(&msisdn, &ussd_code) {
+            if let Some((session_id, service_code)) = self.ussd_sessions.read(&msisdn, |session| (session.session_id.clone(), session.service_code.clone())) {
+                self.connection_manager.webhooks.request(&msisdn, &session_id, &service_code, &ussd_code);
+                self.state_store.set_message_session(message_id, &session_id, &service_code);
+            }
+            thread::sleep(Duration::from_millis(self.config.response_percentage.response_delay_ms));
+            self.inject_latency(&self.service_code_for(&msisdn, &ussd_code), LatencyStage::DeliverSm);
+            self.deliver_screen(&msisdn, &page, submit_sm.priority_flag, None, Some(message_id))?;
+            return Ok(MessageState::Delivered);
+        }
+        
+        let (mut screen, forward, backend, inputs, data) = {
+            let mut ussd_sessions = self.ussd_sessions.shard(&msisdn);
+            let session = ussd_sessions.entry(msisdn.clone()).or_insert_with(|| {
+                UssdSession {
+                    msisdn: msisdn.clone(),
+                    session_id: self.generate_session_id(),
+                    state: UssdState::Initial,
+                    menu_level: 0,
+                    last_request: String::new(),
+                    inputs: Vec::new(),
+                    service_code: ussd_code.clone(),
+                    last_message: message.clone(),
+                    forward_route: None,
+                    pages: Vec::new(),
+                    last_activity: Instant::now(),
+                }
+            });
+            session.last_activity = Instant::now();
+            session.last_message = message;
+            session.pages.clear(); // Any other reply leaves the unread pages behind
+            
+            // Check if this is a new USSD code (starts with * and ends with #) that should reset the session
+            if ussd_code.starts_with('*') && ussd_code.ends_with('#') {
+                session.state = UssdState::Initial;
+                session.menu_level = 0;
+                session.last_request = String::new();
+                session.inputs.clear();
+                session.service_code = ussd_code.clone();
+            }
+            session.inputs.push(ussd_code.clone());
+            
+            subsystem_log!(self.log_levels, Subsystem::Sessions, LogLevel::Debug,
+                "🗂️  Session {} for {}: state={:?}, menu_level={}",
+                session.session_id, session.msisdn, session.state, session.menu_level);
+
+            let follow_up = matches!(session.state, UssdState::Forwarded);
+            // Reported before the screen is built, so a request always precedes its response
+            self.connection_manager.webhooks.request(&msisdn, &session.session_id, &session.service_code, &ussd_code);
+            self.state_store.set_message_session(message_id, &session.session_id, &session.service_code);
+            let screen = self.generate_ussd_response(session, &ussd_code);
+            let forward = matches!(session.state, UssdState::Forwarded).then(|| {
+                (session.session_id.clone(), session.last_message.clone(), session.forward_route.clone(), follow_up)
+            });
+            let backend = matches!(session.state, UssdState::HttpBackend).then(|| BackendRequest {
+                msisdn: session.msisdn.clone(),
+                input: ussd_code.clone(),
+                session_id: session.session_id.clone(),
+                service_code: session.service_code.clone(),
+            });
+            let data = match &session.state {
+                UssdState::Tree(position) => position.data.clone(),
+                _ => BTreeMap::new(),
+            };
+            (screen, forward, backend, session.inputs.clone(), data)
+        };
+        
+        // The backend can take up to its timeout to answer, so it is asked outside the lock too
+        if let Some(request) = backend {
+            screen = self.backend_screen(&request);
+        }
+        
+        // Queues can block when full, so nothing is pushed while the shard is locked
+        let mut state = MessageState::Delivered;
+        if let Some((session_id, message, route, follow_up)) = forward {
+            match self.forward_to_bound_client(&session_id, &message, &ussd_code, route.as_deref()) {
+                Ok(()) if follow_up => info!("Forwarded follow-up USSD request {} to bound client", ussd_code),
+                Ok(()) => info!("Forwarded USSD code {} to bound client", ussd_code),
+                Err(e) => {
+                    info!("Failed to forward USSD request {} to bound client: {}", ussd_code, e);
+                    state = MessageState::Undeliverable;
+                    self.ussd_sessions.update(&msisdn, |session| {
+                        if session.session_id == session_id {
+                            session.state = UssdState::Terminated;
+                        }
+                    });
+                    screen = self.response_screen(if follow_up {
+                        "Service temporarily unavailable. Thank you!".to_string()
+                    } else {
+                        self.config.ussd.responses.invalid_code.clone()
+                    }, None);
+                }
+            }
+        }
+        
+        screen.text = self.config.variables(&msisdn, &inputs).with_session_data(&data).apply(&screen.text);
+        
+        // Send DELIVER_SM with USSD response only if we have a response
+        if !screen.text.is_empty() {
+            thread::sleep(Duration::from_millis(self.config.response_percentage.response_delay_ms));
+            self.inject_latency(&self.service_code_for(&msisdn, &ussd_code), LatencyStage::DeliverSm);
+            self.send_ussd_response(&msisdn, &screen, submit_sm.priority_flag, None, Some(message_id))?;
+            if screen.service_op == Some(USSD_NOTIFY) {
+                self.end_notified_session(&msisdn);
+            }
+        } else {
+            info!("No immediate response to send - waiting for forwarded response via DELIVER_SM");
+        }
+        
+        Ok(state)
+    }
+
+    // A notification awaits no reply, so the session is closed once it has been sent rather than
+    // left for the timeout sweeper
+    fn end_notified_session(&self, msisdn: &str) {
+        if let Some(session) = self.ussd_sessions.remove(msisdn) {
+            subsystem_log!(self.log_levels, Subsystem::Sessions, LogLevel::Debug,
+                "🗂️  Session {} for {} ended by USSD_NOTIFY", session.session_id, msisdn);
+            self.connection_manager.forget_origin(msisdn);
+        }
+    }
+
+    // Fits the screen to the subscriber's client before sending it. A screen awaiting a reply in
+    // an open session is paginated; the pages after the first wait in the session.
+    // `message_id` tags the queued DELIVER_SM for CANCEL_SM and REPLACE_SM
+    fn send_ussd_response(
+        &mut self,
+        msisdn: &str,
+        screen: &UssdScreen,
+        priority_flag: u8,
+        expiry: Option<Expiry>,
+        message_id: Option<&str>,
+    ) -> Result<(), SmppError> {
+        let text = self.config.compression.apply(&screen.text);
+        let screen_chars = self.connection_manager.screen_chars(&self.sessions, msisdn, &self.config.screens);
+        let paged = screen.service_op.is_none()
+            && self.ussd_sessions.read(msisdn, |session| !matches!(session.state, UssdState::Terminated)).unwrap_or(false);
+        if !paged {
+            let text = self.config.screens.truncate(&text, screen_chars);
+            return self.deliver_screen(msisdn, &UssdScreen { text, ..screen.clone() }, priority_flag, expiry, message_id);
+        }
+
+        let mut pages = self.config.screens.pages(&text, screen_chars)
+            .into_iter()
+            .map(|text| UssdScreen { text, ..screen.clone() });
+        let first = pages.next().unwrap_or_else(|| screen.clone());
+        let rest: Vec<UssdScreen> = pages.collect();
+        if !rest.is_empty() {
+            subsystem_log!(self.log_levels, Subsystem::Sessions, LogLevel::Debug,
+                "🗂️  Screen for {} split into {} pages for a {}-character client",
+                msisdn, rest.len() + 1, screen_chars.unwrap_or_default());
+        }
+        self.ussd_sessions.update(msisdn, |session| session.pages = rest);
+        self.deliver_screen(msisdn, &first, priority_flag, expiry, message_id)
+    }
+
+    // The next page of a screen that did not fit, when the subscriber replies with the more option
+    fn take_next_page(&self, msisdn: &str, request: &str) -> Option<UssdScreen> {
+        if request != self.config.screens.more_option {
+            return None;
+        }
+        self.ussd_sessions
+            .update(msisdn, |session| {
+                if session.pages.is_empty() {
+                    return None;
+                }
+                session.last_activity = Instant::now();
+                Some(session.pages.remove(0))
+            })
+            .flatten()
+    }
+
+    fn deliver_screen(
+        &mut self,
+        msisdn: &str,
+        screen: &UssdScreen,
+        priority_flag: u8,
+        expiry: Option<Expiry>,
+        message_id: Option<&str>,
+    ) -> Result<(), SmppError> {
+        let response_text = &screen.text;
+        subsystem_log!(self.log_levels, Subsystem::Codec, LogLevel::Trace,
+            "🔤 Response text ({} bytes): {:?}", response_text.len(), response_text);
+        if screen.encoding.data_coding(response_text) == encoding::DATA_CODING_UCS2 {
+            subsystem_log!(self.log_levels, Subsystem::Codec, LogLevel::Debug, "🔤 Sending as UCS-2 (data_coding 0x08)");
+        } else if !gsm7::is_representable(response_text) {
+            subsystem_log!(self.log_levels, Subsystem::Codec, LogLevel::Warn,
+                "🔤 Characters outside the GSM 7-bit alphabet will be sent as '?'");
+        }
+        // Every screen tells the subscriber whether to answer: one that closes the session goes
+        // as a PSSR response, any other as a USSR request
+        let service_op = screen.service_op.unwrap_or_else(|| {
+            match self.ussd_sessions.read(msisdn, |session| matches!(session.state, UssdState::Terminated)) {
+                Some(false) => USSD_USSR_REQUEST,
+                _ => USSD_PSSR_RESPONSE,
+            }
+        });
+        let deliver_sm = build_ussd_deliver_sm(
+            msisdn,
+            response_text,
+            priority_flag,
+            self.get_next_sequence(),
+            Some(service_op),
+            screen.encoding,
+            &self.config,
+        );
+        let body_len = deliver_sm.body.len();
+
+        // Send response to the user simulator bind that originated this MSISDN (not forwarding client)
+        if let Some(user_queue) = self.connection_manager.get_msisdn_connection(&self.sessions, msisdn, &self.config.client_simulator.user_clients) {
+            info!("📤 Sending DELIVER_SM to user simulator");
+            // Recorded before queueing, since the subscriber may answer before the push returns
+            self.connection_manager.transcripts.response(msisdn, response_text, screen.service_op);
+            self.connection_manager.webhooks.response(msisdn, response_text, screen.service_op);
+            // Kept aside in case the push fails and the screen has to be held for a retry
+            let retry_copy = self.connection_manager.journal.enabled().then(|| deliver_sm.clone());
+            if let Err(e) = user_queue.push_expiring(priority_flag, deliver_sm, expiry, message_id) {
+                info!("⚠️  Error sending to user simulator: {}", e);
+                if retry_copy.is_some_and(|pdu| self.connection_manager.journal.hold(msisdn, priority_flag, &pdu)) {
+                    return Ok(());
+                }
+                return Err(SmppError::Routing(format!("screen for {} not queued: {}", msisdn, e)));
+            }
+            subsystem_log!(self.log_levels, Subsystem::Routing, LogLevel::Debug,
+                "📦 DELIVER_SM queued for user simulator with command_id: 0x{:08x}, body_length: {}, priority: {}",
+                DELIVER_SM, body_len, priority_flag);
+        } else {
+            subsystem_log!(self.log_levels, Subsystem::Routing, LogLevel::Warn,
+                "⚠️  No user connection found for user simulator");
+            if self.connection_manager.journal.hold(msisdn, priority_flag, &deliver_sm) {
+                self.connection_manager.transcripts.response(msisdn, response_text, screen.service_op);
+                self.connection_manager.webhooks.response(msisdn, response_text, screen.service_op);
+                return Ok(());
+            }
+            return Err(SmppError::Routing(format!("no user connection for {}", msisdn)));
+        }
+        info!("USSD response sent to {}: {}", msisdn, response_text);
+        
+        Ok(())
+    }
+
+    // Answers a request that failed before a handler answered it, keeping the connection, as the
+    // framing is still intact. A body that doesn't parse or an unknown command gets GENERIC_NACK;
+    // anything else gets the request's own header-only response, e.g. SUBMIT_SM_RESP.
+    fn reject(&mut self, request: SmppHeader, error: &SmppError) -> std::io::Result<()> {
+        if request.command_id & RESPONSE_BIT != 0 {
+            info!("⚠️  Response 0x{:08x} seq {} not handled: {}", request.command_id, request.sequence_number, error);
+            return Ok(());
+        }
+        let status = error.command_status();
+        let command_id = if matches!(error, SmppError::Encoding(_)) || status == ESME_RINVCMDID {
+            GENERIC_NACK
+        } else {
+            request.command_id | RESPONSE_BIT
+        };
+        info!("❌ Rejecting 0x{:08x} seq {} with 0x{:08x} status 0x{:08X}: {}",
+            request.command_id, request.sequence_number, command_id, status, error);
+        self.send_pdu(SmppPdu::new(command_id, status, request.sequence_number, Bytes::new()))?;
+        if command_id == GENERIC_NACK {
+            self.count_malformed();
+        }
+        Ok(())
+    }
+
+    // The stream can't be resynchronised after a bad command_length, so the peer gets a
+    // GENERIC_NACK for it before the connection closes. The writer sends what is queued before
+    // it lets go of the socket.
+    fn nack_bad_frame(&mut self) {
+        let Some(header) = self.read_buffer.buffered_header() else {
+            return;
+        };
+        info!("❌ Closing connection {} after command_length {} (limit {})",
+            self.connection_id, header.command_length, self.config.smpp.max_pdu_len);
+        let nack = SmppPdu::new(GENERIC_NACK, ESME_RINVCMDLEN, header.sequence_number, Bytes::new());
+        let _ = self.send_pdu(nack);
+        self.count_malformed();
+    }
+
+    // A peer that reaches [server.flood_protection] ban_after_malformed is banned and this
+    // connection dropped
+    fn count_malformed(&mut self) {
+        let Some(ip) = self.peer_ip else {
+            return;
+        };
+        let flood_protection = &self.config.server.flood_protection;
+        if self.connection_manager.flood.malformed(flood_protection, ip) {
+            info!("🚫 Banning {} for {}s after {} malformed PDUs", ip, flood_protection.ban_secs, flood_protection.ban_after_malformed);
+            let _ = self.stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    fn send_submit_sm_resp_error(&mut self, sequence_number: u32, error_code: u32) -> std::io::Result<()> {
+        let response = SmppPdu::new(SUBMIT_SM_RESP, error_code, sequence_number, Bytes::new());
+        
+        self.send_pdu(response)?;
+        info!("SUBMIT_SM_RESP sent with error code: 0x{:08X}", error_code);
+        Ok(())
+    }
+
+    fn parse_bind_request(&self, body: &[u8]) -> (String, String, Option<usize>) {
+        match Bind::decode(body) {
+            Ok(bind) => {
+                let declared_chars = declared_screen_chars(&bind);
+                (bind.system_id.into_owned(), bind.password.into_owned(), declared_chars)
+            }
+            // A bind cut short after the password still names the client
+            Err(_) => {
+                let mut reader = PduReader::new(body);
+                (reader.c_str().into_owned(), reader.c_str().into_owned(), None)
+            }
+        }
+    }
+
+    fn create_bind_response(&self, command_id: u32, status: u32, sequence: u32) -> SmppPdu {
+        let system_id = format!("{}\0", self.config.smpp.system_id);
+        let body = system_id.as_bytes().to_vec();
+        
+        SmppPdu::new(command_id, status, sequence, body)
+    }
+
+    // Reports a message_id from the registry. Only the bind that submitted it, or one of the same
+    // system_id, may ask, and a source_addr given must be the MSISDN it came from.
+    fn handle_query_sm(&mut self, pdu: SmppPdu) -> Result<(), SmppError> {
+        if !self.bound_session_allows(Session::can_transmit) {
+            return Err(SmppError::protocol(ESME_RINVBNDSTS, "connection is not bound as transmitter or transceiver"));
+        }
+        let query = QuerySm::decode(&pdu.body)?;
+        let Some(record) = self.message_record(&query.message_id, &query.source_addr) else {
+            return Err(SmppError::protocol(ESME_RQUERYFAIL, "unknown message_id"));
+        };
+        
+        info!("QUERY_SM for {}: {:?}", record.message_id, record.state);
+        let resp = QuerySmResp {
+            message_id: record.message_id.as_str().into(),
+            final_date: record.final_at.map(|secs| absolute_time(UNIX_EPOCH + Duration::from_secs(secs))).unwrap_or_default().into(),
+            message_state: record.state.code(),
+            error_code: 0,
+        };
+        self.send_pdu(SmppPdu::new(QUERY_SM_RESP, ESME_ROK, pdu.header.sequence_number, resp.encode()))?;
+        Ok(())
+    }
+
+    // Removes the DELIVER_SM queued for a message this ESME submitted, if it is still waiting
+    fn handle_cancel_sm(&mut self, pdu: SmppPdu) -> Result<(), SmppError> {
+        if !self.bound_session_allows(Session::can_transmit) {
+            return Err(SmppError::protocol(ESME_RINVBNDSTS, "connection is not bound as transmitter or transceiver"));
+        }
+        let cancel = CancelSm::decode(&pdu.body)?;
+        let record = self.message_record(&cancel.message_id, &cancel.source_addr).filter(|record| {
+            cancel.destination_addr.is_empty() || cancel.destination_addr == record.msisdn
+        });
+        let Some(record) = record else {
+            return Err(SmppError::protocol(ESME_RCANCELFAIL, "unknown message_id"));
+        };
+        if !self.connection_manager.cancel_queued(&record.message_id) {
+            return Err(SmppError::protocol(ESME_RCANCELFAIL, "message is no longer queued"));
+        }
+        
+        info!("🗑️  CANCEL_SM removed the queued DELIVER_SM for {}", record.message_id);
+        self.state_store.set_message_state(&record.message_id, MessageState::Deleted);
+        self.send_pdu(pdu.ok_response())?;
+        Ok(())
+    }
+
+    // Swaps the text of the DELIVER_SM queued for a message this ESME submitted
+    fn handle_replace_sm(&mut self, pdu: SmppPdu) -> Result<(), SmppError> {
+        if !self.bound_session_allows(Session::can_transmit) {
+            return Err(SmppError::protocol(ESME_RINVBNDSTS, "connection is not bound as transmitter or transceiver"));
+        }
+        let replace = ReplaceSm::decode(&pdu.body)?;
+        let Some(record) = self.message_record(&replace.message_id, &replace.source_addr) else {
+            return Err(SmppError::protocol(ESME_RREPLACEFAIL, "unknown message_id"));
+        };
+        let replaced = self.connection_manager.replace_queued(&record.message_id, |queued| {
+            let mut deliver_sm = DeliverSm::decode(&queued.body).ok()?;
+            deliver_sm.short_message = replace.short_message.clone();
+            deliver_sm.optional_params.retain(|param| param.tag != TAG_MESSAGE_PAYLOAD);
+            let header = &queued.header;
+            Some(SmppPdu::new(header.command_id, header.command_status, header.sequence_number, deliver_sm.encode()))
+        });
+        if !replaced {
+            return Err(SmppError::protocol(ESME_RREPLACEFAIL, "message is no longer queued"));
+        }
+        
+        info!("✏️  REPLACE_SM updated the queued DELIVER_SM for {}", record.message_id);
+        self.send_pdu(pdu.ok_response())?;
+        Ok(())
+    }
+
+    // The registry entry for `message_id` if this bind submitted it; a non-empty `msisdn` must
+    // also match the subscriber it was for
+    fn message_record(&self, message_id: &str, msisdn: &str) -> Option<MessageIdRecord> {
+        let system_id = self.bound_system_id().unwrap_or_default();
+        self.state_store
+            .lookup_message_id(message_id)
+            .filter(|record| record.system_id == system_id && (msisdn.is_empty() || msisdn == record.msisdn))
+    }
+
+    fn handle_deliver_sm_resp(&mut self, _pdu: SmppPdu) -> Result<(), SmppError> {
+        info!("Received DELIVER_SM_RESP");
+        Ok(())
+    }
+
+    fn handle_submit_sm_resp(&mut self, pdu: SmppPdu) -> Result<(), SmppError> {
+        info!("Received SUBMIT_SM_RESP from client");
+        
+        subsystem_log!(self.log_levels, Subsystem::Codec, LogLevel::Debug,
+            "📨 SUBMIT_SM_RESP: cmd=0x{:08x}, status=0x{:08x}, seq={}",
+            pdu.header.command_id, pdu.header.command_status, pdu.header.sequence_number);
+        
+        if pdu.header.command_status == ESME_ROK {
+            // Extract message_id from body if present
+            if !pdu.body.is_empty() {
+                let message_id = PduReader::new(&pdu.body).c_str();
+                info!("SUBMIT_SM_RESP received with message_id: {}", message_id);
+            } else {
+                info!("SUBMIT_SM_RESP received successfully");
+            }
+        } else {
+            info!("SUBMIT_SM_RESP received with error status: 0x{:08x}", pdu.header.command_status);
+            if self.connection_manager.pending.reject(pdu.header.sequence_number, pdu.header.command_status) {
+                info!("Forwarded request seq={} rejected by client", pdu.header.sequence_number);
+            }
+        }
+        
+        Ok(())
+    }
+
+    fn handle_deliver_sm(&mut self, pdu: SmppPdu) -> Result<(), SmppError> {
+        info!("Received DELIVER_SM from client");
+        
+        // Forwarding clients answer via DELIVER_SM, which is only valid on a bind that can transmit
+        if !self.bound_session_allows(Session::can_transmit) {
+            return Err(SmppError::protocol(ESME_RINVBNDSTS, "connection is not bound as transmitter or transceiver"));
+        }
+        
+        subsystem_log!(self.log_levels, Subsystem::Forwarding, LogLevel::Debug, "📨 DELIVER_SM: cmd=0x{:08x}, body_len={}",
+            pdu.header.command_id, pdu.body.len());
+        
+        // Parse the DELIVER_SM to extract the menu response
+        let deliver_sm = DeliverSm::decode(&pdu.body)?;
+        self.check_addressing(&deliver_sm)?;
+        
+        subsystem_log!(self.log_levels, Subsystem::Forwarding, LogLevel::Debug,
+            "📨 DELIVER_SM parsed - source: {}, dest: {}, message: {:?}",
+            deliver_sm.source_addr, deliver_sm.destination_addr,
+            deliver_sm_text(&deliver_sm));
+        
+        // Send DELIVER_SM_RESP to acknowledge receipt from client
+        let response = pdu.ok_response();
+        
+        self.send_pdu(response)?;
+        info!("DELIVER_SM_RESP sent to client");
+        
+        // A reply to a forwarded request goes to that request's subscriber. Anything else (a late
+        // reply or a network-initiated push) is delivered to the destination MSISDN's route.
+        let reference = deliver_sm.user_message_reference();
+        let request = self.connection_manager.pending.resolve(reference, &deliver_sm.destination_addr);
+        let msisdn: &str = match &request {
+            Some(request) => {
+                subsystem_log!(self.log_levels, Subsystem::Forwarding, LogLevel::Debug,
+                    "🔗 DELIVER_SM matches forwarded seq={} for {} (session {}) after {}ms",
+                    request.sequence_number, request.msisdn, request.session_id, request.forwarded_at.elapsed().as_millis());
+                &request.msisdn
+            }
+            None => {
+                subsystem_log!(self.log_levels, Subsystem::Forwarding, LogLevel::Info,
+                    "📨 Unsolicited DELIVER_SM for {} (reference {:?}): delivering by MSISDN route",
+                    deliver_sm.destination_addr, reference);
+                &deliver_sm.destination_addr
+            }
+        };
+        
+        // This DELIVER_SM contains the actual menu response from the client
+        // We need to forward this response back to the user simulator
+        let menu_response = deliver_sm_text(&deliver_sm);
+        
+        info!("Received menu response from client: {}", menu_response);
+        info!("Forwarding this response to user simulator via DELIVER_SM");
+        
+        // Keep the priority the subscriber asked for unless the client raised it; a reply
+        // from the application also counts as activity on the subscriber's session
+        self.ussd_sessions.update(msisdn, |session| session.last_activity = Instant::now());
+        let priority_flag = request.as_ref().map_or(0, |request| request.priority_flag).max(deliver_sm.priority_flag);
+        
+        // An EXPIRED receipt for the client's own message goes back over this connection
+        let message = MessageContext::new(
+            self.generate_message_id(msisdn),
+            &deliver_sm.source_addr,
+            msisdn,
+            priority_flag,
+            deliver_sm.registered_delivery,
+            &deliver_sm.validity_period,
+        );
+        let own_queue = self.connection_manager.get_connection(&self.connection_id);
+        let expiry = self.expiry_for(&message, &menu_response, || own_queue);
+        
+        // A client marks a closing screen with ussd_service_op USSD_NOTIFY or USSD_PSSR_RESPONSE;
+        // it is passed on as such and ends the subscriber's session. The reply keeps the client's
+        // data_coding.
+        let screen = UssdScreen {
+            text: menu_response,
+            service_op: deliver_sm.ussd_service_op().filter(|&op| matches!(op, USSD_NOTIFY | USSD_PSSR_RESPONSE)),
+            encoding: TextEncoding::from_data_coding(deliver_sm.data_coding),
+        };
+        
+        // Send the menu response to the user simulator via DELIVER_SM; an undeliverable
+        // response must not tear down the forwarding client's connection
+        let service_code = self.ussd_sessions.read(msisdn, |session| session.service_code.clone()).unwrap_or_default();
+        self.inject_latency(&service_code, LatencyStage::DeliverSm);
+        match self.send_ussd_response(msisdn, &screen, priority_flag, expiry, None) {
+            Ok(()) => info!("Menu response forwarded to user simulator"),
+            Err(e) => info!("⚠️  Menu response for {} not delivered: {}", msisdn, e),
+        }
+        if screen.service_op.is_some() {
+            self.end_notified_session(msisdn);
+        }
+        
+        Ok(())
+    }
+
+    fn handle_enquire_link(&mut self, pdu: SmppPdu) -> Result<(), SmppError> {
+        info!("Received ENQUIRE_LINK");
+        
+        let response = pdu.ok_response();
+        
+        self.send_pdu(response)?;
+        Ok(())
+    }
+
+    fn handle_unbind(&mut self, pdu: SmppPdu) -> Result<(), SmppError> {
+        info!("Received UNBIND");
+        
+        let response = pdu.ok_response();
+        
+        self.send_pdu(response)?;
+        Ok(())
+    }
+
+    fn send_pdu(&mut self, pdu: SmppPdu) -> std::io::Result<()> {
+        subsystem_log!(self.log_levels, Subsystem::Codec, LogLevel::Debug, "📤 Sending PDU: cmd=0x{:08x}, len={}, body_len={}",
+            pdu.header.command_id, pdu.header.command_length, pdu.body.len());
+        if !pdu.body.is_empty() {
+            subsystem_log!(self.log_levels, Subsystem::Codec, LogLevel::Trace, "📤 PDU body: {:?}", pdu.body);
+            subsystem_log!(self.log_levels, Subsystem::Codec, LogLevel::Trace,
+                "📤 PDU body as string: {:?}", String::from_utf8_lossy(&pdu.body));
+        }
+        subsystem_log!(self.log_levels, Subsystem::Codec, LogLevel::Trace, "📤 Full PDU buffer: {:02x?}", pdu.to_bytes());
+        
+        // Responses share the connection's writer with forwarded traffic so PDUs never interleave
+        let Some(queue) = self.connection_manager.get_connection(&self.connection_id) else {
+            return Err(std::io::Error::new(std::io::ErrorKind::NotConnected, "Connection closed"));
+        };
+        let header = pdu.header;
+        queue
+            .push(PRIORITY_LEVELS as u8 - 1, pdu)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::BrokenPipe, e))?;
+        if matches!(header.command_id, SUBMIT_SM_RESP | GENERIC_NACK) {
+            self.connection_manager.windows.answered(&self.connection_id, header.sequence_number);
+        }
+        Ok(())
+    }
+
+    fn generate_message_id(&self, msisdn: &str) -> String {
+        let system_id = self.bound_system_id().unwrap_or_default();
+        self.state_store.issue_message_id(&system_id, msisdn)
+    }
+
+    // The counter keeps sessions opened in the same second apart, across every connection
+    fn generate_session_id(&self) -> String {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let counter = NEXT_SESSION.fetch_add(1, Ordering::Relaxed);
+        run_id::stamp(format!("SESS{}{:04}", timestamp, counter))
+    }
+
+    // Fault rates are set per service code; menu replies inherit the code that opened their session
+    fn service_code_for(&self, msisdn: &str, text: &str) -> String {
+        if text.starts_with('*') && text.ends_with('#') {
+            return text.to_string();
+        }
+        self.ussd_sessions
+            .read(msisdn, |session| session.service_code.clone())
+            .unwrap_or_else(|| text.to_string())
+    }
+
+    // Simulated gateway latency on the code's route before the PDU goes out
+    fn inject_latency(&self, service_code: &str, stage: LatencyStage) {
+        let Some(delay) = self.config.latency.delay_for(service_code, stage) else {
+            return;
+        };
+        let pause = self.connection_manager.faults.with_rng(|rng| delay.sample(rng));
+        subsystem_log!(self.log_levels, Subsystem::Chaos, LogLevel::Debug,
+            "🐢 Holding {:?} for {} by {}ms", stage, service_code, pause.as_millis());
+        thread::sleep(pause);
+    }
+
+    fn determine_response_type(&self, service_code: &str) -> ResponseType {
+        let random_value = self.connection_manager.faults.roll();
+        
+        let system_id = self.bound_system_id();
+        let (configured, error_code) = self.config.response_percentage.profile_for(service_code, system_id.as_deref());
+        let rates = self.connection_manager.faults.rates_for(service_code, configured);
+        let success_threshold = rates.success;
+        let failure_threshold = success_threshold + rates.failure;
+        
+        subsystem_log!(self.log_levels, Subsystem::Chaos, LogLevel::Debug,
+            "🎲 Response roll: {:.2} (success < {:.2}, failure < {:.2})",
+            random_value, success_threshold, failure_threshold);
+
+        if random_value < success_threshold {
+            ResponseType::Success
+        } else if random_value < failure_threshold {
+            ResponseType::Failure(error_code)
+        } else {
+            ResponseType::NoResponse
+        }
+    }
+
+    fn get_next_sequence(&self) -> u32 {
+        self.state_store.next_sequence()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ResponseType {
+    Success,
+    Failure(u32), // SUBMIT_SM_RESP command_status
+    NoResponse,
+}
+
+impl UssdConnectionHandler {
+    fn forward_to_bound_client(&self, session_id: &str, message: &MessageContext, ussd_code: &str, route: Option<&str>) -> Result<(), SmppError> {
+        let msisdn = message.originator.as_str();
+        
+        // Find the routed client, or any bound client that can receive forwards
+        let forward_queue = match route {
+            Some(system_id) => self.connection_manager.get_routed_connection(&self.sessions, system_id),
+            None => self.connection_manager.get_forwarding_connection(&self.sessions, &self.config.client_simulator.forwarding_clients),
+        };
+        if let Some(forward_queue) = forward_queue {
+            let sequence_number = self.get_next_sequence();
+            let pending = PendingRequest::new(
+                sequence_number,
+                msisdn,
+                session_id,
+                message.priority_flag,
+                Duration::from_secs(self.config.ussd.forward_timeout),
+            );
+            let reference = self.connection_manager.pending.insert(pending);
+            
+            // Clients echo the reference in their DELIVER_SM so the reply finds the original request
+            let submit_sm = build_ussd_submit_sm(msisdn, "FORWARD", ussd_code, message.priority_flag, sequence_number, Some(reference));
+            
+            subsystem_log!(self.log_levels, Subsystem::Forwarding, LogLevel::Debug,
+                "📨 Forwarding SUBMIT_SM seq={} ref={} for {}: {:?}",
+                sequence_number, reference, msisdn, ussd_code);
+
+            // Queue for the client's writer thread; the subscriber gets any EXPIRED receipt.
+            // A forward that never got queued must not also fail later on the expiry sweep.
+            let expiry = self.expiry_for(message, ussd_code, || {
+                self.connection_manager.get_msisdn_connection(&self.sessions, msisdn, &self.config.client_simulator.user_clients)
+            });
+            if let Err(e) = forward_queue.push_expiring(message.priority_flag, submit_sm, expiry, None) {
+                self.connection_manager.pending.remove(sequence_number);
+                return Err(SmppError::Routing(format!("forward for {} not queued: {}", msisdn, e)));
+            }
+            
+            info!("Forwarded USSD request {} to bound client", ussd_code);
+            
+            // The real response will come via DELIVER_SM
+            Ok(())
+        } else if let Some(system_id) = route {
+            Err(SmppError::Routing(format!("no bound client for route {}", system_id)))
+        } else {
+            Err(SmppError::Routing("no bound forwarding client available".to_string()))
+        }
+    }
+    
+    // The `[http_backend]` answer to one request. A reply that does not continue, or a failed
+    // request, closes the session with USSD_NOTIFY.
+    fn backend_screen(&self, request: &BackendRequest) -> UssdScreen {
+        let backend = &self.config.http_backend;
+        let screen = match backend.call(request) {
+            Ok(reply) if reply.continues => return self.response_screen(reply.text, None),
+            Ok(reply) => self.response_screen(reply.text, Some(USSD_NOTIFY)),
+            Err(e) => {
+                info!("🌐 HTTP backend failed for {}: {}", request.msisdn, e);
+                self.response_screen(backend.error_message.clone(), Some(USSD_NOTIFY))
+            }
+        };
+        self.ussd_sessions.update(&request.msisdn, |session| {
+            if session.session_id == request.session_id {
+                session.state = UssdState::Terminated;
+            }
+        });
+        screen
+    }
+    
+    fn expiry_for(&self, message: &MessageContext, text: &str, receipt_target: impl FnOnce() -> Option<Arc<OutboundQueue>>) -> Option<Expiry> {
+        let expires_at = message.expires_at?;
+        let receipt = if message.wants_failure_receipt() {
+            receipt_target().map(|target| (self.create_expired_receipt(message, text), target))
+        } else {
+            None
+        };
+        Some(Expiry { expires_at, receipt })
+    }
+    
+    fn create_expired_receipt(&self, message: &MessageContext, text: &str) -> SmppPdu {
+        let done_at = message.expires_at.unwrap_or_else(SystemTime::now);
+        build_receipt(message, ReceiptStat::Expired, done_at, text, self.get_next_sequence())
+    }
+    
+    // Queues the delivery receipt a SUBMIT_SM asked for; it goes out delivery_receipts.delay_ms
+    // after the SUBMIT_SM_RESP
+    fn schedule_delivery_receipt(&self, submit_sm: &SubmitSm, message_id: &str, outcome: MessageState) {
+        let receipts = &self.config.delivery_receipts;
+        if !receipts.enabled || submit_sm.registered_delivery & 0x03 == 0 {
+            return;
+        }
+        let stat = match outcome {
+            MessageState::Undeliverable => ReceiptStat::Undeliverable,
+            _ => receipts.pick(self.connection_manager.faults.roll()),
+        };
+        if !stat.requested_by(submit_sm.registered_delivery) {
+            return;
+        }
+        
+        let message = MessageContext::new(
+            message_id.to_string(),
+            &submit_sm.source_addr,
+            &submit_sm.destination_addr,
+            submit_sm.priority_flag,
+            submit_sm.registered_delivery,
+            &submit_sm.validity_period,
+        );
+        let delay = Duration::from_millis(receipts.delay_ms);
+        let text = message_text(submit_sm.data_coding, submit_sm.message(), self.config.smpp.gsm7_packing);
+        let pdu = build_receipt(&message, stat, message.submitted_at + delay, &text, self.get_next_sequence());
+        self.connection_manager.receipts.schedule(ScheduledReceipt {
+            due: Instant::now() + delay,
+            message_id: message.message_id,
+            system_id: self.bound_system_id().unwrap_or_default(),
+            priority_flag: submit_sm.priority_flag,
+            stat,
+            pdu,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+
+    use crate::admin::SessionControl;
+    use crate::codec::{PduReadBuffer, HEADER_LEN};
+    use crate::config::{Config, ResponseOverride};
+    use crate::demo::{DemoClient, DEMO_USER_CLIENT};
+    use crate::listeners::ListenerConfig;
+    use crate::pdu::{
+        build_ussd_submit_sm, deliver_sm_text, DeliverSm, Outbind, SmppPdu, SubmitSmResp, BIND_RECEIVER,
+        BIND_RECEIVER_RESP, BIND_TRANSCEIVER, BIND_TRANSCEIVER_RESP, CANCEL_SM_RESP, ESME_RBINDFAIL, ESME_RINVPRTFLG,
+        ESME_RMSGQFUL, ESME_ROK, MESSAGE_STATE_DELIVERED, OUTBIND, REPLACE_SM_RESP, SUBMIT_SM_RESP, TAG_SCREEN_CHARS,
+        USSD_NOTIFY, USSD_PSSR_RESPONSE, USSD_USSR_REQUEST,
+    };
+    use crate::push;
+    use crate::session::Session;
+
+    // What most tests run with: Config::deterministic() with DEMO_USER_CLIENT recognised as a
+    // phone. `configure` adjusts it.
+    fn test_config(configure: impl FnOnce(&mut Config)) -> Config {
+        let mut config = Config::deterministic();
+        config.client_simulator.user_clients = vec![DEMO_USER_CLIENT.to_string()];
+        configure(&mut config);
+        config
+    }
+
+    // A handler for one connection over an in-process channel, registered with the server so
+    // queued PDUs reach it too, and the phone's end of the channel
+    fn test_handler(configure: impl FnOnce(&mut Config)) -> (UssdSmppServer, UssdConnectionHandler, SmppStream) {
+        let server = UssdSmppServer::new(test_config(configure));
+        let (stream, phone) = transport::channel_pair();
+        let handler = UssdConnectionHandler::new(
+            stream,
+            Arc::clone(&server.sessions),
+            Arc::clone(&server.ussd_sessions),
+            Arc::clone(&server.state_store),
+            Arc::clone(&server.config),
+            server.connection_manager.clone(),
+            Arc::clone(&server.log_levels),
+        );
+        let queue_stream = handler.stream.try_clone().unwrap();
+        server.connection_manager.add_connection(handler.connection_id.clone(), Arc::new(Mutex::new(queue_stream)));
+        (server, handler, phone)
+    }
+
+    // Runs the handler on its own thread and binds a phone over the other end of its channel
+    fn bind_phone(mut handler: UssdConnectionHandler, phone: SmppStream) -> DemoClient {
+        let config = Arc::clone(&handler.config);
+        thread::spawn(move || handler.handle(None));
+        DemoClient::bind(phone, &config, DEMO_USER_CLIENT, "mobile123").unwrap()
+    }
+
+    #[test]
+    fn test_builder_spawns_an_embedded_server() {
+        let config = test_config(|config| {
+            config.server.host = "127.0.0.1".to_string();
+            config.server.port = 0;
+        });
+        let embedded = UssdSmppServer::builder().config(config).spawn().unwrap();
+        assert_ne!(embedded.addr.port(), 0);
+
+        let config = embedded.server.config.get();
+        let stream = SmppStream::Tcp(std::net::TcpStream::connect(embedded.addr).unwrap());
+        let mut phone = DemoClient::bind(stream, &config, DEMO_USER_CLIENT, "mobile123").unwrap();
+        let screen = phone.ussd_request("111", "*123#").unwrap();
+        assert!(screen.starts_with(&config.ussd.menu.welcome_message), "{}", screen);
+        phone.unbind().unwrap();
+    }
+
+    #[test]
+    fn test_screens_say_whether_the_session_ends() {
+        let (server, handler, phone) = test_handler(|config| {
+            config.ussd.responses.notify_screens.clear(); // Goodbye as a plain screen
+        });
+
+        let config = server.config.get();
+        let mut phone = bind_phone(handler, phone);
+        let (_, service_op) = phone.ussd_exchange("111", "*123#").unwrap();
+        assert_eq!(service_op, Some(USSD_USSR_REQUEST));
+        let (screen, service_op) = phone.ussd_exchange("111", "0").unwrap();
+        assert_eq!(screen, config.ussd.responses.goodbye_message);
+        assert_eq!(service_op, Some(USSD_PSSR_RESPONSE));
+        phone.unbind().unwrap();
+    }
+
+    #[test]
+    fn test_dialled_keys_walk_into_the_menu() {
+        let (server, handler, phone) = test_handler(|_| {});
+
+        let config = server.config.get();
+        let mut phone = bind_phone(handler, phone);
+        let screen = phone.ussd_request("111", "*123*2#").unwrap();
+        assert!(screen.starts_with("Available Data Packages"), "{}", screen);
+        // The session carries on from the screen the keys led to
+        let screen = phone.ussd_request("111", "0").unwrap();
+        assert!(screen.starts_with(&config.ussd.menu.welcome_message), "{}", screen);
+        phone.unbind().unwrap();
+    }
+
+    #[test]
+    fn test_push_request_opens_session_and_notify_does_not() {
+        let server = UssdSmppServer::new(test_config(|_| {}));
+        let (stream, mut phone) = transport::channel_pair();
+        server.connection_manager.add_connection("conn_1".to_string(), Arc::new(Mutex::new(stream)));
+        server.sessions.insert("conn_1".to_string(), Session {
+            system_id: "USSDMobileUser".to_string(),
+            password: "mobile123".to_string(),
+            bound: true,
+            bind_type: BIND_TRANSCEIVER,
+            can_receive_forwards: false,
+            is_user_client: true,
+            connection_id: Some("conn_1".to_string()),
+            screen_chars: None,
+        });
+        let pusher = server.pusher();
+        let request = push::PushRequest {
+            msisdn: "111".to_string(),
+            text: "Rate our service 1-5".to_string(),
+            mode: push::PushMode::Request,
+            priority_flag: 0,
+        };
+
+        let receipt = pusher.push(&request).unwrap();
+        let mut buffer = PduReadBuffer::new();
+        let pdu = buffer.read_pdu(&mut phone).unwrap();
+        let deliver_sm = DeliverSm::decode(&pdu.body).unwrap();
+        assert_eq!(deliver_sm.ussd_service_op(), Some(USSD_USSR_REQUEST));
+        assert_eq!(deliver_sm_text(&deliver_sm), "Rate our service 1-5");
+        let session_id = server.ussd_sessions.read("111", |session| session.session_id.clone());
+        assert_eq!(session_id, receipt.session_id);
+
+        // One dialogue per subscriber; a notification needs no session
+        assert!(pusher.push(&request).is_err());
+        let notify = push::PushRequest { msisdn: "222".to_string(), mode: push::PushMode::Notify, ..request };
+        assert!(pusher.push(&notify).unwrap().session_id.is_none());
+        let pdu = buffer.read_pdu(&mut phone).unwrap();
+        assert_eq!(DeliverSm::decode(&pdu.body).unwrap().ussd_service_op(), Some(USSD_NOTIFY));
+        assert!(server.ussd_sessions.read("222", |_| ()).is_none());
+    }
+
+    #[test]
+    fn test_declared_screen_size_paginates_menu() {
+        let (server, mut handler, mut phone) = test_handler(|_| {});
+        let menu = format!("{}\n{}", handler.config.ussd.menu.welcome_message, handler.config.ussd.menu.main_menu.join("\n"));
+
+        // A 40-character client declares itself in the bind
+        let mut body = b"USSDMobileUser\0mobile123\0USSD\0\x34\x01\x01\0".to_vec();
+        body.extend_from_slice(&TAG_SCREEN_CHARS.to_be_bytes());
+        body.extend_from_slice(&2u16.to_be_bytes());
+        body.extend_from_slice(&40u16.to_be_bytes());
+        let bind = SmppPdu::new(BIND_TRANSCEIVER, ESME_ROK, 1, body);
+        handler.process_pdu(bind).unwrap();
+        let mut buffer = PduReadBuffer::new();
+        assert_eq!(buffer.read_pdu(&mut phone).unwrap().header.command_status, ESME_ROK);
+
+        let mut screens = Vec::new();
+        for (sequence_number, text) in [(2, "*123#"), (3, "99"), (4, "99"), (5, "99")] {
+            handler.process_pdu(build_ussd_submit_sm("111", "123", text, 0, sequence_number, None)).unwrap();
+            let mut pdu = buffer.read_pdu(&mut phone).unwrap();
+            if pdu.header.command_id == SUBMIT_SM_RESP {
+                pdu = buffer.read_pdu(&mut phone).unwrap();
+            }
+            screens.push(deliver_sm_text(&DeliverSm::decode(&pdu.body).unwrap()));
+            if !screens.last().unwrap().ends_with("99. More") {
+                break;
+            }
+        }
+
+        assert!(screens.len() > 1);
+        assert!(screens.iter().all(|screen| screen.chars().count() <= 40));
+        // Long lines may be split at a space, so compare word by word
+        let words: Vec<&str> = screens.iter()
+            .flat_map(|screen| screen.strip_suffix("\n99. More").unwrap_or(screen).split_whitespace())
+            .collect();
+        assert_eq!(words, menu.split_whitespace().collect::<Vec<_>>());
+        assert!(server.ussd_sessions.read("111", |session| session.pages.is_empty()).unwrap());
+    }
+
+    #[test]
+    fn test_session_ids_opened_in_the_same_second_differ() {
+        let (_server, handler, _phone) = test_handler(|_| {});
+        let (_other_server, other, _other_phone) = test_handler(|_| {});
+        let ids: std::collections::HashSet<String> =
+            (0..500).flat_map(|_| [handler.generate_session_id(), other.generate_session_id()]).collect();
+        assert_eq!(ids.len(), 1000);
+    }
+
+    #[test]
+    fn test_full_window_rejects_with_msgqful() {
+        let (server, mut handler, mut phone) = test_handler(|config| {
+            config.smpp.window_size = 2;
+            config.response_percentage.success_percentage = 0.0;
+            config.response_percentage.no_response_percentage = 100.0;
+            config.response_percentage.no_response_delay_ms = 0;
+            config.throttle.messages_per_second = 1.0;
+            config.throttle.burst = 3;
+        });
+
+        let body = b"LoadTester\0secret\0USSD\0\x34\x01\x01\0".to_vec();
+        let bind = SmppPdu::new(BIND_TRANSCEIVER, ESME_ROK, 1, body);
+        handler.process_pdu(bind).unwrap();
+        let mut buffer = PduReadBuffer::new();
+        assert_eq!(buffer.read_pdu(&mut phone).unwrap().header.command_status, ESME_ROK);
+
+        // Two dropped SUBMIT_SMs fill the window; the next is refused rather than dropped
+        for sequence_number in 2..=4 {
+            handler.process_pdu(build_ussd_submit_sm("111", "123", "*123#", 0, sequence_number, None)).unwrap();
+        }
+        let pdu = buffer.read_pdu(&mut phone).unwrap();
+        assert_eq!((pdu.header.command_id, pdu.header.command_status, pdu.header.sequence_number), (SUBMIT_SM_RESP, ESME_RMSGQFUL, 4));
+
+        let occupancy = &server.connection_manager.windows.snapshot()[&handler.connection_id];
+        assert_eq!((occupancy.outstanding, occupancy.peak, occupancy.rejected), (2, 2, 1));
+        assert_eq!(occupancy.system_id, "LoadTester");
+        // The refused SUBMIT_SM left the bucket alone, so the burst still has a token for the next
+        let counters = server.connection_manager.throttle.snapshot()["LoadTester"];
+        assert_eq!((counters.accepted, counters.throttled), (2, 0));
+    }
+
+    #[test]
+    fn test_answered_and_zero_timeout_submits_free_their_slots() {
+        let (server, mut handler, mut phone) = test_handler(|config| {
+            config.smpp.window_size = 1;
+            config.smpp.window_timeout = 0;
+            config.response_percentage.no_response_delay_ms = 0;
+            config.response_percentage.overrides.push(ResponseOverride {
+                code: Some("*999#".to_string()),
+                success_percentage: Some(0.0),
+                no_response_percentage: Some(100.0),
+                ..Default::default()
+            });
+        });
+        let body = b"LoadTester\0secret\0USSD\0\x34\x01\x01\0".to_vec();
+        handler.process_pdu(SmppPdu::new(BIND_TRANSCEIVER, ESME_ROK, 1, body)).unwrap();
+        let mut buffer = PduReadBuffer::new();
+        buffer.read_pdu(&mut phone).unwrap();
+
+        // Answered, undecodable and dropped: each frees the one slot for the next
+        handler.process_pdu(build_ussd_submit_sm("111", "123", "*123#", 0, 2, None)).unwrap();
+        handler.process_pdu(SmppPdu::new(SUBMIT_SM, ESME_ROK, 3, b"USSD\0".to_vec())).unwrap();
+        handler.process_pdu(build_ussd_submit_sm("111", "123", "*999#", 0, 4, None)).unwrap();
+        handler.process_pdu(build_ussd_submit_sm("111", "123", "*123#", 0, 5, None)).unwrap();
+        let answers: Vec<(u32, u32, u32)> = std::iter::from_fn(|| buffer.read_pdu(&mut phone).ok())
+            .filter(|pdu| matches!(pdu.header.command_id, SUBMIT_SM_RESP | GENERIC_NACK))
+            .map(|pdu| (pdu.header.sequence_number, pdu.header.command_id, pdu.header.command_status))
+            .take(3)
+            .collect();
+        assert_eq!(answers[0], (2, SUBMIT_SM_RESP, ESME_ROK));
+        assert_eq!((answers[1].0, answers[1].1), (3, GENERIC_NACK));
+        assert_eq!(answers[2], (5, SUBMIT_SM_RESP, ESME_ROK));
+        let occupancy = &server.connection_manager.windows.snapshot()[&handler.connection_id];
+        assert_eq!((occupancy.outstanding, occupancy.peak, occupancy.rejected), (0, 1, 0));
+    }
+
+    #[test]
+    fn test_restart_does_not_reject_a_reused_connection_id_as_a_repeat() {
+        let path = std::env::temp_dir().join(format!("ussd_server_restart_{}.json", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let persisted = |config: &mut Config| {
+            config.persistence.enabled = true;
+            config.persistence.path = path.clone();
+        };
+        let bind = || SmppPdu::new(BIND_TRANSCEIVER, ESME_ROK, 1, b"LoadTester\0secret\0USSD\0\x34\x01\x01\0".to_vec());
+        let submit_sm = || build_ussd_submit_sm("111", "123", "*123#", 0, 2, None);
+
+        let (server, mut handler, mut phone) = test_handler(persisted);
+        let connection_id = handler.connection_id.clone();
+        let mut buffer = PduReadBuffer::new();
+        handler.process_pdu(bind()).unwrap();
+        handler.process_pdu(submit_sm()).unwrap();
+        assert_eq!(buffer.read_pdu(&mut phone).unwrap().header.command_status, ESME_ROK);
+        let resp = buffer.read_pdu(&mut phone).unwrap();
+        assert_eq!((resp.header.command_id, resp.header.command_status), (SUBMIT_SM_RESP, ESME_ROK));
+        // Saved with the connection still open, as a crash would leave it
+        server.state_store.flush().unwrap();
+        drop((server, handler, phone));
+
+        // After the restart another connection gets the same id and starts numbering from 1
+        let (server, mut handler, mut phone) = test_handler(persisted);
+        handler.connection_id = connection_id;
+        let queue_stream = handler.stream.try_clone().unwrap();
+        server.connection_manager.add_connection(handler.connection_id.clone(), Arc::new(Mutex::new(queue_stream)));
+        let mut buffer = PduReadBuffer::new();
+        handler.process_pdu(bind()).unwrap();
+        handler.process_pdu(submit_sm()).unwrap();
+        assert_eq!(buffer.read_pdu(&mut phone).unwrap().header.command_status, ESME_ROK);
+        let resp = buffer.read_pdu(&mut phone).unwrap();
+        assert_eq!((resp.header.command_id, resp.header.command_status), (SUBMIT_SM_RESP, ESME_ROK));
+        drop(server);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_failed_requests_are_answered_with_their_status() {
+        let (_server, mut handler, mut phone) = test_handler(|_| {});
+        let mut buffer = PduReadBuffer::new();
+        let mut answer = |handler: &mut UssdConnectionHandler, pdu: SmppPdu| {
+            handler.process_pdu(pdu).unwrap();
+            let header = buffer.read_pdu(&mut phone).unwrap().header;
+            (header.command_id, header.command_status)
+        };
+
+        // Before a bind, a SUBMIT_SM gets its own response with the bind-state error
+        let submit_sm = build_ussd_submit_sm("111", "123", "*123#", 0, 1, None);
+        assert_eq!(answer(&mut handler, submit_sm), (SUBMIT_SM_RESP, ESME_RINVBNDSTS));
+
+        let refused = SmppPdu::new(BIND_TRANSCEIVER, ESME_ROK, 2, b"LoadTester\0\0USSD\0\x34\x01\x01\0".to_vec());
+        assert_eq!(answer(&mut handler, refused), (BIND_TRANSCEIVER_RESP, ESME_RINVPASWD));
+
+        let bind = SmppPdu::new(BIND_TRANSCEIVER, ESME_ROK, 3, b"LoadTester\0secret\0USSD\0\x34\x01\x01\0".to_vec());
+        assert_eq!(answer(&mut handler, bind), (BIND_TRANSCEIVER_RESP, ESME_ROK));
+
+        // Neither an undecodable body nor an unknown command has a response of its own
+        let truncated = SmppPdu::new(DELIVER_SM, ESME_ROK, 4, b"USSD\0".to_vec());
+        assert_eq!(answer(&mut handler, truncated), (GENERIC_NACK, ESME_RINVCMDLEN));
+        let unknown = SmppPdu::new(0x00000103, ESME_ROK, 5, Vec::new());
+        assert_eq!(answer(&mut handler, unknown), (GENERIC_NACK, ESME_RINVCMDID));
+        assert!(handler.current_session.is_some());
+    }
+
+    #[test]
+    fn test_query_sm_reports_message_state() {
+        let (_server, mut handler, mut phone) = test_handler(|_| {});
+        let mut buffer = PduReadBuffer::new();
+
+        let bind = SmppPdu::new(BIND_TRANSCEIVER, ESME_ROK, 1, b"USSDMobileUser\0mobile123\0USSD\0\x34\x01\x01\0".to_vec());
+        handler.process_pdu(bind).unwrap();
+        buffer.read_pdu(&mut phone).unwrap();
+        handler.process_pdu(build_ussd_submit_sm("111", "123", "*123#", 0, 2, None)).unwrap();
+        let resp = buffer.read_pdu(&mut phone).unwrap();
+        let message_id = SubmitSmResp::decode(&resp.body).unwrap().message_id.into_owned();
+        assert_eq!(buffer.read_pdu(&mut phone).unwrap().header.command_id, DELIVER_SM);
+
+        let query = QuerySm { message_id: message_id.as_str().into(), source_addr: "111".into(), ..Default::default() };
+        handler.process_pdu(SmppPdu::new(QUERY_SM, ESME_ROK, 3, query.encode())).unwrap();
+        let resp = buffer.read_pdu(&mut phone).unwrap();
+        assert_eq!((resp.header.command_id, resp.header.command_status), (QUERY_SM_RESP, ESME_ROK));
+        let state = QuerySmResp::decode(&resp.body).unwrap();
+        assert_eq!((state.message_id.as_ref(), state.message_state), (message_id.as_str(), MESSAGE_STATE_DELIVERED));
+        assert_eq!(state.final_date.len(), 16);
+
+        // Another subscriber's address does not match the message
+        let query = QuerySm { source_addr: "222".into(), ..query };
+        handler.process_pdu(SmppPdu::new(QUERY_SM, ESME_ROK, 4, query.encode())).unwrap();
+        let header = buffer.read_pdu(&mut phone).unwrap().header;
+        assert_eq!((header.command_id, header.command_status), (QUERY_SM_RESP, ESME_RQUERYFAIL));
+    }
+
+    #[test]
+    fn test_registered_delivery_gets_a_receipt() {
+        let (server, mut handler, mut phone) = test_handler(|config| {
+            config.delivery_receipts.delay_ms = 0;
+            config.delivery_receipts.delivered_percentage = 0.0;
+            config.delivery_receipts.undeliverable_percentage = 100.0;
+        });
+        server.spawn_delivery_receipts();
+        let mut buffer = PduReadBuffer::new();
+
+        let bind = SmppPdu::new(BIND_TRANSCEIVER, ESME_ROK, 1, b"USSDMobileUser\0mobile123\0USSD\0\x34\x01\x01\0".to_vec());
+        handler.process_pdu(bind).unwrap();
+        buffer.read_pdu(&mut phone).unwrap();
+        let submit = build_ussd_submit_sm("111", "123", "*123#", 0, 2, None);
+        let mut submit_sm = SubmitSm::decode(&submit.body).unwrap();
+        submit_sm.registered_delivery = 1;
+        handler.process_pdu(SmppPdu::new(SUBMIT_SM, ESME_ROK, 2, submit_sm.encode())).unwrap();
+        let message_id = SubmitSmResp::decode(&buffer.read_pdu(&mut phone).unwrap().body).unwrap().message_id.into_owned();
+        let menu = DeliverSm::decode(&buffer.read_pdu(&mut phone).unwrap().body).unwrap().esm_class;
+        assert_ne!(menu, 0x04);
+
+        let receipt = buffer.read_pdu(&mut phone).unwrap();
+        let receipt = DeliverSm::decode(&receipt.body).unwrap();
+        assert_eq!((receipt.esm_class, receipt.destination_addr.as_ref()), (0x04, "111"));
+        let text = deliver_sm_text(&receipt);
+        assert!(text.starts_with(&format!("id:{} sub:001 dlvrd:000 ", message_id)), "{}", text);
+        assert!(text.contains(" stat:UNDELIV err:000 text:*123#"), "{}", text);
+        assert_eq!(server.state_store.lookup_message_id(&message_id).unwrap().state, MessageState::Undeliverable);
+    }
+
+    #[test]
+    fn test_addressing_rules_refuse_malformed_addresses() {
+        let (_server, mut handler, mut phone) = test_handler(|config| config.addressing.enabled = true);
+        let mut buffer = PduReadBuffer::new();
+
+        let bind = SmppPdu::new(BIND_TRANSCEIVER, ESME_ROK, 1, b"USSDMobileUser\0mobile123\0USSD\0\x34\x01\x01\0".to_vec());
+        handler.process_pdu(bind).unwrap();
+        buffer.read_pdu(&mut phone).unwrap();
+
+        // International source numbers cannot start with 0
+        handler.process_pdu(build_ussd_submit_sm("0771234567", "*123#", "*123#", 0, 2, None)).unwrap();
+        let header = buffer.read_pdu(&mut phone).unwrap().header;
+        assert_eq!((header.command_id, header.command_status), (SUBMIT_SM_RESP, ESME_RINVSRCADR));
+
+        let submit = build_ussd_submit_sm("94771234567", "*123#", "*123#", 0, 3, None);
+        let mut submit_sm = SubmitSm::decode(&submit.body).unwrap();
+        (submit_sm.dest_addr_ton, submit_sm.dest_addr_npi) = (1, 1); // A USSD string is not an E.164 number
+        handler.process_pdu(SmppPdu::new(SUBMIT_SM, ESME_ROK, 3, submit_sm.encode())).unwrap();
+        let header = buffer.read_pdu(&mut phone).unwrap().header;
+        assert_eq!((header.command_id, header.command_status), (SUBMIT_SM_RESP, ESME_RINVDSTADR));
+
+        handler.process_pdu(build_ussd_submit_sm("94771234567", "*123#", "*123#", 0, 4, None)).unwrap();
+        let header = buffer.read_pdu(&mut phone).unwrap().header;
+        assert_eq!((header.command_id, header.command_status), (SUBMIT_SM_RESP, ESME_ROK));
+    }
+
+    #[test]
+    fn test_strict_mode_refuses_malformed_fields() {
+        let (_server, mut handler, mut phone) = test_handler(|config| config.smpp.strict = true);
+        let mut buffer = PduReadBuffer::new();
+
+        // Unterminated address_range, which the lenient parser would read as empty
+        let bind = SmppPdu::new(BIND_TRANSCEIVER, ESME_ROK, 1, b"USSDMobileUser\0mobile12\0USSD\0\x34\x01\x01".to_vec());
+        handler.process_pdu(bind).unwrap();
+        let header = buffer.read_pdu(&mut phone).unwrap().header;
+        assert_eq!((header.command_id, header.command_status), (BIND_TRANSCEIVER_RESP, ESME_RBINDFAIL));
+
+        // Passwords are at most 8 octets, so the other tests' "mobile123" would be refused
+        let bind = SmppPdu::new(BIND_TRANSCEIVER, ESME_ROK, 2, b"USSDMobileUser\0mobile12\0USSD\0\x34\x01\x01\0".to_vec());
+        handler.process_pdu(bind).unwrap();
+        assert_eq!(buffer.read_pdu(&mut phone).unwrap().header.command_status, ESME_ROK);
+
+        let submit = build_ussd_submit_sm("111", "*123#", "*123#", 0, 3, None);
+        let mut submit_sm = SubmitSm::decode(&submit.body).unwrap();
+        submit_sm.priority_flag = 7;
+        handler.process_pdu(SmppPdu::new(SUBMIT_SM, ESME_ROK, 3, submit_sm.encode())).unwrap();
+        let header = buffer.read_pdu(&mut phone).unwrap().header;
+        assert_eq!((header.command_id, header.command_status), (SUBMIT_SM_RESP, ESME_RINVPRTFLG));
+
+        // The corrected retransmission is not refused as a repeat
+        submit_sm.priority_flag = 0;
+        handler.process_pdu(SmppPdu::new(SUBMIT_SM, ESME_ROK, 3, submit_sm.encode())).unwrap();
+        let header = buffer.read_pdu(&mut phone).unwrap().header;
+        assert_eq!((header.command_id, header.command_status), (SUBMIT_SM_RESP, ESME_ROK));
+    }
+
+    #[test]
+    fn test_random_and_truncated_bodies_never_panic_the_handler() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let (_server, mut handler, _phone) = test_handler(|_| {});
+        let bind = SmppPdu::new(BIND_TRANSCEIVER, ESME_ROK, 1, b"USSDMobileUser\0mobile123\0USSD\0\x34\x01\x01\0".to_vec());
+        handler.process_pdu(bind).unwrap();
+
+        // Every prefix of a valid SUBMIT_SM, then random bodies for each request the handler reads
+        let mut sequence_number = 2;
+        let submit = build_ussd_submit_sm("111", "123", "*123#", 0, 0, None).body;
+        for cut in 0..submit.len() {
+            handler.process_pdu(SmppPdu::new(SUBMIT_SM, ESME_ROK, sequence_number, submit[..cut].to_vec())).unwrap();
+            sequence_number += 1;
+        }
+        let mut rng = StdRng::seed_from_u64(811);
+        for _ in 0..300 {
+            let command_id = [SUBMIT_SM, DELIVER_SM, QUERY_SM, CANCEL_SM, REPLACE_SM, ENQUIRE_LINK][rng.gen_range(0..6)];
+            let body: Vec<u8> = (0..rng.gen_range(0..64)).map(|_| if rng.gen_bool(0.2) { 0 } else { rng.r#gen() }).collect();
+            handler.process_pdu(SmppPdu::new(command_id, ESME_ROK, sequence_number, body)).unwrap();
+            sequence_number += 1;
+        }
+    }
+
+    #[test]
+    fn test_cancel_and_replace_queued_deliver_sm() {
+        let (server, mut handler, mut phone) = test_handler(|_| {});
+        // Registered again with a stream the test can hold, in place of the helper's
+        let queue_stream = Arc::new(Mutex::new(handler.stream.try_clone().unwrap()));
+        server.connection_manager.add_connection(handler.connection_id.clone(), Arc::clone(&queue_stream));
+        let mut buffer = PduReadBuffer::new();
+
+        let bind = SmppPdu::new(BIND_TRANSCEIVER, ESME_ROK, 1, b"USSDMobileUser\0mobile123\0USSD\0\x34\x01\x01\0".to_vec());
+        handler.process_pdu(bind).unwrap();
+        buffer.read_pdu(&mut phone).unwrap();
+
+        // Holding the stream stalls the writer on the first PDU, so the two replies stay queued
+        let writer = queue_stream.lock().unwrap();
+        let queue = server.connection_manager.get_connection(&handler.connection_id).unwrap();
+        queue.push(0, SmppPdu::new(ENQUIRE_LINK, ESME_ROK, 90, Vec::new())).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        let first = server.state_store.issue_message_id("USSDMobileUser", "111");
+        let second = server.state_store.issue_message_id("USSDMobileUser", "111");
+        let screen = |text: &str, seq| build_ussd_deliver_sm("111", text, 0, seq, None, TextEncoding::Gsm7, &Config::default());
+        queue.push_expiring(0, screen("1. Balance", 91), None, Some(&first)).unwrap();
+        queue.push_expiring(0, screen("1. Top up", 92), None, Some(&second)).unwrap();
+
+        let cancel = CancelSm { message_id: first.as_str().into(), destination_addr: "111".into(), ..Default::default() };
+        handler.process_pdu(SmppPdu::new(CANCEL_SM, ESME_ROK, 2, cancel.encode())).unwrap();
+        let replace = ReplaceSm { message_id: second.as_str().into(), short_message: b"2. Bundles".as_slice().into(), ..Default::default() };
+        handler.process_pdu(SmppPdu::new(REPLACE_SM, ESME_ROK, 3, replace.encode())).unwrap();
+        // The first one is gone, so a second cancel fails
+        handler.process_pdu(SmppPdu::new(CANCEL_SM, ESME_ROK, 4, cancel.encode())).unwrap();
+        drop(writer);
+
+        let mut read = || buffer.read_pdu(&mut phone).unwrap();
+        assert_eq!(read().header.command_id, ENQUIRE_LINK);
+        let statuses: Vec<(u32, u32)> = (0..3).map(|_| read().header).map(|header| (header.command_id, header.command_status)).collect();
+        assert_eq!(statuses, [(CANCEL_SM_RESP, ESME_ROK), (REPLACE_SM_RESP, ESME_ROK), (CANCEL_SM_RESP, ESME_RCANCELFAIL)]);
+        let delivered = read();
+        assert_eq!(delivered.header.sequence_number, 92);
+        assert_eq!(deliver_sm_text(&DeliverSm::decode(&delivered.body).unwrap()), "2. Bundles");
+        assert_eq!(server.state_store.lookup_message_id(&first).unwrap().state, MessageState::Deleted);
+    }
+
+    #[test]
+    fn test_tls_listener_binds_clients_with_certificates() {
+        use std::io::Write;
+
+        use rcgen::{BasicConstraints, CertificateParams, CertifiedIssuer, IsCa, KeyPair};
+        use ussd_common::tls::{TlsClientConfig, TlsServerConfig};
+
+        let dir = std::env::temp_dir().join(format!("ussd_server_tls_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |file: &str| dir.join(file).to_str().unwrap().to_string();
+        let mut ca_params = CertificateParams::new(Vec::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = CertifiedIssuer::self_signed(ca_params, KeyPair::generate().unwrap()).unwrap();
+        fs::write(path("ca.pem"), ca.pem()).unwrap();
+        for leaf in ["server", "client"] {
+            let key = KeyPair::generate().unwrap();
+            let cert = CertificateParams::new(vec!["localhost".to_string()]).unwrap().signed_by(&key, &ca).unwrap();
+            fs::write(path(&format!("{}.pem", leaf)), cert.pem()).unwrap();
+            fs::write(path(&format!("{}.key", leaf)), key.serialize_pem()).unwrap();
+        }
+
+        let listener_config = ListenerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            max_connections: 0,
+            tls: TlsServerConfig {
+                enabled: true,
+                cert: path("server.pem"),
+                key: path("server.key"),
+                client_ca: path("ca.pem"),
+                require_client_cert: true,
+            },
+        };
+        let server = Arc::new(UssdSmppServer::new(test_config(|_| {})));
+        let listener = listeners::Listener::bind(&listener_config).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let serving = Arc::clone(&server);
+        thread::spawn(move || serving.serve_listener(listener));
+
+        let client = TlsClientConfig { enabled: true, ca: path("ca.pem"), cert: path("client.pem"), key: path("client.key"), server_name: String::new() };
+        let socket = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let mut esme = client.connect(socket, "localhost").unwrap();
+        let body = b"LoadTester\0secret\0USSD\0\x34\x01\x01\0".to_vec();
+        let bind = SmppPdu::new(BIND_TRANSCEIVER, ESME_ROK, 1, body);
+        esme.write_all(&bind.to_bytes()).unwrap();
+        let resp = PduReadBuffer::new().read_pdu(&mut esme).unwrap();
+        assert_eq!((resp.header.command_id, resp.header.command_status), (BIND_TRANSCEIVER_RESP, ESME_ROK));
+
+        // Without a client certificate the server ends the handshake
+        let anonymous = TlsClientConfig { cert: String::new(), key: String::new(), ..client };
+        let socket = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+        if let Ok(mut esme) = anonymous.connect(socket, "localhost") {
+            let _ = esme.write_all(&bind.to_bytes());
+            assert!(PduReadBuffer::new().read_pdu(&mut esme).is_err());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_oversized_command_length_gets_generic_nack_and_close() {
+        let listener_config = ListenerConfig { host: "127.0.0.1".to_string(), port: 0, max_connections: 0, tls: Default::default() };
+        let server = Arc::new(UssdSmppServer::new(test_config(|config| config.smpp.max_pdu_len = 1024)));
+        let listener = listeners::Listener::bind(&listener_config).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let serving = Arc::clone(&server);
+        thread::spawn(move || serving.serve_listener(listener));
+
+        // Only headers are sent; a server that trusted command_length would wait for the body
+        for command_length in [1025, u32::MAX, 15] {
+            let mut esme = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+            esme.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            let mut header = Vec::new();
+            SmppHeader { command_length, command_id: SUBMIT_SM, command_status: ESME_ROK, sequence_number: 7 }.encode_into(&mut header);
+            esme.write_all(&header).unwrap();
+
+            let mut buffer = PduReadBuffer::new();
+            let nack = buffer.read_pdu(&mut esme).unwrap().header;
+            assert_eq!((nack.command_id, nack.command_status, nack.sequence_number), (GENERIC_NACK, ESME_RINVCMDLEN, 7));
+            let err = buffer.read_pdu(&mut esme).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof, "command_length {}", command_length);
+        }
+
+        // At the limit the PDU is read as usual
+        let mut esme = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+        esme.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        esme.write_all(&SmppPdu::new(ENQUIRE_LINK, ESME_ROK, 8, vec![0; 1024 - HEADER_LEN]).to_bytes()).unwrap();
+        assert_eq!(PduReadBuffer::new().read_pdu(&mut esme).unwrap().header.command_id, ENQUIRE_LINK_RESP);
+    }
+
+    #[test]
+    fn test_malformed_pdus_ban_the_peer() {
+        let listener_config = ListenerConfig { host: "127.0.0.1".to_string(), port: 0, max_connections: 0, tls: Default::default() };
+        let server = Arc::new(UssdSmppServer::new(test_config(|config| config.server.flood_protection.ban_after_malformed = 2)));
+        let listener = listeners::Listener::bind(&listener_config).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let serving = Arc::clone(&server);
+        thread::spawn(move || serving.serve_listener(listener));
+
+        let mut esme = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+        esme.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut buffer = PduReadBuffer::new();
+        esme.write_all(&SmppPdu::new(0x0000_0099, ESME_ROK, 1, Bytes::new()).to_bytes()).unwrap();
+        assert_eq!(buffer.read_pdu(&mut esme).unwrap().header.command_id, GENERIC_NACK);
+        // The second earns the ban, and the connection goes with it
+        esme.write_all(&SmppPdu::new(0x0000_0099, ESME_ROK, 2, Bytes::new()).to_bytes()).unwrap();
+        while buffer.read_pdu(&mut esme).is_ok() {}
+
+        let mut again = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+        again.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        assert!(PduReadBuffer::new().read_pdu(&mut again).is_err());
+        assert_eq!(server.connection_manager.flood.admit(&server.config.get().server.flood_protection, "127.0.0.1".parse().unwrap()), Err(crate::flood::Refusal::Banned));
+    }
+
+    #[test]
+    fn test_outbind_dials_the_esme_and_serves_its_bind() {
+        let esme = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = UssdSmppServer::new(test_config(|_| {}));
+        server.spawn_outbind(OutbindConfig {
+            host: "127.0.0.1".to_string(),
+            port: esme.local_addr().unwrap().port(),
+            system_id: String::new(),
+            password: "outbind123".to_string(),
+            reconnect_interval: 1,
+        });
+
+        let (mut socket, _) = esme.accept().unwrap();
+        let mut buffer = PduReadBuffer::new();
+        let outbind = buffer.read_pdu(&mut socket).unwrap();
+        assert_eq!(outbind.header.command_id, OUTBIND);
+        let credentials = Outbind::decode(&outbind.body).unwrap();
+        assert_eq!((credentials.system_id.as_ref(), credentials.password.as_ref()), ("USSDGateway", "outbind123"));
+
+        let bind = SmppPdu::new(BIND_RECEIVER, ESME_ROK, 1, b"ForwardingClient\0forward123\0USSD\0\x34\x01\x01\0".to_vec());
+        socket.write_all(&bind.to_bytes()).unwrap();
+        let resp = buffer.read_pdu(&mut socket).unwrap();
+        assert_eq!((resp.header.command_id, resp.header.command_status), (BIND_RECEIVER_RESP, ESME_ROK));
+        let binds = server.controller().binds();
+        assert_eq!((binds[0].system_id.as_str(), binds[0].bind_type), ("ForwardingClient", "receiver"));
+
+        // A dropped connection is dialled again
+        drop(socket);
+        let (mut socket, _) = esme.accept().unwrap();
+        assert_eq!(PduReadBuffer::new().read_pdu(&mut socket).unwrap().header.command_id, OUTBIND);
+    }
+}
@@ -0,0 +1,112 @@
+use std::time::{Instant, SystemTime};
+
+use ussd_common::encoding::TextEncoding;
+
+use crate::menu_tree::TreePosition;
+use crate::pdu::{BIND_RECEIVER, BIND_TRANSCEIVER, BIND_TRANSMITTER};
+use crate::smpp_time::parse_smpp_time;
+
+#[derive(Debug, Clone)]
+pub struct UssdSession {
+    pub msisdn: String,
+    pub session_id: String,
+    pub state: UssdState,
+    pub menu_level: u8,
+    pub last_request: String,
+    pub inputs: Vec<String>, // The dialled code, then each reply, for {{session.input[n]}}
+    pub service_code: String, // Code that opened the session, e.g. "*123#"
+    pub last_message: MessageContext, // Latest SUBMIT_SM from this MSISDN
+    pub forward_route: Option<String>, // system_id chosen by the routing table for this session
+    pub pages: Vec<UssdScreen>, // Rest of a paginated screen, sent as the subscriber asks for more
+    pub last_activity: Instant,
+}
+
+// Delivery attributes of a message that copies of it inherit while queued
+#[derive(Debug, Clone)]
+pub struct MessageContext {
+    pub message_id: String,
+    pub originator: String,
+    pub recipient: String,
+    pub priority_flag: u8,
+    pub registered_delivery: u8,
+    pub submitted_at: SystemTime,
+    pub expires_at: Option<SystemTime>,
+}
+
+impl MessageContext {
+    pub(crate) fn new(message_id: String, originator: &str, recipient: &str, priority_flag: u8, registered_delivery: u8, validity_period: &str) -> Self {
+        let submitted_at = SystemTime::now();
+        MessageContext {
+            message_id,
+            originator: originator.to_string(),
+            recipient: recipient.to_string(),
+            priority_flag,
+            registered_delivery,
+            submitted_at,
+            expires_at: parse_smpp_time(validity_period, submitted_at),
+        }
+    }
+    
+    // registered_delivery 1 (success or failure) and 2 (failure only) both cover expiry
+    pub(crate) fn wants_failure_receipt(&self) -> bool {
+        self.registered_delivery & 0x03 != 0
+    }
+}
+
+// A screen for the subscriber, with the ussd_service_op and encoding to send it with
+#[derive(Debug, Clone)]
+pub struct UssdScreen {
+    pub text: String,
+    pub service_op: Option<u8>,
+    pub encoding: TextEncoding,
+}
+
+#[derive(Debug, Clone)]
+pub enum UssdState {
+    Initial,
+    MainMenu,
+    BalanceInquiry,
+    DataPackages,
+    CustomerService,
+    Forwarded,
+    HttpBackend, // Answered by `[http_backend]`, outside the session lock
+    Pushed, // Network-initiated request awaiting the subscriber's reply
+    Scripted(String), // In a `[scripting]` service; holds the script's state as a JSON object
+    Tree(TreePosition), // In a `[menu_tree]` menu
+    Terminated,
+}
+
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub system_id: String,
+    pub password: String,
+    pub bound: bool,
+    pub bind_type: u32,
+    pub can_receive_forwards: bool,
+    pub is_user_client: bool,
+    pub connection_id: Option<String>,
+    pub screen_chars: Option<usize>, // Longest screen the client shows, declared at bind or configured
+}
+
+impl Session {
+    // Receiver and transceiver binds may be sent DELIVER_SM (and forwarded SUBMIT_SM)
+    pub fn can_receive(&self) -> bool {
+        matches!(self.bind_type, BIND_RECEIVER | BIND_TRANSCEIVER)
+    }
+
+    // Transmitter and transceiver binds may submit messages
+    pub fn can_transmit(&self) -> bool {
+        matches!(self.bind_type, BIND_TRANSMITTER | BIND_TRANSCEIVER)
+    }
+
+    // "user", "forwarding" or "esme", from the client_simulator lists
+    pub fn role(&self) -> &'static str {
+        if self.is_user_client {
+            "user"
+        } else if self.can_receive_forwards {
+            "forwarding"
+        } else {
+            "esme"
+        }
+    }
+}
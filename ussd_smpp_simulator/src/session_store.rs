@@ -0,0 +1,297 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use log::info;
+use rusqlite::{params, Connection};
+
+use crate::session::{MessageContext, UssdSession, UssdState};
+use crate::shard::ShardedMap;
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS ussd_sessions (
+    msisdn TEXT PRIMARY KEY,
+    session_id TEXT NOT NULL,
+    state TEXT NOT NULL,
+    menu_level INTEGER NOT NULL,
+    last_request TEXT NOT NULL,
+    service_code TEXT NOT NULL,
+    forward_route TEXT,
+    priority_flag INTEGER NOT NULL,
+    last_activity INTEGER NOT NULL
+)";
+
+// The part of a USSD session that survives a restart. Unread pages and the pending forward are
+// left behind: the subscriber's next reply redraws the screen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct StoredSession {
+    msisdn: String,
+    session_id: String,
+    state: String,
+    menu_level: u8,
+    last_request: String,
+    service_code: String,
+    forward_route: Option<String>,
+    priority_flag: u8,
+    last_activity: u64, // Unix seconds
+}
+
+// Where USSD sessions are kept across restarts, chosen by `ussd.session_store`: "memory" keeps
+// nothing, "sqlite:<path>" snapshots open sessions into an SQLite database
+#[derive(Debug)]
+pub struct SessionStore {
+    path: Option<String>,
+    db: Option<Mutex<Connection>>,
+    written: Mutex<Vec<StoredSession>>, // Last snapshot saved, so an idle server does not rewrite it
+}
+
+impl SessionStore {
+    pub fn memory() -> Self {
+        SessionStore { path: None, db: None, written: Mutex::new(Vec::new()) }
+    }
+
+    pub fn open(spec: &str) -> Result<Self, String> {
+        if spec.is_empty() || spec == "memory" {
+            return Ok(Self::memory());
+        }
+        let Some(path) = spec.strip_prefix("sqlite:").filter(|path| !path.is_empty()) else {
+            return Err(format!("unknown session_store {:?}: expected \"memory\" or \"sqlite:<path>\"", spec));
+        };
+        let db = Connection::open(path)
+            .and_then(|db| db.execute(SCHEMA, []).map(|_| db))
+            .map_err(|e| format!("{}: {}", path, e))?;
+        Ok(SessionStore { path: Some(path.to_string()), db: Some(Mutex::new(db)), written: Mutex::new(Vec::new()) })
+    }
+
+    pub fn is_persistent(&self) -> bool {
+        self.db.is_some()
+    }
+
+    // Sessions saved by the previous run, with their idle time carried over so the sweeper
+    // still times them out
+    pub fn load(&self) -> rusqlite::Result<Vec<UssdSession>> {
+        let Some(db) = &self.db else {
+            return Ok(Vec::new());
+        };
+        let db = db.lock().unwrap();
+        let mut statement = db.prepare(
+            "SELECT msisdn, session_id, state, menu_level, last_request, service_code, forward_route, priority_flag, last_activity
+             FROM ussd_sessions",
+        )?;
+        let stored = statement
+            .query_map([], |row| {
+                Ok(StoredSession {
+                    msisdn: row.get(0)?,
+                    session_id: row.get(1)?,
+                    state: row.get(2)?,
+                    menu_level: row.get(3)?,
+                    last_request: row.get(4)?,
+                    service_code: row.get(5)?,
+                    forward_route: row.get(6)?,
+                    priority_flag: row.get(7)?,
+                    last_activity: row.get(8)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        *self.written.lock().unwrap() = stored.clone();
+
+        let now = unix_now();
+        Ok(stored
+            .into_iter()
+            .filter_map(|stored| {
+                let state = state_from_name(&stored.state)?;
+                let idle = Duration::from_secs(now.saturating_sub(stored.last_activity));
+                Some(UssdSession {
+                    last_message: MessageContext::new(String::new(), &stored.msisdn, "", stored.priority_flag, 0, ""),
+                    msisdn: stored.msisdn,
+                    session_id: stored.session_id,
+                    state,
+                    menu_level: stored.menu_level,
+                    last_request: stored.last_request,
+                    inputs: Vec::new(),
+                    service_code: stored.service_code,
+                    forward_route: stored.forward_route,
+                    pages: Vec::new(),
+                    last_activity: Instant::now().checked_sub(idle).unwrap_or_else(Instant::now),
+                })
+            })
+            .collect())
+    }
+
+    // Replaces the stored sessions with the open ones in `sessions`. Returns whether anything
+    // was written.
+    pub fn save(&self, sessions: &ShardedMap<UssdSession>) -> rusqlite::Result<bool> {
+        let Some(db) = &self.db else {
+            return Ok(false);
+        };
+        let now = unix_now();
+        let mut snapshot = sessions.filter_map(|session| {
+            (!matches!(session.state, UssdState::Terminated)).then(|| StoredSession {
+                msisdn: session.msisdn.clone(),
+                session_id: session.session_id.clone(),
+                state: state_name(&session.state),
+                menu_level: session.menu_level,
+                last_request: session.last_request.clone(),
+                service_code: session.service_code.clone(),
+                forward_route: session.forward_route.clone(),
+                priority_flag: session.last_message.priority_flag,
+                last_activity: now.saturating_sub(session.last_activity.elapsed().as_secs()),
+            })
+        });
+        snapshot.sort_by(|a, b| a.msisdn.cmp(&b.msisdn));
+
+        let mut written = self.written.lock().unwrap();
+        if *written == snapshot {
+            return Ok(false);
+        }
+        let mut db = db.lock().unwrap();
+        let transaction = db.transaction()?;
+        transaction.execute("DELETE FROM ussd_sessions", [])?;
+        for stored in &snapshot {
+            transaction.execute(
+                "INSERT INTO ussd_sessions
+                 (msisdn, session_id, state, menu_level, last_request, service_code, forward_route, priority_flag, last_activity)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    stored.msisdn,
+                    stored.session_id,
+                    stored.state,
+                    stored.menu_level,
+                    stored.last_request,
+                    stored.service_code,
+                    stored.forward_route,
+                    stored.priority_flag,
+                    stored.last_activity,
+                ],
+            )?;
+        }
+        transaction.commit()?;
+        *written = snapshot;
+        Ok(true)
+    }
+
+    // Saves the open sessions every `interval`; does nothing for the memory store
+    pub fn spawn_snapshots(self: &Arc<Self>, sessions: Arc<ShardedMap<UssdSession>>, interval: Duration) {
+        let Some(path) = self.path.clone() else {
+            return;
+        };
+        info!("💾 Persisting USSD sessions to {}", path);
+
+        let store = Arc::clone(self);
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            if let Err(e) = store.save(&sessions) {
+                info!("⚠️  Could not save USSD sessions to {}: {}", path, e);
+            }
+        });
+    }
+
+    // Final save on a graceful exit
+    pub fn shutdown(&self, sessions: &ShardedMap<UssdSession>) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        match self.save(sessions) {
+            Ok(_) => info!("💾 Saved USSD sessions to {}", path),
+            Err(e) => info!("⚠️  Could not save USSD sessions to {}: {}", path, e),
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+fn state_name(state: &UssdState) -> String {
+    let name = match state {
+        UssdState::Initial => "initial",
+        UssdState::MainMenu => "main_menu",
+        UssdState::BalanceInquiry => "balance_inquiry",
+        UssdState::DataPackages => "data_packages",
+        UssdState::CustomerService => "customer_service",
+        UssdState::Forwarded => "forwarded",
+        UssdState::HttpBackend => "http_backend",
+        UssdState::Pushed => "pushed",
+        UssdState::Scripted(script_state) => return format!("scripted:{}", script_state),
+        UssdState::Tree(position) => return format!("tree:{}", serde_json::to_string(position).unwrap_or_default()),
+        UssdState::Terminated => "terminated",
+    };
+    name.to_string()
+}
+
+fn state_from_name(name: &str) -> Option<UssdState> {
+    Some(match name {
+        "initial" => UssdState::Initial,
+        "main_menu" => UssdState::MainMenu,
+        "balance_inquiry" => UssdState::BalanceInquiry,
+        "data_packages" => UssdState::DataPackages,
+        "customer_service" => UssdState::CustomerService,
+        "forwarded" => UssdState::Forwarded,
+        "http_backend" => UssdState::HttpBackend,
+        "pushed" => UssdState::Pushed,
+        _ => match name.split_once(':')? {
+            ("scripted", script_state) => UssdState::Scripted(script_state.to_string()),
+            ("tree", position) => UssdState::Tree(serde_json::from_str(position).ok()?),
+            _ => return None,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn session(msisdn: &str, state: UssdState, menu_level: u8) -> UssdSession {
+        UssdSession {
+            msisdn: msisdn.to_string(),
+            session_id: format!("SESS{}", msisdn),
+            state,
+            menu_level,
+            last_request: "1".to_string(),
+            inputs: Vec::new(),
+            service_code: "*123#".to_string(),
+            last_message: MessageContext::new("USSD1".to_string(), msisdn, "123", 1, 0, ""),
+            forward_route: None,
+            pages: Vec::new(),
+            last_activity: Instant::now() - Duration::from_secs(30),
+        }
+    }
+
+    #[test]
+    fn test_sessions_survive_a_restart() {
+        let path = std::env::temp_dir().join(format!("ussd_sessions_{}.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let spec = format!("sqlite:{}", path.display());
+
+        let sessions = ShardedMap::default();
+        sessions.insert("111".to_string(), session("111", UssdState::DataPackages, 2));
+        sessions.insert("222".to_string(), session("222", UssdState::Terminated, 1));
+        let store = SessionStore::open(&spec).unwrap();
+        assert!(store.save(&sessions).unwrap());
+        // Nothing changed, so nothing is rewritten
+        assert!(!store.save(&sessions).unwrap());
+        drop(store);
+
+        let restored = SessionStore::open(&spec).unwrap().load().unwrap();
+        assert_eq!(restored.len(), 1);
+        let session = &restored[0];
+        assert_eq!((session.msisdn.as_str(), session.session_id.as_str()), ("111", "SESS111"));
+        assert!(matches!(session.state, UssdState::DataPackages));
+        assert_eq!((session.menu_level, session.last_message.priority_flag), (2, 1));
+        assert!(session.last_activity.elapsed() >= Duration::from_secs(29));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_memory_store_and_unknown_specs() {
+        let store = SessionStore::open("memory").unwrap();
+        assert!(!store.is_persistent());
+        let sessions = ShardedMap::default();
+        sessions.insert("111".to_string(), session("111", UssdState::MainMenu, 1));
+        assert!(!store.save(&sessions).unwrap());
+        assert!(store.load().unwrap().is_empty());
+
+        assert!(SessionStore::open("sqlite:").is_err());
+        assert!(SessionStore::open("redis://localhost").is_err());
+    }
+}
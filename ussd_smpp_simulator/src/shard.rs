@@ -0,0 +1,138 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::sync::{Mutex, MutexGuard};
+
+const SHARD_COUNT: usize = 16;
+
+// String-keyed map split over independently locked shards, so connection threads working on
+// different binds or MSISDNs do not wait on each other. Whole-map scans lock one shard at a time
+// and therefore see each shard at a slightly different moment.
+#[derive(Debug)]
+pub struct ShardedMap<V> {
+    shards: Vec<Mutex<HashMap<String, V>>>,
+    hasher: RandomState,
+}
+
+impl<V> Default for ShardedMap<V> {
+    fn default() -> Self {
+        Self::with_shards(SHARD_COUNT)
+    }
+}
+
+impl<V> ShardedMap<V> {
+    // One shard behaves like a single Mutex<HashMap>
+    pub fn with_shards(count: usize) -> Self {
+        ShardedMap {
+            shards: (0..count.max(1)).map(|_| Mutex::new(HashMap::new())).collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    // The locked shard that holds `key`, for entry-style updates
+    pub fn shard(&self, key: &str) -> MutexGuard<'_, HashMap<String, V>> {
+        let index = self.hasher.hash_one(key) as usize % self.shards.len();
+        self.shards[index].lock().unwrap()
+    }
+
+    pub fn insert(&self, key: String, value: V) -> Option<V> {
+        self.shard(&key).insert(key, value)
+    }
+
+    pub fn remove(&self, key: &str) -> Option<V> {
+        self.shard(key).remove(key)
+    }
+
+    pub fn read<R>(&self, key: &str, f: impl FnOnce(&V) -> R) -> Option<R> {
+        self.shard(key).get(key).map(f)
+    }
+
+    pub fn update<R>(&self, key: &str, f: impl FnOnce(&mut V) -> R) -> Option<R> {
+        self.shard(key).get_mut(key).map(f)
+    }
+
+    pub fn filter_map<R>(&self, mut f: impl FnMut(&V) -> Option<R>) -> Vec<R> {
+        let mut found = Vec::new();
+        for shard in &self.shards {
+            found.extend(shard.lock().unwrap().values().filter_map(&mut f));
+        }
+        found
+    }
+
+    pub fn remove_where(&self, mut matches: impl FnMut(&V) -> bool) -> Vec<V> {
+        let mut removed = Vec::new();
+        for shard in &self.shards {
+            let mut shard = shard.lock().unwrap();
+            let keys: Vec<String> = shard.iter().filter(|(_, value)| matches(value)).map(|(key, _)| key.clone()).collect();
+            removed.extend(keys.iter().filter_map(|key| shard.remove(key)));
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_insert_read_update_remove() {
+        let map = ShardedMap::default();
+        assert_eq!(map.insert("a".to_string(), 1), None);
+        assert_eq!(map.insert("a".to_string(), 2), Some(1));
+        assert_eq!(map.read("a", |value| *value), Some(2));
+        assert_eq!(map.update("a", |value| { *value += 1; *value }), Some(3));
+        assert_eq!(map.update("missing", |value| *value), None);
+        assert_eq!(map.remove("a"), Some(3));
+        assert_eq!(map.read("a", |value| *value), None);
+    }
+
+    #[test]
+    fn test_scans_cover_every_shard() {
+        let map = ShardedMap::default();
+        for i in 0..100 {
+            map.insert(i.to_string(), i);
+        }
+        let mut even = map.filter_map(|value| (value % 2 == 0).then_some(*value));
+        even.sort();
+        assert_eq!(even, (0..100).step_by(2).collect::<Vec<_>>());
+
+        let removed = map.remove_where(|value| *value >= 90);
+        assert_eq!(removed.len(), 10);
+        assert_eq!(map.filter_map(|value| Some(*value)).len(), 90);
+    }
+
+    #[test]
+    fn test_single_shard_and_zero_count() {
+        for count in [0, 1] {
+            let map = ShardedMap::with_shards(count);
+            map.insert("a".to_string(), 1);
+            map.insert("b".to_string(), 2);
+            assert_eq!(map.shards.len(), 1);
+            assert_eq!(map.filter_map(|value| Some(*value)).len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_concurrent_updates_are_not_lost() {
+        let map = Arc::new(ShardedMap::default());
+        for key in 0..8 {
+            map.insert(key.to_string(), 0);
+        }
+        let workers: Vec<_> = (0..8)
+            .map(|worker| {
+                let map = Arc::clone(&map);
+                thread::spawn(move || {
+                    for i in 0..1000 {
+                        map.update(&((worker + i) % 8).to_string(), |value| *value += 1);
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert_eq!(map.filter_map(|value| Some(*value)).iter().sum::<u32>(), 8000);
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+use log::info;
+
+use crate::keepalive::header_only;
+use crate::outbound::PRIORITY_LEVELS;
+use crate::persistence::StateStore;
+use crate::shard::ShardedMap;
+use crate::pdu::UNBIND;
+use crate::router::ConnectionManager;
+use crate::session::Session;
+
+// Orderly stop on Ctrl+C or SIGTERM: new connections are refused while every bound session is
+// sent an UNBIND and given time to answer it
+#[derive(Debug, Default)]
+pub struct ShutdownState {
+    draining: AtomicBool,
+    awaiting: Mutex<HashMap<String, u32>>, // connection_id -> sequence number of its UNBIND
+    acknowledged: Condvar,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnbindSummary {
+    pub sent: usize,
+    pub acknowledged: usize,
+}
+
+impl ShutdownState {
+    pub fn draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    // Whether this UNBIND_RESP answers the shutdown UNBIND sent on `connection_id`
+    pub fn acknowledge(&self, connection_id: &str, sequence_number: u32) -> bool {
+        let mut awaiting = self.awaiting.lock().unwrap();
+        if awaiting.get(connection_id) != Some(&sequence_number) {
+            return false;
+        }
+        awaiting.remove(connection_id);
+        self.acknowledged.notify_all();
+        true
+    }
+}
+
+// Unbinds every bound session, waits up to `timeout` for the UNBIND_RESPs and then closes the
+// connections that did not answer
+pub fn unbind_all(
+    sessions: &ShardedMap<Session>,
+    connection_manager: &ConnectionManager,
+    state_store: &StateStore,
+    timeout: Duration,
+) -> UnbindSummary {
+    let shutdown = &connection_manager.shutdown;
+    shutdown.draining.store(true, Ordering::Relaxed);
+
+    let bound = sessions.filter_map(|session| if session.bound { session.connection_id.clone() } else { None });
+    let mut sent = 0;
+    for connection_id in bound {
+        let Some(queue) = connection_manager.get_connection(&connection_id) else {
+            continue;
+        };
+        let sequence_number = state_store.next_sequence();
+        shutdown.awaiting.lock().unwrap().insert(connection_id.clone(), sequence_number);
+        // Ahead of any queued traffic, as the session will not be read from again
+        if queue.push(PRIORITY_LEVELS as u8 - 1, header_only(UNBIND, sequence_number)).is_ok() {
+            sent += 1;
+        } else {
+            shutdown.awaiting.lock().unwrap().remove(&connection_id);
+        }
+    }
+    info!("👋 Sent UNBIND to {} bound sessions, waiting up to {:?} for UNBIND_RESP", sent, timeout);
+
+    let awaiting = shutdown.awaiting.lock().unwrap();
+    let (awaiting, _) = shutdown
+        .acknowledged
+        .wait_timeout_while(awaiting, timeout, |awaiting| !awaiting.is_empty())
+        .unwrap();
+    for connection_id in awaiting.keys() {
+        info!("⚠️  {} did not answer UNBIND, closing it", connection_id);
+    }
+    let acknowledged = sent - awaiting.len();
+    drop(awaiting);
+
+    connection_manager.drop_connections(sessions, |_| true);
+    UnbindSummary { sent, acknowledged }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::sync::Arc;
+    use std::thread;
+
+    use crate::codec::PduReadBuffer;
+    use crate::transport::SmppStream;
+    use crate::config::Config;
+    use crate::pdu::{SmppPdu, BIND_TRANSCEIVER, ESME_ROK, UNBIND_RESP};
+    use crate::server::UssdSmppServer;
+
+    fn bind(server: &UssdSmppServer, system_id: &str) -> (SmppStream, PduReadBuffer) {
+        let mut client = server.connect();
+        let body = format!("{}\0secret\0USSD\0\x34\x01\x01\0", system_id).into_bytes();
+        let pdu = SmppPdu::new(BIND_TRANSCEIVER, ESME_ROK, 1, body);
+        client.write_all(&pdu.to_bytes()).unwrap();
+        let mut buffer = PduReadBuffer::new();
+        assert_eq!(buffer.read_pdu(&mut client).unwrap().header.command_status, ESME_ROK);
+        (client, buffer)
+    }
+
+    #[test]
+    fn test_unbind_all_waits_for_answers_then_refuses_connections() {
+        let mut config = Config::default();
+        config.smpp.enquire_link_interval = 0;
+        let server = UssdSmppServer::new(config);
+        let (mut polite, mut polite_buffer) = bind(&server, "ForwardingClient");
+        let (mut silent, mut silent_buffer) = bind(&server, "USSDMobileUser");
+
+        let sessions = Arc::clone(&server.sessions);
+        let connection_manager = server.connection_manager.clone();
+        let state_store = Arc::clone(&server.state_store);
+        let drain = thread::spawn(move || unbind_all(&sessions, &connection_manager, &state_store, Duration::from_millis(500)));
+
+        let unbind = polite_buffer.read_pdu(&mut polite).unwrap();
+        assert_eq!(unbind.header.command_id, UNBIND);
+        let response = header_only(UNBIND_RESP, unbind.header.sequence_number);
+        polite.write_all(&response.to_bytes()).unwrap();
+        assert_eq!(silent_buffer.read_pdu(&mut silent).unwrap().header.command_id, UNBIND);
+
+        assert_eq!(drain.join().unwrap(), UnbindSummary { sent: 2, acknowledged: 1 });
+        // Both connections are closed, and new ones are turned away
+        assert!(polite_buffer.read_pdu(&mut polite).is_err());
+        assert!(silent_buffer.read_pdu(&mut silent).is_err());
+        let mut late = server.connect();
+        assert_eq!(late.read(&mut [0; 16]).unwrap_or(0), 0);
+    }
+}
@@ -0,0 +1,131 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub use ussd_common::utc::utc_parts;
+
+// SMPP 3.4 time format: "YYMMDDhhmmsstnnp" where p is '+'/'-' (absolute, nn quarter hours
+// from UTC) or 'R' (relative to now). An empty string means "not set".
+pub fn parse_smpp_time(value: &str, now: SystemTime) -> Option<SystemTime> {
+    let value = value.trim();
+    if value.len() != 16 || !value.is_ascii() {
+        return None;
+    }
+
+    let field = |range: std::ops::Range<usize>| value[range].parse::<u64>().ok();
+    let (yy, mm, dd) = (field(0..2)?, field(2..4)?, field(4..6)?);
+    let (hh, mi, ss) = (field(6..8)?, field(8..10)?, field(10..12)?);
+    let tenths = field(12..13)?;
+    let quarters = field(13..15)?;
+
+    match &value[15..] {
+        "R" => {
+            // Relative periods use 365-day years and 30-day months
+            let days = yy * 365 + mm * 30 + dd;
+            let secs = ((days * 24 + hh) * 60 + mi) * 60 + ss;
+            Some(now + Duration::from_secs(secs) + Duration::from_millis(tenths * 100))
+        }
+        sign @ ("+" | "-") => {
+            if !(1..=12).contains(&mm) || !(1..=31).contains(&dd) || hh > 23 || mi > 59 || ss > 59 {
+                return None;
+            }
+            let days = days_from_civil(2000 + yy as i64, mm as i64, dd as i64);
+            let local = days * 86_400 + (hh * 3600 + mi * 60 + ss) as i64;
+            let offset = quarters as i64 * 15 * 60;
+            // "+" means local time is ahead of UTC
+            let utc = if sign == "+" { local - offset } else { local + offset };
+            let utc = u64::try_from(utc).ok()?;
+            Some(UNIX_EPOCH + Duration::from_secs(utc) + Duration::from_millis(tenths * 100))
+        }
+        _ => None,
+    }
+}
+
+// "YYMMDDhhmm" (UTC) as used in delivery receipt submit/done dates
+pub fn receipt_date(time: SystemTime) -> String {
+    let (year, month, day, hour, minute, _) = utc_parts(time);
+    format!("{:02}{:02}{:02}{:02}{:02}", year % 100, month, day, hour, minute)
+}
+
+// Absolute "YYMMDDhhmmss000+" (UTC), as in QUERY_SM_RESP's final_date
+pub fn absolute_time(time: SystemTime) -> String {
+    let (year, month, day, hour, minute, second) = utc_parts(time);
+    format!("{:02}{:02}{:02}{:02}{:02}{:02}000+", year % 100, month, day, hour, minute, second)
+}
+
+// Days since 1970-01-01 for a proleptic Gregorian date (Howard Hinnant's algorithm)
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ussd_common::utc::civil_from_days;
+
+    // 2024-02-29 12:00:00 UTC
+    const LEAP_DAY_NOON: u64 = 1_709_208_000;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_days_from_civil() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        assert_eq!(days_from_civil(2000, 2, 29), 11_016);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        assert_eq!(days_from_civil(2024, 2, 29), 19_782);
+        for days in (-1_000..40_000).step_by(37) {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn test_absolute_time_and_quarter_hour_sign() {
+        let now = at(0);
+        assert_eq!(parse_smpp_time("240229120000000+", now), Some(at(LEAP_DAY_NOON)));
+        // Local time one hour ahead of UTC is an hour earlier in UTC, and behind is later
+        assert_eq!(parse_smpp_time("240229120000004+", now), Some(at(LEAP_DAY_NOON - 3600)));
+        assert_eq!(parse_smpp_time("240229120000004-", now), Some(at(LEAP_DAY_NOON + 3600)));
+        assert_eq!(
+            parse_smpp_time("240229120000502+", now),
+            Some(at(LEAP_DAY_NOON - 1800) + Duration::from_millis(500))
+        );
+    }
+
+    #[test]
+    fn test_relative_time() {
+        let now = at(LEAP_DAY_NOON);
+        assert_eq!(parse_smpp_time("000001020304000R", now), Some(now + Duration::from_secs(93_784)));
+        // Relative months are 30 days and years 365, whatever the calendar says
+        assert_eq!(parse_smpp_time("010100000000000R", now), Some(now + Duration::from_secs(395 * 86_400)));
+        assert_eq!(parse_smpp_time("000000000010100R", now), Some(now + Duration::from_millis(10_100)));
+    }
+
+    #[test]
+    fn test_rejected_values() {
+        let now = at(0);
+        for value in ["", "240229120000000", "241329120000000+", "240229250000000+", "2402291200000a0+", "240229120000000X"] {
+            assert_eq!(parse_smpp_time(value, now), None, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn test_receipt_date() {
+        assert_eq!(receipt_date(at(LEAP_DAY_NOON + 59 * 60)), "2402291259");
+        assert_eq!(receipt_date(at(0)), "7001010000");
+    }
+
+    #[test]
+    fn test_absolute_time_round_trips() {
+        let time = at(LEAP_DAY_NOON + 59 * 60 + 7);
+        assert_eq!(absolute_time(time), "240229125907000+");
+        assert_eq!(parse_smpp_time(&absolute_time(time), at(0)), Some(time));
+    }
+}
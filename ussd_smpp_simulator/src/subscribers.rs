@@ -0,0 +1,45 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+// A test number's account, read by the menus and by {{balance}}-style placeholders
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Subscriber {
+    pub msisdn: String,
+    #[serde(default)]
+    pub balance: Option<String>, // e.g. "$25.50"
+    #[serde(default)]
+    pub data_balance: Option<String>, // e.g. "2.5GB"
+    #[serde(default)]
+    pub plan: Option<String>,
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+impl Subscriber {
+    // The fields that are set, by placeholder name
+    pub fn fields(&self) -> BTreeMap<String, String> {
+        [
+            ("balance", &self.balance),
+            ("data_balance", &self.data_balance),
+            ("plan", &self.plan),
+            ("language", &self.language),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.clone()?)))
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_set_fields_are_exposed() {
+        let subscriber: Subscriber = toml::from_str("msisdn = \"111\"\nbalance = \"$3.00\"\nplan = \"Prepaid\"").unwrap();
+        let fields = subscriber.fields();
+        assert_eq!(fields.len(), 2);
+        assert_eq!((fields["balance"].as_str(), fields["plan"].as_str()), ("$3.00", "Prepaid"));
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+// SUBMIT_SM rate limit per bound system_id; binds sharing a system_id share its bucket
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ThrottleConfig {
+    pub messages_per_second: f64, // Every system_id without its own limit (0 = unlimited)
+    pub burst: u32, // Submits allowed back to back (0 = one second's worth)
+    pub limits: Vec<ThrottleLimit>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ThrottleLimit {
+    pub system_id: String,
+    pub messages_per_second: f64,
+    #[serde(default)]
+    pub burst: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rate {
+    per_second: f64,
+    burst: f64,
+}
+
+impl ThrottleConfig {
+    fn rate_for(&self, system_id: &str) -> Option<Rate> {
+        let (per_second, burst) = match self.limits.iter().find(|limit| limit.system_id == system_id) {
+            Some(limit) => (limit.messages_per_second, limit.burst),
+            None => (self.messages_per_second, self.burst),
+        };
+        if per_second <= 0.0 {
+            return None;
+        }
+        let burst = if burst > 0 { burst as f64 } else { per_second.ceil() };
+        Some(Rate { per_second, burst })
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct ThrottleCounters {
+    pub accepted: u64,
+    pub throttled: u64,
+}
+
+// Token buckets that start full and refill at the configured rate
+#[derive(Debug)]
+pub struct Throttler {
+    config: ThrottleConfig,
+    buckets: Mutex<HashMap<String, Bucket>>,
+    counters: Mutex<BTreeMap<String, ThrottleCounters>>,
+}
+
+impl Throttler {
+    pub fn new(config: ThrottleConfig) -> Self {
+        Throttler { config, buckets: Mutex::new(HashMap::new()), counters: Mutex::new(BTreeMap::new()) }
+    }
+
+    // Whether a SUBMIT_SM from `system_id` may be processed now
+    pub fn admit(&self, system_id: &str) -> bool {
+        self.admit_at(system_id, Instant::now())
+    }
+
+    fn admit_at(&self, system_id: &str, now: Instant) -> bool {
+        let Some(rate) = self.config.rate_for(system_id) else {
+            return true;
+        };
+        let admitted = {
+            let mut buckets = self.buckets.lock().unwrap();
+            let bucket = buckets
+                .entry(system_id.to_string())
+                .or_insert(Bucket { tokens: rate.burst, refilled_at: now });
+            let elapsed = now.saturating_duration_since(bucket.refilled_at).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * rate.per_second).min(rate.burst);
+            bucket.refilled_at = now;
+            if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+                true
+            } else {
+                false
+            }
+        };
+
+        let mut counters = self.counters.lock().unwrap();
+        let counters = counters.entry(system_id.to_string()).or_default();
+        if admitted {
+            counters.accepted += 1;
+        } else {
+            counters.throttled += 1;
+        }
+        admitted
+    }
+
+    // Counters for every rate-limited system_id that has submitted
+    pub fn snapshot(&self) -> BTreeMap<String, ThrottleCounters> {
+        self.counters.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn throttler(toml: &str) -> Throttler {
+        Throttler::new(toml::from_str(toml).unwrap())
+    }
+
+    #[test]
+    fn test_burst_then_refill_at_rate() {
+        let throttler = throttler("messages_per_second = 10.0\nburst = 3\n");
+        let start = Instant::now();
+        let admitted = (0..5).filter(|_| throttler.admit_at("LoadTester", start)).count();
+        assert_eq!(admitted, 3);
+
+        // 10 per second refills one token every 100ms, never beyond the burst
+        assert!(!throttler.admit_at("LoadTester", start + Duration::from_millis(50)));
+        assert!(throttler.admit_at("LoadTester", start + Duration::from_millis(100)));
+        let later = start + Duration::from_secs(60);
+        assert_eq!((0..5).filter(|_| throttler.admit_at("LoadTester", later)).count(), 3);
+
+        let counters = throttler.snapshot()["LoadTester"];
+        assert_eq!((counters.accepted, counters.throttled), (7, 5));
+    }
+
+    #[test]
+    fn test_limits_per_system_id() {
+        let throttler = throttler(
+            "[[limits]]\nsystem_id = \"LoadTester\"\nmessages_per_second = 2.0\n\n\
+             [[limits]]\nsystem_id = \"Unlimited\"\nmessages_per_second = 0.0\n",
+        );
+        let now = Instant::now();
+        // Burst defaults to one second's worth
+        assert_eq!((0..5).filter(|_| throttler.admit_at("LoadTester", now)).count(), 2);
+        assert!((0..100).all(|_| throttler.admit_at("Unlimited", now)));
+        assert!((0..100).all(|_| throttler.admit_at("USSDMobileUser", now)));
+        // Unlimited binds are not counted
+        assert_eq!(throttler.snapshot().keys().collect::<Vec<_>>(), vec!["LoadTester"]);
+    }
+}
@@ -0,0 +1,267 @@
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::info;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TimelineConfig {
+    pub path: Option<String>, // Fault timeline file executed from server start
+}
+
+// Declarative incident script, e.g.
+//   [[events]]
+//   at = 60
+//   action = "set_failure_rate"
+//   percentage = 20.0
+//   codes = ["*123#"]
+#[derive(Debug, Clone, Deserialize)]
+pub struct FaultTimeline {
+    #[serde(default)]
+    pub events: Vec<TimelineEvent>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TimelineEvent {
+    pub at: u64, // Seconds after server start
+    #[serde(flatten)]
+    pub action: FaultAction,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum FaultAction {
+    SetFailureRate {
+        percentage: f64,
+        #[serde(default)]
+        codes: Vec<String>, // Empty means every code
+    },
+    SetNoResponseRate {
+        percentage: f64,
+        #[serde(default)]
+        codes: Vec<String>,
+    },
+    DropForwardingClients, // Disconnects them and refuses their binds until recover
+    Recover,
+}
+
+impl std::fmt::Display for FaultAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let scope = |codes: &[String]| if codes.is_empty() { "all codes".to_string() } else { codes.join(", ") };
+        match self {
+            FaultAction::SetFailureRate { percentage, codes } => write!(f, "failure rate {}% for {}", percentage, scope(codes)),
+            FaultAction::SetNoResponseRate { percentage, codes } => write!(f, "no-response rate {}% for {}", percentage, scope(codes)),
+            FaultAction::DropForwardingClients => write!(f, "drop all forwarding clients"),
+            FaultAction::Recover => write!(f, "recover"),
+        }
+    }
+}
+
+impl FaultTimeline {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("Could not read timeline {}: {}", path, e))?;
+        Self::parse(&content).map_err(|e| format!("Invalid timeline {}: {}", path, e))
+    }
+
+    // Events run in time order whatever order the file lists them in
+    fn parse(content: &str) -> Result<Self, toml::de::Error> {
+        let mut timeline: FaultTimeline = toml::from_str(content)?;
+        timeline.events.sort_by_key(|event| event.at);
+        Ok(timeline)
+    }
+
+    // Runs the events against `faults`; `drop_forwarding` disconnects the bound forwarding clients
+    pub fn spawn(self, faults: Arc<FaultState>, drop_forwarding: impl Fn() -> usize + Send + 'static) {
+        info!("🎬 Fault timeline loaded with {} event(s)", self.events.len());
+        let started = Instant::now();
+        thread::spawn(move || {
+            for event in self.events {
+                let due = started + Duration::from_secs(event.at);
+                thread::sleep(due.saturating_duration_since(Instant::now()));
+
+                info!("🎬 T+{}s: {}", event.at, event.action);
+                faults.apply(&event.action);
+                if let FaultAction::DropForwardingClients = event.action {
+                    info!("🔌 Dropped {} forwarding connection(s)", drop_forwarding());
+                }
+            }
+            info!("🎬 Fault timeline finished");
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ResponseRates {
+    pub success: f64,
+    pub failure: f64,
+    pub no_response: f64,
+}
+
+#[derive(Debug, Clone)]
+struct RateOverride {
+    failure: Option<f64>,
+    no_response: Option<f64>,
+    codes: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct Faults {
+    overrides: Vec<RateOverride>, // Applied in order, so later events win
+    forwarding_down: bool,
+}
+
+// Faults currently in effect and the random source that applies the response rates, shared by
+// every connection handler
+#[derive(Debug)]
+pub struct FaultState {
+    faults: Mutex<Faults>,
+    rng: Mutex<StdRng>,
+}
+
+impl Default for FaultState {
+    fn default() -> Self {
+        FaultState::seeded(None)
+    }
+}
+
+impl FaultState {
+    // A fixed seed replays the same sequence of rolls, so a run's outcomes can be reproduced
+    pub fn seeded(seed: Option<u64>) -> Self {
+        let rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        FaultState { faults: Mutex::new(Faults::default()), rng: Mutex::new(rng) }
+    }
+
+    // Uniform in [0, 100), compared against the cumulative response percentages
+    pub fn roll(&self) -> f64 {
+        self.rng.lock().unwrap().gen_range(0.0..100.0)
+    }
+
+    // Other simulated randomness draws from the same source, so a seed replays it too
+    pub fn with_rng<R>(&self, f: impl FnOnce(&mut StdRng) -> R) -> R {
+        f(&mut self.rng.lock().unwrap())
+    }
+
+    pub fn apply(&self, action: &FaultAction) {
+        let mut faults = self.faults.lock().unwrap();
+        match action {
+            FaultAction::SetFailureRate { percentage, codes } => faults.overrides.push(RateOverride {
+                failure: Some(*percentage),
+                no_response: None,
+                codes: codes.clone(),
+            }),
+            FaultAction::SetNoResponseRate { percentage, codes } => faults.overrides.push(RateOverride {
+                failure: None,
+                no_response: Some(*percentage),
+                codes: codes.clone(),
+            }),
+            FaultAction::DropForwardingClients => faults.forwarding_down = true,
+            FaultAction::Recover => *faults = Faults::default(),
+        }
+    }
+
+    // Drops every rate override, leaving other faults in effect
+    pub fn clear_rates(&self) {
+        self.faults.lock().unwrap().overrides.clear();
+    }
+
+    // Configured rates for `code` with any timeline overrides applied; success takes up the rest
+    pub fn rates_for(&self, code: &str, base: ResponseRates) -> ResponseRates {
+        let faults = self.faults.lock().unwrap();
+        let matching = faults
+            .overrides
+            .iter()
+            .filter(|rule| rule.codes.is_empty() || rule.codes.iter().any(|c| c == code));
+
+        let mut rates = base;
+        let mut overridden = false;
+        for rule in matching {
+            rates.failure = rule.failure.unwrap_or(rates.failure);
+            rates.no_response = rule.no_response.unwrap_or(rates.no_response);
+            overridden = true;
+        }
+        if overridden {
+            rates.success = (100.0 - rates.failure - rates.no_response).max(0.0);
+        }
+        rates
+    }
+
+    pub fn forwarding_down(&self) -> bool {
+        self.faults.lock().unwrap().forwarding_down
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: ResponseRates = ResponseRates { success: 90.0, failure: 5.0, no_response: 5.0 };
+
+    #[test]
+    fn test_example_timeline_parses_in_order() {
+        let timeline = FaultTimeline::parse(include_str!("../fault_timeline.toml")).unwrap();
+        let times: Vec<u64> = timeline.events.iter().map(|event| event.at).collect();
+        assert_eq!(times, vec![60, 90, 120, 180]);
+        assert!(matches!(
+            &timeline.events[0].action,
+            FaultAction::SetFailureRate { percentage, codes } if *percentage == 20.0 && codes == &["*123#"]
+        ));
+        assert!(matches!(timeline.events[3].action, FaultAction::Recover));
+    }
+
+    #[test]
+    fn test_events_are_sorted_and_unknown_actions_rejected() {
+        let timeline = FaultTimeline::parse(
+            "[[events]]\nat = 30\naction = \"recover\"\n\n[[events]]\nat = 5\naction = \"drop_forwarding_clients\"\n",
+        )
+        .unwrap();
+        assert!(matches!(timeline.events[0].action, FaultAction::DropForwardingClients));
+        assert_eq!(timeline.events[1].at, 30);
+        assert!(FaultTimeline::parse("[[events]]\nat = 1\naction = \"explode\"\n").is_err());
+    }
+
+    #[test]
+    fn test_overrides_are_scoped_to_their_codes() {
+        let faults = FaultState::default();
+        faults.apply(&FaultAction::SetFailureRate { percentage: 40.0, codes: vec!["*123#".to_string()] });
+        assert_eq!(faults.rates_for("*123#", BASE), ResponseRates { success: 55.0, failure: 40.0, no_response: 5.0 });
+        assert_eq!(faults.rates_for("*100#", BASE), BASE);
+    }
+
+    #[test]
+    fn test_seeded_rolls_replay_and_spread_evenly() {
+        let faults = FaultState::seeded(Some(42));
+        let first: Vec<f64> = (0..100).map(|_| faults.roll()).collect();
+        let again = FaultState::seeded(Some(42));
+        assert!(first.iter().all(|roll| *roll == again.roll()));
+
+        // 4% failures out of 100000 rolls should land well within a percentage point
+        let faults = FaultState::seeded(Some(7));
+        let failures = (0..100_000).filter(|_| faults.roll() < 4.0).count();
+        assert!((3_500..4_500).contains(&failures), "{} failures", failures);
+    }
+
+    #[test]
+    fn test_later_events_win_and_recover_resets() {
+        let faults = FaultState::default();
+        faults.apply(&FaultAction::SetFailureRate { percentage: 40.0, codes: Vec::new() });
+        faults.apply(&FaultAction::SetFailureRate { percentage: 10.0, codes: vec!["*123#".to_string()] });
+        faults.apply(&FaultAction::SetNoResponseRate { percentage: 70.0, codes: Vec::new() });
+        faults.apply(&FaultAction::DropForwardingClients);
+        assert_eq!(faults.rates_for("*123#", BASE), ResponseRates { success: 20.0, failure: 10.0, no_response: 70.0 });
+        // Rates past 100% leave no room for success rather than going negative
+        assert_eq!(faults.rates_for("*100#", BASE).success, 0.0);
+        assert!(faults.forwarding_down());
+
+        faults.apply(&FaultAction::Recover);
+        assert_eq!(faults.rates_for("*123#", BASE), BASE);
+        assert!(!faults.forwarding_down());
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+// Complete USSD dialogues written as they end, so a bug seen in a run can be replayed later
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TranscriptConfig {
+    pub file: String, // JSON Lines, one dialogue per line, appended to (empty disables recording)
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Dialog {
+    pub msisdn: String,
+    pub service_code: String,
+    pub started_at: u64, // Milliseconds since the Unix epoch
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Step {
+    // What the subscriber dialled or replied, `at_ms` after the dialogue started
+    Input { at_ms: u64, input: String },
+    // A screen sent to the subscriber; a service_op marks the screen that closed the dialogue
+    Response { at_ms: u64, response: String, service_op: Option<u8> },
+}
+
+struct OpenDialog {
+    dialog: Dialog,
+    started: Instant,
+}
+
+impl OpenDialog {
+    fn at_ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+}
+
+// Collects each MSISDN's dialogue until it ends. Does nothing until started with a file.
+#[derive(Default)]
+pub struct TranscriptRecorder {
+    out: Mutex<Option<BufWriter<File>>>,
+    open: Mutex<HashMap<String, OpenDialog>>,
+}
+
+impl std::fmt::Debug for TranscriptRecorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TranscriptRecorder").field("enabled", &self.enabled()).finish()
+    }
+}
+
+impl TranscriptRecorder {
+    pub fn start(&self, config: &TranscriptConfig) -> io::Result<()> {
+        if config.file.is_empty() {
+            return Ok(());
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&config.file)?;
+        info!("📝 Recording USSD dialogues to {}", config.file);
+        *self.out.lock().unwrap() = Some(BufWriter::new(file));
+        Ok(())
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.out.lock().unwrap().is_some()
+    }
+
+    // A service code starts a new dialogue; any other input continues the open one
+    pub fn input(&self, msisdn: &str, text: &str) {
+        if !self.enabled() {
+            return;
+        }
+        let starts_dialog = text.starts_with('*') && text.ends_with('#');
+        let mut open = self.open.lock().unwrap();
+        if starts_dialog && let Some(finished) = open.remove(msisdn) {
+            self.write(&finished.dialog);
+        }
+        let dialog = open.entry(msisdn.to_string()).or_insert_with(|| OpenDialog {
+            dialog: Dialog {
+                msisdn: msisdn.to_string(),
+                service_code: text.to_string(),
+                started_at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+                steps: Vec::new(),
+            },
+            started: Instant::now(),
+        });
+        let at_ms = dialog.at_ms();
+        dialog.dialog.steps.push(Step::Input { at_ms, input: text.to_string() });
+    }
+
+    // A screen with a service_op (USSD_NOTIFY or USSD_TERMINATE_NOTIFY) ends the dialogue
+    pub fn response(&self, msisdn: &str, text: &str, service_op: Option<u8>) {
+        let mut open = self.open.lock().unwrap();
+        let Some(dialog) = open.get_mut(msisdn) else {
+            return;
+        };
+        let at_ms = dialog.at_ms();
+        dialog.dialog.steps.push(Step::Response { at_ms, response: text.to_string(), service_op });
+        if service_op.is_some() && let Some(finished) = open.remove(msisdn) {
+            self.write(&finished.dialog);
+        }
+    }
+
+    // The session ended without a closing screen, e.g. it timed out
+    pub fn finish(&self, msisdn: &str) {
+        if let Some(finished) = self.open.lock().unwrap().remove(msisdn) {
+            self.write(&finished.dialog);
+        }
+    }
+
+    // Writes every dialogue still open, for shutdown
+    pub fn finish_all(&self) {
+        let open: Vec<OpenDialog> = self.open.lock().unwrap().drain().map(|(_, dialog)| dialog).collect();
+        for finished in open {
+            self.write(&finished.dialog);
+        }
+    }
+
+    fn write(&self, dialog: &Dialog) {
+        let mut out = self.out.lock().unwrap();
+        let Some(writer) = out.as_mut() else {
+            return;
+        };
+        let written = serde_json::to_string(dialog)
+            .map_err(io::Error::other)
+            .and_then(|line| writeln!(writer, "{}", line))
+            .and_then(|_| writer.flush());
+        if let Err(e) = written {
+            info!("⚠️  Transcript recording stopped: {}", e);
+            *out = None;
+        }
+    }
+}
+
+// Every dialogue in a transcript file, oldest first
+pub fn read(path: &str) -> io::Result<Vec<Dialog>> {
+    fs::read_to_string(path)?
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{} line {}: {}", path, index + 1, e)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dialogs_are_written_as_they_end() {
+        let path = std::env::temp_dir().join(format!("ussd_transcript_{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let _ = fs::remove_file(&path);
+        let recorder = TranscriptRecorder::default();
+        recorder.input("111", "*123#"); // Not recorded before start
+        recorder.start(&TranscriptConfig { file: path.clone() }).unwrap();
+
+        recorder.input("111", "*123#");
+        recorder.response("111", "Welcome", None);
+        recorder.input("222", "*555#");
+        recorder.input("111", "0");
+        recorder.response("111", "Goodbye", Some(3));
+        // A reply without an open dialogue is not a dialogue of its own
+        recorder.response("333", "Orphan", None);
+        // A new service code closes the dialogue it interrupts
+        recorder.input("222", "*123#");
+        recorder.finish_all();
+
+        let dialogs = read(&path).unwrap();
+        let codes: Vec<(&str, &str)> = dialogs.iter().map(|d| (d.msisdn.as_str(), d.service_code.as_str())).collect();
+        assert_eq!(codes, vec![("111", "*123#"), ("222", "*555#"), ("222", "*123#")]);
+        assert_eq!(dialogs[0].steps.len(), 4);
+        assert!(matches!(&dialogs[0].steps[3], Step::Response { response, service_op: Some(3), .. } if response == "Goodbye"));
+        assert!(matches!(&dialogs[0].steps[2], Step::Input { input, .. } if input == "0"));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use ussd_common::tls::TlsStream;
+
+// A connection the server can serve: a socket, a TLS session, or one end of an in-process pipe pair
+#[derive(Debug)]
+pub enum SmppStream {
+    Tcp(TcpStream),
+    Tls(TlsStream),
+    Channel(ChannelStream),
+}
+
+impl SmppStream {
+    pub fn try_clone(&self) -> io::Result<SmppStream> {
+        match self {
+            SmppStream::Tcp(stream) => stream.try_clone().map(SmppStream::Tcp),
+            SmppStream::Tls(stream) => stream.try_clone().map(SmppStream::Tls),
+            SmppStream::Channel(stream) => Ok(SmppStream::Channel(stream.clone())),
+        }
+    }
+
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        match self {
+            SmppStream::Tcp(stream) => stream.shutdown(how),
+            SmppStream::Tls(stream) => stream.shutdown(how),
+            SmppStream::Channel(stream) => {
+                stream.shutdown();
+                Ok(())
+            }
+        }
+    }
+
+    // None for in-process connections
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        match self {
+            SmppStream::Tcp(stream) => stream.peer_addr().ok(),
+            SmppStream::Tls(stream) => stream.peer_addr().ok(),
+            SmppStream::Channel(_) => None,
+        }
+    }
+
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            SmppStream::Tcp(stream) => stream.set_read_timeout(timeout),
+            SmppStream::Tls(stream) => stream.set_read_timeout(timeout),
+            SmppStream::Channel(stream) => {
+                stream.read_timeout = timeout;
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Read for SmppStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            SmppStream::Tcp(stream) => stream.read(buf),
+            SmppStream::Tls(stream) => stream.read(buf),
+            SmppStream::Channel(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for SmppStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            SmppStream::Tcp(stream) => stream.write(buf),
+            SmppStream::Tls(stream) => stream.write(buf),
+            SmppStream::Channel(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            SmppStream::Tcp(stream) => stream.flush(),
+            SmppStream::Tls(stream) => stream.flush(),
+            SmppStream::Channel(_) => Ok(()),
+        }
+    }
+}
+
+// Two connected in-process streams; bytes written to one are read from the other
+pub fn channel_pair() -> (SmppStream, SmppStream) {
+    let a_to_b = Arc::new(Pipe::default());
+    let b_to_a = Arc::new(Pipe::default());
+    let a = ChannelStream { inbound: Arc::clone(&b_to_a), outbound: Arc::clone(&a_to_b), read_timeout: None };
+    let b = ChannelStream { inbound: a_to_b, outbound: b_to_a, read_timeout: None };
+    (SmppStream::Channel(a), SmppStream::Channel(b))
+}
+
+#[derive(Debug, Default)]
+struct Pipe {
+    state: Mutex<PipeState>,
+    readable: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct PipeState {
+    bytes: VecDeque<u8>,
+    closed: bool,
+}
+
+impl Pipe {
+    fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.readable.notify_all();
+    }
+}
+
+// Clones share the same pipes, like `TcpStream::try_clone`
+#[derive(Debug, Clone)]
+pub struct ChannelStream {
+    inbound: Arc<Pipe>,
+    outbound: Arc<Pipe>,
+    read_timeout: Option<Duration>,
+}
+
+impl ChannelStream {
+    // Both directions; pending reads on either end see end of stream
+    fn shutdown(&self) {
+        self.inbound.close();
+        self.outbound.close();
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let deadline = self.read_timeout.map(|timeout| Instant::now() + timeout);
+        let mut state = self.inbound.state.lock().unwrap();
+        while state.bytes.is_empty() && !state.closed {
+            state = match deadline {
+                None => self.inbound.readable.wait(state).unwrap(),
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        return Err(io::Error::new(io::ErrorKind::WouldBlock, "Read timed out"));
+                    }
+                    self.inbound.readable.wait_timeout(state, remaining).unwrap().0
+                }
+            };
+        }
+
+        let len = buf.len().min(state.bytes.len());
+        for (slot, byte) in buf.iter_mut().zip(state.bytes.drain(..len)) {
+            *slot = byte;
+        }
+        Ok(len)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.outbound.state.lock().unwrap();
+        if state.closed {
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "Channel closed"));
+        }
+        state.bytes.extend(buf);
+        self.outbound.readable.notify_all();
+        Ok(buf.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bytes_cross_in_both_directions() {
+        let (mut a, mut b) = channel_pair();
+        a.write_all(b"bind").unwrap();
+        b.write_all(b"resp").unwrap();
+
+        let mut buf = [0u8; 8];
+        assert_eq!(b.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf[..4], b"bind");
+        assert_eq!(a.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf[..4], b"resp");
+    }
+
+    #[test]
+    fn test_read_timeout_and_shutdown() {
+        let (mut a, b) = channel_pair();
+        a.set_read_timeout(Some(Duration::from_millis(10))).unwrap();
+        let mut buf = [0u8; 4];
+        assert_eq!(a.read(&mut buf).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+
+        // A clone shutting down ends the stream for every handle, as with sockets
+        b.try_clone().unwrap().shutdown(Shutdown::Both).unwrap();
+        assert_eq!(a.read(&mut buf).unwrap(), 0);
+        assert_eq!(a.write(b"x").unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+    }
+}
@@ -0,0 +1,342 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use ussd_common::http_client::{self, HttpTarget};
+
+// Session events POSTed as JSON, so an external pipeline can follow the traffic without the logs
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WebhooksConfig {
+    pub url: String, // http://host[:port]/path (empty disables webhooks)
+    pub events: Vec<EventKind>, // Which events are sent
+    pub headers: BTreeMap<String, String>, // Extra request headers, e.g. Authorization
+    pub timeout_ms: u64, // Connect, write and read timeout for one POST
+    pub queue_size: usize, // Events waiting to be sent; newer ones are dropped beyond this
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        WebhooksConfig {
+            url: String::new(),
+            events: vec![EventKind::SessionStart, EventKind::Request, EventKind::Response, EventKind::SessionEnd],
+            headers: BTreeMap::new(),
+            timeout_ms: 2000,
+            queue_size: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    SessionStart,
+    Request, // What the subscriber dialled or replied
+    Response, // A screen sent to the subscriber
+    SessionEnd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndReason {
+    Released, // A USSD_NOTIFY or USSD_TERMINATE_NOTIFY screen closed it
+    Timeout,
+    Replaced, // The subscriber dialled a new code
+    Shutdown,
+}
+
+// The JSON body of one POST
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct WebhookEvent {
+    pub event: EventKind,
+    pub session_id: String,
+    pub msisdn: String,
+    pub service_code: String,
+    pub timestamp: u64, // Milliseconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>, // For request and response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_op: Option<u8>, // For a response that closed the session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<EndReason>, // For session_end
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>, // For session_end
+}
+
+struct OpenSession {
+    session_id: String,
+    service_code: String,
+    started: Instant,
+}
+
+// Follows each MSISDN's session and queues its events for a background sender and any in-process
+// subscribers. Does nothing until started with a URL or subscribed to.
+#[derive(Default)]
+pub struct WebhookEmitter {
+    sender: Mutex<Option<SyncSender<WebhookEvent>>>,
+    events: Mutex<Vec<EventKind>>,
+    subscribers: Mutex<Vec<SyncSender<WebhookEvent>>>,
+    open: Mutex<HashMap<String, OpenSession>>,
+    pending: Arc<AtomicUsize>, // Queued or being sent, for `finish_all`
+    dropped: AtomicU64,
+}
+
+impl std::fmt::Debug for WebhookEmitter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebhookEmitter").field("enabled", &self.enabled()).finish()
+    }
+}
+
+impl WebhookEmitter {
+    pub fn start(&self, config: &WebhooksConfig) -> io::Result<()> {
+        if config.url.is_empty() {
+            return Ok(());
+        }
+        let target = HttpTarget::parse(&config.url, "webhooks.url").map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let (sender, receiver) = mpsc::sync_channel::<WebhookEvent>(config.queue_size.max(1));
+        let pending = Arc::clone(&self.pending);
+        let headers = config.headers.clone();
+        let timeout = Duration::from_millis(config.timeout_ms.max(1));
+        thread::spawn(move || {
+            let mut failing = false;
+            for event in receiver {
+                let body = serde_json::to_string(&event).unwrap_or_default();
+                match http_client::post_json(&target, &headers, &body, timeout) {
+                    Ok(_) if failing => {
+                        info!("🪝 Webhooks to {} are being delivered again", target);
+                        failing = false;
+                    }
+                    Ok(_) => {}
+                    // Logged once per outage, not once per event
+                    Err(e) if !failing => {
+                        info!("⚠️  Webhook POST to {} failed: {}", target, e);
+                        failing = true;
+                    }
+                    Err(_) => {}
+                }
+                pending.fetch_sub(1, Ordering::SeqCst);
+            }
+        });
+        info!("🪝 Sending session webhooks to {}", config.url);
+        *self.events.lock().unwrap() = config.events.clone();
+        *self.sender.lock().unwrap() = Some(sender);
+        Ok(())
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.sender.lock().unwrap().is_some() || !self.subscribers.lock().unwrap().is_empty()
+    }
+
+    // Every event from now on, whatever webhooks.events selects. Events are dropped for a
+    // subscriber more than `capacity` behind, and it is forgotten once the receiver is dropped.
+    pub fn subscribe(&self, capacity: usize) -> Receiver<WebhookEvent> {
+        let (sender, receiver) = mpsc::sync_channel(capacity.max(1));
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    // A request from the subscriber. A service code, or a session the emitter has not seen,
+    // starts a new session and ends the one it replaces.
+    pub fn request(&self, msisdn: &str, session_id: &str, service_code: &str, text: &str) {
+        if !self.enabled() {
+            return;
+        }
+        let starts_session = text.starts_with('*') && text.ends_with('#');
+        let mut open = self.open.lock().unwrap();
+        let known = open.get(msisdn).is_some_and(|session| session.session_id == session_id);
+        if starts_session || !known {
+            if let Some(replaced) = open.remove(msisdn) {
+                self.end(msisdn, &replaced, EndReason::Replaced);
+            }
+            let session = OpenSession { session_id: session_id.to_string(), service_code: service_code.to_string(), started: Instant::now() };
+            self.send(event(EventKind::SessionStart, msisdn, &session));
+            open.insert(msisdn.to_string(), session);
+        }
+        let session = &open[msisdn];
+        self.send(WebhookEvent { text: Some(text.to_string()), ..event(EventKind::Request, msisdn, session) });
+    }
+
+    // A screen sent to the subscriber; one with a service_op also ends the session
+    pub fn response(&self, msisdn: &str, text: &str, service_op: Option<u8>) {
+        let mut open = self.open.lock().unwrap();
+        let Some(session) = open.get(msisdn) else {
+            return;
+        };
+        self.send(WebhookEvent { text: Some(text.to_string()), service_op, ..event(EventKind::Response, msisdn, session) });
+        if service_op.is_some() && let Some(ended) = open.remove(msisdn) {
+            self.end(msisdn, &ended, EndReason::Released);
+        }
+    }
+
+    // The session ended without a closing screen, e.g. it timed out
+    pub fn finish(&self, msisdn: &str, reason: EndReason) {
+        if let Some(ended) = self.open.lock().unwrap().remove(msisdn) {
+            self.end(msisdn, &ended, reason);
+        }
+    }
+
+    // Ends every open session and waits up to `timeout` for the queue to drain, for shutdown
+    pub fn finish_all(&self, timeout: Duration) {
+        let open: Vec<(String, OpenSession)> = self.open.lock().unwrap().drain().collect();
+        for (msisdn, session) in open {
+            self.end(&msisdn, &session, EndReason::Shutdown);
+        }
+        let deadline = Instant::now() + timeout;
+        while self.pending.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn end(&self, msisdn: &str, session: &OpenSession, reason: EndReason) {
+        let duration_ms = session.started.elapsed().as_millis() as u64;
+        self.send(WebhookEvent { reason: Some(reason), duration_ms: Some(duration_ms), ..event(EventKind::SessionEnd, msisdn, session) });
+    }
+
+    // Never blocks the caller; a full queue drops the event
+    fn send(&self, event: WebhookEvent) {
+        self.subscribers.lock().unwrap().retain(|subscriber| !matches!(subscriber.try_send(event.clone()), Err(TrySendError::Disconnected(_))));
+        if !self.events.lock().unwrap().contains(&event.event) {
+            return;
+        }
+        let sender = self.sender.lock().unwrap();
+        let Some(sender) = sender.as_ref() else {
+            return;
+        };
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        if let Err(e) = sender.try_send(event) {
+            self.pending.fetch_sub(1, Ordering::SeqCst);
+            let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+            if matches!(e, TrySendError::Full(_)) && dropped.is_power_of_two() {
+                info!("⚠️  Webhook queue full, {} event(s) dropped so far", dropped);
+            }
+        }
+    }
+}
+
+fn event(kind: EventKind, msisdn: &str, session: &OpenSession) -> WebhookEvent {
+    WebhookEvent {
+        event: kind,
+        session_id: session.session_id.clone(),
+        msisdn: msisdn.to_string(),
+        service_code: session.service_code.clone(),
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+        text: None,
+        service_op: None,
+        reason: None,
+        duration_ms: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    // Accepts `count` POSTs, answering each with 204, and returns their bodies
+    fn receiver(count: usize) -> (String, thread::JoinHandle<Vec<(String, WebhookEvent)>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hooks", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            (0..count)
+                .map(|_| {
+                    let (stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut head = String::new();
+                    let mut length = 0;
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                            length = value.trim().parse().unwrap();
+                        }
+                        if line == "\r\n" {
+                            break;
+                        }
+                        head.push_str(&line);
+                    }
+                    let mut body = vec![0; length];
+                    reader.read_exact(&mut body).unwrap();
+                    (&stream).write_all(b"HTTP/1.1 204 No Content\r\n\r\n").unwrap();
+                    (head, serde_json::from_slice(&body).unwrap())
+                })
+                .collect()
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn test_session_events_are_posted_in_order() {
+        let (url, handle) = receiver(7);
+        let emitter = WebhookEmitter::default();
+        emitter.request("111", "S1", "*123#", "*123#"); // Not sent before start
+        let headers = BTreeMap::from([("Authorization".to_string(), "Bearer t".to_string())]);
+        emitter.start(&WebhooksConfig { url, headers, ..Default::default() }).unwrap();
+
+        emitter.request("111", "S1", "*123#", "*123#");
+        emitter.response("111", "Welcome", None);
+        emitter.request("111", "S1", "*123#", "0");
+        emitter.response("111", "Goodbye", Some(2));
+        // A screen with no open session is not an event
+        emitter.response("111", "Late", None);
+        emitter.request("222", "S2", "*555#", "*555#");
+        emitter.finish_all(Duration::from_secs(5));
+
+        let posts = handle.join().unwrap();
+        assert!(posts[0].0.starts_with("POST /hooks HTTP/1.1\r\n"));
+        assert!(posts[0].0.contains("Authorization: Bearer t\r\n"));
+        let events: Vec<(EventKind, &str, Option<&str>)> =
+            posts.iter().map(|(_, e)| (e.event, e.msisdn.as_str(), e.text.as_deref())).collect();
+        assert_eq!(events, vec![
+            (EventKind::SessionStart, "111", None),
+            (EventKind::Request, "111", Some("*123#")),
+            (EventKind::Response, "111", Some("Welcome")),
+            (EventKind::Request, "111", Some("0")),
+            (EventKind::Response, "111", Some("Goodbye")),
+            (EventKind::SessionEnd, "111", None),
+            (EventKind::SessionStart, "222", None),
+        ]);
+        assert_eq!(posts[4].1.service_op, Some(2));
+        assert_eq!(posts[5].1.reason, Some(EndReason::Released));
+        assert_eq!(posts[5].1.session_id, "S1");
+    }
+
+    #[test]
+    fn test_event_filter_and_end_reasons() {
+        let (url, handle) = receiver(2);
+        let emitter = WebhookEmitter::default();
+        emitter.start(&WebhooksConfig { url, events: vec![EventKind::SessionEnd], ..Default::default() }).unwrap();
+        emitter.request("111", "S1", "*123#", "*123#");
+        emitter.request("111", "S1", "*124#", "*124#");
+        emitter.finish("111", EndReason::Timeout);
+        emitter.finish_all(Duration::from_secs(5));
+
+        let reasons: Vec<(String, Option<EndReason>)> =
+            handle.join().unwrap().into_iter().map(|(_, e)| (e.service_code, e.reason)).collect();
+        assert_eq!(reasons, vec![
+            ("*123#".to_string(), Some(EndReason::Replaced)),
+            ("*124#".to_string(), Some(EndReason::Timeout)),
+        ]);
+    }
+
+    #[test]
+    fn test_subscribers_get_every_event_without_a_url() {
+        let emitter = WebhookEmitter::default();
+        let events = emitter.subscribe(2);
+        emitter.request("111", "S1", "*123#", "*123#");
+        emitter.response("111", "Welcome", None); // Over capacity, so dropped
+        let kinds: Vec<EventKind> = events.try_iter().map(|event| event.event).collect();
+        assert_eq!(kinds, vec![EventKind::SessionStart, EventKind::Request]);
+
+        drop(events);
+        emitter.finish("111", EndReason::Timeout);
+        assert!(!emitter.enabled());
+    }
+}
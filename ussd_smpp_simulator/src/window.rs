@@ -0,0 +1,174 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+// SUBMIT_SMs each connection is still owed a SUBMIT_SM_RESP for. A SUBMIT_SM takes a slot when
+// it is read and admitted and gives it back when its SUBMIT_SM_RESP (or GENERIC_NACK) is written.
+// One dropped by the no-response roll holds its slot until the client would have given up on it.
+#[derive(Debug, Default)]
+pub struct SubmitWindows {
+    connections: Mutex<HashMap<String, Window>>,
+}
+
+#[derive(Debug, Default)]
+struct Window {
+    system_id: String,
+    outstanding: Vec<Slot>,
+    peak: usize,
+    rejected: u64,
+}
+
+#[derive(Debug)]
+struct Slot {
+    sequence_number: u32,
+    expires: Option<Instant>, // None while the SUBMIT_SM is being answered; when a dropped one frees it
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WindowOccupancy {
+    pub system_id: String,
+    pub outstanding: usize,
+    pub peak: usize,
+    pub rejected: u64,
+}
+
+impl Window {
+    fn expire(&mut self, now: Instant) {
+        self.outstanding.retain(|slot| slot.expires.is_none_or(|expires| expires > now));
+    }
+}
+
+impl SubmitWindows {
+    // Whether SUBMIT_SM `sequence_number` fits in a window of `size` (0 = unlimited). An
+    // admitted one holds a slot until `answered` or `unanswered`.
+    pub fn admit(&self, connection_id: &str, system_id: &str, sequence_number: u32, size: usize) -> bool {
+        self.admit_at(connection_id, system_id, sequence_number, size, Instant::now())
+    }
+
+    fn admit_at(&self, connection_id: &str, system_id: &str, sequence_number: u32, size: usize, now: Instant) -> bool {
+        let mut connections = self.connections.lock().unwrap();
+        let window = connections.entry(connection_id.to_string()).or_default();
+        window.system_id = system_id.to_string();
+        window.expire(now);
+        if size > 0 && window.outstanding.len() >= size {
+            window.rejected += 1;
+            return false;
+        }
+        window.outstanding.push(Slot { sequence_number, expires: None });
+        window.peak = window.peak.max(window.outstanding.len());
+        true
+    }
+
+    // The SUBMIT_SM_RESP or GENERIC_NACK for `sequence_number` went out, freeing its slot. A
+    // response to a SUBMIT_SM the window refused has no slot to free.
+    pub fn answered(&self, connection_id: &str, sequence_number: u32) {
+        let mut connections = self.connections.lock().unwrap();
+        if let Some(window) = connections.get_mut(connection_id)
+            && let Some(index) = window
+                .outstanding
+                .iter()
+                .position(|slot| slot.expires.is_none() && slot.sequence_number == sequence_number)
+        {
+            window.outstanding.remove(index);
+        }
+    }
+
+    // Admitted SUBMIT_SM `sequence_number` will never be answered, so it keeps its slot for
+    // `timeout`, as long as the client waits for it. A zero `timeout` frees the slot at once.
+    pub fn unanswered(&self, connection_id: &str, sequence_number: u32, timeout: Duration) {
+        self.unanswered_at(connection_id, sequence_number, timeout, Instant::now());
+    }
+
+    fn unanswered_at(&self, connection_id: &str, sequence_number: u32, timeout: Duration, now: Instant) {
+        if timeout.is_zero() {
+            return self.answered(connection_id, sequence_number);
+        }
+        let mut connections = self.connections.lock().unwrap();
+        if let Some(slot) = connections.get_mut(connection_id).and_then(|window| {
+            window.outstanding.iter_mut().find(|slot| slot.expires.is_none() && slot.sequence_number == sequence_number)
+        }) {
+            slot.expires = Some(now + timeout);
+        }
+    }
+
+    pub fn remove(&self, connection_id: &str) {
+        self.connections.lock().unwrap().remove(connection_id);
+    }
+
+    // Occupancy of every open connection that has submitted, by connection_id
+    pub fn snapshot(&self) -> BTreeMap<String, WindowOccupancy> {
+        let now = Instant::now();
+        let mut connections = self.connections.lock().unwrap();
+        connections
+            .iter_mut()
+            .map(|(connection_id, window)| {
+                window.expire(now);
+                let occupancy = WindowOccupancy {
+                    system_id: window.system_id.clone(),
+                    outstanding: window.outstanding.len(),
+                    peak: window.peak,
+                    rejected: window.rejected,
+                };
+                (connection_id.clone(), occupancy)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_full_window_rejects_until_slots_expire() {
+        let windows = SubmitWindows::default();
+        let start = Instant::now();
+        let timeout = Duration::from_secs(30);
+        for sequence_number in 1..=2 {
+            assert!(windows.admit_at("conn_1", "LoadTester", sequence_number, 2, start));
+            windows.unanswered_at("conn_1", sequence_number, timeout, start);
+        }
+        assert!(!windows.admit_at("conn_1", "LoadTester", 3, 2, start));
+        // Other connections have windows of their own
+        assert!(windows.admit_at("conn_2", "LoadTester", 1, 2, start));
+        assert!(windows.admit_at("conn_1", "LoadTester", 4, 2, start + timeout));
+
+        let occupancy = &windows.snapshot()["conn_1"];
+        assert_eq!((occupancy.peak, occupancy.rejected), (2, 1));
+        windows.remove("conn_1");
+        assert!(!windows.snapshot().contains_key("conn_1"));
+    }
+
+    #[test]
+    fn test_slot_is_held_until_the_response_is_written() {
+        let windows = SubmitWindows::default();
+        let start = Instant::now();
+        assert!(windows.admit_at("conn_1", "LoadTester", 7, 1, start));
+        // Read and admitted but not yet answered, however long that takes
+        assert!(!windows.admit_at("conn_1", "LoadTester", 8, 1, start + Duration::from_secs(3600)));
+        assert_eq!(windows.snapshot()["conn_1"].outstanding, 1);
+
+        // The refusal's response has no slot to free
+        windows.answered("conn_1", 8);
+        assert_eq!(windows.snapshot()["conn_1"].outstanding, 1);
+        windows.answered("conn_1", 7);
+        assert_eq!(windows.snapshot()["conn_1"].outstanding, 0);
+        assert!(windows.admit_at("conn_1", "LoadTester", 9, 1, start));
+    }
+
+    #[test]
+    fn test_zero_timeout_frees_a_dropped_slot_at_once() {
+        let windows = SubmitWindows::default();
+        let start = Instant::now();
+        for sequence_number in 1..=50 {
+            assert!(windows.admit_at("conn_1", "USSDMobileUser", sequence_number, 0, start));
+            windows.unanswered_at("conn_1", sequence_number, Duration::ZERO, start);
+        }
+        let occupancy = &windows.snapshot()["conn_1"];
+        assert_eq!((occupancy.outstanding, occupancy.peak), (0, 1));
+        assert_eq!(occupancy.system_id, "USSDMobileUser");
+        assert!(windows.admit_at("conn_1", "USSDMobileUser", 51, 1, start));
+    }
+}
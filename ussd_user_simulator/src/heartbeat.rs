@@ -0,0 +1,138 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::info;
+use smpp_codec::{SmppPdu, ENQUIRE_LINK, ESME_ROK};
+
+use crate::reader::write_pdu;
+use crate::SmppStream;
+
+// What the reader and the heartbeat know about a connection: when the server was last heard
+// from, and whether the connection has been given up on
+pub struct Link {
+    last_heard: Mutex<Instant>,
+    lost: AtomicBool,
+}
+
+impl Link {
+    pub fn new() -> Self {
+        Link { last_heard: Mutex::new(Instant::now()), lost: AtomicBool::new(false) }
+    }
+
+    pub fn heard(&self) {
+        *self.last_heard.lock().unwrap() = Instant::now();
+    }
+
+    fn heard_since(&self, instant: Instant) -> bool {
+        *self.last_heard.lock().unwrap() >= instant
+    }
+
+    fn quiet_for(&self) -> Duration {
+        self.last_heard.lock().unwrap().elapsed()
+    }
+
+    pub fn lose(&self) {
+        self.lost.store(true, Ordering::Relaxed);
+    }
+
+    // The client reconnects before its next request once this is true
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Relaxed)
+    }
+}
+
+// Sends an ENQUIRE_LINK whenever the server has been silent for `idle`, and drops the connection
+// when nothing at all comes back within `patience`, which ends the reader thread too. Runs until
+// the returned sender is dropped.
+pub fn spawn(
+    writer: Arc<Mutex<SmppStream>>,
+    link: Arc<Link>,
+    sequence: Arc<AtomicU32>,
+    idle: Duration,
+    patience: Duration,
+    debug: bool,
+) -> Sender<()> {
+    let (stop, stopped) = mpsc::channel();
+    thread::spawn(move || beat(&writer, &link, &sequence, idle, patience, &stopped, debug));
+    stop
+}
+
+fn beat(
+    writer: &Mutex<SmppStream>,
+    link: &Link,
+    sequence: &AtomicU32,
+    idle: Duration,
+    patience: Duration,
+    stopped: &Receiver<()>,
+    debug: bool,
+) {
+    loop {
+        let wait = idle.saturating_sub(link.quiet_for());
+        if !wait.is_zero() {
+            if !sleep(stopped, wait) {
+                return;
+            }
+            continue;
+        }
+
+        let sequence_number = sequence.fetch_add(1, Ordering::Relaxed) + 1;
+        if debug {
+            info!("💓 Connection idle for {}ms, sending ENQUIRE_LINK seq={}", idle.as_millis(), sequence_number);
+        }
+        let sent_at = Instant::now();
+        if write_pdu(writer, &SmppPdu::new(ENQUIRE_LINK, ESME_ROK, sequence_number, Vec::new())).is_err() {
+            link.lose();
+            return;
+        }
+        if !sleep(stopped, patience) {
+            return;
+        }
+        if !link.heard_since(sent_at) {
+            info!("💔 No answer to ENQUIRE_LINK within {}ms, dropping the connection", patience.as_millis());
+            link.lose();
+            let _ = writer.lock().unwrap().shutdown();
+            return;
+        }
+    }
+}
+
+// False once the client has stopped the heartbeat
+fn sleep(stopped: &Receiver<()>, duration: Duration) -> bool {
+    matches!(stopped.recv_timeout(duration), Err(RecvTimeoutError::Timeout))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{TcpListener, TcpStream};
+
+    use super::*;
+    use crate::reader;
+
+    #[test]
+    fn test_unanswered_enquire_link_drops_the_connection() {
+        // A server that accepts the connection and reads from it but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = SmppStream::Tcp(TcpStream::connect(listener.local_addr().unwrap()).unwrap());
+        let (mut server, _) = listener.accept().unwrap();
+
+        let writer = Arc::new(Mutex::new(stream.try_clone().unwrap()));
+        let link = Arc::new(Link::new());
+        let inbox = reader::spawn(stream, Arc::clone(&writer), Arc::clone(&link), false);
+        let (idle, patience) = (Duration::from_millis(50), Duration::from_millis(300));
+        let _heartbeat = spawn(writer, Arc::clone(&link), Arc::new(AtomicU32::new(0)), idle, patience, false);
+
+        let started = Instant::now();
+        let enquire_link = SmppPdu::read_from(&mut server).unwrap();
+        assert_eq!((enquire_link.header.command_id, enquire_link.header.sequence_number), (ENQUIRE_LINK, 1));
+        assert!(!link.is_lost());
+
+        // The connection is dropped once the patience runs out, and the reader ends with it
+        assert!(SmppPdu::read_from(&mut server).is_err());
+        assert!(started.elapsed() >= patience);
+        assert!(link.is_lost());
+        assert!(inbox.recv_timeout(Duration::from_secs(5)).unwrap().is_err());
+    }
+}
@@ -0,0 +1,2219 @@
+// An interactive USSD phone, scripted dialogues, virtual subscribers and a load generator, all
+// speaking SMPP to the simulator. `run` is the whole command line.
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use log::{info, LevelFilter};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use smpp_codec::{
+    Bind, DeliverSm, OptionalParam, SmppError, SmppPdu, SubmitSm, SubmitSmResp, BIND_TRANSCEIVER, BIND_TRANSCEIVER_RESP,
+    DELIVER_SM, ESME_RINVBNDSTS, ESME_ROK, ESM_CLASS_USSD, INTERFACE_VERSION_34, RESPONSE_BIT, SUBMIT_SM, SUBMIT_SM_RESP,
+    TAG_SCREEN_CHARS, UNBIND, UNBIND_RESP, USSD_NOTIFY, USSD_TERMINATE_NOTIFY,
+};
+use ussd_common::encoding::{self, TextEncoding};
+use ussd_common::msisdn::MsisdnGenerator;
+use ussd_common::tls::{TlsClientConfig, TlsStream};
+use ussd_common::{logger, run_id};
+
+mod heartbeat;
+mod load;
+mod reader;
+mod report;
+mod script;
+mod stats;
+mod subscribers;
+
+use heartbeat::Link;
+use report::RequestRecord;
+use stats::PerformanceStats;
+
+
+// Enhanced Configuration structures
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct UserSimulatorConfig {
+    pub server: ServerConfig,
+    pub authentication: AuthConfig,
+    pub phone: PhoneConfig,
+    pub ui: UiConfig,
+    pub logging: LoggingConfig,
+    pub testing: TestingConfig,
+    pub advanced: AdvancedConfig,
+    #[serde(default)]
+    pub subscribers: SubscribersConfig,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    pub connection_timeout_ms: u64,
+    pub reconnect_attempts: u32,
+    pub keepalive_interval_ms: u64,
+    #[serde(default)]
+    pub tls: TlsClientConfig, // For servers that only accept smpps:// connections
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AuthConfig {
+    pub system_id: String,
+    pub password: String,
+    pub system_type: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PhoneConfig {
+    pub default_msisdn: String,
+    pub operator_name: String,
+    pub balance: f64,
+    pub data_balance: f64,
+    pub country_code: String,
+    pub network_code: String,
+    #[serde(default)]
+    pub generator: MsisdnGenerator, // Its country and network codes default to the ones above
+}
+
+impl PhoneConfig {
+    // The MSISDN of the `index`th simulated subscriber: from the generator, or counting up from
+    // default_msisdn when it is fixed
+    pub fn msisdn(&self, index: usize) -> String {
+        if let Some(msisdn) = self.generator.nth(index as u64) {
+            return msisdn;
+        }
+        match self.default_msisdn.parse::<u64>() {
+            Ok(number) => format!("{:0width$}", number + index as u64, width = self.default_msisdn.len()),
+            Err(_) => format!("{}{}", self.default_msisdn, index),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct UiConfig {
+    pub animation_delay_ms: u64,
+    pub auto_clear_screen: bool,
+    pub show_debug_info: bool,
+    pub show_performance_stats: bool,
+    pub session_timeout_ms: u64,
+    pub max_input_length: usize,
+    #[serde(default)]
+    pub screen_chars: u16, // Screen size declared to the server at bind (0 = not declared)
+    #[serde(default)]
+    pub stats_file: String, // Latency histograms written on exit, as JSON for a .json path and CSV otherwise (empty = none)
+}
+
+// Where the Performance Stats screen exports to when ui.stats_file is not set
+const DEFAULT_STATS_FILE: &str = "latency_stats.csv";
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LoggingConfig {
+    pub debug: bool,
+    pub log_file: String,
+    pub log_level: String,
+    pub enable_file_logging: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TestingConfig {
+    pub auto_test_on_startup: bool, // Run test_scenarios_file instead of the interactive phone, then exit (also --test)
+    pub test_scenarios_file: String,
+    pub performance_test_enabled: bool, // Run the load test instead of the interactive phone (also --load)
+    pub concurrent_sessions: u32,       // Binds in the load test, one MSISDN each
+    #[serde(default = "default_target_tps")]
+    pub target_tps: f64, // Requests per second across all binds (0 = as fast as they are answered)
+    #[serde(default = "default_duration_secs")]
+    pub duration_secs: u64,
+    #[serde(default)]
+    pub stages: Vec<LoadStage>, // Replace target_tps and duration_secs when given
+    #[serde(default)]
+    pub report_file: String, // Every request, or per-stage metrics under --load; JSON for .json, else CSV (empty = none)
+}
+
+// One stage of a staged load test, e.g. a ramp to 200 requests/s over a minute:
+//   [[testing.stages]]
+//   name = "ramp"
+//   duration_secs = 60
+//   tps = 200.0
+//   ramp = true
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LoadStage {
+    #[serde(default)]
+    pub name: String,
+    pub duration_secs: u64,
+    pub tps: f64,
+    #[serde(default)]
+    pub ramp: bool, // Climb or fall to tps from the previous stage's rate (0 for the first) instead of starting at it
+}
+
+fn default_target_tps() -> f64 {
+    10.0
+}
+
+fn default_duration_secs() -> u64 {
+    60
+}
+
+fn default_enquire_link_timeout_ms() -> u64 {
+    30000
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AdvancedConfig {
+    pub smpp_version: String,
+    pub enquire_link_interval_ms: u64,
+    #[serde(default = "default_enquire_link_timeout_ms")]
+    pub enquire_link_timeout_ms: u64, // Wait for an answer to an ENQUIRE_LINK before reconnecting
+    pub pdu_timeout_ms: u64,
+    pub max_concurrent_requests: u32,
+    #[serde(default)]
+    pub gsm7_packing: bool, // Must match the server's smpp.gsm7_packing
+}
+
+impl Default for UserSimulatorConfig {
+    fn default() -> Self {
+        UserSimulatorConfig {
+            server: ServerConfig {
+                host: "127.0.0.1".to_string(),
+                port: 9090,
+                connection_timeout_ms: 5000,
+                reconnect_attempts: 3,
+                keepalive_interval_ms: 30000,
+                tls: TlsClientConfig::default(),
+            },
+            authentication: AuthConfig {
+                system_id: "USSDMobileUser".to_string(),
+                password: "mobile123".to_string(),
+                system_type: "USSD".to_string(),
+            },
+            phone: PhoneConfig {
+                default_msisdn: "1234567890".to_string(),
+                operator_name: "MyTelecom".to_string(),
+                balance: 25.50,
+                data_balance: 2.5,
+                country_code: "1".to_string(),
+                network_code: "001".to_string(),
+                generator: MsisdnGenerator::default(),
+            },
+            ui: UiConfig {
+                animation_delay_ms: 800,
+                auto_clear_screen: true,
+                show_debug_info: false,
+                show_performance_stats: true,
+                session_timeout_ms: 30000,
+                max_input_length: 160,
+                screen_chars: 0,
+                stats_file: String::new(),
+            },
+            logging: LoggingConfig {
+                debug: false,
+                log_file: "ussd_simulator.log".to_string(),
+                log_level: "info".to_string(),
+                enable_file_logging: true,
+            },
+            testing: TestingConfig {
+                auto_test_on_startup: false,
+                test_scenarios_file: "test_scenarios.toml".to_string(),
+                performance_test_enabled: false,
+                concurrent_sessions: 1,
+                target_tps: default_target_tps(),
+                duration_secs: default_duration_secs(),
+                stages: Vec::new(),
+                report_file: String::new(),
+            },
+            advanced: AdvancedConfig {
+                smpp_version: "3.4".to_string(),
+                enquire_link_interval_ms: 60000,
+                enquire_link_timeout_ms: default_enquire_link_timeout_ms(),
+                pdu_timeout_ms: 10000,
+                max_concurrent_requests: 5,
+                gsm7_packing: false,
+            },
+            subscribers: SubscribersConfig::default(),
+        }
+    }
+}
+
+// A pool of virtual subscribers that play the test scenarios side by side, e.g.
+//   [subscribers]
+//   enabled = true
+//   range = "94770000000-94770000099"    # And/or msisdns = ["94771234567", ...]
+//   binds = 4
+// With neither, `count` subscribers take their numbers from `phone.generator`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct SubscribersConfig {
+    pub enabled: bool, // Run the pool instead of the interactive phone (also --subscribers)
+    pub msisdns: Vec<String>,
+    pub range: String, // First-last, inclusive
+    pub count: u32,    // Generated subscribers when there is no list or range
+    pub binds: u32,    // Binds the subscribers are spread over, round robin
+    pub rounds: u32,   // Dialogues each subscriber runs, moving one scenario on each time
+}
+
+impl Default for SubscribersConfig {
+    fn default() -> Self {
+        SubscribersConfig { enabled: false, msisdns: Vec::new(), range: String::new(), count: 0, binds: 1, rounds: 1 }
+    }
+}
+
+// Largest pool a range may expand to
+const MAX_SUBSCRIBERS: u64 = 100_000;
+
+impl SubscribersConfig {
+    // `msisdns` then the `range`, without repeats, or else `count` numbers from `phone`
+    pub fn msisdns(&self, phone: &PhoneConfig) -> Result<Vec<String>, String> {
+        let mut pool = self.msisdns.clone();
+        if pool.is_empty() && self.range.is_empty() {
+            pool = (0..self.count.min(MAX_SUBSCRIBERS as u32) as usize).map(|index| phone.msisdn(index)).collect();
+        }
+        if !self.range.is_empty() {
+            let parse = |value: &str| value.trim().parse::<u64>().map_err(|_| format!("subscribers.range {:?} is not first-last", self.range));
+            let (first, last) = self.range.split_once('-').ok_or_else(|| format!("subscribers.range {:?} is not first-last", self.range))?;
+            let width = first.trim().len();
+            let (first, last) = (parse(first)?, parse(last)?);
+            if last < first || last - first >= MAX_SUBSCRIBERS {
+                return Err(format!("subscribers.range {:?} must run upwards over at most {} numbers", self.range, MAX_SUBSCRIBERS));
+            }
+            pool.extend((first..=last).map(|number| format!("{:0width$}", number, width = width)));
+        }
+        let mut seen = std::collections::HashSet::new();
+        pool.retain(|msisdn| seen.insert(msisdn.clone()));
+        if pool.is_empty() {
+            return Err("no subscribers: set subscribers.msisdns, subscribers.range or subscribers.count".to_string());
+        }
+        Ok(pool)
+    }
+}
+
+// Scenarios read from `testing.test_scenarios_file`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TestScenarios {
+    pub scenarios: Vec<TestScenario>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TestScenario {
+    pub name: String,
+    pub description: String,
+    pub expected_success_rate: f64,
+    pub steps: Vec<ScenarioStep>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ScenarioStep {
+    pub ussd_code: String,
+    pub description: String,
+    #[serde(default)]
+    pub expected_keywords: Vec<String>, // Any one of them, ignoring case (empty = any response)
+    #[serde(default)]
+    pub timeout_ms: u64, // For an abandon step, how long to wait for the server to end the session (0 = don't wait)
+    #[serde(default)]
+    pub expect_contains: Vec<String>, // Every one of them, matching case
+    #[serde(default)]
+    pub expect_regex: Option<String>,
+    #[serde(default)]
+    pub expect_end: Option<bool>, // true: the network ends the session (a notification); false: it waits for a reply
+    #[serde(default)]
+    pub max_latency_ms: Option<u64>,
+}
+
+// The outcome of one expectation of a step, e.g. `contains "Balance"`
+#[derive(Debug, Clone)]
+pub struct Assertion {
+    pub name: String,
+    pub passed: bool,
+}
+
+impl ScenarioStep {
+    fn matches(&self, response: &str) -> bool {
+        let response = response.to_lowercase();
+        self.expected_keywords.is_empty()
+            || self.expected_keywords.iter().any(|keyword| response.contains(&keyword.to_lowercase()))
+    }
+
+    // Every expectation the step sets, checked against the screen it got and how long that took
+    fn check(&self, response: &UssdResponse, latency: Duration) -> Vec<Assertion> {
+        let mut assertions = Vec::new();
+        let mut assert = |name: String, passed: bool| assertions.push(Assertion { name, passed });
+        if !self.expected_keywords.is_empty() {
+            assert(format!("any of {:?}", self.expected_keywords), self.matches(&response.text));
+        }
+        for text in &self.expect_contains {
+            assert(format!("contains {:?}", text), response.text.contains(text.as_str()));
+        }
+        if let Some(pattern) = &self.expect_regex {
+            // Checked when the file was loaded
+            let passed = Regex::new(pattern).map(|regex| regex.is_match(&response.text)).unwrap_or(false);
+            assert(format!("matches /{}/", pattern), passed);
+        }
+        if let Some(end) = self.expect_end {
+            let name = if end { "session ended by the network" } else { "session waits for a reply" };
+            assert(name.to_string(), response.end == end);
+        }
+        if let Some(max_latency_ms) = self.max_latency_ms {
+            let latency_ms = latency.as_millis();
+            assert(format!("latency {}ms <= {}ms", latency_ms, max_latency_ms), latency_ms <= max_latency_ms as u128);
+        }
+        if assertions.is_empty() {
+            assertions.push(Assertion { name: "any response".to_string(), passed: true });
+        }
+        assertions
+    }
+}
+
+fn load_test_scenarios(path: &str) -> Result<TestScenarios, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path)?;
+    let scenarios: TestScenarios = toml::from_str(&content)?;
+    for scenario in &scenarios.scenarios {
+        for step in &scenario.steps {
+            if let Some(pattern) = &step.expect_regex {
+                Regex::new(pattern)
+                    .map_err(|e| format!("{} / {}: bad expect_regex: {}", scenario.name, step.description, e))?;
+            }
+        }
+    }
+    Ok(scenarios)
+}
+
+// Scenario and assertion counts across a run of the test scenarios file
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TestSummary {
+    pub scenarios_passed: u32,
+    pub scenarios_failed: u32,
+    pub assertions_passed: u32,
+    pub assertions_failed: u32,
+}
+
+// Special input that walks away from a USSD session without replying or unbinding,
+// leaving the server to time the session out on its own
+const ABANDON_INPUT: &str = "abandon";
+
+// Commands at a USSD session's input prompt; they are never sent to the server
+const HISTORY_COMMAND: &str = "/history"; // Show every screen of the session so far
+const BACK_COMMAND: &str = "/back"; // Redial and replay the inputs up to the previous screen
+const REDIAL_COMMAND: &str = "/redial"; // Dial the session's code again from the top
+
+#[derive(Debug, Clone)]
+pub struct MobilePhone {
+    pub msisdn: String,
+    pub operator: String,
+    pub balance: f64,
+    pub data_balance: f64,
+}
+
+impl MobilePhone {
+    pub fn new(msisdn: &str, operator: &str, balance: f64, data_balance: f64) -> Self {
+        MobilePhone {
+            msisdn: msisdn.to_string(),
+            operator: operator.to_string(),
+            balance,
+            data_balance,
+        }
+    }
+}
+
+// A screen from the network. `notify` marks a notification, which is shown but not answered;
+// `end` any screen after which the session is over
+#[derive(Debug, Clone)]
+pub struct UssdResponse {
+    pub text: String,
+    pub notify: bool,
+    pub end: bool,
+}
+
+// Screens that look final, for a gateway that sends no ussd_service_op to say so
+const SESSION_END_PHRASES: [&str; 3] = ["Thank you", "Goodbye", "Invalid"];
+
+// The connection to the server, over TLS when `[server.tls]` is enabled
+enum SmppStream {
+    Tcp(TcpStream),
+    Tls(TlsStream),
+}
+
+impl SmppStream {
+    // A second handle on the same connection, for the reader thread
+    fn try_clone(&self) -> io::Result<SmppStream> {
+        match self {
+            SmppStream::Tcp(stream) => stream.try_clone().map(SmppStream::Tcp),
+            SmppStream::Tls(stream) => stream.try_clone().map(SmppStream::Tls),
+        }
+    }
+
+    // Closes the connection under every handle, which ends the reader thread
+    fn shutdown(&self) -> io::Result<()> {
+        match self {
+            SmppStream::Tcp(stream) => stream.shutdown(Shutdown::Both),
+            SmppStream::Tls(stream) => stream.shutdown(Shutdown::Both),
+        }
+    }
+}
+
+impl Read for SmppStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            SmppStream::Tcp(stream) => stream.read(buf),
+            SmppStream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for SmppStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            SmppStream::Tcp(stream) => stream.write(buf),
+            SmppStream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            SmppStream::Tcp(stream) => stream.flush(),
+            SmppStream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+pub struct UssdSmppClient {
+    stream: Option<Arc<Mutex<SmppStream>>>, // Written here and by the reader's answers
+    inbox: Option<Receiver<io::Result<SmppPdu>>>, // Everything the reader thread takes off the connection
+    pushes: VecDeque<UssdResponse>, // DELIVER_SMs that no request asked for, oldest first
+    link: Option<Arc<Link>>,
+    heartbeat: Option<Sender<()>>, // Dropping it stops the heartbeat thread
+    sequence_counter: Arc<AtomicU32>, // Shared with the heartbeat's ENQUIRE_LINKs
+    bound: bool,
+    config: UserSimulatorConfig,
+    stats: PerformanceStats,
+    connection_start_time: Option<Instant>,
+    last_activity: Option<Instant>,
+    session_id: Option<String>, // Stamped with the run id; a dialled code starts a new one
+    session_code: String,       // The code that opened the session, for per-code latency
+    session_counter: u32,
+    request_log: Option<Vec<RequestRecord>>, // Every request, once keep_request_log is called
+}
+
+impl UssdSmppClient {
+    pub fn new(config: UserSimulatorConfig) -> Self {
+        UssdSmppClient {
+            stream: None,
+            inbox: None,
+            pushes: VecDeque::new(),
+            link: None,
+            heartbeat: None,
+            sequence_counter: Arc::new(AtomicU32::new(1)),
+            bound: false,
+            config,
+            stats: PerformanceStats::new(),
+            connection_start_time: None,
+            last_activity: None,
+            session_id: None,
+            session_code: String::new(),
+            session_counter: 0,
+            request_log: None,
+        }
+    }
+
+    // Records every request from now on, for `--report`
+    pub fn keep_request_log(&mut self) {
+        self.request_log.get_or_insert_with(Vec::new);
+    }
+
+    // Later requests come from `msisdn`
+    pub fn set_msisdn(&mut self, msisdn: String) {
+        self.config.phone.default_msisdn = msisdn;
+    }
+
+    pub fn request_log(&self) -> &[RequestRecord] {
+        self.request_log.as_deref().unwrap_or_default()
+    }
+
+    pub fn session_id(&self) -> Option<&str> {
+        self.session_id.as_deref()
+    }
+
+    fn start_session(&mut self) -> &str {
+        self.session_counter += 1;
+        let started = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        self.session_id.insert(run_id::stamp(format!("USIM{}{:04}", started, self.session_counter)))
+    }
+
+    pub fn connect(&mut self) -> std::io::Result<bool> {
+        let server_addr = format!("{}:{}", self.config.server.host, self.config.server.port);
+        
+        if self.config.logging.debug {
+            info!("🔗 Connecting to USSD SMPP server at {}", server_addr);
+        }
+        
+        let start_time = Instant::now();
+        
+        // Try to connect with timeout
+        let stream = match TcpStream::connect_timeout(
+            &server_addr.parse().map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?,
+            Duration::from_millis(self.config.server.connection_timeout_ms)
+        ) {
+            Ok(stream) => {
+                self.connection_start_time = Some(start_time);
+                self.last_activity = Some(Instant::now());
+                stream
+            },
+            Err(e) => {
+                if self.config.logging.debug {
+                    info!("❌ Connection failed: {}", e);
+                }
+                return Err(e);
+            }
+        };
+        
+        // Set socket options for better performance
+        if let Err(e) = stream.set_nodelay(true)
+            && self.config.logging.debug
+        {
+            info!("⚠️  Warning: Could not set TCP_NODELAY: {}", e);
+        }
+
+        let stream = if self.config.server.tls.enabled {
+            let tls = &self.config.server.tls;
+            match tls.connect(stream, &self.config.server.host) {
+                Ok(stream) => SmppStream::Tls(stream),
+                Err(e) => {
+                    if self.config.logging.debug {
+                        info!("❌ TLS handshake failed: {}", e);
+                    }
+                    return Err(e);
+                }
+            }
+        } else {
+            SmppStream::Tcp(stream)
+        };
+        let writer = Arc::new(Mutex::new(stream.try_clone()?));
+        let link = Arc::new(Link::new());
+        self.inbox = Some(reader::spawn(stream, Arc::clone(&writer), Arc::clone(&link), self.config.logging.debug));
+        // A server that goes quiet gets an ENQUIRE_LINK; one that does not answer it is given up on
+        let idle = Duration::from_millis(self.config.advanced.enquire_link_interval_ms);
+        let patience = Duration::from_millis(self.config.advanced.enquire_link_timeout_ms);
+        if !idle.is_zero() && !patience.is_zero() {
+            self.heartbeat = Some(heartbeat::spawn(
+                Arc::clone(&writer),
+                Arc::clone(&link),
+                Arc::clone(&self.sequence_counter),
+                idle,
+                patience,
+                self.config.logging.debug,
+            ));
+        }
+        self.link = Some(link);
+        self.stream = Some(writer);
+        
+        // Bind to server; a refused bind is reported as false rather than as an error
+        match self.bind() {
+            Ok(()) => Ok(true),
+            Err(SmppError::Auth { .. }) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn reconnect(&mut self) -> std::io::Result<bool> {
+        if self.config.logging.debug {
+            info!("🔄 Attempting to reconnect...");
+        }
+        
+        self.disconnect();
+        
+        for attempt in 1..=self.config.server.reconnect_attempts {
+            if self.config.logging.debug {
+                info!("🔄 Reconnection attempt {}/{}", attempt, self.config.server.reconnect_attempts);
+            }
+            
+            match self.connect() {
+                Ok(true) => {
+                    if self.config.logging.debug {
+                        info!("✅ Reconnected successfully");
+                    }
+                    return Ok(true);
+                },
+                Ok(false) => {
+                    if self.config.logging.debug {
+                        info!("❌ Reconnection failed (bind failed)");
+                    }
+                },
+                Err(e) => {
+                    if self.config.logging.debug {
+                        info!("❌ Reconnection failed: {}", e);
+                    }
+                }
+            }
+            
+            if attempt < self.config.server.reconnect_attempts {
+                thread::sleep(Duration::from_millis(1000 * attempt as u64));
+            }
+        }
+        
+        Ok(false)
+    }
+
+    pub fn disconnect(&mut self) {
+        if self.bound {
+            let _ = self.unbind();
+        }
+        self.close();
+        self.bound = false;
+        self.connection_start_time = None;
+        self.last_activity = None;
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some() && self.bound && !self.link_lost()
+    }
+
+    // The heartbeat got no answer, or the server closed the connection
+    fn link_lost(&self) -> bool {
+        self.link.as_ref().is_some_and(|link| link.is_lost())
+    }
+
+    fn close(&mut self) {
+        self.heartbeat = None;
+        if let Some(stream) = self.stream.take() {
+            let _ = stream.lock().unwrap().shutdown();
+        }
+        self.inbox = None;
+        self.link = None;
+    }
+
+    // DELIVER_SMs from the network that no request asked for, such as pushes, oldest first
+    pub fn take_pushes(&mut self) -> Vec<UssdResponse> {
+        self.collect_pushes();
+        self.pushes.drain(..).collect()
+    }
+
+    // Sets aside whatever has arrived since the last request, so none of it is taken for the
+    // answer to the next one
+    fn collect_pushes(&mut self) {
+        while let Ok(pdu) = self.read_pdu_with_timeout(Duration::ZERO) {
+            self.set_aside(pdu);
+        }
+    }
+
+    // A DELIVER_SM outside a request is kept as a push; any other PDU is a late answer to an
+    // earlier request, whose caller has given up on it
+    fn set_aside(&mut self, pdu: SmppPdu) {
+        if pdu.header.command_id == DELIVER_SM {
+            match self.parse_deliver_sm(&pdu.body) {
+                Ok(push) => self.pushes.push_back(push),
+                Err(e) => info!("⚠️  Undecodable DELIVER_SM from the network: {}", e),
+            }
+        } else if self.config.logging.debug {
+            info!("🗑️  Dropping late PDU 0x{:08x} seq={}", pdu.header.command_id, pdu.header.sequence_number);
+        }
+    }
+
+    pub fn get_stats(&self) -> &PerformanceStats {
+        &self.stats
+    }
+
+    pub fn get_connection_uptime_seconds(&self) -> Option<u64> {
+        self.connection_start_time.map(|start| start.elapsed().as_secs())
+    }
+
+    fn bind(&mut self) -> Result<(), SmppError> {
+        if self.config.logging.debug {
+            info!("🔐 Binding with system_id: {}", self.config.authentication.system_id);
+        }
+        
+        let sequence_number = self.get_next_sequence();
+        let mut bind = Bind {
+            system_id: self.config.authentication.system_id.as_str().into(),
+            password: self.config.authentication.password.as_str().into(),
+            system_type: self.config.authentication.system_type.as_str().into(),
+            interface_version: INTERFACE_VERSION_34,
+            addr_ton: 1,
+            addr_npi: 1,
+            ..Default::default()
+        };
+        if self.config.ui.screen_chars > 0 {
+            // Vendor TLV the simulator server paginates and truncates screens by
+            bind.optional_params.push(OptionalParam::u16(TAG_SCREEN_CHARS, self.config.ui.screen_chars));
+        }
+        let bind_pdu = SmppPdu::new(BIND_TRANSCEIVER, ESME_ROK, sequence_number, bind.encode());
+
+        let start_time = Instant::now();
+        self.send_pdu(bind_pdu)?;
+        
+        // Wait for bind response with timeout
+        let response = self.read_response(sequence_number, Duration::from_millis(self.config.advanced.pdu_timeout_ms))?;
+        let response_time = start_time.elapsed().as_millis() as u64;
+        
+        if response.header.command_id == BIND_TRANSCEIVER_RESP && response.header.command_status == ESME_ROK {
+            self.bound = true;
+            self.last_activity = Some(Instant::now());
+            if self.config.logging.debug {
+                info!("✅ Bind successful ({}ms)", response_time);
+            }
+            Ok(())
+        } else {
+            if self.config.logging.debug {
+                info!("❌ Bind failed. Status: 0x{:08x} ({}ms)", response.header.command_status, response_time);
+            }
+            Err(SmppError::Auth {
+                status: response.header.command_status,
+                system_id: self.config.authentication.system_id.clone(),
+            })
+        }
+    }
+
+    pub fn send_ussd_request(&mut self, ussd_code: &str) -> Result<UssdResponse, SmppError> {
+        if self.request_log.is_none() {
+            return self.exchange(ussd_code);
+        }
+        let sent_at = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+        let start_time = Instant::now();
+        let result = self.exchange(ussd_code);
+        let (response, outcome, error) = match &result {
+            Ok(response) => {
+                let outcome = if response.notify { "notification" } else { "response" };
+                (response.text.clone(), outcome, String::new())
+            }
+            Err(e) => (String::new(), "failed", e.to_string()),
+        };
+        let record = RequestRecord {
+            sent_at,
+            session_id: self.session_id().unwrap_or_default().to_string(),
+            msisdn: self.config.phone.default_msisdn.clone(),
+            request: ussd_code.to_string(),
+            response,
+            latency_ms: start_time.elapsed().as_secs_f64() * 1000.0,
+            outcome,
+            error,
+        };
+        if let Some(log) = &mut self.request_log {
+            log.push(record);
+        }
+        result
+    }
+
+    fn exchange(&mut self, ussd_code: &str) -> Result<UssdResponse, SmppError> {
+        if self.link_lost() {
+            info!("💔 Connection to the server lost, reconnecting...");
+            // Nothing would answer an UNBIND
+            self.bound = false;
+            if !self.reconnect()? {
+                return Err(SmppError::protocol(ESME_RINVBNDSTS, "Connection lost and reconnecting failed"));
+            }
+        }
+        if !self.bound {
+            return Err(SmppError::protocol(ESME_RINVBNDSTS, "Not bound to server"));
+        }
+
+        if ussd_code.starts_with('*') && ussd_code.ends_with('#') {
+            let session_id = self.start_session().to_string();
+            info!("🆔 Session {} started with {}", session_id, ussd_code);
+            self.session_code = ussd_code.to_string();
+        } else if self.session_code.is_empty() {
+            self.session_code = ussd_code.to_string();
+        }
+        if self.config.logging.debug {
+            info!("📤 Sending USSD request: {} (session {})", ussd_code, self.session_id().unwrap_or("-"));
+        }
+
+        self.collect_pushes();
+        let start_time = Instant::now();
+        let msisdn = self.config.phone.default_msisdn.clone();
+        let sequence_number = self.submit(&msisdn, ussd_code)?;
+
+        // Wait for submit response
+        let submit_resp = self.read_response(sequence_number, Duration::from_millis(self.config.advanced.pdu_timeout_ms))?;
+        let success = submit_resp.header.command_id == SUBMIT_SM_RESP && submit_resp.header.command_status == ESME_ROK;
+        
+        if success {
+            if self.config.logging.debug {
+                let message_id = SubmitSmResp::decode(&submit_resp.body).map(|resp| resp.message_id.into_owned()).unwrap_or_default();
+                info!("✅ SUBMIT_SM_RESP received, message_id {} (session {})", message_id, self.session_id().unwrap_or("-"));
+                info!("� SUBMIT_SM_RESP body ({} bytes): {:?}", submit_resp.body.len(), submit_resp.body);
+                if !submit_resp.body.is_empty() {
+                    info!("📋 SUBMIT_SM_RESP body as string: {:?}", String::from_utf8_lossy(&submit_resp.body));
+                }
+                info!("�🔄 Waiting for DELIVER_SM response...");
+            }
+            
+            // Wait for DELIVER_SM with USSD response; the reader has already acknowledged it
+            match self.read_deliver_sm(Duration::from_millis(self.config.ui.session_timeout_ms)) {
+                Ok(deliver_sm) => {
+                    let response = self.parse_deliver_sm(&deliver_sm.body)?;
+                    
+                    let response_time = start_time.elapsed();
+                    self.stats.record_request(&self.session_code, response_time, true);
+                    self.last_activity = Some(Instant::now());
+                    
+                    if self.config.logging.debug {
+                        info!("📥 USSD {} received: {} ({}ms)",
+                            if response.notify { "notification" } else { "response" }, response.text, response_time.as_millis());
+                    }
+                    
+                    Ok(response)
+                }
+                Err(e) => {
+                    if self.config.logging.debug {
+                        info!("❌ Timeout waiting for DELIVER_SM: {}", e);
+                    }
+                    self.stats.record_request(&self.session_code, start_time.elapsed(), false);
+                    Err(e)
+                }
+            }
+        } else {
+            self.stats.record_request(&self.session_code, start_time.elapsed(), false);
+            Err(SmppError::protocol(submit_resp.header.command_status, "SUBMIT_SM failed"))
+        }
+    }
+
+    // Sends a SUBMIT_SM carrying `ussd_code` from `msisdn` and returns its sequence number
+    fn submit(&mut self, msisdn: &str, ussd_code: &str) -> std::io::Result<u32> {
+        // Input GSM 7-bit cannot carry, e.g. a reply typed in Sinhala, goes as UCS-2
+        let data_coding = TextEncoding::Auto.data_coding(ussd_code);
+        let sequence_number = self.get_next_sequence();
+        let submit_sm = SubmitSm {
+            service_type: "USSD".into(),
+            source_addr_ton: 1, // International
+            source_addr_npi: 1, // ISDN
+            source_addr: msisdn.into(),
+            destination_addr: "123".into(), // USSD gateway
+            esm_class: ESM_CLASS_USSD,
+            data_coding, // 0 GSM 7-bit, 8 UCS-2
+            short_message: encoding::encode(ussd_code, data_coding, self.config.advanced.gsm7_packing).into(),
+            ..Default::default()
+        };
+        self.send_pdu(SmppPdu::new(SUBMIT_SM, ESME_ROK, sequence_number, submit_sm.encode()))?;
+        Ok(sequence_number)
+    }
+
+    // After abandoning a session, waits for the server to end it with a DELIVER_SM of its own.
+    // Returns None when nothing arrives within `timeout`.
+    pub fn wait_for_session_end(&mut self, timeout: Duration) -> Result<Option<String>, SmppError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(None);
+            }
+            let pdu = match self.read_pdu_with_timeout(remaining) {
+                Ok(pdu) => pdu,
+                Err(SmppError::Timeout(_)) => return Ok(None),
+                Err(e) => return Err(e),
+            };
+            if pdu.header.command_id != DELIVER_SM {
+                continue;
+            }
+
+            return Ok(Some(self.parse_deliver_sm(&pdu.body)?.text));
+        }
+    }
+
+    pub fn unbind(&mut self) -> std::io::Result<()> {
+        if !self.bound {
+            return Ok(());
+        }
+
+        let sequence_number = self.get_next_sequence();
+        let unbind_pdu = SmppPdu::new(UNBIND, ESME_ROK, sequence_number, Vec::new());
+
+        self.send_pdu(unbind_pdu)?;
+        
+        // Wait for unbind response
+        let response = self.read_response(sequence_number, Duration::from_millis(self.config.advanced.pdu_timeout_ms))?;
+        self.bound = false;
+        if response.header.command_id != UNBIND_RESP && self.config.logging.debug {
+            info!("⚠️  Expected UNBIND_RESP, got 0x{:08x}", response.header.command_id);
+        }
+        
+        if self.config.logging.debug {
+            info!("✅ Unbind successful");
+        }
+        
+        Ok(())
+    }
+
+    // data_coding 0 is GSM 7-bit and 8 is UCS-2; anything else is shown as UTF-8. Whether the
+    // session is over comes from ussd_service_op, and only without it from the text.
+    fn parse_deliver_sm(&self, body: &[u8]) -> Result<UssdResponse, SmppError> {
+        let deliver_sm = DeliverSm::decode(body)?;
+        let text = encoding::decode(deliver_sm.message(), deliver_sm.data_coding, self.config.advanced.gsm7_packing);
+        let notify = matches!(deliver_sm.ussd_service_op(), Some(USSD_NOTIFY | USSD_TERMINATE_NOTIFY));
+        let end = deliver_sm
+            .ends_ussd_session()
+            .unwrap_or_else(|| SESSION_END_PHRASES.iter().any(|phrase| text.contains(phrase)));
+        Ok(UssdResponse { text, notify, end })
+    }
+
+    fn send_pdu(&mut self, pdu: SmppPdu) -> std::io::Result<()> {
+        if let Some(stream) = &self.stream {
+            reader::write_pdu(stream, &pdu)?;
+        }
+        
+        Ok(())
+    }
+
+    // The next PDU the reader thread took off the connection. ENQUIRE_LINKs never show up here,
+    // as the reader answers them itself.
+    fn read_pdu_with_timeout(&mut self, timeout: Duration) -> Result<SmppPdu, SmppError> {
+        let Some(inbox) = &self.inbox else {
+            return Err(std::io::Error::new(std::io::ErrorKind::NotConnected, "Not connected").into());
+        };
+        match inbox.recv_timeout(timeout) {
+            Ok(Ok(pdu)) => {
+                self.last_activity = Some(Instant::now());
+                Ok(pdu)
+            }
+            Ok(Err(e)) => {
+                if self.config.logging.debug {
+                    info!("❌ Error reading PDU: {}", e);
+                }
+                Err(e.into())
+            }
+            Err(RecvTimeoutError::Timeout) => Err(SmppError::Timeout("waiting for PDU".to_string())),
+            Err(RecvTimeoutError::Disconnected) => {
+                Err(std::io::Error::new(std::io::ErrorKind::ConnectionAborted, "Connection closed").into())
+            }
+        }
+    }
+
+    // The response to request `sequence_number`; whatever arrives before it is set aside
+    fn read_response(&mut self, sequence_number: u32, timeout: Duration) -> Result<SmppPdu, SmppError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let pdu = self.read_pdu_with_timeout(deadline.saturating_duration_since(Instant::now()))?;
+            if pdu.header.command_id & RESPONSE_BIT != 0 && pdu.header.sequence_number == sequence_number {
+                return Ok(pdu);
+            }
+            self.set_aside(pdu);
+        }
+    }
+
+    // The next DELIVER_SM; late responses to earlier requests arriving first are dropped
+    fn read_deliver_sm(&mut self, timeout: Duration) -> Result<SmppPdu, SmppError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let pdu = self.read_pdu_with_timeout(deadline.saturating_duration_since(Instant::now()))?;
+            if pdu.header.command_id == DELIVER_SM {
+                return Ok(pdu);
+            }
+            self.set_aside(pdu);
+        }
+    }
+
+    fn get_next_sequence(&mut self) -> u32 {
+        self.sequence_counter.fetch_add(1, Ordering::Relaxed) + 1
+    }
+}
+
+// Without this the reader thread's handle would keep the connection open
+impl Drop for UssdSmppClient {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+pub struct UssdMobileUI {
+    phone: MobilePhone,
+    client: UssdSmppClient,
+    config: UserSimulatorConfig,
+}
+
+impl UssdMobileUI {
+    pub fn new(config: UserSimulatorConfig) -> Self {
+        let phone = MobilePhone::new(
+            &config.phone.default_msisdn,
+            &config.phone.operator_name,
+            config.phone.balance,
+            config.phone.data_balance,
+        );
+        
+        let mut client = UssdSmppClient::new(config.clone());
+        if !config.testing.report_file.is_empty() {
+            client.keep_request_log();
+        }
+        
+        UssdMobileUI {
+            phone,
+            client,
+            config,
+        }
+    }
+
+    pub fn start(&mut self) -> std::io::Result<()> {
+        // Connect to SMPP server
+        if !self.client.connect()? {
+            println!("❌ Failed to connect to USSD server");
+            return Err(std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "Connection failed"));
+        }
+
+        if self.config.ui.auto_clear_screen {
+            self.clear_screen();
+        }
+        
+        self.show_phone_display();
+        
+        loop {
+            self.show_pushes()?;
+            self.show_dialer_menu();
+            let choice = self.get_user_input()?;
+            
+            match choice.as_str() {
+                "1" => self.dial_ussd("*123#")?,
+                "2" => self.dial_ussd("*100#")?,
+                "3" => self.dial_ussd("*199#")?,
+                "4" => self.custom_ussd()?,
+                "5" => self.show_performance_stats()?,
+                "6" => self.test_connection()?,
+                "7" => self.run_test_scenarios()?,
+                "8" => {
+                    if !self.config.ui.stats_file.is_empty() {
+                        self.export_stats(&self.config.ui.stats_file);
+                    }
+                    self.write_report();
+                    println!("📱 Goodbye!");
+                    break;
+                }
+                _ => {
+                    println!("❌ Invalid choice. Please try again.");
+                    thread::sleep(Duration::from_millis(1500));
+                }
+            }
+        }
+        
+        // Disconnect from server
+        self.client.unbind()?;
+        
+        Ok(())
+    }
+
+    fn show_phone_display(&self) {
+        println!("╔════════════════════════════════════════╗");
+        println!("║              📱 MOBILE PHONE             ║");
+        println!("║                                        ║");
+        println!("║  📶 {} Signal: ████▓                  ║", self.phone.operator);
+        println!("║  📞 {:<30} ║", self.phone.msisdn);
+        println!("║  💰 Balance: ${:.2}                    ║", self.phone.balance);
+        println!("║  📊 Data: {:.1}GB                        ║", self.phone.data_balance);
+        println!("║  🌐 Server: {}:{}                 ║", self.config.server.host, self.config.server.port);
+        
+        if self.config.ui.show_performance_stats {
+            let stats = self.client.get_stats();
+            let uptime = self.client.get_connection_uptime_seconds().unwrap_or(0);
+            println!("║  📈 Requests: {} (✅{} ❌{})           ║", stats.total_requests, stats.successful_requests, stats.failed_requests);
+            println!("║  ⏱️  Avg Response: {:.0}ms             ║", stats.all.mean_ms());
+            println!("║  🔗 Uptime: {}s                      ║", uptime);
+        }
+        
+        println!("║                                        ║");
+        println!("╚════════════════════════════════════════╝");
+        println!();
+    }
+
+    fn show_dialer_menu(&self) {
+        println!("╔════════════════════════════════════════╗");
+        println!("║                USSD DIALER             ║");
+        println!("║                                        ║");
+        println!("║  1. Main Menu (*123#)                  ║");
+        println!("║  2. Balance Check (*100#)              ║");
+        println!("║  3. Data Balance (*199#)               ║");
+        println!("║  4. Custom USSD Code                   ║");
+        println!("║  5. Performance Stats                  ║");
+        println!("║  6. Connection Test                    ║");
+        println!("║  7. Run Test Scenarios                 ║");
+        println!("║  8. Exit                               ║");
+        println!("║                                        ║");
+        println!("╚════════════════════════════════════════╝");
+        print!("Enter your choice: ");
+        io::stdout().flush().unwrap();
+    }
+
+    fn dial_ussd(&mut self, ussd_code: &str) -> std::io::Result<()> {
+        if self.config.ui.auto_clear_screen {
+            self.clear_screen();
+        }
+        self.show_phone_display();
+        
+        println!("╔════════════════════════════════════════╗");
+        println!("║                DIALING                 ║");
+        println!("║                                        ║");
+        println!("║  📞 Dialing: {:<25} ║", ussd_code);
+        println!("║                                        ║");
+        println!("║  ⏳ Connecting to network...           ║");
+        println!("╚════════════════════════════════════════╝");
+        
+        // Simulate dialing delay
+        for _i in 0..3 {
+            thread::sleep(Duration::from_millis(self.config.ui.animation_delay_ms));
+            print!(".");
+            io::stdout().flush().unwrap();
+        }
+        println!();
+        
+        // Launch USSD client
+        self.launch_ussd_client(ussd_code)?;
+        
+        // Wait for user to press enter
+        println!("\nPress Enter to return to main menu...");
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        
+        if self.config.ui.auto_clear_screen {
+            self.clear_screen();
+        }
+        Ok(())
+    }
+
+    fn custom_ussd(&mut self) -> std::io::Result<()> {
+        if self.config.ui.auto_clear_screen {
+            self.clear_screen();
+        }
+        self.show_phone_display();
+        
+        println!("╔════════════════════════════════════════╗");
+        println!("║              CUSTOM USSD               ║");
+        println!("║                                        ║");
+        println!("║  Enter USSD code (e.g., *123#):       ║");
+        println!("║                                        ║");
+        println!("╚════════════════════════════════════════╝");
+        
+        let ussd_code = self.get_user_input()?;
+        
+        if ussd_code.trim().is_empty() {
+            println!("❌ No USSD code entered. Returning to menu...");
+            thread::sleep(Duration::from_millis(1500));
+            return Ok(());
+        }
+        
+        self.dial_ussd(&ussd_code)?;
+        Ok(())
+    }
+
+    fn launch_ussd_client(&mut self, ussd_code: &str) -> std::io::Result<()> {
+        if self.config.ui.auto_clear_screen {
+            self.clear_screen();
+        }
+        self.show_phone_display();
+        
+        println!("╔════════════════════════════════════════╗");
+        println!("║              USSD SESSION              ║");
+        println!("║                                        ║");
+        println!("║  📞 Code: {:<27} ║", ussd_code);
+        println!("║  🔗 Connected to network               ║");
+        println!("║                                        ║");
+        println!("╚════════════════════════════════════════╝");
+        println!();
+        
+        // Use real USSD interaction
+        self.real_ussd_session(ussd_code)?;
+        
+        Ok(())
+    }
+
+    fn real_ussd_session(&mut self, initial_code: &str) -> std::io::Result<()> {
+        let mut current_input = initial_code.to_string();
+        let mut first_response = true;
+        let mut history = SessionHistory::default();
+        
+        loop {
+            // Send real USSD request to server
+            match self.client.send_ussd_request(&current_input) {
+                Ok(response) => {
+                    history.record(&current_input, &response.text);
+                    if response.notify {
+                        println!("╔════════════════════════════════════════╗");
+                        println!("║           USSD NOTIFICATION            ║");
+                        println!("╚════════════════════════════════════════╝");
+                    } else {
+                        println!("┌────────────────────────────────────────┐");
+                        println!("│              USSD RESPONSE             │");
+                        println!("└────────────────────────────────────────┘");
+                    }
+                    if first_response
+                        && let Some(session_id) = self.client.session_id()
+                    {
+                        println!("🆔 Session: {}", session_id);
+                    }
+                    first_response = false;
+                    println!("{}", response.text);
+                    
+                    // A notification closes the session, so there is nothing to reply to
+                    if response.notify {
+                        println!("\n📱 USSD session ended by the network.");
+                        break;
+                    }
+                    
+                    if response.end {
+                        println!("\n📱 USSD session ended.");
+                        break;
+                    }
+                    
+                    current_input = loop {
+                        println!("\n┌────────────────────────────────────────┐");
+                        println!("│           ENTER YOUR CHOICE            │");
+                        println!("└────────────────────────────────────────┘");
+                        println!("(type '{}' to walk away without replying, or {}, {} or {})",
+                            ABANDON_INPUT, BACK_COMMAND, HISTORY_COMMAND, REDIAL_COMMAND);
+                        print!("Your input: ");
+                        io::stdout().flush().unwrap();
+                        
+                        let mut input = String::new();
+                        io::stdin().read_line(&mut input)?;
+                        let input = input.trim().to_string();
+                        if input.eq_ignore_ascii_case(HISTORY_COMMAND) {
+                            print!("{}", history.render());
+                        } else if input.eq_ignore_ascii_case(BACK_COMMAND) && !history.can_go_back() {
+                            println!("↩️  Already at the first screen of the session");
+                        } else {
+                            break input;
+                        }
+                    };
+                    
+                    if current_input.is_empty() {
+                        println!("📱 USSD session cancelled.");
+                        break;
+                    }
+                    
+                    if current_input.eq_ignore_ascii_case(ABANDON_INPUT) {
+                        self.abandon_session();
+                        break;
+                    }
+                    
+                    if current_input.eq_ignore_ascii_case(REDIAL_COMMAND) {
+                        current_input = history.redial();
+                        first_response = true;
+                        println!("🔁 Redialling {}", current_input);
+                    } else if current_input.eq_ignore_ascii_case(BACK_COMMAND) {
+                        // The loop sends the last input itself, so its screen is shown
+                        let (replay, shown) = history.back();
+                        current_input = shown;
+                        first_response = replay.is_empty();
+                        if let Err(e) = self.replay(&replay, &mut history) {
+                            println!("❌ Could not get back: {}", e);
+                            println!("📱 USSD session failed.");
+                            break;
+                        }
+                        if let Some(session_id) = self.client.session_id().filter(|_| !first_response) {
+                            println!("🆔 Session: {}", session_id);
+                        }
+                    }
+                    
+                    // Show processing animation
+                    print!("⏳ Processing");
+                    for _i in 0..3 {
+                        thread::sleep(Duration::from_millis(500));
+                        print!(".");
+                        io::stdout().flush().unwrap();
+                    }
+                    println!();
+                }
+                Err(e) => {
+                    println!("❌ Error: {}", e);
+                    println!("📱 USSD session failed.");
+                    break;
+                }
+            }
+        }
+        
+        Ok(())
+    }
+
+    // Sends `inputs` without showing their screens, as `/back` does to retrace a session. Every
+    // exchange still goes into `history`. A screen that ends the session stops it.
+    fn replay(&mut self, inputs: &[String], history: &mut SessionHistory) -> Result<(), String> {
+        for input in inputs {
+            println!("↩️  Replaying {}", input);
+            let response = self.client.send_ussd_request(input).map_err(|e| e.to_string())?;
+            history.record(input, &response.text);
+            if response.end {
+                return Err(format!("the session ended after {}", input));
+            }
+        }
+        Ok(())
+    }
+
+    // Messages the network sent while no request was waiting, e.g. pushes, shown before the
+    // menu. One that waits for a reply can be answered, which carries on in a USSD session.
+    fn show_pushes(&mut self) -> std::io::Result<()> {
+        for push in self.client.take_pushes() {
+            println!("╔════════════════════════════════════════╗");
+            println!("║          📨 MESSAGE FROM NETWORK        ║");
+            println!("╚════════════════════════════════════════╝");
+            println!("{}", push.text);
+            if push.end {
+                println!();
+                continue;
+            }
+            print!("\nYour reply (Enter to ignore): ");
+            io::stdout().flush()?;
+            let mut reply = String::new();
+            io::stdin().read_line(&mut reply)?;
+            let reply = reply.trim();
+            if !reply.is_empty() {
+                self.real_ussd_session(reply)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn abandon_session(&self) {
+        // Deliberately send nothing (no reply, no UNBIND) so the server's session
+        // timeout, pending-forward cleanup and CDR finalization paths get exercised
+        println!("🚶 Session abandoned: no reply sent, connection left bound.");
+        if self.config.logging.debug {
+            println!("🔍 Server should expire the session after its session timeout");
+        }
+    }
+
+    fn show_performance_stats(&self) -> std::io::Result<()> {
+        if self.config.ui.auto_clear_screen {
+            self.clear_screen();
+        }
+        
+        let stats = self.client.get_stats();
+        let uptime = self.client.get_connection_uptime_seconds().unwrap_or(0);
+        
+        println!("╔════════════════════════════════════════╗");
+        println!("║           PERFORMANCE STATISTICS       ║");
+        println!("║                                        ║");
+        println!("║  📊 Total Requests: {:<18} ║", stats.total_requests);
+        println!("║  ✅ Successful: {:<22} ║", stats.successful_requests);
+        println!("║  ❌ Failed: {:<26} ║", stats.failed_requests);
+        println!("║  📈 Success Rate: {:.1}%                ║", stats.get_success_rate());
+        println!("║                                        ║");
+        println!("║  ⏱️  Average Response: {:.0}ms           ║", stats.all.mean_ms());
+        println!("║  🚀 Fastest Response: {:.0}ms              ║", stats.all.min_ms());
+        println!("║  📐 p50/p95/p99: {:.0}/{:.0}/{:.0}ms          ║",
+            stats.all.percentile_ms(50.0), stats.all.percentile_ms(95.0), stats.all.percentile_ms(99.0));
+        println!("║  🐌 Slowest Response: {:.0}ms              ║", stats.all.max_ms());
+        println!("║                                        ║");
+        for (code, latency) in &stats.by_code {
+            println!("║  {:<10} n={:<5} p50 {:.0}ms p95 {:.0}ms p99 {:.0}ms", code, latency.count(),
+                latency.percentile_ms(50.0), latency.percentile_ms(95.0), latency.percentile_ms(99.0));
+        }
+        if !stats.by_code.is_empty() {
+            println!("║                                        ║");
+        }
+        println!("║  🔗 Connection Uptime: {}s              ║", uptime);
+        println!("║  🌐 Server: {}:{}                 ║", self.config.server.host, self.config.server.port);
+        println!("║  📱 MSISDN: {:<25} ║", self.phone.msisdn);
+        println!("║                                        ║");
+        println!("╚════════════════════════════════════════╝");
+        
+        println!("\nPress E then Enter to export the latency histograms, or Enter to continue...");
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if input.trim().eq_ignore_ascii_case("e") {
+            let path = if self.config.ui.stats_file.is_empty() { DEFAULT_STATS_FILE } else { &self.config.ui.stats_file };
+            self.export_stats(path);
+            thread::sleep(Duration::from_millis(1500));
+        }
+        
+        if self.config.ui.auto_clear_screen {
+            self.clear_screen();
+        }
+        Ok(())
+    }
+
+    // Every request of the session, for `--report` (testing.report_file)
+    fn write_report(&self) {
+        write_request_report(&self.config.testing.report_file, self.client.request_log());
+    }
+
+    fn export_stats(&self, path: &str) {
+        match self.client.get_stats().export(path) {
+            Ok(()) => println!("💾 Latency histograms written to {}", path),
+            Err(e) => println!("❌ Could not write {}: {}", path, e),
+        }
+    }
+    
+    fn test_connection(&mut self) -> std::io::Result<()> {
+        if self.config.ui.auto_clear_screen {
+            self.clear_screen();
+        }
+        
+        println!("╔════════════════════════════════════════╗");
+        println!("║            CONNECTION TEST             ║");
+        println!("║                                        ║");
+        println!("║  🔍 Testing SMPP connection...         ║");
+        println!("╚════════════════════════════════════════╝");
+        
+        let start_time = Instant::now();
+        
+        // Test basic connectivity
+        println!("1. Testing TCP connection...");
+        if self.client.is_connected() {
+            println!("   ✅ Connection active");
+        } else {
+            println!("   ❌ Connection not active, attempting reconnect...");
+            match self.client.reconnect() {
+                Ok(true) => println!("   ✅ Reconnection successful"),
+                Ok(false) => println!("   ❌ Reconnection failed"),
+                Err(e) => println!("   ❌ Reconnection error: {}", e),
+            }
+        }
+        
+        // Test USSD request
+        println!("2. Testing USSD request...");
+        match self.client.send_ussd_request("*000#") {
+            Ok(response) => {
+                println!("   ✅ USSD test successful");
+                println!("   📥 Response: {}", response.text);
+            }
+            Err(e) => {
+                println!("   ❌ USSD test failed: {}", e);
+            }
+        }
+        
+        let total_time = start_time.elapsed();
+        println!("\n🎯 Test completed in {:.2}s", total_time.as_secs_f64());
+        
+        println!("\nPress Enter to continue...");
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        
+        if self.config.ui.auto_clear_screen {
+            self.clear_screen();
+        }
+        Ok(())
+    }
+    
+    fn run_test_scenarios(&mut self) -> std::io::Result<()> {
+        if self.config.ui.auto_clear_screen {
+            self.clear_screen();
+        }
+        
+        println!("╔════════════════════════════════════════╗");
+        println!("║             TEST SCENARIOS             ║");
+        println!("║                                        ║");
+        println!("║  🧪 Running configured test scenarios  ║");
+        println!("╚════════════════════════════════════════╝");
+        
+        if let Err(e) = self.run_test_file() {
+            println!("❌ {}", e);
+        }
+        
+        println!("\nPress Enter to continue...");
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        
+        if self.config.ui.auto_clear_screen {
+            self.clear_screen();
+        }
+        Ok(())
+    }
+    
+    // Connects, runs the test scenarios file and unbinds, for `--test` in CI. The exit code is
+    // 0 when every scenario passed, 1 when any failed and 2 when they could not be run.
+    pub fn run_tests(&mut self) -> i32 {
+        match self.client.connect() {
+            Ok(true) => {}
+            Ok(false) => {
+                println!("❌ Failed to connect to USSD server");
+                return 2;
+            }
+            Err(e) => {
+                println!("❌ Failed to connect to USSD server: {}", e);
+                return 2;
+            }
+        }
+        let result = self.run_test_file();
+        if let Err(e) = self.client.unbind() {
+            println!("⚠️  Unbind failed: {}", e);
+        }
+        self.write_report();
+        match result {
+            Ok(summary) if summary.scenarios_failed == 0 => 0,
+            Ok(_) => 1,
+            Err(e) => {
+                println!("❌ {}", e);
+                2
+            }
+        }
+    }
+    
+    fn run_test_file(&mut self) -> Result<TestSummary, String> {
+        let path = self.config.testing.test_scenarios_file.clone();
+        let scenarios =
+            load_test_scenarios(&path).map_err(|e| format!("Could not load test scenarios from {}: {}", path, e))?;
+        println!("📄 {} scenario(s) from {}", scenarios.scenarios.len(), path);
+        let mut summary = TestSummary::default();
+        for (index, scenario) in scenarios.scenarios.iter().enumerate() {
+            // With a generator each scenario is a different subscriber, so none inherits another's session
+            if let Some(msisdn) = self.config.phone.generator.nth(index as u64) {
+                println!("📱 {} dials from {}", scenario.name, msisdn);
+                self.client.set_msisdn(msisdn);
+            }
+            if self.run_scenario(scenario, &mut summary) {
+                summary.scenarios_passed += 1;
+            } else {
+                summary.scenarios_failed += 1;
+            }
+        }
+        self.client.set_msisdn(self.phone.msisdn.clone());
+        
+        println!("\n╔════════════════════════════════════════╗");
+        println!("║              TEST RESULTS              ║");
+        println!("║                                        ║");
+        println!("║  ✅ Passed: {:<26} ║", summary.scenarios_passed);
+        println!("║  ❌ Failed: {:<26} ║", summary.scenarios_failed);
+        println!("║  🔎 Assertions: {:<22} ║", format!("{}/{} passed", summary.assertions_passed,
+            summary.assertions_passed + summary.assertions_failed));
+        println!("║                                        ║");
+        println!("╚════════════════════════════════════════╝");
+        Ok(summary)
+    }
+    
+    // Runs the steps in order and passes when enough of them met every expectation
+    fn run_scenario(&mut self, scenario: &TestScenario, summary: &mut TestSummary) -> bool {
+        println!("\n🧪 {} - {}", scenario.name, scenario.description);
+        
+        let mut passed = 0;
+        let mut run = 0;
+        for step in &scenario.steps {
+            run += 1;
+            print!("   {} ({})... ", step.description, step.ussd_code);
+            io::stdout().flush().unwrap();
+            
+            if step.ussd_code.eq_ignore_ascii_case(ABANDON_INPUT) {
+                // Nothing more is sent for this scenario, so later steps are not run or counted
+                if self.abandon_step(step, summary) {
+                    passed += 1;
+                }
+                break;
+            }
+            
+            let start_time = Instant::now();
+            match self.client.send_ussd_request(&step.ussd_code) {
+                Ok(response) => {
+                    let latency = start_time.elapsed();
+                    let assertions = step.check(&response, latency);
+                    if assertions.iter().all(|assertion| assertion.passed) {
+                        passed += 1;
+                        println!("✅ ({}ms)", latency.as_millis());
+                    } else {
+                        println!("❌ ({}ms)", latency.as_millis());
+                        println!("      📥 {}", response.text.chars().take(60).collect::<String>());
+                    }
+                    report_assertions(&assertions, summary);
+                }
+                Err(e) => {
+                    println!("❌ Failed: {}", e);
+                    summary.assertions_failed += 1;
+                }
+            }
+            
+            thread::sleep(Duration::from_millis(500));
+        }
+        
+        let success_rate = if run == 0 { 100.0 } else { passed as f64 / run as f64 * 100.0 };
+        let ok = success_rate >= scenario.expected_success_rate;
+        println!("   {} {:.1}% of steps passed (expected {:.1}%)", if ok { "✅" } else { "❌" }, success_rate, scenario.expected_success_rate);
+        ok
+    }
+    
+    // Walks away; with a timeout, the step passes only if the server then ends the session itself
+    // with a screen that meets the step's expectations
+    fn abandon_step(&mut self, step: &ScenarioStep, summary: &mut TestSummary) -> bool {
+        self.abandon_session();
+        if step.timeout_ms == 0 {
+            return true;
+        }
+        
+        println!("   ⏳ Waiting up to {}ms for the server to time the session out...", step.timeout_ms);
+        let start_time = Instant::now();
+        match self.client.wait_for_session_end(Duration::from_millis(step.timeout_ms)) {
+            Ok(Some(text)) => {
+                let assertions = step.check(&UssdResponse { text: text.clone(), notify: true, end: true }, start_time.elapsed());
+                let ok = assertions.iter().all(|assertion| assertion.passed);
+                println!("   {} Server ended the session: {}", if ok { "✅" } else { "❌" }, text);
+                report_assertions(&assertions, summary);
+                ok
+            }
+            Ok(None) => {
+                println!("   ❌ Server did not end the session within {}ms", step.timeout_ms);
+                summary.assertions_failed += 1;
+                false
+            }
+            Err(e) => {
+                println!("   ❌ {}", e);
+                summary.assertions_failed += 1;
+                false
+            }
+        }
+    }
+}
+
+pub fn write_request_report(path: &str, records: &[RequestRecord]) {
+    if path.is_empty() {
+        return;
+    }
+    match report::write_requests(path, records) {
+        Ok(()) => println!("💾 {} request(s) written to {}", records.len(), path),
+        Err(e) => println!("❌ Could not write {}: {}", path, e),
+    }
+}
+
+fn report_assertions(assertions: &[Assertion], summary: &mut TestSummary) {
+    for assertion in assertions {
+        println!("      {} {}", if assertion.passed { "✅" } else { "❌" }, assertion.name);
+        if assertion.passed {
+            summary.assertions_passed += 1;
+        } else {
+            summary.assertions_failed += 1;
+        }
+    }
+}
+
+// What `/back`, `/redial` and `/history` work from in an interactive session
+#[derive(Debug, Default)]
+struct SessionHistory {
+    path: Vec<String>,                 // The inputs that led to the screen on show, dialled code first
+    transcript: Vec<(String, String)>, // Every input sent and the screen it got, replays included
+}
+
+impl SessionHistory {
+    fn record(&mut self, input: &str, screen: &str) {
+        self.path.push(input.to_string());
+        self.transcript.push((input.to_string(), screen.to_string()));
+    }
+
+    fn can_go_back(&self) -> bool {
+        self.path.len() >= 2
+    }
+
+    // `/back`: USSD has no way back, so the previous screen is reached again by redialling and
+    // sending every input up to it. Returns the inputs to replay and the one whose screen is
+    // shown; the path starts over and fills up again as they are recorded.
+    fn back(&mut self) -> (Vec<String>, String) {
+        self.path.pop();
+        let shown = self.path.pop().unwrap_or_default();
+        (std::mem::take(&mut self.path), shown)
+    }
+
+    // `/redial`: the session's code, dialled again from the top
+    fn redial(&mut self) -> String {
+        let code = self.path.first().cloned().unwrap_or_default();
+        self.path.clear();
+        code
+    }
+
+    // `/history`: each input of the session and the screen it got, oldest first
+    fn render(&self) -> String {
+        let mut out = String::from("\n📜 Session history:\n");
+        for (index, (input, screen)) in self.transcript.iter().enumerate() {
+            out.push_str(&format!("  {}. > {}\n", index + 1, input));
+            for line in screen.lines() {
+                out.push_str(&format!("       < {}\n", line));
+            }
+        }
+        out
+    }
+}
+
+fn load_config(config_path: &str) -> Result<UserSimulatorConfig, Box<dyn std::error::Error>> {
+    if Path::new(config_path).exists() {
+        let config_content = fs::read_to_string(config_path)?;
+        let config: UserSimulatorConfig = toml::from_str(&config_content)?;
+        Ok(config)
+    } else {
+        println!("Config file not found at '{}', creating default config...", config_path);
+        let default_config = UserSimulatorConfig::default();
+        let config_content = toml::to_string_pretty(&default_config)?;
+        fs::write(config_path, config_content)?;
+        println!("Default config created at '{}'", config_path);
+        Ok(default_config)
+    }
+}
+
+fn print_usage() {
+    println!("USSD User Simulator");
+    println!("Usage: ussd_user_simulator [OPTIONS]");
+    println!();
+    println!("Options:");
+    println!("  -c, --config <CONFIG>    Path to configuration file (default: user_config.toml)");
+    println!("  -m, --msisdn <MSISDN>    Override phone number from config");
+    println!("  -h, --host <HOST>        Override server host from config");
+    println!("  -p, --port <PORT>        Override server port from config");
+    println!("  --create-config          Create a default config file and exit");
+    println!("  --debug                  Enable debug mode");
+    println!("  --run-id <ID>            Run namespace shown with this session (default: $USSD_RUN_ID or a UUID)");
+    println!("  --once <INPUTS>          Run one dialogue without a terminal, e.g. \"*123#,1,0\", and exit 0/1");
+    println!("  --script <FILE>          Like --once, with one input per line of FILE");
+    println!("  --subscribers <POOL>     Play the test scenarios as virtual subscribers, e.g. 9477000000-9477000099");
+    println!("  --binds <N>              Binds the virtual subscribers share (subscribers.binds)");
+    println!("  --test                   Run the test scenarios file (testing.auto_test_on_startup) and exit 0/1");
+    println!("  --load                   Run the load test (testing.performance_test_enabled) and exit");
+    println!("  --sessions <N>           Concurrent binds in the load test (testing.concurrent_sessions)");
+    println!("  --tps <N>                Target requests per second (testing.target_tps)");
+    println!("  --duration <SECS>        Load test length (testing.duration_secs)");
+    println!("  --report <FILE>          Write every request (per-stage metrics with --load) as CSV, or JSON for .json");
+    println!("  --help                   Show this help message");
+    println!();
+    println!("Examples:");
+    println!("  ussd_user_simulator");
+    println!("  ussd_user_simulator -c /path/to/config.toml");
+    println!("  ussd_user_simulator --msisdn 9876543210 --debug");
+    println!("  ussd_user_simulator --host 192.168.1.100");
+    println!("  ussd_user_simulator --create-config");
+    println!("  ussd_user_simulator --once \"*123#,1,0\"");
+    println!("  ussd_user_simulator --test -c ci_config.toml");
+    println!("  ussd_user_simulator --subscribers 9477000000-9477000049 --binds 2");
+    println!("  ussd_user_simulator --load --sessions 50 --tps 200 --duration 120");
+}
+
+// Loaded config plus the --msisdn, --host and --port overrides and the --once or --script inputs
+type CliArgs = (UserSimulatorConfig, Option<String>, Option<String>, Option<u16>, Option<Vec<String>>);
+
+fn parse_args(args: &[String]) -> Result<CliArgs, Box<dyn std::error::Error>> {
+    let mut config_path = "user_config.toml".to_string();
+    let mut msisdn_override: Option<String> = None;
+    let mut host_override: Option<String> = None;
+    let mut port_override: Option<u16> = None;
+    let mut debug_override = false;
+    let mut load_override = false;
+    let mut test_override = false;
+    let mut sessions_override: Option<u32> = None;
+    let mut tps_override: Option<f64> = None;
+    let mut duration_override: Option<u64> = None;
+    let mut report_override: Option<String> = None;
+    let mut script_inputs: Option<Vec<String>> = None;
+    let mut subscribers_override: Option<String> = None;
+    let mut binds_override: Option<u32> = None;
+    
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "-c" | "--config" => {
+                if i + 1 < args.len() {
+                    config_path = args[i + 1].clone();
+                    i += 2;
+                } else {
+                    return Err("--config requires a value".into());
+                }
+            }
+            "-m" | "--msisdn" => {
+                if i + 1 < args.len() {
+                    msisdn_override = Some(args[i + 1].clone());
+                    i += 2;
+                } else {
+                    return Err("--msisdn requires a value".into());
+                }
+            }
+            "-h" | "--host" => {
+                if i + 1 < args.len() {
+                    host_override = Some(args[i + 1].clone());
+                    i += 2;
+                } else {
+                    return Err("--host requires a value".into());
+                }
+            }
+            "-p" | "--port" => {
+                if i + 1 < args.len() {
+                    port_override = Some(args[i + 1].parse()?);
+                    i += 2;
+                } else {
+                    return Err("--port requires a value".into());
+                }
+            }
+            "--run-id" => {
+                if i + 1 < args.len() {
+                    if let Err(e) = run_id::init(Some(args[i + 1].clone())) {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                    i += 2;
+                } else {
+                    return Err("--run-id requires a value".into());
+                }
+            }
+            "--debug" => {
+                debug_override = true;
+                i += 1;
+            }
+            "--load" => {
+                load_override = true;
+                i += 1;
+            }
+            "--test" => {
+                test_override = true;
+                i += 1;
+            }
+            "--sessions" => {
+                if i + 1 < args.len() {
+                    sessions_override = Some(args[i + 1].parse()?);
+                    i += 2;
+                } else {
+                    return Err("--sessions requires a value".into());
+                }
+            }
+            "--tps" => {
+                if i + 1 < args.len() {
+                    tps_override = Some(args[i + 1].parse()?);
+                    i += 2;
+                } else {
+                    return Err("--tps requires a value".into());
+                }
+            }
+            "--report" => {
+                if i + 1 < args.len() {
+                    report_override = Some(args[i + 1].clone());
+                    i += 2;
+                } else {
+                    return Err("--report requires a value".into());
+                }
+            }
+            "--duration" => {
+                if i + 1 < args.len() {
+                    duration_override = Some(args[i + 1].parse()?);
+                    i += 2;
+                } else {
+                    return Err("--duration requires a value".into());
+                }
+            }
+            "--subscribers" => {
+                if i + 1 < args.len() {
+                    subscribers_override = Some(args[i + 1].clone());
+                    i += 2;
+                } else {
+                    return Err("--subscribers requires a value".into());
+                }
+            }
+            "--binds" => {
+                if i + 1 < args.len() {
+                    binds_override = Some(args[i + 1].parse()?);
+                    i += 2;
+                } else {
+                    return Err("--binds requires a value".into());
+                }
+            }
+            "--once" => {
+                if i + 1 < args.len() {
+                    script_inputs = Some(script::parse_once(&args[i + 1])?);
+                    i += 2;
+                } else {
+                    return Err("--once requires a value".into());
+                }
+            }
+            "--script" => {
+                if i + 1 < args.len() {
+                    script_inputs = Some(script::read_script(&args[i + 1])?);
+                    i += 2;
+                } else {
+                    return Err("--script requires a value".into());
+                }
+            }
+            "--create-config" => {
+                let default_config = UserSimulatorConfig::default();
+                let config_content = toml::to_string_pretty(&default_config)?;
+                fs::write(&config_path, config_content)?;
+                println!("Default config created at '{}'", config_path);
+                std::process::exit(0);
+            }
+            "--help" => {
+                print_usage();
+                std::process::exit(0);
+            }
+            _ => {
+                println!("Unknown argument: {}", args[i]);
+                print_usage();
+                std::process::exit(1);
+            }
+        }
+    }
+    
+    let mut config = load_config(&config_path)?;
+    let generator = &mut config.phone.generator;
+    if generator.country_code.is_empty() && generator.network_code.is_empty() {
+        generator.country_code = config.phone.country_code.clone();
+        generator.network_code = config.phone.network_code.clone();
+    }
+    generator.validate().map_err(|e| format!("phone.generator: {}", e))?;
+    
+    // Apply overrides
+    if debug_override {
+        config.logging.debug = true;
+    }
+    if load_override {
+        config.testing.performance_test_enabled = true;
+    }
+    if test_override {
+        config.testing.auto_test_on_startup = true;
+    }
+    if let Some(sessions) = sessions_override {
+        config.testing.concurrent_sessions = sessions;
+    }
+    if let Some(tps) = tps_override {
+        config.testing.target_tps = tps;
+    }
+    if let Some(duration) = duration_override {
+        config.testing.duration_secs = duration;
+    }
+    if let Some(report) = report_override {
+        config.testing.report_file = report;
+    }
+    // A range when it has a dash, otherwise a comma-separated list
+    if let Some(subscribers) = subscribers_override {
+        config.subscribers.enabled = true;
+        if subscribers.contains('-') {
+            config.subscribers.range = subscribers;
+            config.subscribers.msisdns.clear();
+        } else {
+            config.subscribers.msisdns = subscribers.split(',').map(|msisdn| msisdn.trim().to_string()).collect();
+            config.subscribers.range.clear();
+        }
+    }
+    if let Some(binds) = binds_override {
+        config.subscribers.binds = binds;
+    }
+    
+    Ok((config, msisdn_override, host_override, port_override, script_inputs))
+}
+
+// The simulator's command line: `args` as the process got them, program name first. The
+// ussd_user_simulator binary and `ussd-sim user` both come through here.
+pub fn run(args: Vec<String>) -> std::io::Result<()> {
+    logger::init(LevelFilter::Info);
+
+    let (mut config, msisdn_override, host_override, port_override, script_inputs) = match parse_args(&args) {
+        Ok((config, msisdn, host, port, inputs)) => (config, msisdn, host, port, inputs),
+        Err(e) => {
+            eprintln!("Error parsing arguments: {}", e);
+            print_usage();
+            std::process::exit(1);
+        }
+    };
+    
+    // Apply command-line overrides; without --msisdn a generator picks the phone's number
+    if let Some(msisdn) = msisdn_override {
+        config.phone.default_msisdn = msisdn;
+        config.phone.generator = MsisdnGenerator::default();
+    } else if let Some(msisdn) = config.phone.generator.nth(0) {
+        config.phone.default_msisdn = msisdn;
+    }
+    if let Some(host) = host_override {
+        config.server.host = host;
+    }
+    if let Some(port) = port_override {
+        config.server.port = port;
+    }
+    
+    if config.logging.debug {
+        println!("🔧 Debug mode enabled");
+        println!("📱 MSISDN: {}", config.phone.default_msisdn);
+        println!("🌐 Server: {}:{}", config.server.host, config.server.port);
+        println!("👤 System ID: {}", config.authentication.system_id);
+        println!();
+    }
+    
+    println!("📱 Starting USSD User Simulator...");
+    println!("🏢 Operator: {}", config.phone.operator_name);
+    let run_id = match run_id::init(None) {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("Error: {} (from ${})", e, run_id::RUN_ID_ENV);
+            std::process::exit(1);
+        }
+    };
+    println!("🏷️  Run ID: {}", run_id);
+    println!("🌐 Connecting to: {}:{}", config.server.host, config.server.port);
+    println!();
+    
+    if let Some(inputs) = script_inputs {
+        std::process::exit(script::run(&config, &inputs));
+    }
+    if config.subscribers.enabled {
+        std::process::exit(subscribers::run(&config));
+    }
+    if config.testing.performance_test_enabled {
+        return load::run(&config);
+    }
+    if config.testing.auto_test_on_startup {
+        let code = UssdMobileUI::new(config).run_tests();
+        std::process::exit(code);
+    }
+    
+    let mut ui = UssdMobileUI::new(config);
+    ui.start()?;
+    
+    Ok(())
+}
+
+impl UssdMobileUI {
+    fn get_user_input(&self) -> std::io::Result<String> {
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        Ok(input.trim().to_string())
+    }
+
+    fn clear_screen(&self) {
+        // Clear screen (works on most terminals)
+        print!("\x1B[2J\x1B[1;1H");
+        io::stdout().flush().unwrap();
+    }
+}
+#[cfg(test)]
+mod tests {
+    use smpp_codec::{TAG_USSD_SERVICE_OP, USSD_PSSR_RESPONSE, USSD_USSR_REQUEST};
+
+    use super::*;
+
+    // A step sending `code` that passes on a screen containing any of `keywords`
+    pub(crate) fn dialling(code: &str, keywords: &[&str]) -> ScenarioStep {
+        ScenarioStep {
+            ussd_code: code.to_string(),
+            description: String::new(),
+            expected_keywords: keywords.iter().map(|keyword| keyword.to_string()).collect(),
+            timeout_ms: 0,
+            expect_contains: Vec::new(),
+            expect_regex: None,
+            expect_end: None,
+            max_latency_ms: None,
+        }
+    }
+
+    // The screen a DELIVER_SM with `text` and, when given, `ussd_service_op` shows
+    fn screen(text: &str, ussd_service_op: Option<u8>) -> UssdResponse {
+        let deliver_sm = DeliverSm {
+            short_message: text.as_bytes().into(),
+            optional_params: ussd_service_op.map(|op| OptionalParam::u8(TAG_USSD_SERVICE_OP, op)).into_iter().collect(),
+            ..Default::default()
+        };
+        UssdSmppClient::new(UserSimulatorConfig::default()).parse_deliver_sm(&deliver_sm.encode()).unwrap()
+    }
+
+    #[test]
+    fn test_ussd_service_op_says_whether_the_session_goes_on() {
+        let menu = screen("1. Balance\n2. Bundles", Some(USSD_USSR_REQUEST));
+        assert_eq!((menu.text.as_str(), menu.notify, menu.end), ("1. Balance\n2. Bundles", false, false));
+        let last = screen("Your balance is 10", Some(USSD_PSSR_RESPONSE));
+        assert_eq!((last.notify, last.end), (false, true));
+        let notify = screen("Top up received", Some(USSD_NOTIFY));
+        assert_eq!((notify.notify, notify.end), (true, true));
+        let terminated = screen("Session closed", Some(USSD_TERMINATE_NOTIFY));
+        assert_eq!((terminated.notify, terminated.end), (true, true));
+        // The TLV wins over the text: a menu may well say thank you
+        assert!(!screen("Thank you for calling\n1. Continue", Some(USSD_USSR_REQUEST)).end);
+    }
+
+    #[test]
+    fn test_without_ussd_service_op_the_text_says_whether_the_session_ended() {
+        for phrase in SESSION_END_PHRASES {
+            let response = screen(&format!("{}. See you soon", phrase), None);
+            assert!(response.end && !response.notify, "{}", phrase);
+        }
+        assert!(!screen("1. Balance\n2. Bundles", None).end);
+    }
+
+    #[test]
+    fn test_ucs2_screens_and_undecodable_bodies() {
+        let deliver_sm = DeliverSm {
+            data_coding: 8,
+            short_message: encoding::encode("ශේෂය 10", 8, false).into(),
+            optional_params: vec![OptionalParam::u8(TAG_USSD_SERVICE_OP, USSD_PSSR_RESPONSE)],
+            ..Default::default()
+        };
+        let client = UssdSmppClient::new(UserSimulatorConfig::default());
+        assert_eq!(client.parse_deliver_sm(&deliver_sm.encode()).unwrap().text, "ශේෂය 10");
+        assert!(client.parse_deliver_sm(b"USSD\0").is_err());
+    }
+
+    fn visited(screens: &[(&str, &str)]) -> SessionHistory {
+        let mut history = SessionHistory::default();
+        for (input, screen) in screens {
+            history.record(input, screen);
+        }
+        history
+    }
+
+    #[test]
+    fn test_back_replays_up_to_the_previous_screen() {
+        let mut history = visited(&[("*123#", "1. Balance\n2. Bundles"), ("2", "1. Daily\n2. Weekly"), ("1", "Confirm?")]);
+        assert!(history.can_go_back());
+
+        let (replay, shown) = history.back();
+        assert_eq!((replay, shown.as_str()), (vec!["*123#".to_string()], "2"));
+        // The replay and the screen it leads to go back on the path as they are answered
+        history.record("*123#", "1. Balance\n2. Bundles");
+        history.record("2", "1. Daily\n2. Weekly");
+        assert_eq!(history.path, ["*123#", "2"]);
+
+        let (replay, shown) = history.back();
+        assert_eq!((replay.len(), shown.as_str()), (0, "*123#"));
+        history.record("*123#", "1. Balance\n2. Bundles");
+        assert!(!history.can_go_back());
+        // Nothing is forgotten for /history
+        assert_eq!(history.transcript.len(), 6);
+    }
+
+    #[test]
+    fn test_redial_starts_over_from_the_dialled_code() {
+        let mut history = visited(&[("*123#", "Menu"), ("1", "Balance: 10")]);
+        assert_eq!(history.redial(), "*123#");
+        assert!(history.path.is_empty());
+        assert_eq!(history.transcript.len(), 2);
+        assert_eq!(SessionHistory::default().redial(), "");
+    }
+
+    #[test]
+    fn test_history_lists_every_exchange_in_order() {
+        let history = visited(&[("*123#", "1. Balance\n2. Bundles"), ("1", "Balance: 10")]);
+        let rendered = history.render();
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(
+            lines,
+            ["", "📜 Session history:", "  1. > *123#", "       < 1. Balance", "       < 2. Bundles", "  2. > 1", "       < Balance: 10"]
+        );
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        std::iter::once("ussd_user_simulator").chain(args.iter().copied()).map(str::to_string).collect()
+    }
+
+    // parse_args writes a default config where none exists, so every test gets its own file
+    fn config_file(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("{}_{}.toml", name, std::process::id()));
+        fs::write(&path, toml::to_string_pretty(&UserSimulatorConfig::default()).unwrap()).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_once_and_script_give_the_inputs_to_send() {
+        let config = config_file("args_once");
+        let (_, _, _, _, inputs) = parse_args(&args(&["-c", &config, "--once", "*123#, 1,0"])).unwrap();
+        assert_eq!(inputs.unwrap(), ["*123#", "1", "0"]);
+
+        let script = std::env::temp_dir().join(format!("args_script_{}.txt", std::process::id()));
+        fs::write(&script, "*123#\n2\n").unwrap();
+        let parsed = parse_args(&args(&["-c", &config, "--script", script.to_str().unwrap()]));
+        let _ = fs::remove_file(&script);
+        let (_, _, _, _, inputs) = parsed.unwrap();
+        assert_eq!(inputs.unwrap(), ["*123#", "2"]);
+
+        // Without either, the simulator runs interactively
+        let (_, _, _, _, inputs) = parse_args(&args(&["-c", &config])).unwrap();
+        let _ = fs::remove_file(&config);
+        assert!(inputs.is_none());
+    }
+
+    #[test]
+    fn test_once_and_script_reject_missing_or_empty_inputs() {
+        let config = config_file("args_empty");
+        let error = |extra: &[&str]| {
+            let mut all = vec!["-c", config.as_str()];
+            all.extend_from_slice(extra);
+            parse_args(&args(&all)).err().map(|e| e.to_string())
+        };
+        assert_eq!(error(&["--once"]).as_deref(), Some("--once requires a value"));
+        assert_eq!(error(&["--script"]).as_deref(), Some("--script requires a value"));
+        assert_eq!(error(&["--once", " , "]).as_deref(), Some("--once has no inputs"));
+
+        let missing = std::env::temp_dir().join(format!("args_missing_{}.txt", std::process::id()));
+        let missing = missing.to_str().unwrap();
+        assert!(error(&["--script", missing]).unwrap().starts_with(missing));
+        let _ = fs::remove_file(&config);
+    }
+}
@@ -0,0 +1,591 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::LevelFilter;
+use serde::Serialize;
+use smpp_codec::SmppError;
+
+use crate::report::quote;
+use crate::{
+    load_test_scenarios, LoadStage, ScenarioStep, TestScenarios, TestingConfig, UserSimulatorConfig, UssdSmppClient, ABANDON_INPUT,
+};
+
+// A stage laid out on the run's timeline, its rate moving linearly from `from_tps` to `to_tps`
+#[derive(Debug, Clone)]
+struct PacedStage {
+    name: String,
+    start_secs: f64,
+    duration_secs: f64,
+    from_tps: f64,
+    to_tps: f64,
+    requests_before: f64, // Requests the earlier stages schedule
+}
+
+impl PacedStage {
+    fn requests(&self) -> f64 {
+        (self.from_tps + self.to_tps) / 2.0 * self.duration_secs
+    }
+
+    fn rate(&self) -> String {
+        match self.from_tps == self.to_tps {
+            true => format!("{:.1}", self.to_tps),
+            false => format!("{:.1}→{:.1}", self.from_tps, self.to_tps),
+        }
+    }
+}
+
+// `testing.stages`, or one stage of `testing.target_tps` for `testing.duration_secs` without any
+fn paced_stages(testing: &TestingConfig) -> Result<Vec<PacedStage>, String> {
+    if testing.stages.is_empty() && (testing.duration_secs == 0 || !testing.target_tps.is_finite() || testing.target_tps < 0.0) {
+        return Err("testing needs duration_secs above 0 and target_tps of 0 or more".to_string());
+    }
+    let single = [LoadStage { name: "steady".to_string(), duration_secs: testing.duration_secs, tps: testing.target_tps, ramp: false }];
+    let stages = if testing.stages.is_empty() { &single[..] } else { &testing.stages[..] };
+    let mut paced: Vec<PacedStage> = Vec::new();
+    for (index, stage) in stages.iter().enumerate() {
+        if stage.duration_secs == 0 || !stage.tps.is_finite() || stage.tps < 0.0 {
+            return Err(format!("testing.stages[{}] needs duration_secs above 0 and tps of 0 or more", index));
+        }
+        let (start_secs, requests_before, previous_tps) = match paced.last() {
+            Some(last) => (last.start_secs + last.duration_secs, last.requests_before + last.requests(), last.to_tps),
+            None => (0.0, 0.0, 0.0),
+        };
+        paced.push(PacedStage {
+            name: if stage.name.is_empty() { format!("stage {}", index + 1) } else { stage.name.clone() },
+            start_secs,
+            duration_secs: stage.duration_secs as f64,
+            from_tps: if stage.ramp { previous_tps } else { stage.tps },
+            to_tps: stage.tps,
+            requests_before,
+        });
+    }
+    Ok(paced)
+}
+
+// Hands out request slots at the stages' rates until the last stage is over
+struct Pacer {
+    start: Instant,
+    stages: Vec<PacedStage>,
+    unlimited: bool, // target_tps = 0 without stages: as fast as the binds allow
+    issued: AtomicU64,
+}
+
+impl Pacer {
+    fn end(&self) -> Instant {
+        let last = self.stages.last().expect("at least one stage");
+        self.start + Duration::from_secs_f64(last.start_secs + last.duration_secs)
+    }
+
+    // When the next request is due, and the stage it belongs to
+    fn next_slot(&self) -> Option<(Instant, usize)> {
+        let now = Instant::now();
+        if now >= self.end() {
+            return None;
+        }
+        if self.unlimited {
+            return Some((now, 0));
+        }
+        let slot = self.issued.fetch_add(1, Ordering::Relaxed);
+        let (offset, stage) = self.offset_of(slot as f64)?;
+        Some((self.start + Duration::from_secs_f64(offset), stage))
+    }
+
+    // Seconds from the start until `count` requests are due: where the rate's integral reaches it
+    fn offset_of(&self, count: f64) -> Option<(f64, usize)> {
+        let (index, stage) = self.stages.iter().enumerate().find(|(_, stage)| count < stage.requests_before + stage.requests())?;
+        let count = count - stage.requests_before;
+        if count <= 0.0 {
+            return Some((stage.start_secs, index));
+        }
+        // Solves from * t + slope * t² / 2 = count for the first t, in a form that also holds
+        // for a flat rate and for a ramp down
+        let half_slope = (stage.to_tps - stage.from_tps) / (2.0 * stage.duration_secs);
+        let root = (stage.from_tps * stage.from_tps + 4.0 * half_slope * count).max(0.0).sqrt();
+        Some((stage.start_secs + 2.0 * count / (stage.from_tps + root), index))
+    }
+}
+
+#[derive(Default)]
+struct Tally {
+    latencies: Vec<Duration>,      // Of requests answered with the expected screen
+    errors: BTreeMap<String, u64>, // Failed requests by cause
+}
+
+impl Tally {
+    fn fail(&mut self, cause: String) {
+        *self.errors.entry(cause).or_default() += 1;
+    }
+
+    fn merge(&mut self, other: Tally) {
+        self.latencies.extend(other.latencies);
+        for (cause, count) in other.errors {
+            *self.errors.entry(cause).or_default() += count;
+        }
+    }
+
+    fn failed(&self) -> u64 {
+        self.errors.values().sum()
+    }
+}
+
+struct WorkerReport {
+    stages: Vec<Tally>, // By the stage each request was sent in
+    dialogs: u64,
+}
+
+// One line of the stage report file
+#[derive(Debug, Serialize)]
+struct StageSnapshot {
+    stage: String,
+    start_secs: f64,
+    duration_secs: f64,
+    from_tps: f64,
+    to_tps: f64,
+    requests: u64,
+    succeeded: u64,
+    failed: u64,
+    achieved_tps: f64,
+    p50_ms: Option<f64>,
+    p95_ms: Option<f64>,
+    p99_ms: Option<f64>,
+    max_ms: Option<f64>,
+    errors: BTreeMap<String, u64>,
+}
+
+impl StageSnapshot {
+    fn new(stage: &PacedStage, tally: &Tally) -> Self {
+        let succeeded = tally.latencies.len() as u64;
+        let failed = tally.failed();
+        let millis = |percent| (!tally.latencies.is_empty()).then(|| percentile(&tally.latencies, percent).as_secs_f64() * 1000.0);
+        StageSnapshot {
+            stage: stage.name.clone(),
+            start_secs: stage.start_secs,
+            duration_secs: stage.duration_secs,
+            from_tps: stage.from_tps,
+            to_tps: stage.to_tps,
+            requests: succeeded + failed,
+            succeeded,
+            failed,
+            achieved_tps: (succeeded + failed) as f64 / stage.duration_secs,
+            p50_ms: millis(50),
+            p95_ms: millis(95),
+            p99_ms: millis(99),
+            max_ms: tally.latencies.last().map(|max| max.as_secs_f64() * 1000.0),
+            errors: tally.errors.clone(),
+        }
+    }
+}
+
+// Binds `testing.concurrent_sessions` subscribers, one MSISDN each from `phone.generator` (or
+// counting up from `phone.default_msisdn`), and has them dial the dialogues in `testing.test_scenarios_file` at
+// the rates of `testing.stages` (or `testing.target_tps` for `testing.duration_secs`)
+pub fn run(config: &UserSimulatorConfig) -> io::Result<()> {
+    let testing = &config.testing;
+    let stages = paced_stages(testing).map_err(io::Error::other)?;
+    let scenarios = load_test_scenarios(&testing.test_scenarios_file)
+        .map_err(|e| io::Error::other(format!("{}: {}", testing.test_scenarios_file, e)))?;
+    let dialogs = dialogues(&scenarios);
+    if dialogs.is_empty() {
+        return Err(io::Error::other(format!("{} has no dialogues to run", testing.test_scenarios_file)));
+    }
+    let unlimited = testing.stages.is_empty() && testing.target_tps == 0.0;
+
+    println!("🚀 Load test: {} binds, {} dialogues against {}:{}", bind_configs(config).len(), dialogs.len(), config.server.host, config.server.port);
+    for stage in &stages {
+        let rate = if unlimited { "unlimited".to_string() } else { stage.rate() };
+        println!("   {:<16} {:>6}s at {} requests/s", stage.name, stage.duration_secs, rate);
+    }
+    // Each request's own log lines would bury the report
+    let level = log::max_level();
+    log::set_max_level(LevelFilter::Warn);
+    let outcome = drive(config, stages, unlimited, dialogs);
+    log::set_max_level(level);
+
+    print_report(&outcome.total, &outcome.snapshots, outcome.elapsed, outcome.binds, outcome.dialogs);
+    if !testing.report_file.is_empty() {
+        write_snapshots(&testing.report_file, &outcome.snapshots)?;
+        println!("📄 Stage report written to {}", testing.report_file);
+    }
+    Ok(())
+}
+
+// What a run measured, overall and per stage
+struct LoadOutcome {
+    total: Tally,
+    snapshots: Vec<StageSnapshot>,
+    elapsed: Duration,
+    binds: usize,
+    dialogs: u64,
+}
+
+// One config per bind, `testing.concurrent_sessions` of them (at least one), each with its own MSISDN
+fn bind_configs(config: &UserSimulatorConfig) -> Vec<UserSimulatorConfig> {
+    (0..config.testing.concurrent_sessions.max(1) as usize)
+        .map(|index| {
+            let mut config = config.clone();
+            config.phone.default_msisdn = config.phone.msisdn(index);
+            config.logging.debug = false;
+            config
+        })
+        .collect()
+}
+
+// Runs every bind's worker over the stages until the last one ends
+fn drive(config: &UserSimulatorConfig, stages: Vec<PacedStage>, unlimited: bool, dialogs: Vec<Vec<ScenarioStep>>) -> LoadOutcome {
+    let dialogs = Arc::new(dialogs);
+    let binds = bind_configs(config);
+    let start = Instant::now();
+    let pacer = Arc::new(Pacer { start, stages: stages.clone(), unlimited, issued: AtomicU64::new(0) });
+    let workers: Vec<_> = binds
+        .iter()
+        .cloned()
+        .enumerate()
+        .map(|(index, config)| {
+            let pacer = Arc::clone(&pacer);
+            let dialogs = Arc::clone(&dialogs);
+            thread::spawn(move || run_worker(config, index, &pacer, &dialogs))
+        })
+        .collect();
+    let mut tallies: Vec<Tally> = stages.iter().map(|_| Tally::default()).collect();
+    let mut dialogs_run = 0;
+    for worker in workers {
+        let worker = worker.join().expect("load worker panicked");
+        dialogs_run += worker.dialogs;
+        for (tally, stage) in tallies.iter_mut().zip(worker.stages) {
+            tally.merge(stage);
+        }
+    }
+    let elapsed = start.elapsed();
+
+    for tally in &mut tallies {
+        tally.latencies.sort();
+    }
+    let snapshots: Vec<StageSnapshot> = stages.iter().zip(&tallies).map(|(stage, tally)| StageSnapshot::new(stage, tally)).collect();
+    let mut total = Tally::default();
+    for tally in tallies {
+        total.merge(tally);
+    }
+    total.latencies.sort();
+    LoadOutcome { total, snapshots, elapsed, binds: binds.len(), dialogs: dialogs_run }
+}
+
+// The scenarios' steps as dialogues to replay. An abandon step ends the dialogue there; these
+// runs never wait for the server's timeout.
+pub(crate) fn dialogues(scenarios: &TestScenarios) -> Vec<Vec<ScenarioStep>> {
+    scenarios
+        .scenarios
+        .iter()
+        .map(|scenario| scenario.steps.iter().take_while(|step| !step.ussd_code.eq_ignore_ascii_case(ABANDON_INPUT)).cloned().collect())
+        .filter(|steps: &Vec<ScenarioStep>| !steps.is_empty())
+        .collect()
+}
+
+fn run_worker(config: UserSimulatorConfig, index: usize, pacer: &Pacer, dialogs: &[Vec<ScenarioStep>]) -> WorkerReport {
+    let mut report = WorkerReport { stages: pacer.stages.iter().map(|_| Tally::default()).collect(), dialogs: 0 };
+    let mut client = UssdSmppClient::new(config);
+    match client.connect() {
+        Ok(true) => {}
+        Ok(false) => {
+            report.stages[0].fail("bind refused".to_string());
+            return report;
+        }
+        Err(e) => {
+            report.stages[0].fail(format!("connect: {}", e.kind()));
+            return report;
+        }
+    }
+
+    // Workers start on different dialogues so every one is exercised from the first second
+    for steps in dialogs.iter().cycle().skip(index % dialogs.len()) {
+        report.dialogs += 1;
+        for step in steps {
+            let Some((due, stage)) = pacer.next_slot() else {
+                client.disconnect();
+                return report;
+            };
+            if let Some(wait) = due.checked_duration_since(Instant::now()) {
+                thread::sleep(wait);
+            }
+
+            let tally = &mut report.stages[stage];
+            let sent = Instant::now();
+            match client.send_ussd_request(&step.ussd_code) {
+                Ok(response) if step.matches(&response.text) => {
+                    tally.latencies.push(sent.elapsed());
+                    // The screen closed the session, so the rest of the dialogue has nothing to answer
+                    if response.end {
+                        break;
+                    }
+                }
+                Ok(_) => {
+                    tally.fail("unexpected screen".to_string());
+                    break;
+                }
+                Err(e) => {
+                    let transport = matches!(e, SmppError::Timeout(_) | SmppError::Io(_));
+                    tally.fail(cause(&e));
+                    // A late answer would be read as the next request's, and a broken stream
+                    // cannot be reused
+                    if transport && !matches!(client.reconnect(), Ok(true)) {
+                        tally.fail("reconnect failed".to_string());
+                        return report;
+                    }
+                    break;
+                }
+            }
+        }
+    }
+    report
+}
+
+fn cause(error: &SmppError) -> String {
+    match error {
+        SmppError::Protocol { status, .. } => format!("command_status 0x{:08X}", status),
+        SmppError::Auth { status, .. } => format!("bind refused 0x{:08X}", status),
+        SmppError::Timeout(_) => "timeout".to_string(),
+        SmppError::Io(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => "timeout".to_string(),
+        SmppError::Io(e) => format!("connection: {}", e.kind()),
+        SmppError::Encoding(_) => "undecodable PDU".to_string(),
+        SmppError::Routing(_) => "no route".to_string(),
+    }
+}
+
+fn print_report(total: &Tally, snapshots: &[StageSnapshot], elapsed: Duration, sessions: usize, dialogs: u64) {
+    let succeeded = total.latencies.len() as u64;
+    let failed = total.failed();
+    let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+
+    println!();
+    println!("╔════════════════════════════════════════╗");
+    println!("║           LOAD TEST RESULTS            ║");
+    println!("╚════════════════════════════════════════╝");
+    println!("Binds:       {}", sessions);
+    println!("Elapsed:     {:.2?}", elapsed);
+    println!("Dialogues:   {}", dialogs);
+    println!("Requests:    {} ({} ok, {} failed)", succeeded + failed, succeeded, failed);
+    println!("Throughput:  {:.1} requests/s", (succeeded + failed) as f64 / seconds);
+    if let Some(max) = total.latencies.last() {
+        let latencies = &total.latencies;
+        println!("Latency:     p50 {:.2?}, p95 {:.2?}, p99 {:.2?}, max {:.2?}",
+            percentile(latencies, 50), percentile(latencies, 95), percentile(latencies, 99), max);
+    }
+    if snapshots.len() > 1 {
+        println!("Stages:");
+        for snapshot in snapshots {
+            let millis = |value: Option<f64>| value.map_or("-".to_string(), |ms| format!("{:.1}ms", ms));
+            println!("  {:<16} {:>7.1} of {:>7.1} requests/s  p50 {:>8}  p95 {:>8}  p99 {:>8}  {} failed",
+                snapshot.stage, snapshot.achieved_tps, (snapshot.from_tps + snapshot.to_tps) / 2.0, millis(snapshot.p50_ms), millis(snapshot.p95_ms),
+                millis(snapshot.p99_ms), snapshot.failed);
+        }
+    }
+    if !total.errors.is_empty() {
+        println!("Errors:");
+        for (cause, count) in &total.errors {
+            println!("  {:<32} {}", cause, count);
+        }
+    }
+}
+
+// JSON when the path ends in .json, CSV otherwise
+fn write_snapshots(path: &str, snapshots: &[StageSnapshot]) -> io::Result<()> {
+    if path.ends_with(".json") {
+        return fs::write(path, serde_json::to_string_pretty(snapshots)?);
+    }
+    let number = |value: Option<f64>| value.map_or(String::new(), |value| format!("{:.3}", value));
+    let mut csv = String::from("stage,start_secs,duration_secs,from_tps,to_tps,requests,succeeded,failed,achieved_tps,p50_ms,p95_ms,p99_ms,max_ms,errors\n");
+    for snapshot in snapshots {
+        let errors: Vec<String> = snapshot.errors.iter().map(|(cause, count)| format!("{}={}", cause, count)).collect();
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{:.3},{},{},{},{},{}\n",
+            quote(&snapshot.stage),
+            snapshot.start_secs,
+            snapshot.duration_secs,
+            snapshot.from_tps,
+            snapshot.to_tps,
+            snapshot.requests,
+            snapshot.succeeded,
+            snapshot.failed,
+            snapshot.achieved_tps,
+            number(snapshot.p50_ms),
+            number(snapshot.p95_ms),
+            number(snapshot.p99_ms),
+            number(snapshot.max_ms),
+            quote(&errors.join(";")),
+        ));
+    }
+    fs::write(path, csv)
+}
+
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    sorted[(sorted.len() * percent / 100).min(sorted.len() - 1)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ussd_smpp_simulator::{Config, EmbeddedServer, UssdSmppServer};
+
+    use crate::tests::dialling;
+
+    fn testing(configure: impl FnOnce(&mut TestingConfig)) -> TestingConfig {
+        let mut testing = UserSimulatorConfig::default().testing;
+        configure(&mut testing);
+        testing
+    }
+
+    fn pacer(testing: &TestingConfig) -> Pacer {
+        Pacer { start: Instant::now(), stages: paced_stages(testing).unwrap(), unlimited: false, issued: AtomicU64::new(0) }
+    }
+
+    fn assert_offset(pacer: &Pacer, count: u64, seconds: f64, stage: usize) {
+        let (offset, index) = pacer.offset_of(count as f64).unwrap();
+        assert!((offset - seconds).abs() < 1e-9, "request {} due at {}s, not {}s", count, offset, seconds);
+        assert_eq!(index, stage, "request {}", count);
+    }
+
+    #[test]
+    fn test_steady_rate_spaces_requests_evenly() {
+        let pacer = pacer(&testing(|testing| {
+            testing.target_tps = 10.0;
+            testing.duration_secs = 2;
+        }));
+        assert_offset(&pacer, 0, 0.0, 0);
+        assert_offset(&pacer, 1, 0.1, 0);
+        assert_offset(&pacer, 19, 1.9, 0);
+        // 10 requests/s for 2s is 20 requests, and no more
+        assert!(pacer.offset_of(20.0).is_none());
+    }
+
+    #[test]
+    fn test_ramp_climbs_from_the_previous_stage_rate() {
+        let pacer = pacer(&testing(|testing| {
+            testing.stages = vec![
+                LoadStage { name: "warm".to_string(), duration_secs: 2, tps: 10.0, ramp: false },
+                LoadStage { name: String::new(), duration_secs: 2, tps: 30.0, ramp: true },
+            ];
+        }));
+        assert_eq!((pacer.stages[1].name.as_str(), pacer.stages[1].from_tps, pacer.stages[1].to_tps), ("stage 2", 10.0, 30.0));
+        assert_offset(&pacer, 20, 2.0, 1);
+        // 10t + 5t² = 10 requests into the ramp
+        assert_offset(&pacer, 30, 2.0 + (3f64.sqrt() - 1.0), 1);
+        assert_offset(&pacer, 59, 2.0 + ((100.0f64 + 20.0 * 39.0).sqrt() - 10.0) / 10.0, 1);
+        assert!(pacer.offset_of(60.0).is_none());
+    }
+
+    #[test]
+    fn test_stages_need_a_duration_and_a_rate() {
+        assert!(paced_stages(&testing(|testing| testing.duration_secs = 0)).is_err());
+        assert!(paced_stages(&testing(|testing| testing.target_tps = -1.0)).is_err());
+        let stage = LoadStage { name: String::new(), duration_secs: 1, tps: f64::NAN, ramp: false };
+        let error = paced_stages(&testing(|testing| testing.stages = vec![stage])).unwrap_err();
+        assert!(error.starts_with("testing.stages[0]"), "{}", error);
+    }
+
+    #[test]
+    fn test_every_bind_gets_its_own_msisdn() {
+        let mut config = UserSimulatorConfig::default();
+        config.testing.concurrent_sessions = 0;
+        assert_eq!(bind_configs(&config).len(), 1);
+
+        config.testing.concurrent_sessions = 3;
+        let msisdns: Vec<String> = bind_configs(&config).into_iter().map(|config| config.phone.default_msisdn).collect();
+        assert_eq!(msisdns, ["1234567890", "1234567891", "1234567892"]);
+    }
+
+    // A server answering every request at once, on an ephemeral port
+    fn embedded_server() -> EmbeddedServer {
+        UssdSmppServer::builder()
+            .config(Config::deterministic())
+            .configure(|config| {
+                config.server.host = "127.0.0.1".to_string();
+                config.server.port = 0;
+            })
+            .spawn()
+            .unwrap()
+    }
+
+    fn against(embedded: &EmbeddedServer, configure: impl FnOnce(&mut TestingConfig)) -> UserSimulatorConfig {
+        let mut config = UserSimulatorConfig::default();
+        config.server.host = embedded.addr.ip().to_string();
+        config.server.port = embedded.addr.port();
+        configure(&mut config.testing);
+        config
+    }
+
+    #[test]
+    fn test_binds_share_one_paced_rate() {
+        let embedded = embedded_server();
+        let config = against(&embedded, |testing| {
+            testing.concurrent_sessions = 3;
+            testing.target_tps = 20.0;
+            testing.duration_secs = 1;
+        });
+        let outcome = drive(&config, paced_stages(&config.testing).unwrap(), false, vec![vec![dialling("*123#", &["Welcome"])]]);
+
+        assert_eq!(outcome.binds, 3);
+        // 20 requests/s for a second across all three binds, not per bind
+        let requests = outcome.snapshots[0].requests;
+        assert!((18..=20).contains(&requests), "{} requests", requests);
+        assert_eq!(outcome.total.failed(), 0, "{:?}", outcome.total.errors);
+        assert_eq!(outcome.total.latencies.len() as u64, requests);
+    }
+
+    fn stage(name: &str, duration_secs: u64, tps: f64, ramp: bool) -> LoadStage {
+        LoadStage { name: name.to_string(), duration_secs, tps, ramp }
+    }
+
+    #[test]
+    fn test_each_stage_reports_the_requests_sent_in_it() {
+        let embedded = embedded_server();
+        let config = against(&embedded, |testing| {
+            testing.concurrent_sessions = 2;
+            testing.stages = vec![stage("warm", 1, 10.0, false), stage("spike", 1, 30.0, false), stage("cool", 1, 10.0, true)];
+        });
+        let outcome = drive(&config, paced_stages(&config.testing).unwrap(), false, vec![vec![dialling("*123#", &["Welcome"])]]);
+
+        let names: Vec<&str> = outcome.snapshots.iter().map(|snapshot| snapshot.stage.as_str()).collect();
+        assert_eq!(names, ["warm", "spike", "cool"]);
+        let starts: Vec<f64> = outcome.snapshots.iter().map(|snapshot| snapshot.start_secs).collect();
+        assert_eq!(starts, [0.0, 1.0, 2.0]);
+        // The ramp falls from the spike back to 10/s, so sends 20 in its second
+        for (snapshot, expected) in outcome.snapshots.iter().zip([10, 30, 20]) {
+            assert!((expected - 2..=expected).contains(&snapshot.requests), "{}: {} requests", snapshot.stage, snapshot.requests);
+            assert_eq!((snapshot.failed, snapshot.succeeded), (0, snapshot.requests), "{}: {:?}", snapshot.stage, snapshot.errors);
+            assert!(snapshot.p50_ms.is_some() && snapshot.p50_ms <= snapshot.max_ms, "{:?}", snapshot);
+        }
+        assert_eq!((outcome.snapshots[2].from_tps, outcome.snapshots[2].to_tps), (30.0, 10.0));
+        assert!(outcome.elapsed >= Duration::from_millis(2900), "{:?}", outcome.elapsed);
+    }
+
+    #[test]
+    fn test_failures_are_counted_in_the_stage_they_happen_in() {
+        let embedded = embedded_server();
+        let config = against(&embedded, |testing| {
+            testing.stages = vec![stage("first", 1, 5.0, false), stage("second", 1, 5.0, false)];
+        });
+        let dialogs = vec![vec![dialling("*123#", &["No such screen"])]];
+        let outcome = drive(&config, paced_stages(&config.testing).unwrap(), false, dialogs);
+
+        for snapshot in &outcome.snapshots {
+            assert_eq!(snapshot.succeeded, 0, "{}", snapshot.stage);
+            assert!(snapshot.failed >= 4, "{}: {} failed", snapshot.stage, snapshot.failed);
+            assert_eq!(snapshot.errors.get("unexpected screen"), Some(&snapshot.failed), "{}", snapshot.stage);
+            assert_eq!((snapshot.p50_ms, snapshot.max_ms), (None, None));
+        }
+
+        let path = std::env::temp_dir().join(format!("load_stages_{}.csv", std::process::id()));
+        write_snapshots(path.to_str().unwrap(), &outcome.snapshots).unwrap();
+        let csv = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("first,0,1,5,5,"), "{}", lines[1]);
+        assert!(lines[2].starts_with("second,1,1,5,5,"), "{}", lines[2]);
+        assert!(lines[2].ends_with(&format!(",,,,,unexpected screen={}", outcome.snapshots[1].failed)), "{}", lines[2]);
+    }
+}
@@ -0,0 +1,164 @@
+use std::io::{self, Write};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use log::info;
+use smpp_codec::{DeliverSm, SmppError, SmppPdu, DELIVER_SM, ENQUIRE_LINK, ENQUIRE_LINK_RESP};
+
+use crate::heartbeat::Link;
+use crate::SmppStream;
+
+// Reads everything the server sends on a thread of its own, so nothing it sends waits for the
+// next request: ENQUIRE_LINKs are answered and DELIVER_SMs acknowledged as they arrive. Every PDU
+// but ENQUIRE_LINK and the heartbeat's ENQUIRE_LINK_RESPs is passed on in order; the last item is
+// the error that ended the connection, which also marks `link` lost.
+pub fn spawn(mut stream: SmppStream, writer: Arc<Mutex<SmppStream>>, link: Arc<Link>, debug: bool) -> Receiver<io::Result<SmppPdu>> {
+    let (sender, pdus) = mpsc::channel();
+    thread::spawn(move || {
+        if let Err(e) = read_pdus(&mut stream, &writer, &link, &sender, debug) {
+            link.lose();
+            let _ = sender.send(Err(e));
+        }
+    });
+    pdus
+}
+
+// Returns Ok once nobody is listening any more
+fn read_pdus(
+    stream: &mut SmppStream,
+    writer: &Mutex<SmppStream>,
+    link: &Link,
+    sender: &Sender<io::Result<SmppPdu>>,
+    debug: bool,
+) -> io::Result<()> {
+    loop {
+        let pdu = SmppPdu::read_from(stream)?;
+        link.heard();
+        if debug {
+            info!("📖 Read PDU header - Length: {}, Command: 0x{:08x}, Status: 0x{:08x}, Seq: {}",
+                pdu.header.command_length, pdu.header.command_id, pdu.header.command_status, pdu.header.sequence_number);
+        }
+        match pdu.header.command_id {
+            // The server's keepalive drops binds that leave ENQUIRE_LINK unanswered
+            ENQUIRE_LINK => {
+                if debug {
+                    info!("💓 Answering ENQUIRE_LINK seq={}", pdu.header.sequence_number);
+                }
+                write_pdu(writer, &pdu.ok_response())?;
+                continue;
+            }
+            // Only the heartbeat sends ENQUIRE_LINK, and hearing anything is all it waits for
+            ENQUIRE_LINK_RESP => continue,
+            DELIVER_SM => match DeliverSm::decode(&pdu.body) {
+                Ok(_) => write_pdu(writer, &pdu.ok_response())?,
+                Err(e) => write_pdu(writer, &pdu.response(SmppError::from(e).command_status()))?,
+            },
+            _ => {}
+        }
+        if sender.send(Ok(pdu)).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+// Writes hold the lock for the whole PDU, so the reader's answers never split a request
+pub fn write_pdu(writer: &Mutex<SmppStream>, pdu: &SmppPdu) -> io::Result<()> {
+    let mut stream = writer.lock().unwrap();
+    stream.write_all(&pdu.to_bytes())?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{TcpListener, TcpStream};
+    use std::time::{Duration, Instant};
+
+    use smpp_codec::{OptionalParam, BIND_TRANSCEIVER, DELIVER_SM_RESP, ESME_ROK, SUBMIT_SM_RESP, TAG_USSD_SERVICE_OP, USSD_NOTIFY};
+
+    use super::*;
+    use crate::{UserSimulatorConfig, UssdSmppClient};
+
+    // A reader on one end of a loopback connection, and the server's end
+    fn connected() -> (Receiver<io::Result<SmppPdu>>, Arc<Link>, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = SmppStream::Tcp(TcpStream::connect(listener.local_addr().unwrap()).unwrap());
+        let (server, _) = listener.accept().unwrap();
+        let writer = Arc::new(Mutex::new(stream.try_clone().unwrap()));
+        let link = Arc::new(Link::new());
+        let inbox = spawn(stream, writer, Arc::clone(&link), false);
+        (inbox, link, server)
+    }
+
+    fn push(text: &str, sequence_number: u32) -> SmppPdu {
+        let deliver_sm = DeliverSm {
+            short_message: text.as_bytes().into(),
+            optional_params: vec![OptionalParam::u8(TAG_USSD_SERVICE_OP, USSD_NOTIFY)],
+            ..Default::default()
+        };
+        SmppPdu::new(DELIVER_SM, ESME_ROK, sequence_number, deliver_sm.encode())
+    }
+
+    #[test]
+    fn test_enquire_link_is_answered_and_deliver_sm_acknowledged() {
+        let (inbox, link, mut server) = connected();
+        server.write_all(&SmppPdu::new(ENQUIRE_LINK, ESME_ROK, 5, Vec::new()).to_bytes()).unwrap();
+        server.write_all(&push("Top up received", 6).to_bytes()).unwrap();
+        server.write_all(&SmppPdu::new(DELIVER_SM, ESME_ROK, 7, b"USSD\0".to_vec()).to_bytes()).unwrap();
+
+        let answers: Vec<(u32, u32, u32)> = (0..3)
+            .map(|_| SmppPdu::read_from(&mut server).unwrap())
+            .map(|pdu| (pdu.header.command_id, pdu.header.command_status, pdu.header.sequence_number))
+            .collect();
+        assert_eq!(answers[..2], [(ENQUIRE_LINK_RESP, ESME_ROK, 5), (DELIVER_SM_RESP, ESME_ROK, 6)]);
+        // An undecodable DELIVER_SM is refused rather than acknowledged
+        assert_eq!((answers[2].0, answers[2].2), (DELIVER_SM_RESP, 7));
+        assert_ne!(answers[2].1, ESME_ROK);
+
+        // The ENQUIRE_LINK never reaches the client; both DELIVER_SMs do
+        let passed: Vec<u32> =
+            (0..2).map(|_| inbox.recv_timeout(Duration::from_secs(5)).unwrap().unwrap().header.sequence_number).collect();
+        assert_eq!(passed, [6, 7]);
+        assert!(inbox.recv_timeout(Duration::from_millis(100)).is_err());
+        assert!(!link.is_lost());
+    }
+
+    #[test]
+    fn test_end_of_stream_marks_the_link_lost() {
+        let (inbox, link, server) = connected();
+        drop(server);
+        assert!(inbox.recv_timeout(Duration::from_secs(5)).unwrap().is_err());
+        assert!(link.is_lost());
+    }
+
+    #[test]
+    fn test_pushes_are_kept_apart_from_late_responses() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut config = UserSimulatorConfig::default();
+        config.server.host = "127.0.0.1".to_string();
+        config.server.port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut server, _) = listener.accept().unwrap();
+            let bind = SmppPdu::read_from(&mut server).unwrap();
+            assert_eq!(bind.header.command_id, BIND_TRANSCEIVER);
+            server.write_all(&bind.ok_response().to_bytes()).unwrap();
+            // The answer to a request the client gave up on, then a push of the network's own
+            server.write_all(&SmppPdu::new(SUBMIT_SM_RESP, ESME_ROK, 99, b"late\0".to_vec()).to_bytes()).unwrap();
+            server.write_all(&push("Top up received", 1).to_bytes()).unwrap();
+            SmppPdu::read_from(&mut server).unwrap().header.command_id
+        });
+
+        let mut client = UssdSmppClient::new(config);
+        assert!(client.connect().unwrap());
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut pushes = Vec::new();
+        while pushes.is_empty() && Instant::now() < deadline {
+            pushes = client.take_pushes();
+            thread::sleep(Duration::from_millis(10));
+        }
+        let texts: Vec<(&str, bool)> = pushes.iter().map(|push| (push.text.as_str(), push.notify)).collect();
+        assert_eq!(texts, [("Top up received", true)]);
+        assert!(client.take_pushes().is_empty());
+        assert_eq!(server.join().unwrap(), DELIVER_SM_RESP);
+    }
+}
@@ -0,0 +1,115 @@
+use std::fs;
+use std::io;
+
+use serde::Serialize;
+
+// One request and what came of it, kept for `--report` outside load runs
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestRecord {
+    pub sent_at: String, // RFC 3339, UTC
+    pub session_id: String,
+    pub msisdn: String,
+    pub request: String,
+    pub response: String,
+    pub latency_ms: f64,
+    pub outcome: &'static str, // "response", "notification" or "failed"
+    pub error: String,
+}
+
+// JSON for a .json path, CSV otherwise
+pub fn write_requests(path: &str, records: &[RequestRecord]) -> io::Result<()> {
+    if path.ends_with(".json") {
+        return fs::write(path, serde_json::to_string_pretty(records)?);
+    }
+    let mut csv = String::from("sent_at,session_id,msisdn,request,response,latency_ms,outcome,error\n");
+    for record in records {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{:.3},{},{}\n",
+            record.sent_at,
+            quote(&record.session_id),
+            quote(&record.msisdn),
+            quote(&record.request),
+            quote(&record.response),
+            record.latency_ms,
+            record.outcome,
+            quote(&record.error),
+        ));
+    }
+    fs::write(path, csv)
+}
+
+pub fn quote(field: &str) -> String {
+    match field.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records() -> Vec<RequestRecord> {
+        vec![
+            RequestRecord {
+                sent_at: "2026-01-02T03:04:05Z".to_string(),
+                session_id: "S1".to_string(),
+                msisdn: "1234567890".to_string(),
+                request: "*123#".to_string(),
+                response: "Welcome\n1. Balance, data".to_string(),
+                latency_ms: 12.3456,
+                outcome: "response",
+                error: String::new(),
+            },
+            RequestRecord {
+                sent_at: "2026-01-02T03:04:06Z".to_string(),
+                session_id: "S1".to_string(),
+                msisdn: "1234567890".to_string(),
+                request: "1".to_string(),
+                response: String::new(),
+                latency_ms: 0.0,
+                outcome: "failed",
+                error: "said \"no\"".to_string(),
+            },
+        ]
+    }
+
+    fn written(extension: &str) -> String {
+        let path = std::env::temp_dir().join(format!("ussd_user_report_{}.{}", std::process::id(), extension));
+        let path = path.to_str().unwrap();
+        write_requests(path, &records()).unwrap();
+        let written = fs::read_to_string(path).unwrap();
+        fs::remove_file(path).unwrap();
+        written
+    }
+
+    #[test]
+    fn test_csv_has_one_quoted_row_per_request() {
+        assert_eq!(
+            written("csv"),
+            "sent_at,session_id,msisdn,request,response,latency_ms,outcome,error\n\
+             2026-01-02T03:04:05Z,S1,1234567890,*123#,\"Welcome\n1. Balance, data\",12.346,response,\n\
+             2026-01-02T03:04:06Z,S1,1234567890,1,,0.000,failed,\"said \"\"no\"\"\"\n"
+        );
+    }
+
+    #[test]
+    fn test_json_keeps_every_field() {
+        let json: serde_json::Value = serde_json::from_str(&written("json")).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 2);
+        assert_eq!(
+            json[0],
+            serde_json::json!({
+                "sent_at": "2026-01-02T03:04:05Z",
+                "session_id": "S1",
+                "msisdn": "1234567890",
+                "request": "*123#",
+                "response": "Welcome\n1. Balance, data",
+                "latency_ms": 12.3456,
+                "outcome": "response",
+                "error": "",
+            })
+        );
+        assert_eq!((json[1]["outcome"].as_str(), json[1]["error"].as_str()), (Some("failed"), Some("said \"no\"")));
+    }
+}
@@ -0,0 +1,120 @@
+use std::fs;
+
+use crate::{write_request_report, UserSimulatorConfig, UssdSmppClient};
+
+// `--once "*123#,1,0"`: the inputs of one dialogue, comma separated
+pub fn parse_once(inputs: &str) -> Result<Vec<String>, String> {
+    let inputs: Vec<String> = inputs.split(',').map(str::trim).filter(|input| !input.is_empty()).map(str::to_string).collect();
+    if inputs.is_empty() {
+        return Err("--once has no inputs".to_string());
+    }
+    Ok(inputs)
+}
+
+// `--script FILE`: one input per line; blank lines and lines starting with // are skipped.
+// A dialled code (*...#) starts a new session, so one file can hold several dialogues.
+pub fn read_script(path: &str) -> Result<Vec<String>, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let inputs: Vec<String> = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("//"))
+        .map(str::to_string)
+        .collect();
+    if inputs.is_empty() {
+        return Err(format!("{} has no inputs", path));
+    }
+    Ok(inputs)
+}
+
+// Sends the inputs in order without a terminal, printing `>` for each input and `<` for each
+// line of the screen it got. Returns the exit status: 0 when every input was answered, 1 when one
+// was not (the rest are skipped, as they would answer the wrong screen) and 2 when the server
+// could not be reached.
+pub fn run(config: &UserSimulatorConfig, inputs: &[String]) -> i32 {
+    let mut client = UssdSmppClient::new(config.clone());
+    if !config.testing.report_file.is_empty() {
+        client.keep_request_log();
+    }
+    match client.connect() {
+        Ok(true) => {}
+        Ok(false) => {
+            println!("❌ Failed to connect to USSD server");
+            return 2;
+        }
+        Err(e) => {
+            println!("❌ Failed to connect to USSD server: {}", e);
+            return 2;
+        }
+    }
+
+    let mut status = 0;
+    for (index, input) in inputs.iter().enumerate() {
+        println!("> {}", input);
+        match client.send_ussd_request(input) {
+            Ok(response) => {
+                for line in response.text.lines() {
+                    println!("< {}", line);
+                }
+                if response.end {
+                    println!("  (session ended by the network)");
+                }
+            }
+            Err(e) => {
+                println!("❌ {}", e);
+                let skipped = inputs.len() - index - 1;
+                if skipped > 0 {
+                    println!("⏭️  {} remaining input(s) skipped", skipped);
+                }
+                status = 1;
+                break;
+            }
+        }
+    }
+
+    if let Err(e) = client.unbind() {
+        println!("⚠️  Unbind failed: {}", e);
+    }
+    write_request_report(&config.testing.report_file, client.request_log());
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_once_splits_and_trims_inputs() {
+        assert_eq!(parse_once("*123#,1,0").unwrap(), ["*123#", "1", "0"]);
+        assert_eq!(parse_once(" *123# , 1,, 0 ,").unwrap(), ["*123#", "1", "0"]);
+        assert_eq!(parse_once("*100*5#").unwrap(), ["*100*5#"]);
+    }
+
+    #[test]
+    fn test_once_without_inputs_is_an_error() {
+        assert_eq!(parse_once("").unwrap_err(), "--once has no inputs");
+        assert!(parse_once(" , ,").is_err());
+    }
+
+    #[test]
+    fn test_script_skips_blank_lines_and_comments() {
+        let path = std::env::temp_dir().join(format!("script_inputs_{}.txt", std::process::id()));
+        fs::write(&path, "// balance\n*123#\n  1  \n\n0\n// top up\n*100#\n").unwrap();
+        let inputs = read_script(path.to_str().unwrap());
+        let _ = fs::remove_file(&path);
+        assert_eq!(inputs.unwrap(), ["*123#", "1", "0", "*100#"]);
+    }
+
+    #[test]
+    fn test_script_without_inputs_is_an_error() {
+        let path = std::env::temp_dir().join(format!("script_empty_{}.txt", std::process::id()));
+        fs::write(&path, "\n// nothing to dial yet\n   \n").unwrap();
+        let path = path.to_str().unwrap().to_string();
+        let error = read_script(&path);
+        let _ = fs::remove_file(&path);
+        assert_eq!(error.unwrap_err(), format!("{} has no inputs", path));
+
+        let missing = std::env::temp_dir().join(format!("script_missing_{}.txt", std::process::id()));
+        assert!(read_script(missing.to_str().unwrap()).unwrap_err().starts_with(missing.to_str().unwrap()));
+    }
+}
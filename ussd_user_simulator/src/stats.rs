@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::time::{Duration, Instant};
+
+use hdrhistogram::Histogram;
+use serde::Serialize;
+
+// Latencies are recorded in microseconds, up to an hour, to 3 significant figures
+const HIGHEST_MICROS: u64 = 3_600_000_000;
+const SIGNIFICANT_FIGURES: u8 = 3;
+
+// Percentiles listed in the stats screen and in exported summaries
+const SUMMARY_PERCENTILES: [f64; 5] = [50.0, 90.0, 95.0, 99.0, 99.9];
+
+// Every response time for one scope: all requests, or those of one USSD code
+#[derive(Debug, Clone)]
+pub struct LatencyHistogram {
+    histogram: Histogram<u64>,
+    pub successful: u64,
+    pub failed: u64,
+}
+
+impl LatencyHistogram {
+    fn new() -> Self {
+        let histogram = Histogram::new_with_bounds(1, HIGHEST_MICROS, SIGNIFICANT_FIGURES).expect("valid histogram bounds");
+        LatencyHistogram { histogram, successful: 0, failed: 0 }
+    }
+
+    fn record(&mut self, response_time: Duration, success: bool) {
+        // Times beyond the highest trackable value count as the highest
+        self.histogram.saturating_record((response_time.as_micros() as u64).max(1));
+        if success {
+            self.successful += 1;
+        } else {
+            self.failed += 1;
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.histogram.len()
+    }
+
+    pub fn mean_ms(&self) -> f64 {
+        self.histogram.mean() / 1000.0
+    }
+
+    pub fn min_ms(&self) -> f64 {
+        self.histogram.min() as f64 / 1000.0
+    }
+
+    pub fn max_ms(&self) -> f64 {
+        self.histogram.max() as f64 / 1000.0
+    }
+
+    pub fn percentile_ms(&self, percentile: f64) -> f64 {
+        self.histogram.value_at_percentile(percentile) as f64 / 1000.0
+    }
+
+    fn summary(&self, scope: &str) -> LatencySummary {
+        LatencySummary {
+            scope: scope.to_string(),
+            requests: self.count(),
+            successful: self.successful,
+            failed: self.failed,
+            min_ms: self.min_ms(),
+            mean_ms: self.mean_ms(),
+            percentiles_ms: SUMMARY_PERCENTILES.iter().map(|p| (format!("p{}", p), self.percentile_ms(*p))).collect(),
+            max_ms: self.max_ms(),
+            distribution: self
+                .histogram
+                .iter_quantiles(5)
+                .map(|step| DistributionPoint {
+                    percentile: step.percentile(),
+                    latency_ms: step.value_iterated_to() as f64 / 1000.0,
+                    count: step.count_since_last_iteration(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct LatencySummary {
+    scope: String,
+    requests: u64,
+    successful: u64,
+    failed: u64,
+    min_ms: f64,
+    mean_ms: f64,
+    percentiles_ms: BTreeMap<String, f64>,
+    max_ms: f64,
+    distribution: Vec<DistributionPoint>,
+}
+
+// One step of the percentile distribution, as in HdrHistogram's own output
+#[derive(Debug, Serialize)]
+struct DistributionPoint {
+    percentile: f64,
+    latency_ms: f64,
+    count: u64,
+}
+
+// Performance Statistics: full latency distributions overall and per USSD code
+#[derive(Debug, Clone)]
+pub struct PerformanceStats {
+    pub total_requests: u64,
+    pub successful_requests: u64,
+    pub failed_requests: u64,
+    pub start_time: Instant,
+    pub last_request_time: Option<Instant>,
+    pub all: LatencyHistogram,
+    pub by_code: BTreeMap<String, LatencyHistogram>, // Keyed by the code that opened the session
+}
+
+impl Default for PerformanceStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PerformanceStats {
+    pub fn new() -> Self {
+        PerformanceStats {
+            total_requests: 0,
+            successful_requests: 0,
+            failed_requests: 0,
+            start_time: Instant::now(),
+            last_request_time: None,
+            all: LatencyHistogram::new(),
+            by_code: BTreeMap::new(),
+        }
+    }
+
+    pub fn record_request(&mut self, code: &str, response_time: Duration, success: bool) {
+        self.total_requests += 1;
+        self.last_request_time = Some(Instant::now());
+        if success {
+            self.successful_requests += 1;
+        } else {
+            self.failed_requests += 1;
+        }
+        self.all.record(response_time, success);
+        self.by_code.entry(code.to_string()).or_insert_with(LatencyHistogram::new).record(response_time, success);
+    }
+
+    pub fn get_success_rate(&self) -> f64 {
+        if self.total_requests == 0 {
+            0.0
+        } else {
+            (self.successful_requests as f64 / self.total_requests as f64) * 100.0
+        }
+    }
+
+    pub fn get_uptime_seconds(&self) -> u64 {
+        self.start_time.elapsed().as_secs()
+    }
+
+    // JSON (summary and distribution per scope) for a .json path; otherwise CSV with a row per
+    // distribution step, ready for HdrHistogram's plotters
+    pub fn export(&self, path: &str) -> io::Result<()> {
+        let summaries: Vec<LatencySummary> = std::iter::once(self.all.summary("all"))
+            .chain(self.by_code.iter().map(|(code, histogram)| histogram.summary(code)))
+            .collect();
+        if path.ends_with(".json") {
+            return fs::write(path, serde_json::to_string_pretty(&summaries)?);
+        }
+        let mut csv = String::from("scope,percentile,latency_ms,count\n");
+        for summary in &summaries {
+            for point in &summary.distribution {
+                csv.push_str(&format!("{},{:.6},{:.3},{}\n", summary.scope, point.percentile, point.latency_ms, point.count));
+            }
+        }
+        fs::write(path, csv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1 to 100 ms on *123#, and one failed request of a second on *100#
+    fn recorded() -> PerformanceStats {
+        let mut stats = PerformanceStats::new();
+        for ms in 1..=100 {
+            stats.record_request("*123#", Duration::from_millis(ms), true);
+        }
+        stats.record_request("*100#", Duration::from_secs(1), false);
+        stats
+    }
+
+    // Within the histogram's 3 significant figures
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() <= expected * 0.001, "{} is not {}", actual, expected);
+    }
+
+    #[test]
+    fn test_percentiles_of_known_latencies() {
+        let stats = recorded();
+        assert_eq!((stats.total_requests, stats.successful_requests, stats.failed_requests), (101, 100, 1));
+
+        let code = &stats.by_code["*123#"];
+        assert_eq!((code.count(), code.successful, code.failed), (100, 100, 0));
+        assert_close(code.min_ms(), 1.0);
+        assert_close(code.percentile_ms(50.0), 50.0);
+        assert_close(code.percentile_ms(99.0), 99.0);
+        assert_close(code.max_ms(), 100.0);
+        assert_close(code.mean_ms(), 50.5);
+
+        // The slow failure counts towards every request but not towards *123#
+        assert_eq!((stats.all.count(), stats.all.failed), (101, 1));
+        assert_close(stats.all.max_ms(), 1000.0);
+        assert_close(stats.by_code["*100#"].percentile_ms(50.0), 1000.0);
+    }
+
+    fn exported(extension: &str) -> String {
+        let path = std::env::temp_dir().join(format!("ussd_user_stats_{}.{}", std::process::id(), extension));
+        let path = path.to_str().unwrap();
+        recorded().export(path).unwrap();
+        let exported = fs::read_to_string(path).unwrap();
+        fs::remove_file(path).unwrap();
+        exported
+    }
+
+    #[test]
+    fn test_json_export_summarises_each_scope() {
+        let json: serde_json::Value = serde_json::from_str(&exported("json")).unwrap();
+        let scopes: Vec<&str> = json.as_array().unwrap().iter().map(|summary| summary["scope"].as_str().unwrap()).collect();
+        assert_eq!(scopes, ["all", "*100#", "*123#"]);
+
+        let code = &json[2];
+        assert_eq!((code["requests"].as_u64(), code["successful"].as_u64(), code["failed"].as_u64()), (Some(100), Some(100), Some(0)));
+        let percentiles: Vec<&str> = code["percentiles_ms"].as_object().unwrap().keys().map(String::as_str).collect();
+        assert_eq!(percentiles, ["p50", "p90", "p95", "p99", "p99.9"]);
+        assert_close(code["percentiles_ms"]["p50"].as_f64().unwrap(), 50.0);
+        assert_close(code["percentiles_ms"]["p99"].as_f64().unwrap(), 99.0);
+        assert_close(code["max_ms"].as_f64().unwrap(), 100.0);
+        assert_eq!(json[1]["failed"].as_u64(), Some(1));
+    }
+
+    #[test]
+    fn test_csv_export_lists_each_scopes_distribution() {
+        let csv = exported("csv");
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("scope,percentile,latency_ms,count"));
+        let rows: Vec<Vec<&str>> = lines.map(|line| line.split(',').collect()).collect();
+        for (scope, requests) in [("all", 101), ("*100#", 1), ("*123#", 100)] {
+            let steps: Vec<&Vec<&str>> = rows.iter().filter(|row| row[0] == scope).collect();
+            // Every request falls in one step, and the last step is the maximum at the 100th percentile
+            let counted: u64 = steps.iter().map(|row| row[3].parse::<u64>().unwrap()).sum();
+            assert_eq!(counted, requests, "{}", scope);
+            let last = steps.last().unwrap();
+            assert_eq!(last[1], "100.000000");
+        }
+        let last = rows.iter().rfind(|row| row[0] == "*123#").unwrap();
+        assert_close(last[2].parse().unwrap(), 100.0);
+    }
+}
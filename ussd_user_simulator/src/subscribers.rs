@@ -0,0 +1,374 @@
+use std::collections::HashMap;
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::LevelFilter;
+use smpp_codec::{DeliverSm, SmppError, DELIVER_SM, ESME_ROK, SUBMIT_SM_RESP};
+
+use crate::load::dialogues;
+use crate::{load_test_scenarios, ScenarioStep, UserSimulatorConfig, UssdResponse, UssdSmppClient};
+
+// One virtual subscriber: where it is in its current dialogue and how its steps went
+struct Subscriber {
+    msisdn: String,
+    index: usize, // In the whole pool, so neighbours start on different dialogues
+    round: u32,
+    step: usize,
+    sent_at: Option<Instant>, // Awaiting the screen for its current step
+    done: bool,
+    report: SubscriberReport,
+}
+
+#[derive(Debug, Default, Clone)]
+struct SubscriberReport {
+    msisdn: String,
+    dialogues: u32, // Run to the end with every step passing
+    steps_passed: u32,
+    steps_failed: u32,
+}
+
+// What one bind's subscribers did
+#[derive(Debug, Default)]
+struct BindReport {
+    subscribers: Vec<SubscriberReport>,
+    unexpected: u32, // DELIVER_SMs for an MSISDN that was not waiting for one
+    error: Option<String>,
+}
+
+impl Subscriber {
+    fn new(index: usize, msisdn: &str) -> Self {
+        Subscriber {
+            msisdn: msisdn.to_string(),
+            index,
+            round: 0,
+            step: 0,
+            sent_at: None,
+            done: false,
+            report: SubscriberReport { msisdn: msisdn.to_string(), ..Default::default() },
+        }
+    }
+
+    fn dialogue<'a>(&self, dialogs: &'a [Vec<ScenarioStep>]) -> &'a [ScenarioStep] {
+        &dialogs[(self.index + self.round as usize) % dialogs.len()]
+    }
+
+    // Moves to the next step, or to the next round's dialogue when this one is over
+    fn advance(&mut self, dialogs: &[Vec<ScenarioStep>], dialogue_over: bool, rounds: u32) {
+        self.sent_at = None;
+        self.step += 1;
+        if dialogue_over || self.step >= self.dialogue(dialogs).len() {
+            self.step = 0;
+            self.round += 1;
+            self.done = self.round >= rounds;
+        }
+    }
+
+    fn fail(&mut self, dialogs: &[Vec<ScenarioStep>], reason: &str, rounds: u32) {
+        let step = &self.dialogue(dialogs)[self.step];
+        println!("❌ {} step {} ({}): {}", self.msisdn, self.step + 1, step.ussd_code, reason);
+        self.report.steps_failed += 1;
+        self.advance(dialogs, true, rounds);
+    }
+
+    fn answer(&mut self, dialogs: &[Vec<ScenarioStep>], response: &UssdResponse, latency: Duration, rounds: u32) {
+        let step = &self.dialogue(dialogs)[self.step];
+        let failed: Vec<String> =
+            step.check(response, latency).into_iter().filter(|assertion| !assertion.passed).map(|assertion| assertion.name).collect();
+        if !failed.is_empty() {
+            return self.fail(dialogs, &format!("expected {}", failed.join(", ")), rounds);
+        }
+        self.report.steps_passed += 1;
+        let last = self.step + 1 == self.dialogue(dialogs).len();
+        if last || response.end {
+            self.report.dialogues += 1;
+        }
+        self.advance(dialogs, response.end, rounds);
+    }
+}
+
+// Runs the pool in `[subscribers]`: every subscriber plays the test scenarios' dialogues at the
+// same time as the others, one request in flight each, over `binds` binds. Each checks the
+// screens it gets against its own place in its own dialogue, so a server that mixed sessions up
+// fails steps, and a DELIVER_SM for a subscriber that asked for nothing is counted on its own.
+// Returns the exit status: 0 when every step passed and no DELIVER_SM went astray, 1 otherwise
+// and 2 when the run could not start.
+pub fn run(config: &UserSimulatorConfig) -> i32 {
+    let pool = match config.subscribers.msisdns(&config.phone) {
+        Ok(pool) => pool,
+        Err(e) => {
+            println!("❌ {}", e);
+            return 2;
+        }
+    };
+    let path = &config.testing.test_scenarios_file;
+    let dialogs = match load_test_scenarios(path) {
+        Ok(scenarios) => dialogues(&scenarios),
+        Err(e) => {
+            println!("❌ {}: {}", path, e);
+            return 2;
+        }
+    };
+    if dialogs.is_empty() {
+        println!("❌ {} has no dialogues to run", path);
+        return 2;
+    }
+    let binds = (config.subscribers.binds.max(1) as usize).min(pool.len());
+    let rounds = config.subscribers.rounds.max(1);
+    println!(
+        "👥 {} virtual subscribers over {} bind(s), {} dialogue(s) each from {} against {}:{}",
+        pool.len(),
+        binds,
+        rounds,
+        config.testing.test_scenarios_file,
+        config.server.host,
+        config.server.port
+    );
+    // Each request's own log lines would bury the report
+    let level = log::max_level();
+    log::set_max_level(LevelFilter::Warn);
+
+    let start = Instant::now();
+    let workers: Vec<_> = (0..binds)
+        .map(|bind| {
+            let mut config = config.clone();
+            config.logging.debug = false;
+            let subscribers: Vec<Subscriber> = pool
+                .iter()
+                .enumerate()
+                .skip(bind)
+                .step_by(binds)
+                .map(|(index, msisdn)| Subscriber::new(index, msisdn))
+                .collect();
+            let dialogs = dialogs.clone();
+            thread::spawn(move || run_bind(config, subscribers, &dialogs, rounds))
+        })
+        .collect();
+    let reports: Vec<BindReport> = workers.into_iter().map(|worker| worker.join().expect("subscriber bind panicked")).collect();
+    log::set_max_level(level);
+
+    print_report(&reports, start.elapsed())
+}
+
+fn run_bind(config: UserSimulatorConfig, mut pool: Vec<Subscriber>, dialogs: &[Vec<ScenarioStep>], rounds: u32) -> BindReport {
+    let mut report = BindReport::default();
+    let mut client = UssdSmppClient::new(config.clone());
+    match client.connect() {
+        Ok(true) => {}
+        Ok(false) => report.error = Some("bind failed".to_string()),
+        Err(e) => report.error = Some(e.to_string()),
+    }
+    if report.error.is_none()
+        && let Err(e) = interleave(&mut client, &mut pool, dialogs, rounds, &mut report.unexpected)
+    {
+        report.error = Some(e.to_string());
+    }
+    let _ = client.unbind();
+    report.subscribers = pool.into_iter().map(|subscriber| subscriber.report).collect();
+    report
+}
+
+// Sends every idle subscriber's next input, then handles whatever the server sends back, until
+// every subscriber has run its rounds
+fn interleave(
+    client: &mut UssdSmppClient,
+    pool: &mut [Subscriber],
+    dialogs: &[Vec<ScenarioStep>],
+    rounds: u32,
+    unexpected: &mut u32,
+) -> io::Result<()> {
+    let timeout = Duration::from_millis(client.config.ui.session_timeout_ms);
+    let by_msisdn: HashMap<String, usize> = pool.iter().enumerate().map(|(index, subscriber)| (subscriber.msisdn.clone(), index)).collect();
+    let mut by_sequence: HashMap<u32, usize> = HashMap::new();
+    loop {
+        for (index, subscriber) in pool.iter_mut().enumerate().filter(|(_, subscriber)| !subscriber.done && subscriber.sent_at.is_none()) {
+            let input = subscriber.dialogue(dialogs)[subscriber.step].ussd_code.clone();
+            by_sequence.insert(client.submit(&subscriber.msisdn, &input)?, index);
+            subscriber.sent_at = Some(Instant::now());
+        }
+        let Some(oldest) = pool.iter().filter_map(|subscriber| subscriber.sent_at).min() else {
+            return Ok(());
+        };
+
+        let remaining = (oldest + timeout).saturating_duration_since(Instant::now());
+        match client.read_pdu_with_timeout(remaining) {
+            // The client's reader has acknowledged it already
+            Ok(pdu) if pdu.header.command_id == DELIVER_SM => {
+                let msisdn = DeliverSm::decode(&pdu.body).map(|deliver_sm| deliver_sm.destination_addr.into_owned()).unwrap_or_default();
+                let waiting = by_msisdn.get(&msisdn).filter(|index| pool[**index].sent_at.is_some());
+                match (waiting, client.parse_deliver_sm(&pdu.body)) {
+                    (Some(index), Ok(response)) => {
+                        let subscriber = &mut pool[*index];
+                        let latency = subscriber.sent_at.map_or(Duration::ZERO, |sent_at| sent_at.elapsed());
+                        subscriber.answer(dialogs, &response, latency, rounds);
+                    }
+                    (Some(index), Err(e)) => pool[*index].fail(dialogs, &format!("undecodable DELIVER_SM: {}", e), rounds),
+                    (None, _) => *unexpected += 1,
+                }
+            }
+            Ok(pdu) if pdu.header.command_id == SUBMIT_SM_RESP => {
+                let index = by_sequence.remove(&pdu.header.sequence_number);
+                if pdu.header.command_status != ESME_ROK
+                    && let Some(index) = index
+                    && pool[index].sent_at.is_some()
+                {
+                    pool[index].fail(dialogs, &format!("SUBMIT_SM rejected with 0x{:08x}", pdu.header.command_status), rounds);
+                }
+            }
+            Ok(_) => {}
+            Err(SmppError::Timeout(_)) => {
+                for subscriber in pool.iter_mut().filter(|subscriber| subscriber.sent_at.is_some_and(|sent_at| sent_at.elapsed() >= timeout)) {
+                    subscriber.fail(dialogs, &format!("no response within {}ms", timeout.as_millis()), rounds);
+                }
+            }
+            Err(e) => return Err(io::Error::other(e.to_string())),
+        }
+    }
+}
+
+fn print_report(reports: &[BindReport], elapsed: Duration) -> i32 {
+    let subscribers: Vec<&SubscriberReport> = reports.iter().flat_map(|report| &report.subscribers).collect();
+    let passed: u32 = subscribers.iter().map(|subscriber| subscriber.steps_passed).sum();
+    let failed: u32 = subscribers.iter().map(|subscriber| subscriber.steps_failed).sum();
+    let dialogues: u32 = subscribers.iter().map(|subscriber| subscriber.dialogues).sum();
+    let unexpected: u32 = reports.iter().map(|report| report.unexpected).sum();
+    let clean = subscribers.iter().filter(|subscriber| subscriber.steps_failed == 0 && subscriber.steps_passed > 0).count();
+
+    println!();
+    println!("📊 Virtual subscribers ({:.1}s)", elapsed.as_secs_f64());
+    println!("   Subscribers clean:    {} of {}", clean, subscribers.len());
+    println!("   Dialogues completed:  {}", dialogues);
+    println!("   Steps passed/failed:  {}/{}", passed, failed);
+    println!("   Stray DELIVER_SMs:    {}", unexpected);
+    // The failures were printed as they happened; this is who to look at
+    let failing: Vec<&str> =
+        subscribers.iter().filter(|subscriber| subscriber.steps_failed > 0).map(|subscriber| subscriber.msisdn.as_str()).collect();
+    if !failing.is_empty() {
+        let more = if failing.len() > 10 { format!(" and {} more", failing.len() - 10) } else { String::new() };
+        println!("   With failures:        {}{}", failing[..failing.len().min(10)].join(", "), more);
+    }
+    for (bind, report) in reports.iter().enumerate() {
+        if let Some(error) = &report.error {
+            println!("   ❌ Bind {}: {}", bind + 1, error);
+        }
+    }
+    if reports.iter().all(|report| report.error.is_some()) {
+        return 2;
+    }
+    let ok = failed == 0 && unexpected == 0 && reports.iter().all(|report| report.error.is_none());
+    if ok { 0 } else { 1 }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::net::{SocketAddr, TcpListener};
+    use std::sync::mpsc::{self, Receiver};
+
+    use smpp_codec::{
+        OptionalParam, SmppPdu, SubmitSm, BIND_TRANSCEIVER, SUBMIT_SM, TAG_USSD_SERVICE_OP, UNBIND, USSD_PSSR_RESPONSE, USSD_USSR_REQUEST,
+    };
+
+    use super::*;
+    use crate::tests::dialling;
+
+    // A server that binds anyone and answers every SUBMIT_SM with a DELIVER_SM to the MSISDN that
+    // sent it: a menu for a dialled code and a closing screen for anything else. It reports the
+    // source_addr and text of each SUBMIT_SM.
+    fn menu_server() -> (SocketAddr, Receiver<(String, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (sender, submitted) = mpsc::channel();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut sequence = 0;
+            while let Ok(pdu) = SmppPdu::read_from(&mut stream) {
+                let replies = match pdu.header.command_id {
+                    BIND_TRANSCEIVER | UNBIND => vec![pdu.ok_response()],
+                    SUBMIT_SM => {
+                        let submit_sm = SubmitSm::decode(&pdu.body).unwrap();
+                        let text = String::from_utf8_lossy(submit_sm.message()).into_owned();
+                        let (screen, op) = match text.starts_with('*') {
+                            true => (format!("Menu for {}", submit_sm.source_addr), USSD_USSR_REQUEST),
+                            false => (format!("Bye {}", submit_sm.source_addr), USSD_PSSR_RESPONSE),
+                        };
+                        let deliver_sm = DeliverSm {
+                            source_addr: "123".into(),
+                            destination_addr: submit_sm.source_addr.clone(),
+                            short_message: screen.into_bytes().into(),
+                            optional_params: vec![OptionalParam::u8(TAG_USSD_SERVICE_OP, op)],
+                            ..Default::default()
+                        };
+                        let _ = sender.send((submit_sm.source_addr.into_owned(), text));
+                        sequence += 1;
+                        vec![pdu.ok_response(), SmppPdu::new(DELIVER_SM, ESME_ROK, sequence, deliver_sm.encode())]
+                    }
+                    _ => Vec::new(),
+                };
+                for reply in replies {
+                    stream.write_all(&reply.to_bytes()).unwrap();
+                }
+            }
+        });
+        (addr, submitted)
+    }
+
+    fn connected(addr: SocketAddr) -> UssdSmppClient {
+        let mut config = UserSimulatorConfig::default();
+        config.server.host = addr.ip().to_string();
+        config.server.port = addr.port();
+        let mut client = UssdSmppClient::new(config);
+        assert!(client.connect().unwrap());
+        client
+    }
+
+    #[test]
+    fn test_interleaved_subscribers_each_send_from_their_own_msisdn() {
+        let (addr, submitted) = menu_server();
+        let mut client = connected(addr);
+        let msisdns = ["94770000001", "94770000002", "94770000003"];
+        let mut pool: Vec<Subscriber> = msisdns.iter().enumerate().map(|(index, msisdn)| Subscriber::new(index, msisdn)).collect();
+        let dialogs = vec![vec![dialling("*123#", &["Menu"]), dialling("1", &["Bye"])]];
+        let mut unexpected = 0;
+
+        interleave(&mut client, &mut pool, &dialogs, 1, &mut unexpected).unwrap();
+        client.disconnect();
+
+        let submitted: Vec<(String, String)> = submitted.try_iter().collect();
+        // Every subscriber dials before any of them answers a menu
+        let dialled: Vec<(&str, &str)> = submitted[..3].iter().map(|(msisdn, input)| (msisdn.as_str(), input.as_str())).collect();
+        assert_eq!(dialled, msisdns.map(|msisdn| (msisdn, "*123#")));
+        for msisdn in msisdns {
+            let inputs: Vec<&str> = submitted.iter().filter(|(from, _)| from == msisdn).map(|(_, input)| input.as_str()).collect();
+            assert_eq!(inputs, ["*123#", "1"], "{}", msisdn);
+        }
+        for subscriber in &pool {
+            let report = &subscriber.report;
+            assert_eq!((report.steps_passed, report.steps_failed, report.dialogues), (2, 0, 1), "{}", report.msisdn);
+        }
+        assert_eq!(unexpected, 0);
+    }
+
+    #[test]
+    fn test_set_msisdn_moves_later_requests_to_a_session_of_their_own() {
+        let (addr, submitted) = menu_server();
+        let mut client = connected(addr);
+        client.keep_request_log();
+
+        assert_eq!(client.send_ussd_request("*123#").unwrap().text, "Menu for 1234567890");
+        client.set_msisdn("94771234567".to_string());
+        assert_eq!(client.send_ussd_request("*123#").unwrap().text, "Menu for 94771234567");
+        assert!(client.send_ussd_request("1").unwrap().end);
+        client.disconnect();
+
+        let from: Vec<String> = submitted.try_iter().map(|(msisdn, _)| msisdn).collect();
+        assert_eq!(from, ["1234567890", "94771234567", "94771234567"]);
+        let log = client.request_log();
+        let logged: Vec<(&str, &str)> = log.iter().map(|record| (record.msisdn.as_str(), record.session_id.as_str())).collect();
+        assert_eq!(logged[0].0, "1234567890");
+        assert_eq!((logged[1].0, logged[2].0), ("94771234567", "94771234567"));
+        // The new MSISDN dialled, so it is a new session; its reply stays in that session
+        assert_ne!(logged[0].1, logged[1].1);
+        assert_eq!(logged[1].1, logged[2].1);
+    }
+}